@@ -0,0 +1,53 @@
+fn main() {
+    // The gRPC service is described by hand (see proto/happycharts.proto for the wire
+    // contract), so building it needs neither protoc nor a generated message file.
+    #[cfg(feature = "grpc")]
+    {
+        use tonic_build::manual::{Builder, Method, Service};
+
+        let method = |name: &str, route: &str, input: &str, output: &str| {
+            Method::builder()
+                .name(name)
+                .route_name(route)
+                .input_type(format!("crate::grpc::proto::{}", input))
+                .output_type(format!("crate::grpc::proto::{}", output))
+                .codec_path("tonic::codec::ProstCodec")
+        };
+
+        let service = Service::builder()
+            .name("HappyCharts")
+            .package("happycharts")
+            .method(method("analyze", "Analyze", "AnalyzeRequest", "AnalyzeResponse").build())
+            .method(
+                method(
+                    "run_backtest",
+                    "RunBacktest",
+                    "RunBacktestRequest",
+                    "RunBacktestResponse",
+                )
+                .build(),
+            )
+            .method(
+                method(
+                    "get_prompt_history",
+                    "GetPromptHistory",
+                    "GetPromptHistoryRequest",
+                    "GetPromptHistoryResponse",
+                )
+                .build(),
+            )
+            .method(
+                method(
+                    "live_signals",
+                    "LiveSignals",
+                    "LiveSignalsRequest",
+                    "Signal",
+                )
+                .server_streaming()
+                .build(),
+            )
+            .build();
+
+        Builder::new().compile(&[service]);
+    }
+}
@@ -0,0 +1,71 @@
+/* C ABI for the happychartsv2 labeling, indicator and simulation core.
+ * Build with `cargo build --release --no-default-features --features ffi` and link
+ * against the resulting libhappychartsv2 shared library.
+ *
+ * Candles are `len` contiguous rows of six doubles,
+ * [time, open, high, low, close, volume], in chronological order.
+ * Every function returns a status code and writes results through out-pointers. */
+#ifndef HAPPYCHARTS_H
+#define HAPPYCHARTS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define HC_OK 0
+#define HC_NO_RESULT 1
+#define HC_NULL_POINTER (-1)
+#define HC_INVALID_ARGUMENT (-2)
+
+#define HC_ACTION_NONE 0
+#define HC_ACTION_LONG 1
+#define HC_ACTION_SHORT 2
+
+typedef struct {
+    double low;
+    double high;
+    double volume;
+} HcVolumeBucket;
+
+typedef struct {
+    int32_t side;
+    double entry_time;
+    double entry_price;
+    double exit_time;
+    double exit_price;
+    double return_pct;
+} HcTrade;
+
+typedef struct {
+    size_t trades;
+    double total_return;
+    double win_rate;
+    double max_drawdown;
+} HcSimulationSummary;
+
+/* One action code per candle, as the backtest labels them. */
+int32_t hc_label_candles(const double *candles, size_t len, int32_t *out_actions);
+
+int32_t hc_vwap(const double *candles, size_t len, double *out);
+int32_t hc_vwap_distance(const double *candles, size_t len, double *out);
+
+/* Fills `buckets` entries of `out_buckets`. */
+int32_t hc_volume_profile(const double *candles, size_t len, size_t buckets,
+                          HcVolumeBucket *out_buckets);
+
+/* Enters at the close of candle `entry` and exits at the next close. */
+int32_t hc_next_candle_trade(const double *candles, size_t len, size_t entry, int32_t side,
+                             double fee, HcTrade *out);
+
+/* One next-candle trade per non-NONE action; `actions` has `len` entries. */
+int32_t hc_simulate(const double *candles, const int32_t *actions, size_t len, double fee,
+                    HcSimulationSummary *out);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* HAPPYCHARTS_H */
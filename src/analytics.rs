@@ -0,0 +1,235 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::audit::WindowMetadata;
+use crate::hallucination::ClaimCheck;
+use crate::postmortem::FailureAnalysis;
+use crate::rationale::RationaleGrade;
+use crate::simulator::{LevelOutcome, Trade};
+use crate::Action;
+
+pub const ANALYTICS_DIR: &str = "cache/analytics";
+const PREDICTIONS_FILE: &str = "predictions.jsonl";
+const TRADES_FILE: &str = "trades.jsonl";
+const FAILURE_ANALYSES_FILE: &str = "failure_analyses.jsonl";
+const RATIONALE_GRADES_FILE: &str = "rationale_grades.jsonl";
+const DUCKDB_INIT_FILE: &str = "duckdb_init.sql";
+
+/// One model prediction for one window, verified against its label when known.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PredictionRecord {
+    pub run_id: String,
+    /// `"backtest"` or `"live"`.
+    pub source: String,
+    pub symbol: String,
+    pub model: String,
+    pub prompt_hash: String,
+    /// Open time of the last candle in the window.
+    pub window_end: f64,
+    pub action: Action,
+    pub label: Option<Action>,
+    pub correct: Option<bool>,
+    pub rationale: String,
+    #[serde(default)]
+    pub key_factors: Vec<String>,
+    #[serde(default)]
+    pub invalidation_price: Option<f64>,
+    #[serde(default)]
+    pub target_price: Option<f64>,
+    #[serde(default)]
+    pub confidence: Option<f64>,
+    /// Which stated level the next candle reached, once verified.
+    #[serde(default)]
+    pub level_outcome: Option<LevelOutcome>,
+    /// Model round trip in milliseconds; `None` for cached responses.
+    #[serde(default)]
+    pub latency_ms: Option<f64>,
+    /// What the prediction was asked about and with, for audits; `None` in rows recorded
+    /// before it was kept.
+    #[serde(default)]
+    pub metadata: Option<WindowMetadata>,
+    /// Prices the rationale cites, checked against the window's candles.
+    #[serde(default)]
+    pub numeric_claims: Option<ClaimCheck>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradeRecord {
+    pub run_id: String,
+    pub symbol: String,
+    /// Version of the prompt whose signal opened the trade; empty in rows recorded before
+    /// trades were tagged.
+    #[serde(default)]
+    pub prompt_hash: String,
+    #[serde(flatten)]
+    pub trade: Trade,
+}
+
+/// Append-only JSON Lines tables of predictions and simulated trades. DuckDB reads these
+/// natively, so `duckdb -init cache/analytics/duckdb_init.sql` gives an ad-hoc SQL shell
+/// over every run without an export step.
+#[derive(Debug, Clone)]
+pub struct AnalyticsStore {
+    dir: PathBuf,
+}
+
+impl Default for AnalyticsStore {
+    fn default() -> Self {
+        Self::new(ANALYTICS_DIR)
+    }
+}
+
+impl AnalyticsStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn append<T: Serialize>(&self, file: &str, rows: &[T]) -> Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(file);
+        let mut buf = Vec::new();
+        for row in rows {
+            serde_json::to_writer(&mut buf, row)?;
+            buf.push(b'\n');
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut f| f.write_all(&buf))
+            .with_context(|| format!("Failed to append to {}", path.display()))
+    }
+
+    pub fn append_predictions(&self, rows: &[PredictionRecord]) -> Result<()> {
+        self.append(PREDICTIONS_FILE, rows)
+    }
+
+    pub fn append_trades(&self, rows: &[TradeRecord]) -> Result<()> {
+        self.append(TRADES_FILE, rows)
+    }
+
+    pub fn append_failure_analyses(&self, rows: &[FailureAnalysis]) -> Result<()> {
+        self.append(FAILURE_ANALYSES_FILE, rows)
+    }
+
+    pub fn append_rationale_grades(&self, rows: &[RationaleGrade]) -> Result<()> {
+        self.append(RATIONALE_GRADES_FILE, rows)
+    }
+
+    fn read<T: for<'de> Deserialize<'de>>(&self, file: &str) -> Result<Vec<T>> {
+        let path = self.dir.join(file);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        fs::read_to_string(&path)?
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| serde_json::from_str(l).context("Corrupt analytics row"))
+            .collect()
+    }
+
+    pub fn predictions(&self) -> Result<Vec<PredictionRecord>> {
+        self.read(PREDICTIONS_FILE)
+    }
+
+    pub fn trades(&self) -> Result<Vec<TradeRecord>> {
+        self.read(TRADES_FILE)
+    }
+
+    pub fn failure_analyses(&self) -> Result<Vec<FailureAnalysis>> {
+        self.read(FAILURE_ANALYSES_FILE)
+    }
+
+    pub fn rationale_grades(&self) -> Result<Vec<RationaleGrade>> {
+        self.read(RATIONALE_GRADES_FILE)
+    }
+
+    /// Writes a DuckDB init script defining `predictions` and `trades` views over the
+    /// JSON Lines tables and returns its path.
+    pub fn write_duckdb_init(&self) -> Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(DUCKDB_INIT_FILE);
+        fs::write(&path, self.duckdb_init_sql())?;
+        Ok(path)
+    }
+
+    pub fn duckdb_init_sql(&self) -> String {
+        let table = |name: &str, file: &str| {
+            format!(
+                "CREATE OR REPLACE VIEW {} AS SELECT * FROM read_json_auto('{}', format = 'newline_delimited');\n",
+                name,
+                self.dir.join(file).display()
+            )
+        };
+        let mut sql = String::new();
+        sql.push_str(&table("predictions", PREDICTIONS_FILE));
+        sql.push_str(&table("trades", TRADES_FILE));
+        sql.push_str(&table("failure_analyses", FAILURE_ANALYSES_FILE));
+        sql.push_str(&table("rationale_grades", RATIONALE_GRADES_FILE));
+        sql.push_str(
+            "-- e.g. SELECT hour(to_timestamp(window_end)) AS hour, avg(correct::INT) AS accuracy\n\
+             --      FROM predictions WHERE source = 'backtest' GROUP BY hour ORDER BY hour;\n",
+        );
+        sql
+    }
+}
+
+/// Identifier shared by every record produced in one run.
+pub fn new_run_id() -> String {
+    chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_and_read_back() {
+        let dir = std::env::temp_dir().join("happycharts_analytics");
+        let _ = fs::remove_dir_all(&dir);
+        let store = AnalyticsStore::new(&dir);
+
+        let record = PredictionRecord {
+            run_id: "run".to_string(),
+            source: "backtest".to_string(),
+            symbol: "ETH".to_string(),
+            model: "o1-mini".to_string(),
+            prompt_hash: "abc".to_string(),
+            window_end: 3600.0,
+            action: Action::Long,
+            label: Some(Action::Short),
+            correct: Some(false),
+            rationale: "test".to_string(),
+            key_factors: vec!["higher closes".to_string()],
+            invalidation_price: Some(95.0),
+            target_price: None,
+            confidence: Some(0.6),
+            level_outcome: Some(LevelOutcome::Open),
+            latency_ms: Some(1500.0),
+            metadata: None,
+            numeric_claims: None,
+        };
+        store
+            .append_predictions(&[record.clone(), record.clone()])
+            .unwrap();
+        store.append_predictions(&[record]).unwrap();
+        assert_eq!(store.predictions().unwrap().len(), 3);
+        assert!(store.trades().unwrap().is_empty());
+
+        let init = store.write_duckdb_init().unwrap();
+        let sql = fs::read_to_string(init).unwrap();
+        assert!(sql.contains("CREATE OR REPLACE VIEW predictions"));
+        assert!(sql.contains("predictions.jsonl"));
+    }
+}
@@ -0,0 +1,323 @@
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use arrow::array::{
+    Array, ArrayRef, BooleanArray, Float64Array, RecordBatch, StringArray, TimestampSecondArray,
+};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::writer::FileWriter;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+
+use crate::analytics::{PredictionRecord, TradeRecord};
+use crate::Action;
+
+const CANDLE_COLUMNS: [&str; 6] = ["time", "open", "high", "low", "close", "volume"];
+
+fn timestamp_type() -> DataType {
+    DataType::Timestamp(TimeUnit::Second, Some("UTC".into()))
+}
+
+pub fn candle_schema() -> SchemaRef {
+    let mut fields = vec![
+        Field::new("symbol", DataType::Utf8, false),
+        Field::new("time", timestamp_type(), false),
+    ];
+    fields.extend(
+        CANDLE_COLUMNS[1..]
+            .iter()
+            .map(|name| Field::new(*name, DataType::Float64, false)),
+    );
+    Arc::new(Schema::new(fields))
+}
+
+/// One symbol's candles as a RecordBatch with a `symbol` column, a UTC second timestamp
+/// and float OHLCV columns, the layout polars and pyarrow expect.
+pub fn candles_to_batch(symbol: &str, candles: &[[f64; 6]]) -> Result<RecordBatch> {
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from(vec![symbol; candles.len()])),
+        Arc::new(
+            TimestampSecondArray::from_iter_values(candles.iter().map(|c| c[0] as i64))
+                .with_timezone("UTC"),
+        ),
+    ];
+    for i in 1..CANDLE_COLUMNS.len() {
+        columns.push(Arc::new(Float64Array::from_iter_values(
+            candles.iter().map(|c| c[i]),
+        )));
+    }
+    Ok(RecordBatch::try_new(candle_schema(), columns)?)
+}
+
+/// Reads candles back from a batch. Only the time and OHLCV columns are required; time may
+/// be a timestamp of any unit or plain epoch seconds, and numeric columns of any type are
+/// cast to `f64`. Rows come back in batch order.
+pub fn batch_to_candles(batch: &RecordBatch) -> Result<Vec<[f64; 6]>> {
+    let column = |name: &str| {
+        batch
+            .column_by_name(name)
+            .with_context(|| format!("Arrow batch has no `{}` column", name))
+    };
+
+    let time = column("time")?;
+    let seconds = match time.data_type() {
+        DataType::Timestamp(unit, _) => {
+            let divisor = match unit {
+                TimeUnit::Second => 1.0,
+                TimeUnit::Millisecond => 1e3,
+                TimeUnit::Microsecond => 1e6,
+                TimeUnit::Nanosecond => 1e9,
+            };
+            let raw = cast(time, &DataType::Int64)?;
+            let raw = cast(&raw, &DataType::Float64)?;
+            let raw = as_f64(&raw)?;
+            Arc::new(Float64Array::from_iter(
+                raw.iter().map(|v| v.map(|v| v / divisor)),
+            )) as ArrayRef
+        }
+        _ => cast(time, &DataType::Float64)?,
+    };
+
+    let mut columns = vec![seconds];
+    for name in &CANDLE_COLUMNS[1..] {
+        columns.push(cast(column(name)?, &DataType::Float64)?);
+    }
+    let columns = columns
+        .iter()
+        .map(|c| as_f64(c))
+        .collect::<Result<Vec<_>>>()?;
+
+    (0..batch.num_rows())
+        .map(|row| {
+            let mut candle = [0.0; 6];
+            for (value, col) in candle.iter_mut().zip(&columns) {
+                anyhow::ensure!(col.is_valid(row), "Null candle value in row {}", row);
+                *value = col.value(row);
+            }
+            Ok(candle)
+        })
+        .collect()
+}
+
+fn as_f64(array: &ArrayRef) -> Result<&Float64Array> {
+    array
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .context("Expected a Float64 column")
+}
+
+fn action_str(action: Action) -> &'static str {
+    match action {
+        Action::Long => "long",
+        Action::Short => "short",
+        Action::None => "none",
+    }
+}
+
+pub fn predictions_to_batch(rows: &[PredictionRecord]) -> Result<RecordBatch> {
+    let strings = |f: fn(&PredictionRecord) -> &str| -> ArrayRef {
+        Arc::new(StringArray::from_iter_values(rows.iter().map(f)))
+    };
+    let schema = Schema::new(vec![
+        Field::new("run_id", DataType::Utf8, false),
+        Field::new("source", DataType::Utf8, false),
+        Field::new("symbol", DataType::Utf8, false),
+        Field::new("model", DataType::Utf8, false),
+        Field::new("prompt_hash", DataType::Utf8, false),
+        Field::new("window_end", timestamp_type(), false),
+        Field::new("action", DataType::Utf8, false),
+        Field::new("label", DataType::Utf8, true),
+        Field::new("correct", DataType::Boolean, true),
+        Field::new("rationale", DataType::Utf8, false),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        strings(|r| &r.run_id),
+        strings(|r| &r.source),
+        strings(|r| &r.symbol),
+        strings(|r| &r.model),
+        strings(|r| &r.prompt_hash),
+        Arc::new(
+            TimestampSecondArray::from_iter_values(rows.iter().map(|r| r.window_end as i64))
+                .with_timezone("UTC"),
+        ),
+        strings(|r| action_str(r.action)),
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|r| r.label.map(action_str)),
+        )),
+        Arc::new(BooleanArray::from_iter(rows.iter().map(|r| r.correct))),
+        strings(|r| &r.rationale),
+    ];
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
+
+pub fn trades_to_batch(rows: &[TradeRecord]) -> Result<RecordBatch> {
+    let timestamps = |f: fn(&TradeRecord) -> f64| -> ArrayRef {
+        Arc::new(
+            TimestampSecondArray::from_iter_values(rows.iter().map(|r| f(r) as i64))
+                .with_timezone("UTC"),
+        )
+    };
+    let floats = |f: fn(&TradeRecord) -> f64| -> ArrayRef {
+        Arc::new(Float64Array::from_iter_values(rows.iter().map(f)))
+    };
+    let schema = Schema::new(vec![
+        Field::new("run_id", DataType::Utf8, false),
+        Field::new("symbol", DataType::Utf8, false),
+        Field::new("side", DataType::Utf8, false),
+        Field::new("entry_time", timestamp_type(), false),
+        Field::new("entry_price", DataType::Float64, false),
+        Field::new("exit_time", timestamp_type(), false),
+        Field::new("exit_price", DataType::Float64, false),
+        Field::new("return_pct", DataType::Float64, false),
+        Field::new("prompt_hash", DataType::Utf8, false),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| &r.run_id),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| &r.symbol),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| action_str(r.trade.side)),
+        )),
+        timestamps(|r| r.trade.entry_time),
+        floats(|r| r.trade.entry_price),
+        timestamps(|r| r.trade.exit_time),
+        floats(|r| r.trade.exit_price),
+        floats(|r| r.trade.return_pct),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| &r.prompt_hash),
+        )),
+    ];
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
+
+/// Writes batches sharing one schema as an Arrow IPC file (`pyarrow.ipc.open_file`,
+/// `polars.read_ipc`), which readers can memory-map without copying.
+pub fn write_ipc(path: &Path, batches: &[RecordBatch]) -> Result<()> {
+    let first = batches.first().context("No batches to write")?;
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut writer = FileWriter::try_new(file, &first.schema())?;
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+pub fn read_ipc(path: &Path) -> Result<Vec<RecordBatch>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    FileReader::try_new(file, None)?
+        .map(|batch| Ok(batch?))
+        .collect()
+}
+
+/// Writes batches sharing one schema as a Parquet file, the columnar format archives are
+/// usually kept in (`pandas.read_parquet`, `polars.read_parquet`, DuckDB).
+pub fn write_parquet(path: &Path, batches: &[RecordBatch]) -> Result<()> {
+    let first = batches.first().context("No batches to write")?;
+    let file =
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut writer = ArrowWriter::try_new(file, first.schema(), None)?;
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+pub fn read_parquet(path: &Path) -> Result<Vec<RecordBatch>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    ParquetRecordBatchReaderBuilder::try_new(file)?
+        .build()?
+        .map(|batch| Ok(batch?))
+        .collect()
+}
+
+/// Whether `path` names a Parquet file rather than an Arrow IPC one, by its extension.
+pub fn is_parquet(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("parquet"))
+}
+
+/// Writes `batches` as Parquet or Arrow IPC, as [`is_parquet`] decides from `path`.
+pub fn write_batches(path: &Path, batches: &[RecordBatch]) -> Result<()> {
+    if is_parquet(path) {
+        write_parquet(path, batches)
+    } else {
+        write_ipc(path, batches)
+    }
+}
+
+/// Reads batches from a Parquet or Arrow IPC file, as [`is_parquet`] decides from `path`.
+pub fn read_batches(path: &Path) -> Result<Vec<RecordBatch>> {
+    if is_parquet(path) {
+        read_parquet(path)
+    } else {
+        read_ipc(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+
+    #[test]
+    fn test_candles_roundtrip_through_ipc() {
+        let candles = vec![
+            [3600.0, 1.0, 2.0, 0.5, 1.5, 10.0],
+            [7200.0, 1.5, 2.5, 1.0, 2.0, 20.0],
+        ];
+        let path = std::env::temp_dir().join("happycharts_candles.arrow");
+        write_ipc(&path, &[candles_to_batch("ETH", &candles).unwrap()]).unwrap();
+
+        let batches = read_ipc(&path).unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].schema(), candle_schema());
+        assert_eq!(batch_to_candles(&batches[0]).unwrap(), candles);
+
+        let path = std::env::temp_dir().join("happycharts_candles.parquet");
+        assert!(is_parquet(&path));
+        write_batches(&path, &[candles_to_batch("ETH", &candles).unwrap()]).unwrap();
+        let batches = read_batches(&path).unwrap();
+        assert_eq!(batches[0].schema(), candle_schema());
+        assert_eq!(batch_to_candles(&batches[0]).unwrap(), candles);
+
+        // Foreign input: millisecond timestamps and integer volume, no symbol column
+        let schema = Schema::new(vec![
+            Field::new(
+                "time",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+            Field::new("open", DataType::Float64, false),
+            Field::new("high", DataType::Float64, false),
+            Field::new("low", DataType::Float64, false),
+            Field::new("close", DataType::Float64, false),
+            Field::new("volume", DataType::Int64, false),
+        ]);
+        let floats = |v: f64| Arc::new(Float64Array::from(vec![v])) as ArrayRef;
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(arrow::array::TimestampMillisecondArray::from(vec![
+                    3_600_000,
+                ])),
+                floats(1.0),
+                floats(2.0),
+                floats(0.5),
+                floats(1.5),
+                Arc::new(Int64Array::from(vec![10])),
+            ],
+        )
+        .unwrap();
+        assert_eq!(batch_to_candles(&batch).unwrap(), vec![candles[0]]);
+    }
+}
@@ -0,0 +1,277 @@
+use std::env;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use reqwest::Url;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::compute::sha256_hex;
+
+const SHA256_BLOCK: usize = 64;
+
+/// One file produced by a run, uploaded as `{prefix}runs/{run_id}/{name}`.
+#[derive(Debug, Clone)]
+pub struct Artifact {
+    pub name: String,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Artifact {
+    pub fn json<T: Serialize>(name: &str, value: &T) -> Result<Self> {
+        Ok(Self {
+            name: name.to_string(),
+            content_type: "application/json",
+            body: serde_json::to_vec_pretty(value)?,
+        })
+    }
+
+    pub fn json_lines<T: Serialize>(name: &str, rows: &[T]) -> Result<Self> {
+        let mut body = Vec::new();
+        for row in rows {
+            serde_json::to_writer(&mut body, row)?;
+            body.push(b'\n');
+        }
+        Ok(Self {
+            name: name.to_string(),
+            content_type: "application/x-ndjson",
+            body,
+        })
+    }
+
+    pub fn html(name: &str, page: String) -> Self {
+        Self {
+            name: name.to_string(),
+            content_type: "text/html",
+            body: page.into_bytes(),
+        }
+    }
+}
+
+/// Uploads run artifacts to an S3-compatible bucket (AWS, MinIO, R2, ...) so results from
+/// ephemeral machines survive them. Requests are signed with SigV4 and use path-style
+/// addressing, which every S3-compatible store accepts.
+#[derive(Debug, Clone)]
+pub struct S3Sink {
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    prefix: String,
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+    client: reqwest::Client,
+}
+
+impl S3Sink {
+    /// Configured by `ARTIFACT_BUCKET`; returns `None` when it is unset. Credentials come
+    /// from the usual `AWS_ACCESS_KEY_ID` / `AWS_SECRET_ACCESS_KEY` / `AWS_SESSION_TOKEN`,
+    /// and `ARTIFACT_ENDPOINT` points at a non-AWS store.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(bucket) = env::var("ARTIFACT_BUCKET") else {
+            return Ok(None);
+        };
+        let region = env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+        let endpoint = env::var("ARTIFACT_ENDPOINT")
+            .unwrap_or_else(|_| format!("https://s3.{}.amazonaws.com", region));
+
+        Ok(Some(Self {
+            endpoint: Url::parse(&endpoint).context("Invalid ARTIFACT_ENDPOINT")?,
+            bucket,
+            region,
+            prefix: env::var("ARTIFACT_PREFIX").unwrap_or_default(),
+            access_key: env::var("AWS_ACCESS_KEY_ID")
+                .context("ARTIFACT_BUCKET is set but AWS_ACCESS_KEY_ID is missing")?,
+            secret_key: env::var("AWS_SECRET_ACCESS_KEY")
+                .context("ARTIFACT_BUCKET is set but AWS_SECRET_ACCESS_KEY is missing")?,
+            session_token: env::var("AWS_SESSION_TOKEN").ok(),
+            client: reqwest::Client::new(),
+        }))
+    }
+
+    pub fn run_key(&self, run_id: &str, name: &str) -> String {
+        format!("{}runs/{}/{}", self.prefix, run_id, name)
+    }
+
+    /// `s3://` URL of an artifact of a run.
+    pub fn run_url(&self, run_id: &str, name: &str) -> String {
+        format!("s3://{}/{}", self.bucket, self.run_key(run_id, name))
+    }
+
+    /// Uploads every artifact of a run, stopping at the first failure.
+    pub async fn upload_run(&self, run_id: &str, artifacts: &[Artifact]) -> Result<()> {
+        for artifact in artifacts {
+            let key = self.run_key(run_id, &artifact.name);
+            self.put_object(&key, artifact.content_type, artifact.body.clone())
+                .await
+                .with_context(|| format!("Failed to upload s3://{}/{}", self.bucket, key))?;
+        }
+        tracing::info!(
+            bucket = %self.bucket,
+            %run_id,
+            count = artifacts.len(),
+            "Uploaded run artifacts"
+        );
+        Ok(())
+    }
+
+    pub async fn put_object(&self, key: &str, content_type: &str, body: Vec<u8>) -> Result<()> {
+        let path = format!(
+            "/{}/{}",
+            uri_encode(&self.bucket, true),
+            uri_encode(key, false)
+        );
+        let mut url = self.endpoint.clone();
+        url.set_path(&path);
+
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = sha256_hex(&body);
+
+        let mut headers = vec![
+            ("content-type", content_type.to_string()),
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+
+        let authorization = self.authorization("PUT", &path, &headers, &payload_hash, &amz_date);
+
+        let mut request = self.client.put(url).body(body);
+        for (name, value) in &headers {
+            if *name != "host" {
+                request = request.header(*name, value);
+            }
+        }
+        let response = request
+            .header("authorization", authorization)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!("S3 PUT returned {}: {}", status, body);
+        }
+        Ok(())
+    }
+
+    /// SigV4 `Authorization` header. `headers` must be sorted by lowercase name.
+    fn authorization(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, String)],
+        payload_hash: &str,
+        amz_date: &str,
+    ) -> String {
+        let date = &amz_date[..8];
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_request = format!(
+            "{}\n{}\n\n{}\n{}\n{}",
+            method, path, canonical_headers, signed_headers, payload_hash
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            sha256_hex(canonical_request.as_bytes())
+        );
+
+        let key = signing_key(&self.secret_key, date, &self.region, "s3");
+        let signature = to_hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        )
+    }
+}
+
+/// SigV4 signing key for one day, region and service.
+fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> [u8; 32] {
+    let k_date = hmac_sha256(format!("AWS4{}", secret).as_bytes(), date.as_bytes());
+    let k_region = hmac_sha256(&k_date, region.as_bytes());
+    let k_service = hmac_sha256(&k_region, service.as_bytes());
+    hmac_sha256(&k_service, b"aws4_request")
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; SHA256_BLOCK];
+    if key.len() > SHA256_BLOCK {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    inner.update(data);
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Percent-encodes everything except RFC 3986 unreserved characters (and `/` unless
+/// `encode_slash`), as SigV4 requires.
+fn uri_encode(s: &str, encode_slash: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            b'/' if !encode_slash => out.push('/'),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sigv4_primitives() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            to_hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        // Example from the AWS SigV4 documentation
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            to_hex(&key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+
+        assert_eq!(uri_encode("runs/a b+c.json", false), "runs/a%20b%2Bc.json");
+        assert_eq!(uri_encode("a/b", true), "a%2Fb");
+    }
+}
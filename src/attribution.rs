@@ -0,0 +1,417 @@
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::compute::{assemble_prompt, assemble_snapshot_prompt, AnnotatedWindow, PROMPT_SYMBOLS};
+use crate::features::FeatureSet;
+use crate::prompt_builder::SymbolRole;
+use crate::snapshot::MarketSnapshot;
+use crate::Action;
+
+/// A part of the prompt that can be left out to measure what it contributes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptSection {
+    /// The candles of a context symbol, along with its volume features and notes.
+    Context(&'static str),
+    VolumeFeatures,
+    AnomalyNotes,
+}
+
+impl PromptSection {
+    pub fn name(&self) -> String {
+        match self {
+            PromptSection::Context(symbol) => format!("{} data", symbol),
+            PromptSection::VolumeFeatures => "volume features".to_string(),
+            PromptSection::AnomalyNotes => "data quality notes".to_string(),
+        }
+    }
+}
+
+/// Every section an attribution sweep removes in turn: each configured context symbol, then
+/// the indicators and notes. The target symbol's candles are never removed.
+pub fn ablations() -> Vec<PromptSection> {
+    PROMPT_SYMBOLS
+        .iter()
+        .filter(|spec| spec.role == SymbolRole::Context)
+        .map(|spec| PromptSection::Context(spec.symbol))
+        .chain([PromptSection::VolumeFeatures, PromptSection::AnomalyNotes])
+        .collect()
+}
+
+/// The prompt [`assemble_prompt`] would build, minus `section`.
+pub fn ablated_prompt(
+    base_prompt: &str,
+    windows: &[AnnotatedWindow],
+    section: PromptSection,
+) -> String {
+    match section {
+        PromptSection::Context(symbol) => {
+            let kept = windows
+                .iter()
+                .filter(|(s, _, _)| *s != symbol)
+                .copied()
+                .collect::<Vec<_>>();
+            assemble_prompt(base_prompt, &kept)
+        }
+        PromptSection::VolumeFeatures => assemble_snapshot_prompt(
+            base_prompt,
+            &MarketSnapshot::from_windows(windows).without_volume(),
+        ),
+        PromptSection::AnomalyNotes => assemble_snapshot_prompt(
+            base_prompt,
+            &MarketSnapshot::from_windows(windows).without_anomalies(),
+        ),
+    }
+}
+
+/// A change to one window's snapshot whose effect on the model's answers a perturbation
+/// run measures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Perturbation {
+    /// The section left out, as in an attribution sweep.
+    Removed(PromptSection),
+    /// The feature's values set to zero, keeping its lines in the features section.
+    FeatureZeroed(&'static str),
+    /// The feature's values swapped with another sampled window's, keeping their
+    /// distribution but breaking their link to the window.
+    FeatureShuffled(&'static str),
+}
+
+impl Perturbation {
+    pub fn name(&self) -> String {
+        match self {
+            Perturbation::Removed(section) => format!("{} removed", section.name()),
+            Perturbation::FeatureZeroed(name) => format!("{} zeroed", name),
+            Perturbation::FeatureShuffled(name) => format!("{} shuffled", name),
+        }
+    }
+
+    /// `snapshot` with the perturbation applied. `donor` holds the features of the window
+    /// a shuffled feature's values are taken from.
+    pub fn apply<'a>(
+        self,
+        mut snapshot: MarketSnapshot<'a>,
+        donor: &FeatureSet,
+    ) -> MarketSnapshot<'a> {
+        match self {
+            Perturbation::Removed(PromptSection::Context(symbol)) => {
+                snapshot.symbols.retain(|s| s.spec.symbol != symbol);
+                snapshot.features.0.retain(|v| v.symbol != symbol);
+                snapshot
+            }
+            Perturbation::Removed(PromptSection::VolumeFeatures) => snapshot.without_volume(),
+            Perturbation::Removed(PromptSection::AnomalyNotes) => snapshot.without_anomalies(),
+            Perturbation::FeatureZeroed(name) => {
+                for v in snapshot.features.0.iter_mut().filter(|v| v.name == name) {
+                    v.value = 0.0;
+                }
+                snapshot
+            }
+            Perturbation::FeatureShuffled(name) => {
+                snapshot.features.0.retain_mut(|v| {
+                    if v.name != name {
+                        return true;
+                    }
+                    match donor.get(&v.symbol, name) {
+                        Some(value) => {
+                            v.value = value;
+                            true
+                        }
+                        None => false,
+                    }
+                });
+                snapshot
+            }
+        }
+    }
+}
+
+/// Every perturbation of a sensitivity run: each [`ablations`] section removed, then each
+/// of the `features` zeroed and shuffled.
+pub fn perturbations(features: &[&'static str]) -> Vec<Perturbation> {
+    ablations()
+        .into_iter()
+        .map(Perturbation::Removed)
+        .chain(features.iter().flat_map(|&name| {
+            [
+                Perturbation::FeatureZeroed(name),
+                Perturbation::FeatureShuffled(name),
+            ]
+        }))
+        .collect()
+}
+
+/// The model's action for the window ending at an index, and the window's label.
+pub type Answer = (usize, Action, Action);
+
+/// How one perturbation changed the model's answers.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Sensitivity {
+    pub perturbation: String,
+    pub windows: usize,
+    /// Fraction of windows answered with the same action as the unperturbed prompt.
+    pub agreement: f64,
+    pub accuracy: f64,
+}
+
+/// Per-perturbation sensitivity against the unperturbed answers, most sensitive first.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SensitivityReport {
+    pub prompt_version: String,
+    pub windows: usize,
+    pub accuracy: f64,
+    pub perturbations: Vec<Sensitivity>,
+}
+
+impl SensitivityReport {
+    /// Builds the report from `(window, action, label)` answers: `baseline` for the
+    /// unperturbed prompt and one list per perturbation.
+    pub fn new(
+        prompt_version: String,
+        baseline: &[Answer],
+        perturbed: &[(Perturbation, Vec<Answer>)],
+    ) -> Self {
+        let accuracy = |answers: &[Answer]| {
+            let correct = answers.iter().filter(|(_, a, l)| a == l).count();
+            correct as f64 / answers.len().max(1) as f64
+        };
+        let mut perturbations = perturbed
+            .iter()
+            .map(|(perturbation, answers)| {
+                let agreed = answers
+                    .iter()
+                    .filter(|(w, action, _)| {
+                        baseline.iter().any(|(bw, b, _)| bw == w && b == action)
+                    })
+                    .count();
+                Sensitivity {
+                    perturbation: perturbation.name(),
+                    windows: answers.len(),
+                    agreement: agreed as f64 / answers.len().max(1) as f64,
+                    accuracy: accuracy(answers),
+                }
+            })
+            .collect::<Vec<_>>();
+        perturbations.sort_by(|a, b| a.agreement.total_cmp(&b.agreement));
+        Self {
+            prompt_version,
+            windows: baseline.len(),
+            accuracy: accuracy(baseline),
+            perturbations,
+        }
+    }
+
+    /// The report as a text table.
+    pub fn describe(&self) -> String {
+        let mut out = format!(
+            "Prompt {} over {} windows: accuracy {:.2}%\n{:<36} {:>9} {:>9}\n",
+            self.prompt_version,
+            self.windows,
+            self.accuracy * 100.0,
+            "perturbation",
+            "agreement",
+            "accuracy"
+        );
+        for s in &self.perturbations {
+            let _ = writeln!(
+                out,
+                "{:<36} {:>8.1}% {:>8.1}%",
+                s.perturbation,
+                s.agreement * 100.0,
+                s.accuracy * 100.0
+            );
+        }
+        out
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SectionImportance {
+    pub section: String,
+    /// Accuracy with the section removed.
+    pub ablated_accuracy: f64,
+    /// Full-prompt accuracy minus `ablated_accuracy`; positive when the section helps.
+    pub delta: f64,
+}
+
+/// Per-section importance from an attribution sweep, most useful section first.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AttributionReport {
+    pub prompt_version: String,
+    pub windows: usize,
+    pub accuracy: f64,
+    pub sections: Vec<SectionImportance>,
+    /// SVG chart of the full prompt's signal on the latest window, shown in the HTML.
+    #[serde(skip)]
+    pub chart: Option<String>,
+}
+
+impl AttributionReport {
+    pub fn new(
+        prompt_version: String,
+        windows: usize,
+        accuracy: f64,
+        ablated: &[(PromptSection, f64)],
+    ) -> Self {
+        let mut sections = ablated
+            .iter()
+            .map(|(section, ablated_accuracy)| SectionImportance {
+                section: section.name(),
+                ablated_accuracy: *ablated_accuracy,
+                delta: accuracy - ablated_accuracy,
+            })
+            .collect::<Vec<_>>();
+        sections.sort_by(|a, b| b.delta.total_cmp(&a.delta));
+        Self {
+            prompt_version,
+            windows,
+            accuracy,
+            sections,
+            chart: None,
+        }
+    }
+
+    /// The report with `chart` embedded in its HTML.
+    pub fn with_chart(mut self, chart: String) -> Self {
+        self.chart = Some(chart);
+        self
+    }
+
+    /// Standalone HTML page with the importance table.
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Prompt section attribution</title></head>\n<body>\n",
+        );
+        let _ = writeln!(
+            html,
+            "<h1>Prompt section attribution</h1>\n<p>Prompt {} over {} windows: accuracy {:.2}%</p>",
+            self.prompt_version,
+            self.windows,
+            self.accuracy * 100.0
+        );
+        html.push_str(
+            "<table>\n<tr><th>Section removed</th><th>Accuracy</th><th>Importance</th></tr>\n",
+        );
+        for s in &self.sections {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{:.2}%</td><td>{:+.2} pts</td></tr>",
+                s.section,
+                s.ablated_accuracy * 100.0,
+                s.delta * 100.0
+            );
+        }
+        html.push_str("</table>\n");
+        if let Some(chart) = &self.chart {
+            html.push_str("<h2>Latest window, full prompt</h2>\n");
+            html.push_str(chart);
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ablation_and_attribution() {
+        let candles = [[0.0, 100.0, 101.0, 99.0, 100.5, 2.0]; 3];
+        let windows: [AnnotatedWindow; 2] = [("ETH", &candles, &[]), ("BTC", &candles, &[])];
+        let full = assemble_prompt("base", &windows);
+
+        let no_btc = ablated_prompt("base", &windows, PromptSection::Context("BTC"));
+        assert!(full.contains("BTC: [[") && !no_btc.contains("BTC: [["));
+        assert!(no_btc.contains("ETH: [["));
+        let no_volume = ablated_prompt("base", &windows, PromptSection::VolumeFeatures);
+        assert!(full.contains("VWAP") && !no_volume.contains("VWAP"));
+        assert_eq!(
+            ablated_prompt("base", &windows, PromptSection::AnomalyNotes),
+            full
+        );
+        assert_eq!(
+            ablations(),
+            [
+                PromptSection::Context("BTC"),
+                PromptSection::Context("SOL"),
+                PromptSection::VolumeFeatures,
+                PromptSection::AnomalyNotes,
+            ]
+        );
+
+        let report = AttributionReport::new(
+            "abc".to_string(),
+            10,
+            0.6,
+            &[
+                (PromptSection::Context("SOL"), 0.7),
+                (PromptSection::VolumeFeatures, 0.4),
+            ],
+        );
+        assert_eq!(report.sections[0].section, "volume features");
+        assert!((report.sections[0].delta - 0.2).abs() < 1e-9);
+        assert!(report.sections[1].delta < 0.0);
+        assert!(report
+            .to_html()
+            .contains("<td>SOL data</td><td>70.00%</td>"));
+        let chart =
+            crate::chart::render_signal_svg(&candles, Action::Long, Some(99.0), Some(102.0));
+        let html = report.with_chart(chart.clone()).to_html();
+        assert!(html.contains(&chart) && html.ends_with("</svg>\n</body>\n</html>\n"));
+    }
+
+    #[test]
+    fn test_perturbations_and_sensitivity() {
+        use crate::features::{FeatureRegistry, RealizedVolatility, VwapDistance};
+
+        let candles = [
+            [0.0, 100.0, 101.0, 99.0, 100.5, 2.0],
+            [3600.0, 100.5, 103.0, 100.0, 102.0, 3.0],
+            [7200.0, 102.0, 102.5, 98.0, 99.0, 1.0],
+        ];
+        let windows: [AnnotatedWindow; 2] = [("ETH", &candles, &[]), ("BTC", &candles, &[])];
+        let registry = FeatureRegistry::default()
+            .with(VwapDistance)
+            .with(RealizedVolatility);
+        let snapshot = || MarketSnapshot::from_windows(&windows).with_features(&registry);
+        let mut donor = snapshot().features;
+        for v in &mut donor.0 {
+            v.value = 9.0;
+        }
+
+        let zeroed = Perturbation::FeatureZeroed("vwap_distance").apply(snapshot(), &donor);
+        assert_eq!(zeroed.features.get("ETH", "vwap_distance"), Some(0.0));
+        assert!(zeroed.features.get("ETH", "realized_volatility").is_some());
+        let shuffled = Perturbation::FeatureShuffled("vwap_distance").apply(snapshot(), &donor);
+        assert_eq!(shuffled.features.get("BTC", "vwap_distance"), Some(9.0));
+        let no_btc = Perturbation::Removed(PromptSection::Context("BTC")).apply(snapshot(), &donor);
+        assert_eq!(no_btc.symbols.len(), 1);
+        assert_eq!(no_btc.features.get("BTC", "vwap_distance"), None);
+
+        let all = perturbations(&registry.names());
+        assert_eq!(all.len(), ablations().len() + 4);
+        assert_eq!(all.last().unwrap().name(), "realized_volatility shuffled");
+
+        let (long, short) = (Action::Long, Action::Short);
+        let baseline = [(1, long, long), (2, short, long)];
+        let report = SensitivityReport::new(
+            "abc".to_string(),
+            &baseline,
+            &[
+                (all[0], vec![(1, long, long), (2, short, long)]),
+                (all[1], vec![(1, short, long), (2, long, long)]),
+            ],
+        );
+        assert_eq!(report.accuracy, 0.5);
+        assert_eq!(report.perturbations[0].perturbation, "SOL data removed");
+        assert_eq!(report.perturbations[0].agreement, 0.0);
+        assert_eq!(report.perturbations[1].agreement, 1.0);
+        assert!(report
+            .describe()
+            .lines()
+            .nth(2)
+            .unwrap()
+            .starts_with("SOL data removed"));
+    }
+}
@@ -0,0 +1,353 @@
+#[cfg(feature = "native")]
+use std::fs;
+#[cfg(feature = "native")]
+use std::path::PathBuf;
+
+#[cfg(feature = "native")]
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[cfg(feature = "native")]
+use crate::analytics::{AnalyticsStore, PredictionRecord};
+use crate::compute::sha256_hex;
+#[cfg(feature = "native")]
+use crate::llm_cache::LlmCache;
+use crate::prediction::Prediction;
+#[cfg(feature = "native")]
+use crate::{compression, Model};
+use crate::{Action, ChatOptions, GRANULARITY};
+
+#[cfg(feature = "native")]
+pub const AUDIT_DIR: &str = "cache/audit";
+
+/// Where one prediction came from, stored beside it for audits. None of it is shown to
+/// the model: it describes the prompt rather than being part of it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WindowMetadata {
+    /// [`window_id`] of the window asked about.
+    pub window_id: String,
+    /// Open time of the first candle in the window.
+    pub window_start: f64,
+    /// Open time of the last candle in the window.
+    pub window_end: f64,
+    pub prompt_version: String,
+    /// [`config_hash`] of the run's model, chat options and window length, as the run's
+    /// manifest records it; a routed run hashes its model pair, e.g. `o1-mini->o1`.
+    pub config_hash: String,
+    /// Chat options the model was asked with, which also key its cached response.
+    pub chat: ChatOptions,
+    /// Hex SHA-256 of the exact prompt sent, its key in the [`AuditStore`].
+    pub prompt_sha: String,
+}
+
+impl WindowMetadata {
+    pub fn new(
+        symbol: &str,
+        window: &[[f64; 6]],
+        prompt_version: &str,
+        model: &str,
+        chat: ChatOptions,
+        prompt: &str,
+    ) -> Self {
+        let (start, end) = match window {
+            [first, .., last] => (first[0], last[0]),
+            [only] => (only[0], only[0]),
+            [] => (0.0, 0.0),
+        };
+        Self {
+            window_id: window_id(symbol, start, end),
+            window_start: start,
+            window_end: end,
+            prompt_version: prompt_version.to_string(),
+            config_hash: config_hash(model, chat, window.len()),
+            chat,
+            prompt_sha: sha256_hex(prompt.as_bytes()),
+        }
+    }
+}
+
+/// Identifies a window by symbol and the open times of its first and last candles, the
+/// same for every run and prompt that asks about it, e.g. `ETH:1710892800-1710975600`.
+pub fn window_id(symbol: &str, start: f64, end: f64) -> String {
+    format!("{}:{}-{}", symbol, start as i64, end as i64)
+}
+
+/// Short hash of the settings a prediction depends on besides its prompt: the model, its
+/// chat options and the window length. Two records with equal prompt SHAs and config
+/// hashes asked the same question.
+pub fn config_hash(model: &str, chat: ChatOptions, window_candles: usize) -> String {
+    let config = json!({
+        "model": model,
+        "chat": chat,
+        "granularity": GRANULARITY,
+        "window_candles": window_candles,
+    });
+    sha256_hex(config.to_string().as_bytes())[..16].to_string()
+}
+
+/// The settings a live decision was made with, in full; [`config_hash`] hashes part of them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigSnapshot {
+    pub model: String,
+    pub chat: ChatOptions,
+    pub granularity: u32,
+    pub window_candles: usize,
+    /// The target, then the context symbols whose windows were shown.
+    pub symbols: Vec<String>,
+    /// File the base prompt was read from.
+    pub prompt_file: String,
+}
+
+/// Identifies a live signal by its symbol and the run that recorded it, e.g.
+/// `ETH-20240320T140000.123`.
+pub fn signal_id(symbol: &str, run_id: &str) -> String {
+    format!("{}-{}", symbol, run_id)
+}
+
+/// Hex SHA-256 of every candle shown to the model, window by window, so two decisions
+/// can be checked to have seen the same data.
+pub fn input_hash(windows: &[&[[f64; 6]]]) -> String {
+    let bytes = windows
+        .iter()
+        .flat_map(|w| w.iter().flatten())
+        .flat_map(|v| v.to_le_bytes())
+        .collect::<Vec<_>>();
+    sha256_hex(&bytes)
+}
+
+/// Everything behind one live decision, kept so it can be explained later: what the model
+/// was asked and answered, what was made of the answer, and with which data and settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExplanationBundle {
+    /// [`signal_id`] of the decision.
+    pub signal_id: String,
+    pub symbol: String,
+    pub run_id: String,
+    pub metadata: WindowMetadata,
+    /// The exact prompt sent.
+    pub prompt: String,
+    /// The model's answer as received, before parsing.
+    pub response: String,
+    pub prediction: Prediction,
+    /// What was acted on: the predicted action, unless it was held back, and why.
+    pub action: Action,
+    pub rationale: String,
+    /// [`input_hash`] of the windows in the prompt.
+    pub input_hash: String,
+    pub config: ConfigSnapshot,
+}
+
+/// Whether `id` could be a [`signal_id`], so it is safe as a file name.
+pub fn is_signal_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+/// Every prediction recorded for `window_id`, optionally only those of `run_id`.
+#[cfg(feature = "native")]
+pub fn find_predictions(
+    records: Vec<PredictionRecord>,
+    window_id: &str,
+    run_id: Option<&str>,
+) -> Vec<PredictionRecord> {
+    records
+        .into_iter()
+        .filter(|r| {
+            r.metadata
+                .as_ref()
+                .is_some_and(|m| m.window_id == window_id)
+        })
+        .filter(|r| run_id.is_none_or(|id| r.run_id == id))
+        .collect()
+}
+
+/// A historical prediction with the exact prompt it was asked with and the raw response,
+/// when they are still stored.
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, Serialize)]
+pub struct Reconstruction {
+    pub record: PredictionRecord,
+    pub prompt: Option<String>,
+    /// From the LLM response cache, for models it serves.
+    pub response: Option<String>,
+}
+
+/// Content-addressed, compressed copies of every prompt a recorded prediction was asked
+/// with, keyed by [`WindowMetadata::prompt_sha`]. Identical prompts are stored once.
+#[cfg(feature = "native")]
+#[derive(Debug, Clone)]
+pub struct AuditStore {
+    dir: PathBuf,
+}
+
+#[cfg(feature = "native")]
+impl Default for AuditStore {
+    fn default() -> Self {
+        Self::new(AUDIT_DIR)
+    }
+}
+
+#[cfg(feature = "native")]
+impl AuditStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, sha: &str) -> PathBuf {
+        self.dir.join("prompts").join(format!("{}.txt", sha))
+    }
+
+    /// Stores `prompt` unless an identical one already is.
+    pub fn put_prompt(&self, prompt: &str) -> Result<()> {
+        let path = self.path(&sha256_hex(prompt.as_bytes()));
+        if compression::compressed_path(&path).exists() {
+            return Ok(());
+        }
+        fs::create_dir_all(self.dir.join("prompts"))?;
+        compression::write(&path, prompt.as_bytes()).context("Failed to store audited prompt")
+    }
+
+    pub fn prompt(&self, sha: &str) -> Result<Option<String>> {
+        compression::read(&self.path(sha))?
+            .map(|bytes| String::from_utf8(bytes).context("Corrupt audited prompt"))
+            .transpose()
+    }
+
+    fn bundle_path(&self, signal_id: &str) -> Result<PathBuf> {
+        anyhow::ensure!(is_signal_id(signal_id), "Invalid signal id {:?}", signal_id);
+        Ok(self.dir.join("bundles").join(format!("{}.json", signal_id)))
+    }
+
+    pub fn put_bundle(&self, bundle: &ExplanationBundle) -> Result<()> {
+        let path = self.bundle_path(&bundle.signal_id)?;
+        fs::create_dir_all(self.dir.join("bundles"))?;
+        compression::write(&path, &serde_json::to_vec(bundle)?)
+            .context("Failed to store explanation bundle")
+    }
+
+    /// The explanation bundle of live signal `signal_id`, if one was kept.
+    pub fn bundle(&self, signal_id: &str) -> Result<Option<ExplanationBundle>> {
+        compression::read(&self.bundle_path(signal_id)?)?
+            .map(|bytes| serde_json::from_slice(&bytes).context("Corrupt explanation bundle"))
+            .transpose()
+    }
+
+    /// The prompt and cached response behind `record`.
+    pub async fn reconstruct(
+        &self,
+        record: PredictionRecord,
+        cache: &LlmCache,
+    ) -> Result<Reconstruction> {
+        let prompt = match &record.metadata {
+            Some(metadata) => self.prompt(&metadata.prompt_sha)?,
+            None => None,
+        };
+        let response = match (&prompt, record.model.parse::<Model>()) {
+            (Some(prompt), Ok(model)) => {
+                let chat = record.metadata.as_ref().map(|m| m.chat).unwrap_or_default();
+                cache.get(model, &chat.cache_key(prompt)).await
+            }
+            _ => None,
+        };
+        Ok(Reconstruction {
+            record,
+            prompt,
+            response,
+        })
+    }
+}
+
+/// Every recorded prediction about `window_id`, optionally only those of `run_id`, with
+/// the prompt and response behind each.
+#[cfg(feature = "native")]
+pub async fn reconstruct_predictions(
+    window_id: &str,
+    run_id: Option<&str>,
+) -> Result<Vec<Reconstruction>> {
+    let records = find_predictions(AnalyticsStore::default().predictions()?, window_id, run_id);
+    let (audit, cache) = (AuditStore::default(), LlmCache::from_env().await);
+    let mut reconstructions = Vec::new();
+    for record in records {
+        reconstructions.push(audit.reconstruct(record, &cache).await?);
+    }
+    Ok(reconstructions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_metadata() {
+        let candle = |t: f64| [t, 1.0, 1.0, 1.0, 1.0, 1.0];
+        let window = [candle(0.0), candle(3600.0), candle(7200.0)];
+        let metadata = WindowMetadata::new(
+            "ETH",
+            &window,
+            "abc",
+            "o1-mini",
+            Default::default(),
+            "prompt",
+        );
+        assert_eq!(metadata.window_id, "ETH:0-7200");
+        assert_eq!((metadata.window_start, metadata.window_end), (0.0, 7200.0));
+        assert_eq!(metadata.prompt_sha, sha256_hex(b"prompt"));
+
+        let config = config_hash("o1-mini", ChatOptions::default(), 3);
+        assert_eq!(metadata.config_hash, config);
+        assert_ne!(config, config_hash("o1", ChatOptions::default(), 3));
+        assert_ne!(config, config_hash("o1-mini", ChatOptions::default(), 24));
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn test_audit_store_round_trip() {
+        let dir = std::env::temp_dir().join("happycharts_audit");
+        let _ = fs::remove_dir_all(&dir);
+        let store = AuditStore::new(&dir);
+        store.put_prompt("Analyze this").unwrap();
+        store.put_prompt("Analyze this").unwrap();
+        let sha = sha256_hex(b"Analyze this");
+        assert_eq!(store.prompt(&sha).unwrap().as_deref(), Some("Analyze this"));
+        assert_eq!(store.prompt("missing").unwrap(), None);
+
+        let candle = [3600.0, 1.0, 2.0, 0.5, 1.5, 10.0];
+        let bundle = ExplanationBundle {
+            signal_id: signal_id("ETH", "20240320T140000.123"),
+            symbol: "ETH".into(),
+            run_id: "20240320T140000.123".into(),
+            metadata: WindowMetadata::default(),
+            prompt: "Analyze this".into(),
+            response: r#"{"action": "short", "rationale": "Lower highs"}"#.into(),
+            prediction: Prediction {
+                action: Action::Short,
+                rationale: "Lower highs".into(),
+                ..Default::default()
+            },
+            action: Action::None,
+            rationale: "Held back around CPI".into(),
+            input_hash: input_hash(&[&[candle]]),
+            config: ConfigSnapshot {
+                model: "o1-mini".into(),
+                chat: ChatOptions::default(),
+                granularity: GRANULARITY,
+                window_candles: 1,
+                symbols: vec!["ETH".into()],
+                prompt_file: "prompt.txt".into(),
+            },
+        };
+        store.put_bundle(&bundle).unwrap();
+        assert_eq!(
+            store.bundle("ETH-20240320T140000.123").unwrap(),
+            Some(bundle)
+        );
+        assert_eq!(store.bundle("BTC-20240320T140000.123").unwrap(), None);
+        assert!(store.bundle("../prompts").is_err());
+        // Any candle changing changes the hash
+        let moved = [3600.0, 1.0, 2.0, 0.5, 1.6, 10.0];
+        assert_ne!(input_hash(&[&[candle]]), input_hash(&[&[moved]]));
+    }
+}
@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+
+use crate::compute::sha256_hex;
+
+/// The tokens the dashboard and the gRPC API accept. Without it they only serve
+/// localhost, and the dashboard only serves pages to read.
+#[cfg(feature = "native")]
+pub const API_TOKENS_FILE: &str = "api_tokens.json";
+
+/// What a token may do. Each role may also do everything the ones before it may.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Read pages, prompt history and signals.
+    Read,
+    /// Also trigger analyses and backtests and promote prompts.
+    Admin,
+}
+
+impl std::str::FromStr for Role {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(Role::Read),
+            "admin" => Ok(Role::Admin),
+            _ => anyhow::bail!("Unknown role {:?} (expected read or admin)", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub name: String,
+    /// Hex SHA-256 of the token; the token itself is not kept.
+    pub sha256: String,
+    pub role: Role,
+}
+
+/// Why a request was turned away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthError {
+    Missing,
+    Unknown,
+    /// The token is valid but its role is not enough.
+    Forbidden,
+    /// An action asked of a server without tokens, which only serves reading.
+    NoTokens,
+    /// A request sent from a page of another origin, or to a host name other than
+    /// localhost's when there are no tokens.
+    CrossOrigin,
+}
+
+impl AuthError {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            AuthError::Missing => "An API token is required",
+            AuthError::Unknown => "Unknown API token",
+            AuthError::Forbidden => "This API token's role does not allow that",
+            AuthError::NoTokens => "Actions need an admin API token; add one with `tokens add`",
+            AuthError::CrossOrigin => "Requests must come from the dashboard's own origin",
+        }
+    }
+}
+
+/// The configured tokens, a JSON array of `{"name", "sha256", "role"}` objects.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ApiTokens(pub Vec<ApiToken>);
+
+impl ApiTokens {
+    pub fn parse(json: &str) -> anyhow::Result<Self> {
+        let tokens: Self = serde_json::from_str(json)?;
+        for (i, t) in tokens.0.iter().enumerate() {
+            anyhow::ensure!(
+                t.sha256.len() == 64 && t.sha256.chars().all(|c| c.is_ascii_hexdigit()),
+                "Token {:?} needs the hex SHA-256 of the token as sha256",
+                t.name
+            );
+            anyhow::ensure!(
+                tokens.0[..i].iter().all(|o| o.name != t.name),
+                "Token name {:?} is used twice",
+                t.name
+            );
+        }
+        Ok(tokens)
+    }
+
+    /// Adds `token` as `name`, replacing a token of that name.
+    pub fn add(&mut self, name: &str, token: &str, role: Role) {
+        self.0.retain(|t| t.name != name);
+        self.0.push(ApiToken {
+            name: name.to_string(),
+            sha256: sha256_hex(token.as_bytes()),
+            role,
+        });
+    }
+
+    /// The token presented by `authorization`, an HTTP `Authorization` header or gRPC
+    /// metadata value, if its role allows `required`.
+    pub fn authorize(
+        &self,
+        authorization: Option<&str>,
+        required: Role,
+    ) -> Result<&ApiToken, AuthError> {
+        let token = authorization
+            .and_then(presented_token)
+            .ok_or(AuthError::Missing)?;
+        let hash = sha256_hex(token.as_bytes());
+        let found = self
+            .0
+            .iter()
+            .find(|t| t.sha256.eq_ignore_ascii_case(&hash))
+            .ok_or(AuthError::Unknown)?;
+        if found.role >= required {
+            Ok(found)
+        } else {
+            Err(AuthError::Forbidden)
+        }
+    }
+}
+
+/// The token in an `Authorization` value: a bearer token, or the password of basic
+/// credentials, which is what a browser sends after prompting for them.
+pub fn presented_token(authorization: &str) -> Option<String> {
+    let (scheme, credentials) = authorization.trim().split_once(' ')?;
+    let credentials = credentials.trim();
+    if scheme.eq_ignore_ascii_case("bearer") {
+        return (!credentials.is_empty()).then(|| credentials.to_string());
+    }
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = String::from_utf8(base64_decode(credentials)?).ok()?;
+    let (_, password) = decoded.split_once(':')?;
+    (!password.is_empty()).then(|| password.to_string())
+}
+
+/// Whether a request's `Origin`, when a browser sent one, is the `Host` it was sent to.
+/// Browsers send it on every cross-site POST, along with any basic credentials they hold,
+/// so this is what keeps another site's page from acting through the dashboard.
+pub fn same_origin(origin: Option<&str>, host: Option<&str>) -> bool {
+    let Some(origin) = origin else {
+        return true;
+    };
+    let authority = origin
+        .split_once("://")
+        .map_or(origin, |(_, authority)| authority)
+        .trim_end_matches('/');
+    host.is_some_and(|host| authority.eq_ignore_ascii_case(host))
+}
+
+/// Whether a `Host` header names the local machine, with or without a port. Other names
+/// resolving to it are how a DNS-rebinding page reaches a localhost server.
+pub fn is_loopback_host(host: &str) -> bool {
+    // A bare IPv6 address is all colons, so it can't have a port split off
+    if let Ok(ip) = host.parse::<std::net::IpAddr>() {
+        return ip.is_loopback();
+    }
+    let name = match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or(""),
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    };
+    name.eq_ignore_ascii_case("localhost")
+        || name
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    };
+    let mut bytes = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in text.trim_end_matches('=').bytes() {
+        buffer = buffer << 6 | value(c)? as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// Reads the tokens file; `None` when there is none or it lists no tokens.
+#[cfg(feature = "native")]
+pub fn load_tokens(path: &std::path::Path) -> anyhow::Result<Option<ApiTokens>> {
+    use anyhow::Context;
+
+    if !path.exists() {
+        return Ok(None);
+    }
+    let json = std::fs::read_to_string(path)?;
+    ApiTokens::parse(&json)
+        .map(|tokens| (!tokens.0.is_empty()).then_some(tokens))
+        .with_context(|| format!("Invalid API tokens in {}", path.display()))
+}
+
+/// Refuses to serve beyond localhost without tokens. Even on localhost, actions need an
+/// admin token, since any page the browser opens can send requests there.
+#[cfg(feature = "native")]
+pub fn check_exposure(
+    addr: std::net::SocketAddr,
+    tokens: Option<&ApiTokens>,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        addr.ip().is_loopback() || tokens.is_some(),
+        "Serving on {} needs API tokens in {}; add one with `tokens add`",
+        addr,
+        API_TOKENS_FILE
+    );
+    Ok(())
+}
+
+/// A new random token, 32 bytes from the OS as hex.
+#[cfg(feature = "native")]
+pub fn new_token() -> anyhow::Result<String> {
+    use std::io::Read;
+
+    let mut bytes = [0u8; 32];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_authorize_by_role() {
+        let mut tokens = ApiTokens::default();
+        tokens.add("grafana", "viewer-token", Role::Read);
+        tokens.add("ops", "admin-token", Role::Admin);
+        let tokens = ApiTokens::parse(&serde_json::to_string(&tokens).unwrap()).unwrap();
+
+        let admin = tokens.authorize(Some("Bearer admin-token"), Role::Admin);
+        assert_eq!(admin.map(|t| t.name.as_str()), Ok("ops"));
+        assert_eq!(
+            tokens
+                .authorize(Some("bearer viewer-token"), Role::Read)
+                .map(|t| t.role),
+            Ok(Role::Read)
+        );
+        assert_eq!(
+            tokens.authorize(Some("Bearer viewer-token"), Role::Admin),
+            Err(AuthError::Forbidden)
+        );
+        assert_eq!(
+            tokens.authorize(Some("Bearer guess"), Role::Read),
+            Err(AuthError::Unknown)
+        );
+        assert_eq!(tokens.authorize(None, Role::Read), Err(AuthError::Missing));
+        // "anyone:viewer-token", as a browser sends it
+        assert_eq!(
+            presented_token("Basic YW55b25lOnZpZXdlci10b2tlbg==").as_deref(),
+            Some("viewer-token")
+        );
+        assert_eq!(presented_token("Digest abc"), None);
+
+        assert!(same_origin(None, Some("127.0.0.1:8080")));
+        assert!(same_origin(
+            Some("http://127.0.0.1:8080"),
+            Some("127.0.0.1:8080")
+        ));
+        assert!(!same_origin(
+            Some("https://evil.example"),
+            Some("127.0.0.1:8080")
+        ));
+        assert!(!same_origin(Some("null"), Some("127.0.0.1:8080")));
+        assert!(is_loopback_host("localhost:8080") && is_loopback_host("[::1]:8080"));
+        assert!(is_loopback_host("127.0.0.1") && !is_loopback_host("rebind.example:8080"));
+        assert!(is_loopback_host("::1") && is_loopback_host("[::1]"));
+        assert!(!is_loopback_host("::2") && !is_loopback_host("[2001:db8::1]:8080"));
+
+        assert!(ApiTokens::parse(r#"[{"name": "x", "sha256": "abc", "role": "read"}]"#).is_err());
+        assert!("owner".parse::<Role>().is_err());
+    }
+}
@@ -3,79 +3,531 @@ use chrono::{DateTime, Duration, Utc};
 use futures::stream::StreamExt;
 use futures::TryFutureExt;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Write as FmtWrite;
 use std::fs;
 use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
 
+use crate::analytics::{new_run_id, AnalyticsStore, PredictionRecord, TradeRecord};
+use crate::artifacts::{Artifact, S3Sink};
+use crate::attribution::{
+    ablated_prompt, ablations, perturbations, AttributionReport, Perturbation, PromptSection,
+    SensitivityReport,
+};
+use crate::audit::{config_hash, AuditStore, WindowMetadata};
+use crate::baseline::{vwap_reversion, vwap_reversion_on, VWAP_REVERSION_BAND};
+use crate::calendar::{Calendar, CalendarEvent};
+use crate::calibration::{CalibrationReport, CalibrationTracker};
+use crate::candidates::{
+    load_candidate, load_evaluation, remove_candidate, save_evaluation, CandidateEvaluation,
+    VALIDATION_WINDOWS,
+};
+use crate::challenger::HeadToHead;
+use crate::chart::render_signal_svg;
+use crate::comparison::{rank_scores, PromptScore};
+use crate::compute::{
+    assemble_snapshot_prompt, join_data_section, label_candles_with, market_snapshot,
+    AnnotatedWindow,
+};
+use crate::confusion::{ConfusionMatrix, ImprovementFocus};
+use crate::data_quality::{check_series, CandleAnomaly, SeriesReport};
+use crate::dedup::find_duplicate;
+use crate::dev::DevScore;
+use crate::encoding::{decode_level, CandleEncoding};
+use crate::features::{FeatureRegistry, FeatureReport};
+use crate::funding::{funding_covers, FundingPoint, FundingStore};
+use crate::granularity::check_granularity;
+use crate::hallucination::{
+    check_claims, hallucination_rates, HallucinationRate, IMPROVER_HALLUCINATIONS,
+};
+use crate::implied_vol::{ImpliedVolStore, IvPoint, RegimeBreakdown};
+use crate::input_drift::{InputProfile, InputStats};
+use crate::journal::{journal_entries, JournalEntry};
+use crate::latency::{throughput, LatencyStats};
+use crate::leaderboard::{leaderboard, LeaderboardEntry};
+use crate::leakage::assert_no_future_candles;
+use crate::ledger::Lot;
+use crate::lint::{lint_prompt, LintViolation};
+use crate::liquidations::{LiquidationBucket, LiquidationStore};
+use crate::live::{
+    challenger_prompts, load_calendar, metered, request_allocation, request_multi_asset_prediction,
+    request_prediction, stored_context, CHALLENGER_DIR, LIVE_SYMBOLS,
+};
+use crate::llm_cache::LlmCache;
+use crate::manifest::RunManifest;
+use crate::out_of_sample::{OutOfSampleQueue, OutOfSampleResult};
+use crate::postmortem::{
+    build_explanation_prompt, summary_of, FailureAnalysis, IMPROVER_POSTMORTEMS,
+};
+use crate::prediction::{
+    parse_allocation_response, parse_model_response, parse_multi_asset_response, Allocation,
+    Prediction,
+};
+use crate::profiles::{SymbolProfile, DEFAULT_TARGET};
 use crate::prompt_builder::build_data_section;
+use crate::prompt_pnl::{prompt_pnl, LiveOutcome, PromptPnl};
+use crate::rationale::{
+    build_grading_prompt, parse_grade, rationale_quality, RationaleGrade, RationaleQuality,
+};
+use crate::rescore::{
+    load_predictions, parse_signals_csv, rescore, RecordedPrediction, RescoreConfig, RescoreReport,
+};
+use crate::research_export::{align_signals, research_csv, ResearchFormat};
+use crate::routing::{Escalation, ModelRouter, RoutingStats};
+use crate::sampling::SeededRng;
+use crate::scenarios::{Scenario, ScenarioResult};
+use crate::sessions::SessionBreakdown;
+use crate::simulator::{
+    accrue_funding, bracket_trade, level_outcome, margin_trade, pair_trade, rebalance_portfolio,
+    simulate_exits, summarize, DelayedFills, FillModel, MarginModel, PortfolioSummary, Signal,
+    SimulationSummary, Trade, EXIT_RULES, FEE_RATE, FILL_GRANULARITY,
+};
+use crate::snapshot::{AuxContext, MarketSnapshot};
+use crate::storage::{self, Storage, HISTORY_LIMIT};
+use crate::store::{covers, fetch_range, CandleStore};
+use crate::streaming::{effective_samples, stream_windows, RunningMetrics};
+use crate::sweep::SweepPoint;
+use crate::thresholds::{load_labeling, LABELING_FILE};
+use crate::truncation::{estimate_tokens, fit_prompt, fit_prompt_with};
 use crate::{
-    analyze_data_gpt, candles_to_array, get_candle_data, label_candles, Action, CoinbaseCandle,
-    Model,
+    analyze_data_gpt, assemble_allocation_prompt, assemble_multi_asset_prompt,
+    assemble_pair_prompt, label_pair, prepare_candles, prompt_version, Action, CallKind,
+    ChatOptions, Model, ReasoningEffort, TokenUsage, CANDLE_HOURS, GRANULARITY, LONG_THRESHOLD,
+    PROMPT_FILE, RESPONSE_STRICTNESS, SHORT_THRESHOLD,
 };
 
-const CANDLE_HOURS: usize = 24; // 24-hour window
-const CACHE_DIR: &str = "cache";
-const PROMPT_FILE: &str = "prompt.txt";
-const HISTORY_FILE: &str = "prompt_history.json";
+const CLOSE: usize = 4;
+const REPORT_HTML: &str = "report.html";
+/// Model requests in flight at once.
+const MAX_IN_FLIGHT: usize = 20;
+/// Streaming evaluation writes predictions out in batches of this size.
+const PREDICTION_FLUSH: usize = 500;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct PromptRecord {
-    prompt: String,
-    score: f64,
+pub struct PromptRecord {
+    pub prompt: String,
+    pub score: f64,
+    /// Accuracy on the period after the prompt was improved, once it has been evaluated
+    /// (see [`run_due_out_of_sample`]).
+    #[serde(default)]
+    pub out_of_sample: Option<f64>,
+    /// Target symbol the prompt was scored on; each keeps its own history.
+    #[serde(default = "default_target")]
+    pub symbol: String,
+}
+
+fn default_target() -> String {
+    DEFAULT_TARGET.to_string()
+}
+
+/// How a backtest picks its windows.
+#[derive(Debug, Clone)]
+pub struct BacktestOptions {
+    /// Seed for any random window selection. A fresh one is drawn when unset; either way
+    /// it is recorded in the run manifest.
+    pub seed: Option<u64>,
+    /// Candles between consecutive windows. 1 slides the window a candle at a time, so
+    /// neighbouring windows overlap almost entirely; [`NON_OVERLAPPING`] makes them
+    /// disjoint.
+    pub stride: usize,
+    /// Score only this many windows, drawn with `seed` from those the stride selects.
+    /// Much cheaper while exploring prompts; confirm promising ones without it.
+    pub sample: Option<usize>,
+    /// Route windows between a cheap and an expensive model instead of asking o1-mini.
+    pub routing: Option<ModelRouter>,
+    /// Leverage and short financing of the simulated trades.
+    pub margin: MarginModel,
+    /// Reasoning effort and token allowance of every model call.
+    pub chat: ChatOptions,
+    /// Delay between a window's last candle closing and its trade filling, priced from
+    /// [`FILL_GRANULARITY`] candles. Trades fill at that close when unset.
+    pub execution_delay: Option<std::time::Duration>,
+    /// Position size and market impact of the simulated trades' fills; every size fills at
+    /// the quoted price when unset.
+    pub fills: Option<FillModel>,
+    /// Target symbol, prompt file and context symbols of [`backtest_current_prompt`]; ETH
+    /// on `prompt.txt` by default.
+    pub profile: SymbolProfile,
+}
+
+/// Stride that gives non-overlapping windows.
+pub const NON_OVERLAPPING: usize = CANDLE_HOURS;
+
+impl Default for BacktestOptions {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            stride: 1,
+            sample: None,
+            routing: None,
+            margin: MarginModel::default(),
+            chat: ChatOptions::default(),
+            execution_delay: None,
+            fills: None,
+            profile: SymbolProfile::default(),
+        }
+    }
+}
+
+impl BacktestOptions {
+    /// Prompt tokens the smallest-context model in use can take.
+    fn token_budget(&self) -> usize {
+        match &self.routing {
+            Some(router) => router
+                .cheap
+                .prompt_token_budget()
+                .min(router.expensive.prompt_token_budget()),
+            None => Model::O1Mini.prompt_token_budget(),
+        }
+    }
+
+    /// These options with the seed fixed, drawing one if unset, so repeated runs select
+    /// windows the same way.
+    pub fn seeded(&self) -> Self {
+        let mut seeded = self.clone();
+        seeded.seed.get_or_insert_with(entropy_seed);
+        seeded
+    }
+}
+
+/// A seed from the clock, for runs that were not given one.
+pub fn entropy_seed() -> u64 {
+    let nanos = Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64;
+    SeededRng::new(nanos).next_u64()
 }
 
+/// Backtests the current prompt, then asks the model for an improved prompt and saves it
+/// over `prompt.txt` when any window was wrong. Returns the accuracy of the prompt that
+/// was tested.
 pub async fn run_backtest_and_improve() -> Result<f64> {
-    // Ensure cache directory exists
-    fs::create_dir_all(CACHE_DIR)?;
+    let run = backtest_current_prompt(&BacktestOptions::default()).await?;
+    if let Some(improved) = improve_prompt(&run, false).await? {
+        fs::write(PROMPT_FILE, improved)?;
+        tracing::info!("Prompt improved and saved to {}", PROMPT_FILE);
+    }
+    Ok(run.accuracy)
+}
 
-    // We'll fetch data for the last N hours
-    let end = Utc::now() - Duration::hours(48);
-    let start = end - Duration::hours(48 * 2); // 48 hours of data
+/// A backtest of one prompt, with what the improvement step needs.
+#[derive(Debug, Clone)]
+pub struct BacktestRun {
+    pub run_id: String,
+    pub prompt: String,
+    pub accuracy: f64,
+    /// `(window, predicted, label, rationale)` for every wrong window.
+    pub failures: Vec<(usize, Action, Action, String)>,
+    /// `(window, unsupported prices, rationale)` for every window whose rationale cited
+    /// prices absent from its data, most unsupported prices first.
+    pub hallucinations: Vec<(usize, Vec<f64>, String)>,
+    /// Recent prompt history, including this run.
+    pub history: Vec<PromptRecord>,
+    pub confusion: ConfusionMatrix,
+    /// Compounded return of the simulated next-candle trades.
+    pub total_return: f64,
+    /// Long and short multipliers the windows were labeled with.
+    pub label_thresholds: (f64, f64),
+    /// The symbols the prompt was shown, the target first.
+    pub symbols: Vec<String>,
+}
+
+/// Backtests the profile's prompt (`prompt.txt` on ETH by default) over the fixed recent
+/// range, recording predictions, trades, the manifest and the symbol's prompt history.
+pub async fn backtest_current_prompt(options: &BacktestOptions) -> Result<BacktestRun> {
+    let seed = options.seeded().seed.expect("seeded");
+    let storage = storage::from_env().await?;
+    let cache = LlmCache::from_env().await;
+    let started_at = Utc::now();
 
-    // Fetch or load cached data
-    let eth_candles = candles_to_array(load_or_fetch("ETH", start, end).await?);
-    let btc_candles = candles_to_array(load_or_fetch("BTC", start, end).await?);
-    let sol_candles = candles_to_array(load_or_fetch("SOL", start, end).await?);
+    let (start, end) = backtest_range();
 
-    // Label ETH data for ground truth
-    let labels = label_candles(&eth_candles);
+    // Fetch or load cached data, the target first
+    let profile = &options.profile;
+    let target = profile.symbol.as_str();
+    let context = profile.context_symbols(&LIVE_SYMBOLS);
+    let symbols = std::iter::once(target)
+        .chain(context.iter().map(String::as_str))
+        .collect::<Vec<_>>();
+    let mut series = Vec::new();
+    for symbol in &symbols {
+        series.push(load_prepared(storage.as_ref(), symbol, start, end).await?);
+    }
+    let (target_candles, _) = &series[0];
+    let fill_candles = match options.execution_delay {
+        Some(_) => load_or_fetch(storage.as_ref(), target, FILL_GRANULARITY, start, end).await?,
+        None => Vec::new(),
+    };
+    // Settled funding replaces the flat short cost for trades its fetched history covers
+    let funding = FundingStore::default().load(target)?;
+    // Funding is charged from the close of the entry candle to the close of the exit one
+    let covered = |entry_time: f64, exit_time: f64| {
+        let interval = GRANULARITY as f64;
+        funding_covers(&funding, entry_time + interval, exit_time + interval)
+    };
+    let delayed_fills = options.execution_delay.map(|delay| DelayedFills {
+        candles: &fill_candles,
+        delay_secs: delay.as_secs_f64(),
+    });
 
-    if eth_candles.len() < CANDLE_HOURS {
-        anyhow::bail!("Not enough ETH candles to perform backtesting");
+    // Label the target's data for ground truth, with its calibrated thresholds if any
+    let thresholds = load_labeling(Path::new(LABELING_FILE))?.thresholds(target);
+    let labels = label_candles_with(target_candles, thresholds.0, thresholds.1);
+
+    if target_candles.len() < CANDLE_HOURS {
+        anyhow::bail!("Not enough {} candles to perform backtesting", target);
     }
 
     // Load the current prompt from a file
-    let base_prompt = fs::read_to_string(PROMPT_FILE).context("Failed to read base prompt file")?;
+    let base_prompt = fs::read_to_string(&profile.prompt).with_context(|| {
+        format!(
+            "Failed to read base prompt file {}",
+            profile.prompt.display()
+        )
+    })?;
+
+    let named = symbols
+        .iter()
+        .zip(&series)
+        .map(|(symbol, (candles, _))| (*symbol, &candles[..]))
+        .collect::<Vec<_>>();
+    for warning in check_granularity(&named, CANDLE_HOURS, &base_prompt, thresholds) {
+        tracing::warn!(%target, "Suspicious granularity: {}", warning);
+    }
 
     // Prepare tasks for each candle window
-    let tasks = (CANDLE_HOURS..eth_candles.len()).filter_map(|i| {
-        if btc_candles.len() < i || sol_candles.len() < i {
+    let stride = options.stride.max(1);
+    let (window_ends, candidates) = select_windows(target_candles.len(), options, seed);
+    let token_budget = options.token_budget();
+    // Windows all have the same length, so any truncation is the same for each of them
+    let truncation = Mutex::new(None);
+    let market = StoredMarket::load(&symbols)?;
+    let tasks = window_ends.into_iter().filter_map(|i| {
+        if series.iter().any(|(candles, _)| candles.len() < i) {
             return None;
         }
 
-        let eth_window = &eth_candles[i - CANDLE_HOURS..i];
-        let btc_window = &btc_candles[i - CANDLE_HOURS..i];
-        let sol_window = &sol_candles[i - CANDLE_HOURS..i];
+        let target_window = &target_candles[i - CANDLE_HOURS..i];
+        let windows = symbols
+            .iter()
+            .zip(&series)
+            .map(|(symbol, (candles, anomalies))| {
+                (*symbol, &candles[i - CANDLE_HOURS..i], &anomalies[..])
+            })
+            .collect::<Vec<_>>();
 
-        let data_section = build_data_section(eth_window, btc_window, sol_window);
-        let full_prompt = format!("{}\n\n{}", base_prompt, data_section);
+        let (events, context) = market.at(&symbols, target_candles[i - 1][0])?;
+        let regime = context
+            .iter()
+            .find(|(symbol, _)| *symbol == target)
+            .and_then(|(_, aux)| aux.implied_volatility)
+            .map(|iv| iv.regime);
+        let (full_prompt, applied) = fit_prompt_with(&windows, token_budget, |w| {
+            let snapshot = market_snapshot(w, target, &events, &context);
+            let snapshot = snapshot
+                .with_encoding(profile.encoding)
+                .with_prompt_features(profile.prompt_features);
+            assemble_snapshot_prompt(&base_prompt, &snapshot)
+        });
+        if applied.is_some() {
+            *truncation.lock().unwrap() = applied;
+        }
+        assert_no_future_candles(&full_prompt, target_candles[i - 1][0]);
         let label = labels[i - 1];
+        let features = market_snapshot(&windows, target, &events, &context).features;
+        let baseline = vwap_reversion_on(&features, target, VWAP_REVERSION_BAND);
 
-        let fut = query_model_and_compare(full_prompt, label).map_ok(move |res| (i, res));
+        let fut = query_model_and_compare(
+            &cache,
+            options.routing,
+            options.chat,
+            full_prompt,
+            target_window,
+            label,
+        )
+        .map_ok(move |res| (i, baseline, regime, features, res));
         Some(fut)
     });
 
-    let results = futures::stream::iter(tasks).buffer_unordered(20);
+    // Nothing is spawned: returning on the first failed or timed-out window drops the
+    // stream, cancelling the windows still in flight
+    let results = futures::stream::iter(tasks).buffer_unordered(MAX_IN_FLIGHT);
     futures::pin_mut!(results);
 
     let mut correct_count = 0usize;
+    let mut baseline_correct = 0usize;
     let mut total = 0usize;
     let mut failures = Vec::new();
+    let mut confusion = ConfusionMatrix::default();
+
+    let run_id = new_run_id();
+    let version = prompt_version(&base_prompt);
+    let run_model = match &options.routing {
+        Some(router) => format!("{}->{}", router.cheap.as_str(), router.expensive.as_str()),
+        None => Model::O1Mini.as_str().to_string(),
+    };
+    let config = config_hash(&run_model, options.chat, CANDLE_HOURS);
+    let audit = AuditStore::default();
+    let mut predictions = Vec::new();
+    let mut trades = Vec::new();
+    let mut signals = Vec::new();
+    let mut calibration = CalibrationTracker::default();
+    let mut sessions = SessionBreakdown::default();
+    let mut regimes = RegimeBreakdown::default();
+    let mut window_regimes = HashMap::new();
+    let mut feature_report = FeatureReport::default();
+    let mut hallucinations = Vec::new();
+    let mut input_stats = Vec::new();
+    let mut latencies = LatencyStats::default();
+    let mut routing = RoutingStats::default();
+    let timer = Instant::now();
 
     while let Some(res) = results.next().await {
-        let (i, (pred, rationale, label)) = res?;
+        let (
+            i,
+            baseline,
+            regime,
+            features,
+            Scored {
+                prediction:
+                    Prediction {
+                        action: pred,
+                        rationale,
+                        key_factors,
+                        invalidation_price,
+                        target_price,
+                        confidence,
+                    },
+                label,
+                latency,
+                model,
+                escalation,
+                prompt,
+            },
+        ) = res?;
         total += 1;
+        let window = &target_candles[i - CANDLE_HOURS..i];
+        let decode =
+            |level: Option<f64>| level.and_then(|l| decode_level(window, profile.encoding, l));
+        let (invalidation_price, target_price) = (decode(invalidation_price), decode(target_price));
+        if let Some(router) = &options.routing {
+            routing.record(router, escalation, pred == label);
+        }
+        if let Some(latency) = latency {
+            latencies.record(latency);
+        }
+        if baseline == label {
+            baseline_correct += 1;
+        }
+        let outcome = level_outcome(
+            pred,
+            target_candles[i - 1][CLOSE],
+            invalidation_price,
+            target_price,
+            &target_candles[i],
+        );
+        calibration.record(pred == label, confidence, outcome);
+        sessions.record_prediction(target_candles[i - 1][0], pred == label);
+        if let Some(regime) = regime {
+            regimes.record_prediction(regime, pred == label);
+            window_regimes.insert(target_candles[i - 1][0] as i64, regime);
+        }
+        feature_report.record(&features, target, pred == label);
+        confusion.record(pred, label);
+        audit.put_prompt(&prompt)?;
+        input_stats.push(InputStats::of(window));
+        // Encoded rationales quote encoded numbers, which the raw candles can't support
+        let claims = (profile.encoding == CandleEncoding::Raw).then(|| {
+            let windows = series
+                .iter()
+                .map(|(candles, _)| &candles[i - CANDLE_HOURS..i])
+                .collect::<Vec<_>>();
+            check_claims(&rationale, &windows, &[invalidation_price, target_price])
+        });
+        if let Some(claims) = claims.as_ref().filter(|c| c.is_hallucinated()) {
+            hallucinations.push((i, claims.unsupported.clone(), rationale.clone()));
+        }
+
+        predictions.push(PredictionRecord {
+            run_id: run_id.clone(),
+            source: "backtest".to_string(),
+            symbol: target.to_string(),
+            model: model.as_str().to_string(),
+            prompt_hash: version.clone(),
+            window_end: target_candles[i - 1][0],
+            action: pred,
+            label: Some(label),
+            correct: Some(pred == label),
+            rationale: rationale.clone(),
+            key_factors,
+            invalidation_price,
+            target_price,
+            confidence,
+            level_outcome: outcome,
+            latency_ms: latency.map(|l| l.as_secs_f64() * 1e3),
+            // The run's model, as the manifest hashes it: a routed window's config matches
+            // its run whichever model of the pair answered
+            metadata: Some(WindowMetadata::new(
+                target,
+                window,
+                &version,
+                &run_model,
+                options.chat,
+                &prompt,
+            )),
+            numeric_claims: claims,
+        });
+        signals.push(Signal {
+            entry: i - 1,
+            side: pred,
+            stop: invalidation_price,
+            target: target_price,
+        });
+        let delayed = delayed_fills.and_then(|fills| fills.reprice(target_candles, i - 1));
+        let (data, entry) = match (&delayed_fills, &delayed) {
+            (Some(_), Some(repriced)) => (&repriced[..], 0),
+            // No fill data for this window: the trade can't be priced with the delay
+            (Some(_), None) => (&[][..], 0),
+            (None, _) => (&target_candles[..], i - 1),
+        };
+        let funded = data
+            .get(entry..entry + 2)
+            .is_some_and(|c| covered(c[0][0], c[1][0]));
+        let margin = MarginModel {
+            short_cost_per_hour: if funded {
+                0.0
+            } else {
+                options.margin.short_cost_per_hour
+            },
+            ..options.margin
+        };
+        let trade = margin_trade(
+            data,
+            entry,
+            pred,
+            invalidation_price,
+            target_price,
+            FEE_RATE,
+            &margin,
+        )
+        .map(|trade| {
+            if funded {
+                accrue_funding(trade, &funding, GRANULARITY as f64, margin.leverage)
+            } else {
+                trade
+            }
+        });
+        trades.extend(trade.map(|trade| match &options.fills {
+            Some(model) => model.apply(
+                trade,
+                &data[entry],
+                &data[entry + 1],
+                options.margin.leverage,
+            ),
+            None => trade,
+        }));
+
         if pred == label {
             correct_count += 1;
         } else {
@@ -83,120 +535,2429 @@ pub async fn run_backtest_and_improve() -> Result<f64> {
         }
     }
 
-    let accuracy = if total > 0 {
-        correct_count as f64 / total as f64
+    trades.sort_by(|a, b| a.entry_time.total_cmp(&b.entry_time));
+    let simulation = summarize(&trades);
+    tracing::info!(?simulation, "Simulated next-candle PnL");
+    let hallucination = hallucination_rates(
+        predictions
+            .iter()
+            .filter_map(|p| Some((version.as_str(), p.numeric_claims.as_ref()?))),
+    )
+    .pop();
+    tracing::info!(
+        ?hallucination,
+        "Rationales citing prices absent from the data"
+    );
+    hallucinations.sort_by_key(|(i, unsupported, _)| (std::cmp::Reverse(unsupported.len()), *i));
+    for trade in &trades {
+        sessions.record_trade(trade.entry_time, trade.return_pct);
+        if let Some(&regime) = window_regimes.get(&(trade.entry_time as i64)) {
+            regimes.record_trade(regime, trade.return_pct);
+        }
+    }
+    signals.sort_by_key(|s| s.entry);
+    let exit_rules = EXIT_RULES
+        .iter()
+        .map(|&rule| {
+            let trades = simulate_exits(target_candles, &signals, rule, FEE_RATE)
+                .into_iter()
+                .map(|trade| {
+                    if covered(trade.entry_time, trade.exit_time) {
+                        accrue_funding(trade, &funding, GRANULARITY as f64, 1.0)
+                    } else {
+                        trade
+                    }
+                })
+                .collect::<Vec<_>>();
+            let summary = summarize(&trades);
+            tracing::info!(?rule, ?summary, "Simulated PnL by exit rule");
+            (rule, summary)
+        })
+        .collect::<Vec<_>>();
+    let calibration = calibration.report();
+    tracing::info!(?calibration, "Target and confidence calibration");
+    let latency = latencies.summary();
+    let throughput = throughput(total, timer.elapsed());
+    tracing::info!(
+        ?latency,
+        throughput,
+        "Model latency (ms) and windows per second"
+    );
+
+    storage.append_predictions(&predictions).await?;
+    let trades = trades
+        .into_iter()
+        .map(|trade| TradeRecord {
+            run_id: run_id.clone(),
+            symbol: target.to_string(),
+            prompt_hash: version.clone(),
+            trade,
+        })
+        .collect::<Vec<_>>();
+    storage.append_trades(&trades).await?;
+
+    let (accuracy, baseline_accuracy) = if total > 0 {
+        (
+            correct_count as f64 / total as f64,
+            baseline_correct as f64 / total as f64,
+        )
     } else {
-        0.0
+        (0.0, 0.0)
     };
 
-    tracing::info!("Backtesting complete. Accuracy: {:.2}%", accuracy * 100.0);
+    tracing::info!(
+        "Backtesting complete. Accuracy: {:.2}% (VWAP reversion baseline: {:.2}%)",
+        accuracy * 100.0,
+        baseline_accuracy * 100.0
+    );
+    // A sample can't be more independent than the windows it was drawn from
+    let effective_samples = effective_samples(candidates, stride, CANDLE_HOURS).min(total as f64);
+    tracing::info!(
+        windows = total,
+        stride,
+        effective_samples,
+        "Independent samples behind the accuracy"
+    );
 
-    // Update prompt history
-    let history_path = format!("{}/{}", CACHE_DIR, HISTORY_FILE);
-    let mut history: Vec<PromptRecord> = if Path::new(&history_path).exists() {
-        let data = fs::read_to_string(&history_path)?;
-        serde_json::from_str(&data).unwrap_or_default()
-    } else {
-        Vec::new()
+    let routing = options.routing.map(|_| routing.report());
+    if let Some(routing) = &routing {
+        tracing::info!(?routing, "Blended accuracy and cost of model routing");
+    }
+
+    let manifest = RunManifest {
+        run_id: run_id.clone(),
+        started_at,
+        finished_at: Some(Utc::now()),
+        model: run_model,
+        prompt_version: version.clone(),
+        symbols: symbols.iter().map(|s| s.to_string()).collect(),
+        granularity: GRANULARITY,
+        window_candles: CANDLE_HOURS,
+        data_start: start,
+        data_end: end,
+        windows: total,
+        accuracy: Some(accuracy),
+        seed: Some(seed),
+        stride,
+        sample: options.sample,
+        truncation: *truncation.lock().unwrap(),
+        prompt: Some(base_prompt.clone()),
+        total_return: Some(simulation.total_return),
+        chat: options.chat,
+        cost: Some(match &routing {
+            Some(routing) => routing.cost,
+            None => total as f64 * Model::O1Mini.relative_cost(),
+        }),
+        config_hash: Some(config),
+        inputs: InputProfile::from_stats(&input_stats),
+        execution_delay_secs: options.execution_delay.map(|d| d.as_secs_f64()),
+        label_thresholds: (thresholds != (LONG_THRESHOLD, SHORT_THRESHOLD)).then_some(thresholds),
+        encoding: profile.encoding,
     };
+    storage.save_manifest(&manifest).await?;
 
-    // Append current prompt and score
-    history.push(PromptRecord {
+    // Append current prompt and score to the prompt history
+    storage
+        .append_prompt_record(&PromptRecord {
+            prompt: base_prompt.clone(),
+            score: accuracy,
+            out_of_sample: None,
+            symbol: target.to_string(),
+        })
+        .await?;
+    let history = storage.prompt_history(target, HISTORY_LIMIT).await?;
+
+    if let Some(sink) = S3Sink::from_env()? {
+        let report = json!({
+            "manifest": &manifest,
+            "baseline_accuracy": baseline_accuracy,
+            "simulation": &simulation,
+            "margin": &options.margin,
+            "fills": &options.fills,
+            "exit_rules": exit_rules
+                .iter()
+                .map(|(rule, summary)| json!({ "rule": rule, "simulation": summary }))
+                .collect::<Vec<_>>(),
+            "calibration": &calibration,
+            "sessions": &sessions,
+            "iv_regimes": &regimes,
+            "features": &feature_report,
+            "hallucination": &hallucination,
+            "confusion": &confusion,
+            "latency": &latency,
+            "throughput": throughput,
+            "effective_samples": effective_samples,
+            "routing": &routing,
+        });
+        let artifacts = [
+            Artifact::json("report.json", &report)?,
+            Artifact::json_lines("predictions.jsonl", &predictions)?,
+            Artifact::json_lines("trades.jsonl", &trades)?,
+            Artifact::json("prompt_history.json", &history)?,
+        ];
+        sink.upload_run(&run_id, &artifacts).await?;
+    }
+
+    Ok(BacktestRun {
+        run_id,
         prompt: base_prompt.clone(),
-        score: accuracy,
+        accuracy,
+        failures,
+        hallucinations,
+        history,
+        confusion,
+        total_return: simulation.total_return,
+        label_thresholds: thresholds,
+        symbols: symbols.iter().map(|s| s.to_string()).collect(),
+    })
+}
+
+/// Scores the current prompt at each reasoning effort and candle encoding in one shared
+/// pass, and reports what each combination bought in accuracy and return against its
+/// token spend. Nothing is recorded.
+pub async fn sweep_reasoning_effort(
+    options: &BacktestOptions,
+    efforts: &[ReasoningEffort],
+    encodings: &[CandleEncoding],
+) -> Result<Vec<SweepPoint>> {
+    let options = options.seeded();
+    let base_prompt = fs::read_to_string(PROMPT_FILE).context("Failed to read base prompt file")?;
+    let model = match &options.routing {
+        Some(router) => router.expensive,
+        None => Model::O1Mini,
+    };
+    let token_budget = options.token_budget();
+    let combinations = efforts
+        .iter()
+        .flat_map(|&e| encodings.iter().map(move |&c| (e, c)))
+        .collect::<Vec<_>>();
+    let variants = combinations
+        .iter()
+        .map(|&(effort, encoding)| {
+            let (base_prompt, prompt_features) = (&base_prompt, options.profile.prompt_features);
+            Variant {
+                prompt: Box::new(move |windows, _| {
+                    fit_prompt_with(windows, token_budget, |w| {
+                        let snapshot = MarketSnapshot::from_windows(w)
+                            .with_encoding(encoding)
+                            .with_prompt_features(prompt_features);
+                        assemble_snapshot_prompt(base_prompt, &snapshot)
+                    })
+                    .0
+                }),
+                chat: ChatOptions {
+                    reasoning_effort: Some(effort),
+                    ..options.chat
+                },
+                encoding,
+            }
+        })
+        .collect::<Vec<_>>();
+    let tallies = score_in_one_pass(&variants, &options).await?;
+    let points = combinations
+        .into_iter()
+        .zip(tallies)
+        .map(|((effort, encoding), tally)| {
+            let point = SweepPoint {
+                reasoning_effort: effort,
+                encoding,
+                accuracy: tally.accuracy(),
+                total_return: summarize(&tally.trades).total_return,
+                completion_tokens: tally.usage.completion,
+                reasoning_tokens: tally.usage.reasoning,
+                cost: tally.usage.cost(model),
+            };
+            tracing::info!(?point, "Swept reasoning effort and encoding");
+            point
+        })
+        .collect();
+    Ok(points)
+}
+
+/// Where the backtest's candles come from: the 96 hours up to two days ago.
+fn backtest_range() -> (DateTime<Utc>, DateTime<Utc>) {
+    let end = Utc::now() - Duration::hours(48);
+    (end - Duration::hours(48 * 2), end)
+}
+
+/// Ends of the windows a backtest scores over `len` candles, and how many windows the
+/// stride allowed before any sampling.
+fn select_windows(len: usize, options: &BacktestOptions, seed: u64) -> (Vec<usize>, usize) {
+    let mut window_ends = (CANDLE_HOURS..len)
+        .step_by(options.stride.max(1))
+        .collect::<Vec<_>>();
+    let candidates = window_ends.len();
+    if let Some(k) = options.sample {
+        window_ends = SeededRng::new(seed)
+            .sample_indices(candidates, k)
+            .into_iter()
+            .map(|j| window_ends[j])
+            .collect();
+        tracing::info!(
+            sampled = window_ends.len(),
+            candidates,
+            seed,
+            "Scoring a sample of windows"
+        );
+    }
+    (window_ends, candidates)
+}
+
+/// Seed of the dev loop's window sample, fixed so every edit is scored on the same windows.
+const DEV_SEED: u64 = 0;
+
+/// A symbol's cleaned candles and the anomalies found in them.
+type PreparedSeries = (&'static str, Vec<[f64; 6]>, Vec<CandleAnomaly>);
+
+/// The data and windows of a dev loop, loaded once so each prompt edit is scored against
+/// the same candles even as the backtest range moves on.
+pub struct DevSession {
+    candles: Vec<PreparedSeries>,
+    labels: Vec<Action>,
+    window_ends: Vec<usize>,
+    cache: LlmCache,
+}
+
+impl DevSession {
+    pub async fn load(windows: usize) -> Result<Self> {
+        let storage = storage::from_env().await?;
+        let (start, end) = backtest_range();
+        let mut candles = Vec::new();
+        for symbol in ["ETH", "BTC", "SOL"] {
+            let (series, anomalies) = load_prepared(storage.as_ref(), symbol, start, end).await?;
+            candles.push((symbol, series, anomalies));
+        }
+        let len = candles.iter().map(|(_, c, _)| c.len()).min().unwrap_or(0);
+        if len < CANDLE_HOURS {
+            anyhow::bail!("Not enough candles to run the dev backtest");
+        }
+        let options = BacktestOptions {
+            sample: Some(windows),
+            ..Default::default()
+        };
+        let (mut window_ends, _) = select_windows(len, &options, DEV_SEED);
+        window_ends.sort_unstable();
+        Ok(Self {
+            labels: load_labeling(Path::new(LABELING_FILE))?.label(candles[0].0, &candles[0].1),
+            candles,
+            window_ends,
+            cache: LlmCache::from_env().await,
+        })
+    }
+
+    /// Scores `base_prompt` over the session's windows. Cached answers are replayed; at most
+    /// `max_fresh` windows without one are asked fresh, and the rest are skipped. Nothing is
+    /// recorded in the prompt history or run manifests.
+    pub async fn score(&self, base_prompt: &str, max_fresh: usize) -> Result<DevScore> {
+        let model = Model::O1Mini;
+        let eth = &self.candles[0].1;
+        let mut answers = Vec::new();
+        let mut uncached = Vec::new();
+        for &i in &self.window_ends {
+            let windows = self
+                .candles
+                .iter()
+                .map(|(symbol, series, anomalies)| {
+                    (*symbol, &series[i - CANDLE_HOURS..i], anomalies.as_slice())
+                })
+                .collect::<Vec<_>>();
+            let (prompt, _) = fit_prompt(base_prompt, &windows, model.prompt_token_budget());
+            assert_no_future_candles(&prompt, eth[i - 1][0]);
+            let cached = self.cache.get(model, &prompt).await;
+            match cached.and_then(|c| parse_model_response(&c, RESPONSE_STRICTNESS).ok()) {
+                Some(prediction) => answers.push((i, prediction, false)),
+                None => uncached.push((i, prompt)),
+            }
+        }
+
+        let skipped = uncached.len().saturating_sub(max_fresh);
+        uncached.truncate(max_fresh);
+        let fresh = futures::future::try_join_all(uncached.iter().map(|(i, prompt)| {
+            ask_model(&self.cache, model, ChatOptions::default(), prompt)
+                .map_ok(move |(p, _)| (*i, p, true))
+        }))
+        .await?;
+        answers.extend(fresh);
+        answers.sort_by_key(|(i, _, _)| *i);
+
+        let mut score = DevScore {
+            prompt_version: prompt_version(base_prompt),
+            skipped,
+            ..Default::default()
+        };
+        let mut trades = Vec::new();
+        for (i, prediction, fresh) in answers {
+            score.scored.push(i);
+            score.correct += (prediction.action == self.labels[i - 1]) as usize;
+            if fresh {
+                score.fresh += 1;
+            } else {
+                score.replayed += 1;
+            }
+            trades.extend(margin_trade(
+                eth,
+                i - 1,
+                prediction.action,
+                prediction.invalidation_price,
+                prediction.target_price,
+                FEE_RATE,
+                &MarginModel::default(),
+            ));
+        }
+        score.total_return = summarize(&trades).total_return;
+        Ok(score)
+    }
+}
+
+/// Builds a prompt from a window and the window's shared data section.
+type PromptFn<'a> = dyn Fn(&[AnnotatedWindow], &str) -> String + Send + Sync + 'a;
+
+/// The upcoming events and per-symbol market context of one window.
+type WindowContext<'a> = (Vec<CalendarEvent>, Vec<(&'a str, AuxContext)>);
+
+/// The calendar and stored market context a backtest shows beside each window.
+struct StoredMarket {
+    calendar: Calendar,
+    /// Liquidations and implied volatility, per symbol.
+    stored: Vec<(Vec<LiquidationBucket>, Vec<IvPoint>)>,
+}
+
+impl StoredMarket {
+    fn load(symbols: &[&str]) -> Result<Self> {
+        let (liquidation_store, iv_store) =
+            (LiquidationStore::default(), ImpliedVolStore::default());
+        let mut stored = Vec::new();
+        for symbol in symbols {
+            stored.push((liquidation_store.load(symbol)?, iv_store.load(symbol)?));
+        }
+        Ok(Self {
+            calendar: load_calendar(),
+            stored,
+        })
+    }
+
+    /// The events and market context live analysis would have seen when the candle opening
+    /// at `last_open` closed, for `symbols` in load order.
+    fn at<'a>(&self, symbols: &[&'a str], last_open: f64) -> Option<WindowContext<'a>> {
+        let decided_at = DateTime::from_timestamp(last_open as i64 + GRANULARITY as i64, 0)?;
+        let context = symbols
+            .iter()
+            .zip(&self.stored)
+            .map(|(symbol, (liquidations, iv))| {
+                let aux = stored_context(liquidations, iv, decided_at.timestamp() as f64);
+                (*symbol, aux)
+            })
+            .filter(|(_, aux)| !aux.is_empty())
+            .collect();
+        Some((self.calendar.upcoming(decided_at), context))
+    }
+}
+
+/// One way of prompting each window in a [`score_in_one_pass`] comparison.
+struct Variant<'a> {
+    prompt: Box<PromptFn<'a>>,
+    chat: ChatOptions,
+    /// How the prompt encodes the candles, and so the levels the model quotes.
+    encoding: CandleEncoding,
+}
+
+/// What one variant scored over a shared pass.
+#[derive(Debug, Default)]
+struct VariantTally {
+    windows: usize,
+    correct: usize,
+    /// Simulated next-candle trades, in entry order.
+    trades: Vec<Trade>,
+    /// Billed for the variant's fresh answers, including those it shared with another.
+    usage: TokenUsage,
+    /// End of the latest window scored and the chart of the variant's signal on it.
+    latest: Option<(usize, String)>,
+}
+
+impl VariantTally {
+    fn accuracy(&self) -> f64 {
+        if self.windows == 0 {
+            0.0
+        } else {
+            self.correct as f64 / self.windows as f64
+        }
+    }
+}
+
+/// Scores every variant over the same backtest windows in a single pass: each window's
+/// candles are sliced and its data section built once, then every variant's request for it
+/// is fanned out. Variants asking the same prompt with the same settings share one request,
+/// rather than racing each other to the same LLM cache miss. Nothing is recorded.
+async fn score_in_one_pass(
+    variants: &[Variant<'_>],
+    options: &BacktestOptions,
+) -> Result<Vec<VariantTally>> {
+    let seed = options.seeded().seed.expect("seeded");
+    let storage = storage::from_env().await?;
+    let cache = LlmCache::from_env().await;
+    let (start, end) = backtest_range();
+
+    let mut series = Vec::new();
+    for symbol in ["ETH", "BTC", "SOL"] {
+        let (candles, anomalies) = load_prepared(storage.as_ref(), symbol, start, end).await?;
+        series.push((symbol, candles, anomalies));
+    }
+    let eth = &series[0].1;
+    let labels = load_labeling(Path::new(LABELING_FILE))?.label("ETH", eth);
+    let len = series.iter().map(|(_, c, _)| c.len()).min().unwrap_or(0);
+    let (window_ends, _) = select_windows(eth.len(), options, seed);
+
+    let cache = &cache;
+    let tasks = window_ends.into_iter().filter(|&i| i <= len).flat_map(|i| {
+        let windows = series
+            .iter()
+            .map(|(symbol, candles, anomalies)| {
+                (*symbol, &candles[i - CANDLE_HOURS..i], anomalies.as_slice())
+            })
+            .collect::<Vec<_>>();
+        let data = build_data_section(&MarketSnapshot::from_windows(&windows));
+
+        // (prompt, chat, variants asking it)
+        let mut asks: Vec<(String, ChatOptions, Vec<usize>)> = Vec::new();
+        for (v, variant) in variants.iter().enumerate() {
+            let prompt = (variant.prompt)(&windows, &data);
+            assert_no_future_candles(&prompt, eth[i - 1][0]);
+            match asks
+                .iter_mut()
+                .find(|(p, chat, _)| *p == prompt && *chat == variant.chat)
+            {
+                Some((_, _, askers)) => askers.push(v),
+                None => asks.push((prompt, variant.chat, vec![v])),
+            }
+        }
+
+        let (eth_window, label) = (&eth[i - CANDLE_HOURS..i], labels[i - 1]);
+        asks.into_iter().map(move |(prompt, chat, askers)| {
+            let query =
+                query_model_and_compare(cache, options.routing, chat, prompt, eth_window, label);
+            async move {
+                let (scored, usage) = metered(query).await;
+                scored.map(|scored| (i, askers, scored, usage))
+            }
+        })
     });
+    let results = futures::stream::iter(tasks).buffer_unordered(MAX_IN_FLIGHT);
+    futures::pin_mut!(results);
+
+    let mut tallies = variants
+        .iter()
+        .map(|_| VariantTally::default())
+        .collect::<Vec<_>>();
+    let (mut requests, mut shared) = (0usize, 0usize);
+    while let Some(res) = results.next().await {
+        let (
+            i,
+            askers,
+            Scored {
+                prediction, label, ..
+            },
+            usage,
+        ) = res?;
+        requests += 1;
+        shared += askers.len() - 1;
+        let window = &eth[i - CANDLE_HOURS..i];
+        for v in askers {
+            let decode = |level: Option<f64>| {
+                level.and_then(|l| decode_level(window, variants[v].encoding, l))
+            };
+            let (stop, target) = (
+                decode(prediction.invalidation_price),
+                decode(prediction.target_price),
+            );
+            let trade = margin_trade(
+                eth,
+                i - 1,
+                prediction.action,
+                stop,
+                target,
+                FEE_RATE,
+                &options.margin,
+            );
+            let tally = &mut tallies[v];
+            if tally.latest.as_ref().is_none_or(|(j, _)| i > *j) {
+                let chart = render_signal_svg(window, prediction.action, stop, target);
+                tally.latest = Some((i, chart));
+            }
+            tally.windows += 1;
+            tally.correct += (prediction.action == label) as usize;
+            tally.trades.extend(trade);
+            tally.usage = tally.usage.plus(&usage);
+        }
+    }
+    for tally in &mut tallies {
+        tally
+            .trades
+            .sort_by(|a, b| a.entry_time.total_cmp(&b.entry_time));
+    }
+    tracing::info!(
+        variants = variants.len(),
+        requests,
+        shared,
+        "Scored variants in one pass"
+    );
+    Ok(tallies)
+}
+
+/// Scores each named prompt over the same backtest windows in one shared pass, fitting each
+/// to the context like [`backtest_current_prompt`]. Best first; nothing is recorded.
+pub async fn compare_prompts(
+    prompts: &[(String, String)],
+    options: &BacktestOptions,
+) -> Result<Vec<PromptScore>> {
+    let token_budget = options.token_budget();
+    let variants = prompts
+        .iter()
+        .map(|(_, base_prompt)| Variant {
+            prompt: Box::new(move |windows, data| {
+                let prompt = join_data_section(base_prompt, data);
+                if estimate_tokens(&prompt) <= token_budget {
+                    prompt
+                } else {
+                    fit_prompt(base_prompt, windows, token_budget).0
+                }
+            }),
+            chat: options.chat,
+            encoding: CandleEncoding::Raw,
+        })
+        .collect::<Vec<_>>();
+    let tallies = score_in_one_pass(&variants, options).await?;
+    let mut scores = prompts
+        .iter()
+        .zip(tallies)
+        .map(|((name, base_prompt), tally)| PromptScore {
+            name: name.clone(),
+            prompt_version: prompt_version(base_prompt),
+            windows: tally.windows,
+            accuracy: tally.accuracy(),
+            total_return: summarize(&tally.trades).total_return,
+        })
+        .collect::<Vec<_>>();
+    rank_scores(&mut scores);
+    Ok(scores)
+}
+
+/// Scores the current prompt over the backtest windows with each [`ablations`] section
+/// removed in turn, and writes the result to `report.html`. The full prompt's answers
+/// are usually already cached from its backtest.
+pub async fn attribute_prompt_sections(options: &BacktestOptions) -> Result<AttributionReport> {
+    let base_prompt = fs::read_to_string(PROMPT_FILE).context("Failed to read base prompt file")?;
+
+    // `None` is the full prompt
+    let sections = std::iter::once(None)
+        .chain(ablations().into_iter().map(Some))
+        .collect::<Vec<_>>();
+    let variants = sections
+        .iter()
+        .map(|&section| {
+            let base_prompt = &base_prompt;
+            Variant {
+                prompt: Box::new(move |windows, data| match section {
+                    Some(section) => ablated_prompt(base_prompt, windows, section),
+                    None => join_data_section(base_prompt, data),
+                }),
+                chat: options.chat,
+                encoding: CandleEncoding::Raw,
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut tallies = score_in_one_pass(&variants, options).await?;
+    let windows = tallies[0].windows;
+    let chart = tallies[0].latest.take().map(|(_, chart)| chart);
+    let accuracies = sections
+        .into_iter()
+        .zip(&tallies)
+        .map(|(section, tally)| {
+            let accuracy = tally.accuracy();
+            tracing::info!(section = ?section, accuracy, "Scored prompt variant");
+            (section, accuracy)
+        })
+        .collect::<Vec<_>>();
+
+    let accuracy = accuracies[0].1;
+    let ablated = accuracies
+        .into_iter()
+        .filter_map(|(section, accuracy)| section.map(|s| (s, accuracy)))
+        .collect::<Vec<_>>();
+    let mut report =
+        AttributionReport::new(prompt_version(&base_prompt), windows, accuracy, &ablated);
+    if let Some(chart) = chart {
+        report = report.with_chart(chart);
+    }
+    fs::write(REPORT_HTML, report.to_html())?;
+    if let Some(sink) = S3Sink::from_env()? {
+        let artifacts = [
+            Artifact::json("attribution.json", &report)?,
+            Artifact::html(REPORT_HTML, report.to_html()),
+        ];
+        sink.upload_run(&new_run_id(), &artifacts).await?;
+    }
+    Ok(report)
+}
+
+/// Re-asks a seeded sample of the profile's backtest windows with each [`perturbations`]
+/// change applied in turn, and measures how often the model still gives the unperturbed
+/// prompt's answer. The unperturbed prompts are built exactly as [`backtest_current_prompt`]
+/// builds them, so their answers usually come from the LLM cache.
+pub async fn perturbation_sensitivity(options: &BacktestOptions) -> Result<SensitivityReport> {
+    let seed = options.seeded().seed.expect("seeded");
+    let storage = storage::from_env().await?;
+    let cache = LlmCache::from_env().await;
+    let (start, end) = backtest_range();
 
-    // Keep only the last 10
-    if history.len() > 10 {
-        let start = history.len() - 10;
-        history = history[start..].to_vec();
+    let profile = &options.profile;
+    let target = profile.symbol.as_str();
+    let context = profile.context_symbols(&LIVE_SYMBOLS);
+    let symbols = std::iter::once(target)
+        .chain(context.iter().map(String::as_str))
+        .collect::<Vec<_>>();
+    let mut series = Vec::new();
+    for symbol in &symbols {
+        series.push(load_prepared(storage.as_ref(), symbol, start, end).await?);
     }
+    let (target_candles, _) = &series[0];
+    let labels = load_labeling(Path::new(LABELING_FILE))?.label(&profile.symbol, target_candles);
+    let base_prompt = fs::read_to_string(&profile.prompt)
+        .with_context(|| format!("Failed to read {}", profile.prompt.display()))?;
+    let market = StoredMarket::load(&symbols)?;
 
-    // Save updated history
-    let json = serde_json::to_string_pretty(&history)?;
-    fs::write(&history_path, json)?;
+    let (mut window_ends, _) = select_windows(target_candles.len(), options, seed);
+    window_ends.retain(|&i| series.iter().all(|(candles, _)| candles.len() >= i));
+    let inputs = window_ends
+        .iter()
+        .filter_map(|&i| {
+            let windows = symbols
+                .iter()
+                .zip(&series)
+                .map(|(symbol, (candles, anomalies))| {
+                    (*symbol, &candles[i - CANDLE_HOURS..i], &anomalies[..])
+                })
+                .collect::<Vec<_>>();
+            let (events, context) = market.at(&symbols, target_candles[i - 1][0])?;
+            Some((i, windows, events, context))
+        })
+        .collect::<Vec<_>>();
+    anyhow::ensure!(!inputs.is_empty(), "No {} windows to perturb", target);
 
-    if !failures.is_empty() {
-        tracing::debug!(?failures);
+    // Shuffled features come from another window of the sample, a seeded offset away
+    let features = inputs
+        .iter()
+        .map(|(_, windows, events, context)| {
+            market_snapshot(windows, target, events, context).features
+        })
+        .collect::<Vec<_>>();
+    let n = inputs.len();
+    let offset = 1 + SeededRng::new(seed).below((n - 1).max(1));
+    let donor = |k: usize| &features[(k + offset) % n];
+
+    // A lone window has no other to take shuffled values from
+    if n < 2 {
+        tracing::warn!("Skipping feature shuffles: only one window to perturb");
+    }
+    let perturbations = perturbations(&FeatureRegistry::standard().names())
+        .into_iter()
+        .filter(|p| match p {
+            Perturbation::Removed(PromptSection::Context(symbol)) => symbols.contains(symbol),
+            Perturbation::FeatureShuffled(_) => n >= 2,
+            _ => true,
+        })
+        .collect::<Vec<_>>();
+    // `None` is the unperturbed prompt
+    let variants = std::iter::once(None)
+        .chain(perturbations.iter().copied().map(Some))
+        .collect::<Vec<_>>();
+    let token_budget = options.token_budget();
+    let (cache, base_prompt, labels) = (&cache, &base_prompt, &labels);
+    let tasks = inputs
+        .iter()
+        .enumerate()
+        .flat_map(|(k, (i, windows, events, context))| {
+            variants.iter().enumerate().map(move |(v, variant)| {
+                let (prompt, _) = fit_prompt_with(windows, token_budget, |w| {
+                    // Features are shown regardless of the profile, or ablating them would
+                    // change nothing the model sees
+                    let snapshot = market_snapshot(w, target, events, context)
+                        .with_encoding(options.profile.encoding)
+                        .with_prompt_features(true);
+                    let snapshot = match variant {
+                        Some(perturbation) => perturbation.apply(snapshot, donor(k)),
+                        None => snapshot,
+                    };
+                    assemble_snapshot_prompt(base_prompt, &snapshot)
+                });
+                let (target_window, label) = (&target_candles[i - CANDLE_HOURS..*i], labels[i - 1]);
+                query_model_and_compare(
+                    cache,
+                    options.routing,
+                    options.chat,
+                    prompt,
+                    target_window,
+                    label,
+                )
+                .map_ok(move |scored| (v, *i, scored.prediction.action, scored.label))
+            })
+        });
+    let results = futures::stream::iter(tasks).buffer_unordered(MAX_IN_FLIGHT);
+    futures::pin_mut!(results);
 
-        // Prepare previous prompts and their scores for improvement prompt
-        let prev_prompts_scores: Vec<(String, f64)> = history
+    let mut answers = vec![Vec::new(); variants.len()];
+    while let Some(res) = results.next().await {
+        let (v, i, action, label) = res?;
+        answers[v].push((i, action, label));
+    }
+    let perturbed = perturbations
+        .into_iter()
+        .zip(answers.drain(1..))
+        .collect::<Vec<_>>();
+    let report = SensitivityReport::new(prompt_version(base_prompt), &answers[0], &perturbed);
+    tracing::info!(
+        windows = report.windows,
+        perturbations = perturbed.len(),
+        "Measured prompt sensitivity"
+    );
+    Ok(report)
+}
+
+/// Accuracy of one symbol's signals in a multi-asset backtest.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AssetScore {
+    pub symbol: String,
+    pub windows: usize,
+    pub accuracy: f64,
+}
+
+/// Backtests the current prompt in multi-asset mode: each window is one o1-mini request
+/// answering for every symbol, and each answer is scored against that symbol's own labels.
+/// Predictions are recorded per symbol under one run id.
+pub async fn backtest_multi_asset(options: &BacktestOptions) -> Result<Vec<AssetScore>> {
+    const SYMBOLS: [&str; 3] = ["ETH", "BTC", "SOL"];
+    let seed = options.seeded().seed.expect("seeded");
+    let storage = storage::from_env().await?;
+    let cache = LlmCache::from_env().await;
+    let (start, end) = backtest_range();
+
+    let mut series = Vec::new();
+    for symbol in SYMBOLS {
+        series.push(load_prepared(storage.as_ref(), symbol, start, end).await?);
+    }
+    let labeling = load_labeling(Path::new(LABELING_FILE))?;
+    let labels = SYMBOLS
+        .iter()
+        .zip(&series)
+        .map(|(symbol, (candles, _))| labeling.label(symbol, candles))
+        .collect::<Vec<_>>();
+    let shortest = series.iter().map(|(c, _)| c.len()).min().unwrap_or(0);
+    if shortest < CANDLE_HOURS {
+        anyhow::bail!("Not enough candles to perform backtesting");
+    }
+    let base_prompt = fs::read_to_string(PROMPT_FILE).context("Failed to read base prompt file")?;
+    let (window_ends, _) = select_windows(shortest, options, seed);
+
+    let tasks = window_ends.iter().map(|&i| {
+        let windows = SYMBOLS
             .iter()
-            .map(|r| (r.prompt.clone(), r.score))
+            .zip(&series)
+            .map(|(symbol, (candles, anomalies))| {
+                (*symbol, &candles[i - CANDLE_HOURS..i], &anomalies[..])
+            })
+            .collect::<Vec<_>>();
+        let prompt = assemble_multi_asset_prompt(&base_prompt, &windows);
+        assert_no_future_candles(&prompt, series[0].0[i - 1][0]);
+        ask_multi_asset(&cache, Model::O1Mini, prompt.clone(), &SYMBOLS)
+            .map_ok(move |p| (i, prompt, p))
+    });
+    let mut results = futures::stream::iter(tasks).buffer_unordered(MAX_IN_FLIGHT);
+
+    let run_id = new_run_id();
+    let version = prompt_version(&base_prompt);
+    let audit = AuditStore::default();
+    let mut correct = [0usize; SYMBOLS.len()];
+    let mut predictions = Vec::new();
+    while let Some(res) = results.next().await {
+        let (i, prompt, answers) = res?;
+        audit.put_prompt(&prompt)?;
+        for (k, (symbol, p)) in answers.into_iter().enumerate() {
+            let label = labels[k][i - 1];
+            correct[k] += (p.action == label) as usize;
+            let metadata = WindowMetadata::new(
+                &symbol,
+                &series[k].0[i - CANDLE_HOURS..i],
+                &version,
+                Model::O1Mini.as_str(),
+                ChatOptions::default(),
+                &prompt,
+            );
+            let windows = series
+                .iter()
+                .map(|(candles, _)| &candles[i - CANDLE_HOURS..i])
+                .collect::<Vec<_>>();
+            let claims = check_claims(
+                &p.rationale,
+                &windows,
+                &[p.invalidation_price, p.target_price],
+            );
+            predictions.push(PredictionRecord {
+                run_id: run_id.clone(),
+                source: "backtest".to_string(),
+                symbol,
+                model: Model::O1Mini.as_str().to_string(),
+                prompt_hash: version.clone(),
+                window_end: series[k].0[i - 1][0],
+                action: p.action,
+                label: Some(label),
+                correct: Some(p.action == label),
+                rationale: p.rationale,
+                key_factors: p.key_factors,
+                invalidation_price: p.invalidation_price,
+                target_price: p.target_price,
+                confidence: p.confidence,
+                level_outcome: None,
+                latency_ms: None,
+                metadata: Some(metadata),
+                numeric_claims: Some(claims),
+            });
+        }
+    }
+    storage.append_predictions(&predictions).await?;
+
+    let windows = window_ends.len();
+    let scores = SYMBOLS
+        .iter()
+        .zip(correct)
+        .map(|(symbol, correct)| AssetScore {
+            symbol: symbol.to_string(),
+            windows,
+            accuracy: if windows > 0 {
+                correct as f64 / windows as f64
+            } else {
+                0.0
+            },
+        })
+        .collect::<Vec<_>>();
+    tracing::info!(?scores, "Multi-asset backtest complete");
+    Ok(scores)
+}
+
+/// Backtests the current prompt in allocation mode: every hour o1-mini sets target
+/// weights across the symbols and cash, and the portfolio is rebalanced to them and held
+/// until the next close.
+pub async fn backtest_allocation() -> Result<PortfolioSummary> {
+    const SYMBOLS: [&str; 3] = ["ETH", "BTC", "SOL"];
+    let storage = storage::from_env().await?;
+    let cache = LlmCache::from_env().await;
+    let (start, end) = backtest_range();
+
+    let mut series = Vec::new();
+    for symbol in SYMBOLS {
+        series.push(load_prepared(storage.as_ref(), symbol, start, end).await?);
+    }
+    let shortest = series.iter().map(|(c, _)| c.len()).min().unwrap_or(0);
+    if shortest <= CANDLE_HOURS {
+        anyhow::bail!("Not enough candles to perform backtesting");
+    }
+    let base_prompt = fs::read_to_string(PROMPT_FILE).context("Failed to read base prompt file")?;
+
+    // Consecutive windows, so each allocation is held until the next one; the last window
+    // needs a following candle to be scored
+    let window_ends = (CANDLE_HOURS..shortest).collect::<Vec<_>>();
+    let tasks = window_ends.iter().map(|&i| {
+        let windows = SYMBOLS
+            .iter()
+            .zip(&series)
+            .map(|(symbol, (candles, anomalies))| {
+                (*symbol, &candles[i - CANDLE_HOURS..i], &anomalies[..])
+            })
+            .collect::<Vec<_>>();
+        let prompt = assemble_allocation_prompt(&base_prompt, &windows);
+        assert_no_future_candles(&prompt, series[0].0[i - 1][0]);
+        ask_allocation(&cache, Model::O1Mini, prompt, &SYMBOLS)
+    });
+    // `buffered` keeps the hourly order the rebalancing depends on
+    let allocations = futures::stream::iter(tasks)
+        .buffered(MAX_IN_FLIGHT)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut steps = Vec::new();
+    for (&i, allocation) in window_ends.iter().zip(allocations) {
+        let allocation = allocation?;
+        let returns = series
+            .iter()
+            .map(|(candles, _)| candles[i][CLOSE] / candles[i - 1][CLOSE] - 1.0)
             .collect();
+        steps.push((
+            allocation.weights.iter().map(|(_, w)| *w).collect(),
+            returns,
+        ));
+    }
+    let summary = rebalance_portfolio(&steps, FEE_RATE);
+    tracing::info!(?summary, "Allocation backtest complete");
+    Ok(summary)
+}
 
-        let improvement_prompt =
-            build_improvement_prompt(&base_prompt, &failures, &prev_prompts_scores);
-        let improved_prompt = analyze_data_gpt(&improvement_prompt, Model::O1Preview).await?;
-        fs::write(PROMPT_FILE, improved_prompt)?;
-        tracing::info!("Prompt improved and saved to {}", PROMPT_FILE);
+/// Result of a pair-mode backtest.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PairReport {
+    pub pair: String,
+    pub windows: usize,
+    pub accuracy: f64,
+    pub simulation: SimulationSummary,
+}
+
+/// Backtests the current prompt in pair mode: o1-mini calls ETH against BTC for the next
+/// candle, scored on [`label_pair`] and traded as a spread.
+pub async fn backtest_pair(options: &BacktestOptions) -> Result<PairReport> {
+    let seed = options.seeded().seed.expect("seeded");
+    let storage = storage::from_env().await?;
+    let cache = LlmCache::from_env().await;
+    let (start, end) = backtest_range();
+
+    let (eth_candles, eth_anomalies) = load_prepared(storage.as_ref(), "ETH", start, end).await?;
+    let (btc_candles, btc_anomalies) = load_prepared(storage.as_ref(), "BTC", start, end).await?;
+    let (sol_candles, sol_anomalies) = load_prepared(storage.as_ref(), "SOL", start, end).await?;
+    let labels = label_pair(&eth_candles, &btc_candles);
+    let base_prompt = fs::read_to_string(PROMPT_FILE).context("Failed to read base prompt file")?;
+
+    let (window_ends, _) = select_windows(labels.len(), options, seed);
+    let tasks = window_ends
+        .iter()
+        .filter(|&&i| sol_candles.len() >= i)
+        .map(|&i| {
+            let windows = [
+                ("ETH", &eth_candles[i - CANDLE_HOURS..i], &eth_anomalies[..]),
+                ("BTC", &btc_candles[i - CANDLE_HOURS..i], &btc_anomalies[..]),
+                ("SOL", &sol_candles[i - CANDLE_HOURS..i], &sol_anomalies[..]),
+            ];
+            let prompt = assemble_pair_prompt(&base_prompt, &windows, "ETH", "BTC");
+            assert_no_future_candles(&prompt, eth_candles[i - 1][0]);
+            let cache = &cache;
+            async move {
+                let (prediction, _) =
+                    ask_model(cache, Model::O1Mini, ChatOptions::default(), &prompt).await?;
+                anyhow::Ok((i, prompt, prediction))
+            }
+        });
+    let mut results = futures::stream::iter(tasks).buffer_unordered(MAX_IN_FLIGHT);
+
+    let run_id = new_run_id();
+    let version = prompt_version(&base_prompt);
+    let audit = AuditStore::default();
+    let mut correct = 0usize;
+    let mut predictions = Vec::new();
+    let mut trades = Vec::new();
+    while let Some(res) = results.next().await {
+        let (i, prompt, p) = res?;
+        audit.put_prompt(&prompt)?;
+        let label = labels[i - 1];
+        let windows = [&eth_candles, &btc_candles, &sol_candles].map(|c| &c[i - CANDLE_HOURS..i]);
+        let claims = check_claims(&p.rationale, &windows, &[]);
+        correct += (p.action == label) as usize;
+        trades.extend(pair_trade(
+            &eth_candles,
+            &btc_candles,
+            i - 1,
+            p.action,
+            FEE_RATE,
+        ));
+        predictions.push(PredictionRecord {
+            run_id: run_id.clone(),
+            source: "backtest".to_string(),
+            symbol: "ETH/BTC".to_string(),
+            model: Model::O1Mini.as_str().to_string(),
+            prompt_hash: version.clone(),
+            window_end: eth_candles[i - 1][0],
+            action: p.action,
+            label: Some(label),
+            correct: Some(p.action == label),
+            rationale: p.rationale,
+            key_factors: p.key_factors,
+            invalidation_price: None,
+            target_price: None,
+            confidence: p.confidence,
+            level_outcome: None,
+            latency_ms: None,
+            metadata: Some(WindowMetadata::new(
+                "ETH/BTC",
+                &eth_candles[i - CANDLE_HOURS..i],
+                &version,
+                Model::O1Mini.as_str(),
+                ChatOptions::default(),
+                &prompt,
+            )),
+            numeric_claims: Some(claims),
+        });
     }
+    storage.append_predictions(&predictions).await?;
 
-    Ok(accuracy)
+    trades.sort_by(|a, b| a.entry_time.total_cmp(&b.entry_time));
+    let windows = predictions.len();
+    let report = PairReport {
+        pair: "ETH/BTC".to_string(),
+        windows,
+        accuracy: if windows > 0 {
+            correct as f64 / windows as f64
+        } else {
+            0.0
+        },
+        simulation: summarize(&trades),
+    };
+    tracing::info!(?report, "Pair backtest complete");
+    Ok(report)
+}
+
+/// Re-asks when the improver returns a prompt that fails linting, before giving up on the
+/// iteration.
+const LINT_REASKS: usize = 2;
+/// Re-asks when the improver returns a prompt that nearly repeats one already tried, each
+/// calling out every repeat so far, before giving up on the iteration.
+const DUPLICATE_REASKS: usize = 3;
+
+/// Asks the model for a prompt that fixes the run's failures. `None` when there were none,
+/// or when every answer failed linting or repeated a prompt already tried.
+pub async fn improve_prompt(run: &BacktestRun, postmortems: bool) -> Result<Option<String>> {
+    if run.failures.is_empty() {
+        return Ok(None);
+    }
+    tracing::debug!(failures = ?run.failures);
+
+    // Analyses stored by `explain`, newest first, of this prompt's failures
+    let summaries = if postmortems {
+        let version = prompt_version(&run.prompt);
+        AnalyticsStore::default()
+            .failure_analyses()?
+            .into_iter()
+            .rev()
+            .filter(|a| a.prompt_hash == version)
+            .take(IMPROVER_POSTMORTEMS)
+            .map(|a| a.summary)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    // Prepare previous prompts and their scores for improvement prompt
+    let prev_prompts_scores: Vec<(String, f64)> = run
+        .history
+        .iter()
+        .map(|r| (r.prompt.clone(), r.score))
+        .collect();
+
+    // Each pass targets the most common kind of mistake
+    let focus = run.confusion.focus();
+    tracing::info!(?focus, confusion = ?run.confusion.counts, "Improvement pass focus");
+    let improvement_prompt = build_improvement_prompt(
+        &run.symbols,
+        &run.prompt,
+        &run.failures,
+        &prev_prompts_scores,
+        &summaries,
+        &run.hallucinations[..run.hallucinations.len().min(IMPROVER_HALLUCINATIONS)],
+        focus,
+    );
+    let mut improved_prompt =
+        analyze_data_gpt(&improvement_prompt, Model::O1Preview, CallKind::Improvement).await?;
+
+    // A near-copy of a prompt already tried would only repeat its score
+    let tried = || {
+        std::iter::once(run.prompt.as_str()).chain(run.history.iter().map(|r| r.prompt.as_str()))
+    };
+    let mut lint_reasks = 0;
+    // (answer, the prompt it repeated, that prompt's score)
+    let mut duplicates = Vec::new();
+    loop {
+        let violations = lint_prompt(&improved_prompt, run.label_thresholds);
+        let reask = if !violations.is_empty() {
+            tracing::warn!(lint_reasks, ?violations, "Improved prompt failed linting");
+            if lint_reasks == LINT_REASKS {
+                tracing::warn!("Skipping evaluation: the improver returned no prompt that lints");
+                return Ok(None);
+            }
+            lint_reasks += 1;
+            build_lint_feedback(&improvement_prompt, &improved_prompt, &violations)
+        } else if let Some(duplicate) = find_duplicate(&improved_prompt, tried()) {
+            tracing::warn!(
+                duplicates = duplicates.len(),
+                similarity = duplicate.similarity,
+                exact = duplicate.exact,
+                "Improved prompt repeats one already tried"
+            );
+            if duplicates.len() == DUPLICATE_REASKS {
+                tracing::warn!(
+                    "Skipping evaluation: the improver only returned prompts already tried"
+                );
+                return Ok(None);
+            }
+            let repeated = tried().nth(duplicate.index).unwrap_or_default();
+            let score = match duplicate.index {
+                0 => run.accuracy,
+                i => run.history[i - 1].score,
+            };
+            duplicates.push((improved_prompt, repeated, score));
+            build_duplicate_feedback(&improvement_prompt, &duplicates)
+        } else {
+            return Ok(Some(improved_prompt));
+        };
+        improved_prompt = analyze_data_gpt(&reask, Model::O1Preview, CallKind::Improvement).await?;
+    }
+}
+
+/// Asks o1-preview why a recorded backtest window was answered wrongly and stores its
+/// analysis. The window is the latest recorded ETH failure, narrowed to `run_id` and the
+/// window whose last candle opens at `window_end` when given. Its prompt is rebuilt from the
+/// run's manifest and stored candles, so it matches what the model saw.
+pub async fn explain_failure(
+    run_id: Option<&str>,
+    window_end: Option<f64>,
+) -> Result<FailureAnalysis> {
+    let store = AnalyticsStore::default();
+    let failure = store
+        .predictions()?
+        .into_iter()
+        .rev()
+        .filter(|p| p.source == "backtest" && p.symbol == "ETH" && p.correct == Some(false))
+        .filter(|p| run_id.is_none_or(|id| p.run_id == id))
+        .find(|p| window_end.is_none_or(|t| p.window_end == t))
+        .context("No recorded backtest failure matches")?;
+
+    let storage = storage::from_env().await?;
+    let manifest = storage
+        .manifests()
+        .await?
+        .into_iter()
+        .find(|m| m.run_id == failure.run_id)
+        .with_context(|| format!("No manifest for run {}", failure.run_id))?;
+    let base_prompt = match manifest.prompt {
+        Some(prompt) => prompt,
+        // Manifests from before prompts were recorded: only the current prompt can be used
+        None => fs::read_to_string(PROMPT_FILE)
+            .ok()
+            .filter(|p| prompt_version(p) == failure.prompt_hash)
+            .context("The run's prompt was not recorded and prompt.txt has changed since")?,
+    };
+
+    let mut series = Vec::new();
+    for symbol in ["ETH", "BTC", "SOL"] {
+        let (candles, anomalies) = load_prepared(
+            storage.as_ref(),
+            symbol,
+            manifest.data_start,
+            manifest.data_end,
+        )
+        .await?;
+        series.push((symbol, candles, anomalies));
+    }
+    let eth = &series[0].1;
+    let i = eth
+        .iter()
+        .position(|c| c[0] == failure.window_end)
+        .map(|last| last + 1)
+        .filter(|&i| i >= CANDLE_HOURS && i < eth.len())
+        .context("The window's candles are no longer stored")?;
+    let windows = series
+        .iter()
+        .map(|(symbol, candles, anomalies)| {
+            (*symbol, &candles[i - CANDLE_HOURS..i], &anomalies[..])
+        })
+        .collect::<Vec<_>>();
+    // Backtests size prompts for o1-mini, the smallest context of any model they route to
+    let (prompt, _) = fit_prompt(&base_prompt, &windows, Model::O1Mini.prompt_token_budget());
+    assert_no_future_candles(&prompt, failure.window_end);
+
+    // Labeled with the thresholds the run used when the failure predates recorded labels
+    let (long, short) = manifest
+        .label_thresholds
+        .unwrap_or((LONG_THRESHOLD, SHORT_THRESHOLD));
+    let label = failure
+        .label
+        .unwrap_or_else(|| label_candles_with(eth, long, short)[i - 1]);
+    let explanation_prompt =
+        build_explanation_prompt(&prompt, failure.action, &failure.rationale, label, &eth[i]);
+    let analysis =
+        analyze_data_gpt(&explanation_prompt, Model::O1Preview, CallKind::Improvement).await?;
+    let record = FailureAnalysis {
+        run_id: failure.run_id,
+        prompt_hash: failure.prompt_hash,
+        symbol: failure.symbol,
+        window_end: failure.window_end,
+        action: failure.action,
+        label,
+        model: Model::O1Preview.as_str().to_string(),
+        summary: summary_of(&analysis),
+        analysis,
+    };
+    store.append_failure_analyses(std::slice::from_ref(&record))?;
+    Ok(record)
+}
+
+/// Model grading rationales; cheap, since every graded window costs one call.
+const RATIONALE_GRADER: Model = Model::O1Mini;
+
+/// Grades the rationales of a seeded sample of `sample` recorded backtest predictions not
+/// graded before, optionally only those of `run_id`, against the exact prompts they
+/// answered. Predictions recorded before prompts were audited are skipped. Returns the
+/// rationale quality of every prompt version graded so far.
+pub async fn grade_rationales(
+    run_id: Option<&str>,
+    sample: usize,
+    seed: u64,
+) -> Result<Vec<RationaleQuality>> {
+    let store = AnalyticsStore::default();
+    let graded = store
+        .rationale_grades()?
+        .into_iter()
+        .map(|g| (g.run_id, g.symbol, g.window_end.to_bits()))
+        .collect::<std::collections::HashSet<_>>();
+    let audit = AuditStore::default();
+    let candidates = store
+        .predictions()?
+        .into_iter()
+        .filter(|p| p.source == "backtest" && !p.rationale.is_empty())
+        .filter(|p| run_id.is_none_or(|id| p.run_id == id))
+        .filter(|p| p.metadata.is_some())
+        .filter(|p| !graded.contains(&(p.run_id.clone(), p.symbol.clone(), p.window_end.to_bits())))
+        .collect::<Vec<_>>();
+    let mut picked = Vec::new();
+    for k in SeededRng::new(seed).sample_indices(candidates.len(), sample) {
+        let p = &candidates[k];
+        let sha = &p.metadata.as_ref().expect("filtered").prompt_sha;
+        match audit.prompt(sha)? {
+            Some(prompt) => picked.push((p, prompt)),
+            None => {
+                tracing::warn!(run_id = %p.run_id, window_end = p.window_end, "Audited prompt is missing")
+            }
+        }
+    }
+
+    let tasks = picked.iter().map(|(p, prompt)| {
+        let grading_prompt = build_grading_prompt(prompt, p.action, &p.rationale, &p.key_factors);
+        async move {
+            let response =
+                analyze_data_gpt(&grading_prompt, RATIONALE_GRADER, CallKind::Backtest).await?;
+            anyhow::Ok((p, parse_grade(&response)))
+        }
+    });
+    let mut results = futures::stream::iter(tasks).buffer_unordered(MAX_IN_FLIGHT);
+    let mut grades = Vec::new();
+    while let Some(res) = results.next().await {
+        let (p, scores) = res?;
+        match scores {
+            Ok(scores) => grades.push(RationaleGrade {
+                run_id: p.run_id.clone(),
+                prompt_hash: p.prompt_hash.clone(),
+                symbol: p.symbol.clone(),
+                window_end: p.window_end,
+                action: p.action,
+                correct: p.correct,
+                model: RATIONALE_GRADER.as_str().to_string(),
+                scores,
+            }),
+            Err(err) => tracing::warn!(
+                run_id = %p.run_id,
+                window_end = p.window_end,
+                kind = err.kind(),
+                "Unparseable rationale grade"
+            ),
+        }
+    }
+    store.append_rationale_grades(&grades)?;
+    tracing::info!(graded = grades.len(), "Graded rationales");
+    Ok(rationale_quality(&store.rationale_grades()?))
+}
+
+/// Evaluates the current prompt over a stored range of any length without loading it into
+/// memory: windows are streamed from the candle store (fill it with `fetch` first), prompts
+/// are built only for the windows in flight and metrics are aggregated incrementally.
+/// Unlike [`run_backtest_and_improve`] the prompt is not changed and candles are used as
+/// stored, without anomaly correction.
+pub async fn evaluate_stored_range(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    stride: usize,
+) -> Result<RunningMetrics> {
+    let base_prompt = fs::read_to_string(PROMPT_FILE).context("Failed to read base prompt file")?;
+    let profile = SymbolProfile::default();
+    evaluate_range(&profile, &base_prompt, start, end, stride, "backtest").await
+}
+
+/// Fetches any candles of each scenario the store lacks, then scores the current prompt on
+/// every window whose next candle falls within the scenario. Predictions are recorded with
+/// source `"stress"`.
+pub async fn stress_test(scenarios: &[&Scenario]) -> Result<Vec<ScenarioResult>> {
+    let base_prompt = fs::read_to_string(PROMPT_FILE).context("Failed to read base prompt file")?;
+    let mut results = Vec::new();
+    for scenario in scenarios {
+        let (start, end) = scenario.range();
+        let metrics = fetch_and_evaluate(
+            &SymbolProfile::default(),
+            &base_prompt,
+            start,
+            end,
+            "stress",
+        )
+        .await
+        .with_context(|| format!("Failed to stress-test {}", scenario.name))?;
+        let simulation = metrics.simulation();
+        let result = ScenarioResult {
+            name: scenario.name.to_string(),
+            kind: scenario.kind,
+            windows: metrics.windows,
+            accuracy: metrics.accuracy(),
+            baseline_accuracy: metrics.baseline_accuracy(),
+            total_return: simulation.total_return,
+            max_drawdown: simulation.max_drawdown,
+            liquidations: simulation.liquidations,
+        };
+        tracing::info!(?result, "Stress-tested scenario");
+        results.push(result);
+    }
+    Ok(results)
+}
+
+/// Scores every queued out-of-sample job whose period has closed on its profile's symbols,
+/// fetching any candles the store lacks, and records each accuracy next to the prompt's
+/// in-sample score in that symbol's prompt history. Predictions are recorded with source
+/// `"out_of_sample"`; jobs still waiting for data stay queued.
+pub async fn run_due_out_of_sample() -> Result<Vec<OutOfSampleResult>> {
+    let queue = OutOfSampleQueue::default();
+    let storage = storage::from_env().await?;
+    let mut results = Vec::new();
+    for job in queue.due(Utc::now())? {
+        let metrics = fetch_and_evaluate(
+            &job.profile,
+            &job.prompt,
+            job.start,
+            job.end,
+            "out_of_sample",
+        )
+        .await?;
+        let result = OutOfSampleResult::new(&job, metrics.windows, metrics.accuracy());
+        storage
+            .record_out_of_sample(
+                &job.profile.symbol,
+                &job.prompt,
+                job.in_sample,
+                result.out_of_sample,
+            )
+            .await?;
+        queue.complete(&job)?;
+        tracing::info!(?result, "Evaluated prompt out of sample");
+        results.push(result);
+    }
+    Ok(results)
+}
+
+/// Fetches any candles of `[start, end)` the store lacks, then scores `base_prompt` on every
+/// window of the profile's symbols whose next candle falls within it.
+async fn fetch_and_evaluate(
+    profile: &SymbolProfile,
+    base_prompt: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    source: &str,
+) -> Result<RunningMetrics> {
+    let store = CandleStore::default();
+    // The first window is the one just before `start`, so its label is `start`'s candle
+    let from = start - Duration::hours(CANDLE_HOURS as i64);
+    for symbol in profile_symbols(profile) {
+        store
+            .fetch_history(&symbol, GRANULARITY, from, end)
+            .await
+            .with_context(|| format!("Failed to fetch {}", symbol))?;
+    }
+    evaluate_range(profile, base_prompt, from, end, 1, source).await
+}
+
+/// The profile's target followed by its context symbols.
+fn profile_symbols(profile: &SymbolProfile) -> Vec<String> {
+    std::iter::once(profile.symbol.clone())
+        .chain(profile.context_symbols(&LIVE_SYMBOLS))
+        .collect()
+}
+
+async fn evaluate_range(
+    profile: &SymbolProfile,
+    base_prompt: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    stride: usize,
+    source: &str,
+) -> Result<RunningMetrics> {
+    let storage = storage::from_env().await?;
+    let cache = LlmCache::from_env().await;
+    let run_id = new_run_id();
+    let version = prompt_version(base_prompt);
+    let target = profile.symbol.as_str();
+    let thresholds = load_labeling(Path::new(LABELING_FILE))?.thresholds(target);
+    let symbols = profile_symbols(profile);
+    let symbols = symbols.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let windows = stream_windows(
+        &CandleStore::default(),
+        &symbols,
+        GRANULARITY,
+        start,
+        end,
+        CANDLE_HOURS,
+        stride,
+    )?;
+    // `buffered` keeps results in window order, which the running drawdown relies on
+    let results = futures::stream::iter(windows)
+        .map(|window| {
+            let (cache, symbols) = (&cache, &symbols);
+            async move {
+                let window = window?;
+                let named = symbols
+                    .iter()
+                    .zip(&window.series)
+                    .map(|(symbol, candles)| (*symbol, &candles[..], &[][..]))
+                    .collect::<Vec<_>>();
+                let (full_prompt, truncation) =
+                    fit_prompt(base_prompt, &named, Model::O1Mini.prompt_token_budget());
+                let target_window = &window.series[0];
+                if let Some(truncation) = truncation {
+                    tracing::debug!(?truncation, "Down-sampled window to fit the context");
+                }
+                assert_no_future_candles(&full_prompt, window.next[0] - GRANULARITY as f64);
+                let baseline = vwap_reversion(target_window, VWAP_REVERSION_BAND);
+                let scored = query_model_and_compare(
+                    cache,
+                    None,
+                    ChatOptions::default(),
+                    full_prompt,
+                    target_window,
+                    window.label(thresholds),
+                )
+                .await?;
+                Ok::<_, anyhow::Error>((window, baseline, scored))
+            }
+        })
+        .buffered(MAX_IN_FLIGHT);
+    futures::pin_mut!(results);
+
+    let timer = Instant::now();
+    let audit = AuditStore::default();
+    let mut metrics = RunningMetrics::default();
+    let mut pending = Vec::new();
+    while let Some(res) = results.next().await {
+        let (
+            window,
+            baseline,
+            Scored {
+                prediction:
+                    Prediction {
+                        action: pred,
+                        rationale,
+                        key_factors,
+                        invalidation_price,
+                        target_price,
+                        confidence,
+                    },
+                label,
+                latency,
+                prompt,
+                ..
+            },
+        ) = res?;
+        audit.put_prompt(&prompt)?;
+        metrics.record(pred, baseline, label);
+        if let Some(latency) = latency {
+            metrics.latency.record(latency);
+        }
+        let outcome = level_outcome(
+            pred,
+            window.last()[CLOSE],
+            invalidation_price,
+            target_price,
+            &window.next,
+        );
+        metrics
+            .calibration
+            .record(pred == label, confidence, outcome);
+        if let Some(trade) = margin_trade(
+            &window.trade_candles(),
+            0,
+            pred,
+            invalidation_price,
+            target_price,
+            FEE_RATE,
+            &MarginModel::default(),
+        ) {
+            metrics.record_trade(&trade);
+        }
+        let windows = window.series.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let claims = check_claims(&rationale, &windows, &[invalidation_price, target_price]);
+
+        pending.push(PredictionRecord {
+            run_id: run_id.clone(),
+            source: source.to_string(),
+            symbol: target.to_string(),
+            model: Model::O1Mini.as_str().to_string(),
+            prompt_hash: version.clone(),
+            window_end: window.last()[0],
+            action: pred,
+            label: Some(label),
+            correct: Some(pred == label),
+            rationale,
+            key_factors,
+            invalidation_price,
+            target_price,
+            confidence,
+            level_outcome: outcome,
+            latency_ms: latency.map(|l| l.as_secs_f64() * 1e3),
+            metadata: Some(WindowMetadata::new(
+                target,
+                &window.series[0],
+                &version,
+                Model::O1Mini.as_str(),
+                ChatOptions::default(),
+                &prompt,
+            )),
+            numeric_claims: Some(claims),
+        });
+        if pending.len() >= PREDICTION_FLUSH {
+            storage.append_predictions(&pending).await?;
+            pending.clear();
+        }
+    }
+    storage.append_predictions(&pending).await?;
+    metrics.elapsed = timer.elapsed();
+    metrics.effective_samples = effective_samples(metrics.windows, stride, CANDLE_HOURS);
+
+    tracing::info!(
+        windows = metrics.windows,
+        accuracy = metrics.accuracy(),
+        baseline_accuracy = metrics.baseline_accuracy(),
+        simulation = ?metrics.simulation(),
+        calibration = ?metrics.calibration.report(),
+        latency = ?metrics.latency.summary(),
+        throughput = metrics.throughput(),
+        effective_samples = metrics.effective_samples,
+        "Streaming evaluation complete"
+    );
+    Ok(metrics)
+}
+
+async fn load_prepared(
+    storage: &dyn Storage,
+    symbol: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<(Vec<[f64; 6]>, Vec<CandleAnomaly>)> {
+    Ok(prepare_candles(
+        symbol,
+        load_or_fetch(storage, symbol, GRANULARITY, start, end).await?,
+    ))
 }
 
 async fn load_or_fetch(
+    storage: &dyn Storage,
     symbol: &str,
+    granularity: u32,
     start: DateTime<Utc>,
     end: DateTime<Utc>,
-) -> Result<Vec<CoinbaseCandle>> {
-    let cache_file = format!("{}/{}_data.json", CACHE_DIR, symbol);
-    if Path::new(&cache_file).exists() {
-        let data = fs::read_to_string(&cache_file)?;
-        let candles: Vec<CoinbaseCandle> =
-            serde_json::from_str(&data).context("Failed to deserialize cached candle data")?;
-        Ok(candles)
-    } else {
-        let candles = get_candle_data(symbol, start, end).await?;
-        // Serialize and store them in the cache file for next time
-        let json = serde_json::to_string(&candles)?;
-        fs::write(&cache_file, json)?;
-        Ok(candles)
+) -> Result<Vec<[f64; 6]>> {
+    let candles = storage
+        .load_candles(symbol, granularity, start, end)
+        .await?;
+    if covers(&candles, granularity, start, end) {
+        return Ok(candles);
+    }
+
+    let fetched = fetch_range(symbol, granularity, start, end).await?;
+    storage.save_candles(symbol, granularity, &fetched).await?;
+    storage.load_candles(symbol, granularity, start, end).await
+}
+
+/// Verifies recorded live predictions against the candle that followed each of them and
+/// reports target and confidence calibration. Predictions whose next candle has not closed
+/// yet are skipped. Reads the local analytics tables.
+pub async fn live_calibration() -> Result<CalibrationReport> {
+    let mut calibration = CalibrationTracker::default();
+    let labeling = load_labeling(Path::new(LABELING_FILE))?;
+    for (p, [last, next]) in verified_live_predictions(&["live"]).await? {
+        let label = labeling.label(&p.symbol, &[last, next])[0];
+        let outcome = level_outcome(
+            p.action,
+            last[CLOSE],
+            p.invalidation_price,
+            p.target_price,
+            &next,
+        );
+        calibration.record(p.action == label, p.confidence, outcome);
+    }
+    Ok(calibration.report())
+}
+
+/// Re-scores the predictions in a JSONL file, such as the analytics predictions table,
+/// under each of `configs` against `symbol`'s candles, fetching only those not stored.
+pub async fn rescore_predictions(
+    path: &Path,
+    symbol: &str,
+    configs: &[RescoreConfig],
+) -> Result<Vec<RescoreReport>> {
+    let horizon = configs.iter().map(|c| c.horizon()).max().unwrap_or(1);
+    let (predictions, candles) = predictions_with_candles(path, symbol, horizon).await?;
+    Ok(configs
+        .iter()
+        .map(|c| rescore(&predictions, &candles, c))
+        .collect())
+}
+
+/// `symbol`'s candles over the predictions in a JSONL file with each candle's predicted
+/// action, laid out for `format`.
+pub async fn export_research(path: &Path, symbol: &str, format: ResearchFormat) -> Result<String> {
+    let (predictions, candles) = predictions_with_candles(path, symbol, 1).await?;
+    let signals = align_signals(&predictions, &candles);
+    Ok(research_csv(format, &candles, &signals))
+}
+
+/// Scores another strategy's signals, read from a CSV by [`parse_signals_csv`], next to
+/// the recorded predictions in a JSONL file under each of `configs`. Both are cut to the
+/// span they share and scored on the same candles. Returns the predictions' reports, then
+/// the signals'.
+pub async fn benchmark_signals(
+    predictions: &Path,
+    signals: &Path,
+    symbol: &str,
+    configs: &[RescoreConfig],
+) -> Result<(Vec<RescoreReport>, Vec<RescoreReport>)> {
+    let predictions = symbol_predictions(predictions, symbol)?;
+    let csv = fs::read_to_string(signals)
+        .with_context(|| format!("Failed to read {}", signals.display()))?;
+    let signals = parse_signals_csv(&csv)?;
+    let span = |rows: &[RecordedPrediction]| {
+        let times = rows.iter().map(|p| p.window_end);
+        (
+            times.clone().min_by(f64::total_cmp),
+            times.max_by(f64::total_cmp),
+        )
+    };
+    let ((Some(a0), Some(a1)), (Some(b0), Some(b1))) = (span(&predictions), span(&signals)) else {
+        anyhow::bail!(
+            "Need both recorded {} predictions and signals to compare",
+            symbol
+        );
+    };
+    let (first, last) = (a0.max(b0), a1.min(b1));
+    if first > last {
+        anyhow::bail!("The predictions and signals cover no common period");
+    }
+    let within = |rows: Vec<RecordedPrediction>| {
+        rows.into_iter()
+            .filter(|p| (first..=last).contains(&p.window_end))
+            .collect::<Vec<_>>()
+    };
+    let (predictions, signals) = (within(predictions), within(signals));
+
+    let horizon = configs.iter().map(|c| c.horizon()).max().unwrap_or(1);
+    let candles = candles_for(&predictions, symbol, horizon).await?;
+    let score = |rows: &[RecordedPrediction]| {
+        configs
+            .iter()
+            .map(|c| rescore(rows, &candles, c))
+            .collect::<Vec<_>>()
+    };
+    Ok((score(&predictions), score(&signals)))
+}
+
+/// The predictions in a JSONL file for `symbol`, with its candles from the first window
+/// to `horizon` candles past the last.
+async fn predictions_with_candles(
+    path: &Path,
+    symbol: &str,
+    horizon: usize,
+) -> Result<(Vec<RecordedPrediction>, Vec<[f64; 6]>)> {
+    let predictions = symbol_predictions(path, symbol)?;
+    let candles = candles_for(&predictions, symbol, horizon).await?;
+    Ok((predictions, candles))
+}
+
+/// The predictions in a JSONL file for `symbol`. Rows of other symbols are skipped; rows
+/// without one are taken to be `symbol`'s.
+fn symbol_predictions(path: &Path, symbol: &str) -> Result<Vec<RecordedPrediction>> {
+    Ok(load_predictions(path)?
+        .into_iter()
+        .filter(|p| p.symbol.is_empty() || p.symbol == symbol)
+        .collect())
+}
+
+/// `symbol`'s candles from the first of `predictions`' windows to `horizon` candles past
+/// the last, fetching only those not stored.
+async fn candles_for(
+    predictions: &[RecordedPrediction],
+    symbol: &str,
+    horizon: usize,
+) -> Result<Vec<[f64; 6]>> {
+    let (Some(first), Some(last)) = (
+        predictions
+            .iter()
+            .map(|p| p.window_end)
+            .min_by(f64::total_cmp),
+        predictions
+            .iter()
+            .map(|p| p.window_end)
+            .max_by(f64::total_cmp),
+    ) else {
+        anyhow::bail!("No {} predictions to score", symbol);
+    };
+
+    let start = DateTime::from_timestamp(first as i64, 0).context("Invalid window_end")?;
+    let end =
+        DateTime::from_timestamp((last + (horizon + 1) as f64 * GRANULARITY as f64) as i64, 0)
+            .context("Invalid window_end")?
+            .min(Utc::now());
+    let storage = storage::from_env().await?;
+    load_or_fetch(storage.as_ref(), symbol, GRANULARITY, start, end).await
+}
+
+/// Recorded ETH predictions from `sources` whose next candle has closed, with the window's
+/// last candle and that next one.
+async fn verified_live_predictions(
+    sources: &[&str],
+) -> Result<Vec<(PredictionRecord, [[f64; 6]; 2])>> {
+    let live = AnalyticsStore::default()
+        .predictions()?
+        .into_iter()
+        .filter(|p| sources.contains(&p.source.as_str()) && p.symbol == "ETH")
+        .collect::<Vec<_>>();
+    let step = GRANULARITY as f64;
+    let (Some(first), Some(last)) = (
+        live.iter().map(|p| p.window_end).min_by(f64::total_cmp),
+        live.iter().map(|p| p.window_end).max_by(f64::total_cmp),
+    ) else {
+        return Ok(Vec::new());
+    };
+
+    let start = DateTime::from_timestamp(first as i64, 0).context("Invalid window_end")?;
+    let end = DateTime::from_timestamp((last + 2.0 * step) as i64, 0)
+        .context("Invalid window_end")?
+        .min(Utc::now());
+    let storage = storage::from_env().await?;
+    let candles = load_or_fetch(storage.as_ref(), "ETH", GRANULARITY, start, end).await?;
+
+    Ok(live
+        .into_iter()
+        .filter_map(|p| {
+            let i = candles.iter().position(|c| c[0] == p.window_end)?;
+            let next = candles.get(i + 1).filter(|n| n[0] == p.window_end + step)?;
+            Some((p, [candles[i], *next]))
+        })
+        .collect())
+}
+
+/// Accuracy and PnL by session of every recorded backtest window and trade, and of the
+/// verified live signals with their paper trades.
+pub async fn session_breakdowns() -> Result<(SessionBreakdown, SessionBreakdown)> {
+    let store = AnalyticsStore::default();
+    let mut backtest = SessionBreakdown::default();
+    for p in store.predictions()? {
+        if let (true, Some(correct)) = (p.source == "backtest", p.correct) {
+            backtest.record_prediction(p.window_end, correct);
+        }
+    }
+    for r in store.trades()? {
+        backtest.record_trade(r.trade.entry_time, r.trade.return_pct);
+    }
+
+    let mut live = SessionBreakdown::default();
+    let mut funding = FundingHistories::default();
+    let labeling = load_labeling(Path::new(LABELING_FILE))?;
+    for (p, candles) in verified_live_predictions(&["live"]).await? {
+        let label = labeling.label(&p.symbol, &candles)[0];
+        live.record_prediction(p.window_end, p.action == label);
+        if let Some(trade) = paper_trade(&p, &candles, &mut funding) {
+            live.record_trade(trade.entry_time, trade.return_pct);
+        }
+    }
+    Ok((backtest, live))
+}
+
+/// Stored funding histories by symbol, read on first use.
+#[derive(Default)]
+struct FundingHistories(HashMap<String, Vec<FundingPoint>>);
+
+impl FundingHistories {
+    /// The settlements of `symbol`; none when its history is missing or unreadable.
+    fn get(&mut self, symbol: &str) -> &[FundingPoint] {
+        self.0.entry(symbol.to_string()).or_insert_with(|| {
+            FundingStore::default().load(symbol).unwrap_or_else(|err| {
+                tracing::warn!(symbol, %err, "Leaving funding out of paper trades");
+                Vec::new()
+            })
+        })
+    }
+}
+
+/// The paper trade of a verified live signal over its next candle, net of the funding its
+/// symbol's perpetual settled while it was held.
+fn paper_trade(
+    p: &PredictionRecord,
+    candles: &[[f64; 6]],
+    funding: &mut FundingHistories,
+) -> Option<Trade> {
+    let trade = bracket_trade(
+        candles,
+        0,
+        p.action,
+        p.invalidation_price,
+        p.target_price,
+        FEE_RATE,
+    )?;
+    Some(accrue_funding(
+        trade,
+        funding.get(&p.symbol),
+        GRANULARITY as f64,
+        1.0,
+    ))
+}
+
+/// How often each prompt version's recorded rationales cited prices absent from their
+/// data, over every source.
+pub fn hallucinations_by_prompt() -> Result<Vec<HallucinationRate>> {
+    let predictions = AnalyticsStore::default().predictions()?;
+    Ok(hallucination_rates(predictions.iter().filter_map(|p| {
+        Some((p.prompt_hash.as_str(), p.numeric_claims.as_ref()?))
+    })))
+}
+
+/// The verified live signals and their paper trades, attributed to the prompt version
+/// that produced each, so a swap that hurt live PnL shows up against its predecessor.
+pub async fn live_pnl_by_prompt() -> Result<Vec<PromptPnl>> {
+    let mut funding = FundingHistories::default();
+    let labeling = load_labeling(Path::new(LABELING_FILE))?;
+    let outcomes = verified_live_predictions(&["live"])
+        .await?
+        .into_iter()
+        .map(|(p, candles)| LiveOutcome {
+            correct: p.action == labeling.label(&p.symbol, &candles)[0],
+            trade: paper_trade(&p, &candles, &mut funding),
+            prompt_version: p.prompt_hash,
+            window_end: p.window_end,
+        })
+        .collect::<Vec<_>>();
+    Ok(prompt_pnl(&outcomes))
+}
+
+/// Every challenger's record against the champion it ran beside, over the live hours whose
+/// next candle has closed; most significant edge first.
+pub async fn challenger_standings() -> Result<Vec<HeadToHead>> {
+    live_head_to_heads("challenger", |p| p.prompt_hash.clone()).await
+}
+
+/// Every shadow model's record against the champion, like [`challenger_standings`].
+pub async fn shadow_standings() -> Result<Vec<HeadToHead>> {
+    live_head_to_heads("shadow", |p| p.model.clone()).await
+}
+
+/// Pairs each verified prediction from `source` with the current champion's from the same
+/// live run, grouping them by `key`.
+async fn live_head_to_heads(
+    source: &str,
+    key: impl Fn(&PredictionRecord) -> String,
+) -> Result<Vec<HeadToHead>> {
+    // Only the current champion's hours count, so a promotion resets every comparison
+    let champion = fs::read_to_string(PROMPT_FILE).context("Failed to read base prompt file")?;
+    let champion_version = prompt_version(&champion);
+    let mut champions = HashMap::new();
+    let mut rivals = Vec::new();
+    let labeling = load_labeling(Path::new(LABELING_FILE))?;
+    for (p, [last, next]) in verified_live_predictions(&["live", source]).await? {
+        let correct = p.action == labeling.label(&p.symbol, &[last, next])[0];
+        if p.source == source {
+            rivals.push((key(&p), p.run_id, correct));
+        } else if p.prompt_hash == champion_version {
+            champions.insert(p.run_id, correct);
+        }
+    }
+
+    let mut outcomes: BTreeMap<String, Vec<(bool, bool)>> = BTreeMap::new();
+    for (rival, run_id, correct) in rivals {
+        if let Some(champion) = champions.get(&run_id) {
+            outcomes
+                .entry(rival)
+                .or_default()
+                .push((*champion, correct));
+        }
+    }
+    let mut standings = outcomes
+        .into_iter()
+        .map(|(rival, outcomes)| HeadToHead::from_outcomes(rival, &outcomes))
+        .collect::<Vec<_>>();
+    standings.sort_by(|a, b| b.z_score().total_cmp(&a.z_score()));
+    Ok(standings)
+}
+
+/// Promotes the challenger with the most significant edge once it has beaten the champion
+/// over at least `min_hours` live hours: its prompt replaces `prompt.txt` and the old
+/// champion becomes a challenger. Returns the promoted challenger's record, if any.
+pub async fn promote_challenger(min_hours: usize) -> Result<Option<HeadToHead>> {
+    let Some(winner) = challenger_standings()
+        .await?
+        .into_iter()
+        .find(|h| h.beats_champion(min_hours))
+    else {
+        return Ok(None);
+    };
+    let Some((path, prompt)) = challenger_prompts()?
+        .into_iter()
+        .find(|(_, prompt)| prompt_version(prompt) == winner.challenger)
+    else {
+        tracing::warn!(challenger = %winner.challenger, "Winning challenger's prompt file is gone");
+        return Ok(None);
+    };
+
+    let champion = fs::read_to_string(PROMPT_FILE).context("Failed to read base prompt file")?;
+    fs::write(
+        Path::new(CHALLENGER_DIR).join(format!("{}.txt", prompt_version(&champion))),
+        &champion,
+    )?;
+    fs::write(PROMPT_FILE, &prompt)?;
+    fs::remove_file(&path)?;
+    storage::from_env()
+        .await?
+        .append_prompt_record(&PromptRecord {
+            prompt,
+            score: winner.challenger_correct as f64 / winner.hours as f64,
+            out_of_sample: None,
+            symbol: DEFAULT_TARGET.to_string(),
+        })
+        .await?;
+    tracing::info!(
+        challenger = %winner.challenger,
+        hours = winner.hours,
+        z = winner.z_score(),
+        "Promoted challenger to champion"
+    );
+    Ok(Some(winner))
+}
+
+/// Seed of the validation windows prompt candidates are scored on, fixed so every candidate
+/// meets the same ones.
+const VALIDATION_SEED: u64 = 0;
+
+/// Scores candidate `name` beside the champion in `profile`'s prompt file over the
+/// validation set, [`VALIDATION_WINDOWS`] non-overlapping windows of its symbol drawn with a
+/// fixed seed, and keeps the result beside the candidate. Nothing is recorded in the prompt
+/// history.
+pub async fn evaluate_candidate(
+    name: &str,
+    profile: &SymbolProfile,
+) -> Result<CandidateEvaluation> {
+    let candidate =
+        load_candidate(name)?.with_context(|| format!("No prompt candidate named {name}"))?;
+    let champion = fs::read_to_string(&profile.prompt)
+        .with_context(|| format!("Failed to read {}", profile.prompt.display()))?;
+    // Candidate names can't contain a dot, so they can't be mistaken for a prompt file's
+    let champion_name = profile.prompt.display().to_string();
+    let scores = compare_prompts(
+        &[
+            (champion_name.clone(), champion),
+            (name.to_string(), candidate),
+        ],
+        &BacktestOptions {
+            seed: Some(VALIDATION_SEED),
+            stride: NON_OVERLAPPING,
+            sample: Some(VALIDATION_WINDOWS),
+            profile: profile.clone(),
+            ..Default::default()
+        },
+    )
+    .await?;
+    let score = |prompt: &str| scores.iter().find(|s| s.name == prompt).cloned();
+    let (Some(champion), Some(candidate)) = (score(&champion_name), score(name)) else {
+        anyhow::bail!("Comparison of {name} left out a prompt");
+    };
+    let evaluation = CandidateEvaluation {
+        champion,
+        candidate,
+    };
+    save_evaluation(name, &evaluation)?;
+    tracing::info!(
+        candidate = name,
+        accuracy = evaluation.candidate.accuracy,
+        gain = evaluation.accuracy_gain(),
+        "Evaluated prompt candidate"
+    );
+    Ok(evaluation)
+}
+
+/// Makes candidate `name` the champion of `profile` once it has been evaluated against the
+/// current one and beaten it, or regardless of the score with `force`: its prompt replaces
+/// the profile's prompt file and, for the default prompt, the old champion becomes a
+/// challenger, as with [`promote_challenger`]. The evaluation is the promotion's record in
+/// the symbol's prompt history.
+pub async fn promote_candidate(
+    name: &str,
+    profile: &SymbolProfile,
+    force: bool,
+) -> Result<CandidateEvaluation> {
+    let candidate =
+        load_candidate(name)?.with_context(|| format!("No prompt candidate named {name}"))?;
+    let champion = fs::read_to_string(&profile.prompt)
+        .with_context(|| format!("Failed to read {}", profile.prompt.display()))?;
+    let evaluation = load_evaluation(name)?
+        .filter(|e| e.is_current(&prompt_version(&champion), &prompt_version(&candidate)))
+        .with_context(|| {
+            format!("Candidate {name} has no evaluation against the current champion yet")
+        })?;
+    anyhow::ensure!(
+        force || evaluation.accuracy_gain() > 0.0,
+        "Candidate {name} scored {:+.1} pts against the champion; promote it with force to \
+         replace the champion anyway",
+        evaluation.accuracy_gain() * 100.0
+    );
+
+    // Challengers are asked beside the default target's live prompt only
+    if profile.prompt == Path::new(PROMPT_FILE) {
+        fs::create_dir_all(CHALLENGER_DIR)?;
+        fs::write(
+            Path::new(CHALLENGER_DIR).join(format!("{}.txt", prompt_version(&champion))),
+            &champion,
+        )?;
     }
+    fs::write(&profile.prompt, &candidate)?;
+    remove_candidate(name)?;
+    storage::from_env()
+        .await?
+        .append_prompt_record(&PromptRecord {
+            prompt: candidate,
+            score: evaluation.candidate.accuracy,
+            out_of_sample: None,
+            symbol: profile.symbol.clone(),
+        })
+        .await?;
+    tracing::info!(
+        candidate = name,
+        accuracy = evaluation.candidate.accuracy,
+        gain = evaluation.accuracy_gain(),
+        "Promoted prompt candidate to champion"
+    );
+    Ok(evaluation)
+}
+
+/// Every recorded trade as a journal entry sized to `notional`: the simulated backtest
+/// trades, plus paper trades of the live signals whose next candle has closed.
+pub async fn trade_journal(notional: f64) -> Result<Vec<JournalEntry>> {
+    let store = AnalyticsStore::default();
+    let predictions = store.predictions()?;
+    let mut journal = journal_entries(
+        &store.trades()?,
+        &predictions,
+        "backtest",
+        notional,
+        FEE_RATE,
+    );
+
+    let mut funding = FundingHistories::default();
+    let (paper, live): (Vec<_>, Vec<_>) = verified_live_predictions(&["live"])
+        .await?
+        .into_iter()
+        .filter_map(|(p, candles)| {
+            let trade = paper_trade(&p, &candles, &mut funding)?;
+            let record = TradeRecord {
+                run_id: p.run_id.clone(),
+                symbol: p.symbol.clone(),
+                prompt_hash: p.prompt_hash.clone(),
+                trade,
+            };
+            Some((record, p))
+        })
+        .unzip();
+    journal.extend(journal_entries(&paper, &live, "paper", notional, FEE_RATE));
+    journal.sort_by(|a, b| a.entry_time.cmp(&b.entry_time));
+    Ok(journal)
 }
 
+/// The paper ledger of the live signals, sized to `notional` and denominated in `quote`: a
+/// closed lot per verified signal's paper trade, and an open one, marked at the latest
+/// close, for any signal whose next candle is still trading.
+pub async fn paper_ledger(notional: f64, quote: &str) -> Result<Vec<Lot>> {
+    let mut funding = FundingHistories::default();
+    let mut lots = verified_live_predictions(&["live"])
+        .await?
+        .into_iter()
+        .filter_map(|(p, candles)| {
+            let trade = paper_trade(&p, &candles, &mut funding)?;
+            Some(Lot::closed(&p.symbol, &trade, notional, FEE_RATE).in_currency(quote))
+        })
+        .collect::<Vec<_>>();
+
+    let step = GRANULARITY as f64;
+    let now = Utc::now();
+    let mut held = BTreeMap::<String, Vec<_>>::new();
+    for p in AnalyticsStore::default()
+        .predictions()?
+        .into_iter()
+        .filter(|p| p.source == "live" && p.action != Action::None)
+        .filter(|p| p.window_end + 2.0 * step > now.timestamp() as f64)
+    {
+        held.entry(p.symbol.clone()).or_default().push(p);
+    }
+    if !held.is_empty() {
+        let storage = storage::from_env().await?;
+        // Each symbol's open lots are marked at that symbol's latest close
+        for (symbol, held) in held {
+            let first = held
+                .iter()
+                .map(|p| p.window_end)
+                .fold(f64::INFINITY, f64::min);
+            let start = DateTime::from_timestamp(first as i64, 0).context("Invalid window_end")?;
+            let candles = load_or_fetch(storage.as_ref(), &symbol, GRANULARITY, start, now).await?;
+            for p in held {
+                let entry = candles.iter().find(|c| c[0] == p.window_end);
+                if let (Some(entry), Some(mark)) = (entry, candles.last()) {
+                    lots.push(
+                        Lot::open(
+                            &p.symbol,
+                            p.action,
+                            p.window_end,
+                            entry[CLOSE],
+                            mark[CLOSE],
+                            notional,
+                            FEE_RATE,
+                        )
+                        .in_currency(quote),
+                    );
+                }
+            }
+        }
+    }
+    lots.sort_by(|a, b| a.opened_at.total_cmp(&b.opened_at));
+    Ok(lots)
+}
+
+/// Every prompt version with a recorded run, ranked against the one in `prompt.txt`.
+pub async fn prompt_leaderboard() -> Result<Vec<LeaderboardEntry>> {
+    let champion = fs::read_to_string(PROMPT_FILE).ok();
+    let version = champion.as_deref().map(prompt_version).unwrap_or_default();
+    let storage = storage::from_env().await?;
+    let mut board = leaderboard(&storage.manifests().await?, &version, champion.as_deref());
+    if let Some(sink) = S3Sink::from_env()? {
+        for entry in &mut board {
+            entry.links = entry
+                .runs
+                .iter()
+                .map(|run_id| sink.run_url(run_id, "report.json"))
+                .collect();
+        }
+    }
+    Ok(board)
+}
+
+/// Runs the data-quality checks over every series in the candle store.
+pub fn check_cached_data() -> Result<Vec<SeriesReport>> {
+    let store = CandleStore::default();
+    store
+        .series()?
+        .into_iter()
+        .map(|(symbol, granularity)| {
+            let candles = store.load_stored(&symbol, granularity)?;
+            let mut report = check_series(&symbol, &candles);
+            report.granularity.get_or_insert(granularity as i64);
+            Ok(report)
+        })
+        .collect()
+}
+
+/// A model's prediction for one window, next to the window's label.
+struct Scored {
+    prediction: Prediction,
+    label: Action,
+    /// Model round trips; `None` when every response came from the cache.
+    latency: Option<std::time::Duration>,
+    /// The model whose answer was kept.
+    model: Model,
+    escalation: Option<Escalation>,
+    /// The prompt the model was asked.
+    prompt: String,
+}
+
+/// Asks o1-mini about `window`, or lets `router` pick the model.
 async fn query_model_and_compare(
+    cache: &LlmCache,
+    router: Option<ModelRouter>,
+    chat: ChatOptions,
     prompt: String,
+    window: &[[f64; 6]],
     label: Action,
-) -> Result<(Action, String, Action)> {
-    let response = analyze_data_gpt(&prompt, Model::O1Mini).await?;
+) -> Result<Scored> {
+    let Some(router) = router else {
+        let (prediction, latency) = ask_model(cache, Model::O1Mini, chat, &prompt).await?;
+        return Ok(Scored {
+            prediction,
+            label,
+            latency,
+            model: Model::O1Mini,
+            escalation: None,
+            prompt,
+        });
+    };
 
-    // Clean up the response to remove code fences if present
-    let clean_response = response.replace("```json", "").replace("```", "");
+    let mut latency = None;
+    let mut escalation = router.escalate_before(window);
+    if escalation.is_none() {
+        let (prediction, cheap_latency) = ask_model(cache, router.cheap, chat, &prompt).await?;
+        escalation = router.escalate_after(&prediction);
+        if escalation.is_none() {
+            return Ok(Scored {
+                prediction,
+                label,
+                latency: cheap_latency,
+                model: router.cheap,
+                escalation,
+                prompt,
+            });
+        }
+        latency = cheap_latency;
+    }
 
-    let val: Value = serde_json::from_str(&clean_response)
-        .with_context(|| format!("Response not valid JSON: {}", clean_response))?;
-    let action_str = val
-        .get("action")
-        .and_then(|a| a.as_str())
-        .context("Missing 'action' field in response")?;
-    let rationale = val
-        .get("rationale")
-        .and_then(|r| r.as_str())
-        .unwrap_or("")
-        .to_string();
+    let (prediction, expensive_latency) = ask_model(cache, router.expensive, chat, &prompt).await?;
+    Ok(Scored {
+        prediction,
+        label,
+        latency: match (latency, expensive_latency) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        },
+        model: router.expensive,
+        escalation,
+        prompt,
+    })
+}
 
-    let pred = match action_str {
-        "long" => Action::Long,
-        "short" => Action::Short,
-        "none" => Action::None,
-        _ => Action::None,
-    };
+/// Returns the prediction and the model latency, which is `None` when the response came
+/// from the cache.
+async fn ask_model(
+    cache: &LlmCache,
+    model: Model,
+    chat: ChatOptions,
+    prompt: &str,
+) -> Result<(Prediction, Option<std::time::Duration>)> {
+    let key = chat.cache_key(prompt);
+    // Cached responses from before strict validation may not parse; those are asked again
+    if let Some(cached) = cache.get(model, &key).await {
+        if let Ok(prediction) = parse_model_response(&cached, RESPONSE_STRICTNESS) {
+            return Ok((prediction, None));
+        }
+    }
+
+    // Only the accepted response is cached, under the original prompt
+    let timer = Instant::now();
+    let (prediction, response) = request_prediction(prompt, model, chat).await?;
+    let latency = timer.elapsed();
+    cache.put(model, &key, &response).await?;
+    Ok((prediction, Some(latency)))
+}
 
-    Ok((pred, rationale, label))
+/// [`ask_model`] in multi-asset mode.
+async fn ask_multi_asset(
+    cache: &LlmCache,
+    model: Model,
+    prompt: String,
+    symbols: &[&str],
+) -> Result<Vec<(String, Prediction)>> {
+    if let Some(cached) = cache.get(model, &prompt).await {
+        if let Ok(predictions) = parse_multi_asset_response(&cached, symbols, RESPONSE_STRICTNESS) {
+            return Ok(predictions);
+        }
+    }
+    let (predictions, response) =
+        request_multi_asset_prediction(&prompt, model, symbols, CallKind::Backtest).await?;
+    cache.put(model, &prompt, &response).await?;
+    Ok(predictions)
+}
+
+/// [`ask_model`] in allocation mode.
+async fn ask_allocation(
+    cache: &LlmCache,
+    model: Model,
+    prompt: String,
+    symbols: &[&str],
+) -> Result<Allocation> {
+    if let Some(cached) = cache.get(model, &prompt).await {
+        if let Ok(allocation) = parse_allocation_response(&cached, symbols, RESPONSE_STRICTNESS) {
+            return Ok(allocation);
+        }
+    }
+    let (allocation, response) = request_allocation(&prompt, model, symbols).await?;
+    cache.put(model, &prompt, &response).await?;
+    Ok(allocation)
+}
+
+fn build_lint_feedback(
+    improvement_prompt: &str,
+    candidate: &str,
+    violations: &[LintViolation],
+) -> String {
+    let mut prompt = format!(
+        "{}\n\nYour previous answer was:\n{}\n\nIt cannot be used as a prompt because:\n",
+        improvement_prompt, candidate
+    );
+    for violation in violations {
+        let _ = writeln!(prompt, "- {}", violation);
+    }
+    prompt.push_str("\nReturn a corrected prompt that fixes every problem above.\n");
+    prompt
+}
+
+/// Calls out each of `duplicates`, the answers so far that repeated a prompt already tried,
+/// so a re-ask doesn't circle back to one of them.
+fn build_duplicate_feedback(
+    improvement_prompt: &str,
+    duplicates: &[(String, &str, f64)],
+) -> String {
+    let mut prompt = improvement_prompt.to_string();
+    for (candidate, repeated, score) in duplicates {
+        let _ = write!(
+            prompt,
+            "\n\nYou answered:\n{}\n\nIt is nearly identical to this prompt, which was already tested and scored {:.2}%:\n{}",
+            candidate,
+            score * 100.0,
+            repeated
+        );
+    }
+    prompt.push_str("\n\nA prompt that close would score the same. Make a substantive revision that changes how the model reads the data, not just the wording.\n");
+    prompt
+}
+
+/// `symbols` written out as prose: "ETH, BTC, and SOL".
+fn list_symbols(symbols: &[String]) -> String {
+    match symbols {
+        [] => String::new(),
+        [only] => only.clone(),
+        [a, b] => format!("{} and {}", a, b),
+        [rest @ .., last] => format!("{}, and {}", rest.join(", "), last),
+    }
 }
 
 fn build_improvement_prompt(
+    symbols: &[String],
     base_prompt: &str,
     failures: &[(usize, Action, Action, String)],
     previous_prompts: &[(String, f64)],
+    postmortems: &[String],
+    hallucinations: &[(usize, Vec<f64>, String)],
+    focus: Option<ImprovementFocus>,
 ) -> String {
     let mut prompt = String::new();
     prompt.push_str("You are an assistant that improves trading prompts.\n");
-    prompt.push_str("We have a base prompt (below) that instructs the model to produce an action (long, short, or none) and a brief rationale based on provided ETH, BTC, and SOL market data.\n");
+    let _ = writeln!(
+        prompt,
+        "We have a base prompt (below) that instructs the model to produce an action (long, short, or none) on {} and a brief rationale based on provided {} market data.",
+        symbols.first().map_or("the target", String::as_str),
+        list_symbols(symbols)
+    );
     prompt.push_str("We performed backtesting and found some instances where the model's predicted action did not match the correct action.\n\n");
     prompt.push_str("Below are some examples of these failures:\n");
-    for (i, pred, label, rationale) in failures.iter().take(10) {
+    // Examples of the targeted mistake come first
+    let (targeted, others): (Vec<_>, Vec<_>) = failures
+        .iter()
+        .partition(|(_, pred, label, _)| focus.is_some_and(|f| f.matches(*pred, *label)));
+    for (i, pred, label, rationale) in targeted.into_iter().chain(others).take(10) {
         let _ = writeln!(
             prompt,
             "Window {}: Model predicted {:?}, but the correct action was {:?}. Model's rationale: {}",
@@ -204,6 +2965,33 @@ fn build_improvement_prompt(
         );
     }
 
+    if !postmortems.is_empty() {
+        prompt.push_str(
+            "\nAsked afterwards why it got some of these windows wrong, the model concluded:\n",
+        );
+        for summary in postmortems {
+            let _ = writeln!(prompt, "- {}", summary);
+        }
+    }
+
+    if !hallucinations.is_empty() {
+        prompt.push_str(
+            "\nSome rationales cited prices that appear nowhere in the data they were given:\n",
+        );
+        for (i, unsupported, rationale) in hallucinations {
+            let prices = unsupported
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let _ = writeln!(
+                prompt,
+                "Window {}: cited {} not in the data. Model's rationale: {}",
+                i, prices, rationale
+            );
+        }
+    }
+
     prompt.push_str(
         "\nWe also have a history of previous prompts and their overall accuracy scores:\n",
     );
@@ -217,8 +3005,18 @@ fn build_improvement_prompt(
     }
 
     prompt.push_str("\nWe need to improve the prompt so that:\n");
-    prompt.push_str("- The model is more likely to produce correct 'action' decisions.\n");
+    match focus {
+        Some(focus) => {
+            let _ = writeln!(prompt, "- {}", focus.instruction());
+        }
+        None => {
+            prompt.push_str("- The model is more likely to produce correct 'action' decisions.\n")
+        }
+    }
     prompt.push_str("- The rationale remains concise and well-aligned with the chosen action.\n");
+    if !hallucinations.is_empty() {
+        prompt.push_str("- The rationale only cites prices that appear in the data.\n");
+    }
     prompt.push_str(
         "- The model should not provide disclaimers or mention hypothetical scenarios.\n",
     );
@@ -230,3 +3028,50 @@ fn build_improvement_prompt(
 
     prompt
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_windows() {
+        let fixed = BacktestOptions {
+            seed: Some(7),
+            ..Default::default()
+        };
+        assert_eq!(fixed.seeded().seed, Some(7));
+        let drawn = BacktestOptions::default().seeded();
+        assert!(drawn.seed.is_some());
+        // Seeding twice keeps the first draw, so every later use selects the same windows
+        assert_eq!(drawn.seeded().seed, drawn.seed);
+        assert_ne!(entropy_seed(), entropy_seed());
+
+        let sampled = BacktestOptions {
+            sample: Some(10),
+            ..Default::default()
+        };
+        let len = CANDLE_HOURS + 100;
+        let (windows, candidates) = select_windows(len, &sampled, 42);
+        assert_eq!(candidates, 100);
+        assert_eq!(windows.len(), 10);
+        assert!(windows.iter().all(|&w| (CANDLE_HOURS..len).contains(&w)));
+        assert_eq!(select_windows(len, &sampled, 42).0, windows);
+        assert_ne!(select_windows(len, &sampled, 43).0, windows);
+
+        // The sample never exceeds the windows the stride leaves
+        let strided = BacktestOptions {
+            stride: NON_OVERLAPPING,
+            sample: Some(1000),
+            ..Default::default()
+        };
+        let (windows, candidates) = select_windows(len, &strided, 42);
+        assert_eq!(windows.len(), candidates);
+        assert_eq!(
+            windows,
+            (CANDLE_HOURS..len)
+                .step_by(NON_OVERLAPPING)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(select_windows(len, &Default::default(), 42).0.len(), 100);
+    }
+}
@@ -0,0 +1,48 @@
+use crate::features::{Feature, FeatureSet, VwapDistance};
+use crate::indicators::vwap_distance;
+use crate::Action;
+
+/// Minimum distance from VWAP (as a fraction) before the reversion baseline takes a side.
+pub const VWAP_REVERSION_BAND: f64 = 0.01;
+
+/// Rule-based mean-reversion baseline: fade moves that stretch too far from the
+/// window VWAP. Used to judge whether the model beats a trivial strategy.
+pub fn vwap_reversion(data: &[[f64; 6]], band: f64) -> Action {
+    fade(vwap_distance(data), band)
+}
+
+/// [`vwap_reversion`] on `symbol`, from its already computed [`VwapDistance`] feature.
+pub fn vwap_reversion_on(features: &FeatureSet, symbol: &str, band: f64) -> Action {
+    fade(features.get(symbol, VwapDistance.name()), band)
+}
+
+fn fade(distance: Option<f64>, band: f64) -> Action {
+    match distance {
+        Some(d) if d >= band => Action::Short,
+        Some(d) if d <= -band => Action::Long,
+        _ => Action::None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vwap_reversion() {
+        let stretched_up = [
+            [0.0, 0.0, 100.0, 100.0, 100.0, 10.0],
+            [0.0, 0.0, 105.0, 105.0, 105.0, 1.0],
+        ];
+        assert_eq!(vwap_reversion(&stretched_up, 0.01), Action::Short);
+
+        let stretched_down = [
+            [0.0, 0.0, 100.0, 100.0, 100.0, 10.0],
+            [0.0, 0.0, 95.0, 95.0, 95.0, 1.0],
+        ];
+        assert_eq!(vwap_reversion(&stretched_down, 0.01), Action::Long);
+
+        let flat = [[0.0, 0.0, 100.0, 100.0, 100.0, 10.0]];
+        assert_eq!(vwap_reversion(&flat, 0.01), Action::None);
+    }
+}
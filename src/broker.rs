@@ -0,0 +1,1279 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+#[cfg(feature = "native")]
+use anyhow::Context;
+use anyhow::Result;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+#[cfg(feature = "native")]
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::exposure::{cap_correlated, CorrelationMatrix};
+use crate::ledger::{Account, DEFAULT_CURRENCY};
+use crate::simulator::FEE_RATE;
+use crate::Action;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// A market order, or a limit order when `limit` is set.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Order {
+    /// Our own id for the order, so a retried signal is recognizable at the venue.
+    pub client_id: String,
+    pub symbol: String,
+    pub side: Side,
+    /// Units of the symbol.
+    pub quantity: f64,
+    pub limit: Option<f64>,
+    /// Price the order was sized at. The paper engine fills market orders here; venues
+    /// fill at their own prices.
+    pub reference_price: f64,
+}
+
+impl Order {
+    /// Quantity with the sign of the position change: positive buying, negative selling.
+    pub fn signed_quantity(&self) -> f64 {
+        match self.side {
+            Side::Buy => self.quantity,
+            Side::Sell => -self.quantity,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderStatus {
+    Open,
+    Filled,
+    /// Logged by a [`DryRun`] and never sent.
+    Simulated,
+}
+
+/// A venue's answer to a placed order.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrderAck {
+    /// The venue's id of the order, which cancels it.
+    pub id: String,
+    pub status: OrderStatus,
+    /// Units filled so far.
+    pub filled: f64,
+    pub average_price: Option<f64>,
+}
+
+/// An order resting at the venue.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenOrder {
+    pub id: String,
+    pub symbol: String,
+    pub side: Side,
+    pub quantity: f64,
+    /// Units already filled.
+    pub filled: f64,
+}
+
+/// A net holding of one symbol.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    pub symbol: String,
+    /// Units held, negative when short.
+    pub quantity: f64,
+    /// `None` where the venue does not track it, as with spot holdings.
+    pub entry_price: Option<f64>,
+}
+
+/// A venue orders are sent to. The daemon and the risk checks only ever see this trait, so
+/// a venue is swapped, or wrapped in a [`DryRun`], without touching either.
+pub trait Broker: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Whether the venue can hold a position short. Spot venues can't: a short signal
+    /// there only closes the long.
+    fn can_short(&self) -> bool {
+        true
+    }
+
+    fn place<'a>(&'a self, order: &'a Order) -> BoxFuture<'a, Result<OrderAck>>;
+
+    /// Cancels an open order of `symbol` by the id [`Broker::place`] returned.
+    fn cancel<'a>(&'a self, symbol: &'a str, order_id: &'a str) -> BoxFuture<'a, Result<()>>;
+
+    /// Every order still resting at the venue.
+    fn open_orders(&self) -> BoxFuture<'_, Result<Vec<OpenOrder>>>;
+
+    /// Every position that is not flat.
+    fn positions(&self) -> BoxFuture<'_, Result<Vec<Position>>>;
+
+    fn balances(&self) -> BoxFuture<'_, Result<Account>>;
+}
+
+/// How the paper engine settles fills in cash.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PaperSettlement {
+    /// Every fill's whole cost moves cash, as on spot: a buy pays for its units and a
+    /// short sale is credited its proceeds, owing the units back.
+    #[default]
+    Cash,
+    /// Positions are held on margin, as on a perpetual: cash only moves by the fee and the
+    /// PnL realized on what a fill closes, so an open position's value lies in its entry
+    /// price rather than in the balances.
+    Margin,
+}
+
+#[derive(Debug, Default)]
+struct PaperState {
+    settlement: PaperSettlement,
+    account: Account,
+    positions: BTreeMap<String, Position>,
+    resting: Vec<(String, Order)>,
+    placed: u64,
+}
+
+impl PaperState {
+    /// Fills `order` at `price`, paying [`FEE_RATE`] in USD and settling the rest per the
+    /// [`PaperSettlement`].
+    fn fill(&mut self, order: &Order, price: f64) {
+        let change = order.signed_quantity();
+        self.account
+            .deposit(DEFAULT_CURRENCY, -order.quantity * price * FEE_RATE);
+        if self.settlement == PaperSettlement::Cash {
+            self.account.deposit(DEFAULT_CURRENCY, -change * price);
+        }
+
+        let position = self
+            .positions
+            .entry(order.symbol.clone())
+            .or_insert_with(|| Position {
+                symbol: order.symbol.clone(),
+                quantity: 0.0,
+                entry_price: None,
+            });
+        let held = position.quantity;
+        let quantity = held + change;
+        let closes = held != 0.0 && held.signum() == -change.signum();
+        if closes && self.settlement == PaperSettlement::Margin {
+            let closed = change.abs().min(held.abs());
+            let entry = position.entry_price.unwrap_or(price);
+            self.account
+                .deposit(DEFAULT_CURRENCY, closed * (price - entry) * held.signum());
+        }
+        position.entry_price = if held == 0.0 || held.signum() != quantity.signum() {
+            // Opened or flipped: the whole position is new
+            Some(price)
+        } else if held.signum() == change.signum() {
+            let entry = position.entry_price.unwrap_or(price);
+            Some((entry * held + price * change) / quantity)
+        } else {
+            // Reduced: what is left keeps its entry
+            position.entry_price
+        };
+        position.quantity = quantity;
+        if quantity.abs() < 1e-12 {
+            self.positions.remove(&order.symbol);
+        }
+    }
+
+    /// Fills the resting limit orders of `symbol` that `price` crosses.
+    fn cross(&mut self, symbol: &str, price: f64) {
+        let (crossed, resting) = std::mem::take(&mut self.resting)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(_, o)| o.symbol == symbol && is_marketable(o, price));
+        self.resting = resting;
+        for (_, order) in crossed {
+            let limit = order.limit.unwrap_or(price);
+            self.fill(&order, limit);
+        }
+    }
+}
+
+fn is_marketable(order: &Order, price: f64) -> bool {
+    match (order.side, order.limit) {
+        (_, None) => true,
+        (Side::Buy, Some(limit)) => price <= limit,
+        (Side::Sell, Some(limit)) => price >= limit,
+    }
+}
+
+/// The paper engine: fills orders in memory against their reference prices, so a strategy
+/// runs end to end without a venue. Limit orders rest until a later order's reference
+/// price crosses them. It can hold shorts under either [`PaperSettlement`].
+#[derive(Debug, Default)]
+pub struct PaperBroker {
+    state: Mutex<PaperState>,
+}
+
+impl PaperBroker {
+    /// A paper account starting with `cash` USD, settling fills in cash.
+    pub fn new(cash: f64) -> Self {
+        let broker = Self::default();
+        broker.lock().account.deposit(DEFAULT_CURRENCY, cash);
+        broker
+    }
+
+    /// A paper account starting with `cash` USD, holding positions on margin. Its balances
+    /// plus the unrealized PnL of its positions are its equity, as
+    /// [`equity`](crate::derisk::equity) marks it.
+    pub fn on_margin(cash: f64) -> Self {
+        let broker = Self::new(cash);
+        broker.lock().settlement = PaperSettlement::Margin;
+        broker
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PaperState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Broker for PaperBroker {
+    fn name(&self) -> &str {
+        "paper"
+    }
+
+    fn place<'a>(&'a self, order: &'a Order) -> BoxFuture<'a, Result<OrderAck>> {
+        async move {
+            anyhow::ensure!(
+                order.quantity > 0.0 && order.reference_price > 0.0,
+                "Order {} needs a positive quantity and reference price",
+                order.client_id
+            );
+            let mut state = self.lock();
+            state.placed += 1;
+            let id = format!("paper-{}", state.placed);
+            state.cross(&order.symbol, order.reference_price);
+            if !is_marketable(order, order.reference_price) {
+                state.resting.push((id.clone(), order.clone()));
+                return Ok(OrderAck {
+                    id,
+                    status: OrderStatus::Open,
+                    filled: 0.0,
+                    average_price: None,
+                });
+            }
+            state.fill(order, order.reference_price);
+            Ok(OrderAck {
+                id,
+                status: OrderStatus::Filled,
+                filled: order.quantity,
+                average_price: Some(order.reference_price),
+            })
+        }
+        .boxed()
+    }
+
+    fn cancel<'a>(&'a self, _symbol: &'a str, order_id: &'a str) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let mut state = self.lock();
+            let before = state.resting.len();
+            state.resting.retain(|(id, _)| id != order_id);
+            anyhow::ensure!(
+                state.resting.len() < before,
+                "No open paper order {}",
+                order_id
+            );
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn open_orders(&self) -> BoxFuture<'_, Result<Vec<OpenOrder>>> {
+        async move {
+            Ok(self
+                .lock()
+                .resting
+                .iter()
+                .map(|(id, order)| OpenOrder {
+                    id: id.clone(),
+                    symbol: order.symbol.clone(),
+                    side: order.side,
+                    quantity: order.quantity,
+                    filled: 0.0,
+                })
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn positions(&self) -> BoxFuture<'_, Result<Vec<Position>>> {
+        async move { Ok(self.lock().positions.values().cloned().collect()) }.boxed()
+    }
+
+    fn balances(&self) -> BoxFuture<'_, Result<Account>> {
+        async move { Ok(self.lock().account.clone()) }.boxed()
+    }
+}
+
+/// Wraps a broker so orders are only logged: positions and balances still come from the
+/// venue, but nothing is placed or cancelled.
+pub struct DryRun<B>(pub B);
+
+impl<B: Broker> Broker for DryRun<B> {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn can_short(&self) -> bool {
+        self.0.can_short()
+    }
+
+    fn place<'a>(&'a self, order: &'a Order) -> BoxFuture<'a, Result<OrderAck>> {
+        async move {
+            tracing::info!(
+                broker = self.0.name(),
+                client_id = %order.client_id,
+                symbol = %order.symbol,
+                side = ?order.side,
+                quantity = order.quantity,
+                limit = ?order.limit,
+                reference_price = order.reference_price,
+                "Dry run: order not placed"
+            );
+            Ok(OrderAck {
+                id: format!("dry-run-{}", order.client_id),
+                status: OrderStatus::Simulated,
+                filled: 0.0,
+                average_price: None,
+            })
+        }
+        .boxed()
+    }
+
+    fn cancel<'a>(&'a self, symbol: &'a str, order_id: &'a str) -> BoxFuture<'a, Result<()>> {
+        async move {
+            tracing::info!(
+                broker = self.0.name(),
+                %symbol,
+                %order_id,
+                "Dry run: order not cancelled"
+            );
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn open_orders(&self) -> BoxFuture<'_, Result<Vec<OpenOrder>>> {
+        self.0.open_orders()
+    }
+
+    fn positions(&self) -> BoxFuture<'_, Result<Vec<Position>>> {
+        self.0.positions()
+    }
+
+    fn balances(&self) -> BoxFuture<'_, Result<Account>> {
+        self.0.balances()
+    }
+}
+
+/// `positions` less the `external` holdings of each symbol. A symbol whose external holding
+/// covers all of it is left out, so the daemon never trades into what isn't its own.
+pub fn owned_positions(positions: &[Position], external: &[Position]) -> Vec<Position> {
+    positions
+        .iter()
+        .filter_map(|p| {
+            let external = external
+                .iter()
+                .filter(|e| e.symbol == p.symbol)
+                .fold(0.0, |sum, e| sum + e.quantity);
+            let quantity = p.quantity - external;
+            (quantity * p.quantity > 0.0).then(|| Position {
+                quantity,
+                ..p.clone()
+            })
+        })
+        .collect()
+}
+
+/// Wraps a broker so only what the daemon holds there is seen as a position: the
+/// `external` holdings, such as a spot account's existing balances, are left out, so no
+/// signal ever sells them. Orders go to the broker unchanged.
+pub struct Owned<'a> {
+    pub broker: &'a dyn Broker,
+    pub external: &'a [Position],
+}
+
+impl Broker for Owned<'_> {
+    fn name(&self) -> &str {
+        self.broker.name()
+    }
+
+    fn can_short(&self) -> bool {
+        self.broker.can_short()
+    }
+
+    fn place<'a>(&'a self, order: &'a Order) -> BoxFuture<'a, Result<OrderAck>> {
+        self.broker.place(order)
+    }
+
+    fn cancel<'a>(&'a self, symbol: &'a str, order_id: &'a str) -> BoxFuture<'a, Result<()>> {
+        self.broker.cancel(symbol, order_id)
+    }
+
+    fn open_orders(&self) -> BoxFuture<'_, Result<Vec<OpenOrder>>> {
+        self.broker.open_orders()
+    }
+
+    fn positions(&self) -> BoxFuture<'_, Result<Vec<Position>>> {
+        async move {
+            let positions = self.broker.positions().await?;
+            Ok(owned_positions(&positions, self.external))
+        }
+        .boxed()
+    }
+
+    fn balances(&self) -> BoxFuture<'_, Result<Account>> {
+        self.broker.balances()
+    }
+}
+
+/// Limits every order is checked against before it reaches a broker.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RiskLimits {
+    /// Notional a signal's position is sized to, in USD.
+    pub notional: f64,
+    /// Largest position, long or short, in USD.
+    pub max_position: f64,
+    /// Smaller changes to a position are not worth the fee and are skipped.
+    pub min_order: f64,
+    /// Largest net exposure along any one symbol, in USD, counting the other positions in
+    /// proportion to their correlation with it: long ETH and long SOL are mostly one bet.
+    pub max_correlated: f64,
+}
+
+impl Default for RiskLimits {
+    fn default() -> Self {
+        Self {
+            notional: 1000.0,
+            max_position: 5000.0,
+            min_order: 10.0,
+            max_correlated: 5000.0,
+        }
+    }
+}
+
+/// Units of the position `action` calls for at `price`: the signal's notional, capped at
+/// the largest position allowed.
+pub fn target_quantity(action: Action, price: f64, limits: &RiskLimits) -> f64 {
+    if price <= 0.0 {
+        return 0.0;
+    }
+    let size = limits.notional.min(limits.max_position) / price;
+    match action {
+        Action::Long => size,
+        Action::Short => -size,
+        Action::None => 0.0,
+    }
+}
+
+/// The order taking a `held` position in `symbol` to `target` units at `price`; `None`
+/// when it is already close enough.
+pub fn rebalance_order(
+    symbol: &str,
+    held: f64,
+    target: f64,
+    price: f64,
+    limits: &RiskLimits,
+    client_id: &str,
+) -> Option<Order> {
+    if price <= 0.0 {
+        return None;
+    }
+    let change = target - held;
+    // Closing out is always worth it, however small the position
+    if change == 0.0 || (target != 0.0 && change.abs() * price < limits.min_order) {
+        return None;
+    }
+    Some(Order {
+        client_id: client_id.to_string(),
+        symbol: symbol.to_string(),
+        side: if change > 0.0 { Side::Buy } else { Side::Sell },
+        quantity: change.abs(),
+        limit: None,
+        reference_price: price,
+    })
+}
+
+/// Moves `broker`'s position in `symbol` to the one `action` calls for, within `limits`,
+/// or to flat for a short on a broker that [can't short](Broker::can_short). With
+/// `correlations`, the position is also held to the correlated exposure limit given the
+/// broker's other positions. Returns the placed order's acknowledgement, `None` when no
+/// order was needed.
+pub async fn follow_signal(
+    broker: &dyn Broker,
+    symbol: &str,
+    action: Action,
+    price: f64,
+    limits: &RiskLimits,
+    correlations: Option<&CorrelationMatrix>,
+    client_id: &str,
+) -> Result<Option<OrderAck>> {
+    let positions = broker.positions().await?;
+    let held = positions
+        .iter()
+        .filter(|p| p.symbol == symbol)
+        .fold(0.0, |held, p| held + p.quantity);
+    let mut target = target_quantity(action, price, limits);
+    if target < 0.0 && !broker.can_short() {
+        tracing::info!(broker = broker.name(), %symbol, "Short signal closes the long; the venue can't short");
+        target = 0.0;
+    }
+    if let Some(correlations) = correlations {
+        let capped = cap_correlated(symbol, target, price, &positions, correlations, limits);
+        if capped != target {
+            tracing::info!(
+                %symbol,
+                wanted = target * price,
+                allowed = capped * price,
+                "Position held back by correlated exposure"
+            );
+        }
+        target = capped;
+    }
+    let Some(order) = rebalance_order(symbol, held, target, price, limits, client_id) else {
+        return Ok(None);
+    };
+    tracing::info!(
+        broker = broker.name(),
+        %symbol,
+        side = ?order.side,
+        quantity = order.quantity,
+        held,
+        "Placing order"
+    );
+    broker.place(&order).await.map(Some)
+}
+
+/// A broker by name (`paper`, `coinbase` or `binance`), wrapped in a [`DryRun`] when
+/// `dry_run` is set. Paper accounts start with `cash` USD and hold positions
+/// [on margin](PaperBroker::on_margin), so the drawdown rule marks their equity.
+#[cfg(feature = "native")]
+pub fn broker_from_name(name: &str, dry_run: bool, cash: f64) -> Result<Box<dyn Broker>> {
+    fn wrap<B: Broker + 'static>(broker: B, dry_run: bool) -> Box<dyn Broker> {
+        if dry_run {
+            Box::new(DryRun(broker))
+        } else {
+            Box::new(broker)
+        }
+    }
+    Ok(match name {
+        "paper" => wrap(PaperBroker::on_margin(cash), dry_run),
+        "coinbase" => wrap(CoinbaseBroker::from_env()?, dry_run),
+        "binance" => wrap(BinanceBroker::from_env()?, dry_run),
+        other => anyhow::bail!("Unknown broker {}; use paper, coinbase or binance", other),
+    })
+}
+
+/// Currencies Coinbase balances are held in rather than positions.
+#[cfg(feature = "native")]
+const CASH_CURRENCIES: [&str; 4] = ["USD", "USDC", "USDT", "EUR"];
+#[cfg(feature = "native")]
+const COINBASE_URL: &str = "https://api.exchange.coinbase.com";
+
+/// Spot orders on the Coinbase Exchange, in `<symbol>-USD` products. Holdings of anything
+/// but cash count as long positions, the account's own included; the daemon trades it
+/// through [`Owned`] so those are left alone.
+#[cfg(feature = "native")]
+pub struct CoinbaseBroker {
+    credentials: crate::secrets::ExchangeCredentials,
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "native")]
+impl CoinbaseBroker {
+    pub fn from_env() -> Result<Self> {
+        let credentials = crate::secrets::ExchangeCredentials::lookup().context(
+            "COINBASE_API_KEY, COINBASE_API_SECRET and COINBASE_API_PASSPHRASE are required",
+        )?;
+        Ok(Self {
+            credentials,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<T> {
+        let body = body.map(|b| b.to_string()).unwrap_or_default();
+        let timestamp = chrono::Utc::now().timestamp();
+        let mut request = self
+            .client
+            .request(method.clone(), format!("{}{}", COINBASE_URL, path))
+            .header("User-Agent", "Mozilla/5.0")
+            .header("Content-Type", "application/json")
+            .timeout(std::time::Duration::from_secs(10));
+        for (name, value) in self
+            .credentials
+            .headers(timestamp, method.as_str(), path, &body)?
+        {
+            request = request.header(name, value);
+        }
+        if !body.is_empty() {
+            request = request.body(body);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Coinbase API error on {}: {} - {}", path, status, text);
+        }
+        Ok(response.json().await?)
+    }
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+struct CoinbaseOrder {
+    id: String,
+    status: String,
+    #[serde(default)]
+    filled_size: Option<String>,
+    #[serde(default)]
+    executed_value: Option<String>,
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+struct CoinbaseOpenOrder {
+    id: String,
+    product_id: String,
+    side: Side,
+    size: String,
+    filled_size: String,
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+struct CoinbaseAccount {
+    currency: String,
+    balance: String,
+}
+
+/// A UUID-shaped id derived from `client_id`, since Coinbase only accepts UUIDs as client
+/// order ids.
+#[cfg(feature = "native")]
+fn client_uuid(client_id: &str) -> String {
+    let hex = crate::compute::sha256_hex(client_id.as_bytes());
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+#[cfg(feature = "native")]
+impl Broker for CoinbaseBroker {
+    fn name(&self) -> &str {
+        "coinbase"
+    }
+
+    fn can_short(&self) -> bool {
+        false
+    }
+
+    fn place<'a>(&'a self, order: &'a Order) -> BoxFuture<'a, Result<OrderAck>> {
+        async move {
+            let mut body = serde_json::json!({
+                "product_id": format!("{}-USD", order.symbol),
+                "side": order.side,
+                "size": format!("{:.8}", order.quantity),
+                "client_oid": client_uuid(&order.client_id),
+                "type": "market",
+            });
+            if let Some(limit) = order.limit {
+                body["type"] = "limit".into();
+                body["price"] = format!("{:.2}", limit).into();
+            }
+            let placed: CoinbaseOrder = self
+                .send(reqwest::Method::POST, "/orders", Some(body))
+                .await?;
+            let filled = placed
+                .filled_size
+                .as_deref()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0.0);
+            let value: Option<f64> = placed
+                .executed_value
+                .as_deref()
+                .and_then(|s| s.parse().ok());
+            Ok(OrderAck {
+                id: placed.id,
+                status: if placed.status == "done" {
+                    OrderStatus::Filled
+                } else {
+                    OrderStatus::Open
+                },
+                filled,
+                average_price: value.filter(|_| filled > 0.0).map(|v| v / filled),
+            })
+        }
+        .boxed()
+    }
+
+    fn cancel<'a>(&'a self, symbol: &'a str, order_id: &'a str) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let path = format!("/orders/{}?product_id={}-USD", order_id, symbol);
+            let _: serde_json::Value = self.send(reqwest::Method::DELETE, &path, None).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn open_orders(&self) -> BoxFuture<'_, Result<Vec<OpenOrder>>> {
+        async move {
+            let orders: Vec<CoinbaseOpenOrder> = self
+                .send(reqwest::Method::GET, "/orders?status=open", None)
+                .await?;
+            orders
+                .into_iter()
+                .map(|o| {
+                    Ok(OpenOrder {
+                        symbol: o.product_id.trim_end_matches("-USD").to_string(),
+                        id: o.id,
+                        side: o.side,
+                        quantity: o.size.parse()?,
+                        filled: o.filled_size.parse()?,
+                    })
+                })
+                .collect()
+        }
+        .boxed()
+    }
+
+    fn positions(&self) -> BoxFuture<'_, Result<Vec<Position>>> {
+        async move {
+            let account = self.balances().await?;
+            Ok(account
+                .balances
+                .into_iter()
+                .filter(|(currency, amount)| {
+                    *amount != 0.0 && !CASH_CURRENCIES.contains(&currency.as_str())
+                })
+                .map(|(symbol, quantity)| Position {
+                    symbol,
+                    quantity,
+                    entry_price: None,
+                })
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn balances(&self) -> BoxFuture<'_, Result<Account>> {
+        async move {
+            let accounts: Vec<CoinbaseAccount> =
+                self.send(reqwest::Method::GET, "/accounts", None).await?;
+            let mut account = Account::default();
+            for a in accounts {
+                let balance: f64 = a.balance.parse()?;
+                if balance != 0.0 {
+                    account.deposit(&a.currency, balance);
+                }
+            }
+            Ok(account)
+        }
+        .boxed()
+    }
+}
+
+#[cfg(feature = "native")]
+const BINANCE_URL: &str = "https://fapi.binance.com";
+
+/// Orders on Binance USDT-margined perpetuals, in `<symbol>USDT` contracts, which can be
+/// held short.
+#[cfg(feature = "native")]
+pub struct BinanceBroker {
+    key: String,
+    secret: String,
+    client: reqwest::Client,
+    /// Each contract's quantity step and price tick, fetched on the first order.
+    filters: Mutex<Option<BTreeMap<String, ContractFilters>>>,
+}
+
+/// The increments a contract's orders are quoted in, as Binance's `LOT_SIZE` and
+/// `PRICE_FILTER` give them, e.g. `"0.001"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractFilters {
+    pub step: String,
+    pub tick: String,
+}
+
+impl ContractFilters {
+    /// `quantity` rounded down to a whole number of steps; `None` below one step.
+    pub fn quantity(&self, quantity: f64) -> Option<String> {
+        let steps = (quantity / parse_increment(&self.step)? + 1e-9).floor();
+        (steps >= 1.0).then(|| in_increments(steps, &self.step))
+    }
+
+    /// `price` rounded to the nearest tick.
+    pub fn price(&self, price: f64) -> Option<String> {
+        let ticks = (price / parse_increment(&self.tick)?).round();
+        Some(in_increments(ticks, &self.tick))
+    }
+}
+
+fn parse_increment(increment: &str) -> Option<f64> {
+    increment.parse().ok().filter(|&i: &f64| i > 0.0)
+}
+
+/// `count` increments, written with as many decimals as the increment is significant to:
+/// `"0.0010"` counts in thousandths.
+fn in_increments(count: f64, increment: &str) -> String {
+    let decimals = increment
+        .split_once('.')
+        .map_or(0, |(_, fraction)| fraction.trim_end_matches('0').len());
+    let value = count * increment.parse::<f64>().unwrap_or(0.0);
+    format!("{:.*}", decimals, value)
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinanceOrder {
+    order_id: i64,
+    status: String,
+    executed_qty: String,
+    avg_price: String,
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinanceOpenOrder {
+    order_id: i64,
+    symbol: String,
+    side: String,
+    orig_qty: String,
+    executed_qty: String,
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinancePosition {
+    symbol: String,
+    position_amt: String,
+    entry_price: String,
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+struct BinanceExchangeInfo {
+    symbols: Vec<BinanceContract>,
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+struct BinanceContract {
+    symbol: String,
+    filters: Vec<serde_json::Value>,
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+struct BinanceBalance {
+    asset: String,
+    balance: String,
+}
+
+#[cfg(feature = "native")]
+impl BinanceBroker {
+    pub fn from_env() -> Result<Self> {
+        let lookup = |name: &str| {
+            crate::secrets::secret(name).with_context(|| format!("{} is required", name))
+        };
+        Ok(Self {
+            key: lookup("BINANCE_API_KEY")?,
+            secret: lookup("BINANCE_API_SECRET")?,
+            client: reqwest::Client::new(),
+            filters: Mutex::new(None),
+        })
+    }
+
+    /// The quantity step and price tick of the `<symbol>USDT` contract, from the public
+    /// exchange info, fetched once.
+    async fn contract_filters(&self, symbol: &str) -> Result<ContractFilters> {
+        let contract = format!("{}USDT", symbol);
+        if let Some(filters) = self.filters.lock().unwrap().as_ref() {
+            return filters
+                .get(&contract)
+                .cloned()
+                .with_context(|| format!("No Binance contract {}", contract));
+        }
+        let info: BinanceExchangeInfo = self
+            .client
+            .get(format!("{}/fapi/v1/exchangeInfo", BINANCE_URL))
+            .timeout(std::time::Duration::from_secs(10))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let filter = |filters: &[serde_json::Value], kind: &str, field: &str| {
+            filters
+                .iter()
+                .find(|f| f["filterType"] == kind)
+                .and_then(|f| f[field].as_str())
+                .map(str::to_string)
+        };
+        let filters = info
+            .symbols
+            .into_iter()
+            .filter_map(|c| {
+                let step = filter(&c.filters, "LOT_SIZE", "stepSize")?;
+                let tick = filter(&c.filters, "PRICE_FILTER", "tickSize")?;
+                Some((c.symbol, ContractFilters { step, tick }))
+            })
+            .collect::<BTreeMap<_, _>>();
+        let found = filters.get(&contract).cloned();
+        *self.filters.lock().unwrap() = Some(filters);
+        found.with_context(|| format!("No Binance contract {}", contract))
+    }
+
+    /// A request signed as Binance requires: an HMAC-SHA256 of the query string, timestamp
+    /// included, appended as `signature`.
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        params: &[(&str, String)],
+    ) -> Result<T> {
+        let mut query = params
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>();
+        query.push(format!(
+            "timestamp={}",
+            chrono::Utc::now().timestamp_millis()
+        ));
+        let query = query.join("&");
+        let signature = crate::artifacts::hmac_sha256(self.secret.as_bytes(), query.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        let response = self
+            .client
+            .request(
+                method,
+                format!("{}{}?{}&signature={}", BINANCE_URL, path, query, signature),
+            )
+            .header("X-MBX-APIKEY", &self.key)
+            .timeout(std::time::Duration::from_secs(10))
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Binance API error on {}: {} - {}", path, status, text);
+        }
+        Ok(response.json().await?)
+    }
+}
+
+#[cfg(feature = "native")]
+impl Broker for BinanceBroker {
+    fn name(&self) -> &str {
+        "binance"
+    }
+
+    fn place<'a>(&'a self, order: &'a Order) -> BoxFuture<'a, Result<OrderAck>> {
+        async move {
+            let side = match order.side {
+                Side::Buy => "BUY",
+                Side::Sell => "SELL",
+            };
+            let filters = self.contract_filters(&order.symbol).await?;
+            let quantity = filters.quantity(order.quantity).with_context(|| {
+                format!(
+                    "{} {} is below the lot size {}",
+                    order.quantity, order.symbol, filters.step
+                )
+            })?;
+            let mut params = vec![
+                ("symbol", format!("{}USDT", order.symbol)),
+                ("side", side.to_string()),
+                ("quantity", quantity),
+                ("newClientOrderId", order.client_id.clone()),
+            ];
+            match order.limit {
+                Some(limit) => params.extend([
+                    ("type", "LIMIT".to_string()),
+                    ("timeInForce", "GTC".to_string()),
+                    (
+                        "price",
+                        filters.price(limit).context("Invalid Binance price tick")?,
+                    ),
+                ]),
+                None => params.push(("type", "MARKET".to_string())),
+            }
+            let placed: BinanceOrder = self
+                .send(reqwest::Method::POST, "/fapi/v1/order", &params)
+                .await?;
+            let filled = placed.executed_qty.parse().unwrap_or(0.0);
+            let average: f64 = placed.avg_price.parse().unwrap_or(0.0);
+            Ok(OrderAck {
+                id: placed.order_id.to_string(),
+                status: if placed.status == "FILLED" {
+                    OrderStatus::Filled
+                } else {
+                    OrderStatus::Open
+                },
+                filled,
+                average_price: (average > 0.0).then_some(average),
+            })
+        }
+        .boxed()
+    }
+
+    fn cancel<'a>(&'a self, symbol: &'a str, order_id: &'a str) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let params = [
+                ("symbol", format!("{}USDT", symbol)),
+                ("orderId", order_id.to_string()),
+            ];
+            let _: serde_json::Value = self
+                .send(reqwest::Method::DELETE, "/fapi/v1/order", &params)
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn open_orders(&self) -> BoxFuture<'_, Result<Vec<OpenOrder>>> {
+        async move {
+            let orders: Vec<BinanceOpenOrder> = self
+                .send(reqwest::Method::GET, "/fapi/v1/openOrders", &[])
+                .await?;
+            orders
+                .into_iter()
+                .map(|o| {
+                    Ok(OpenOrder {
+                        id: o.order_id.to_string(),
+                        symbol: o.symbol.trim_end_matches("USDT").to_string(),
+                        side: if o.side == "BUY" {
+                            Side::Buy
+                        } else {
+                            Side::Sell
+                        },
+                        quantity: o.orig_qty.parse()?,
+                        filled: o.executed_qty.parse()?,
+                    })
+                })
+                .collect()
+        }
+        .boxed()
+    }
+
+    fn positions(&self) -> BoxFuture<'_, Result<Vec<Position>>> {
+        async move {
+            let positions: Vec<BinancePosition> = self
+                .send(reqwest::Method::GET, "/fapi/v2/positionRisk", &[])
+                .await?;
+            let mut out = Vec::new();
+            for p in positions {
+                let quantity: f64 = p.position_amt.parse()?;
+                if quantity == 0.0 {
+                    continue;
+                }
+                out.push(Position {
+                    symbol: p.symbol.trim_end_matches("USDT").to_string(),
+                    quantity,
+                    entry_price: p.entry_price.parse().ok(),
+                });
+            }
+            Ok(out)
+        }
+        .boxed()
+    }
+
+    fn balances(&self) -> BoxFuture<'_, Result<Account>> {
+        async move {
+            let balances: Vec<BinanceBalance> = self
+                .send(reqwest::Method::GET, "/fapi/v2/balance", &[])
+                .await?;
+            let mut account = Account::default();
+            for b in balances {
+                let balance: f64 = b.balance.parse()?;
+                if balance != 0.0 {
+                    account.deposit(&b.asset, balance);
+                }
+            }
+            Ok(account)
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signals_through_paper_and_dry_run_brokers() {
+        let limits = RiskLimits {
+            notional: 1000.0,
+            max_position: 800.0,
+            ..Default::default()
+        };
+        let paper = PaperBroker::new(10_000.0);
+        futures::executor::block_on(async {
+            // Capped at the largest position: 8 units rather than 10
+            let ack = follow_signal(&paper, "ETH", Action::Long, 100.0, &limits, None, "a")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(ack.status, OrderStatus::Filled);
+            assert!((ack.filled - 8.0).abs() < 1e-9);
+            // Already there
+            let again = follow_signal(&paper, "ETH", Action::Long, 100.0, &limits, None, "b");
+            assert_eq!(again.await.unwrap(), None);
+
+            // Flipping short sells both the long and the new short, entered at 110
+            let ack = follow_signal(&paper, "ETH", Action::Short, 110.0, &limits, None, "c")
+                .await
+                .unwrap()
+                .unwrap();
+            assert!((ack.filled - (8.0 + 800.0 / 110.0)).abs() < 1e-9);
+            let positions = paper.positions().await.unwrap();
+            assert!((positions[0].quantity + 800.0 / 110.0).abs() < 1e-9);
+            assert_eq!(positions[0].entry_price, Some(110.0));
+
+            // The dry run sees the same position but changes nothing
+            let dry = DryRun(paper);
+            let ack = follow_signal(&dry, "ETH", Action::None, 110.0, &limits, None, "d")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(ack.status, OrderStatus::Simulated);
+            assert_eq!(dry.positions().await.unwrap().len(), 1);
+
+            // Long 8 at 100, then short at 110: 80 gained less fees on 1600 of notional
+            let cash = dry.balances().await.unwrap().balances[DEFAULT_CURRENCY];
+            let fees = (800.0 + 880.0 + 800.0) * FEE_RATE;
+            assert!((cash - (10_000.0 + 80.0 + 800.0 - fees)).abs() < 1e-9);
+
+            // A limit buy below the market rests until a later price crosses it
+            let paper = dry.0;
+            let target = target_quantity(Action::Long, 50.0, &limits);
+            let mut bid = rebalance_order("BTC", 0.0, target, 50.0, &limits, "e").unwrap();
+            bid.limit = Some(45.0);
+            let ack = paper.place(&bid).await.unwrap();
+            assert_eq!(ack.status, OrderStatus::Open);
+            assert_eq!(paper.open_orders().await.unwrap()[0].id, ack.id);
+            paper.cancel("BTC", &ack.id).await.unwrap();
+            assert!(paper.cancel("BTC", &ack.id).await.is_err());
+        });
+    }
+
+    #[test]
+    fn test_paper_margin_settlement() {
+        let limits = RiskLimits {
+            notional: 1000.0,
+            max_position: 800.0,
+            ..Default::default()
+        };
+        let paper = PaperBroker::on_margin(10_000.0);
+        let cash = |broker: &PaperBroker| broker.lock().account.balances[DEFAULT_CURRENCY];
+        futures::executor::block_on(async {
+            // Opening only pays the fee: the 800 of ETH is held on margin
+            follow_signal(&paper, "ETH", Action::Long, 100.0, &limits, None, "a")
+                .await
+                .unwrap();
+            assert!((cash(&paper) - (10_000.0 - 800.0 * FEE_RATE)).abs() < 1e-9);
+
+            // Flipping short realizes the long's 80 and opens the short at 110, on margin too
+            follow_signal(&paper, "ETH", Action::Short, 110.0, &limits, None, "b")
+                .await
+                .unwrap();
+            let fees = (800.0 + 880.0 + 800.0) * FEE_RATE;
+            assert!((cash(&paper) - (10_000.0 + 80.0 - fees)).abs() < 1e-9);
+
+            // Covering at 99 realizes the short's 11 a unit
+            follow_signal(&paper, "ETH", Action::None, 99.0, &limits, None, "c")
+                .await
+                .unwrap();
+            let covered = 800.0 / 110.0;
+            let fees = fees + covered * 99.0 * FEE_RATE;
+            let pnl = 80.0 + covered * 11.0;
+            assert!((cash(&paper) - (10_000.0 + pnl - fees)).abs() < 1e-9);
+            assert!(paper.positions().await.unwrap().is_empty());
+        });
+    }
+
+    /// A paper account that can't short, as on a spot venue.
+    struct Spot(PaperBroker);
+
+    impl Broker for Spot {
+        fn name(&self) -> &str {
+            "spot"
+        }
+
+        fn can_short(&self) -> bool {
+            false
+        }
+
+        fn place<'a>(&'a self, order: &'a Order) -> BoxFuture<'a, Result<OrderAck>> {
+            self.0.place(order)
+        }
+
+        fn cancel<'a>(&'a self, symbol: &'a str, order_id: &'a str) -> BoxFuture<'a, Result<()>> {
+            self.0.cancel(symbol, order_id)
+        }
+
+        fn open_orders(&self) -> BoxFuture<'_, Result<Vec<OpenOrder>>> {
+            self.0.open_orders()
+        }
+
+        fn positions(&self) -> BoxFuture<'_, Result<Vec<Position>>> {
+            self.0.positions()
+        }
+
+        fn balances(&self) -> BoxFuture<'_, Result<Account>> {
+            self.0.balances()
+        }
+    }
+
+    #[test]
+    fn test_spot_shorts_and_contract_increments() {
+        let limits = RiskLimits::default();
+        let spot = DryRun(Spot(PaperBroker::new(10_000.0)));
+        assert!(!spot.can_short());
+        futures::executor::block_on(async {
+            follow_signal(&spot.0, "ETH", Action::Long, 100.0, &limits, None, "a")
+                .await
+                .unwrap();
+            // A short signal only sells the long
+            let ack = follow_signal(&spot.0, "ETH", Action::Short, 100.0, &limits, None, "b")
+                .await
+                .unwrap()
+                .unwrap();
+            assert!((ack.filled - 10.0).abs() < 1e-9);
+            assert!(spot.positions().await.unwrap().is_empty());
+            let again = follow_signal(&spot.0, "ETH", Action::Short, 100.0, &limits, None, "c");
+            assert_eq!(again.await.unwrap(), None);
+        });
+
+        let btc = ContractFilters {
+            step: "0.001".into(),
+            tick: "0.10".into(),
+        };
+        assert_eq!(btc.quantity(0.0149).as_deref(), Some("0.014"));
+        assert_eq!(btc.quantity(0.0009), None);
+        assert_eq!(btc.price(64_123.46).as_deref(), Some("64123.5"));
+        let doge = ContractFilters {
+            step: "1".into(),
+            tick: "0.00001".into(),
+        };
+        assert_eq!(doge.quantity(1234.9).as_deref(), Some("1234"));
+        assert_eq!(doge.price(0.123456).as_deref(), Some("0.12346"));
+    }
+}
@@ -0,0 +1,191 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+/// Scheduled events maintained by hand, relative to the working directory.
+pub const CALENDAR_FILE: &str = "calendar.json";
+/// How far ahead the prompt lists events.
+pub const EVENT_HORIZON: Duration = Duration::hours(24);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Fomc,
+    Cpi,
+    OptionsExpiry,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Impact {
+    Low,
+    Medium,
+    High,
+}
+
+fn default_impact() -> Impact {
+    Impact::High
+}
+
+/// A scheduled macro or crypto market event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CalendarEvent {
+    pub time: DateTime<Utc>,
+    pub name: String,
+    pub kind: EventKind,
+    #[serde(default = "default_impact")]
+    pub impact: Impact,
+}
+
+/// A source of scheduled events.
+pub trait CalendarProvider: Send + Sync {
+    /// Events at or after `from` and before `to`, in any order.
+    fn events(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<CalendarEvent>;
+}
+
+/// A fixed list of events, such as FOMC decisions and CPI releases from [`CALENDAR_FILE`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScheduledEvents(pub Vec<CalendarEvent>);
+
+/// Parses a calendar file: a JSON array of events, e.g.
+/// `[{"time": "2024-03-20T18:00:00Z", "name": "FOMC rate decision", "kind": "fomc"}]`.
+pub fn parse_calendar(json: &str) -> anyhow::Result<ScheduledEvents> {
+    Ok(ScheduledEvents(serde_json::from_str(json)?))
+}
+
+impl CalendarProvider for ScheduledEvents {
+    fn events(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<CalendarEvent> {
+        self.0
+            .iter()
+            .filter(|e| e.time >= from && e.time < to)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Monthly and quarterly crypto options expiries, computed: the last Friday of each month
+/// at 08:00 UTC, as on Deribit. Weekly expiries are too small to list.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OptionsExpiries;
+
+impl CalendarProvider for OptionsExpiries {
+    fn events(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<CalendarEvent> {
+        let mut events = Vec::new();
+        let mut day = from.date_naive();
+        while day <= to.date_naive() {
+            if let Some(event) = expiry_on(day).filter(|e| e.time >= from && e.time < to) {
+                events.push(event);
+            }
+            day = day.succ_opt().unwrap_or(NaiveDate::MAX);
+            if day == NaiveDate::MAX {
+                break;
+            }
+        }
+        events
+    }
+}
+
+/// The monthly options expiry on `day`, if it is the month's last Friday.
+fn expiry_on(day: NaiveDate) -> Option<CalendarEvent> {
+    let last_friday =
+        day.weekday() == Weekday::Fri && (day + Duration::days(7)).month() != day.month();
+    if !last_friday {
+        return None;
+    }
+    let quarterly = day.month().is_multiple_of(3);
+    Some(CalendarEvent {
+        time: Utc.from_utc_datetime(&day.and_hms_opt(8, 0, 0)?),
+        name: if quarterly {
+            "Quarterly crypto options expiry".to_string()
+        } else {
+            "Monthly crypto options expiry".to_string()
+        },
+        kind: EventKind::OptionsExpiry,
+        impact: Impact::High,
+    })
+}
+
+/// Every configured provider, merged.
+#[derive(Default)]
+pub struct Calendar {
+    providers: Vec<Box<dyn CalendarProvider>>,
+}
+
+impl Calendar {
+    pub fn with(mut self, provider: impl CalendarProvider + 'static) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+
+    /// High-impact events within [`EVENT_HORIZON`] after `now`, soonest first.
+    pub fn upcoming(&self, now: DateTime<Utc>) -> Vec<CalendarEvent> {
+        let mut events = self
+            .providers
+            .iter()
+            .flat_map(|p| p.events(now, now + EVENT_HORIZON))
+            .filter(|e| e.impact == Impact::High)
+            .collect::<Vec<_>>();
+        events.sort_by_key(|e| e.time);
+        events
+    }
+
+    /// The high-impact event within `window` of `now`, before or after, if any; signals
+    /// are held back during it.
+    pub fn blackout(&self, now: DateTime<Utc>, window: Duration) -> Option<CalendarEvent> {
+        self.providers
+            .iter()
+            .flat_map(|p| p.events(now - window, now + window + Duration::seconds(1)))
+            .filter(|e| e.impact == Impact::High)
+            .min_by_key(|e| (e.time - now).abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_options_expiries_fall_on_the_last_friday() {
+        let events = OptionsExpiries.events(at("2024-03-01T00:00:00Z"), at("2024-05-01T00:00:00Z"));
+        let times = events.iter().map(|e| e.time).collect::<Vec<_>>();
+        assert_eq!(
+            times,
+            [at("2024-03-29T08:00:00Z"), at("2024-04-26T08:00:00Z")]
+        );
+        assert_eq!(events[0].name, "Quarterly crypto options expiry");
+        assert_eq!(events[1].name, "Monthly crypto options expiry");
+    }
+
+    #[test]
+    fn test_upcoming_and_blackout() {
+        let scheduled = parse_calendar(
+            r#"[
+                {"time": "2024-03-20T18:00:00Z", "name": "FOMC rate decision", "kind": "fomc"},
+                {"time": "2024-03-20T12:30:00Z", "name": "Jobless claims", "kind": "other", "impact": "low"},
+                {"time": "2024-03-22T12:30:00Z", "name": "CPI", "kind": "cpi"}
+            ]"#,
+        )
+        .unwrap();
+        let calendar = Calendar::default().with(scheduled).with(OptionsExpiries);
+
+        let upcoming = calendar.upcoming(at("2024-03-20T10:00:00Z"));
+        let names = upcoming.iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["FOMC rate decision"]);
+
+        let window = Duration::minutes(30);
+        assert_eq!(calendar.blackout(at("2024-03-20T17:00:00Z"), window), None);
+        assert_eq!(
+            calendar
+                .blackout(at("2024-03-20T18:30:00Z"), window)
+                .map(|e| e.kind),
+            Some(EventKind::Fomc)
+        );
+        assert!(calendar
+            .blackout(at("2024-03-29T07:45:00Z"), window)
+            .is_some());
+    }
+}
@@ -0,0 +1,137 @@
+use serde::Serialize;
+
+use crate::simulator::LevelOutcome;
+
+/// Confidence buckets in the calibration table, each `1 / CALIBRATION_BUCKETS` wide.
+pub const CALIBRATION_BUCKETS: usize = 10;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CalibrationBucket {
+    pub lower: f64,
+    pub upper: f64,
+    pub predictions: usize,
+    pub mean_confidence: f64,
+    /// Fraction of the bucket's predictions that were correct.
+    pub accuracy: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CalibrationReport {
+    pub predictions: usize,
+    /// Predictions that stated a usable invalidation or target price.
+    pub with_levels: usize,
+    pub target_hit: usize,
+    pub invalidated: usize,
+    /// Fraction of predictions with levels whose target was reached before invalidation.
+    pub target_hit_rate: f64,
+    pub with_confidence: usize,
+    /// Mean squared error of confidence against realized correctness; lower is better and
+    /// 0.25 is what a constant 50% guess scores.
+    pub brier_score: Option<f64>,
+    /// Non-empty confidence buckets, lowest first.
+    pub buckets: Vec<CalibrationBucket>,
+}
+
+/// Accumulates target and confidence statistics one verified prediction at a time, so
+/// backtests, streaming evaluation and live history share the same figures.
+#[derive(Debug, Clone, Default)]
+pub struct CalibrationTracker {
+    predictions: usize,
+    with_levels: usize,
+    target_hit: usize,
+    invalidated: usize,
+    with_confidence: usize,
+    squared_error: f64,
+    // Per bucket: (count, summed confidence, correct)
+    buckets: [(usize, f64, usize); CALIBRATION_BUCKETS],
+}
+
+impl CalibrationTracker {
+    pub fn record(&mut self, correct: bool, confidence: Option<f64>, levels: Option<LevelOutcome>) {
+        self.predictions += 1;
+        match levels {
+            Some(LevelOutcome::Target) => self.target_hit += 1,
+            Some(LevelOutcome::Invalidated) => self.invalidated += 1,
+            _ => {}
+        }
+        if levels.is_some() {
+            self.with_levels += 1;
+        }
+
+        if let Some(c) = confidence.filter(|c| (0.0..=1.0).contains(c)) {
+            let outcome = if correct { 1.0 } else { 0.0 };
+            self.with_confidence += 1;
+            self.squared_error += (c - outcome).powi(2);
+            let bucket = &mut self.buckets
+                [((c * CALIBRATION_BUCKETS as f64) as usize).min(CALIBRATION_BUCKETS - 1)];
+            bucket.0 += 1;
+            bucket.1 += c;
+            bucket.2 += correct as usize;
+        }
+    }
+
+    pub fn report(&self) -> CalibrationReport {
+        let width = 1.0 / CALIBRATION_BUCKETS as f64;
+        CalibrationReport {
+            predictions: self.predictions,
+            with_levels: self.with_levels,
+            target_hit: self.target_hit,
+            invalidated: self.invalidated,
+            target_hit_rate: ratio(self.target_hit, self.with_levels),
+            with_confidence: self.with_confidence,
+            brier_score: (self.with_confidence > 0)
+                .then(|| self.squared_error / self.with_confidence as f64),
+            buckets: self
+                .buckets
+                .iter()
+                .enumerate()
+                .filter(|(_, (count, _, _))| *count > 0)
+                .map(|(i, &(count, sum, correct))| CalibrationBucket {
+                    lower: i as f64 * width,
+                    upper: (i + 1) as f64 * width,
+                    predictions: count,
+                    mean_confidence: sum / count as f64,
+                    accuracy: ratio(correct, count),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn ratio(n: usize, d: usize) -> f64 {
+    if d == 0 {
+        0.0
+    } else {
+        n as f64 / d as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calibration_report() {
+        let mut tracker = CalibrationTracker::default();
+        tracker.record(true, Some(0.9), Some(LevelOutcome::Target));
+        tracker.record(false, Some(0.8), Some(LevelOutcome::Invalidated));
+        tracker.record(true, Some(1.0), Some(LevelOutcome::Open));
+        tracker.record(false, None, None);
+
+        let report = tracker.report();
+        assert_eq!(report.predictions, 4);
+        assert_eq!(report.with_levels, 3);
+        assert!((report.target_hit_rate - 1.0 / 3.0).abs() < 1e-12);
+        assert_eq!(report.with_confidence, 3);
+        let brier = (0.1f64.powi(2) + 0.8f64.powi(2)) / 3.0;
+        assert!((report.brier_score.unwrap() - brier).abs() < 1e-12);
+
+        // 0.8 and 0.9 fall in separate buckets; 1.0 shares the top one with 0.9
+        assert_eq!(report.buckets.len(), 2);
+        assert_eq!(report.buckets[1].predictions, 2);
+        assert_eq!(report.buckets[1].accuracy, 1.0);
+        assert!((report.buckets[1].mean_confidence - 0.95).abs() < 1e-12);
+
+        assert_eq!(CalibrationTracker::default().report().brier_score, None);
+    }
+}
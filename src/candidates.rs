@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+
+use crate::comparison::PromptScore;
+
+/// Where uploaded prompt candidates wait for review, `{name}.txt`, with the latest
+/// evaluation of each beside it as `{name}.json`. Unlike challengers they are never asked
+/// live.
+#[cfg(feature = "native")]
+pub const CANDIDATE_DIR: &str = "candidates";
+/// Windows in the validation set a candidate and the champion are scored on.
+pub const VALIDATION_WINDOWS: usize = 48;
+/// Largest candidate prompt accepted, in bytes.
+pub const MAX_CANDIDATE_BYTES: usize = 64 * 1024;
+
+/// Whether `name` can name a candidate: 1 to 64 ASCII letters, digits, `-` or `_`, so it is
+/// safe as a file name and in a URL path.
+pub fn is_valid_name(name: &str) -> bool {
+    (1..=64).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Why `prompt` can't be uploaded as candidate `name`, if it can't.
+pub fn candidate_problem(name: &str, prompt: &str) -> Option<String> {
+    if !is_valid_name(name) {
+        Some(format!(
+            "Candidate name {:?} must be 1 to 64 letters, digits, - or _",
+            name
+        ))
+    } else if prompt.trim().is_empty() {
+        Some("The candidate prompt is empty".to_string())
+    } else if prompt.len() > MAX_CANDIDATE_BYTES {
+        Some(format!(
+            "The candidate prompt is {} bytes; at most {} are accepted",
+            prompt.len(),
+            MAX_CANDIDATE_BYTES
+        ))
+    } else {
+        None
+    }
+}
+
+/// A candidate scored beside the champion it would replace, over the same validation
+/// windows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CandidateEvaluation {
+    pub champion: PromptScore,
+    pub candidate: PromptScore,
+}
+
+impl CandidateEvaluation {
+    /// Accuracy the candidate gains over the champion; negative when it loses some.
+    pub fn accuracy_gain(&self) -> f64 {
+        self.candidate.accuracy - self.champion.accuracy
+    }
+
+    /// Whether this is still an evaluation of the prompts with these versions. Editing
+    /// either prompt makes it stale.
+    pub fn is_current(&self, champion_version: &str, candidate_version: &str) -> bool {
+        self.champion.prompt_version == champion_version
+            && self.candidate.prompt_version == candidate_version
+    }
+}
+
+#[cfg(feature = "native")]
+fn candidate_path(name: &str, extension: &str) -> anyhow::Result<std::path::PathBuf> {
+    anyhow::ensure!(is_valid_name(name), "Invalid candidate name {:?}", name);
+    Ok(std::path::Path::new(CANDIDATE_DIR).join(format!("{name}.{extension}")))
+}
+
+/// Saves `prompt` as candidate `name`, replacing it and dropping its evaluation.
+#[cfg(feature = "native")]
+pub fn save_candidate(name: &str, prompt: &str) -> anyhow::Result<()> {
+    if let Some(problem) = candidate_problem(name, prompt) {
+        anyhow::bail!(problem);
+    }
+    std::fs::create_dir_all(CANDIDATE_DIR)?;
+    std::fs::write(candidate_path(name, "txt")?, prompt)?;
+    match std::fs::remove_file(candidate_path(name, "json")?) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Candidate `name`'s prompt, `None` when there is no such candidate.
+#[cfg(feature = "native")]
+pub fn load_candidate(name: &str) -> anyhow::Result<Option<String>> {
+    match std::fs::read_to_string(candidate_path(name, "txt")?) {
+        Ok(prompt) => Ok(Some(prompt)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Candidate `name`'s latest evaluation, `None` before its first.
+#[cfg(feature = "native")]
+pub fn load_evaluation(name: &str) -> anyhow::Result<Option<CandidateEvaluation>> {
+    let path = candidate_path(name, "json")?;
+    match std::fs::read_to_string(&path) {
+        Ok(json) => Ok(Some(serde_json::from_str(&json)?)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(feature = "native")]
+pub fn save_evaluation(name: &str, evaluation: &CandidateEvaluation) -> anyhow::Result<()> {
+    std::fs::write(
+        candidate_path(name, "json")?,
+        serde_json::to_string_pretty(evaluation)?,
+    )?;
+    Ok(())
+}
+
+/// Removes candidate `name` and its evaluation.
+#[cfg(feature = "native")]
+pub fn remove_candidate(name: &str) -> anyhow::Result<()> {
+    for extension in ["txt", "json"] {
+        match std::fs::remove_file(candidate_path(name, extension)?) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Names of the uploaded candidates, sorted.
+#[cfg(feature = "native")]
+pub fn candidate_names() -> anyhow::Result<Vec<String>> {
+    let Ok(dir) = std::fs::read_dir(CANDIDATE_DIR) else {
+        return Ok(Vec::new());
+    };
+    let mut names = Vec::new();
+    for entry in dir {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "txt") {
+            if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidate_names_and_staleness() {
+        assert!(is_valid_name("macd-focus_2"));
+        for name in ["", "../prompt", "a.txt", "with space", &"x".repeat(65)] {
+            assert!(!is_valid_name(name), "{name:?}");
+        }
+        assert_eq!(candidate_problem("ok", "Predict ETH"), None);
+        assert!(candidate_problem("ok", " \n").is_some());
+        assert!(candidate_problem("ok", &"x".repeat(MAX_CANDIDATE_BYTES + 1)).is_some());
+
+        let score = |name: &str, version: &str, accuracy: f64| PromptScore {
+            name: name.into(),
+            prompt_version: version.into(),
+            windows: VALIDATION_WINDOWS,
+            accuracy,
+            total_return: 0.0,
+        };
+        let evaluation = CandidateEvaluation {
+            champion: score("prompt.txt", "aaa", 0.5),
+            candidate: score("macd", "bbb", 0.625),
+        };
+        assert_eq!(evaluation.accuracy_gain(), 0.125);
+        assert!(evaluation.is_current("aaa", "bbb"));
+        // The champion has changed since
+        assert!(!evaluation.is_current("ccc", "bbb"));
+    }
+}
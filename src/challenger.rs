@@ -0,0 +1,76 @@
+use serde::Serialize;
+
+/// Live hours a challenger must be scored alongside the champion before it can be promoted.
+pub const PROMOTION_HOURS: usize = 72;
+/// One-sided z threshold (95%) the challenger's edge must clear.
+pub const PROMOTION_Z: f64 = 1.645;
+
+/// A challenger's record against the champion over the live hours both were asked.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HeadToHead {
+    /// Prompt version of a challenger prompt, or name of a shadow model.
+    pub challenger: String,
+    pub hours: usize,
+    pub champion_correct: usize,
+    pub challenger_correct: usize,
+    /// Hours only the champion got right.
+    pub champion_only: usize,
+    /// Hours only the challenger got right.
+    pub challenger_only: usize,
+}
+
+impl HeadToHead {
+    /// Tallies paired `(champion_correct, challenger_correct)` outcomes, one per hour.
+    pub fn from_outcomes(challenger: String, outcomes: &[(bool, bool)]) -> Self {
+        let count = |f: fn(&(bool, bool)) -> bool| outcomes.iter().filter(|o| f(o)).count();
+        Self {
+            challenger,
+            hours: outcomes.len(),
+            champion_correct: count(|o| o.0),
+            challenger_correct: count(|o| o.1),
+            champion_only: count(|o| o.0 && !o.1),
+            challenger_only: count(|o| o.1 && !o.0),
+        }
+    }
+
+    /// McNemar z statistic over the hours the two disagreed on; positive when the
+    /// challenger is ahead.
+    pub fn z_score(&self) -> f64 {
+        let discordant = self.champion_only + self.challenger_only;
+        if discordant == 0 {
+            return 0.0;
+        }
+        (self.challenger_only as f64 - self.champion_only as f64) / (discordant as f64).sqrt()
+    }
+
+    /// Whether the challenger has been scored for at least `min_hours` and is significantly
+    /// more accurate than the champion.
+    pub fn beats_champion(&self, min_hours: usize) -> bool {
+        self.hours >= min_hours && self.z_score() >= PROMOTION_Z
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_head_to_head_promotion() {
+        // 10 hours where only the challenger was right, 2 where only the champion was
+        let mut outcomes = vec![(false, true); 10];
+        outcomes.extend([(true, false); 2]);
+        outcomes.extend([(true, true); 60]);
+        let h2h = HeadToHead::from_outcomes("abc".to_string(), &outcomes);
+        assert_eq!(
+            (h2h.hours, h2h.champion_correct, h2h.challenger_correct),
+            (72, 62, 70)
+        );
+        assert!((h2h.z_score() - 8.0 / 12f64.sqrt()).abs() < 1e-9);
+        assert!(h2h.beats_champion(PROMOTION_HOURS));
+        assert!(!h2h.beats_champion(100));
+
+        let even = HeadToHead::from_outcomes("abc".to_string(), &[(true, true); 80]);
+        assert_eq!(even.z_score(), 0.0);
+        assert!(!even.beats_champion(PROMOTION_HOURS));
+    }
+}
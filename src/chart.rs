@@ -0,0 +1,219 @@
+use std::fmt::Write as FmtWrite;
+
+use crate::Action;
+
+/// Candles drawn in a signal chart.
+pub const CHART_HOURS: usize = 24;
+/// Where the daemon keeps each symbol's latest signal page, `{symbol}.html`.
+#[cfg(feature = "native")]
+pub const CHART_DIR: &str = "cache/charts";
+
+const WIDTH: f64 = 480.0;
+const HEIGHT: f64 = 240.0;
+const MARGIN: f64 = 12.0;
+/// Room right of the candles for the level labels and the direction arrow.
+const GUTTER: f64 = 96.0;
+const UP: &str = "#26a69a";
+const DOWN: &str = "#ef5350";
+const NEUTRAL: &str = "#9e9e9e";
+
+/// A small SVG chart of a signal: the window's last [`CHART_HOURS`] candles, dashed lines
+/// at the entry (the last close), stop and target, and an arrow in the predicted direction.
+pub fn render_signal_svg(
+    window: &[[f64; 6]],
+    action: Action,
+    stop: Option<f64>,
+    target: Option<f64>,
+) -> String {
+    let candles = &window[window.len().saturating_sub(CHART_HOURS)..];
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{HEIGHT}\" \
+         viewBox=\"0 0 {WIDTH} {HEIGHT}\" font-family=\"sans-serif\" font-size=\"11\">\n\
+         <rect width=\"{WIDTH}\" height=\"{HEIGHT}\" fill=\"#ffffff\"/>\n"
+    );
+    let Some(entry) = candles.last().map(|c| c[4]) else {
+        svg.push_str("<text x=\"12\" y=\"24\">No candles</text>\n</svg>\n");
+        return svg;
+    };
+
+    let levels = [Some(entry), stop, target].into_iter().flatten();
+    let low = candles
+        .iter()
+        .map(|c| c[3])
+        .chain(levels.clone())
+        .fold(f64::INFINITY, f64::min);
+    let high = candles
+        .iter()
+        .map(|c| c[2])
+        .chain(levels)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let span = if high > low { high - low } else { 1.0 };
+    let y = |price: f64| MARGIN + (high - price) / span * (HEIGHT - 2.0 * MARGIN);
+    let slot = (WIDTH - GUTTER - MARGIN) / candles.len() as f64;
+
+    for (i, c) in candles.iter().enumerate() {
+        let x = MARGIN + slot * (i as f64 + 0.5);
+        let color = if c[4] >= c[1] { UP } else { DOWN };
+        let top = y(c[1].max(c[4]));
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{x:.1}\" y1=\"{:.1}\" x2=\"{x:.1}\" y2=\"{:.1}\" stroke=\"{color}\"/>",
+            y(c[2]),
+            y(c[3])
+        );
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{top:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{color}\"/>",
+            x - slot * 0.3,
+            slot * 0.6,
+            (y(c[1].min(c[4])) - top).max(1.0)
+        );
+    }
+
+    let right = WIDTH - GUTTER;
+    for (label, price, color) in [
+        ("entry", Some(entry), NEUTRAL),
+        ("stop", stop, DOWN),
+        ("target", target, UP),
+    ] {
+        let Some(price) = price else { continue };
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{MARGIN}\" y1=\"{0:.1}\" x2=\"{right}\" y2=\"{0:.1}\" stroke=\"{color}\" \
+             stroke-dasharray=\"4 3\"/>\n\
+             <text x=\"{1}\" y=\"{2:.1}\" fill=\"{color}\">{label} {price:.2}</text>",
+            y(price),
+            right + 4.0,
+            y(price) + 4.0
+        );
+    }
+
+    let (x, tip) = (WIDTH - 16.0, y(entry));
+    let _ = match action {
+        Action::Long => writeln!(
+            svg,
+            "<polygon points=\"{x},{:.1} {:.1},{:.1} {:.1},{:.1}\" fill=\"{UP}\"/>",
+            tip - 10.0,
+            x - 7.0,
+            tip + 4.0,
+            x + 7.0,
+            tip + 4.0
+        ),
+        Action::Short => writeln!(
+            svg,
+            "<polygon points=\"{x},{:.1} {:.1},{:.1} {:.1},{:.1}\" fill=\"{DOWN}\"/>",
+            tip + 10.0,
+            x - 7.0,
+            tip - 4.0,
+            x + 7.0,
+            tip - 4.0
+        ),
+        Action::None => writeln!(
+            svg,
+            "<circle cx=\"{x}\" cy=\"{tip:.1}\" r=\"5\" fill=\"{NEUTRAL}\"/>"
+        ),
+    };
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// A line chart of `points` (time, value) in `color`, with the range of values labeled.
+pub fn render_line_svg(points: &[(f64, f64)], color: &str) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{HEIGHT}\" \
+         viewBox=\"0 0 {WIDTH} {HEIGHT}\" font-family=\"sans-serif\" font-size=\"11\">\n\
+         <rect width=\"{WIDTH}\" height=\"{HEIGHT}\" fill=\"#ffffff\"/>\n"
+    );
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        svg.push_str("<text x=\"12\" y=\"24\">No data</text>\n</svg>\n");
+        return svg;
+    };
+    let low = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    let high = points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+    let span = if high > low { high - low } else { 1.0 };
+    let duration = if last.0 > first.0 {
+        last.0 - first.0
+    } else {
+        1.0
+    };
+    let right = WIDTH - GUTTER;
+    let line = points
+        .iter()
+        .map(|(t, v)| {
+            format!(
+                "{:.1},{:.1}",
+                MARGIN + (t - first.0) / duration * (right - MARGIN),
+                MARGIN + (high - v) / span * (HEIGHT - 2.0 * MARGIN)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    let _ = writeln!(
+        svg,
+        "<polyline points=\"{line}\" fill=\"none\" stroke=\"{color}\" stroke-width=\"1.5\"/>\n\
+         <text x=\"{0}\" y=\"{1}\" fill=\"{NEUTRAL}\">{high:.4}</text>\n\
+         <text x=\"{0}\" y=\"{2}\" fill=\"{NEUTRAL}\">{low:.4}</text>",
+        right + 4.0,
+        MARGIN + 4.0,
+        HEIGHT - MARGIN + 4.0
+    );
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Standalone HTML page of a signal with its chart embedded, for reviewing it at a glance.
+pub fn signal_html(symbol: &str, action: Action, rationale: &str, chart: &str) -> String {
+    let action = match action {
+        Action::Long => "long",
+        Action::Short => "short",
+        Action::None => "none",
+    };
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{symbol} signal</title></head>\n<body>\n\
+         <h1>{symbol}: {action}</h1>\n{chart}<p>{}</p>\n</body>\n</html>\n",
+        escape_html(rationale)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_chart_draws_candles_levels_and_arrow() {
+        let window = (0..30)
+            .map(|i| {
+                let open = 100.0 + i as f64;
+                [
+                    i as f64 * 3600.0,
+                    open,
+                    open + 2.0,
+                    open - 2.0,
+                    open + 1.0,
+                    1.0,
+                ]
+            })
+            .collect::<Vec<_>>();
+        let svg = render_signal_svg(&window, Action::Long, Some(120.0), Some(140.0));
+        // A background and one body per candle of the last day
+        assert_eq!(svg.matches("<rect").count(), 1 + CHART_HOURS);
+        assert!(svg.contains("entry 130.00") && svg.contains("stop 120.00"));
+        // The target is above every candle, so it sets the top of the scale
+        assert!(svg.contains(&format!("y1=\"{MARGIN:.1}\"")));
+        assert!(svg.contains(&format!("fill=\"{UP}\"/>\n</svg>")));
+
+        let flat = render_signal_svg(&window, Action::None, None, None);
+        assert!(!flat.contains("target") && flat.contains("<circle"));
+        assert!(render_signal_svg(&[], Action::Short, None, None).contains("No candles"));
+
+        let page = signal_html("ETH", Action::Long, "RSI < 30 & rising", &svg);
+        assert!(page.contains("<h1>ETH: long</h1>") && page.contains("RSI &lt; 30 &amp; rising"));
+    }
+}
@@ -0,0 +1,77 @@
+use std::fmt::Write as _;
+
+use serde::{Deserialize, Serialize};
+
+/// One prompt's result in a comparison scored over a shared pass of the same windows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptScore {
+    pub name: String,
+    pub prompt_version: String,
+    pub windows: usize,
+    pub accuracy: f64,
+    /// Compounded return of the simulated next-candle trades.
+    pub total_return: f64,
+}
+
+/// Sorts `scores` best first: by accuracy, then by return.
+pub fn rank_scores(scores: &mut [PromptScore]) {
+    scores.sort_by(|a, b| {
+        b.accuracy
+            .total_cmp(&a.accuracy)
+            .then(b.total_return.total_cmp(&a.total_return))
+    });
+}
+
+/// The scores as a table, one line per prompt in the order given.
+pub fn describe_comparison(scores: &[PromptScore]) -> String {
+    let mut out = format!(
+        "{:>4}  {:<24} {:<16} {:>7} {:>9} {:>9}\n",
+        "rank", "prompt", "version", "windows", "accuracy", "return"
+    );
+    for (rank, s) in scores.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "{:>4}  {:<24} {:<16} {:>7} {:>8.1}% {:>+8.2}%",
+            rank + 1,
+            s.name,
+            s.prompt_version,
+            s.windows,
+            s.accuracy * 100.0,
+            s.total_return * 100.0
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(name: &str, accuracy: f64, total_return: f64) -> PromptScore {
+        PromptScore {
+            name: name.to_string(),
+            prompt_version: format!("{}-version", name),
+            windows: 40,
+            accuracy,
+            total_return,
+        }
+    }
+
+    #[test]
+    fn test_rank_and_describe() {
+        let mut scores = vec![
+            score("a.txt", 0.5, 0.01),
+            score("b.txt", 0.6, -0.02),
+            score("c.txt", 0.6, 0.03),
+        ];
+        rank_scores(&mut scores);
+        let names = scores.iter().map(|s| s.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["c.txt", "b.txt", "a.txt"]);
+
+        let table = describe_comparison(&scores);
+        assert_eq!(
+            table.lines().nth(1).unwrap(),
+            "   1  c.txt                    c.txt-version         40     60.0%    +3.00%"
+        );
+    }
+}
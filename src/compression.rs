@@ -0,0 +1,128 @@
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+pub const ZSTD_LEVEL: i32 = 3;
+const EXTENSION: &str = "zst";
+
+/// Where the compressed copy of a logical cache path lives (`<path>.zst`).
+pub fn compressed_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(EXTENSION);
+    PathBuf::from(name)
+}
+
+/// Strips the compression suffix from an on-disk file name, if present.
+pub fn logical_name(file_name: &str) -> &str {
+    file_name.strip_suffix(".zst").unwrap_or(file_name)
+}
+
+/// Reads a cache entry by its logical path, decompressing it. An existing uncompressed
+/// entry from before compression was introduced is migrated in place.
+pub fn read(path: &Path) -> Result<Option<Vec<u8>>> {
+    let compressed = compressed_path(path);
+    if compressed.exists() {
+        let bytes = fs::read(&compressed)?;
+        let data = zstd::decode_all(bytes.as_slice())
+            .with_context(|| format!("Failed to decompress {}", compressed.display()))?;
+        return Ok(Some(data));
+    }
+
+    if path.exists() {
+        let data = fs::read(path)?;
+        write(path, &data)?;
+        fs::remove_file(path)?;
+        tracing::debug!(path = %path.display(), "Migrated cache entry to zstd");
+        return Ok(Some(data));
+    }
+
+    Ok(None)
+}
+
+/// Opens a cache entry for streaming reads, decompressing on the fly. Unlike [`read`]
+/// this leaves a legacy uncompressed entry untouched.
+pub fn reader(path: &Path) -> Result<Option<Box<dyn BufRead>>> {
+    let compressed = compressed_path(path);
+    if compressed.exists() {
+        let decoder = zstd::stream::read::Decoder::new(File::open(&compressed)?)
+            .with_context(|| format!("Failed to decompress {}", compressed.display()))?;
+        return Ok(Some(Box::new(BufReader::new(decoder))));
+    }
+    if path.exists() {
+        return Ok(Some(Box::new(BufReader::new(File::open(path)?))));
+    }
+    Ok(None)
+}
+
+/// Compresses and writes a cache entry, via a temporary file so readers never observe
+/// a partial write.
+pub fn write(path: &Path, data: &[u8]) -> Result<()> {
+    let compressed = compressed_path(path);
+    let tmp = compressed.with_extension("zst.tmp");
+    let bytes = zstd::encode_all(data, ZSTD_LEVEL)?;
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, &compressed)?;
+    Ok(())
+}
+
+/// Removes a cache entry in either representation.
+pub fn remove(path: &Path) -> Result<()> {
+    for p in [compressed_path(path), path.to_path_buf()] {
+        if p.exists() {
+            fs::remove_file(p)?;
+        }
+    }
+    Ok(())
+}
+
+/// On-disk size of a cache entry in either representation.
+pub fn metadata(path: &Path) -> Result<fs::Metadata> {
+    let compressed = compressed_path(path);
+    let p = if compressed.exists() {
+        compressed.as_path()
+    } else {
+        path
+    };
+    Ok(fs::metadata(p)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_roundtrip_and_migration() {
+        let dir = std::env::temp_dir().join("happycharts_compression");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("entry.json");
+        assert!(read(&path).unwrap().is_none());
+
+        write(&path, b"hello").unwrap();
+        assert!(compressed_path(&path).exists());
+        assert_eq!(read(&path).unwrap().as_deref(), Some(&b"hello"[..]));
+        let mut streamed = String::new();
+        reader(&path)
+            .unwrap()
+            .unwrap()
+            .read_to_string(&mut streamed)
+            .unwrap();
+        assert_eq!(streamed, "hello");
+
+        // A legacy plain file is migrated on first read
+        let legacy = dir.join("legacy.json");
+        fs::write(&legacy, b"old data").unwrap();
+        assert_eq!(read(&legacy).unwrap().as_deref(), Some(&b"old data"[..]));
+        assert!(!legacy.exists());
+        assert!(compressed_path(&legacy).exists());
+
+        remove(&path).unwrap();
+        assert!(read(&path).unwrap().is_none());
+        assert_eq!(logical_name("ETH_3600.jsonl.zst"), "ETH_3600.jsonl");
+    }
+}
@@ -0,0 +1,269 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::calendar::CalendarEvent;
+use crate::data_quality::{
+    anomalies_in_window, filter_candles, AnomalyPolicy, CandleAnomaly, OUTLIER_SIGMA,
+};
+use crate::features::FeatureRegistry;
+use crate::prediction::Strictness;
+use crate::prompt_builder::{
+    build_allocation_instruction, build_anomaly_notes, build_data_section,
+    build_multi_asset_instruction, build_pair_instruction, SymbolRole, SymbolSpec,
+};
+use crate::snapshot::{AuxContext, MarketSnapshot};
+
+// Profit threshold multipliers
+pub const LONG_THRESHOLD: f64 = 1.05;
+pub const SHORT_THRESHOLD: f64 = 0.95;
+
+// Next-candle relative return (target vs hedge) that labels a pair trade
+pub const PAIR_THRESHOLD: f64 = 0.005;
+
+// How glitchy candles are handled before labeling and prompting
+pub const ANOMALY_POLICY: AnomalyPolicy = AnomalyPolicy::Correct;
+
+// How strictly model responses are validated before they are scored. A rejected response
+// is re-asked once with a corrective instruction before it counts as a failure.
+pub const RESPONSE_STRICTNESS: Strictness = Strictness::Strict;
+
+// Role of each symbol in the prompt's data section; unlisted symbols are plain context
+pub const PROMPT_SYMBOLS: [SymbolSpec<'static>; 3] = [
+    SymbolSpec {
+        symbol: "ETH",
+        role: SymbolRole::Target,
+        description: None,
+    },
+    SymbolSpec {
+        symbol: "BTC",
+        role: SymbolRole::Context,
+        description: Some("market leader"),
+    },
+    SymbolSpec {
+        symbol: "SOL",
+        role: SymbolRole::Context,
+        description: None,
+    },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Long,
+    Short,
+    #[default]
+    None,
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Short, stable identifier of a base prompt's text, used to attribute results to it.
+pub fn prompt_version(base_prompt: &str) -> String {
+    sha256_hex(base_prompt.as_bytes())[..16].to_string()
+}
+
+/// Runs the data-quality pass on chronological candle arrays.
+pub fn prepare_candles(symbol: &str, data: Vec<[f64; 6]>) -> (Vec<[f64; 6]>, Vec<CandleAnomaly>) {
+    let (data, anomalies) = filter_candles(data, ANOMALY_POLICY, OUTLIER_SIGMA);
+    if !anomalies.is_empty() {
+        tracing::debug!(symbol, ?anomalies, "Data-quality pass flagged candles");
+    }
+    (data, anomalies)
+}
+
+/// A symbol's prompt window together with the anomalies found in its full series.
+pub type AnnotatedWindow<'a> = (&'a str, &'a [[f64; 6]], &'a [CandleAnomaly]);
+
+/// Data-quality notes for the candles of each symbol's window, empty when nothing was flagged.
+pub fn window_anomaly_notes(series: &[AnnotatedWindow]) -> String {
+    series
+        .iter()
+        .map(|(symbol, window, anomalies)| {
+            build_anomaly_notes(symbol, &anomalies_in_window(anomalies, window))
+        })
+        .collect()
+}
+
+/// The configured [`PROMPT_SYMBOLS`] entry for `symbol`, or a plain context entry.
+pub fn symbol_spec(symbol: &str) -> SymbolSpec<'_> {
+    PROMPT_SYMBOLS
+        .iter()
+        .find(|s| s.symbol == symbol)
+        .copied()
+        .unwrap_or(SymbolSpec {
+            symbol,
+            role: SymbolRole::Context,
+            description: None,
+        })
+}
+
+/// The full model prompt for one window of any number of symbols: base instructions, the
+/// role-annotated candle data sections and any data-quality notes.
+pub fn assemble_prompt(base_prompt: &str, windows: &[AnnotatedWindow]) -> String {
+    assemble_snapshot_prompt(base_prompt, &MarketSnapshot::from_windows(windows))
+}
+
+/// The full model prompt for a snapshot, with whatever auxiliary context it carries.
+pub fn assemble_snapshot_prompt(base_prompt: &str, snapshot: &MarketSnapshot) -> String {
+    join_data_section(base_prompt, &build_data_section(snapshot))
+}
+
+/// The full model prompt from an already built data section, for callers that pair one
+/// window's data with several base prompts.
+pub fn join_data_section(base_prompt: &str, data_section: &str) -> String {
+    format!("{}\n\n{}", base_prompt, data_section)
+}
+
+/// [`assemble_prompt`] deciding on `target` instead of the configured target symbol.
+pub fn assemble_target_prompt(
+    base_prompt: &str,
+    windows: &[AnnotatedWindow],
+    target: &str,
+) -> String {
+    assemble_snapshot_prompt(
+        base_prompt,
+        &MarketSnapshot::from_windows(windows).with_target(target),
+    )
+}
+
+/// [`assemble_target_prompt`], listing the upcoming calendar `events`, each symbol's
+/// auxiliary `context` and the standard features.
+pub fn assemble_market_prompt(
+    base_prompt: &str,
+    windows: &[AnnotatedWindow],
+    target: &str,
+    events: &[CalendarEvent],
+    context: &[(&str, AuxContext)],
+) -> String {
+    assemble_snapshot_prompt(
+        base_prompt,
+        &market_snapshot(windows, target, events, context),
+    )
+}
+
+/// The snapshot behind [`assemble_market_prompt`], with the
+/// [standard](FeatureRegistry::standard) features computed.
+pub fn market_snapshot<'a>(
+    windows: &[AnnotatedWindow<'a>],
+    target: &str,
+    events: &[CalendarEvent],
+    context: &[(&str, AuxContext)],
+) -> MarketSnapshot<'a> {
+    context
+        .iter()
+        .fold(
+            MarketSnapshot::from_windows(windows)
+                .with_target(target)
+                .with_events(events.to_vec()),
+            |snapshot, (symbol, context)| snapshot.with_context(symbol, context.clone()),
+        )
+        .with_features(&FeatureRegistry::standard())
+}
+
+/// [`assemble_prompt`], asking for a decision on every symbol in `windows`.
+pub fn assemble_multi_asset_prompt(base_prompt: &str, windows: &[AnnotatedWindow]) -> String {
+    let symbols = windows.iter().map(|(s, _, _)| *s).collect::<Vec<_>>();
+    format!(
+        "{}{}",
+        assemble_prompt(base_prompt, windows),
+        build_multi_asset_instruction(&symbols)
+    )
+}
+
+/// [`assemble_prompt`], asking for portfolio weights over every symbol in `windows`.
+pub fn assemble_allocation_prompt(base_prompt: &str, windows: &[AnnotatedWindow]) -> String {
+    let symbols = windows.iter().map(|(s, _, _)| *s).collect::<Vec<_>>();
+    format!(
+        "{}{}",
+        assemble_prompt(base_prompt, windows),
+        build_allocation_instruction(&symbols)
+    )
+}
+
+/// Labels each candle for the target-vs-hedge pair: `Long` when the target outperforms the
+/// hedge by at least [`PAIR_THRESHOLD`] over the next candle (long target, short hedge),
+/// `Short` when it underperforms by as much. The series must be aligned candle for candle.
+pub fn label_pair(target: &[[f64; 6]], hedge: &[[f64; 6]]) -> Vec<Action> {
+    const CLOSE: usize = 4;
+    let mut labels = target
+        .windows(2)
+        .zip(hedge.windows(2))
+        .map(|(t, h)| {
+            let relative = (t[1][CLOSE] / t[0][CLOSE]) / (h[1][CLOSE] / h[0][CLOSE]) - 1.0;
+            if relative >= PAIR_THRESHOLD {
+                Action::Long
+            } else if relative <= -PAIR_THRESHOLD {
+                Action::Short
+            } else {
+                Action::None
+            }
+        })
+        .collect::<Vec<_>>();
+    labels.resize(target.len().min(hedge.len()), Action::None);
+    labels
+}
+
+/// [`assemble_prompt`], asking whether `target` will out- or underperform `hedge`.
+pub fn assemble_pair_prompt(
+    base_prompt: &str,
+    windows: &[AnnotatedWindow],
+    target: &str,
+    hedge: &str,
+) -> String {
+    format!(
+        "{}{}",
+        assemble_prompt(base_prompt, windows),
+        build_pair_instruction(target, hedge, PAIR_THRESHOLD)
+    )
+}
+
+pub fn label_candles(data: &[[f64; 6]]) -> Vec<Action> {
+    label_candles_with(data, LONG_THRESHOLD, SHORT_THRESHOLD)
+}
+
+/// [`label_candles`] with other multipliers: `Long` when the next high reaches the close
+/// times `long_threshold`, `Short` when the next low reaches the close times
+/// `short_threshold`.
+pub fn label_candles_with(
+    data: &[[f64; 6]],
+    long_threshold: f64,
+    short_threshold: f64,
+) -> Vec<Action> {
+    use Action::*;
+    // For convenience, define indexes into the candle array
+    const HIGH: usize = 2;
+    const LOW: usize = 3;
+    const CLOSE: usize = 4;
+
+    let mut labels = data
+        .windows(2)
+        .map(|w| {
+            let current = &w[0];
+            let next = &w[1];
+
+            let c_close = current[CLOSE];
+            let next_high = next[HIGH];
+            let next_low = next[LOW];
+
+            let long_cond = next_high >= c_close * long_threshold;
+            let short_cond = next_low <= c_close * short_threshold;
+
+            match (long_cond, short_cond) {
+                (true, true) => Short, // tie-break: choose "short"
+                (true, false) => Long,
+                (false, true) => Short,
+                (false, false) => None,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // The last candle has no future candle, so "none"
+    labels.push(None);
+
+    labels
+}
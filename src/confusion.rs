@@ -0,0 +1,145 @@
+use serde::Serialize;
+
+use crate::Action;
+
+const ACTIONS: [Action; 3] = [Action::Long, Action::Short, Action::None];
+
+fn index(action: Action) -> usize {
+    match action {
+        Action::Long => 0,
+        Action::Short => 1,
+        Action::None => 2,
+    }
+}
+
+/// Counts of predicted against correct actions over a backtest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ConfusionMatrix {
+    /// Indexed `[label][prediction]`, each in the order long, short, none.
+    pub counts: [[usize; 3]; 3],
+}
+
+impl ConfusionMatrix {
+    pub fn record(&mut self, prediction: Action, label: Action) {
+        self.counts[index(label)][index(prediction)] += 1;
+    }
+
+    pub fn count(&self, prediction: Action, label: Action) -> usize {
+        self.counts[index(label)][index(prediction)]
+    }
+
+    /// Windows where `prediction` was given but was wrong.
+    pub fn false_calls(&self, prediction: Action) -> usize {
+        ACTIONS
+            .iter()
+            .filter(|&&label| label != prediction)
+            .map(|&label| self.count(prediction, label))
+            .sum()
+    }
+
+    /// Windows labeled `label` that got another answer.
+    pub fn misses(&self, label: Action) -> usize {
+        ACTIONS
+            .iter()
+            .filter(|&&prediction| prediction != label)
+            .map(|&prediction| self.count(prediction, label))
+            .sum()
+    }
+
+    /// The kind of mistake the matrix has most of, or `None` without any mistakes.
+    pub fn focus(&self) -> Option<ImprovementFocus> {
+        ImprovementFocus::ALL
+            .into_iter()
+            .map(|focus| (focus.errors(self), focus))
+            .filter(|(errors, _)| *errors > 0)
+            // The first listed wins a tie
+            .rev()
+            .max_by_key(|(errors, _)| *errors)
+            .map(|(_, focus)| focus)
+    }
+}
+
+/// A kind of mistake one improvement pass concentrates on, instead of asking the improver
+/// to do better in general.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImprovementFocus {
+    FalseLongs,
+    FalseShorts,
+    MissedLongs,
+    MissedShorts,
+}
+
+impl ImprovementFocus {
+    pub const ALL: [ImprovementFocus; 4] = [
+        ImprovementFocus::FalseLongs,
+        ImprovementFocus::FalseShorts,
+        ImprovementFocus::MissedLongs,
+        ImprovementFocus::MissedShorts,
+    ];
+
+    pub fn errors(&self, matrix: &ConfusionMatrix) -> usize {
+        match self {
+            ImprovementFocus::FalseLongs => matrix.false_calls(Action::Long),
+            ImprovementFocus::FalseShorts => matrix.false_calls(Action::Short),
+            ImprovementFocus::MissedLongs => matrix.misses(Action::Long),
+            ImprovementFocus::MissedShorts => matrix.misses(Action::Short),
+        }
+    }
+
+    /// Whether a wrong answer is the kind of mistake this pass is about.
+    pub fn matches(&self, prediction: Action, label: Action) -> bool {
+        prediction != label
+            && match self {
+                ImprovementFocus::FalseLongs => prediction == Action::Long,
+                ImprovementFocus::FalseShorts => prediction == Action::Short,
+                ImprovementFocus::MissedLongs => label == Action::Long,
+                ImprovementFocus::MissedShorts => label == Action::Short,
+            }
+    }
+
+    /// What the improver is asked to fix in this pass.
+    pub fn instruction(&self) -> &'static str {
+        match self {
+            ImprovementFocus::FalseLongs => "The model calls 'long' too often when the next candle does not rise enough. Make it demand stronger evidence before going long, without suppressing longs that are warranted.",
+            ImprovementFocus::FalseShorts => "The model calls 'short' too often when the next candle does not fall enough. Make it demand stronger evidence before going short, without suppressing shorts that are warranted.",
+            ImprovementFocus::MissedLongs => "The model misses windows where the next candle rises enough for a long. Make it recognise the setups that precede those moves, without adding false longs.",
+            ImprovementFocus::MissedShorts => "The model misses windows where the next candle falls enough for a short. Make it recognise the setups that precede those moves, without adding false shorts.",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Action::*;
+
+    #[test]
+    fn test_focus_follows_the_most_common_mistake() {
+        let mut matrix = ConfusionMatrix::default();
+        assert_eq!(matrix.focus(), Option::None);
+        for (prediction, label) in [(Long, Long), (Short, Short), (None, None)] {
+            matrix.record(prediction, label);
+        }
+        assert_eq!(matrix.focus(), Option::None);
+
+        // Two longs into flat candles and one into a drop
+        matrix.record(Long, None);
+        matrix.record(Long, None);
+        matrix.record(Long, Short);
+        matrix.record(None, Short);
+        assert_eq!(matrix.false_calls(Long), 3);
+        assert_eq!(matrix.misses(Short), 2);
+        assert_eq!(matrix.focus(), Some(ImprovementFocus::FalseLongs));
+
+        matrix.record(None, Short);
+        matrix.record(Short, Short);
+        matrix.record(None, Short);
+        assert_eq!(matrix.focus(), Some(ImprovementFocus::MissedShorts));
+
+        assert!(ImprovementFocus::MissedShorts.matches(Long, Short));
+        assert!(ImprovementFocus::FalseLongs.matches(Long, Short));
+        assert!(!ImprovementFocus::FalseLongs.matches(Long, Long));
+        assert!(!ImprovementFocus::MissedLongs.matches(Short, None));
+    }
+}
@@ -0,0 +1,957 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveTime, Timelike, Utc};
+use serde_json::json;
+
+use crate::analytics::AnalyticsStore;
+use crate::broker::{follow_signal, Broker, OrderAck, Owned, RiskLimits};
+use crate::chart::{render_signal_svg, signal_html, CHART_DIR};
+use crate::derisk::{equity, DeriskChange, DrawdownRule, DERISK_RESET_FILE};
+use crate::drift::{recorded_mix, DriftAlarm, DriftMonitor};
+use crate::exposure::CorrelationMatrix;
+use crate::health;
+use crate::live::latest_price;
+use crate::profiles::{describe_trackers, parse_profiles, SymbolProfile, VerificationTracker};
+use crate::reconcile::{
+    describe_discrepancies, recover, side_of, Bracket, ExecutionState, ReconcilePolicy,
+    EXECUTION_STATE_FILE,
+};
+use crate::reporting;
+use crate::signal_filter::SignalFilter;
+use crate::subscriptions::Subscription;
+use crate::thresholds::{load_labeling, LABELING_FILE};
+use crate::{run_symbol_analysis_at, Action, SymbolSignal};
+
+/// Wait after each interval boundary, so the exchange has published the candle that just
+/// opened.
+const SETTLE: chrono::Duration = chrono::Duration::seconds(30);
+
+/// Reads and validates the daemon's symbol profiles.
+pub fn load_profiles(path: &Path) -> Result<Vec<SymbolProfile>> {
+    let json = fs::read_to_string(path).with_context(|| {
+        format!(
+            "Failed to read {}; it lists the symbols to analyze, e.g. \
+             [{{\"symbol\": \"ETH\"}}, {{\"symbol\": \"BTC\", \"prompt\": \"prompts/btc.txt\"}}]",
+            path.display()
+        )
+    })?;
+    parse_profiles(&json).with_context(|| format!("Invalid profiles in {}", path.display()))
+}
+
+/// The first run after `now`: the next multiple of `interval` since the epoch, plus a
+/// settling delay.
+pub fn next_run(now: DateTime<Utc>, interval: std::time::Duration) -> DateTime<Utc> {
+    let secs = interval.as_secs().max(1) as i64;
+    let boundary = (now - SETTLE).timestamp().div_euclid(secs) * secs + secs;
+    DateTime::from_timestamp(boundary, 0).unwrap_or(now) + SETTLE
+}
+
+/// Whether the daemon is running for real or rehearsing a replay. A replay goes through the
+/// same steps, but its errors, warnings and heartbeats are only logged, so a rehearsal
+/// never pages anyone or vouches for the live daemon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Live,
+    Replay,
+}
+
+impl Mode {
+    async fn capture_error(
+        self,
+        err: &(dyn std::error::Error + 'static),
+        context: &[(&str, String)],
+    ) {
+        if self == Mode::Live {
+            reporting::capture_error(err, context).await;
+        }
+    }
+
+    async fn capture_warning(self, category: &str, message: &str, context: &[(&str, String)]) {
+        if self == Mode::Live {
+            reporting::capture_warning(category, message, context).await;
+        }
+    }
+
+    async fn ping_heartbeat(self, ok: bool) {
+        match self {
+            Mode::Live => health::ping_heartbeat(ok).await,
+            Mode::Replay => tracing::debug!(ok, "Replay: heartbeat not pinged"),
+        }
+    }
+}
+
+/// When the scheduler's runs happen, and which window each analyzes.
+enum Clock {
+    /// The wall clock: each run analyzes the latest candles.
+    Live,
+    /// Simulated time from `from`, `speed` times faster than real, while the boundaries
+    /// are at or before `end`: each run analyzes the candle that closed at its boundary.
+    Replay {
+        from: DateTime<Utc>,
+        now: DateTime<Utc>,
+        end: DateTime<Utc>,
+        speed: Speed,
+        started: tokio::time::Instant,
+    },
+}
+
+impl Clock {
+    /// A replay clock at the first run of `date`, at midnight, ending with its last run
+    /// or, on a day still under way, with the latest closed candle. `None` before the day
+    /// has started.
+    fn replay(date: NaiveDate, speed: Speed) -> Option<Self> {
+        let midnight = date.and_time(NaiveTime::MIN).and_utc();
+        let end =
+            (midnight + chrono::Duration::days(1) - chrono::Duration::seconds(1)).min(Utc::now());
+        (midnight <= end).then(|| Clock::Replay {
+            from: midnight + SETTLE,
+            now: midnight + SETTLE,
+            end,
+            speed,
+            started: tokio::time::Instant::now(),
+        })
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        match self {
+            Clock::Live => Utc::now(),
+            Clock::Replay { now, .. } => *now,
+        }
+    }
+
+    /// Open time of the last candle the run due now analyzes; `None` for the latest.
+    fn at(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Clock::Live => None,
+            Clock::Replay { now, .. } => {
+                Some(*now - SETTLE - chrono::Duration::seconds(crate::GRANULARITY as i64))
+            }
+        }
+    }
+
+    /// Waits for the [next run](next_run) after the current time; `false` when a replay
+    /// has none left.
+    async fn wait_for_next(&mut self, interval: std::time::Duration) -> bool {
+        let next = next_run(self.now(), interval);
+        tracing::debug!(%next, "Sleeping until the next run");
+        match self {
+            Clock::Live => {
+                tokio::time::sleep((next - Utc::now()).to_std().unwrap_or_default()).await;
+            }
+            Clock::Replay {
+                from,
+                now,
+                end,
+                speed,
+                started,
+            } => {
+                if next - SETTLE > *end {
+                    return false;
+                }
+                let elapsed = (next - *from).to_std().unwrap_or_default();
+                tokio::time::sleep_until(*started + elapsed.div_f64(speed.0)).await;
+                *now = next;
+            }
+        }
+        true
+    }
+}
+
+/// Posts `signal` to a webhook with its chart as SVG. A failed post is only logged.
+async fn notify(url: &str, signal: &SymbolSignal, chart: &str) {
+    let body = json!({
+        "symbol": signal.symbol,
+        "action": signal.action,
+        "confidence": signal.confidence,
+        "rationale": signal.rationale,
+        "window_end": signal.window_end,
+        "prompt_version": signal.prompt_version,
+        "signal_id": signal.signal_id,
+        "chart_svg": chart,
+    });
+    let sent = reqwest::Client::new()
+        .post(url)
+        .json(&body)
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status());
+    if let Err(err) = sent {
+        tracing::warn!(symbol = %signal.symbol, %err, "Signal webhook failed");
+    }
+}
+
+/// Replaces the symbol's page in [`CHART_DIR`] with `signal` and its chart. A failed write
+/// is only logged.
+fn write_signal_page(dir: &Path, signal: &SymbolSignal, chart: &str) {
+    let page = signal_html(&signal.symbol, signal.action, &signal.rationale, chart);
+    let path = dir.join(format!("{}.html", signal.symbol));
+    let written = fs::create_dir_all(dir).and_then(|_| fs::write(&path, page));
+    if let Err(err) = written {
+        tracing::warn!(symbol = %signal.symbol, %err, "Failed to write signal page");
+    }
+}
+
+/// Posts `alarm` to the profile's webhook, told apart from signals by its `kind`. A failed
+/// post is only logged.
+async fn notify_drift(url: &str, alarm: &DriftAlarm) {
+    let body = json!({
+        "kind": "action_drift",
+        "symbol": alarm.symbol,
+        "prompt_version": alarm.prompt_version,
+        "distance": alarm.distance,
+        "live": alarm.live,
+        "backtest": alarm.backtest,
+        "message": alarm.describe(),
+    });
+    let sent = reqwest::Client::new()
+        .post(url)
+        .json(&body)
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status());
+    if let Err(err) = sent {
+        tracing::warn!(symbol = %alarm.symbol, %err, "Drift webhook failed");
+    }
+}
+
+/// Posts a sizing change to the webhook, told apart from signals by its `kind`. A failed
+/// post is only logged.
+async fn notify_derisk(url: &str, change: &DeriskChange) {
+    let mut body = json!(change);
+    body["message"] = change.describe().into();
+    let sent = reqwest::Client::new()
+        .post(url)
+        .json(&body)
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status());
+    if let Err(err) = sent {
+        tracing::warn!(%err, "De-risk webhook failed");
+    }
+}
+
+/// Where the daemon sends orders for its signals, the limits they are checked against,
+/// what to do when the venue disagrees with the recorded state on startup, and when to cut
+/// sizing after a drawdown.
+pub struct Execution {
+    pub broker: Box<dyn Broker>,
+    pub limits: RiskLimits,
+    pub policy: ReconcilePolicy,
+    pub drawdown: Option<DrawdownRule>,
+}
+
+/// Marks the account's equity against its peak and returns the share of the usual
+/// notional to size positions to. Crossing the drawdown rule either way is alerted on and
+/// posted to every profile's webhook; a requested reset is applied first.
+async fn check_drawdown(
+    mode: Mode,
+    execution: &Execution,
+    state: &mut ExecutionState,
+    state_file: &Path,
+    prices: &CorrelationMatrix,
+    profiles: &[SymbolProfile],
+    now: DateTime<Utc>,
+) -> f64 {
+    let Some(rule) = &execution.drawdown else {
+        return 1.0;
+    };
+    let reset = Path::new(DERISK_RESET_FILE);
+    if reset.exists() {
+        state.drawdown.reset();
+        if let Err(err) = fs::remove_file(reset) {
+            tracing::warn!(%err, "Failed to remove the de-risk reset request");
+        }
+        tracing::info!("De-risking reset by request");
+    }
+    let broker = execution.broker.as_ref();
+    let marked =
+        async { Ok::<_, anyhow::Error>((broker.balances().await?, broker.positions().await?)) };
+    let (account, positions) = match marked.await {
+        Ok(marked) => marked,
+        Err(err) => {
+            tracing::warn!(%err, "Failed to mark equity; sizing unchanged");
+            return state.drawdown.sizing(rule);
+        }
+    };
+    let equity = equity(&account, &positions, prices);
+    let now = now.timestamp() as f64;
+    if let Some(change) = state.drawdown.update(equity, rule, now) {
+        tracing::warn!("{}", change.describe());
+        mode.capture_warning(
+            "derisk",
+            &change.describe(),
+            &[
+                ("source", "daemon".to_string()),
+                ("broker", broker.name().to_string()),
+            ],
+        )
+        .await;
+        let mut urls = profiles
+            .iter()
+            .filter_map(|p| p.webhook.as_deref())
+            .collect::<Vec<_>>();
+        urls.sort_unstable();
+        urls.dedup();
+        for url in urls {
+            notify_derisk(url, &change).await;
+        }
+    }
+    if let Err(err) = state.save(state_file) {
+        tracing::error!(%err, "Failed to save the execution state");
+    }
+    state.drawdown.sizing(rule)
+}
+
+/// The recorded execution state, reconciled with the venue per the policy. Discrepancies
+/// are alerted on like an error even when resolved.
+async fn recover_state(execution: &Execution) -> Result<ExecutionState> {
+    let path = Path::new(EXECUTION_STATE_FILE);
+    let mut state = ExecutionState::load(path)?;
+    let mut prices = Vec::new();
+    if execution.policy == ReconcilePolicy::Flatten {
+        for symbol in state.owned() {
+            let price = latest_price(&symbol).await?;
+            prices.push((symbol, price));
+        }
+    }
+    let found = recover(
+        execution.broker.as_ref(),
+        &mut state,
+        execution.policy,
+        &prices,
+    )
+    .await?;
+    if !found.is_empty() {
+        let message = format!(
+            "{} differed from the recorded execution state; resolved by {:?}:\n{}",
+            execution.broker.name(),
+            execution.policy,
+            describe_discrepancies(&found)
+        );
+        tracing::warn!("{}", message);
+        reporting::capture_warning(
+            "execution_reconcile",
+            &message,
+            &[
+                ("source", "daemon".to_string()),
+                ("broker", execution.broker.name().to_string()),
+            ],
+        )
+        .await;
+    }
+    state.save(path)?;
+    tracing::info!(
+        positions = state.positions.len(),
+        orders = state.orders.len(),
+        brackets = state.brackets.len(),
+        "Execution state recovered"
+    );
+    Ok(state)
+}
+
+fn log_order(symbol: &str, ack: &OrderAck) {
+    tracing::info!(
+        %symbol,
+        id = %ack.id,
+        status = ?ack.status,
+        filled = ack.filled,
+        "Order placed"
+    );
+}
+
+/// Closes the symbol's position when the latest candle reached its bracket, then moves it
+/// to what `signal` calls for at the window's last close, bracketed by the signal's levels
+/// and held within `limits` and the correlated exposure limit.
+async fn trade_signal(
+    execution: &Execution,
+    state: &mut ExecutionState,
+    signal: &SymbolSignal,
+    correlations: &CorrelationMatrix,
+    limits: &RiskLimits,
+) -> Result<()> {
+    // Only what the daemon bought is traded, never the account's own holdings
+    let external = state.external.clone();
+    let owned = Owned {
+        broker: execution.broker.as_ref(),
+        external: &external,
+    };
+    let broker: &dyn Broker = &owned;
+    let symbol = &signal.symbol;
+    let Some(candle) = signal.window.last() else {
+        return Ok(());
+    };
+    let client_id = format!("{}-{}", symbol, signal.window_end as i64);
+    if let Some(exit) = state.bracket(symbol).and_then(|b| b.triggered(candle)) {
+        tracing::info!(%symbol, exit, "Position reached its stop or target");
+        let exit_id = format!("{}-exit", client_id);
+        if let Some(ack) = follow_signal(
+            broker,
+            symbol,
+            Action::None,
+            exit,
+            limits,
+            Some(correlations),
+            &exit_id,
+        )
+        .await?
+        {
+            log_order(symbol, &ack);
+        }
+        state.set_bracket(symbol, None);
+    }
+
+    let price = candle[4];
+    let placed = follow_signal(
+        broker,
+        symbol,
+        signal.action,
+        price,
+        limits,
+        Some(correlations),
+        &client_id,
+    )
+    .await?;
+    if let Some(ack) = placed {
+        log_order(symbol, &ack);
+    }
+    state.adopt(broker.positions().await?, broker.open_orders().await?);
+    let held = state.held(symbol);
+    let holding = match signal.action {
+        Action::Long => held > 0.0,
+        Action::Short => held < 0.0,
+        Action::None => false,
+    };
+    let bracket =
+        (holding && (signal.stop.is_some() || signal.target.is_some())).then(|| Bracket {
+            symbol: symbol.clone(),
+            side: signal.action,
+            entry_price: price,
+            stop: signal.stop,
+            target: signal.target,
+        });
+    state.set_bracket(symbol, bracket);
+    Ok(())
+}
+
+/// Trades `signal` and saves the resulting state. A failed order is reported like a failed
+/// analysis but holds up nothing else.
+async fn execute(
+    mode: Mode,
+    execution: &Execution,
+    state: &mut ExecutionState,
+    state_file: &Path,
+    signal: &SymbolSignal,
+    correlations: &CorrelationMatrix,
+    limits: &RiskLimits,
+) {
+    let traded = trade_signal(execution, state, signal, correlations, limits).await;
+    if let Err(err) = state.save(state_file) {
+        tracing::error!(%err, "Failed to save the execution state");
+    }
+    match traded {
+        Ok(()) => {}
+        Err(err) => {
+            tracing::error!(symbol = %signal.symbol, %err, "Order failed");
+            mode.capture_error(
+                err.as_ref(),
+                &[
+                    ("source", "broker".to_string()),
+                    ("broker", execution.broker.name().to_string()),
+                    ("symbol", signal.symbol.clone()),
+                ],
+            )
+            .await;
+        }
+    }
+}
+
+/// Points `monitor` at the backtest action mix of `prompt_version` when the prompt changed
+/// or no backtest of it was recorded yet.
+fn refresh_baseline(monitor: &mut DriftMonitor, symbol: &str, prompt_version: &str) {
+    if monitor.prompt_version() == prompt_version && monitor.has_baseline() {
+        return;
+    }
+    match AnalyticsStore::default().predictions() {
+        Ok(records) => {
+            let mix = recorded_mix(&records, "backtest", symbol, prompt_version);
+            monitor.set_baseline(prompt_version, Some(mix));
+        }
+        Err(err) => {
+            tracing::warn!(%symbol, %err, "Failed to read the backtest action mix");
+            monitor.set_baseline(prompt_version, None);
+        }
+    }
+}
+
+/// The repeat filters of one profile's signals: its webhook's, and each subscription's.
+struct RepeatFilters {
+    webhook: SignalFilter,
+    subscriptions: Vec<SignalFilter>,
+}
+
+impl RepeatFilters {
+    fn new(profile: &SymbolProfile, subscriptions: &[Subscription]) -> Self {
+        Self {
+            webhook: SignalFilter::new(profile.repeat),
+            subscriptions: subscriptions
+                .iter()
+                .map(|s| SignalFilter::new(s.repeat))
+                .collect(),
+        }
+    }
+
+    /// The webhooks `signal` of `profile` is posted to at `now`: the profile's and those
+    /// of the subscriptions that want it, each unless its policy holds back a repeat of the
+    /// open position. With `held`, the side the daemon actually holds the symbol on and
+    /// whether its venue can short, that position is the one repeats are judged against.
+    fn recipients(
+        &mut self,
+        profile: &SymbolProfile,
+        subscriptions: &[Subscription],
+        signal: &SymbolSignal,
+        held: Option<(Action, bool)>,
+        now: DateTime<Utc>,
+    ) -> Vec<String> {
+        let symbol = &signal.symbol;
+        let time = now.timestamp() as f64;
+        let admit = |filter: &mut SignalFilter| {
+            if let Some((side, can_short)) = held {
+                // Flat is all a short signal leaves at a venue that can't short
+                let spot_short =
+                    !can_short && side == Action::None && filter.position(symbol) == Action::Short;
+                if !spot_short {
+                    filter.sync(symbol, side);
+                }
+            }
+            filter.admit(symbol, signal.action, time)
+        };
+        let mut urls = Vec::new();
+        if let Some(url) = &profile.webhook {
+            if admit(&mut self.webhook) {
+                urls.push(url.clone());
+            }
+        }
+        for (s, filter) in subscriptions.iter().zip(&mut self.subscriptions) {
+            if s.wants(symbol, signal.action, signal.confidence, now.hour()) && admit(filter) {
+                urls.push(s.webhook.clone());
+            }
+        }
+        urls
+    }
+}
+
+/// The daemon between runs: what it has tracked of each profile's signals and, with an
+/// execution, the positions it holds.
+struct Daemon {
+    mode: Mode,
+    profiles: Vec<SymbolProfile>,
+    subscriptions: Vec<Subscription>,
+    trackers: Vec<(String, VerificationTracker)>,
+    monitors: Vec<DriftMonitor>,
+    filters: Vec<RepeatFilters>,
+    execution: Option<Execution>,
+    state: Option<ExecutionState>,
+    /// Where the execution state is saved and the signal pages written.
+    state_file: PathBuf,
+    chart_dir: PathBuf,
+}
+
+impl Daemon {
+    fn new(
+        profiles: Vec<SymbolProfile>,
+        subscriptions: Vec<Subscription>,
+        execution: Option<Execution>,
+        state: Option<ExecutionState>,
+    ) -> Self {
+        Self {
+            mode: Mode::Live,
+            trackers: profiles
+                .iter()
+                .map(|p| (p.symbol.clone(), VerificationTracker::default()))
+                .collect(),
+            monitors: vec![DriftMonitor::default(); profiles.len()],
+            filters: profiles
+                .iter()
+                .map(|p| RepeatFilters::new(p, &subscriptions))
+                .collect(),
+            profiles,
+            subscriptions,
+            execution,
+            state,
+            state_file: EXECUTION_STATE_FILE.into(),
+            chart_dir: CHART_DIR.into(),
+        }
+    }
+
+    /// Analyzes every profile once, of the window ending with the candle opening at `at`
+    /// or of the latest, and acts on the signals. Fails naming the symbols whose analysis
+    /// failed, after acting on the others'.
+    async fn run(&mut self, at: Option<DateTime<Utc>>) -> Result<()> {
+        let now = at.map_or_else(Utc::now, |at| {
+            at + chrono::Duration::seconds(crate::GRANULARITY as i64)
+        });
+        let labeling = load_labeling(Path::new(LABELING_FILE))?;
+        let results =
+            futures::future::join_all(self.profiles.iter().map(|p| run_symbol_analysis_at(p, at)))
+                .await;
+        let windows = results
+            .iter()
+            .filter_map(|r| r.as_ref().ok())
+            .map(|s| (s.symbol.as_str(), &s.window[..]))
+            .collect::<Vec<_>>();
+        let correlations = CorrelationMatrix::from_windows(&windows);
+        let limits = match (&self.execution, &mut self.state) {
+            (Some(execution), Some(state)) => {
+                let sizing = check_drawdown(
+                    self.mode,
+                    execution,
+                    state,
+                    &self.state_file,
+                    &correlations,
+                    &self.profiles,
+                    now,
+                )
+                .await;
+                RiskLimits {
+                    notional: execution.limits.notional * sizing,
+                    ..execution.limits
+                }
+            }
+            _ => RiskLimits::default(),
+        };
+
+        let mut failed = Vec::new();
+        let symbols = self
+            .profiles
+            .iter()
+            .zip(&mut self.trackers)
+            .zip(&mut self.monitors)
+            .zip(&mut self.filters);
+        for ((((profile, (_, tracker)), monitor), filters), result) in symbols.zip(results) {
+            match result {
+                Ok(signal) => {
+                    let verified =
+                        tracker.verify(&signal.window, labeling.thresholds(&profile.symbol));
+                    tracker.record_signal(signal.window_end, signal.action);
+                    tracing::info!(
+                        symbol = %signal.symbol,
+                        action = ?signal.action,
+                        rationale = %signal.rationale,
+                        verified,
+                        "Live signal"
+                    );
+                    let chart = render_signal_svg(
+                        &signal.window,
+                        signal.action,
+                        signal.stop,
+                        signal.target,
+                    );
+                    write_signal_page(&self.chart_dir, &signal, &chart);
+                    // Judged against what was held before the signal is traded
+                    let held = match (&self.execution, &self.state) {
+                        (Some(execution), Some(state)) => Some((
+                            side_of(state.held(&signal.symbol)),
+                            execution.broker.can_short(),
+                        )),
+                        _ => None,
+                    };
+                    let urls = filters.recipients(profile, &self.subscriptions, &signal, held, now);
+                    for url in urls {
+                        notify(&url, &signal, &chart).await;
+                    }
+                    if let (Some(execution), Some(state)) = (&self.execution, &mut self.state) {
+                        execute(
+                            self.mode,
+                            execution,
+                            state,
+                            &self.state_file,
+                            &signal,
+                            &correlations,
+                            &limits,
+                        )
+                        .await;
+                    }
+                    refresh_baseline(monitor, &signal.symbol, &signal.prompt_version);
+                    if let Some(alarm) = monitor.record(&signal.symbol, signal.action) {
+                        tracing::warn!("{}", alarm.describe());
+                        self.mode
+                            .capture_warning(
+                                "action_drift",
+                                &alarm.describe(),
+                                &[
+                                    ("source", "daemon".to_string()),
+                                    ("symbol", alarm.symbol.clone()),
+                                    ("prompt_version", alarm.prompt_version.clone()),
+                                ],
+                            )
+                            .await;
+                        if let Some(url) = &profile.webhook {
+                            notify_drift(url, &alarm).await;
+                        }
+                    }
+                }
+                Err(err) => {
+                    tracing::error!(symbol = %profile.symbol, %err, "Symbol analysis failed");
+                    self.mode
+                        .capture_error(
+                            err.as_ref(),
+                            &[
+                                ("source", "daemon".to_string()),
+                                ("symbol", profile.symbol.clone()),
+                            ],
+                        )
+                        .await;
+                    failed.push(profile.symbol.as_str());
+                }
+            }
+        }
+        tracing::info!("Verified signals:\n{}", describe_trackers(&self.trackers));
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("Analysis failed for {}", failed.join(", ")))
+        }
+    }
+}
+
+/// Runs every profile's analysis once per `interval`, on interval boundaries, until the
+/// process is stopped. Symbols are analyzed concurrently and independently: one failing
+/// never holds up or suppresses the others' signals. Each symbol's earlier signals are
+/// verified against its next window as the candles close, and its recent actions compared
+/// with the prompt's backtest: a sharp drift is alerted on like an error. Signals go to the
+/// profile's webhook and to every subscription that wants them. With an
+/// `execution`, each signal is also traded through its broker, after the state recorded
+/// before a restart has been reconciled with the venue.
+pub async fn run_daemon(
+    profiles: Vec<SymbolProfile>,
+    subscriptions: Vec<Subscription>,
+    interval: std::time::Duration,
+    execution: Option<Execution>,
+) -> Result<()> {
+    health::mark_started();
+    let state = match &execution {
+        Some(execution) => Some(recover_state(execution).await?),
+        None => None,
+    };
+    tracing::info!(
+        symbols = ?profiles.iter().map(|p| &p.symbol).collect::<Vec<_>>(),
+        interval_secs = interval.as_secs(),
+        broker = ?execution.as_ref().map(|e| e.broker.name()),
+        subscriptions = subscriptions.len(),
+        "Daemon started"
+    );
+    let mut daemon = Daemon::new(profiles, subscriptions, execution, state);
+    schedule(&mut daemon, interval, Clock::Live).await;
+    Ok(())
+}
+
+/// Runs `daemon` now and then at every [`next_run`] of `clock`, recording each outcome in
+/// the health state and pinging the heartbeat. Returns the number of runs and of failed
+/// runs once a replay clock runs out; the wall clock never does.
+async fn schedule(
+    daemon: &mut Daemon,
+    interval: std::time::Duration,
+    mut clock: Clock,
+) -> (usize, usize) {
+    let (mut runs, mut failed) = (0, 0);
+    loop {
+        let at = clock.at();
+        if let Some(at) = at {
+            tracing::info!(%at, "Replaying the run");
+        }
+        let outcome = daemon.run(at).await;
+        health::record_analysis(&outcome);
+        daemon.mode.ping_heartbeat(outcome.is_ok()).await;
+        runs += 1;
+        if let Err(err) = &outcome {
+            tracing::warn!(at = ?at, %err, "Run failed");
+            failed += 1;
+        }
+        if !clock.wait_for_next(interval).await {
+            return (runs, failed);
+        }
+    }
+}
+
+/// Where a replay saves its execution state and writes its signal pages, apart from the
+/// daemon's.
+pub const REPLAY_DIR: &str = "cache/replay";
+
+/// How many times faster than real time a replay runs, e.g. `60x`: an hour of the day
+/// replayed per minute.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Speed(pub f64);
+
+impl std::str::FromStr for Speed {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_suffix('x').unwrap_or(s).parse::<f64>() {
+            Ok(speed) if speed.is_finite() && speed > 0.0 => Ok(Speed(speed)),
+            _ => anyhow::bail!("Unknown speed {:?} (expected a multiple like 60x)", s),
+        }
+    }
+}
+
+/// Replays `date` through the daemon's whole pipeline at `speed`: its scheduler, stepping
+/// through the day's hourly boundaries on a simulated clock, each hour's analysis of every
+/// profile, its prediction, signal pages, notifications, paper trades and health record,
+/// for end-to-end validation before going live. The first run, at midnight, decides on the
+/// last candle of the day before. Signals are posted only to the `sandbox` webhook, never
+/// to the profiles' or subscriptions', the predictions are recorded as replays, and errors
+/// and heartbeats are only logged. Hours still to come are left out. Faults injected beforehand (see
+/// [`crate::faults::inject_faults`]) strike the replay's calls like any others.
+pub async fn run_replay(
+    profiles: Vec<SymbolProfile>,
+    date: NaiveDate,
+    speed: Speed,
+    sandbox: Option<String>,
+    execution: Execution,
+) -> Result<()> {
+    let profiles = profiles
+        .into_iter()
+        .map(|p| SymbolProfile {
+            webhook: sandbox.clone(),
+            ..p
+        })
+        .collect::<Vec<_>>();
+    let clock =
+        Clock::replay(date, speed).with_context(|| format!("{} hasn't started yet", date))?;
+    tracing::info!(
+        %date,
+        symbols = ?profiles.iter().map(|p| &p.symbol).collect::<Vec<_>>(),
+        speed = speed.0,
+        broker = execution.broker.name(),
+        "Replay started"
+    );
+    let mut daemon = Daemon {
+        mode: Mode::Replay,
+        state_file: Path::new(REPLAY_DIR).join("execution_state.json"),
+        chart_dir: Path::new(REPLAY_DIR).join("charts"),
+        ..Daemon::new(
+            profiles,
+            Vec::new(),
+            Some(execution),
+            Some(ExecutionState::default()),
+        )
+    };
+    let interval = std::time::Duration::from_secs(crate::GRANULARITY as u64);
+    let (runs, failed) = schedule(&mut daemon, interval, clock).await;
+    if let Some(execution) = &daemon.execution {
+        let account = execution.broker.balances().await?;
+        let positions = execution.broker.positions().await?;
+        tracing::info!(
+            runs,
+            failed,
+            balances = ?account.balances,
+            ?positions,
+            faults = ?crate::faults::injected(),
+            "Replay finished"
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal_filter::RepeatPolicy;
+    use crate::subscriptions::parse_subscriptions;
+
+    #[test]
+    fn test_next_run_waits_for_the_next_boundary() {
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let hour = std::time::Duration::from_secs(3600);
+        assert_eq!(
+            next_run(at("2024-03-01T10:20:00Z"), hour),
+            at("2024-03-01T11:00:30Z")
+        );
+        // Still settling after the boundary: that run is due now, not skipped
+        assert_eq!(
+            next_run(at("2024-03-01T11:00:10Z"), hour),
+            at("2024-03-01T11:00:30Z")
+        );
+        assert_eq!(
+            next_run(at("2024-03-01T11:00:30Z"), hour),
+            at("2024-03-01T12:00:30Z")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_runs_each_hour_of_the_day() {
+        assert_eq!("60x".parse::<Speed>().unwrap(), Speed(60.0));
+        assert_eq!("2.5".parse::<Speed>().unwrap(), Speed(2.5));
+        for speed in ["0x", "-3x", "fast", "x"] {
+            assert!(speed.parse::<Speed>().is_err(), "{speed}");
+        }
+
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 3, 12).unwrap();
+        let mut clock = Clock::replay(date, Speed(1e9)).unwrap();
+        let hour = std::time::Duration::from_secs(3600);
+        let mut hours = vec![clock.at().unwrap()];
+        while clock.wait_for_next(hour).await {
+            hours.push(clock.at().unwrap());
+        }
+        assert_eq!(hours.len(), 24);
+        // Decided at midnight, on the last candle of the day before
+        assert_eq!(hours[0], at("2024-03-11T23:00:00Z"));
+        assert_eq!(hours[23], at("2024-03-12T22:00:00Z"));
+        assert_eq!(clock.now(), at("2024-03-12T23:00:30Z"));
+
+        let tomorrow = Utc::now().date_naive() + chrono::Duration::days(1);
+        assert!(Clock::replay(tomorrow, Speed(1.0)).is_none());
+    }
+
+    #[test]
+    fn test_repeated_long_is_posted_once() {
+        let profile = SymbolProfile {
+            webhook: Some("https://example.com/eth".into()),
+            repeat: RepeatPolicy::OnChange,
+            ..SymbolProfile::default()
+        };
+        let subscriptions = parse_subscriptions(
+            r#"[
+                {"name": "changes", "webhook": "https://example.com/changes",
+                 "repeat": "on_change"},
+                {"name": "hourly", "webhook": "https://example.com/hourly",
+                 "repeat": {"cooldown": 7200}}
+            ]"#,
+        )
+        .unwrap();
+        let mut filters = RepeatFilters::new(&profile, &subscriptions);
+        let signal = |action| SymbolSignal {
+            symbol: "ETH".into(),
+            action,
+            rationale: String::new(),
+            window_end: 0.0,
+            prompt_version: "v1".into(),
+            window: Vec::new(),
+            stop: None,
+            target: None,
+            confidence: None,
+            signal_id: "ETH-1".into(),
+        };
+        let at = |hour: i64| DateTime::from_timestamp(hour * 3600, 0).unwrap();
+        let mut post = |action, held, hour| {
+            filters.recipients(&profile, &subscriptions, &signal(action), held, at(hour))
+        };
+
+        assert_eq!(post(Action::Long, None, 0).len(), 3);
+        assert!(post(Action::Long, None, 1).is_empty());
+        assert_eq!(post(Action::Long, None, 2), ["https://example.com/hourly"]);
+
+        // Traded, the position actually held decides: still long is a repeat, stopped out
+        // is not
+        assert!(post(Action::Long, Some((Action::Long, true)), 3).is_empty());
+        assert_eq!(post(Action::Long, Some((Action::None, true)), 4).len(), 3);
+        // A spot venue holds a short signal flat, so that isn't a change either
+        assert_eq!(post(Action::Short, Some((Action::Long, false)), 5).len(), 3);
+        assert!(post(Action::Short, Some((Action::None, false)), 6).is_empty());
+    }
+}
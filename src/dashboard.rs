@@ -0,0 +1,948 @@
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use crate::analytics::{AnalyticsStore, PredictionRecord, TradeRecord};
+use crate::audit::{is_signal_id, signal_id, AuditStore};
+use crate::auth::{check_exposure, is_loopback_host, same_origin, ApiTokens, AuthError, Role};
+use crate::backtest::{evaluate_candidate, promote_candidate, promote_challenger, PromptRecord};
+use crate::candidates::{
+    candidate_names, candidate_problem, is_valid_name, load_candidate, load_evaluation,
+    save_candidate, CandidateEvaluation, MAX_CANDIDATE_BYTES,
+};
+use crate::challenger::PROMOTION_HOURS;
+use crate::chart::{escape_html, render_line_svg, CHART_DIR};
+use crate::compute::prompt_version;
+use crate::derisk::DrawdownState;
+use crate::health;
+use crate::jobs::{Job, JobQueue, JobRequest};
+use crate::leaderboard::diff_lines;
+use crate::postmortem::FailureAnalysis;
+use crate::profiles::{profile_for, DEFAULT_TARGET};
+use crate::reconcile::{ExecutionState, EXECUTION_STATE_FILE};
+use crate::simulator::summarize;
+use crate::storage::{self, Storage, HISTORY_LIMIT};
+use crate::{run_live_analysis, Action};
+
+/// Live signals listed, newest first.
+const RECENT_SIGNALS: usize = 50;
+/// Failure analyses listed, newest first.
+const RECENT_FAILURES: usize = 20;
+/// Scored predictions the rolling accuracy is taken over.
+pub const ROLLING_PREDICTIONS: usize = 50;
+/// Largest request read, head and body: room for a candidate prompt and its headers.
+const MAX_REQUEST_BYTES: usize = MAX_CANDIDATE_BYTES + 16 * 1024;
+/// How long a client has to send its whole request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+const HTML: &str = "text/html; charset=utf-8";
+const JSON: &str = "application/json";
+
+const PAGES: [(&str, &str); 7] = [
+    ("/", "Signals"),
+    ("/accuracy", "Accuracy"),
+    ("/equity", "Equity"),
+    ("/prompts", "Prompts"),
+    ("/candidates", "Candidates"),
+    ("/jobs", "Jobs"),
+    ("/failures", "Failures"),
+];
+
+fn page(title: &str, body: &str) -> String {
+    let nav = PAGES
+        .iter()
+        .map(|(path, name)| format!("<a href=\"{path}\">{name}</a>"))
+        .collect::<Vec<_>>()
+        .join(" | ");
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{title}</title>\
+         <meta http-equiv=\"refresh\" content=\"300\"></head>\n<body>\n<nav>{nav}</nav>\n\
+         <h1>{title}</h1>\n{body}</body>\n</html>\n"
+    )
+}
+
+fn time(ts: f64) -> String {
+    DateTime::from_timestamp(ts as i64, 0)
+        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+fn action(action: Action) -> &'static str {
+    match action {
+        Action::Long => "long",
+        Action::Short => "short",
+        Action::None => "none",
+    }
+}
+
+fn price(level: Option<f64>) -> String {
+    level.map_or("-".to_string(), |p| format!("{p:.2}"))
+}
+
+/// The latest live predictions, newest first, each symbol linking to its signal chart and
+/// each time to the signal's explanation bundle.
+pub fn signals_page(predictions: &[PredictionRecord]) -> String {
+    let mut body = String::from(
+        "<table>\n<tr><th>Window end</th><th>Symbol</th><th>Action</th><th>Stop</th>\
+         <th>Target</th><th>Correct</th><th>Rationale</th></tr>\n",
+    );
+    let live = predictions.iter().rev().filter(|p| p.source == "live");
+    for p in live.take(RECENT_SIGNALS) {
+        let symbol = escape_html(&p.symbol);
+        let _ = writeln!(
+            body,
+            "<tr><td><a href=\"/signals/{}\">{}</a></td>\
+             <td><a href=\"/charts/{symbol}\">{symbol}</a></td><td>{}</td>\
+             <td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape_html(&signal_id(&p.symbol, &p.run_id)),
+            time(p.window_end),
+            action(p.action),
+            price(p.invalidation_price),
+            price(p.target_price),
+            p.correct.map_or("-", |c| if c { "yes" } else { "no" }),
+            escape_html(&p.rationale)
+        );
+    }
+    body.push_str("</table>\n");
+    page("Live signals", &body)
+}
+
+/// Accuracy over the last [`ROLLING_PREDICTIONS`] scored predictions from `source`, at
+/// each of them in window order.
+pub fn rolling_accuracy(predictions: &[PredictionRecord], source: &str) -> Vec<(f64, f64)> {
+    let mut scored = predictions
+        .iter()
+        .filter(|p| p.source == source)
+        .filter_map(|p| Some((p.window_end, p.correct?)))
+        .collect::<Vec<_>>();
+    scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+    (0..scored.len())
+        .map(|i| {
+            let recent = &scored[(i + 1).saturating_sub(ROLLING_PREDICTIONS)..=i];
+            let correct = recent.iter().filter(|(_, c)| *c).count();
+            (scored[i].0, correct as f64 / recent.len() as f64)
+        })
+        .collect()
+}
+
+pub fn accuracy_page(predictions: &[PredictionRecord]) -> String {
+    let mut body = String::new();
+    for source in ["live", "backtest"] {
+        let points = rolling_accuracy(predictions, source);
+        let latest = points
+            .last()
+            .map_or("-".to_string(), |(_, a)| format!("{:.1}%", a * 100.0));
+        let _ = writeln!(
+            body,
+            "<h2>{source}</h2>\n<p>{} scored predictions; latest rolling accuracy {latest}</p>\n{}",
+            points.len(),
+            render_line_svg(&points, "#1e88e5")
+        );
+    }
+    page(
+        &format!("Rolling accuracy over {ROLLING_PREDICTIONS} predictions"),
+        &body,
+    )
+}
+
+/// Compounded equity of the most recently recorded run's trades, starting at 1, at each
+/// exit.
+pub fn equity_curve(trades: &[TradeRecord]) -> Vec<(f64, f64)> {
+    let Some(run_id) = trades.last().map(|t| &t.run_id) else {
+        return Vec::new();
+    };
+    let mut run = trades
+        .iter()
+        .filter(|t| &t.run_id == run_id)
+        .map(|t| &t.trade)
+        .collect::<Vec<_>>();
+    run.sort_by(|a, b| a.exit_time.total_cmp(&b.exit_time));
+    let mut equity = 1.0;
+    run.iter()
+        .map(|t| {
+            equity *= 1.0 + t.return_pct;
+            (t.exit_time, equity)
+        })
+        .collect()
+}
+
+pub fn equity_page(trades: &[TradeRecord], drawdown: Option<&DrawdownState>) -> String {
+    let mut body = String::new();
+    if let Some(d) = drawdown.filter(|d| d.peak > 0.0) {
+        let _ = writeln!(
+            body,
+            "<h2>Account</h2>\n<p>Equity {:.2}, peak {:.2}, drawdown {:.1}%{}</p>",
+            d.equity,
+            d.peak,
+            d.drawdown(d.equity) * 100.0,
+            if d.is_derisked() {
+                "; sizing is reduced"
+            } else {
+                ""
+            }
+        );
+    }
+    let run = trades.last().map(|t| t.run_id.as_str()).unwrap_or("-");
+    let summary = summarize(
+        &trades
+            .iter()
+            .filter(|t| t.run_id == run)
+            .map(|t| t.trade)
+            .collect::<Vec<_>>(),
+    );
+    let _ = writeln!(
+        body,
+        "<h2>Run {}</h2>\n<p>{} trades, return {:+.2}%, win rate {:.1}%, max drawdown \
+         {:.2}%</p>\n{}",
+        escape_html(run),
+        summary.trades,
+        summary.total_return * 100.0,
+        summary.win_rate * 100.0,
+        summary.max_drawdown * 100.0,
+        render_line_svg(&equity_curve(trades), "#43a047")
+    );
+    page("Equity", &body)
+}
+
+/// The lines `after` removes from `before` and then the ones it adds.
+fn diff_html(before: &str, after: &str) -> String {
+    let diff = diff_lines(before, after);
+    diff.removed
+        .iter()
+        .map(|l| format!("<del>- {}</del>", escape_html(l)))
+        .chain(
+            diff.added
+                .iter()
+                .map(|l| format!("<ins>+ {}</ins>", escape_html(l))),
+        )
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `symbol`'s prompt history, newest first, each version shown as its diff from the one
+/// before.
+pub fn prompts_page(symbol: &str, history: &[PromptRecord]) -> String {
+    let mut body = String::new();
+    for (i, record) in history.iter().enumerate().rev() {
+        let out_of_sample = record
+            .out_of_sample
+            .map_or("-".to_string(), |a| format!("{:.1}%", a * 100.0));
+        let _ = writeln!(
+            body,
+            "<h2>Version {}</h2>\n<p>Accuracy {:.1}%, out of sample {out_of_sample}</p>",
+            i + 1,
+            record.score * 100.0
+        );
+        let text = match i.checked_sub(1).map(|p| &history[p]) {
+            Some(previous) => diff_html(&previous.prompt, &record.prompt),
+            None => escape_html(&record.prompt),
+        };
+        let _ = writeln!(body, "<pre>{text}</pre>");
+    }
+    page(&format!("{} prompt history", escape_html(symbol)), &body)
+}
+
+fn percent(fraction: f64) -> String {
+    format!("{:.1}%", fraction * 100.0)
+}
+
+/// The uploaded prompt candidates, with each one's latest validation accuracy against the
+/// champion's.
+pub fn candidates_page(candidates: &[(String, Option<CandidateEvaluation>)]) -> String {
+    let mut body = String::from(
+        "<table>\n<tr><th>Candidate</th><th>Accuracy</th><th>Champion</th><th>Gain</th></tr>\n",
+    );
+    for (name, evaluation) in candidates {
+        let (accuracy, champion, gain) = match evaluation {
+            Some(e) => (
+                percent(e.candidate.accuracy),
+                percent(e.champion.accuracy),
+                format!("{:+.1} pts", e.accuracy_gain() * 100.0),
+            ),
+            None => (
+                "-".to_string(),
+                "-".to_string(),
+                "not evaluated".to_string(),
+            ),
+        };
+        let _ = writeln!(
+            body,
+            "<tr><td><a href=\"/candidates/{name}\">{name}</a></td><td>{accuracy}</td>\
+             <td>{champion}</td><td>{gain}</td></tr>"
+        );
+    }
+    body.push_str(
+        "</table>\n<p>Upload a candidate with <code>POST /candidates/NAME</code> and the \
+         prompt as the body, then <code>POST /candidates/NAME/evaluate</code> and \
+         <code>POST /candidates/NAME/promote</code>.</p>\n",
+    );
+    page("Prompt candidates", &body)
+}
+
+/// Candidate `name`'s comparison with the champion, if it has been evaluated against this
+/// one, and its diff from it.
+pub fn candidate_page(
+    name: &str,
+    prompt: &str,
+    champion: &str,
+    evaluation: Option<&CandidateEvaluation>,
+) -> String {
+    let mut body = String::new();
+    let current =
+        evaluation.filter(|e| e.is_current(&prompt_version(champion), &prompt_version(prompt)));
+    match current {
+        Some(e) => {
+            let _ = writeln!(
+                body,
+                "<table>\n<tr><th>Prompt</th><th>Version</th><th>Windows</th><th>Accuracy</th>\
+                 <th>Return</th></tr>"
+            );
+            for (label, score) in [("champion", &e.champion), ("candidate", &e.candidate)] {
+                let _ = writeln!(
+                    body,
+                    "<tr><td>{label}</td><td>{}</td><td>{}</td><td>{}</td><td>{:+.2}%</td></tr>",
+                    score.prompt_version,
+                    score.windows,
+                    percent(score.accuracy),
+                    score.total_return * 100.0
+                );
+            }
+            let _ = writeln!(
+                body,
+                "</table>\n<p>Accuracy gain {:+.1} pts</p>",
+                e.accuracy_gain() * 100.0
+            );
+        }
+        None if evaluation.is_some() => body.push_str(
+            "<p>Evaluated against an earlier champion or version; evaluate it again.</p>\n",
+        ),
+        None => body.push_str("<p>Not evaluated yet.</p>\n"),
+    }
+    let _ = writeln!(
+        body,
+        "<h2>Changes from the champion</h2>\n<pre>{}</pre>",
+        diff_html(champion, prompt)
+    );
+    page(&format!("Candidate {}", escape_html(name)), &body)
+}
+
+/// Backtest and sweep jobs, newest first, with what each produced or why it failed.
+pub fn jobs_page(jobs: &[Job]) -> String {
+    let mut body = String::from(
+        "<table>\n<tr><th>Id</th><th>Kind</th><th>Status</th><th>Priority</th>\
+         <th>Submitted</th><th>Started</th><th>Finished</th><th>Outcome</th></tr>\n",
+    );
+    for j in jobs.iter().rev() {
+        let at =
+            |t: Option<DateTime<Utc>>| t.map_or("-".to_string(), |t| time(t.timestamp() as f64));
+        let outcome = match (&j.result, &j.error) {
+            (Some(result), _) => result.to_string(),
+            (_, Some(error)) => error.clone(),
+            _ => String::new(),
+        };
+        let _ = writeln!(
+            body,
+            "<tr><td><a href=\"/jobs/{0}\">{0}</a></td><td>{1}</td><td>{2}</td><td>{3}</td>\
+             <td>{4}</td><td>{5}</td><td>{6}</td><td><code>{7}</code></td></tr>",
+            j.id,
+            j.spec.kind(),
+            j.status.name(),
+            j.priority,
+            at(Some(j.submitted_at)),
+            at(j.started_at),
+            at(j.finished_at),
+            escape_html(&outcome)
+        );
+    }
+    body.push_str(
+        "</table>\n<p>Submit a job with <code>POST /jobs</code> and a body like \
+         <code>{\"kind\": \"sweep\", \"efforts\": [\"low\", \"high\"], \"sample\": 50, \
+         \"priority\": 1}</code>; lower priorities run first. \
+         <code>POST /jobs/ID/cancel</code> cancels one and <code>POST /jobs/ID/priority</code> \
+         with the new priority as the body reorders it.</p>\n",
+    );
+    page("Jobs", &body)
+}
+
+pub fn failures_page(failures: &[FailureAnalysis]) -> String {
+    let mut body = String::new();
+    for f in failures.iter().rev().take(RECENT_FAILURES) {
+        let _ = writeln!(
+            body,
+            "<h2>{} {}: {} but {}</h2>\n<p>{}</p>\n<details><summary>Analysis</summary>\
+             <pre>{}</pre></details>",
+            escape_html(&f.symbol),
+            time(f.window_end),
+            action(f.action),
+            action(f.label),
+            escape_html(&f.summary),
+            escape_html(&f.analysis)
+        );
+    }
+    page("Recent failures", &body)
+}
+
+/// The role a request needs: reading for pages, admin for actions. `None` for the
+/// health check, which monitors poll without a token.
+fn required_role(method: &str, path: &str) -> Option<Role> {
+    match (method, path) {
+        (_, "/healthz") => None,
+        ("GET", _) => Some(Role::Read),
+        _ => Some(Role::Admin),
+    }
+}
+
+/// Why a request with `head` may not do `method` on `path`, if it may not. Actions always
+/// need an admin token, even on localhost, and nothing is served to another origin or,
+/// without tokens, to a host name other than localhost's.
+fn denied(tokens: Option<&ApiTokens>, method: &str, path: &str, head: &str) -> Option<AuthError> {
+    let required = required_role(method, path)?;
+    let host = header(head, "host");
+    if !same_origin(header(head, "origin"), host) {
+        return Some(AuthError::CrossOrigin);
+    }
+    match tokens {
+        Some(tokens) => tokens
+            .authorize(header(head, "authorization"), required)
+            .err(),
+        None if required == Role::Admin => Some(AuthError::NoTokens),
+        None => (!host.is_some_and(is_loopback_host)).then_some(AuthError::CrossOrigin),
+    }
+}
+
+/// Status, content type and body of the response to `method` on `target` with `body`.
+async fn respond(
+    method: &str,
+    target: &str,
+    body: &str,
+    storage: &dyn Storage,
+) -> Result<(&'static str, &'static str, String)> {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let store = AnalyticsStore::default();
+    Ok(match (method, path) {
+        ("GET", "/") => ("200 OK", HTML, signals_page(&store.predictions()?)),
+        ("GET", "/accuracy") => ("200 OK", HTML, accuracy_page(&store.predictions()?)),
+        ("GET", "/equity") => {
+            let state = ExecutionState::load(Path::new(EXECUTION_STATE_FILE))?;
+            (
+                "200 OK",
+                HTML,
+                equity_page(&store.trades()?, Some(&state.drawdown)),
+            )
+        }
+        ("GET", "/prompts") => {
+            let symbol = param(query, "symbol").unwrap_or(DEFAULT_TARGET);
+            let history = storage.prompt_history(symbol, HISTORY_LIMIT).await?;
+            ("200 OK", HTML, prompts_page(symbol, &history))
+        }
+        ("GET", "/failures") => ("200 OK", HTML, failures_page(&store.failure_analyses()?)),
+        ("GET", "/healthz") => ("200 OK", JSON, serde_json::to_string(&health::report())?),
+        ("POST", "/analyze") => {
+            let (action, rationale) = run_live_analysis().await?;
+            let body = json!({ "action": action, "rationale": rationale });
+            ("200 OK", JSON, body.to_string())
+        }
+        ("POST", "/challengers/promote") => {
+            let promoted = promote_challenger(PROMOTION_HOURS).await?;
+            let body = json!({ "promoted": promoted.map(|h| h.challenger) });
+            ("200 OK", JSON, body.to_string())
+        }
+        ("GET", "/candidates") => {
+            let mut candidates = Vec::new();
+            for name in candidate_names()? {
+                let evaluation = load_evaluation(&name)?;
+                candidates.push((name, evaluation));
+            }
+            ("200 OK", HTML, candidates_page(&candidates))
+        }
+        ("GET", _) if path.starts_with("/signals/") => {
+            let id = &path["/signals/".len()..];
+            if !is_signal_id(id) {
+                ("404 Not Found", "text/plain", String::new())
+            } else if let Some(bundle) = AuditStore::default().bundle(id)? {
+                ("200 OK", JSON, serde_json::to_string(&bundle)?)
+            } else {
+                let missing = format!("No explanation bundle for signal {id}");
+                ("404 Not Found", "text/plain", missing)
+            }
+        }
+        ("GET", "/jobs") => ("200 OK", HTML, jobs_page(&JobQueue::default().jobs()?)),
+        ("POST", "/jobs") => match serde_json::from_str::<JobRequest>(body) {
+            Ok(request) => {
+                let job = JobQueue::default().submit(request, Utc::now())?;
+                ("201 Created", JSON, serde_json::to_string(&job)?)
+            }
+            Err(err) => (
+                "400 Bad Request",
+                "text/plain",
+                format!("Invalid job: {err}"),
+            ),
+        },
+        _ if path.starts_with("/jobs/") => respond_job(method, &path["/jobs/".len()..], body)?,
+        _ if path.starts_with("/candidates/") => {
+            respond_candidate(method, &path["/candidates/".len()..], query, body).await?
+        }
+        ("GET", _) => match path.strip_prefix("/charts/") {
+            Some(symbol)
+                if symbol
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-') =>
+            {
+                match fs::read_to_string(Path::new(CHART_DIR).join(format!("{symbol}.html"))) {
+                    Ok(chart) => ("200 OK", HTML, chart),
+                    Err(_) => ("404 Not Found", HTML, page("No chart yet", "")),
+                }
+            }
+            _ => ("404 Not Found", HTML, page("Not found", "")),
+        },
+        _ => ("404 Not Found", "text/plain", String::new()),
+    })
+}
+
+/// Response to `method` on `/jobs/{rest}`: a job as JSON, or cancelling or reprioritizing
+/// it.
+fn respond_job(
+    method: &str,
+    rest: &str,
+    body: &str,
+) -> Result<(&'static str, &'static str, String)> {
+    let (id, action) = rest.split_once('/').unwrap_or((rest, ""));
+    let Ok(id) = id.parse::<u64>() else {
+        return Ok(("404 Not Found", "text/plain", String::new()));
+    };
+    let queue = JobQueue::default();
+    let job = match (method, action) {
+        ("GET", "") => queue.get(id)?,
+        ("POST", "cancel") => queue.cancel(id, Utc::now())?,
+        ("POST", "priority") => match body.trim().parse::<u8>() {
+            Ok(priority) => queue.set_priority(id, priority)?,
+            Err(_) => {
+                let problem = format!("Priority {:?} is not a number from 0 to 255", body.trim());
+                return Ok(("400 Bad Request", "text/plain", problem));
+            }
+        },
+        _ => return Ok(("404 Not Found", "text/plain", String::new())),
+    };
+    Ok(match job {
+        Some(job) => ("200 OK", JSON, serde_json::to_string(&job)?),
+        None => ("404 Not Found", "text/plain", format!("No job {id}")),
+    })
+}
+
+/// The value of parameter `name` in a URL's `query`.
+fn param<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .find_map(|p| p.strip_prefix(name)?.strip_prefix('='))
+}
+
+/// Response to `method` on `/candidates/{rest}`: a candidate's page, or uploading the
+/// body as one, evaluating it or promoting it. The champion is that of the `symbol` in the
+/// query, the default target's without one; `force=true` promotes a candidate that didn't
+/// beat it.
+async fn respond_candidate(
+    method: &str,
+    rest: &str,
+    query: &str,
+    body: &str,
+) -> Result<(&'static str, &'static str, String)> {
+    let (name, action) = rest.split_once('/').unwrap_or((rest, ""));
+    if !is_valid_name(name) {
+        return Ok(("404 Not Found", HTML, page("Not found", "")));
+    }
+    if method != "POST" || action.is_empty() {
+        // Uploading is the only action on a candidate that needn't exist yet
+    } else if load_candidate(name)?.is_none() {
+        let missing = format!("No prompt candidate named {name}");
+        return Ok(("404 Not Found", "text/plain", missing));
+    }
+    let profile = profile_for(param(query, "symbol").unwrap_or(DEFAULT_TARGET))?;
+    Ok(match (method, action) {
+        ("GET", "") => match load_candidate(name)? {
+            Some(prompt) => {
+                let champion = fs::read_to_string(&profile.prompt)?;
+                let evaluation = load_evaluation(name)?;
+                let page = candidate_page(name, &prompt, &champion, evaluation.as_ref());
+                ("200 OK", HTML, page)
+            }
+            None => ("404 Not Found", HTML, page("No such candidate", "")),
+        },
+        ("POST", "") => match candidate_problem(name, body) {
+            Some(problem) => ("400 Bad Request", "text/plain", problem),
+            None => {
+                save_candidate(name, body)?;
+                let body = json!({ "name": name, "prompt_version": prompt_version(body) });
+                ("201 Created", JSON, body.to_string())
+            }
+        },
+        ("POST", "evaluate") => {
+            // Its stream of model calls isn't `Send`, so it can't run on the connection's
+            // task; it gets a blocking thread of its own instead
+            let name = name.to_string();
+            let evaluation = tokio::task::spawn_blocking(move || {
+                tokio::runtime::Handle::current().block_on(evaluate_candidate(&name, &profile))
+            })
+            .await??;
+            ("200 OK", JSON, serde_json::to_string(&evaluation)?)
+        }
+        ("POST", "promote") => {
+            let force = param(query, "force") == Some("true");
+            let evaluation = promote_candidate(name, &profile, force).await?;
+            ("200 OK", JSON, serde_json::to_string(&evaluation)?)
+        }
+        _ => ("404 Not Found", "text/plain", String::new()),
+    })
+}
+
+/// The value of header `name` in a request's head.
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines()
+        .take_while(|l| !l.is_empty())
+        .filter_map(|l| l.split_once(':'))
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+/// The head and body of `request` once all the body its `Content-Length` announces has
+/// arrived.
+fn complete_request(request: &[u8]) -> Option<(String, String)> {
+    let end = request.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let head = String::from_utf8_lossy(&request[..end]).into_owned();
+    let length = header(&head, "content-length")
+        .and_then(|l| l.parse::<usize>().ok())
+        .unwrap_or(0);
+    let body = request.get(end + 4..end + 4 + length)?;
+    Some((head, String::from_utf8_lossy(body).into_owned()))
+}
+
+/// Why a request could not be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BadRequest {
+    /// The client sent nothing complete within [`REQUEST_TIMEOUT`].
+    TimedOut,
+    /// The connection closed or failed before the request was complete.
+    Incomplete,
+    /// The request ran past [`MAX_REQUEST_BYTES`].
+    TooLarge,
+}
+
+impl BadRequest {
+    /// The HTTP response the request is answered with.
+    fn response(self) -> String {
+        let (status, reason) = match self {
+            BadRequest::TimedOut => (
+                "408 Request Timeout",
+                format!("Requests must arrive within {}s", REQUEST_TIMEOUT.as_secs()),
+            ),
+            BadRequest::Incomplete => ("400 Bad Request", "Incomplete request".to_string()),
+            BadRequest::TooLarge => (
+                "413 Payload Too Large",
+                format!("Requests are limited to {MAX_REQUEST_BYTES} bytes"),
+            ),
+        };
+        format!("HTTP/1.1 {status}\r\nConnection: close\r\n\r\n{reason}")
+    }
+}
+
+/// Reads a request, giving up once `timeout` passes without it being complete.
+async fn read_request<S: AsyncRead + Unpin>(
+    socket: &mut S,
+    timeout: Duration,
+) -> Result<(String, String), BadRequest> {
+    let read = async {
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            if let Some(parts) = complete_request(&request) {
+                return Ok(parts);
+            }
+            if request.len() > MAX_REQUEST_BYTES {
+                return Err(BadRequest::TooLarge);
+            }
+            match socket.read(&mut buf).await {
+                Ok(0) | Err(_) => return Err(BadRequest::Incomplete),
+                Ok(n) => request.extend_from_slice(&buf[..n]),
+            }
+        }
+    };
+    tokio::time::timeout(timeout, read)
+        .await
+        .unwrap_or(Err(BadRequest::TimedOut))
+}
+
+/// Serves the dashboard over plain HTTP on `addr`: live signals, rolling accuracy, the
+/// equity curve, prompt history and recent failures, from the local analytics tables and
+/// the configured storage. `/signals/{id}` is a live signal's explanation bundle as JSON.
+/// `POST /analyze` runs a live analysis and `POST /challengers/promote` promotes a
+/// challenger that has beaten the champion. `POST /candidates/{name}` uploads its body as
+/// a prompt candidate, which `POST /candidates/{name}/evaluate` scores beside the champion
+/// on the validation windows and `POST /candidates/{name}/promote` then makes the champion
+/// if it won; `/candidates/{name}` shows the comparison. `/jobs` lists the job queue, and
+/// `POST /jobs`, `POST /jobs/{id}/cancel` and `POST /jobs/{id}/priority` manage it.
+/// Actions need an admin token, so without `tokens` the dashboard is read-only; with them,
+/// every request but the health check needs one.
+pub async fn serve_dashboard(addr: SocketAddr, tokens: Option<ApiTokens>) -> Result<()> {
+    check_exposure(addr, tokens.as_ref())?;
+    let storage: Arc<dyn Storage> = storage::from_env().await?.into();
+    let tokens = Arc::new(tokens);
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!(%addr, authenticated = tokens.is_some(), "Serving dashboard");
+    loop {
+        let (mut socket, _) = listener.accept().await?;
+        let (storage, tokens) = (storage.clone(), tokens.clone());
+        tokio::spawn(async move {
+            let (request, body) = match read_request(&mut socket, REQUEST_TIMEOUT).await {
+                Ok(parts) => parts,
+                Err(err) => {
+                    let _ = socket.write_all(err.response().as_bytes()).await;
+                    return;
+                }
+            };
+            let mut line = request.lines().next().unwrap_or("").split_whitespace();
+            let (method, target) = (line.next().unwrap_or(""), line.next().unwrap_or("/"));
+            let path = target.split_once('?').map_or(target, |(p, _)| p);
+
+            let (status, content_type, body) =
+                match denied(tokens.as_ref().as_ref(), method, path, &request) {
+                    Some(
+                        err @ (AuthError::Forbidden | AuthError::NoTokens | AuthError::CrossOrigin),
+                    ) => ("403 Forbidden", "text/plain", err.describe().to_string()),
+                    Some(err) => ("401 Unauthorized", "text/plain", err.describe().to_string()),
+                    None => match respond(method, target, &body, storage.as_ref()).await {
+                        Ok(response) => response,
+                        Err(err) => {
+                            tracing::warn!(%method, %target, %err, "Dashboard request failed");
+                            (
+                                "500 Internal Server Error",
+                                "text/plain",
+                                format!("{:#}", err),
+                            )
+                        }
+                    },
+                };
+            let challenge = if status.starts_with("401") {
+                "WWW-Authenticate: Basic realm=\"happychartsv2\"\r\n"
+            } else {
+                ""
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+                status,
+                content_type,
+                body.len(),
+                challenge,
+                body
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dashboard_pages() {
+        let prediction = |t: f64, source: &str, correct: Option<bool>| {
+            serde_json::from_value::<PredictionRecord>(serde_json::json!({
+                "run_id": "r1",
+                "source": source,
+                "symbol": "ETH",
+                "model": "o1-mini",
+                "prompt_hash": "abc",
+                "window_end": t,
+                "action": "long",
+                "label": null,
+                "correct": correct,
+                "rationale": "Breakout <above> resistance",
+            }))
+            .unwrap()
+        };
+        let predictions = (0..60)
+            .map(|i| prediction(i as f64 * 3600.0, "backtest", Some(i % 2 == 0 || i >= 50)))
+            .chain([prediction(0.0, "live", None)])
+            .collect::<Vec<_>>();
+        let rolling = rolling_accuracy(&predictions, "backtest");
+        assert_eq!(rolling.len(), 60);
+        assert_eq!(rolling[1].1, 0.5);
+        // The last 50 hold 10 hits in 10 tries plus 20 of the 40 before
+        assert!((rolling[59].1 - 30.0 / 50.0).abs() < 1e-9);
+
+        let signals = signals_page(&predictions);
+        assert_eq!(signals.matches("<tr>").count(), 2);
+        assert!(signals.contains("Breakout &lt;above&gt;") && signals.contains("/charts/ETH"));
+
+        let trade = |run: &str, exit_time: f64, return_pct: f64| {
+            serde_json::from_value::<TradeRecord>(serde_json::json!({
+                "run_id": run,
+                "symbol": "ETH",
+                "side": "long",
+                "entry_time": exit_time - 3600.0,
+                "entry_price": 100.0,
+                "exit_time": exit_time,
+                "exit_price": 101.0,
+                "return_pct": return_pct,
+            }))
+            .unwrap()
+        };
+        let trades = [
+            trade("r0", 0.0, 0.5),
+            trade("r1", 7200.0, -0.5),
+            trade("r1", 3600.0, 0.1),
+        ];
+        assert_eq!(equity_curve(&trades), [(3600.0, 1.1), (7200.0, 0.55)]);
+
+        let history = [
+            PromptRecord {
+                prompt: "Be careful\nUse RSI".into(),
+                score: 0.5,
+                out_of_sample: None,
+                symbol: "ETH".into(),
+            },
+            PromptRecord {
+                prompt: "Be careful\nUse MACD".into(),
+                score: 0.6,
+                out_of_sample: Some(0.55),
+                symbol: "ETH".into(),
+            },
+        ];
+        let prompts = prompts_page("ETH", &history);
+        assert!(prompts.find("Version 2") < prompts.find("Version 1"));
+        assert!(prompts.contains("<del>- Use RSI</del>\n<ins>+ Use MACD</ins>"));
+
+        let score = |prompt: &str, accuracy: f64| crate::comparison::PromptScore {
+            name: prompt.into(),
+            prompt_version: prompt_version(prompt),
+            windows: 48,
+            accuracy,
+            total_return: 0.01,
+        };
+        let (champion, candidate) = ("Be careful\nUse RSI", "Be careful\nUse <MACD>");
+        let evaluation = CandidateEvaluation {
+            champion: score(champion, 0.5),
+            candidate: score(candidate, 0.5625),
+        };
+        let page = candidate_page("macd", candidate, champion, Some(&evaluation));
+        assert!(
+            page.contains("Accuracy gain +6.2 pts") && page.contains("<ins>+ Use &lt;MACD&gt;")
+        );
+        let stale = candidate_page("macd", "Use ADX", champion, Some(&evaluation));
+        assert!(stale.contains("evaluate it again") && !stale.contains("Accuracy gain"));
+        assert!(candidates_page(&[("macd".into(), None)]).contains("not evaluated"));
+
+        let upload = b"POST /candidates/macd HTTP/1.1\r\nContent-Length: 7\r\n\r\nUse ADX";
+        let (head, body) = complete_request(upload).unwrap();
+        assert_eq!(
+            (header(&head, "content-length"), body.as_str()),
+            (Some("7"), "Use ADX")
+        );
+        // The body hasn't all arrived yet
+        assert_eq!(complete_request(&upload[..upload.len() - 1]), None);
+
+        // Without tokens the dashboard is read-only, and only to localhost
+        let local = "GET / HTTP/1.1\r\nHost: 127.0.0.1:8080";
+        assert_eq!(denied(None, "GET", "/", local), None);
+        assert_eq!(
+            denied(None, "POST", "/analyze", local),
+            Some(AuthError::NoTokens)
+        );
+        let rebound = "GET / HTTP/1.1\r\nHost: rebind.example:8080";
+        assert_eq!(
+            denied(None, "GET", "/", rebound),
+            Some(AuthError::CrossOrigin)
+        );
+        // A page elsewhere can't act with the credentials the browser holds
+        let mut tokens = ApiTokens::default();
+        tokens.add("ops", "admin-token", Role::Admin);
+        let post = |origin: &str| {
+            format!(
+                "POST /candidates/x/promote HTTP/1.1\r\nHost: 127.0.0.1:8080\r\n\
+                 Origin: {origin}\r\nAuthorization: Bearer admin-token"
+            )
+        };
+        let promote = |head: &str| denied(Some(&tokens), "POST", "/candidates/x/promote", head);
+        assert_eq!(promote(&post("http://127.0.0.1:8080")), None);
+        assert_eq!(
+            promote(&post("https://evil.example")),
+            Some(AuthError::CrossOrigin)
+        );
+
+        let job = serde_json::from_value::<Job>(serde_json::json!({
+            "id": 3,
+            "spec": { "kind": "sweep", "efforts": ["low"], "seed": null, "stride": 1, "sample": 20 },
+            "priority": 1,
+            "status": "failed",
+            "submitted_at": "2024-03-20T12:00:00Z",
+            "error": "No <candles>",
+        }))
+        .unwrap();
+        let jobs = jobs_page(&[job]);
+        assert!(
+            jobs.contains("<td>sweep</td><td>failed</td>") && jobs.contains("No &lt;candles&gt;")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_request_failures() {
+        let timeout = Duration::from_millis(50);
+        let request = b"POST /analyze HTTP/1.1\r\nContent-Length: 2\r\n\r\nok";
+
+        let (mut client, mut server) = tokio::io::duplex(64 * 1024);
+        client.write_all(request).await.unwrap();
+        assert_eq!(
+            read_request(&mut server, timeout).await,
+            Ok((
+                "POST /analyze HTTP/1.1\r\nContent-Length: 2".into(),
+                "ok".into()
+            ))
+        );
+
+        // A client that goes quiet mid-request is timed out rather than held open.
+        let (mut client, mut server) = tokio::io::duplex(64 * 1024);
+        client.write_all(&request[..20]).await.unwrap();
+        assert_eq!(
+            read_request(&mut server, timeout).await,
+            Err(BadRequest::TimedOut)
+        );
+
+        let (mut client, mut server) = tokio::io::duplex(64 * 1024);
+        client
+            .write_all(&request[..request.len() - 1])
+            .await
+            .unwrap();
+        drop(client);
+        assert_eq!(
+            read_request(&mut server, timeout).await,
+            Err(BadRequest::Incomplete)
+        );
+
+        let (mut client, mut server) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(async move {
+            let head = format!(
+                "POST /candidates/big HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+                2 * MAX_REQUEST_BYTES
+            );
+            client.write_all(head.as_bytes()).await.unwrap();
+            let _ = client.write_all(&vec![b'x'; 2 * MAX_REQUEST_BYTES]).await;
+        });
+        assert_eq!(
+            read_request(&mut server, Duration::from_secs(10)).await,
+            Err(BadRequest::TooLarge)
+        );
+        assert!(BadRequest::TooLarge.response().starts_with("HTTP/1.1 413"));
+        assert!(BadRequest::TimedOut.response().starts_with("HTTP/1.1 408"));
+        assert!(BadRequest::Incomplete
+            .response()
+            .starts_with("HTTP/1.1 400"));
+    }
+}
@@ -0,0 +1,405 @@
+use serde::{Deserialize, Serialize};
+
+// Indexes into the `[time, open, high, low, close, volume]` candle array
+const TIME: usize = 0;
+const OPEN: usize = 1;
+const HIGH: usize = 2;
+const LOW: usize = 3;
+const CLOSE: usize = 4;
+const VOLUME: usize = 5;
+
+/// A candle whose high-low range exceeds this fraction of its close is treated as a glitch.
+pub const MAX_WICK_RANGE: f64 = 0.25;
+/// Closes deviating from their neighbours by more than this many (robust) standard
+/// deviations of the series' returns are flagged as outliers.
+pub const OUTLIER_SIGMA: f64 = 6.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyKind {
+    AbsurdWick,
+    ZeroVolume,
+    PriceOutlier,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CandleAnomaly {
+    pub index: usize,
+    pub time: f64,
+    pub kind: AnomalyKind,
+}
+
+/// What to do with anomalous candles before labeling and prompting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnomalyPolicy {
+    /// Repair prices in place (clamp wicks, replace outlier closes by the neighbour median).
+    Correct,
+    /// Drop flagged candles entirely.
+    Exclude,
+    /// Leave the data untouched; the anomalies are reported alongside it.
+    Annotate,
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    })
+}
+
+/// Median close of up to two candles either side of `i`, excluding `i` itself.
+fn neighbour_close(data: &[[f64; 6]], i: usize) -> Option<f64> {
+    let closes = (i.saturating_sub(2)..(i + 3).min(data.len()))
+        .filter(|&j| j != i)
+        .map(|j| data[j][CLOSE])
+        .collect::<Vec<_>>();
+    if closes.len() < 2 {
+        return None;
+    }
+    median(closes)
+}
+
+/// Scans chronologically ordered candles for exchange glitches.
+pub fn detect_anomalies(data: &[[f64; 6]], sigma: f64) -> Vec<CandleAnomaly> {
+    // Robust volatility estimate: the median absolute return rescaled to a standard
+    // deviation, so a single glitch can't inflate the threshold that should catch it.
+    let abs_returns = data
+        .windows(2)
+        .filter(|w| w[0][CLOSE] > 0.0)
+        .map(|w| (w[1][CLOSE] / w[0][CLOSE] - 1.0).abs())
+        .collect::<Vec<_>>();
+    let scale = median(abs_returns).unwrap_or(0.0) / 0.6745;
+
+    let mut anomalies = Vec::new();
+    for (i, c) in data.iter().enumerate() {
+        let mut flag = |kind| {
+            anomalies.push(CandleAnomaly {
+                index: i,
+                time: c[TIME],
+                kind,
+            })
+        };
+
+        let body_high = c[OPEN].max(c[CLOSE]);
+        let body_low = c[OPEN].min(c[CLOSE]);
+        if c[HIGH] < body_high
+            || c[LOW] > body_low
+            || c[CLOSE] <= 0.0
+            || (c[HIGH] - c[LOW]) / c[CLOSE] > MAX_WICK_RANGE
+        {
+            flag(AnomalyKind::AbsurdWick);
+        }
+
+        if c[VOLUME] <= 0.0 {
+            flag(AnomalyKind::ZeroVolume);
+        }
+
+        if let Some(reference) = neighbour_close(data, i) {
+            let deviation = (c[CLOSE] - reference).abs() / reference;
+            if scale > 0.0 && deviation > sigma * scale {
+                flag(AnomalyKind::PriceOutlier);
+            }
+        }
+    }
+
+    anomalies
+}
+
+/// Applies `policy` to the flagged candles, returning the cleaned series and what was found.
+pub fn filter_candles(
+    mut data: Vec<[f64; 6]>,
+    policy: AnomalyPolicy,
+    sigma: f64,
+) -> (Vec<[f64; 6]>, Vec<CandleAnomaly>) {
+    let anomalies = detect_anomalies(&data, sigma);
+
+    match policy {
+        AnomalyPolicy::Annotate => {}
+        AnomalyPolicy::Exclude => {
+            let mut idx = 0;
+            data.retain(|_| {
+                let keep = !anomalies.iter().any(|a| a.index == idx);
+                idx += 1;
+                keep
+            });
+        }
+        AnomalyPolicy::Correct => {
+            for a in &anomalies {
+                let i = a.index;
+                match a.kind {
+                    AnomalyKind::PriceOutlier => {
+                        let Some(fixed) = neighbour_close(&data, i) else {
+                            continue;
+                        };
+                        let open = if i > 0 { data[i - 1][CLOSE] } else { fixed };
+                        let c = &mut data[i];
+                        c[OPEN] = open;
+                        c[CLOSE] = fixed;
+                        c[HIGH] = open.max(fixed);
+                        c[LOW] = open.min(fixed);
+                    }
+                    AnomalyKind::AbsurdWick => {
+                        let c = &mut data[i];
+                        c[HIGH] = c[OPEN].max(c[CLOSE]);
+                        c[LOW] = c[OPEN].min(c[CLOSE]);
+                    }
+                    // Nothing to repair: the prices are still usable.
+                    AnomalyKind::ZeroVolume => {}
+                }
+            }
+        }
+    }
+
+    if !anomalies.is_empty() {
+        tracing::warn!(
+            count = anomalies.len(),
+            ?policy,
+            "Anomalous candles detected"
+        );
+    }
+
+    (data, anomalies)
+}
+
+/// Anomalies whose timestamps fall inside the given window.
+pub fn anomalies_in_window<'a>(
+    anomalies: &'a [CandleAnomaly],
+    window: &[[f64; 6]],
+) -> Vec<&'a CandleAnomaly> {
+    let (Some(first), Some(last)) = (window.first(), window.last()) else {
+        return Vec::new();
+    };
+    anomalies
+        .iter()
+        .filter(|a| a.time >= first[TIME] && a.time <= last[TIME])
+        .collect()
+}
+
+/// Structured data-quality summary for one cached candle series.
+#[derive(Debug, Clone, Serialize)]
+pub struct SeriesReport {
+    pub symbol: String,
+    /// Granularity in seconds, inferred from the most common timestamp spacing.
+    pub granularity: Option<i64>,
+    pub candles: usize,
+    pub first: Option<f64>,
+    pub last: Option<f64>,
+    /// Missing intervals as `(after, before, missing_candles)`.
+    pub gaps: Vec<(f64, f64, usize)>,
+    pub duplicates: Vec<f64>,
+    pub out_of_order: usize,
+    pub anomalies: Vec<CandleAnomaly>,
+}
+
+impl SeriesReport {
+    pub fn is_clean(&self) -> bool {
+        self.gaps.is_empty()
+            && self.duplicates.is_empty()
+            && self.out_of_order == 0
+            && self.anomalies.is_empty()
+    }
+}
+
+pub(crate) fn infer_granularity(times: &[f64]) -> Option<i64> {
+    let mut counts = std::collections::HashMap::new();
+    for w in times.windows(2) {
+        let step = (w[1] - w[0]) as i64;
+        if step > 0 {
+            *counts.entry(step).or_insert(0usize) += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .max_by_key(|&(step, n)| (n, std::cmp::Reverse(step)))
+        .map(|(step, _)| step)
+}
+
+/// Checks a series in its stored order for gaps, duplicates, ordering and outliers.
+pub fn check_series(symbol: &str, stored: &[[f64; 6]]) -> SeriesReport {
+    // Stored series may be newest-first (Coinbase order) or chronological; only
+    // steps against the dominant direction count as out of order.
+    let descending = stored.len() > 1 && stored[0][TIME] > stored[stored.len() - 1][TIME];
+    let out_of_order = stored
+        .windows(2)
+        .filter(|w| {
+            if descending {
+                w[1][TIME] > w[0][TIME]
+            } else {
+                w[1][TIME] < w[0][TIME]
+            }
+        })
+        .count();
+
+    let mut sorted = stored.to_vec();
+    sorted.sort_by(|a, b| a[TIME].total_cmp(&b[TIME]));
+
+    let mut duplicates = Vec::new();
+    sorted.dedup_by(|b, a| {
+        let dup = a[TIME] == b[TIME];
+        if dup {
+            duplicates.push(a[TIME]);
+        }
+        dup
+    });
+
+    let times = sorted.iter().map(|c| c[TIME]).collect::<Vec<_>>();
+    let granularity = infer_granularity(&times);
+    let gaps = match granularity {
+        Some(g) => times
+            .windows(2)
+            .filter(|w| (w[1] - w[0]) as i64 > g)
+            .map(|w| (w[0], w[1], ((w[1] - w[0]) as i64 / g - 1) as usize))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    SeriesReport {
+        symbol: symbol.to_string(),
+        granularity,
+        candles: stored.len(),
+        first: times.first().copied(),
+        last: times.last().copied(),
+        gaps,
+        duplicates,
+        out_of_order,
+        anomalies: detect_anomalies(&sorted, OUTLIER_SIGMA),
+    }
+}
+
+/// What [`repair_order`] had to fix in a series.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct OrderRepair {
+    /// Steps back in time between consecutive candles.
+    pub out_of_order: usize,
+    pub duplicates: usize,
+}
+
+impl OrderRepair {
+    pub fn is_clean(&self) -> bool {
+        self.out_of_order == 0 && self.duplicates == 0
+    }
+}
+
+/// Sorts `candles` chronologically and drops repeated timestamps, keeping the first copy,
+/// unless the times already strictly increase.
+pub fn repair_order(candles: &mut Vec<[f64; 6]>) -> OrderRepair {
+    let mut repair = OrderRepair::default();
+    for w in candles.windows(2) {
+        if w[1][TIME] < w[0][TIME] {
+            repair.out_of_order += 1;
+        }
+    }
+    if repair.out_of_order == 0 && candles.windows(2).all(|w| w[1][TIME] > w[0][TIME]) {
+        return repair;
+    }
+    candles.sort_by(|a, b| a[TIME].total_cmp(&b[TIME]));
+    let before = candles.len();
+    candles.dedup_by(|later, earlier| later[TIME] == earlier[TIME]);
+    repair.duplicates = before - candles.len();
+    repair
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle(time: f64, close: f64) -> [f64; 6] {
+        [time, close, close * 1.001, close * 0.999, close, 10.0]
+    }
+
+    fn series() -> Vec<[f64; 6]> {
+        (0..20)
+            .map(|i| candle(i as f64 * 3600.0, 100.0 + (i % 3) as f64 * 0.1))
+            .collect()
+    }
+
+    #[test]
+    fn test_repair_order() {
+        let mut clean = series();
+        assert!(repair_order(&mut clean).is_clean());
+        assert_eq!(clean, series());
+
+        // Written newest first, with one candle stored twice
+        let mut stored = series();
+        stored.reverse();
+        stored.insert(3, candle(stored[3][TIME], 1.0));
+        let repair = repair_order(&mut stored);
+        assert_eq!(repair.duplicates, 1);
+        assert_eq!(repair.out_of_order, 19);
+        assert_eq!(stored.len(), 20);
+        assert!(stored.windows(2).all(|w| w[1][TIME] > w[0][TIME]));
+    }
+
+    #[test]
+    fn test_detect_outlier_and_zero_volume() {
+        let mut data = series();
+        data[10] = candle(36000.0, 180.0);
+        data[5][VOLUME] = 0.0;
+
+        let anomalies = detect_anomalies(&data, OUTLIER_SIGMA);
+        assert!(anomalies
+            .iter()
+            .any(|a| a.index == 10 && a.kind == AnomalyKind::PriceOutlier));
+        assert!(anomalies
+            .iter()
+            .any(|a| a.index == 5 && a.kind == AnomalyKind::ZeroVolume));
+    }
+
+    #[test]
+    fn test_detect_absurd_wick() {
+        let mut data = series();
+        data[3][LOW] = 1.0;
+        let anomalies = detect_anomalies(&data, OUTLIER_SIGMA);
+        assert_eq!(
+            anomalies,
+            vec![CandleAnomaly {
+                index: 3,
+                time: 3.0 * 3600.0,
+                kind: AnomalyKind::AbsurdWick
+            }]
+        );
+    }
+
+    #[test]
+    fn test_filter_policies() {
+        let mut data = series();
+        data[10] = candle(36000.0, 180.0);
+
+        let (excluded, _) = filter_candles(data.clone(), AnomalyPolicy::Exclude, OUTLIER_SIGMA);
+        assert_eq!(excluded.len(), data.len() - 1);
+
+        let (corrected, anomalies) =
+            filter_candles(data.clone(), AnomalyPolicy::Correct, OUTLIER_SIGMA);
+        assert!(!anomalies.is_empty());
+        assert!((corrected[10][CLOSE] - 100.0).abs() < 1.0);
+        assert!(detect_anomalies(&corrected, OUTLIER_SIGMA).is_empty());
+
+        let (annotated, _) = filter_candles(data.clone(), AnomalyPolicy::Annotate, OUTLIER_SIGMA);
+        assert_eq!(annotated, data);
+    }
+
+    #[test]
+    fn test_check_series_report() {
+        let mut data = series();
+        data.reverse(); // newest first, as Coinbase returns them
+        data.swap(3, 4);
+        data.remove(8);
+        let dup = data[1];
+        data.insert(1, dup);
+
+        let report = check_series("ETH", &data);
+        assert_eq!(report.granularity, Some(3600));
+        assert_eq!(report.out_of_order, 1);
+        assert_eq!(report.duplicates, vec![dup[TIME]]);
+        assert_eq!(report.gaps.len(), 1);
+        assert_eq!(report.gaps[0].2, 1);
+        assert!(!report.is_clean());
+        assert!(check_series("ETH", &series()).is_clean());
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashSet;
+
+use crate::compute::prompt_version;
+
+/// Word-bigram similarity at or above which a candidate prompt counts as a near-duplicate.
+pub const DUPLICATE_SIMILARITY: f64 = 0.9;
+
+/// A prompt already tried that a candidate repeats.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Duplicate {
+    /// Index of the repeated prompt in the history searched.
+    pub index: usize,
+    pub similarity: f64,
+    /// Identical apart from case and whitespace.
+    pub exact: bool,
+}
+
+/// Lowercased, with every whitespace run collapsed to one space.
+fn normalize(prompt: &str) -> String {
+    prompt
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn bigrams(normalized: &str) -> HashSet<(&str, &str)> {
+    let words = normalized.split(' ').collect::<Vec<_>>();
+    words.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+/// Jaccard similarity of the two prompts' word bigrams, ignoring case and whitespace.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize(a), normalize(b));
+    if a == b {
+        return 1.0;
+    }
+    let (a, b) = (bigrams(&a), bigrams(&b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+/// The prompt in `history` that `candidate` most closely repeats, if any is an exact match
+/// by normalized hash or at least [`DUPLICATE_SIMILARITY`] similar.
+pub fn find_duplicate<'a>(
+    candidate: &str,
+    history: impl IntoIterator<Item = &'a str>,
+) -> Option<Duplicate> {
+    let hash = prompt_version(&normalize(candidate));
+    history
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, prompt)| {
+            if prompt_version(&normalize(prompt)) == hash {
+                return Some(Duplicate {
+                    index,
+                    similarity: 1.0,
+                    exact: true,
+                });
+            }
+            let similarity = similarity(candidate, prompt);
+            (similarity >= DUPLICATE_SIMILARITY).then_some(Duplicate {
+                index,
+                similarity,
+                exact: false,
+            })
+        })
+        .max_by(|a, b| a.similarity.total_cmp(&b.similarity))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_duplicate() {
+        let base = "Analyze the ETH, BTC and SOL candles below and answer with a JSON object \
+                    holding the action, a short rationale, the key factors, an invalidation \
+                    price, a target price and your confidence between 0 and 1.";
+        let reworded = format!("{} Be concise.", base);
+        let rewritten = "Focus on BTC leading ETH. Respond in JSON with action and rationale.";
+        let history = [rewritten, base];
+
+        let exact = find_duplicate(&base.to_uppercase(), history).unwrap();
+        assert_eq!((exact.index, exact.exact), (1, true));
+
+        let near = find_duplicate(&reworded, history).unwrap();
+        assert_eq!(near.index, 1);
+        assert!(!near.exact && near.similarity >= DUPLICATE_SIMILARITY && near.similarity < 1.0);
+
+        assert_eq!(find_duplicate("Something else entirely.", history), None);
+        assert!(similarity(base, rewritten) < 0.2);
+    }
+}
@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+
+use crate::broker::Position;
+use crate::exposure::CorrelationMatrix;
+use crate::ledger::{Account, Rates, DEFAULT_CURRENCY};
+
+/// Requesting a manual reset of the de-risking, which the daemon picks up on its next run.
+#[cfg(feature = "native")]
+pub const DERISK_RESET_FILE: &str = "cache/derisk_reset";
+/// Stablecoins valued one to one with USD in the equity.
+const STABLECOINS: [&str; 2] = ["USDC", "USDT"];
+
+/// What sizing does once the drawdown limit is crossed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeriskAction {
+    /// Positions are sized to half the usual notional.
+    #[default]
+    Halve,
+    /// Positions are closed and no new ones opened.
+    Pause,
+}
+
+impl std::str::FromStr for DeriskAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "halve" => Ok(DeriskAction::Halve),
+            "pause" => Ok(DeriskAction::Pause),
+            _ => anyhow::bail!("Unknown de-risk action {:?} (expected halve or pause)", s),
+        }
+    }
+}
+
+/// When to de-risk, and when to stop.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DrawdownRule {
+    /// Drawdown from the equity peak, as a fraction, that triggers the action.
+    pub threshold: f64,
+    /// Drawdown the equity must recover to before sizing returns to normal.
+    pub resume_below: f64,
+    pub action: DeriskAction,
+}
+
+/// The equity peak and whether sizing is currently reduced, kept with the execution state
+/// so a restart neither forgets the peak nor lifts the de-risking.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DrawdownState {
+    pub peak: f64,
+    pub equity: f64,
+    /// Time in seconds the sizing was reduced at; `None` while trading normally.
+    pub derisked_at: Option<f64>,
+}
+
+/// A change of sizing for the daemon to log and notify.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DeriskChange {
+    Derisked {
+        action: DeriskAction,
+        drawdown: f64,
+        equity: f64,
+        peak: f64,
+    },
+    Resumed {
+        drawdown: f64,
+        equity: f64,
+        peak: f64,
+    },
+}
+
+impl DeriskChange {
+    pub fn describe(&self) -> String {
+        match self {
+            DeriskChange::Derisked {
+                action,
+                drawdown,
+                equity,
+                peak,
+            } => format!(
+                "Equity {equity:.2} is {:.1}% below its peak of {peak:.2}; sizing is {} until \
+                 it recovers or is reset",
+                drawdown * 100.0,
+                match action {
+                    DeriskAction::Halve => "halved",
+                    DeriskAction::Pause => "paused",
+                }
+            ),
+            DeriskChange::Resumed {
+                drawdown,
+                equity,
+                peak,
+            } => format!(
+                "Equity {equity:.2} has recovered to {:.1}% below its peak of {peak:.2}; \
+                 sizing is back to normal",
+                drawdown * 100.0
+            ),
+        }
+    }
+}
+
+impl DrawdownState {
+    /// Fractional drawdown of `equity` from the peak.
+    pub fn drawdown(&self, equity: f64) -> f64 {
+        if self.peak <= 0.0 {
+            return 0.0;
+        }
+        (1.0 - equity / self.peak).max(0.0)
+    }
+
+    pub fn is_derisked(&self) -> bool {
+        self.derisked_at.is_some()
+    }
+
+    /// Records the latest `equity` at `now`, returning the change of sizing it causes.
+    pub fn update(&mut self, equity: f64, rule: &DrawdownRule, now: f64) -> Option<DeriskChange> {
+        self.peak = self.peak.max(equity);
+        self.equity = equity;
+        let drawdown = self.drawdown(equity);
+        match self.derisked_at {
+            None if drawdown >= rule.threshold => {
+                self.derisked_at = Some(now);
+                Some(DeriskChange::Derisked {
+                    action: rule.action,
+                    drawdown,
+                    equity,
+                    peak: self.peak,
+                })
+            }
+            Some(_) if drawdown <= rule.resume_below => {
+                self.derisked_at = None;
+                Some(DeriskChange::Resumed {
+                    drawdown,
+                    equity,
+                    peak: self.peak,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Lifts the de-risking and starts the peak over from the next equity seen.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Share of the usual notional positions are sized to.
+    pub fn sizing(&self, rule: &DrawdownRule) -> f64 {
+        match (self.is_derisked(), rule.action) {
+            (false, _) => 1.0,
+            (true, DeriskAction::Halve) => 0.5,
+            (true, DeriskAction::Pause) => 0.0,
+        }
+    }
+}
+
+/// Account equity in USD: the balances, coins at `prices`' latest closes, plus the
+/// unrealized PnL of positions held on margin. Spot holdings, without an entry price, are
+/// already in the balances.
+pub fn equity(account: &Account, positions: &[Position], prices: &CorrelationMatrix) -> f64 {
+    let rates = prices
+        .symbols
+        .iter()
+        .filter_map(|s| Some((s, prices.price(s)?)))
+        .fold(Rates::new(DEFAULT_CURRENCY), |rates, (s, price)| {
+            rates.with(s, price)
+        });
+    let rates = STABLECOINS
+        .iter()
+        .fold(rates, |rates, coin| rates.with(coin, 1.0));
+    let (balances, _) = account.value(&rates);
+    let unrealized = positions
+        .iter()
+        .filter_map(|p| {
+            let price = prices.price(&p.symbol)?;
+            Some(p.quantity * (price - p.entry_price?))
+        })
+        .sum::<f64>();
+    balances + unrealized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drawdown_derisks_until_recovered() {
+        let rule = DrawdownRule {
+            threshold: 0.2,
+            resume_below: 0.1,
+            action: DeriskAction::Halve,
+        };
+        let mut state = DrawdownState::default();
+        assert_eq!(state.update(10_000.0, &rule, 0.0), None);
+        assert_eq!(state.update(12_000.0, &rule, 1.0), None);
+        assert_eq!(state.sizing(&rule), 1.0);
+
+        // 25% off the 12k peak
+        let change = state.update(9_000.0, &rule, 2.0).unwrap();
+        assert!(matches!(change, DeriskChange::Derisked { .. }));
+        assert!(change
+            .describe()
+            .contains("25.0% below its peak of 12000.00"));
+        assert_eq!(state.sizing(&rule), 0.5);
+        // Recovering part of the way is not enough
+        assert_eq!(state.update(10_000.0, &rule, 3.0), None);
+        assert_eq!(state.derisked_at, Some(2.0));
+        assert!(matches!(
+            state.update(11_000.0, &rule, 4.0),
+            Some(DeriskChange::Resumed { .. })
+        ));
+        assert_eq!(state.sizing(&rule), 1.0);
+
+        let pause = DrawdownRule {
+            action: DeriskAction::Pause,
+            ..rule
+        };
+        state.update(5_000.0, &pause, 5.0);
+        assert_eq!(state.sizing(&pause), 0.0);
+        state.reset();
+        assert!(!state.is_derisked());
+        assert_eq!(state.update(5_000.0, &pause, 6.0), None);
+
+        // Cash and coins at their closes, plus a short's unrealized gain
+        let mut account = Account::default();
+        account.deposit("USD", 1_000.0);
+        account.deposit("USDC", 500.0);
+        account.deposit("ETH", 2.0);
+        let window = [[0.0, 100.0, 100.0, 100.0, 100.0, 1.0]];
+        let prices = CorrelationMatrix::from_windows(&[("ETH", &window)]);
+        let short = Position {
+            symbol: "ETH".into(),
+            quantity: -1.0,
+            entry_price: Some(110.0),
+        };
+        assert!((equity(&account, &[short], &prices) - 1_710.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,105 @@
+use serde::Serialize;
+
+/// Windows the dev loop scores on every prompt edit.
+pub const DEV_WINDOWS: usize = 12;
+/// Model calls the dev loop may make per edit for windows whose answer isn't cached yet.
+pub const DEV_FRESH_CALLS: usize = 4;
+
+/// A quick score of one prompt over the dev loop's fixed windows.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DevScore {
+    pub prompt_version: String,
+    /// Ends of the windows that were scored.
+    pub scored: Vec<usize>,
+    pub correct: usize,
+    /// Windows answered from the LLM cache.
+    pub replayed: usize,
+    /// Windows answered by a fresh model call.
+    pub fresh: usize,
+    /// Windows left unscored once the fresh call budget ran out.
+    pub skipped: usize,
+    /// Compounded return of the next-candle trades.
+    pub total_return: f64,
+}
+
+impl DevScore {
+    pub fn accuracy(&self) -> f64 {
+        if self.scored.is_empty() {
+            0.0
+        } else {
+            self.correct as f64 / self.scored.len() as f64
+        }
+    }
+
+    /// One line with the metrics and how they moved since `previous`.
+    pub fn describe(&self, previous: Option<&DevScore>) -> String {
+        let delta = |now: f64, before: f64| format!(" ({:+.1} pts)", (now - before) * 100.0);
+        let (accuracy_delta, return_delta) = match previous {
+            Some(p) => (
+                delta(self.accuracy(), p.accuracy()),
+                delta(self.total_return, p.total_return),
+            ),
+            None => (String::new(), String::new()),
+        };
+        let mut line = format!(
+            "{}  accuracy {:.1}%{}  return {:+.2}%{}  over {} windows ({} replayed, {} fresh, {} skipped)",
+            self.prompt_version,
+            self.accuracy() * 100.0,
+            accuracy_delta,
+            self.total_return * 100.0,
+            return_delta,
+            self.scored.len(),
+            self.replayed,
+            self.fresh,
+            self.skipped
+        );
+        if previous.is_some_and(|p| p.scored != self.scored) {
+            line.push_str("  [different windows than last time]");
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dev_score_delta() {
+        let before = DevScore {
+            prompt_version: "aaaa".into(),
+            scored: vec![24, 30, 40, 50],
+            correct: 2,
+            replayed: 4,
+            total_return: 0.01,
+            ..Default::default()
+        };
+        assert_eq!(
+            before.describe(None),
+            "aaaa  accuracy 50.0%  return +1.00%  over 4 windows (4 replayed, 0 fresh, 0 skipped)"
+        );
+
+        let after = DevScore {
+            prompt_version: "bbbb".into(),
+            correct: 3,
+            fresh: 4,
+            replayed: 0,
+            total_return: 0.005,
+            ..before.clone()
+        };
+        assert_eq!(
+            after.describe(Some(&before)),
+            "bbbb  accuracy 75.0% (+25.0 pts)  return +0.50% (-0.5 pts)  over 4 windows (0 replayed, 4 fresh, 0 skipped)"
+        );
+
+        let partial = DevScore {
+            scored: vec![24, 30],
+            skipped: 2,
+            ..after.clone()
+        };
+        assert!(partial
+            .describe(Some(&after))
+            .ends_with("[different windows than last time]"));
+        assert_eq!(DevScore::default().accuracy(), 0.0);
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+
+use serde::Serialize;
+
+#[cfg(feature = "native")]
+use crate::analytics::PredictionRecord;
+use crate::Action;
+
+/// Live signals per symbol the action mix is taken over.
+pub const DRIFT_WINDOW: usize = 48;
+/// Live signals needed before the mix is compared at all.
+pub const MIN_DRIFT_SIGNALS: usize = 24;
+/// Total variation distance from the backtest mix that raises the alarm.
+pub const DRIFT_THRESHOLD: f64 = 0.35;
+/// Backtest predictions needed for their mix to serve as the baseline.
+pub const MIN_BASELINE_PREDICTIONS: usize = 50;
+
+/// How many times each action was chosen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ActionMix {
+    pub long: usize,
+    pub short: usize,
+    pub none: usize,
+}
+
+impl ActionMix {
+    pub fn from_actions(actions: impl IntoIterator<Item = Action>) -> Self {
+        let mut mix = Self::default();
+        for action in actions {
+            match action {
+                Action::Long => mix.long += 1,
+                Action::Short => mix.short += 1,
+                Action::None => mix.none += 1,
+            }
+        }
+        mix
+    }
+
+    pub fn total(&self) -> usize {
+        self.long + self.short + self.none
+    }
+
+    /// Shares of long, short and none, all zero for an empty mix.
+    pub fn shares(&self) -> [f64; 3] {
+        let total = self.total().max(1) as f64;
+        [self.long, self.short, self.none].map(|n| n as f64 / total)
+    }
+
+    /// Total variation distance between the two mixes' shares: 0 when equal, 1 when they
+    /// never choose the same action.
+    pub fn distance(&self, other: &Self) -> f64 {
+        let (a, b) = (self.shares(), other.shares());
+        a.iter().zip(&b).map(|(x, y)| (x - y).abs()).sum::<f64>() / 2.0
+    }
+}
+
+fn describe_shares(mix: &ActionMix) -> String {
+    let [long, short, none] = mix.shares().map(|s| s * 100.0);
+    format!("{long:.0}% long, {short:.0}% short, {none:.0}% none")
+}
+
+/// Live actions having drifted away from what the prompt chose in backtests.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DriftAlarm {
+    pub symbol: String,
+    pub prompt_version: String,
+    pub live: ActionMix,
+    pub backtest: ActionMix,
+    pub distance: f64,
+}
+
+impl DriftAlarm {
+    pub fn describe(&self) -> String {
+        let mut out = format!(
+            "{} actions drifted from the backtest of prompt {} (distance {:.2}):\n",
+            self.symbol, self.prompt_version, self.distance
+        );
+        let _ = writeln!(
+            out,
+            "  live, last {:>4}: {}",
+            self.live.total(),
+            describe_shares(&self.live)
+        );
+        let _ = writeln!(
+            out,
+            "  backtest, {:>6}: {}",
+            self.backtest.total(),
+            describe_shares(&self.backtest)
+        );
+        out.push_str("A sudden skew usually means a prompt regression or bad input data.\n");
+        out
+    }
+}
+
+/// Compares one symbol's recent live actions with its prompt's backtest mix. The alarm is
+/// raised once on drifting and again only after the mix has come back.
+#[derive(Debug, Clone, Default)]
+pub struct DriftMonitor {
+    prompt_version: String,
+    baseline: Option<ActionMix>,
+    recent: VecDeque<Action>,
+    drifting: bool,
+}
+
+impl DriftMonitor {
+    /// Compares against `baseline` from now on. A new prompt version starts a new live
+    /// window, since the old prompt's actions say nothing about it.
+    pub fn set_baseline(&mut self, prompt_version: &str, baseline: Option<ActionMix>) {
+        if self.prompt_version != prompt_version {
+            self.prompt_version = prompt_version.to_string();
+            self.recent.clear();
+            self.drifting = false;
+        }
+        self.baseline = baseline.filter(|b| b.total() >= MIN_BASELINE_PREDICTIONS);
+    }
+
+    pub fn prompt_version(&self) -> &str {
+        &self.prompt_version
+    }
+
+    pub fn has_baseline(&self) -> bool {
+        self.baseline.is_some()
+    }
+
+    pub fn live_mix(&self) -> ActionMix {
+        ActionMix::from_actions(self.recent.iter().copied())
+    }
+
+    /// Records a live action, returning an alarm when it tips the mix into drift.
+    pub fn record(&mut self, symbol: &str, action: Action) -> Option<DriftAlarm> {
+        self.recent.push_back(action);
+        if self.recent.len() > DRIFT_WINDOW {
+            self.recent.pop_front();
+        }
+        let backtest = self.baseline?;
+        if self.recent.len() < MIN_DRIFT_SIGNALS {
+            return None;
+        }
+        let live = self.live_mix();
+        let distance = live.distance(&backtest);
+        let was_drifting = std::mem::replace(&mut self.drifting, distance >= DRIFT_THRESHOLD);
+        (self.drifting && !was_drifting).then(|| DriftAlarm {
+            symbol: symbol.to_string(),
+            prompt_version: self.prompt_version.clone(),
+            live,
+            backtest,
+            distance,
+        })
+    }
+}
+
+/// The action mix `records` hold for `symbol` and `prompt_version` from `source`,
+/// `"backtest"` or `"live"`.
+#[cfg(feature = "native")]
+pub fn recorded_mix(
+    records: &[PredictionRecord],
+    source: &str,
+    symbol: &str,
+    prompt_version: &str,
+) -> ActionMix {
+    ActionMix::from_actions(
+        records
+            .iter()
+            .filter(|r| r.source == source && r.symbol == symbol)
+            .filter(|r| r.prompt_hash == prompt_version)
+            .map(|r| r.action),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drift_monitor_alarms_once() {
+        let backtest = ActionMix {
+            long: 40,
+            short: 35,
+            none: 25,
+        };
+        let mut monitor = DriftMonitor::default();
+        monitor.set_baseline("abc", Some(backtest));
+
+        // A mix like the backtest's stays quiet
+        let mixed = [Action::Long, Action::Short, Action::None, Action::Long];
+        for action in mixed.iter().cycle().take(DRIFT_WINDOW) {
+            assert_eq!(monitor.record("ETH", *action), None);
+        }
+
+        // Suddenly only shorts: one alarm, not one per signal
+        let alarms = (0..DRIFT_WINDOW)
+            .filter_map(|_| monitor.record("ETH", Action::Short))
+            .collect::<Vec<_>>();
+        assert_eq!(alarms.len(), 1);
+        assert!(alarms[0].distance >= DRIFT_THRESHOLD);
+        assert!(alarms[0].live.short > alarms[0].live.long + alarms[0].live.none);
+        assert!(alarms[0].describe().contains("% short"));
+
+        // A new prompt starts over; too small a backtest is no baseline
+        monitor.set_baseline("def", Some(ActionMix::from_actions([Action::Long; 5])));
+        assert_eq!(monitor.live_mix().total(), 0);
+        for _ in 0..DRIFT_WINDOW {
+            assert_eq!(monitor.record("ETH", Action::Short), None);
+        }
+    }
+}
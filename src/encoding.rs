@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+
+use crate::prediction::Prediction;
+
+/// How each symbol's candles are scaled before they reach the prompt. Raw prices let a
+/// series at 50,000 dwarf one at 150; the other encodings put every symbol on one scale.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CandleEncoding {
+    /// Prices and volume as quoted.
+    #[default]
+    Raw,
+    /// Prices as percent changes from the previous candle's close, the first candle's from
+    /// its own open; volume as a multiple of the window's mean.
+    Returns,
+    /// Prices as standard deviations from the mean of the window's closes; volume as
+    /// standard deviations from its mean.
+    ZScore,
+}
+
+impl CandleEncoding {
+    pub const ALL: [CandleEncoding; 3] = [
+        CandleEncoding::Raw,
+        CandleEncoding::Returns,
+        CandleEncoding::ZScore,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CandleEncoding::Raw => "raw",
+            CandleEncoding::Returns => "returns",
+            CandleEncoding::ZScore => "zscore",
+        }
+    }
+
+    /// How the prompt's data header explains the encoded values, and the units it asks
+    /// levels in; nothing for raw candles.
+    pub fn note(&self) -> Option<&'static str> {
+        match self {
+            CandleEncoding::Raw => None,
+            CandleEncoding::Returns => Some(
+                "prices in % change from the previous close, volume as a multiple of the \
+                 window's mean; give the invalidation and target prices as % change from \
+                 the last close",
+            ),
+            CandleEncoding::ZScore => Some(
+                "prices and volume as z-scores over the window; give the invalidation and \
+                 target prices as z-scores too",
+            ),
+        }
+    }
+}
+
+impl std::str::FromStr for CandleEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CandleEncoding::ALL
+            .into_iter()
+            .find(|e| e.as_str() == s)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown candle encoding {:?} (expected raw, returns or zscore)",
+                    s
+                )
+            })
+    }
+}
+
+/// Mean and standard deviation of `values`, the deviation 1 when they don't vary so
+/// constant series encode as zeros rather than NaN.
+fn mean_std(values: impl Iterator<Item = f64> + Clone) -> (f64, f64) {
+    let n = values.clone().count().max(1) as f64;
+    let mean = values.clone().sum::<f64>() / n;
+    let std = (values.map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+    (mean, if std > 0.0 { std } else { 1.0 })
+}
+
+/// Chronological `candles` in `encoding`, timestamps untouched.
+pub fn encode_candles(candles: &[[f64; 6]], encoding: CandleEncoding) -> Vec<[f64; 6]> {
+    match encoding {
+        CandleEncoding::Raw => candles.to_vec(),
+        CandleEncoding::Returns => {
+            let (mean_volume, _) = mean_std(candles.iter().map(|c| c[5]));
+            candles
+                .iter()
+                .enumerate()
+                .map(|(i, c)| {
+                    let base = if i == 0 { c[1] } else { candles[i - 1][4] };
+                    let pct = |p: f64| {
+                        if base > 0.0 {
+                            (p / base - 1.0) * 100.0
+                        } else {
+                            0.0
+                        }
+                    };
+                    let volume = if mean_volume > 0.0 {
+                        c[5] / mean_volume
+                    } else {
+                        0.0
+                    };
+                    [c[0], pct(c[1]), pct(c[2]), pct(c[3]), pct(c[4]), volume]
+                })
+                .collect()
+        }
+        CandleEncoding::ZScore => {
+            let (mean, std) = mean_std(candles.iter().map(|c| c[4]));
+            let (mean_volume, std_volume) = mean_std(candles.iter().map(|c| c[5]));
+            let z = |p: f64| (p - mean) / std;
+            candles
+                .iter()
+                .map(|c| {
+                    [
+                        c[0],
+                        z(c[1]),
+                        z(c[2]),
+                        z(c[3]),
+                        z(c[4]),
+                        (c[5] - mean_volume) / std_volume,
+                    ]
+                })
+                .collect()
+        }
+    }
+}
+
+/// A stop or target the model gave in the units `encoding` shows the target's `window` in,
+/// as a price: a percent change from the last close for returns, a z-score over the
+/// window's closes for z-scores. `None` when it maps to no positive price.
+pub fn decode_level(window: &[[f64; 6]], encoding: CandleEncoding, level: f64) -> Option<f64> {
+    let price = match encoding {
+        CandleEncoding::Raw => level,
+        CandleEncoding::Returns => window.last()?[4] * (1.0 + level / 100.0),
+        CandleEncoding::ZScore => {
+            let (mean, std) = mean_std(window.iter().map(|c| c[4]));
+            mean + level * std
+        }
+    };
+    (price.is_finite() && price > 0.0).then_some(price)
+}
+
+/// `prediction` with its levels [decoded](decode_level) into prices of the target's
+/// `window`.
+pub fn decode_prediction(
+    prediction: Prediction,
+    window: &[[f64; 6]],
+    encoding: CandleEncoding,
+) -> Prediction {
+    let decode = |level: Option<f64>| level.and_then(|l| decode_level(window, encoding, l));
+    Prediction {
+        invalidation_price: decode(prediction.invalidation_price),
+        target_price: decode(prediction.target_price),
+        ..prediction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute::symbol_spec;
+    use crate::prompt_builder::build_candle_section;
+
+    #[test]
+    fn test_encodings_put_symbols_on_one_scale() {
+        let candles = |level: f64| {
+            [1.0, 1.02, 0.99, 1.01]
+                .iter()
+                .enumerate()
+                .map(|(i, m)| {
+                    let close = level * m;
+                    [
+                        i as f64 * 3600.0,
+                        close * 0.995,
+                        close * 1.01,
+                        close * 0.99,
+                        close,
+                        level / 10.0 * (i + 1) as f64,
+                    ]
+                })
+                .collect::<Vec<_>>()
+        };
+        let (btc, sol) = (candles(50_000.0), candles(150.0));
+        assert_eq!(encode_candles(&btc, CandleEncoding::Raw), btc);
+
+        for encoding in [CandleEncoding::Returns, CandleEncoding::ZScore] {
+            let (btc, sol) = (
+                encode_candles(&btc, encoding),
+                encode_candles(&sol, encoding),
+            );
+            for (b, s) in btc.iter().zip(&sol) {
+                assert_eq!(b[0], s[0]);
+                assert!(b.iter().zip(s).all(|(x, y)| (x - y).abs() < 1e-9));
+            }
+        }
+        let returns = encode_candles(&btc, CandleEncoding::Returns);
+        assert!((returns[1][4] - 2.0).abs() < 1e-9);
+        assert!((returns[0][5] - 0.4).abs() < 1e-9);
+        let zscores = encode_candles(&btc, CandleEncoding::ZScore);
+        assert!(zscores.iter().map(|c| c[4]).sum::<f64>().abs() < 1e-9);
+
+        // Levels come back as prices: 2% above the last close, and the last close's z-score
+        let last = btc[3][4];
+        let up = decode_level(&btc, CandleEncoding::Returns, 2.0).unwrap();
+        assert!((up - last * 1.02).abs() < 1e-6);
+        let z = decode_level(&btc, CandleEncoding::ZScore, zscores[3][4]).unwrap();
+        assert!((z - last).abs() < 1e-6);
+        assert_eq!(decode_level(&btc, CandleEncoding::Returns, -100.0), None);
+
+        let section = build_candle_section(&[(symbol_spec("BTC"), &btc)], CandleEncoding::Returns);
+        assert!(section.contains("volume]; prices in % change from the previous close"));
+        assert!(section.contains("BTC: [[0.00,0.000,1.508,-0.503,0.503,0.400],"));
+
+        assert_eq!(
+            "zscore".parse::<CandleEncoding>().unwrap(),
+            CandleEncoding::ZScore
+        );
+        assert!("log".parse::<CandleEncoding>().is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use serde::Serialize;
+
+use crate::broker::{Position, RiskLimits};
+use crate::features::{correlation, returns};
+
+/// Pairwise correlations of the symbols' hourly returns over their recent windows, with
+/// the latest close of each, which values positions.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CorrelationMatrix {
+    pub symbols: Vec<String>,
+    pub prices: Vec<f64>,
+    /// `values[i][j]`, 1 on the diagonal and 0 where too few returns overlap.
+    pub values: Vec<Vec<f64>>,
+}
+
+impl CorrelationMatrix {
+    pub fn from_windows(windows: &[(&str, &[[f64; 6]])]) -> Self {
+        let series = windows
+            .iter()
+            .map(|(_, candles)| returns(candles))
+            .collect::<Vec<_>>();
+        let values = (0..windows.len())
+            .map(|i| {
+                (0..windows.len())
+                    .map(|j| {
+                        if i == j {
+                            1.0
+                        } else {
+                            correlation(&series[i], &series[j]).unwrap_or(0.0)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        Self {
+            symbols: windows.iter().map(|(s, _)| s.to_string()).collect(),
+            prices: windows
+                .iter()
+                .map(|(_, candles)| candles.last().map_or(0.0, |c| c[4]))
+                .collect(),
+            values,
+        }
+    }
+
+    fn index(&self, symbol: &str) -> Option<usize> {
+        self.symbols.iter().position(|s| s == symbol)
+    }
+
+    /// Correlation of two symbols' returns: 1 for a symbol with itself, 0 when unknown.
+    pub fn get(&self, a: &str, b: &str) -> f64 {
+        if a == b {
+            return 1.0;
+        }
+        match (self.index(a), self.index(b)) {
+            (Some(i), Some(j)) => self.values[i][j],
+            _ => 0.0,
+        }
+    }
+
+    pub fn price(&self, symbol: &str) -> Option<f64> {
+        self.index(symbol)
+            .map(|i| self.prices[i])
+            .filter(|p| *p > 0.0)
+    }
+}
+
+/// Net exposure along `symbol` of the positions in other symbols, in USD: each one's
+/// notional weighted by its correlation with `symbol`. Positions priced neither by
+/// `matrix` nor by their entry are left out.
+pub fn correlated_exposure(
+    symbol: &str,
+    positions: &[Position],
+    matrix: &CorrelationMatrix,
+) -> f64 {
+    positions
+        .iter()
+        .filter(|p| p.symbol != symbol)
+        .filter_map(|p| {
+            let price = matrix.price(&p.symbol).or(p.entry_price)?;
+            Some(p.quantity * price * matrix.get(symbol, &p.symbol))
+        })
+        .sum()
+}
+
+/// `target` units of `symbol` at `price`, reduced so the net exposure along it, its own
+/// notional plus the other positions' correlated exposure, stays within
+/// `limits.max_correlated`. The target is never enlarged or flipped.
+pub fn cap_correlated(
+    symbol: &str,
+    target: f64,
+    price: f64,
+    positions: &[Position],
+    matrix: &CorrelationMatrix,
+    limits: &RiskLimits,
+) -> f64 {
+    if target == 0.0 || price <= 0.0 {
+        return target;
+    }
+    let others = correlated_exposure(symbol, positions, matrix);
+    let cap = limits.max_correlated;
+    if target > 0.0 {
+        ((cap - others) / price).clamp(0.0, target)
+    } else {
+        ((-cap - others) / price).clamp(target, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_correlated_positions_share_one_cap() {
+        let window = |price: f64, moves: [f64; 8]| {
+            let mut close = price;
+            let mut candles = vec![[0.0, close, close, close, close, 1.0]];
+            for (i, m) in moves.into_iter().enumerate() {
+                close *= 1.0 + m;
+                candles.push([(i + 1) as f64 * 3600.0, close, close, close, close, 1.0]);
+            }
+            candles
+        };
+        let (up, down) = (0.01, -0.01);
+        let eth = window(100.0, [up, down, up, down, up, down, up, down]);
+        let sol = window(10.0, [0.02, -0.02, 0.02, -0.02, 0.02, -0.02, 0.02, -0.02]);
+        let btc = window(1000.0, [up, up, down, down, up, up, down, down]);
+        let matrix =
+            CorrelationMatrix::from_windows(&[("ETH", &eth), ("SOL", &sol), ("BTC", &btc)]);
+        assert!((matrix.get("ETH", "SOL") - 1.0).abs() < 1e-9);
+        assert!(matrix.get("ETH", "BTC").abs() < 1e-9);
+        assert_eq!(matrix.get("ETH", "DOGE"), 0.0);
+
+        // Long 4500 of SOL leaves room for 500 more of the same bet in ETH
+        let sol_price = matrix.price("SOL").unwrap();
+        let positions = [Position {
+            symbol: "SOL".into(),
+            quantity: 4500.0 / sol_price,
+            entry_price: None,
+        }];
+        let limits = RiskLimits::default();
+        let eth_price = matrix.price("ETH").unwrap();
+        let capped = cap_correlated(
+            "ETH",
+            1000.0 / eth_price,
+            eth_price,
+            &positions,
+            &matrix,
+            &limits,
+        );
+        assert!((capped * eth_price - 500.0).abs() < 1e-6);
+        // A short ETH offsets the SOL long, and BTC is a bet of its own
+        let short = -1000.0 / eth_price;
+        assert_eq!(
+            cap_correlated("ETH", short, eth_price, &positions, &matrix, &limits),
+            short
+        );
+        let btc_price = matrix.price("BTC").unwrap();
+        let btc = 1000.0 / btc_price;
+        assert!(
+            (cap_correlated("BTC", btc, btc_price, &positions, &matrix, &limits) - btc).abs()
+                < 1e-9
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::compute::sha256_hex;
+
+/// A failure that can be injected into the calls the pipeline makes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fault {
+    /// A model or exchange call that runs out its deadline.
+    Timeout,
+    /// A model or exchange call answered 429 Too Many Requests.
+    RateLimit,
+    /// A model answer cut off partway through its JSON.
+    MalformedJson,
+    /// A candle missing from the exchange's answer.
+    CandleGap,
+}
+
+impl Fault {
+    pub const ALL: [Fault; 4] = [
+        Fault::Timeout,
+        Fault::RateLimit,
+        Fault::MalformedJson,
+        Fault::CandleGap,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fault::Timeout => "timeout",
+            Fault::RateLimit => "rate_limit",
+            Fault::MalformedJson => "malformed_json",
+            Fault::CandleGap => "candle_gap",
+        }
+    }
+}
+
+/// How often each fault strikes, from 0 to 1, and the seed deciding which calls it
+/// strikes. A fault left out never does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FaultConfig {
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default)]
+    pub timeout: f64,
+    #[serde(default)]
+    pub rate_limit: f64,
+    #[serde(default)]
+    pub malformed_json: f64,
+    #[serde(default)]
+    pub candle_gap: f64,
+}
+
+impl FaultConfig {
+    pub fn probability(&self, fault: Fault) -> f64 {
+        match fault {
+            Fault::Timeout => self.timeout,
+            Fault::RateLimit => self.rate_limit,
+            Fault::MalformedJson => self.malformed_json,
+            Fault::CandleGap => self.candle_gap,
+        }
+    }
+
+    /// Whether `fault` strikes try `n` of the call identified by `key`. The same seed,
+    /// key and try always decide alike, whatever order concurrent calls run in.
+    pub fn strikes(&self, fault: Fault, key: &str, n: u32) -> bool {
+        let probability = self.probability(fault);
+        if probability <= 0.0 {
+            return false;
+        }
+        let hash = sha256_hex(format!("{}:{}:{}:{}", self.seed, fault.name(), key, n).as_bytes());
+        let bits = u64::from_str_radix(&hash[..16], 16).unwrap_or(0) >> 11;
+        let draw = bits as f64 / (1u64 << 53) as f64;
+        draw < probability
+    }
+}
+
+/// Parses a fault file: a JSON object with the `seed` and any of the faults' probabilities,
+/// e.g. `{"seed": 7, "rate_limit": 0.2, "candle_gap": 0.05}`.
+pub fn parse_faults(json: &str) -> Result<FaultConfig> {
+    let config: FaultConfig = serde_json::from_str(json)?;
+    for fault in Fault::ALL {
+        anyhow::ensure!(
+            (0.0..=1.0).contains(&config.probability(fault)),
+            "The {} probability must be between 0 and 1",
+            fault.name()
+        );
+    }
+    Ok(config)
+}
+
+#[cfg(feature = "native")]
+struct FaultInjector {
+    config: FaultConfig,
+    /// Tries so far of each call, by fault and key.
+    tries: std::sync::Mutex<std::collections::HashMap<(Fault, String), u32>>,
+    injected: [std::sync::atomic::AtomicU64; 4],
+}
+
+#[cfg(feature = "native")]
+static INJECTOR: std::sync::OnceLock<FaultInjector> = std::sync::OnceLock::new();
+
+/// Reads a fault file.
+#[cfg(feature = "native")]
+pub fn load_faults(path: &std::path::Path) -> Result<FaultConfig> {
+    use anyhow::Context;
+
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    parse_faults(&json).with_context(|| format!("Invalid faults in {}", path.display()))
+}
+
+/// Injects faults per `config` into every model and exchange call for the rest of the
+/// process. Only the first configuration installed counts.
+#[cfg(feature = "native")]
+pub fn inject_faults(config: FaultConfig) {
+    let installed = INJECTOR.set(FaultInjector {
+        config,
+        tries: Default::default(),
+        injected: Default::default(),
+    });
+    if installed.is_ok() {
+        tracing::warn!(?config, "Injecting faults");
+    }
+}
+
+/// Whether `fault` strikes this try of the call identified by `key`; never unless faults
+/// are being injected.
+#[cfg(feature = "native")]
+pub(crate) fn strikes(fault: Fault, key: &str) -> bool {
+    let Some(injector) = INJECTOR.get() else {
+        return false;
+    };
+    if injector.config.probability(fault) <= 0.0 {
+        return false;
+    }
+    let n = {
+        let mut tries = injector.tries.lock().unwrap_or_else(|e| e.into_inner());
+        let n = tries.entry((fault, key.to_string())).or_default();
+        *n += 1;
+        *n
+    };
+    let struck = injector.config.strikes(fault, key, n);
+    if struck {
+        let i = Fault::ALL.iter().position(|f| *f == fault).unwrap_or(0);
+        injector.injected[i].fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        tracing::info!(fault = fault.name(), %key, "Injected a fault");
+    }
+    struck
+}
+
+/// How many of each fault were injected so far.
+#[cfg(feature = "native")]
+pub fn injected() -> Vec<(&'static str, u64)> {
+    let Some(injector) = INJECTOR.get() else {
+        return Vec::new();
+    };
+    Fault::ALL
+        .iter()
+        .zip(&injector.injected)
+        .map(|(f, n)| (f.name(), n.load(std::sync::atomic::Ordering::Relaxed)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_faults_strike_deterministically() {
+        let config = parse_faults(r#"{"seed": 7, "rate_limit": 0.3, "candle_gap": 1}"#).unwrap();
+        let struck = |config: &FaultConfig| {
+            (0..1000)
+                .filter(|i| config.strikes(Fault::RateLimit, &format!("call-{i}"), 1))
+                .count()
+        };
+        let count = struck(&config);
+        assert!((240..360).contains(&count), "{count}");
+        assert_eq!(struck(&config), count);
+        // Another seed strikes other calls
+        let reseeded = FaultConfig { seed: 8, ..config };
+        assert!((0..50).any(|i| {
+            let key = format!("call-{i}");
+            config.strikes(Fault::RateLimit, &key, 1) != reseeded.strikes(Fault::RateLimit, &key, 1)
+        }));
+
+        assert!(config.strikes(Fault::CandleGap, "ETH-0", 1));
+        assert!(!config.strikes(Fault::Timeout, "call-0", 1));
+        assert!(parse_faults(r#"{"timeout": 1.5}"#).is_err());
+        assert!(parse_faults(r#"{"outage": 0.1}"#).is_err());
+    }
+}
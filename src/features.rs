@@ -0,0 +1,313 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::implied_vol::IvRegime;
+use crate::indicators::vwap_distance;
+use crate::prompt_builder::SymbolRole;
+use crate::routing::realized_volatility;
+use crate::snapshot::{MarketSnapshot, SymbolSnapshot};
+
+const CLOSE: usize = 4;
+
+/// A named value derived from a [`MarketSnapshot`], per symbol. Each feature is one type;
+/// adding it to [`FeatureRegistry::standard`] puts it in prompts, rule strategies and
+/// backtest reports alike.
+pub trait Feature: Send + Sync {
+    /// Stable snake_case name, as prompts and reports show it.
+    fn name(&self) -> &'static str;
+
+    /// The value for `symbol` within `snapshot`, or `None` when its inputs are missing.
+    fn value(&self, snapshot: &MarketSnapshot, symbol: &SymbolSnapshot) -> Option<f64>;
+}
+
+/// One computed feature value.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FeatureValue {
+    pub symbol: String,
+    pub name: &'static str,
+    pub value: f64,
+}
+
+/// Every feature value of one snapshot, by symbol in prompt order, then in registry order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FeatureSet(pub Vec<FeatureValue>);
+
+impl FeatureSet {
+    pub fn get(&self, symbol: &str, name: &str) -> Option<f64> {
+        self.0
+            .iter()
+            .find(|v| v.symbol == symbol && v.name == name)
+            .map(|v| v.value)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// The features computed for every snapshot.
+#[derive(Default)]
+pub struct FeatureRegistry {
+    features: Vec<Box<dyn Feature>>,
+}
+
+impl FeatureRegistry {
+    /// Every built-in feature.
+    pub fn standard() -> Self {
+        Self::default()
+            .with(VwapDistance)
+            .with(RealizedVolatility)
+            .with(TargetCorrelation)
+            .with(FundingRate)
+            .with(LiquidationLongShare)
+            .with(ImpliedVolLevel)
+            .with(IvSpike)
+            .with(TakerBuyShare)
+    }
+
+    pub fn with(mut self, feature: impl Feature + 'static) -> Self {
+        self.features.push(Box::new(feature));
+        self
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.features.iter().map(|f| f.name()).collect()
+    }
+
+    pub fn compute(&self, snapshot: &MarketSnapshot) -> FeatureSet {
+        let mut values = Vec::new();
+        for symbol in &snapshot.symbols {
+            for feature in &self.features {
+                if let Some(value) = feature.value(snapshot, symbol).filter(|v| v.is_finite()) {
+                    values.push(FeatureValue {
+                        symbol: symbol.spec.symbol.to_string(),
+                        name: feature.name(),
+                        value,
+                    });
+                }
+            }
+        }
+        FeatureSet(values)
+    }
+}
+
+/// Distance of the last close from the window VWAP, as a fraction.
+pub struct VwapDistance;
+
+impl Feature for VwapDistance {
+    fn name(&self) -> &'static str {
+        "vwap_distance"
+    }
+
+    fn value(&self, _: &MarketSnapshot, symbol: &SymbolSnapshot) -> Option<f64> {
+        vwap_distance(symbol.candles)
+    }
+}
+
+/// Standard deviation of the window's hourly close-to-close returns.
+pub struct RealizedVolatility;
+
+impl Feature for RealizedVolatility {
+    fn name(&self) -> &'static str {
+        "realized_volatility"
+    }
+
+    fn value(&self, _: &MarketSnapshot, symbol: &SymbolSnapshot) -> Option<f64> {
+        realized_volatility(symbol.candles)
+    }
+}
+
+/// Correlation of a context symbol's hourly returns with the target's over the window.
+pub struct TargetCorrelation;
+
+impl Feature for TargetCorrelation {
+    fn name(&self) -> &'static str {
+        "correlation_to_target"
+    }
+
+    fn value(&self, snapshot: &MarketSnapshot, symbol: &SymbolSnapshot) -> Option<f64> {
+        if symbol.spec.role == SymbolRole::Target {
+            return None;
+        }
+        let target = snapshot
+            .symbols
+            .iter()
+            .find(|s| s.spec.role == SymbolRole::Target)?;
+        correlation(&returns(target.candles), &returns(symbol.candles))
+    }
+}
+
+pub(crate) fn returns(candles: &[[f64; 6]]) -> Vec<f64> {
+    candles
+        .windows(2)
+        .map(|w| w[1][CLOSE] / w[0][CLOSE] - 1.0)
+        .collect()
+}
+
+/// Pearson correlation over the most recent values both series have.
+pub(crate) fn correlation(a: &[f64], b: &[f64]) -> Option<f64> {
+    let n = a.len().min(b.len());
+    if n < 3 {
+        return None;
+    }
+    let (a, b) = (&a[a.len() - n..], &b[b.len() - n..]);
+    let mean = |x: &[f64]| x.iter().sum::<f64>() / n as f64;
+    let (ma, mb) = (mean(a), mean(b));
+    let cov = a
+        .iter()
+        .zip(b)
+        .map(|(x, y)| (x - ma) * (y - mb))
+        .sum::<f64>();
+    let va = a.iter().map(|x| (x - ma).powi(2)).sum::<f64>();
+    let vb = b.iter().map(|y| (y - mb).powi(2)).sum::<f64>();
+    (va > 0.0 && vb > 0.0).then(|| cov / (va * vb).sqrt())
+}
+
+/// Perpetual funding rate per interval, as a fraction.
+pub struct FundingRate;
+
+impl Feature for FundingRate {
+    fn name(&self) -> &'static str {
+        "funding_rate"
+    }
+
+    fn value(&self, _: &MarketSnapshot, symbol: &SymbolSnapshot) -> Option<f64> {
+        symbol.context.funding_rate
+    }
+}
+
+/// Share of the day's liquidated value that was long.
+pub struct LiquidationLongShare;
+
+impl Feature for LiquidationLongShare {
+    fn name(&self) -> &'static str {
+        "liquidation_long_share"
+    }
+
+    fn value(&self, _: &MarketSnapshot, symbol: &SymbolSnapshot) -> Option<f64> {
+        let liq = symbol.context.liquidations?;
+        let total = liq.long_usd + liq.short_usd;
+        (total > 0.0).then(|| liq.long_usd / total)
+    }
+}
+
+/// Implied volatility index level, annualized percent.
+pub struct ImpliedVolLevel;
+
+impl Feature for ImpliedVolLevel {
+    fn name(&self) -> &'static str {
+        "implied_vol"
+    }
+
+    fn value(&self, _: &MarketSnapshot, symbol: &SymbolSnapshot) -> Option<f64> {
+        symbol.context.implied_volatility.map(|iv| iv.level)
+    }
+}
+
+/// 1 when implied volatility is in its spike regime, 0 otherwise.
+pub struct IvSpike;
+
+impl Feature for IvSpike {
+    fn name(&self) -> &'static str {
+        "iv_spike"
+    }
+
+    fn value(&self, _: &MarketSnapshot, symbol: &SymbolSnapshot) -> Option<f64> {
+        let iv = symbol.context.implied_volatility?;
+        Some(if iv.regime == IvRegime::Spike {
+            1.0
+        } else {
+            0.0
+        })
+    }
+}
+
+/// Share of recent traded volume bought by takers, live only.
+pub struct TakerBuyShare;
+
+impl Feature for TakerBuyShare {
+    fn name(&self) -> &'static str {
+        "taker_buy_share"
+    }
+
+    fn value(&self, _: &MarketSnapshot, symbol: &SymbolSnapshot) -> Option<f64> {
+        symbol.context.microstructure.map(|m| m.taker_buy_share)
+    }
+}
+
+/// How a feature of the target differed between windows the model called right and wrong.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FeatureStats {
+    pub correct: usize,
+    pub wrong: usize,
+    pub mean_when_correct: f64,
+    pub mean_when_wrong: f64,
+}
+
+/// Per-feature [`FeatureStats`] over a backtest, for the target symbol.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FeatureReport {
+    pub by_feature: BTreeMap<&'static str, FeatureStats>,
+}
+
+impl FeatureReport {
+    pub fn record(&mut self, features: &FeatureSet, target: &str, correct: bool) {
+        for v in features.0.iter().filter(|v| v.symbol == target) {
+            let s = self.by_feature.entry(v.name).or_default();
+            let (n, mean) = if correct {
+                (&mut s.correct, &mut s.mean_when_correct)
+            } else {
+                (&mut s.wrong, &mut s.mean_when_wrong)
+            };
+            *n += 1;
+            *mean += (v.value - *mean) / *n as f64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::{AuxContext, ImpliedVolatility};
+
+    #[test]
+    fn test_standard_features() {
+        let candle = |close: f64| [0.0, close, close, close, close, 1.0];
+        let eth = [100.0, 102.0, 101.0, 104.0, 103.0].map(candle);
+        let btc = [50.0, 51.0, 50.5, 52.0, 51.5].map(candle);
+        let windows = [("ETH", &eth[..], &[][..]), ("BTC", &btc[..], &[][..])];
+        let snapshot = MarketSnapshot::from_windows(&windows)
+            .with_target("ETH")
+            .with_context(
+                "ETH",
+                AuxContext {
+                    implied_volatility: Some(ImpliedVolatility {
+                        level: 70.0,
+                        change_24h: Some(0.2),
+                        regime: IvRegime::Spike,
+                    }),
+                    ..Default::default()
+                },
+            );
+
+        let registry = FeatureRegistry::standard();
+        let features = registry.compute(&snapshot);
+        assert_eq!(features.get("ETH", "iv_spike"), Some(1.0));
+        assert_eq!(features.get("ETH", "implied_vol"), Some(70.0));
+        assert_eq!(features.get("BTC", "implied_vol"), None);
+        assert_eq!(features.get("ETH", "correlation_to_target"), None);
+        // BTC moves in lockstep with ETH at half the price
+        let corr = features.get("BTC", "correlation_to_target").unwrap();
+        assert!((corr - 1.0).abs() < 1e-9);
+        assert!(features.get("ETH", "realized_volatility").unwrap() > 0.0);
+
+        let mut report = FeatureReport::default();
+        report.record(&features, "ETH", true);
+        report.record(&features, "ETH", false);
+        let iv = &report.by_feature["implied_vol"];
+        assert_eq!((iv.correct, iv.wrong), (1, 1));
+        assert_eq!(iv.mean_when_wrong, 70.0);
+        assert!(!report.by_feature.contains_key("correlation_to_target"));
+    }
+}
@@ -0,0 +1,298 @@
+use std::slice;
+
+use crate::indicators::{volume_profile, vwap, vwap_distance};
+use crate::simulator::{next_candle_trade, summarize, Trade};
+use crate::{label_candles, Action};
+
+// C ABI over the labeling, indicator and simulation core; `include/happycharts.h` declares
+// it. Candles are passed as `len` contiguous rows of six doubles,
+// `[time, open, high, low, close, volume]`, in chronological order. Functions return one
+// of the status codes below and write their results through out-pointers.
+
+pub const HC_OK: i32 = 0;
+/// The computation has no result for this input (e.g. a window without volume).
+pub const HC_NO_RESULT: i32 = 1;
+pub const HC_NULL_POINTER: i32 = -1;
+pub const HC_INVALID_ARGUMENT: i32 = -2;
+
+pub const HC_ACTION_NONE: i32 = 0;
+pub const HC_ACTION_LONG: i32 = 1;
+pub const HC_ACTION_SHORT: i32 = 2;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HcVolumeBucket {
+    pub low: f64,
+    pub high: f64,
+    pub volume: f64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HcTrade {
+    pub side: i32,
+    pub entry_time: f64,
+    pub entry_price: f64,
+    pub exit_time: f64,
+    pub exit_price: f64,
+    pub return_pct: f64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HcSimulationSummary {
+    pub trades: usize,
+    pub total_return: f64,
+    pub win_rate: f64,
+    pub max_drawdown: f64,
+}
+
+fn action_code(action: Action) -> i32 {
+    match action {
+        Action::None => HC_ACTION_NONE,
+        Action::Long => HC_ACTION_LONG,
+        Action::Short => HC_ACTION_SHORT,
+    }
+}
+
+fn action_from_code(code: i32) -> Option<Action> {
+    match code {
+        HC_ACTION_NONE => Some(Action::None),
+        HC_ACTION_LONG => Some(Action::Long),
+        HC_ACTION_SHORT => Some(Action::Short),
+        _ => None,
+    }
+}
+
+impl From<Trade> for HcTrade {
+    fn from(t: Trade) -> Self {
+        Self {
+            side: action_code(t.side),
+            entry_time: t.entry_time,
+            entry_price: t.entry_price,
+            exit_time: t.exit_time,
+            exit_price: t.exit_price,
+            return_pct: t.return_pct,
+        }
+    }
+}
+
+/// Borrows `len` candles, allowing a null pointer only when `len` is zero.
+unsafe fn borrow_candles<'a>(ptr: *const f64, len: usize) -> Option<&'a [[f64; 6]]> {
+    if len == 0 {
+        return Some(&[]);
+    }
+    (!ptr.is_null()).then(|| slice::from_raw_parts(ptr.cast::<[f64; 6]>(), len))
+}
+
+/// Writes one action code per candle to `out_actions` (`len` entries), using the same
+/// thresholds as the backtest labels.
+///
+/// # Safety
+/// `candles` must point to `len * 6` doubles and `out_actions` to `len` writable ints.
+#[no_mangle]
+pub unsafe extern "C" fn hc_label_candles(
+    candles: *const f64,
+    len: usize,
+    out_actions: *mut i32,
+) -> i32 {
+    let Some(data) = borrow_candles(candles, len) else {
+        return HC_NULL_POINTER;
+    };
+    if len > 0 && out_actions.is_null() {
+        return HC_NULL_POINTER;
+    }
+    for (i, label) in label_candles(data).into_iter().enumerate() {
+        *out_actions.add(i) = action_code(label);
+    }
+    HC_OK
+}
+
+/// Volume-weighted average price of the window.
+///
+/// # Safety
+/// `candles` must point to `len * 6` doubles and `out` to a writable double.
+#[no_mangle]
+pub unsafe extern "C" fn hc_vwap(candles: *const f64, len: usize, out: *mut f64) -> i32 {
+    write_option(borrow_candles(candles, len).map(vwap), out)
+}
+
+/// Distance of the last close from the VWAP, as a fraction of the VWAP.
+///
+/// # Safety
+/// `candles` must point to `len * 6` doubles and `out` to a writable double.
+#[no_mangle]
+pub unsafe extern "C" fn hc_vwap_distance(candles: *const f64, len: usize, out: *mut f64) -> i32 {
+    write_option(borrow_candles(candles, len).map(vwap_distance), out)
+}
+
+unsafe fn write_option(value: Option<Option<f64>>, out: *mut f64) -> i32 {
+    match value {
+        None => HC_NULL_POINTER,
+        Some(_) if out.is_null() => HC_NULL_POINTER,
+        Some(None) => HC_NO_RESULT,
+        Some(Some(v)) => {
+            *out = v;
+            HC_OK
+        }
+    }
+}
+
+/// Fills `out_buckets` with a `buckets`-band volume profile of the window.
+///
+/// # Safety
+/// `candles` must point to `len * 6` doubles and `out_buckets` to `buckets` writable
+/// buckets.
+#[no_mangle]
+pub unsafe extern "C" fn hc_volume_profile(
+    candles: *const f64,
+    len: usize,
+    buckets: usize,
+    out_buckets: *mut HcVolumeBucket,
+) -> i32 {
+    let Some(data) = borrow_candles(candles, len) else {
+        return HC_NULL_POINTER;
+    };
+    if buckets == 0 {
+        return HC_INVALID_ARGUMENT;
+    }
+    if out_buckets.is_null() {
+        return HC_NULL_POINTER;
+    }
+    let profile = volume_profile(data, buckets);
+    if profile.is_empty() {
+        return HC_NO_RESULT;
+    }
+    for (i, b) in profile.into_iter().enumerate() {
+        *out_buckets.add(i) = HcVolumeBucket {
+            low: b.low,
+            high: b.high,
+            volume: b.volume,
+        };
+    }
+    HC_OK
+}
+
+/// Trade entered at the close of candle `entry` and exited at the next close. Returns
+/// `HC_NO_RESULT` for `HC_ACTION_NONE` or when there is no next candle.
+///
+/// # Safety
+/// `candles` must point to `len * 6` doubles and `out` to a writable trade.
+#[no_mangle]
+pub unsafe extern "C" fn hc_next_candle_trade(
+    candles: *const f64,
+    len: usize,
+    entry: usize,
+    side: i32,
+    fee: f64,
+    out: *mut HcTrade,
+) -> i32 {
+    let Some(data) = borrow_candles(candles, len) else {
+        return HC_NULL_POINTER;
+    };
+    let Some(side) = action_from_code(side) else {
+        return HC_INVALID_ARGUMENT;
+    };
+    if out.is_null() {
+        return HC_NULL_POINTER;
+    }
+    match next_candle_trade(data, entry, side, fee) {
+        Some(trade) => {
+            *out = trade.into();
+            HC_OK
+        }
+        None => HC_NO_RESULT,
+    }
+}
+
+/// Simulates one next-candle trade per non-`NONE` action (`len` entries, one per candle)
+/// and summarizes the equity curve.
+///
+/// # Safety
+/// `candles` must point to `len * 6` doubles, `actions` to `len` ints and `out` to a
+/// writable summary.
+#[no_mangle]
+pub unsafe extern "C" fn hc_simulate(
+    candles: *const f64,
+    actions: *const i32,
+    len: usize,
+    fee: f64,
+    out: *mut HcSimulationSummary,
+) -> i32 {
+    let Some(data) = borrow_candles(candles, len) else {
+        return HC_NULL_POINTER;
+    };
+    if out.is_null() || (len > 0 && actions.is_null()) {
+        return HC_NULL_POINTER;
+    }
+
+    let mut trades = Vec::new();
+    for i in 0..len {
+        let Some(side) = action_from_code(*actions.add(i)) else {
+            return HC_INVALID_ARGUMENT;
+        };
+        trades.extend(next_candle_trade(data, i, side, fee));
+    }
+
+    let summary = summarize(&trades);
+    *out = HcSimulationSummary {
+        trades: summary.trades,
+        total_return: summary.total_return,
+        win_rate: summary.win_rate,
+        max_drawdown: summary.max_drawdown,
+    };
+    HC_OK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    #[test]
+    fn test_c_abi_matches_rust_core() {
+        let data = [
+            [0.0, 100.0, 100.0, 100.0, 100.0, 1.0],
+            [3600.0, 100.0, 110.0, 100.0, 110.0, 1.0],
+            [7200.0, 110.0, 110.0, 99.0, 99.0, 1.0],
+        ];
+        let flat = data.as_ptr().cast::<f64>();
+
+        unsafe {
+            let mut labels = [-1; 3];
+            assert_eq!(hc_label_candles(flat, 3, labels.as_mut_ptr()), HC_OK);
+            let expected = label_candles(&data).into_iter().map(action_code);
+            assert!(labels.iter().copied().eq(expected));
+
+            let mut v = 0.0;
+            assert_eq!(hc_vwap(flat, 3, &mut v), HC_OK);
+            assert_eq!(Some(v), vwap(&data));
+            assert_eq!(hc_vwap(ptr::null(), 0, &mut v), HC_NO_RESULT);
+            assert_eq!(hc_vwap(ptr::null(), 3, &mut v), HC_NULL_POINTER);
+
+            let mut profile = [HcVolumeBucket::default(); 2];
+            assert_eq!(hc_volume_profile(flat, 3, 2, profile.as_mut_ptr()), HC_OK);
+            assert_eq!(profile.iter().map(|b| b.volume).sum::<f64>(), 3.0);
+
+            let mut trade = HcTrade::default();
+            assert_eq!(
+                hc_next_candle_trade(flat, 3, 0, HC_ACTION_LONG, 0.0, &mut trade),
+                HC_OK
+            );
+            assert!((trade.return_pct - 0.1).abs() < 1e-12);
+            assert_eq!(
+                hc_next_candle_trade(flat, 3, 0, 7, 0.0, &mut trade),
+                HC_INVALID_ARGUMENT
+            );
+
+            let actions = [HC_ACTION_LONG, HC_ACTION_SHORT, HC_ACTION_LONG];
+            let mut summary = HcSimulationSummary::default();
+            assert_eq!(
+                hc_simulate(flat, actions.as_ptr(), 3, 0.0, &mut summary),
+                HC_OK
+            );
+            assert_eq!(summary.trades, 2);
+            assert_eq!(summary.win_rate, 1.0);
+        }
+    }
+}
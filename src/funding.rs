@@ -0,0 +1,176 @@
+#[cfg(feature = "native")]
+use std::path::PathBuf;
+
+#[cfg(feature = "native")]
+use anyhow::Result;
+#[cfg(feature = "native")]
+use chrono::{DateTime, Utc};
+#[cfg(feature = "native")]
+use serde::Deserialize;
+
+#[cfg(feature = "native")]
+use crate::store::RowStore;
+use crate::Action;
+
+/// One perpetual funding settlement, `[time, rate]`, with the settlement time in seconds
+/// and the rate as a fraction of notional that longs pay shorts (shorts pay when negative).
+pub type FundingPoint = [f64; 2];
+
+#[cfg(feature = "native")]
+pub const FUNDING_DIR: &str = "cache/funding";
+
+/// Funding a `side` position pays over the settlements in `(from, to]`, as a fraction of
+/// notional; negative when it is paid funding. `funding` is chronological.
+pub fn funding_paid(funding: &[FundingPoint], side: Action, from: f64, to: f64) -> f64 {
+    let direction = match side {
+        Action::Long => 1.0,
+        Action::Short => -1.0,
+        Action::None => return 0.0,
+    };
+    let start = funding.partition_point(|p| p[0] <= from);
+    let end = funding.partition_point(|p| p[0] <= to);
+    funding[start..end.max(start)]
+        .iter()
+        .map(|p| p[1])
+        .sum::<f64>()
+        * direction
+}
+
+/// Whether `funding` spans `(from, to]`: it has a settlement at or before `from` and one at
+/// or after `to`, so the settlements in between are all there were rather than the edge of
+/// the fetched history.
+pub fn funding_covers(funding: &[FundingPoint], from: f64, to: f64) -> bool {
+    funding.first().is_some_and(|p| p[0] <= from) && funding.last().is_some_and(|p| p[0] >= to)
+}
+
+/// On-disk funding history in a [`RowStore`] of [`FundingPoint`]s, which the simulator
+/// charges positions held across settlements.
+#[cfg(feature = "native")]
+#[derive(Debug, Clone)]
+pub struct FundingStore {
+    rows: RowStore<2>,
+}
+
+#[cfg(feature = "native")]
+impl Default for FundingStore {
+    fn default() -> Self {
+        Self::new(FUNDING_DIR)
+    }
+}
+
+#[cfg(feature = "native")]
+impl FundingStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            rows: RowStore::new(dir),
+        }
+    }
+
+    /// The stored settlements of `symbol`, oldest first; empty when none were fetched.
+    pub fn load(&self, symbol: &str) -> Result<Vec<FundingPoint>> {
+        self.rows.load(symbol)
+    }
+
+    /// Downloads the settlements of `[from, to)` into the store, page by page. Returns the
+    /// number of new settlements stored.
+    pub async fn fetch_history(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<usize> {
+        let mut added = 0;
+        let mut page_start = from;
+        while page_start < to {
+            let points = fetch_funding(symbol, page_start, to).await?;
+            let Some(last) = points.last() else {
+                break;
+            };
+            let new = self.rows.merge(symbol, &points)?;
+            added += new;
+            tracing::info!(symbol, %page_start, new, "Fetched funding page");
+            page_start = DateTime::from_timestamp(last[0] as i64 + 1, 0).unwrap_or(to);
+        }
+        Ok(added)
+    }
+}
+
+/// The exchange returns at most this many settlements per request.
+#[cfg(feature = "native")]
+const MAX_POINTS_PER_REQUEST: usize = 1000;
+#[cfg(feature = "native")]
+const FUNDING_URL: &str = "https://fapi.binance.com/fapi/v1/fundingRate";
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Settlement {
+    /// Settlement time in milliseconds.
+    funding_time: i64,
+    funding_rate: String,
+}
+
+/// Funding settlements of the `symbol` USDT perpetual over `[from, to)`, oldest first, at
+/// most [`MAX_POINTS_PER_REQUEST`] of them.
+#[cfg(feature = "native")]
+pub async fn fetch_funding(
+    symbol: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<FundingPoint>> {
+    let response = reqwest::Client::new()
+        .get(FUNDING_URL)
+        .query(&[
+            ("symbol", format!("{}USDT", symbol)),
+            ("startTime", from.timestamp_millis().to_string()),
+            ("endTime", (to.timestamp_millis() - 1).to_string()),
+            ("limit", MAX_POINTS_PER_REQUEST.to_string()),
+        ])
+        .timeout(std::time::Duration::from_secs(30))
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        anyhow::bail!("Funding API error for {}: {} - {}", symbol, status, text);
+    }
+    let settlements: Vec<Settlement> = response.json().await?;
+    let mut points = settlements
+        .into_iter()
+        .map(|s| Ok([(s.funding_time / 1000) as f64, s.funding_rate.parse()?]))
+        .collect::<Result<Vec<_>>>()?;
+    points.sort_by(|a, b| a[0].total_cmp(&b[0]));
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_funding_paid_over_held_settlements() {
+        let eight_hours = 8.0 * 3600.0;
+        let funding = (1..=4)
+            .map(|i| [i as f64 * eight_hours, 0.0001 * i as f64])
+            .collect::<Vec<_>>();
+
+        // Held from just after the first settlement through the third
+        let from = eight_hours;
+        let to = 3.0 * eight_hours;
+        assert!((funding_paid(&funding, Action::Long, from, to) - 0.0005).abs() < 1e-12);
+        assert!((funding_paid(&funding, Action::Short, from, to) + 0.0005).abs() < 1e-12);
+        // An hour between settlements pays nothing
+        assert_eq!(
+            funding_paid(&funding, Action::Long, from, from + 3600.0),
+            0.0
+        );
+        assert_eq!(funding_paid(&funding, Action::None, 0.0, to), 0.0);
+        assert_eq!(funding_paid(&[], Action::Long, 0.0, to), 0.0);
+
+        assert!(funding_covers(&funding, from, from + 3600.0));
+        assert!(!funding_covers(&funding, 0.0, to));
+        assert!(!funding_covers(&funding, to, 5.0 * eight_hours));
+        assert!(!funding_covers(&[], from, to));
+    }
+}
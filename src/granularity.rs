@@ -0,0 +1,233 @@
+use std::fmt;
+
+use crate::compute::{LONG_THRESHOLD, SHORT_THRESHOLD};
+use crate::data_quality::infer_granularity;
+use crate::GRANULARITY;
+
+/// Shortest and longest span a window should cover; the prompts and thresholds were tuned
+/// on a day of hourly candles.
+const MIN_WINDOW_SECS: u64 = 12 * 3600;
+const MAX_WINDOW_SECS: u64 = 7 * 86_400;
+/// How prompts name candle granularities.
+const WORDINGS: [(&str, u32); 7] = [
+    ("1-minute", 60),
+    ("5-minute", 300),
+    ("15-minute", 900),
+    ("30-minute", 1800),
+    ("4-hour", 14_400),
+    ("hourly", 3600),
+    ("daily", 86_400),
+];
+
+/// Granularity of chronological `candles` in seconds: their most common spacing. `None`
+/// with fewer than two.
+pub fn candle_granularity(candles: &[[f64; 6]]) -> Option<u32> {
+    let times = candles.iter().map(|c| c[0]).collect::<Vec<_>>();
+    infer_granularity(&times).and_then(|g| u32::try_from(g).ok())
+}
+
+/// How the prompt describes candles of `granularity` seconds, e.g. "hourly" or "15-minute".
+pub fn candle_wording(granularity: u32) -> String {
+    match WORDINGS.iter().find(|(_, g)| *g == granularity) {
+        Some((wording, _)) => wording.to_string(),
+        None if granularity.is_multiple_of(3600) => format!("{}-hour", granularity / 3600),
+        None if granularity.is_multiple_of(60) => format!("{}-minute", granularity / 60),
+        None => format!("{}-second", granularity),
+    }
+}
+
+/// The labeling multipliers for a next-candle horizon of `granularity` seconds: the
+/// hourly thresholds' move scaled by the square root of time, as a random walk's typical
+/// move scales.
+pub fn scaled_thresholds(granularity: u32) -> (f64, f64) {
+    let scale = (granularity as f64 / GRANULARITY as f64).sqrt();
+    (
+        1.0 + (LONG_THRESHOLD - 1.0) * scale,
+        1.0 - (1.0 - SHORT_THRESHOLD) * scale,
+    )
+}
+
+/// A combination of candle granularity, window, labels and prompt that is likely a
+/// mistake.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GranularityWarning {
+    /// A symbol's candles are spaced unlike the target's.
+    Mixed {
+        symbol: String,
+        granularity: u32,
+        expected: u32,
+    },
+    /// The prompt talks of candles of another granularity.
+    PromptWording {
+        says: &'static str,
+        granularity: u32,
+    },
+    /// The window spans much less or much more time than the prompts were tuned on.
+    WindowSpan { candles: usize, granularity: u32 },
+    /// The labels still use the hourly thresholds for another horizon.
+    UnscaledThresholds {
+        granularity: u32,
+        suggested: (f64, f64),
+    },
+}
+
+impl fmt::Display for GranularityWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GranularityWarning::Mixed {
+                symbol,
+                granularity,
+                expected,
+            } => write!(
+                f,
+                "{} has {} candles but the target has {} ones",
+                symbol,
+                candle_wording(*granularity),
+                candle_wording(*expected)
+            ),
+            GranularityWarning::PromptWording { says, granularity } => write!(
+                f,
+                "the prompt mentions {} candles but the data is {}",
+                says,
+                candle_wording(*granularity)
+            ),
+            GranularityWarning::WindowSpan {
+                candles,
+                granularity,
+            } => write!(
+                f,
+                "a window of {} {} candles covers {:.1} hours",
+                candles,
+                candle_wording(*granularity),
+                (*candles as u64 * *granularity as u64) as f64 / 3600.0
+            ),
+            GranularityWarning::UnscaledThresholds {
+                granularity,
+                suggested: (long, short),
+            } => write!(
+                f,
+                "labels use the hourly thresholds for {} candles; about {:.4} and {:.4} \
+                 would match the shorter or longer horizon",
+                candle_wording(*granularity),
+                long,
+                short
+            ),
+        }
+    }
+}
+
+/// Whether `text` mentions `wording` as a word of its own, so "15-minute" isn't taken for
+/// "5-minute".
+fn mentions(text: &str, wording: &str) -> bool {
+    text.match_indices(wording).any(|(i, _)| {
+        !text[..i]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric())
+    })
+}
+
+/// Checks that `series`, the target's first, fit each other, a window of
+/// `window_candles`, the labeling `thresholds` and the wording of `base_prompt`.
+pub fn check_granularity(
+    series: &[(&str, &[[f64; 6]])],
+    window_candles: usize,
+    base_prompt: &str,
+    thresholds: (f64, f64),
+) -> Vec<GranularityWarning> {
+    let Some(granularity) = series.first().and_then(|(_, c)| candle_granularity(c)) else {
+        return Vec::new();
+    };
+    let mut warnings = series[1..]
+        .iter()
+        .filter_map(|(symbol, candles)| {
+            let other = candle_granularity(candles)?;
+            (other != granularity).then(|| GranularityWarning::Mixed {
+                symbol: symbol.to_string(),
+                granularity: other,
+                expected: granularity,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let prompt = base_prompt.to_lowercase();
+    if let Some((says, _)) = WORDINGS
+        .iter()
+        .find(|(wording, g)| *g != granularity && mentions(&prompt, wording))
+    {
+        warnings.push(GranularityWarning::PromptWording { says, granularity });
+    }
+    let span = window_candles as u64 * granularity as u64;
+    if !(MIN_WINDOW_SECS..=MAX_WINDOW_SECS).contains(&span) {
+        warnings.push(GranularityWarning::WindowSpan {
+            candles: window_candles,
+            granularity,
+        });
+    }
+    if granularity != GRANULARITY && thresholds == (LONG_THRESHOLD, SHORT_THRESHOLD) {
+        warnings.push(GranularityWarning::UnscaledThresholds {
+            granularity,
+            suggested: scaled_thresholds(granularity),
+        });
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_granularity_checks() {
+        let candles = |step: f64, n: usize| {
+            (0..n)
+                .map(|i| [i as f64 * step, 1.0, 1.0, 1.0, 1.0, 1.0])
+                .collect::<Vec<_>>()
+        };
+        let (hourly, quarter) = (candles(3600.0, 24), candles(900.0, 24));
+        let defaults = (LONG_THRESHOLD, SHORT_THRESHOLD);
+        let prompt = "Predict from the hourly candles below.";
+
+        assert_eq!(candle_granularity(&quarter), Some(900));
+        assert_eq!(candle_wording(900), "15-minute");
+        assert_eq!(candle_wording(7200), "2-hour");
+        let (long, short) = scaled_thresholds(900);
+        assert!((long - 1.025).abs() < 1e-9 && (short - 0.975).abs() < 1e-9);
+
+        assert!(
+            check_granularity(&[("ETH", &hourly), ("BTC", &hourly)], 24, prompt, defaults)
+                .is_empty()
+        );
+        let warnings =
+            check_granularity(&[("ETH", &quarter), ("BTC", &hourly)], 24, prompt, defaults);
+        assert_eq!(
+            warnings,
+            vec![
+                GranularityWarning::Mixed {
+                    symbol: "BTC".into(),
+                    granularity: 3600,
+                    expected: 900
+                },
+                GranularityWarning::PromptWording {
+                    says: "hourly",
+                    granularity: 900
+                },
+                GranularityWarning::WindowSpan {
+                    candles: 24,
+                    granularity: 900
+                },
+                GranularityWarning::UnscaledThresholds {
+                    granularity: 900,
+                    suggested: (long, short)
+                },
+            ]
+        );
+        assert_eq!(
+            warnings[2].to_string(),
+            "a window of 24 15-minute candles covers 6.0 hours"
+        );
+        // Scaled thresholds and a longer window over a prompt that says so are fine
+        let prompt = "Predict from the 15-minute candles below.";
+        assert!(check_granularity(&[("ETH", &quarter)], 96, prompt, (long, short)).is_empty());
+    }
+}
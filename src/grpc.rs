@@ -0,0 +1,497 @@
+use std::net::SocketAddr;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use futures::Stream;
+use tokio::sync::{mpsc, Mutex};
+use tonic::{Request, Response, Status};
+
+use crate::auth::{check_exposure, load_tokens, ApiTokens, AuthError, Role, API_TOKENS_FILE};
+use crate::backtest::{self, PromptRecord};
+use crate::challenger::PROMOTION_HOURS;
+use crate::health;
+use crate::profiles::DEFAULT_TARGET;
+use crate::reporting;
+use crate::signal_filter::{RepeatPolicy, SignalFilter};
+use crate::storage::{self, Storage, HISTORY_LIMIT};
+use crate::{run_live_analysis, run_live_multi_asset_analysis, Action};
+
+use proto::happy_charts_server::{HappyCharts, HappyChartsServer};
+
+/// Shortest interval a `LiveSignals` subscriber may request.
+pub const MIN_SIGNAL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Messages of `proto/happycharts.proto`, written out by hand so the build needs no protoc.
+pub mod proto {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Action {
+        None = 0,
+        Long = 1,
+        Short = 2,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct AnalyzeRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct AnalyzeResponse {
+        #[prost(enumeration = "Action", tag = "1")]
+        pub action: i32,
+        #[prost(string, tag = "2")]
+        pub rationale: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RunBacktestRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RunBacktestResponse {
+        #[prost(double, tag = "1")]
+        pub accuracy: f64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct GetPromptHistoryRequest {
+        #[prost(uint32, tag = "1")]
+        pub limit: u32,
+        #[prost(string, tag = "2")]
+        pub symbol: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct PromptRecord {
+        #[prost(string, tag = "1")]
+        pub prompt: String,
+        #[prost(double, tag = "2")]
+        pub score: f64,
+        #[prost(double, optional, tag = "3")]
+        pub out_of_sample: Option<f64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct GetPromptHistoryResponse {
+        #[prost(message, repeated, tag = "1")]
+        pub records: Vec<PromptRecord>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct LiveSignalsRequest {
+        #[prost(uint64, tag = "1")]
+        pub interval_secs: u64,
+        #[prost(bool, tag = "2")]
+        pub multi_asset: bool,
+        #[prost(uint64, tag = "3")]
+        pub cooldown_secs: u64,
+        #[prost(bool, tag = "4")]
+        pub changes_only: bool,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct AssetSignal {
+        #[prost(string, tag = "1")]
+        pub symbol: String,
+        #[prost(enumeration = "Action", tag = "2")]
+        pub action: i32,
+        #[prost(string, tag = "3")]
+        pub rationale: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Signal {
+        #[prost(enumeration = "Action", tag = "1")]
+        pub action: i32,
+        #[prost(string, tag = "2")]
+        pub rationale: String,
+        #[prost(int64, tag = "3")]
+        pub timestamp: i64,
+        #[prost(message, repeated, tag = "4")]
+        pub assets: Vec<AssetSignal>,
+    }
+
+    tonic::include_proto!("happycharts.HappyCharts");
+}
+
+impl From<Action> for proto::Action {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::Long => proto::Action::Long,
+            Action::Short => proto::Action::Short,
+            Action::None => proto::Action::None,
+        }
+    }
+}
+
+impl From<proto::Action> for Action {
+    fn from(action: proto::Action) -> Self {
+        match action {
+            proto::Action::Long => Action::Long,
+            proto::Action::Short => Action::Short,
+            proto::Action::None => Action::None,
+        }
+    }
+}
+
+fn internal(e: anyhow::Error) -> Status {
+    Status::internal(format!("{:#}", e))
+}
+
+pub struct HappyChartsService {
+    storage: Arc<dyn Storage>,
+    /// Backtests rewrite prompt.txt, so only one may run at a time.
+    backtest: Mutex<()>,
+    /// Tokens requests must present in their `authorization` metadata; `None` lets
+    /// every request through.
+    tokens: Option<ApiTokens>,
+}
+
+impl HappyChartsService {
+    pub fn new(storage: Arc<dyn Storage>) -> Self {
+        Self {
+            storage,
+            backtest: Mutex::new(()),
+            tokens: None,
+        }
+    }
+
+    pub fn with_tokens(mut self, tokens: ApiTokens) -> Self {
+        self.tokens = Some(tokens);
+        self
+    }
+
+    /// Why `request` may not act as `required`, if it may not.
+    fn denied<T>(&self, request: &Request<T>, required: Role) -> Option<Status> {
+        let authorization = request
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok());
+        match self.tokens.as_ref()?.authorize(authorization, required) {
+            Ok(_) => None,
+            Err(err @ AuthError::Forbidden) => Some(Status::permission_denied(err.describe())),
+            Err(err) => Some(Status::unauthenticated(err.describe())),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl HappyCharts for HappyChartsService {
+    async fn analyze(
+        &self,
+        request: Request<proto::AnalyzeRequest>,
+    ) -> Result<Response<proto::AnalyzeResponse>, Status> {
+        if let Some(status) = self.denied(&request, Role::Admin) {
+            return Err(status);
+        }
+        let (action, rationale) = run_live_analysis().await.map_err(internal)?;
+        Ok(Response::new(proto::AnalyzeResponse {
+            action: proto::Action::from(action).into(),
+            rationale,
+        }))
+    }
+
+    async fn run_backtest(
+        &self,
+        request: Request<proto::RunBacktestRequest>,
+    ) -> Result<Response<proto::RunBacktestResponse>, Status> {
+        if let Some(status) = self.denied(&request, Role::Admin) {
+            return Err(status);
+        }
+        let _guard = self
+            .backtest
+            .try_lock()
+            .map_err(|_| Status::aborted("A backtest is already running"))?;
+        let accuracy = backtest::run_backtest_and_improve()
+            .await
+            .map_err(internal)?;
+        Ok(Response::new(proto::RunBacktestResponse { accuracy }))
+    }
+
+    async fn get_prompt_history(
+        &self,
+        request: Request<proto::GetPromptHistoryRequest>,
+    ) -> Result<Response<proto::GetPromptHistoryResponse>, Status> {
+        if let Some(status) = self.denied(&request, Role::Read) {
+            return Err(status);
+        }
+        let request = request.into_inner();
+        let limit = match request.limit {
+            0 => HISTORY_LIMIT,
+            n => n as usize,
+        };
+        let symbol = match request.symbol.as_str() {
+            "" => DEFAULT_TARGET,
+            symbol => symbol,
+        };
+        let history = self
+            .storage
+            .prompt_history(symbol, limit)
+            .await
+            .map_err(internal)?;
+        Ok(Response::new(proto::GetPromptHistoryResponse {
+            records: history
+                .into_iter()
+                .map(
+                    |PromptRecord {
+                         prompt,
+                         score,
+                         out_of_sample,
+                         ..
+                     }| proto::PromptRecord {
+                        prompt,
+                        score,
+                        out_of_sample,
+                    },
+                )
+                .collect(),
+        }))
+    }
+
+    type LiveSignalsStream = Pin<Box<dyn Stream<Item = Result<proto::Signal, Status>> + Send>>;
+
+    async fn live_signals(
+        &self,
+        request: Request<proto::LiveSignalsRequest>,
+    ) -> Result<Response<Self::LiveSignalsStream>, Status> {
+        if let Some(status) = self.denied(&request, Role::Read) {
+            return Err(status);
+        }
+        let request = request.into_inner();
+        let interval = Duration::from_secs(request.interval_secs);
+        if interval < MIN_SIGNAL_INTERVAL {
+            return Err(Status::invalid_argument(format!(
+                "interval_secs must be at least {}",
+                MIN_SIGNAL_INTERVAL.as_secs()
+            )));
+        }
+
+        let mut filter = SignalFilter::new(repeat_policy(&request));
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            loop {
+                let started = Instant::now();
+                let signal = if request.multi_asset {
+                    live_asset_signals().await
+                } else {
+                    run_live_analysis()
+                        .await
+                        .map(|(action, rationale)| proto::Signal {
+                            action: proto::Action::from(action).into(),
+                            rationale,
+                            timestamp: chrono::Utc::now().timestamp(),
+                            assets: Vec::new(),
+                        })
+                };
+                health::record_analysis(&signal);
+                health::ping_heartbeat(signal.is_ok()).await;
+                if let Err(err) = &signal {
+                    let mode = if request.multi_asset {
+                        "multi_asset"
+                    } else {
+                        "single_asset"
+                    };
+                    reporting::capture_error(
+                        err.as_ref(),
+                        &[("source", "live".to_string()), ("mode", mode.to_string())],
+                    )
+                    .await;
+                }
+                let signal = signal.map_err(internal);
+                // A failed send means the subscriber went away
+                if admit(&mut filter, &signal) && tx.send(signal).await.is_err() {
+                    break;
+                }
+                // The next signal comes from a challenger that has proven itself
+                if !request.multi_asset {
+                    if let Err(err) = backtest::promote_challenger(PROMOTION_HOURS).await {
+                        tracing::warn!(%err, "Challenger review failed");
+                    }
+                }
+                // Keep the cadence fixed: the analysis time comes out of the interval
+                let elapsed = started.elapsed();
+                if elapsed >= interval {
+                    tracing::warn!(
+                        elapsed_secs = elapsed.as_secs(),
+                        interval_secs = interval.as_secs(),
+                        "Live analysis overran its interval; a signal was missed"
+                    );
+                }
+                tokio::time::sleep(interval.saturating_sub(elapsed)).await;
+            }
+        });
+
+        let stream = futures::stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|signal| (signal, rx))
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+fn repeat_policy(request: &proto::LiveSignalsRequest) -> RepeatPolicy {
+    if request.changes_only {
+        RepeatPolicy::OnChange
+    } else if request.cooldown_secs > 0 {
+        RepeatPolicy::Cooldown(request.cooldown_secs as f64)
+    } else {
+        RepeatPolicy::Always
+    }
+}
+
+/// Whether `signal` passes the subscriber's repeat policy. Errors always go out, and a
+/// multi-asset signal goes out when any of its assets does.
+fn admit(filter: &mut SignalFilter, signal: &Result<proto::Signal, Status>) -> bool {
+    let Ok(signal) = signal else {
+        return true;
+    };
+    let time = signal.timestamp as f64;
+    if signal.assets.is_empty() {
+        return filter.admit("ETH", Action::from(signal.action()), time);
+    }
+    // Every asset is recorded, so no position falls behind
+    let admitted = signal
+        .assets
+        .iter()
+        .map(|asset| filter.admit(&asset.symbol, Action::from(asset.action()), time))
+        .collect::<Vec<_>>();
+    admitted.contains(&true)
+}
+
+/// One multi-asset analysis as a [`proto::Signal`]. The top-level action and rationale are
+/// the first symbol's, so single-asset clients keep working.
+async fn live_asset_signals() -> Result<proto::Signal> {
+    let assets = run_live_multi_asset_analysis()
+        .await?
+        .into_iter()
+        .map(|(symbol, action, rationale)| proto::AssetSignal {
+            symbol,
+            action: proto::Action::from(action).into(),
+            rationale,
+        })
+        .collect::<Vec<_>>();
+    let (action, rationale) = assets
+        .first()
+        .map(|a| (a.action, a.rationale.clone()))
+        .unwrap_or_default();
+    Ok(proto::Signal {
+        action,
+        rationale,
+        timestamp: chrono::Utc::now().timestamp(),
+        assets,
+    })
+}
+
+/// Serves the gRPC API on `addr` until the process is stopped.
+/// Serves the API on `addr`, and `/healthz` on `health_addr` when given. Requests need a
+/// token from [`API_TOKENS_FILE`] when it lists any: reads any token, analyses and
+/// backtests an admin one.
+pub async fn serve(addr: SocketAddr, health_addr: Option<SocketAddr>) -> Result<()> {
+    let tokens = load_tokens(Path::new(API_TOKENS_FILE))?;
+    check_exposure(addr, tokens.as_ref())?;
+    let storage: Arc<dyn Storage> = storage::from_env().await?.into();
+    health::mark_started();
+    if let Some(health_addr) = health_addr {
+        tokio::spawn(async move {
+            if let Err(err) = health::serve_health(health_addr).await {
+                tracing::error!(%err, "Health endpoint stopped");
+            }
+        });
+    }
+    let service = match tokens {
+        Some(tokens) => HappyChartsService::new(storage).with_tokens(tokens),
+        None => HappyChartsService::new(storage),
+    };
+    tracing::info!(%addr, "Serving gRPC");
+    tonic::transport::Server::builder()
+        .add_service(HappyChartsServer::new(service))
+        .serve(addr)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FileStorage;
+
+    #[tokio::test]
+    async fn test_prompt_history_and_interval_validation() {
+        let dir = std::env::temp_dir().join("happycharts_grpc");
+        let _ = std::fs::remove_dir_all(&dir);
+        let storage = FileStorage::new(&dir);
+        for i in 0..3 {
+            let record = PromptRecord {
+                prompt: format!("prompt {}", i),
+                score: i as f64 / 10.0,
+                out_of_sample: None,
+                symbol: DEFAULT_TARGET.to_string(),
+            };
+            storage.append_prompt_record(&record).await.unwrap();
+        }
+        let service = HappyChartsService::new(Arc::new(storage));
+
+        let history = service
+            .get_prompt_history(Request::new(proto::GetPromptHistoryRequest {
+                limit: 2,
+                ..Default::default()
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(history.records.len(), 2);
+        assert_eq!(history.records[1].prompt, "prompt 2");
+        let sol = service
+            .get_prompt_history(Request::new(proto::GetPromptHistoryRequest {
+                limit: 0,
+                symbol: "SOL".into(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(sol.records.is_empty());
+
+        let err = service
+            .live_signals(Request::new(proto::LiveSignalsRequest {
+                interval_secs: 1,
+                multi_asset: false,
+                ..Default::default()
+            }))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+
+        let mut tokens = ApiTokens::default();
+        tokens.add("viewer", "viewer-token", Role::Read);
+        let service = service.with_tokens(tokens);
+        let history_request = |authorization: Option<&str>| {
+            let mut request = Request::new(proto::GetPromptHistoryRequest {
+                limit: 1,
+                ..Default::default()
+            });
+            if let Some(value) = authorization {
+                request
+                    .metadata_mut()
+                    .insert("authorization", value.parse().unwrap());
+            }
+            request
+        };
+        let err = service
+            .get_prompt_history(history_request(None))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+        assert!(service
+            .get_prompt_history(history_request(Some("Bearer viewer-token")))
+            .await
+            .is_ok());
+        let mut analyze = Request::new(proto::AnalyzeRequest::default());
+        analyze
+            .metadata_mut()
+            .insert("authorization", "Bearer viewer-token".parse().unwrap());
+        let err = service.analyze(analyze).await.err().unwrap();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+    }
+}
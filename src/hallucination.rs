@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use serde::{Deserialize, Serialize};
+
+/// Relative slack within which a cited price counts as taken from the data.
+pub const CLAIM_TOLERANCE: f64 = 0.005;
+/// Windows whose rationales cited the most unsupported prices shown to the improver.
+pub const IMPROVER_HALLUCINATIONS: usize = 3;
+/// A number within this factor of a window's traded range is read as a price of it.
+const PRICE_SCALE: f64 = 2.0;
+const LOW: usize = 3;
+const HIGH: usize = 2;
+
+/// The prices a rationale cites, checked against the candles it was given.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClaimCheck {
+    /// Cited numbers read as prices.
+    pub checked: usize,
+    /// Those outside every window's traded range, beyond [`CLAIM_TOLERANCE`].
+    pub unsupported: Vec<f64>,
+}
+
+impl ClaimCheck {
+    pub fn is_hallucinated(&self) -> bool {
+        !self.unsupported.is_empty()
+    }
+}
+
+/// Numbers written in `text`, such as `3,412.50`, `$3412` or `3.4k`. Percentages and
+/// dates are left out.
+pub fn cited_numbers(text: &str) -> Vec<f64> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut numbers = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let joined = i > 0 && (chars[i - 1].is_alphanumeric() || chars[i - 1] == '.');
+        let dated = i > 1 && chars[i - 1] == '-' && chars[i - 2].is_ascii_digit();
+        let starts = chars[i].is_ascii_digit() && !joined && !dated;
+        if !starts {
+            i += 1;
+            continue;
+        }
+        let mut digits = String::new();
+        let mut seen_point = false;
+        while i < chars.len() {
+            let c = chars[i];
+            let next_digit = chars.get(i + 1).is_some_and(|n| n.is_ascii_digit());
+            if c.is_ascii_digit() {
+                digits.push(c);
+            } else if c == ',' && next_digit && !seen_point {
+                // A thousands separator
+            } else if c == '.' && next_digit && !seen_point {
+                seen_point = true;
+                digits.push(c);
+            } else {
+                break;
+            }
+            i += 1;
+        }
+        let (multiplier, suffix) = match chars.get(i) {
+            Some('k' | 'K') if !chars.get(i + 1).is_some_and(|c| c.is_alphanumeric()) => (1e3, 1),
+            _ => (1.0, 0),
+        };
+        let unit = chars.get(i + suffix);
+        let date = unit == Some(&'-') && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit());
+        if unit != Some(&'%') && !date {
+            if let Ok(n) = digits.parse::<f64>() {
+                numbers.push(n * multiplier);
+            }
+        }
+        i += suffix;
+    }
+    numbers
+}
+
+/// Checks the prices `rationale` cites against the traded range of each of `windows`.
+/// The model's own `levels`, its stop and target, are predictions rather than claims
+/// about the data and are not checked.
+pub fn check_claims(
+    rationale: &str,
+    windows: &[&[[f64; 6]]],
+    levels: &[Option<f64>],
+) -> ClaimCheck {
+    let ranges = windows
+        .iter()
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let low = w.iter().map(|c| c[LOW]).fold(f64::INFINITY, f64::min);
+            let high = w.iter().map(|c| c[HIGH]).fold(f64::NEG_INFINITY, f64::max);
+            (low, high)
+        })
+        .collect::<Vec<_>>();
+    let near = |a: f64, b: f64| (a - b).abs() <= CLAIM_TOLERANCE * b.abs();
+
+    let mut check = ClaimCheck::default();
+    for n in cited_numbers(rationale) {
+        let is_price = ranges
+            .iter()
+            .any(|&(low, high)| n >= low / PRICE_SCALE && n <= high * PRICE_SCALE);
+        if !is_price || levels.iter().flatten().any(|&level| near(n, level)) {
+            continue;
+        }
+        check.checked += 1;
+        let supported = ranges.iter().any(|&(low, high)| {
+            n >= low * (1.0 - CLAIM_TOLERANCE) && n <= high * (1.0 + CLAIM_TOLERANCE)
+        });
+        if !supported {
+            check.unsupported.push(n);
+        }
+    }
+    check
+}
+
+/// How often one prompt version's rationales cited prices absent from their data.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct HallucinationRate {
+    pub prompt_version: String,
+    /// Rationales citing at least one price.
+    pub checked: usize,
+    /// Those citing an unsupported price.
+    pub hallucinated: usize,
+    pub rate: f64,
+}
+
+/// Hallucination rates per prompt version, in version order, from `(prompt version,
+/// check)` pairs.
+pub fn hallucination_rates<'a>(
+    checks: impl IntoIterator<Item = (&'a str, &'a ClaimCheck)>,
+) -> Vec<HallucinationRate> {
+    let mut by_version = BTreeMap::<&str, HallucinationRate>::new();
+    for (version, check) in checks {
+        if check.checked == 0 {
+            continue;
+        }
+        let rate = by_version.entry(version).or_default();
+        rate.checked += 1;
+        rate.hallucinated += check.is_hallucinated() as usize;
+    }
+    by_version
+        .into_iter()
+        .map(|(version, rate)| HallucinationRate {
+            prompt_version: version.to_string(),
+            rate: rate.hallucinated as f64 / rate.checked as f64,
+            ..rate
+        })
+        .collect()
+}
+
+pub fn describe_hallucination_rates(rates: &[HallucinationRate]) -> String {
+    let mut out = format!(
+        "{:<16} {:>8} {:>13} {:>7}\n",
+        "prompt", "checked", "hallucinated", "rate"
+    );
+    for r in rates {
+        let _ = writeln!(
+            out,
+            "{:<16} {:>8} {:>13} {:>6.1}%",
+            r.prompt_version,
+            r.checked,
+            r.hallucinated,
+            r.rate * 100.0
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cited_numbers() {
+        assert_eq!(
+            cited_numbers("Support at $3,412.50 held; up 2.5% in 24h, next 3.6k. RSI14 at 70."),
+            vec![3412.5, 24.0, 3600.0, 70.0]
+        );
+        assert_eq!(
+            cited_numbers("Since 2024-03-20 price fell."),
+            Vec::<f64>::new()
+        );
+        assert_eq!(cited_numbers("Closed at 3400."), vec![3400.0]);
+    }
+
+    #[test]
+    fn test_check_claims() {
+        let eth = [
+            [0.0, 3400.0, 3450.0, 3380.0, 3420.0, 1.0],
+            [3600.0, 3420.0, 3500.0, 3410.0, 3490.0, 1.0],
+        ];
+        let sol = [[0.0, 150.0, 152.0, 148.0, 151.0, 1.0]];
+        let windows = [&eth[..], &sol[..]];
+
+        let check = check_claims(
+            "ETH broke 3500 after holding 3,378, SOL at 151; 24 candles of higher lows toward 3700, stop 3300",
+            &windows,
+            &[Some(3300.0), None],
+        );
+        // 3378 is within tolerance of the 3380 low; 24 is no price of either symbol
+        assert_eq!(check.checked, 4);
+        assert_eq!(check.unsupported, vec![3700.0]);
+        assert!(check.is_hallucinated());
+
+        let clean = check_claims("SOL held 149", &windows, &[]);
+        let silent = check_claims("Momentum is strong", &windows, &[]);
+        let rates = hallucination_rates([("aaa", &check), ("aaa", &clean), ("aaa", &silent)]);
+        assert_eq!(rates.len(), 1);
+        assert_eq!((rates[0].checked, rates[0].hallucinated), (2, 1));
+        assert_eq!(rates[0].rate, 0.5);
+        assert!(describe_hallucination_rates(&rates).contains("50.0%"));
+    }
+}
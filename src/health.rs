@@ -0,0 +1,195 @@
+use std::env;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// The daemon is unhealthy once this long has passed without a prediction.
+pub const STALE_AFTER: Duration = Duration::hours(2);
+/// The daemon is unhealthy after this many live analyses in a row have failed.
+pub const MAX_CONSECUTIVE_FAILURES: usize = 3;
+
+static HEALTH: Mutex<HealthState> = Mutex::new(HealthState::new());
+
+/// What the daemon's `/healthz` endpoint reports.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthReport {
+    pub healthy: bool,
+    pub started_at: Option<DateTime<Utc>>,
+    pub last_candle_fetch: Option<DateTime<Utc>>,
+    pub last_llm_call: Option<DateTime<Utc>>,
+    pub last_prediction: Option<DateTime<Utc>>,
+    /// Model requests waiting on a response.
+    pub llm_in_flight: usize,
+    /// Live analyses that failed since the last success.
+    pub consecutive_failures: usize,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct HealthState {
+    started_at: Option<DateTime<Utc>>,
+    last_candle_fetch: Option<DateTime<Utc>>,
+    last_llm_call: Option<DateTime<Utc>>,
+    last_prediction: Option<DateTime<Utc>>,
+    llm_in_flight: usize,
+    consecutive_failures: usize,
+    last_error: Option<String>,
+}
+
+impl HealthState {
+    const fn new() -> Self {
+        Self {
+            started_at: None,
+            last_candle_fetch: None,
+            last_llm_call: None,
+            last_prediction: None,
+            llm_in_flight: 0,
+            consecutive_failures: 0,
+            last_error: None,
+        }
+    }
+
+    fn report(&self, now: DateTime<Utc>) -> HealthReport {
+        let fresh = self
+            .last_prediction
+            .or(self.started_at)
+            .is_some_and(|t| now - t < STALE_AFTER);
+        HealthReport {
+            healthy: fresh && self.consecutive_failures < MAX_CONSECUTIVE_FAILURES,
+            started_at: self.started_at,
+            last_candle_fetch: self.last_candle_fetch,
+            last_llm_call: self.last_llm_call,
+            last_prediction: self.last_prediction,
+            llm_in_flight: self.llm_in_flight,
+            consecutive_failures: self.consecutive_failures,
+            last_error: self.last_error.clone(),
+        }
+    }
+}
+
+fn update(f: impl FnOnce(&mut HealthState)) {
+    f(&mut HEALTH.lock().unwrap_or_else(|e| e.into_inner()));
+}
+
+pub fn mark_started() {
+    update(|h| h.started_at = Some(Utc::now()));
+}
+
+pub fn record_candle_fetch() {
+    update(|h| h.last_candle_fetch = Some(Utc::now()));
+}
+
+pub fn llm_call_started() {
+    update(|h| h.llm_in_flight += 1);
+}
+
+/// Ends a call counted by [`llm_call_started`]; only answered calls count as the last one.
+pub fn llm_call_finished(answered: bool) {
+    update(|h| {
+        h.llm_in_flight = h.llm_in_flight.saturating_sub(1);
+        if answered {
+            h.last_llm_call = Some(Utc::now());
+        }
+    });
+}
+
+/// Records the outcome of one live analysis.
+pub fn record_analysis<T>(result: &Result<T>) {
+    update(|h| match result {
+        Ok(_) => {
+            h.last_prediction = Some(Utc::now());
+            h.consecutive_failures = 0;
+        }
+        Err(err) => {
+            h.consecutive_failures += 1;
+            h.last_error = Some(format!("{:#}", err));
+        }
+    });
+}
+
+pub fn report() -> HealthReport {
+    HEALTH
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .report(Utc::now())
+}
+
+/// Pings the dead-man's switch at `HEARTBEAT_URL` (healthchecks.io style), at its `/fail`
+/// path when the analysis failed. Does nothing when the variable is unset.
+pub async fn ping_heartbeat(ok: bool) {
+    let Ok(url) = env::var("HEARTBEAT_URL") else {
+        return;
+    };
+    let url = if ok {
+        url
+    } else {
+        format!("{}/fail", url.trim_end_matches('/'))
+    };
+    let ping = reqwest::Client::new()
+        .get(&url)
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status());
+    if let Err(err) = ping {
+        tracing::warn!(%err, "Heartbeat ping failed");
+    }
+}
+
+/// Serves `GET /healthz` as JSON over plain HTTP, with status 503 while unhealthy.
+pub async fn serve_health(addr: SocketAddr) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!(%addr, "Serving /healthz");
+    loop {
+        let (mut socket, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            let n = socket.read(&mut buf).await.unwrap_or(0);
+            let request = String::from_utf8_lossy(&buf[..n]);
+            let (status, body) = if request.starts_with("GET /healthz ") {
+                let report = report();
+                let status = if report.healthy {
+                    "200 OK"
+                } else {
+                    "503 Service Unavailable"
+                };
+                (status, serde_json::to_string(&report).unwrap_or_default())
+            } else {
+                ("404 Not Found", String::new())
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_health_report() {
+        let now = Utc::now();
+        let mut state = HealthState::new();
+        assert!(!state.report(now).healthy);
+
+        state.started_at = Some(now - Duration::hours(1));
+        assert!(state.report(now).healthy);
+        assert!(!state.report(now + STALE_AFTER).healthy);
+
+        state.last_prediction = Some(now + STALE_AFTER);
+        assert!(state.report(now + STALE_AFTER).healthy);
+        state.consecutive_failures = MAX_CONSECUTIVE_FAILURES;
+        assert!(!state.report(now + STALE_AFTER).healthy);
+    }
+}
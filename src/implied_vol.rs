@@ -0,0 +1,281 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "native")]
+use std::path::PathBuf;
+
+#[cfg(feature = "native")]
+use anyhow::Result;
+#[cfg(feature = "native")]
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::sessions::SessionStats;
+use crate::snapshot::ImpliedVolatility;
+#[cfg(feature = "native")]
+use crate::store::RowStore;
+
+/// One hour of a volatility index, `[time, close]`, with the hour's open time in seconds.
+pub type IvPoint = [f64; 2];
+
+#[cfg(feature = "native")]
+pub const IMPLIED_VOL_DIR: &str = "cache/implied_vol";
+/// Symbols Deribit publishes a DVOL index for.
+pub const DVOL_CURRENCIES: [&str; 2] = ["BTC", "ETH"];
+/// Hours of index history the current level is compared with.
+pub const IV_BASELINE_HOURS: usize = 30 * 24;
+/// Rise over a day, as a fraction, that counts as a spike whatever the level.
+const SPIKE_CHANGE: f64 = 0.15;
+const HOUR: f64 = 3600.0;
+
+/// Where implied volatility stands against its own recent history. Spikes often precede
+/// the large moves the labels care about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IvRegime {
+    /// At most 80% of the baseline median.
+    Calm,
+    Normal,
+    /// At least 120% of the baseline median.
+    Elevated,
+    /// Up [`SPIKE_CHANGE`] or more over the last day.
+    Spike,
+}
+
+impl IvRegime {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IvRegime::Calm => "calm",
+            IvRegime::Normal => "normal",
+            IvRegime::Elevated => "elevated",
+            IvRegime::Spike => "spike",
+        }
+    }
+
+    /// The regime of an index at `level`, against the `median` of its baseline and its
+    /// relative `change_24h`, when known.
+    pub fn classify(level: f64, median: f64, change_24h: Option<f64>) -> Self {
+        if change_24h.is_some_and(|c| c >= SPIKE_CHANGE) {
+            IvRegime::Spike
+        } else if level >= median * 1.2 {
+            IvRegime::Elevated
+        } else if level <= median * 0.8 {
+            IvRegime::Calm
+        } else {
+            IvRegime::Normal
+        }
+    }
+}
+
+/// The index as of `as_of`, from chronological hourly `points`: the close of the latest
+/// hour that had closed by then, its change over the day before and its regime against
+/// the [`IV_BASELINE_HOURS`] before it. `None` when no hour had closed in the last one.
+pub fn implied_volatility_as_of(points: &[IvPoint], as_of: f64) -> Option<ImpliedVolatility> {
+    let from = as_of - IV_BASELINE_HOURS as f64 * HOUR;
+    let closed = points
+        .iter()
+        .filter(|p| p[0] + HOUR > from && p[0] + HOUR <= as_of)
+        .collect::<Vec<_>>();
+    let latest = closed.last().filter(|p| p[0] + 2.0 * HOUR > as_of)?;
+    let level = latest[1];
+    let day_ago = closed
+        .iter()
+        .find(|p| p[0] == latest[0] - 24.0 * HOUR)
+        .map(|p| p[1]);
+    let change_24h = day_ago.filter(|&v| v > 0.0).map(|v| level / v - 1.0);
+
+    let mut levels = closed.iter().map(|p| p[1]).collect::<Vec<_>>();
+    levels.sort_by(f64::total_cmp);
+    let median = levels[levels.len() / 2];
+    Some(ImpliedVolatility {
+        level,
+        change_24h,
+        regime: IvRegime::classify(level, median, change_24h),
+    })
+}
+
+/// Accuracy and PnL of a backtest by the target's implied-volatility regime at each
+/// window, for the windows where it was known.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RegimeBreakdown {
+    pub by_regime: BTreeMap<IvRegime, SessionStats>,
+}
+
+impl RegimeBreakdown {
+    pub fn record_prediction(&mut self, regime: IvRegime, correct: bool) {
+        let s = self.by_regime.entry(regime).or_default();
+        s.windows += 1;
+        s.correct += correct as usize;
+        s.accuracy = s.correct as f64 / s.windows as f64;
+    }
+
+    pub fn record_trade(&mut self, regime: IvRegime, return_pct: f64) {
+        let s = self.by_regime.entry(regime).or_default();
+        s.trades += 1;
+        s.total_return += return_pct;
+    }
+}
+
+/// DVOL history in a [`RowStore`] of [`IvPoint`]s, filled by live analysis and `fetch`.
+#[cfg(feature = "native")]
+#[derive(Debug, Clone)]
+pub struct ImpliedVolStore {
+    rows: RowStore<2>,
+}
+
+#[cfg(feature = "native")]
+impl Default for ImpliedVolStore {
+    fn default() -> Self {
+        Self::new(IMPLIED_VOL_DIR)
+    }
+}
+
+#[cfg(feature = "native")]
+impl ImpliedVolStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            rows: RowStore::new(dir),
+        }
+    }
+
+    /// The stored hours of `symbol`, oldest first; empty when none were fetched.
+    pub fn load(&self, symbol: &str) -> Result<Vec<IvPoint>> {
+        self.rows.load(symbol)
+    }
+
+    /// Downloads the hours of `[from, to)` into the store, page by page. Returns the
+    /// number of new hours stored.
+    pub async fn fetch_history(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<usize> {
+        let page = Duration::hours(MAX_HOURS_PER_REQUEST);
+        let mut added = 0;
+        let mut page_start = from;
+        while page_start < to {
+            let page_end = (page_start + page).min(to);
+            let points = fetch_dvol(symbol, page_start, page_end).await?;
+            let new = self.rows.merge(symbol, &points)?;
+            added += new;
+            tracing::info!(symbol, %page_start, %page_end, new, "Fetched DVOL page");
+            page_start = page_end;
+        }
+        Ok(added)
+    }
+
+    /// Fetches the baseline hours of `symbol` into the store and reads the index as of
+    /// `now`.
+    pub async fn refresh(
+        &self,
+        symbol: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<ImpliedVolatility>> {
+        let from = now - Duration::hours(IV_BASELINE_HOURS as i64 + 1);
+        self.rows
+            .merge(symbol, &fetch_dvol(symbol, from, now).await?)?;
+        Ok(implied_volatility_as_of(
+            &self.load(symbol)?,
+            now.timestamp() as f64,
+        ))
+    }
+}
+
+/// Deribit returns at most this many points per request.
+#[cfg(feature = "native")]
+const MAX_HOURS_PER_REQUEST: i64 = 1000;
+#[cfg(feature = "native")]
+const DVOL_URL: &str = "https://www.deribit.com/api/v2/public/get_volatility_index_data";
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+struct DvolResponse {
+    result: DvolResult,
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+struct DvolResult {
+    /// `[time_ms, open, high, low, close]` per hour.
+    data: Vec<[f64; 5]>,
+}
+
+/// Hourly DVOL closes of `symbol` over `[from, to)`, oldest first.
+#[cfg(feature = "native")]
+pub async fn fetch_dvol(
+    symbol: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<IvPoint>> {
+    anyhow::ensure!(
+        DVOL_CURRENCIES.contains(&symbol),
+        "Deribit has no DVOL index for {}",
+        symbol
+    );
+    let response = reqwest::Client::new()
+        .get(DVOL_URL)
+        .query(&[
+            ("currency", symbol.to_string()),
+            ("resolution", "3600".to_string()),
+            ("start_timestamp", from.timestamp_millis().to_string()),
+            ("end_timestamp", to.timestamp_millis().to_string()),
+        ])
+        .timeout(std::time::Duration::from_secs(30))
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        anyhow::bail!("Deribit API error for {}: {} - {}", symbol, status, text);
+    }
+    let (from, to) = (from.timestamp_millis() as f64, to.timestamp_millis() as f64);
+    let mut points = response
+        .json::<DvolResponse>()
+        .await?
+        .result
+        .data
+        .into_iter()
+        .filter(|p| p[0] >= from && p[0] < to)
+        .map(|p| [(p[0] / 1000.0).floor(), p[4]])
+        .collect::<Vec<_>>();
+    points.sort_by(|a, b| a[0].total_cmp(&b[0]));
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_implied_volatility_as_of() {
+        // A flat month at 50, then a rise to 60 over the last day
+        let mut points = (0..IV_BASELINE_HOURS)
+            .map(|h| [h as f64 * HOUR, 50.0])
+            .collect::<Vec<_>>();
+        let last = IV_BASELINE_HOURS as f64 * HOUR;
+        points.push([last, 60.0]);
+
+        let iv = implied_volatility_as_of(&points, last + HOUR).unwrap();
+        assert_eq!(iv.level, 60.0);
+        assert!((iv.change_24h.unwrap() - 0.2).abs() < 1e-9);
+        assert_eq!(iv.regime, IvRegime::Spike);
+
+        // The rising hour is still open
+        let iv = implied_volatility_as_of(&points, last + HOUR / 2.0).unwrap();
+        assert_eq!((iv.level, iv.regime), (50.0, IvRegime::Normal));
+
+        // Stale data is not shown as current
+        assert_eq!(implied_volatility_as_of(&points, last + 5.0 * HOUR), None);
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(IvRegime::classify(39.0, 50.0, None), IvRegime::Calm);
+        assert_eq!(
+            IvRegime::classify(61.0, 50.0, Some(0.05)),
+            IvRegime::Elevated
+        );
+        assert_eq!(IvRegime::classify(45.0, 50.0, Some(0.16)), IvRegime::Spike);
+        assert_eq!(IvRegime::classify(50.0, 50.0, Some(-0.3)), IvRegime::Normal);
+    }
+}
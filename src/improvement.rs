@@ -0,0 +1,303 @@
+use std::fs;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::backtest::{backtest_current_prompt, improve_prompt, BacktestOptions, BacktestRun};
+use crate::out_of_sample::{OutOfSampleJob, OutOfSampleQueue};
+
+/// Why an [`ImprovementLoop`] stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    TargetReached,
+    IterationLimit,
+    BudgetExhausted,
+    /// The best prompt got every window right, so there is nothing to improve on.
+    NoFailures,
+    /// The improver kept returning prompts that failed linting or had already been tried.
+    UnusablePrompts,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LoopSummary {
+    pub iterations: usize,
+    /// Accuracy of every prompt tested, in order.
+    pub scores: Vec<f64>,
+    pub best_accuracy: f64,
+    /// Rewritten prompts discarded by validation gating.
+    pub rejected: usize,
+    /// Full-set reruns of prompts that reached the target on a sample.
+    pub confirmations: usize,
+    /// Full-set accuracy of the last confirmed prompt.
+    pub confirmed_accuracy: Option<f64>,
+    pub stop_reason: StopReason,
+    /// When the improved prompt's out-of-sample evaluation can run (see
+    /// `out-of-sample`); `None` if no rewrite beat the starting prompt, or the loop tuned
+    /// another target than the default, which out-of-sample evaluation doesn't cover.
+    pub out_of_sample_due: Option<DateTime<Utc>>,
+}
+
+/// Backtest-and-rewrite loop over the prompt file of the backtest's symbol profile
+/// (`prompt.txt` by default): each iteration scores the current prompt and, unless a stop
+/// condition is met, replaces it with a model-improved version. Each target symbol keeps
+/// its own prompt history, so a loop per symbol tunes each prompt to its own market.
+#[derive(Debug, Clone)]
+pub struct ImprovementLoop {
+    /// Stop once a prompt scores at least this accuracy.
+    pub target_accuracy: f64,
+    pub max_iterations: usize,
+    /// Wall-clock budget; no new iteration starts once it is spent.
+    pub budget: Option<Duration>,
+    /// Keep a rewritten prompt only if it beats the best score so far. Rejected prompts are
+    /// replaced by the best one, which is then improved again from its own failures.
+    pub validation_gating: bool,
+    /// Window selection for every iteration. An unset seed is drawn once, so all
+    /// iterations are scored on the same windows. With a `sample`, a prompt that reaches
+    /// the target is rescored on every window before the loop accepts it.
+    pub backtest: BacktestOptions,
+    /// Show the improver the model's stored analyses of the prompt's failures (see
+    /// `explain`).
+    pub postmortems: bool,
+}
+
+impl Default for ImprovementLoop {
+    fn default() -> Self {
+        Self {
+            target_accuracy: 0.7,
+            max_iterations: 10,
+            budget: None,
+            validation_gating: false,
+            backtest: BacktestOptions::default(),
+            postmortems: false,
+        }
+    }
+}
+
+impl ImprovementLoop {
+    /// The stop condition after `iterations` completed iterations, `best` being the
+    /// accuracy the next rewrite would start from.
+    pub fn stop_reason(
+        &self,
+        iterations: usize,
+        best: f64,
+        has_failures: bool,
+        elapsed: Duration,
+    ) -> Option<StopReason> {
+        if best >= self.target_accuracy {
+            Some(StopReason::TargetReached)
+        } else if !has_failures {
+            Some(StopReason::NoFailures)
+        } else if iterations >= self.max_iterations {
+            Some(StopReason::IterationLimit)
+        } else if self.budget.is_some_and(|b| elapsed >= b) {
+            Some(StopReason::BudgetExhausted)
+        } else {
+            None
+        }
+    }
+
+    /// The stop condition for the best run `from`. A sampled score is only a hint: a prompt
+    /// that reached the target on a sample is rescored on every window by `full`, and that
+    /// run decides instead. It is returned too, as what the next rewrite improves on.
+    async fn confirmed_stop<Fut: Future<Output = Result<BacktestRun>>>(
+        &self,
+        from: &BacktestRun,
+        sampled: bool,
+        iterations: usize,
+        elapsed: Duration,
+        full: impl FnOnce() -> Fut,
+    ) -> Result<(Option<StopReason>, Option<BacktestRun>)> {
+        let stop = |run: &BacktestRun| {
+            self.stop_reason(iterations, run.accuracy, !run.failures.is_empty(), elapsed)
+        };
+        if !sampled || stop(from) != Some(StopReason::TargetReached) {
+            return Ok((stop(from), None));
+        }
+        let full = full().await?;
+        Ok((stop(&full), Some(full)))
+    }
+
+    pub async fn run(&self) -> Result<LoopSummary> {
+        let prompt_file = &self.backtest.profile.prompt;
+        let started = Instant::now();
+        let mut scores = Vec::new();
+        let mut rejected = 0;
+        let mut best: Option<BacktestRun> = None;
+        let options = self.backtest.seeded();
+        let full_options = BacktestOptions {
+            sample: None,
+            ..options.clone()
+        };
+        let mut confirmations = 0;
+        let mut confirmed_accuracy = None;
+        let mut full_run: Option<BacktestRun> = None;
+        let mut starting_prompt = None;
+
+        loop {
+            let run = backtest_current_prompt(&options).await?;
+            scores.push(run.accuracy);
+            starting_prompt.get_or_insert_with(|| run.prompt.clone());
+            tracing::info!(
+                iteration = scores.len(),
+                accuracy = run.accuracy,
+                "Prompt backtested"
+            );
+
+            match &best {
+                Some(b) if self.validation_gating && run.accuracy <= b.accuracy => {
+                    tracing::info!(
+                        accuracy = run.accuracy,
+                        best = b.accuracy,
+                        "Rewritten prompt rejected; restoring the best prompt"
+                    );
+                    rejected += 1;
+                    fs::write(prompt_file, &b.prompt)?;
+                }
+                _ => best = Some(run),
+            }
+            let mut from = best.as_ref().expect("set on the first iteration");
+            // The prompt file holds the best prompt at this point
+            let (stop, full) = self
+                .confirmed_stop(
+                    from,
+                    options.sample.is_some(),
+                    scores.len(),
+                    started.elapsed(),
+                    || backtest_current_prompt(&full_options),
+                )
+                .await?;
+            if let Some(full) = full {
+                confirmations += 1;
+                confirmed_accuracy = Some(full.accuracy);
+                tracing::info!(
+                    sampled = from.accuracy,
+                    full = full.accuracy,
+                    "Confirmed prompt on all windows"
+                );
+                // Improve from the full run's failures if the prompt didn't hold up
+                from = full_run.insert(full);
+            }
+
+            let stop_reason = match stop {
+                Some(stop_reason) => stop_reason,
+                None => match improve_prompt(from, self.postmortems).await? {
+                    Some(improved) => {
+                        fs::write(prompt_file, improved)?;
+                        continue;
+                    }
+                    None => StopReason::UnusablePrompts,
+                },
+            };
+            let best_accuracy = scores.iter().copied().fold(0.0, f64::max);
+
+            // Score the improved prompt on data that didn't exist yet while it was tuned
+            let out_of_sample_due = if starting_prompt.as_ref() != Some(&from.prompt) {
+                let job = OutOfSampleJob::new(
+                    self.backtest.profile.clone(),
+                    from.prompt.clone(),
+                    from.accuracy,
+                    Utc::now(),
+                );
+                let due = job.due_at();
+                OutOfSampleQueue::default().schedule(job)?;
+                tracing::info!(%due, "Scheduled out-of-sample evaluation");
+                Some(due)
+            } else {
+                None
+            };
+            return Ok(LoopSummary {
+                iterations: scores.len(),
+                scores,
+                best_accuracy,
+                rejected,
+                confirmations,
+                confirmed_accuracy,
+                stop_reason,
+                out_of_sample_due,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Action;
+
+    #[test]
+    fn test_stop_reason() {
+        let looped = ImprovementLoop {
+            budget: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let minute = Duration::from_secs(60);
+
+        assert_eq!(looped.stop_reason(1, 0.5, true, Duration::ZERO), None);
+        assert_eq!(
+            looped.stop_reason(1, 0.7, true, Duration::ZERO),
+            Some(StopReason::TargetReached)
+        );
+        assert_eq!(
+            looped.stop_reason(1, 0.5, false, Duration::ZERO),
+            Some(StopReason::NoFailures)
+        );
+        assert_eq!(
+            looped.stop_reason(10, 0.5, true, Duration::ZERO),
+            Some(StopReason::IterationLimit)
+        );
+        assert_eq!(
+            looped.stop_reason(1, 0.5, true, minute),
+            Some(StopReason::BudgetExhausted)
+        );
+    }
+
+    #[test]
+    fn test_sampled_target_is_confirmed_on_all_windows() {
+        let looped = ImprovementLoop::default();
+        let run = |accuracy: f64| BacktestRun {
+            run_id: "r1".into(),
+            prompt: "Use RSI".into(),
+            accuracy,
+            failures: vec![(3, Action::Long, Action::Short, "Breakout".into())],
+            hallucinations: Vec::new(),
+            history: Vec::new(),
+            confusion: Default::default(),
+            total_return: 0.0,
+            label_thresholds: (1.01, 0.99),
+            symbols: vec!["ETH".into()],
+        };
+        let confirm = |from: BacktestRun, sampled: bool, full: f64| {
+            let rescored = std::cell::Cell::new(false);
+            let (stop, confirmed) = futures::executor::block_on(looped.confirmed_stop(
+                &from,
+                sampled,
+                1,
+                Duration::ZERO,
+                || {
+                    rescored.set(true);
+                    std::future::ready(Ok(run(full)))
+                },
+            ))
+            .unwrap();
+            assert_eq!(rescored.get(), confirmed.is_some());
+            (stop, confirmed.map(|c| c.accuracy))
+        };
+
+        // A lucky sample doesn't stop the loop once the full set disagrees
+        assert_eq!(confirm(run(0.8), true, 0.6), (None, Some(0.6)));
+        assert_eq!(
+            confirm(run(0.8), true, 0.75),
+            (Some(StopReason::TargetReached), Some(0.75))
+        );
+        // Only a sampled run that reached the target is rescored
+        assert_eq!(confirm(run(0.5), true, 0.9), (None, None));
+        assert_eq!(
+            confirm(run(0.8), false, 0.6),
+            (Some(StopReason::TargetReached), None)
+        );
+    }
+}
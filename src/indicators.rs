@@ -0,0 +1,140 @@
+// Volume-weighted price features computed over a candle window.
+// Candles use the `[time, open, high, low, close, volume]` layout produced by `candles_to_array`.
+
+use serde::Serialize;
+
+const HIGH: usize = 2;
+const LOW: usize = 3;
+const CLOSE: usize = 4;
+const VOLUME: usize = 5;
+
+/// Number of price buckets used for the coarse volume profile.
+pub const VOLUME_PROFILE_BUCKETS: usize = 6;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VolumeBucket {
+    pub low: f64,
+    pub high: f64,
+    pub volume: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VolumeFeatures {
+    pub vwap: f64,
+    /// Distance of the last close from the VWAP, as a fraction of the VWAP.
+    pub vwap_distance: f64,
+    pub profile: Vec<VolumeBucket>,
+}
+
+impl VolumeFeatures {
+    /// The bucket holding the most volume (the "point of control").
+    pub fn point_of_control(&self) -> Option<&VolumeBucket> {
+        self.profile
+            .iter()
+            .max_by(|a, b| a.volume.total_cmp(&b.volume))
+    }
+}
+
+fn typical_price(c: &[f64; 6]) -> f64 {
+    (c[HIGH] + c[LOW] + c[CLOSE]) / 3.0
+}
+
+/// Volume-weighted average of the typical price over the window.
+pub fn vwap(data: &[[f64; 6]]) -> Option<f64> {
+    let (pv, vol) = data.iter().fold((0.0, 0.0), |(pv, vol), c| {
+        (pv + typical_price(c) * c[VOLUME], vol + c[VOLUME])
+    });
+
+    (vol > 0.0).then(|| pv / vol)
+}
+
+pub fn vwap_distance(data: &[[f64; 6]]) -> Option<f64> {
+    let last = data.last()?;
+    let vwap = vwap(data)?;
+    Some((last[CLOSE] - vwap) / vwap)
+}
+
+/// Splits the window's price range into `buckets` equal bands and assigns each
+/// candle's volume to the band containing its typical price.
+pub fn volume_profile(data: &[[f64; 6]], buckets: usize) -> Vec<VolumeBucket> {
+    if data.is_empty() || buckets == 0 {
+        return Vec::new();
+    }
+
+    let low = data.iter().map(|c| c[LOW]).fold(f64::INFINITY, f64::min);
+    let high = data
+        .iter()
+        .map(|c| c[HIGH])
+        .fold(f64::NEG_INFINITY, f64::max);
+    let step = (high - low) / buckets as f64;
+
+    let mut profile = (0..buckets)
+        .map(|i| VolumeBucket {
+            low: low + step * i as f64,
+            high: low + step * (i + 1) as f64,
+            volume: 0.0,
+        })
+        .collect::<Vec<_>>();
+
+    for c in data {
+        let idx = if step > 0.0 {
+            (((typical_price(c) - low) / step) as usize).min(buckets - 1)
+        } else {
+            0
+        };
+        profile[idx].volume += c[VOLUME];
+    }
+
+    profile
+}
+
+pub fn volume_features(data: &[[f64; 6]]) -> Option<VolumeFeatures> {
+    Some(VolumeFeatures {
+        vwap: vwap(data)?,
+        vwap_distance: vwap_distance(data)?,
+        profile: volume_profile(data, VOLUME_PROFILE_BUCKETS),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vwap_weights_by_volume() {
+        let data = [
+            [0.0, 0.0, 100.0, 100.0, 100.0, 1.0],
+            [0.0, 0.0, 200.0, 200.0, 200.0, 3.0],
+        ];
+        assert_eq!(vwap(&data), Some(175.0));
+        let distance = vwap_distance(&data).unwrap();
+        assert!((distance - 25.0 / 175.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_vwap_zero_volume() {
+        let data = [[0.0, 0.0, 100.0, 100.0, 100.0, 0.0]];
+        assert_eq!(vwap(&data), None);
+        assert!(volume_features(&data).is_none());
+    }
+
+    #[test]
+    fn test_volume_profile_buckets() {
+        let data = [
+            [0.0, 0.0, 101.0, 99.0, 100.0, 5.0],
+            [0.0, 0.0, 111.0, 109.0, 110.0, 2.0],
+            [0.0, 0.0, 110.5, 109.5, 110.0, 4.0],
+        ];
+        let profile = volume_profile(&data, 2);
+        assert_eq!(profile.len(), 2);
+        assert_eq!(profile[0].volume, 5.0);
+        assert_eq!(profile[1].volume, 6.0);
+        assert_eq!(profile[0].low, 99.0);
+        assert_eq!(profile[1].high, 111.0);
+
+        let features = volume_features(&data).unwrap();
+        let total: f64 = features.profile.iter().map(|b| b.volume).sum();
+        assert_eq!(total, 11.0);
+        assert!(features.point_of_control().unwrap().low >= 105.0);
+    }
+}
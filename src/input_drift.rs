@@ -0,0 +1,162 @@
+use std::fmt::Write as _;
+
+use serde::{Deserialize, Serialize};
+
+use crate::routing::realized_volatility;
+use crate::GRANULARITY;
+
+/// Slack beyond the backtest's range, as a share of that range, before a live value counts
+/// as outside the evaluated regime.
+pub const INPUT_DRIFT_MARGIN: f64 = 0.5;
+const VOLUME: usize = 5;
+
+/// Summary statistics of one prompt window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputStats {
+    /// Standard deviation of close-to-close returns.
+    pub volatility: f64,
+    /// Mean volume per candle.
+    pub volume: f64,
+    /// Missing candles between the first and last.
+    pub gaps: usize,
+}
+
+impl InputStats {
+    pub fn of(window: &[[f64; 6]]) -> Self {
+        let interval = GRANULARITY as f64;
+        Self {
+            volatility: realized_volatility(window).unwrap_or(0.0),
+            volume: window.iter().map(|c| c[VOLUME]).sum::<f64>() / window.len().max(1) as f64,
+            gaps: window
+                .windows(2)
+                .map(|w| (((w[1][0] - w[0][0]) / interval).round() as usize).saturating_sub(1))
+                .sum(),
+        }
+    }
+}
+
+/// Range and median of one statistic over the windows of a backtest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatRange {
+    pub min: f64,
+    pub median: f64,
+    pub max: f64,
+}
+
+impl StatRange {
+    fn of(mut values: Vec<f64>) -> Self {
+        values.sort_by(f64::total_cmp);
+        Self {
+            min: values[0],
+            median: values[values.len() / 2],
+            max: values[values.len() - 1],
+        }
+    }
+
+    /// Whether `value` lies beyond the range by more than [`INPUT_DRIFT_MARGIN`] of it.
+    pub fn is_far_outside(&self, value: f64) -> bool {
+        let slack = INPUT_DRIFT_MARGIN * (self.max - self.min);
+        value < self.min - slack || value > self.max + slack
+    }
+}
+
+/// The input regime a backtest evaluated the prompt in, from its target's windows.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InputProfile {
+    pub windows: usize,
+    pub volatility: StatRange,
+    pub volume: StatRange,
+    pub gaps: StatRange,
+}
+
+impl InputProfile {
+    /// The profile of `stats`, `None` when there are none.
+    pub fn from_stats(stats: &[InputStats]) -> Option<Self> {
+        if stats.is_empty() {
+            return None;
+        }
+        let range = |f: fn(&InputStats) -> f64| StatRange::of(stats.iter().map(f).collect());
+        Some(Self {
+            windows: stats.len(),
+            volatility: range(|s| s.volatility),
+            volume: range(|s| s.volume),
+            gaps: range(|s| s.gaps as f64),
+        })
+    }
+
+    /// The statistics of a live window far outside this profile.
+    pub fn drift(&self, live: &InputStats) -> Vec<InputDrift> {
+        [
+            ("volatility", live.volatility, self.volatility),
+            ("volume", live.volume, self.volume),
+            ("gaps", live.gaps as f64, self.gaps),
+        ]
+        .into_iter()
+        .filter(|(_, value, range)| range.is_far_outside(*value))
+        .map(|(stat, value, backtest)| InputDrift {
+            stat,
+            value,
+            backtest,
+        })
+        .collect()
+    }
+}
+
+/// A live input statistic the prompt was never backtested near.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InputDrift {
+    pub stat: &'static str,
+    pub value: f64,
+    pub backtest: StatRange,
+}
+
+pub fn describe_input_drift(drift: &[InputDrift]) -> String {
+    let mut out = String::new();
+    for d in drift {
+        let _ = writeln!(
+            out,
+            "{}: live {:.4}, backtest {:.4} to {:.4} (median {:.4})",
+            d.stat, d.value, d.backtest.min, d.backtest.max, d.backtest.median
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_drift() {
+        let hour = GRANULARITY as f64;
+        let window = |step: f64, volume: f64| {
+            (0..24)
+                .map(|i| {
+                    let close = 100.0 + if i % 2 == 0 { step } else { 0.0 };
+                    [i as f64 * hour, close, close, close, close, volume]
+                })
+                .collect::<Vec<_>>()
+        };
+        let backtest = [(0.5, 900.0), (1.0, 1000.0), (1.5, 1100.0)]
+            .map(|(step, volume)| InputStats::of(&window(step, volume)));
+        let profile = InputProfile::from_stats(&backtest).unwrap();
+        assert_eq!(profile.windows, 3);
+        assert_eq!(profile.volume.median, 1000.0);
+        assert_eq!(profile.gaps.max, 0.0);
+
+        assert!(profile
+            .drift(&InputStats::of(&window(1.2, 1150.0)))
+            .is_empty());
+
+        // Twice the swings on a tenth of the volume, with two candles missing
+        let mut thin = window(3.0, 100.0);
+        thin.drain(5..7);
+        let live = InputStats::of(&thin);
+        assert_eq!(live.gaps, 2);
+        let drift = profile.drift(&live);
+        let stats = drift.iter().map(|d| d.stat).collect::<Vec<_>>();
+        assert_eq!(stats, ["volatility", "volume", "gaps"]);
+        assert!(describe_input_drift(&drift).contains("volume: live 100.0000"));
+        assert_eq!(InputProfile::from_stats(&[]), None);
+    }
+}
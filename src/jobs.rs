@@ -0,0 +1,505 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::Duration as StdDuration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::backtest::{backtest_current_prompt, sweep_reasoning_effort, BacktestOptions};
+use crate::encoding::CandleEncoding;
+use crate::profiles::SymbolProfile;
+use crate::storage::DATA_DIR;
+use crate::ReasoningEffort;
+
+const JOBS_FILE: &str = "jobs.json";
+/// Where each running job keeps its copy of the prompt, beside the queue file.
+const JOB_PROMPTS_DIR: &str = "job_prompts";
+/// Finished jobs kept in the queue file, newest first; older ones are dropped.
+const FINISHED_KEPT: usize = 100;
+/// How often an idle runner looks for work, and a busy one checks for cancellation.
+const POLL: StdDuration = StdDuration::from_secs(5);
+/// Priority of jobs submitted without one; lower runs first.
+pub const DEFAULT_PRIORITY: u8 = 5;
+
+/// Read-modify-write of the queue file, so the API and the runner don't lose each other's
+/// changes.
+static QUEUE_LOCK: Mutex<()> = Mutex::new(());
+
+/// A long experiment, with the options of the command it runs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobSpec {
+    /// A backtest of the current prompt, recorded like `backtest` records one.
+    Backtest {
+        #[serde(default)]
+        seed: Option<u64>,
+        #[serde(default = "default_stride")]
+        stride: usize,
+        #[serde(default)]
+        sample: Option<usize>,
+    },
+    /// A reasoning-effort and candle-encoding sweep, as `sweep` runs.
+    Sweep {
+        #[serde(default = "default_efforts")]
+        efforts: Vec<ReasoningEffort>,
+        #[serde(default = "default_encodings")]
+        encodings: Vec<CandleEncoding>,
+        #[serde(default)]
+        seed: Option<u64>,
+        #[serde(default = "default_stride")]
+        stride: usize,
+        #[serde(default)]
+        sample: Option<usize>,
+    },
+}
+
+fn default_stride() -> usize {
+    1
+}
+
+fn default_efforts() -> Vec<ReasoningEffort> {
+    ReasoningEffort::ALL.to_vec()
+}
+
+fn default_encodings() -> Vec<CandleEncoding> {
+    vec![CandleEncoding::Raw]
+}
+
+impl JobSpec {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            JobSpec::Backtest { .. } => "backtest",
+            JobSpec::Sweep { .. } => "sweep",
+        }
+    }
+
+    fn options(&self) -> BacktestOptions {
+        let (JobSpec::Backtest {
+            seed,
+            stride,
+            sample,
+        }
+        | JobSpec::Sweep {
+            seed,
+            stride,
+            sample,
+            ..
+        }) = self;
+        BacktestOptions {
+            seed: *seed,
+            stride: *stride,
+            sample: *sample,
+            ..Default::default()
+        }
+    }
+}
+
+/// A job submission: what to run, and how urgently.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct JobRequest {
+    #[serde(flatten)]
+    pub spec: JobSpec,
+    #[serde(default = "default_priority")]
+    pub priority: u8,
+}
+
+fn default_priority() -> u8 {
+    DEFAULT_PRIORITY
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn name(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    pub spec: JobSpec,
+    /// Queued jobs run lowest first; ties in submission order.
+    pub priority: u8,
+    pub status: JobStatus,
+    pub submitted_at: DateTime<Utc>,
+    #[serde(default)]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub finished_at: Option<DateTime<Utc>>,
+    /// What a completed job produced.
+    #[serde(default)]
+    pub result: Option<serde_json::Value>,
+    /// Why a failed job failed.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Backtest and sweep jobs, queued, running and recently finished, kept in one JSON file so
+/// they survive restarts.
+#[derive(Debug, Clone)]
+pub struct JobQueue {
+    path: PathBuf,
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        Self::new(DATA_DIR)
+    }
+}
+
+impl JobQueue {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            path: dir.as_ref().join(JOBS_FILE),
+        }
+    }
+
+    /// Every job, in submission order.
+    pub fn jobs(&self) -> Result<Vec<Job>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let data = fs::read_to_string(&self.path)?;
+        serde_json::from_str(&data).context("Corrupt job queue")
+    }
+
+    pub fn get(&self, id: u64) -> Result<Option<Job>> {
+        Ok(self.jobs()?.into_iter().find(|j| j.id == id))
+    }
+
+    fn update<T>(&self, change: impl FnOnce(&mut Vec<Job>) -> T) -> Result<T> {
+        let _lock = QUEUE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut jobs = self.jobs()?;
+        let out = change(&mut jobs);
+
+        let finished = jobs.iter().filter(|j| j.status.is_finished()).count();
+        let mut excess = finished.saturating_sub(FINISHED_KEPT);
+        jobs.retain(|j| {
+            let drop = excess > 0 && j.status.is_finished();
+            excess -= drop as usize;
+            !drop
+        });
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(&jobs)?;
+        let partial = self.path.with_extension("json.partial");
+        fs::write(&partial, json)?;
+        fs::rename(&partial, &self.path).context("Failed to write job queue")?;
+        Ok(out)
+    }
+
+    fn change_job(&self, id: u64, change: impl FnOnce(&mut Job)) -> Result<Option<Job>> {
+        self.update(|jobs| {
+            let job = jobs.iter_mut().find(|j| j.id == id)?;
+            change(job);
+            Some(job.clone())
+        })
+    }
+
+    pub fn submit(&self, request: JobRequest, now: DateTime<Utc>) -> Result<Job> {
+        self.update(|jobs| {
+            let job = Job {
+                id: jobs.iter().map(|j| j.id).max().unwrap_or(0) + 1,
+                spec: request.spec,
+                priority: request.priority,
+                status: JobStatus::Queued,
+                submitted_at: now,
+                started_at: None,
+                finished_at: None,
+                result: None,
+                error: None,
+            };
+            jobs.push(job.clone());
+            job
+        })
+    }
+
+    /// Marks the most urgent queued job running and returns it.
+    pub fn start_next(&self, now: DateTime<Utc>) -> Result<Option<Job>> {
+        self.update(|jobs| {
+            let job = jobs
+                .iter_mut()
+                .filter(|j| j.status == JobStatus::Queued)
+                .min_by_key(|j| (j.priority, j.id))?;
+            job.status = JobStatus::Running;
+            job.started_at = Some(now);
+            Some(job.clone())
+        })
+    }
+
+    /// Records how a running job ended. A job cancelled while it ran stays cancelled.
+    pub fn finish(
+        &self,
+        id: u64,
+        outcome: Result<serde_json::Value, String>,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        self.change_job(id, |job| {
+            if job.status != JobStatus::Running {
+                return;
+            }
+            job.finished_at = Some(now);
+            match outcome {
+                Ok(result) => {
+                    job.status = JobStatus::Completed;
+                    job.result = Some(result);
+                }
+                Err(error) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(error);
+                }
+            }
+        })?;
+        Ok(())
+    }
+
+    /// Cancels a queued or running job; a running one is stopped by its runner within
+    /// [`POLL`]. Finished jobs are left as they are. `None` when there is no such job.
+    pub fn cancel(&self, id: u64, now: DateTime<Utc>) -> Result<Option<Job>> {
+        self.change_job(id, |job| {
+            if !job.status.is_finished() {
+                job.status = JobStatus::Cancelled;
+                job.finished_at = Some(now);
+            }
+        })
+    }
+
+    /// Moves a job up or down the queue. `None` when there is no such job.
+    pub fn set_priority(&self, id: u64, priority: u8) -> Result<Option<Job>> {
+        self.change_job(id, |job| job.priority = priority)
+    }
+
+    /// Copies the prompt at `from` for job `id` to run on, so the job never reads or writes
+    /// the file the live loop uses.
+    fn copy_prompt(&self, id: u64, from: &Path) -> Result<PathBuf> {
+        let dir = self
+            .path
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(JOB_PROMPTS_DIR);
+        fs::create_dir_all(&dir)?;
+        let copy = dir.join(format!("{}.txt", id));
+        fs::copy(from, &copy)
+            .with_context(|| format!("Failed to copy {} for job {}", from.display(), id))?;
+        Ok(copy)
+    }
+
+    /// Queues again the jobs a runner that stopped left running.
+    pub fn requeue_interrupted(&self) -> Result<usize> {
+        self.update(|jobs| {
+            let running = jobs
+                .iter_mut()
+                .filter(|j| j.status == JobStatus::Running)
+                .collect::<Vec<_>>();
+            for job in &running {
+                tracing::warn!(
+                    id = job.id,
+                    kind = job.spec.kind(),
+                    "Requeuing interrupted job"
+                );
+            }
+            let count = running.len();
+            for job in running {
+                job.status = JobStatus::Queued;
+                job.started_at = None;
+            }
+            count
+        })
+    }
+}
+
+async fn run_spec(spec: &JobSpec, prompt: &Path) -> Result<serde_json::Value> {
+    let options = BacktestOptions {
+        profile: SymbolProfile {
+            prompt: prompt.to_path_buf(),
+            ..SymbolProfile::default()
+        },
+        ..spec.options()
+    };
+    match spec {
+        JobSpec::Backtest { .. } => {
+            let run = backtest_current_prompt(&options).await?;
+            Ok(json!({
+                "run_id": run.run_id,
+                "accuracy": run.accuracy,
+                "total_return": run.total_return,
+            }))
+        }
+        JobSpec::Sweep {
+            efforts, encodings, ..
+        } => {
+            let points = sweep_reasoning_effort(&options, efforts, encodings).await?;
+            Ok(serde_json::to_value(points)?)
+        }
+    }
+}
+
+/// Resolves once job `id` has been cancelled or removed.
+async fn cancelled(queue: &JobQueue, id: u64) {
+    loop {
+        tokio::time::sleep(POLL).await;
+        match queue.get(id) {
+            Ok(Some(job)) if job.status != JobStatus::Cancelled => {}
+            Err(err) => tracing::warn!(id, %err, "Failed to read the job queue"),
+            _ => return,
+        }
+    }
+}
+
+/// Runs the started job `id` on a copy of the default prompt and records how it ended.
+/// This is what the process [`run_jobs`] starts for each job runs.
+pub async fn run_job(queue: &JobQueue, id: u64) -> Result<()> {
+    let job = queue.get(id)?.with_context(|| format!("No job {}", id))?;
+    anyhow::ensure!(
+        job.status == JobStatus::Running,
+        "Job {} is {}, not running",
+        id,
+        job.status.name()
+    );
+    let outcome = match queue.copy_prompt(id, &SymbolProfile::default().prompt) {
+        Ok(prompt) => {
+            let outcome = run_spec(&job.spec, &prompt).await;
+            let _ = fs::remove_file(&prompt);
+            outcome
+        }
+        Err(err) => Err(err),
+    };
+    if let Err(err) = &outcome {
+        tracing::warn!(id, %err, "Job failed");
+    }
+    queue.finish(id, outcome.map_err(|e| format!("{e:#}")), Utc::now())
+}
+
+/// Runs the queue's jobs one at a time, most urgent first, for as long as the process
+/// lives. Jobs a previous runner left running are queued again first. Each job runs in a
+/// process of its own (`run-job <id>`), so its model calls queue in that process rather
+/// than among the live loop's, and cancelling it kills the process, calls in flight
+/// included.
+pub async fn run_jobs(queue: JobQueue) -> Result<()> {
+    queue.requeue_interrupted()?;
+    let exe = std::env::current_exe().context("Failed to locate the executable for jobs")?;
+    loop {
+        let Some(job) = queue.start_next(Utc::now())? else {
+            tokio::time::sleep(POLL).await;
+            continue;
+        };
+        tracing::info!(id = job.id, kind = job.spec.kind(), "Running job");
+        let mut child = match tokio::process::Command::new(&exe)
+            .args(["run-job", &job.id.to_string()])
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(err) => {
+                let error = format!("Failed to start the job process: {err}");
+                queue.finish(job.id, Err(error), Utc::now())?;
+                continue;
+            }
+        };
+        tokio::select! {
+            status = child.wait() => {
+                // A process that died before recording its outcome leaves the job running
+                let status = status?;
+                if !status.success() {
+                    let error = format!("Job process exited with {status}");
+                    queue.finish(job.id, Err(error), Utc::now())?;
+                }
+            }
+            _ = cancelled(&queue, job.id) => {
+                child.kill().await?;
+                tracing::info!(id = job.id, "Stopped cancelled job");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_runs_by_priority_and_cancels() {
+        let dir = std::env::temp_dir().join("happycharts_jobs");
+        let _ = fs::remove_dir_all(&dir);
+        let queue = JobQueue::new(&dir);
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let submit = |json: serde_json::Value| {
+            let request = serde_json::from_value::<JobRequest>(json).unwrap();
+            queue.submit(request, now).unwrap()
+        };
+
+        let backtest = submit(json!({ "kind": "backtest", "sample": 20 }));
+        assert_eq!(backtest.priority, DEFAULT_PRIORITY);
+        assert_eq!(backtest.spec.options().stride, 1);
+        let sweep = submit(json!({ "kind": "sweep", "efforts": ["low"], "priority": 1 }));
+        let other = submit(json!({ "kind": "backtest" }));
+        assert!(serde_json::from_value::<JobRequest>(json!({ "kind": "train" })).is_err());
+
+        // The sweep was submitted later but is more urgent
+        let started = queue.start_next(now).unwrap().unwrap();
+        assert_eq!(started.id, sweep.id);
+        queue.set_priority(other.id, 0).unwrap();
+        queue.cancel(sweep.id, now).unwrap();
+        // Its result arrives after it was cancelled, and is dropped
+        queue.finish(sweep.id, Ok(json!(1)), now).unwrap();
+        assert_eq!(
+            queue.get(sweep.id).unwrap().unwrap().status,
+            JobStatus::Cancelled
+        );
+
+        assert_eq!(queue.start_next(now).unwrap().unwrap().id, other.id);
+        // A restarted runner picks the interrupted job up again
+        assert_eq!(queue.requeue_interrupted().unwrap(), 1);
+        assert_eq!(queue.start_next(now).unwrap().unwrap().id, other.id);
+        queue
+            .finish(other.id, Err("No candles".into()), now)
+            .unwrap();
+        assert_eq!(queue.start_next(now).unwrap().unwrap().id, backtest.id);
+        assert_eq!(queue.start_next(now).unwrap(), None);
+
+        let jobs = queue.jobs().unwrap();
+        let statuses = jobs.iter().map(|j| j.status).collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            [JobStatus::Running, JobStatus::Cancelled, JobStatus::Failed]
+        );
+        assert_eq!(queue.cancel(99, now).unwrap(), None);
+        assert!(!dir.join("jobs.json.partial").exists());
+
+        // Jobs run on their own copy of the prompt
+        let live = dir.join("prompt.txt");
+        fs::write(&live, "champion").unwrap();
+        let copy = queue.copy_prompt(backtest.id, &live).unwrap();
+        assert_ne!(copy, live);
+        assert_eq!(fs::read_to_string(copy).unwrap(), "champion");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use chrono::DateTime;
+use serde::Serialize;
+
+use crate::analytics::{PredictionRecord, TradeRecord};
+use crate::Action;
+
+/// Position size every journal entry is scaled to, in USD of entry notional.
+pub const JOURNAL_NOTIONAL: f64 = 1000.0;
+
+/// One closed trade, in the flat shape trade-analysis tools import.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JournalEntry {
+    pub run_id: String,
+    /// `"backtest"` for simulated trades, `"paper"` for verified live signals.
+    pub source: String,
+    pub symbol: String,
+    pub side: String,
+    /// RFC 3339, UTC.
+    pub entry_time: String,
+    pub entry_price: f64,
+    pub exit_time: String,
+    pub exit_price: f64,
+    /// Units bought or sold.
+    pub quantity: f64,
+    /// Entry and exit fees in USD.
+    pub fees: f64,
+    /// Net of fees, in USD.
+    pub pnl: f64,
+    pub return_pct: f64,
+    pub prompt_version: String,
+    pub rationale: String,
+}
+
+const CSV_HEADER: &str = "run_id,source,symbol,side,entry_time,entry_price,exit_time,exit_price,quantity,fees,pnl,return_pct,prompt_version,rationale";
+
+/// Journal entries for `trades`, each sized to `notional` and paired with the prediction
+/// it came from (same run, symbol and entry candle). `fee` is the rate the trades were
+/// simulated with. Trades without a matching prediction keep an empty rationale.
+pub fn journal_entries(
+    trades: &[TradeRecord],
+    predictions: &[PredictionRecord],
+    source: &str,
+    notional: f64,
+    fee: f64,
+) -> Vec<JournalEntry> {
+    let by_window = predictions
+        .iter()
+        .map(|p| {
+            (
+                (p.run_id.as_str(), p.symbol.as_str(), p.window_end as i64),
+                p,
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    trades
+        .iter()
+        .map(|r| {
+            let t = &r.trade;
+            let prediction =
+                by_window.get(&(r.run_id.as_str(), r.symbol.as_str(), t.entry_time as i64));
+            let exit_notional = notional * t.exit_price / t.entry_price;
+            JournalEntry {
+                run_id: r.run_id.clone(),
+                source: source.to_string(),
+                symbol: r.symbol.clone(),
+                side: match t.side {
+                    Action::Long => "long",
+                    Action::Short => "short",
+                    Action::None => "none",
+                }
+                .to_string(),
+                entry_time: rfc3339(t.entry_time),
+                entry_price: t.entry_price,
+                exit_time: rfc3339(t.exit_time),
+                exit_price: t.exit_price,
+                quantity: notional / t.entry_price,
+                fees: (notional + exit_notional) * fee,
+                pnl: notional * t.return_pct,
+                return_pct: t.return_pct,
+                prompt_version: if r.prompt_hash.is_empty() {
+                    // Recorded before trades were tagged
+                    prediction
+                        .map(|p| p.prompt_hash.clone())
+                        .unwrap_or_default()
+                } else {
+                    r.prompt_hash.clone()
+                },
+                rationale: prediction.map(|p| p.rationale.clone()).unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+fn rfc3339(ts: f64) -> String {
+    DateTime::from_timestamp(ts as i64, 0)
+        .map(|t| t.to_rfc3339())
+        .unwrap_or_default()
+}
+
+/// Renders entries as RFC 4180 CSV with a header row.
+pub fn to_csv(entries: &[JournalEntry]) -> String {
+    let mut csv = format!("{}\n", CSV_HEADER);
+    for e in entries {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            csv_field(&e.run_id),
+            csv_field(&e.source),
+            csv_field(&e.symbol),
+            e.side,
+            e.entry_time,
+            e.entry_price,
+            e.exit_time,
+            e.exit_price,
+            e.quantity,
+            e.fees,
+            e.pnl,
+            e.return_pct,
+            csv_field(&e.prompt_version),
+            csv_field(&e.rationale)
+        );
+    }
+    csv
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::Trade;
+
+    #[test]
+    fn test_journal_entries_and_csv() {
+        let trade = TradeRecord {
+            run_id: "run".to_string(),
+            symbol: "ETH".to_string(),
+            prompt_hash: String::new(),
+            trade: Trade {
+                side: Action::Long,
+                entry_time: 3600.0,
+                entry_price: 100.0,
+                exit_time: 7200.0,
+                exit_price: 110.0,
+                return_pct: 0.1 - 0.002,
+                funding: 0.0,
+            },
+        };
+        let prediction = PredictionRecord {
+            run_id: "run".to_string(),
+            source: "backtest".to_string(),
+            symbol: "ETH".to_string(),
+            model: "o1-mini".to_string(),
+            prompt_hash: "abc".to_string(),
+            window_end: 3600.0,
+            action: Action::Long,
+            label: Some(Action::Long),
+            correct: Some(true),
+            rationale: "higher lows, \"strong\" close".to_string(),
+            key_factors: Vec::new(),
+            invalidation_price: None,
+            target_price: None,
+            confidence: None,
+            level_outcome: None,
+            latency_ms: None,
+            metadata: None,
+            numeric_claims: None,
+        };
+
+        let entries = journal_entries(&[trade], &[prediction], "backtest", 1000.0, 0.001);
+        let e = &entries[0];
+        assert_eq!(e.entry_time, "1970-01-01T01:00:00+00:00");
+        assert_eq!(e.quantity, 10.0);
+        assert!((e.fees - 2.1).abs() < 1e-9);
+        assert!((e.pnl - 98.0).abs() < 1e-9);
+        assert_eq!(e.prompt_version, "abc");
+
+        let csv = to_csv(&entries);
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some(CSV_HEADER));
+        assert!(lines
+            .next()
+            .unwrap()
+            .ends_with(",abc,\"higher lows, \"\"strong\"\" close\""));
+    }
+}
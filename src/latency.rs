@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Distribution of model latencies over a run, in milliseconds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LatencySummary {
+    pub requests: usize,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Collects per-window model latencies. Cache hits are not model requests and should not
+/// be recorded.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+    samples_ms: Vec<f64>,
+}
+
+impl LatencyStats {
+    pub fn record(&mut self, latency: Duration) {
+        self.samples_ms.push(latency.as_secs_f64() * 1e3);
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        let mut sorted = self.samples_ms.clone();
+        sorted.sort_by(f64::total_cmp);
+        let n = sorted.len();
+        if n == 0 {
+            return LatencySummary::default();
+        }
+        // Nearest-rank percentile
+        let pct = |p: f64| sorted[((p * n as f64).ceil() as usize).clamp(1, n) - 1];
+        LatencySummary {
+            requests: n,
+            mean_ms: sorted.iter().sum::<f64>() / n as f64,
+            p50_ms: pct(0.5),
+            p90_ms: pct(0.9),
+            p99_ms: pct(0.99),
+            max_ms: sorted[n - 1],
+        }
+    }
+}
+
+/// Windows scored per second of wall-clock time.
+pub fn throughput(windows: usize, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 {
+        windows as f64 / secs
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_summary() {
+        let mut stats = LatencyStats::default();
+        assert_eq!(stats.summary(), LatencySummary::default());
+
+        for ms in (1..=100).rev() {
+            stats.record(Duration::from_millis(ms));
+        }
+        let summary = stats.summary();
+        assert_eq!(summary.requests, 100);
+        assert!((summary.mean_ms - 50.5).abs() < 1e-9);
+        assert!((summary.p50_ms - 50.0).abs() < 1e-9);
+        assert!((summary.p90_ms - 90.0).abs() < 1e-9);
+        assert!((summary.p99_ms - 99.0).abs() < 1e-9);
+        assert!((summary.max_ms - 100.0).abs() < 1e-9);
+
+        assert_eq!(throughput(30, Duration::from_secs(10)), 3.0);
+        assert_eq!(throughput(30, Duration::ZERO), 0.0);
+    }
+}
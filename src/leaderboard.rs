@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::encoding::CandleEncoding;
+use crate::manifest::RunManifest;
+
+/// Lines added to and removed from the champion prompt to get another one.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PromptDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Line diff of `to` against `from`, by longest common subsequence.
+pub fn diff_lines(from: &str, to: &str) -> PromptDiff {
+    let (a, b) = (
+        from.lines().collect::<Vec<_>>(),
+        to.lines().collect::<Vec<_>>(),
+    );
+    // lcs[i][j]: common subsequence length of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = PromptDiff::default();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.removed.push(a[i].to_string());
+            i += 1;
+        } else {
+            diff.added.push(b[j].to_string());
+            j += 1;
+        }
+    }
+    diff.removed.extend(a[i..].iter().map(|l| l.to_string()));
+    diff.added.extend(b[j..].iter().map(|l| l.to_string()));
+    diff
+}
+
+/// One prompt version's results over every run that tested it with one candle encoding.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LeaderboardEntry {
+    pub rank: usize,
+    pub prompt_version: String,
+    pub encoding: CandleEncoding,
+    pub champion: bool,
+    /// Run ids, most recent first.
+    pub runs: Vec<String>,
+    /// Where each run's report lives, in the order of `runs`; empty when unknown.
+    pub links: Vec<String>,
+    pub windows: usize,
+    /// Accuracy over every scored window of every run.
+    pub accuracy: f64,
+    /// Mean simulated return per run, over runs that recorded one.
+    pub total_return: Option<f64>,
+    /// Mean nominal model cost per window, over runs that recorded one.
+    pub cost_per_window: Option<f64>,
+    /// Changes from the champion prompt; `None` for the champion or when either text is
+    /// unknown.
+    pub diff: Option<PromptDiff>,
+}
+
+/// Ranks every prompt version and encoding in `manifests` by accuracy, then simulated
+/// return, then cost per window. `champion` is the version currently deployed and `champion_prompt`
+/// its text.
+pub fn leaderboard(
+    manifests: &[RunManifest],
+    champion: &str,
+    champion_prompt: Option<&str>,
+) -> Vec<LeaderboardEntry> {
+    let mut by_version: BTreeMap<(&str, &str), Vec<&RunManifest>> = BTreeMap::new();
+    for m in manifests.iter().filter(|m| m.accuracy.is_some()) {
+        let key = (m.prompt_version.as_str(), m.encoding.as_str());
+        by_version.entry(key).or_default().push(m);
+    }
+
+    let mut entries = by_version
+        .into_iter()
+        .map(|((version, _), mut runs)| {
+            runs.sort_by_key(|m| std::cmp::Reverse(m.started_at));
+            let windows = runs.iter().map(|m| m.windows).sum::<usize>();
+            let correct = runs
+                .iter()
+                .map(|m| m.accuracy.unwrap_or(0.0) * m.windows as f64)
+                .sum::<f64>();
+            let cost_windows = runs
+                .iter()
+                .filter(|m| m.cost.is_some())
+                .map(|m| m.windows)
+                .sum::<usize>();
+            let prompt = runs.iter().find_map(|m| m.prompt.as_deref());
+            LeaderboardEntry {
+                rank: 0,
+                prompt_version: version.to_string(),
+                encoding: runs[0].encoding,
+                champion: version == champion,
+                runs: runs.iter().map(|m| m.run_id.clone()).collect(),
+                links: Vec::new(),
+                windows,
+                accuracy: if windows > 0 {
+                    correct / windows as f64
+                } else {
+                    0.0
+                },
+                total_return: mean(runs.iter().filter_map(|m| m.total_return)),
+                cost_per_window: (cost_windows > 0)
+                    .then(|| runs.iter().filter_map(|m| m.cost).sum::<f64>() / cost_windows as f64),
+                diff: match (version == champion, champion_prompt, prompt) {
+                    (false, Some(from), Some(to)) => Some(diff_lines(from, to)),
+                    _ => None,
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+
+    entries.sort_by(|a, b| {
+        b.accuracy
+            .total_cmp(&a.accuracy)
+            .then(
+                b.total_return
+                    .unwrap_or(f64::MIN)
+                    .total_cmp(&a.total_return.unwrap_or(f64::MIN)),
+            )
+            .then(
+                a.cost_per_window
+                    .unwrap_or(f64::MAX)
+                    .total_cmp(&b.cost_per_window.unwrap_or(f64::MAX)),
+            )
+    });
+    for (i, e) in entries.iter_mut().enumerate() {
+        e.rank = i + 1;
+    }
+    entries
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, n) = values.fold((0.0, 0usize), |(s, n), v| (s + v, n + 1));
+    (n > 0).then(|| sum / n as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn run(id: &str, version: &str, windows: usize, accuracy: f64, hour: u32) -> RunManifest {
+        let at = Utc.with_ymd_and_hms(2024, 12, 1, hour, 0, 0).unwrap();
+        RunManifest {
+            run_id: id.to_string(),
+            started_at: at,
+            finished_at: Some(at),
+            model: "o1-mini".to_string(),
+            prompt_version: version.to_string(),
+            symbols: vec!["ETH".to_string()],
+            granularity: 3600,
+            window_candles: 24,
+            data_start: at,
+            data_end: at,
+            windows,
+            accuracy: Some(accuracy),
+            seed: None,
+            stride: 1,
+            sample: None,
+            truncation: None,
+            prompt: Some(format!("Analyze\nversion {}", version)),
+            total_return: Some(accuracy - 0.5),
+            chat: Default::default(),
+            cost: Some(windows as f64),
+            config_hash: None,
+            inputs: None,
+            execution_delay_secs: None,
+            label_thresholds: None,
+            encoding: CandleEncoding::Raw,
+        }
+    }
+
+    #[test]
+    fn test_leaderboard_ranks_and_diffs() {
+        let manifests = [
+            run("r1", "aaa", 10, 0.5, 1),
+            run("r2", "bbb", 10, 0.8, 2),
+            run("r3", "aaa", 30, 0.7, 3),
+            RunManifest {
+                encoding: CandleEncoding::ZScore,
+                ..run("r4", "aaa", 10, 0.1, 4)
+            },
+        ];
+        let board = leaderboard(&manifests, "aaa", Some("Analyze\nversion aaa"));
+        assert_eq!(board[0].prompt_version, "bbb");
+        assert_eq!(board[1].rank, 2);
+        assert_eq!(board[1].runs, vec!["r3", "r1"]);
+        assert!((board[1].accuracy - 0.65).abs() < 1e-9);
+        assert!(board[1].champion && board[1].diff.is_none());
+        assert_eq!(
+            board[0].diff,
+            Some(PromptDiff {
+                added: vec!["version bbb".to_string()],
+                removed: vec!["version aaa".to_string()],
+            })
+        );
+        assert_eq!(board[0].cost_per_window, Some(1.0));
+        // Another encoding of a prompt is ranked on its own
+        assert_eq!(board.len(), 3);
+        assert_eq!(
+            (board[2].runs.as_slice(), board[2].encoding),
+            (&["r4".to_string()][..], CandleEncoding::ZScore)
+        );
+    }
+}
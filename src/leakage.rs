@@ -0,0 +1,99 @@
+use crate::GRANULARITY;
+
+const TIME: usize = 0;
+const CLOSE: usize = 4;
+
+/// Candle rows of every `SYMBOL: [[...]]` line in a prompt, as the prompt shows them.
+pub fn prompt_candles(prompt: &str) -> Vec<(&str, Vec<[f64; 6]>)> {
+    prompt
+        .lines()
+        .filter_map(|line| {
+            let (symbol, rows) = line.split_once(": ")?;
+            if !rows.starts_with("[[") {
+                return None;
+            }
+            Some((symbol, serde_json::from_str(rows).ok()?))
+        })
+        .collect()
+}
+
+/// Candles in `prompt` that open at or after the candle opening at `labeled` closes: the
+/// model would see the very candle its label is computed from. Returned as
+/// `(symbol, open time)`.
+pub fn future_candles(prompt: &str, labeled: f64) -> Vec<(&str, f64)> {
+    let cutoff = labeled + GRANULARITY as f64;
+    prompt_candles(prompt)
+        .into_iter()
+        .flat_map(|(symbol, rows)| {
+            rows.into_iter()
+                .filter(move |c| c[TIME] >= cutoff)
+                .map(move |c| (symbol, c[TIME]))
+        })
+        .collect()
+}
+
+/// Panics when `prompt` shows any candle from after the one opening at `labeled`, which the
+/// window's label belongs to. A windowing bug like that silently inflates accuracy.
+pub fn assert_no_future_candles(prompt: &str, labeled: f64) {
+    let leaks = future_candles(prompt, labeled);
+    assert!(
+        leaks.is_empty(),
+        "prompt for the window labeled at {} shows later candles: {:?}",
+        labeled,
+        leaks
+    );
+}
+
+/// Prices of `next`, the candle that decides the label, that appear in `prompt` as the
+/// prompt formats them. Prices `window` itself contains are skipped, since they show up
+/// legitimately. For tests that build prompts the way a backtest does.
+pub fn future_values(prompt: &str, window: &[[f64; 6]], next: &[f64; 6]) -> Vec<f64> {
+    let format = |v: f64| format!("{:.2}", v);
+    let known = window
+        .iter()
+        .flat_map(|c| c[1..=CLOSE].iter().map(|&v| format(v)))
+        .collect::<Vec<_>>();
+    next[1..=CLOSE]
+        .iter()
+        .copied()
+        .filter(|&v| {
+            let shown = format(v);
+            !known.contains(&shown) && prompt.contains(&shown)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute::assemble_prompt;
+
+    fn candle(hour: usize, close: f64) -> [f64; 6] {
+        let t = (hour * GRANULARITY as usize) as f64;
+        [t, close - 1.0, close + 2.0, close - 3.0, close, 10.0]
+    }
+
+    #[test]
+    fn test_backtest_windows_do_not_leak() {
+        let candles = (0..30)
+            .map(|h| candle(h, 1000.0 + 7.0 * h as f64))
+            .collect::<Vec<_>>();
+        // Sliced the way the backtest slices: the window ends at the labeled candle
+        let i = 24;
+        let window = &candles[i - 24..i];
+        let prompt = assemble_prompt("base", &[("ETH", window, &[]), ("BTC", window, &[])]);
+        assert_eq!(prompt_candles(&prompt).len(), 2);
+        assert!(future_candles(&prompt, candles[i - 1][TIME]).is_empty());
+        assert!(future_values(&prompt, window, &candles[i]).is_empty());
+        assert_no_future_candles(&prompt, candles[i - 1][TIME]);
+
+        // One candle too far
+        let leaky_window = &candles[i - 23..=i];
+        let leaky = assemble_prompt("base", &[("ETH", leaky_window, &[])]);
+        assert_eq!(
+            future_candles(&leaky, candles[i - 1][TIME]),
+            [("ETH", candles[i][TIME])]
+        );
+        assert!(!future_values(&leaky, window, &candles[i]).is_empty());
+    }
+}
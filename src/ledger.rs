@@ -0,0 +1,413 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+#[cfg(feature = "native")]
+use anyhow::Result;
+use chrono::{DateTime, Datelike};
+#[cfg(feature = "native")]
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::simulator::Trade;
+use crate::Action;
+
+const TAX_LOT_HEADER: &str =
+    "symbol,side,quantity,opened,closed,currency,cost_basis,proceeds,gain,funding_paid";
+/// Currency lots are denominated in unless they say otherwise.
+pub const DEFAULT_CURRENCY: &str = "USD";
+
+/// One position of the paper ledger, opened by a single fill and closed, if it has been, by
+/// another. A long buys its lot; a short sells a borrowed one and buys it back.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Lot {
+    pub symbol: String,
+    pub side: Action,
+    /// Currency the lot's prices, fees and PnL are in: the quote currency of the pair, or
+    /// the coin of a coin-margined position.
+    pub currency: String,
+    /// Units bought or sold.
+    pub quantity: f64,
+    /// Time of the opening fill, in seconds.
+    pub opened_at: f64,
+    pub open_price: f64,
+    /// Fee paid on the opening fill.
+    pub open_fee: f64,
+    /// Time of the closing fill, `None` while the lot is open.
+    pub closed_at: Option<f64>,
+    pub close_price: Option<f64>,
+    pub close_fee: f64,
+    /// Perpetual funding paid while held; negative when received.
+    pub funding_paid: f64,
+    /// Latest price an open lot is valued at.
+    pub mark_price: Option<f64>,
+}
+
+impl Lot {
+    /// The closed lot of a paper `trade` sized to `notional`, simulated with `fee`, with
+    /// the funding the trade accrued.
+    pub fn closed(symbol: &str, trade: &Trade, notional: f64, fee: f64) -> Self {
+        let quantity = notional / trade.entry_price;
+        Self {
+            symbol: symbol.to_string(),
+            side: trade.side,
+            currency: DEFAULT_CURRENCY.to_string(),
+            quantity,
+            opened_at: trade.entry_time,
+            open_price: trade.entry_price,
+            open_fee: notional * fee,
+            closed_at: Some(trade.exit_time),
+            close_price: Some(trade.exit_price),
+            close_fee: quantity * trade.exit_price * fee,
+            funding_paid: notional * trade.funding,
+            mark_price: None,
+        }
+    }
+
+    /// A lot opened at `open_price` and still held, valued at `mark_price`.
+    pub fn open(
+        symbol: &str,
+        side: Action,
+        opened_at: f64,
+        open_price: f64,
+        mark_price: f64,
+        notional: f64,
+        fee: f64,
+    ) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            side,
+            currency: DEFAULT_CURRENCY.to_string(),
+            quantity: notional / open_price,
+            opened_at,
+            open_price,
+            open_fee: notional * fee,
+            closed_at: None,
+            close_price: None,
+            close_fee: 0.0,
+            funding_paid: 0.0,
+            mark_price: Some(mark_price),
+        }
+    }
+
+    /// The lot with its amounts in `currency` rather than [`DEFAULT_CURRENCY`].
+    pub fn in_currency(mut self, currency: &str) -> Self {
+        self.currency = currency.to_string();
+        self
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.closed_at.is_none()
+    }
+
+    /// What acquiring the lot cost, fees included: the purchase of a long, the buy-back
+    /// of a short. `None` while a short is open.
+    pub fn cost_basis(&self) -> Option<f64> {
+        match self.side {
+            Action::Short => Some(self.quantity * self.close_price? + self.close_fee),
+            _ => Some(self.quantity * self.open_price + self.open_fee),
+        }
+    }
+
+    /// What disposing of the lot brought in, net of fees: the sale of a long, the short
+    /// sale of a short. `None` while a long is open.
+    pub fn proceeds(&self) -> Option<f64> {
+        match self.side {
+            Action::Short => Some(self.quantity * self.open_price - self.open_fee),
+            _ => Some(self.quantity * self.close_price? - self.close_fee),
+        }
+    }
+
+    /// Gain of a closed lot, net of fees and funding.
+    pub fn realized_pnl(&self) -> Option<f64> {
+        self.closed_at?;
+        Some(self.proceeds()? - self.cost_basis()? - self.funding_paid)
+    }
+
+    /// Gain of an open lot if it were closed at its mark, before the closing fee.
+    pub fn unrealized_pnl(&self) -> Option<f64> {
+        let mark = self.mark_price.filter(|_| self.is_open())?;
+        Some((mark - self.open_price) * self.quantity * direction(self.side) - self.open_fee)
+    }
+}
+
+fn direction(side: Action) -> f64 {
+    match side {
+        Action::Short => -1.0,
+        _ => 1.0,
+    }
+}
+
+/// What one unit of each currency is worth in a reporting currency.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Rates {
+    pub reporting: String,
+    prices: BTreeMap<String, f64>,
+}
+
+impl Rates {
+    pub fn new(reporting: &str) -> Self {
+        Self {
+            reporting: reporting.to_string(),
+            prices: BTreeMap::from([(reporting.to_string(), 1.0)]),
+        }
+    }
+
+    /// These rates with one unit of `currency` worth `price` of the reporting currency.
+    pub fn with(mut self, currency: &str, price: f64) -> Self {
+        self.prices.insert(currency.to_string(), price);
+        self
+    }
+
+    /// `amount` of `currency` in the reporting currency; `None` without a rate for it.
+    pub fn convert(&self, amount: f64, currency: &str) -> Option<f64> {
+        Some(amount * self.prices.get(currency)?)
+    }
+}
+
+/// Balances of a paper or live account by currency, in fiat, stablecoins or coins.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Account {
+    pub balances: BTreeMap<String, f64>,
+}
+
+impl Account {
+    pub fn deposit(&mut self, currency: &str, amount: f64) {
+        *self.balances.entry(currency.to_string()).or_default() += amount;
+    }
+
+    /// Books the realized PnL of every closed lot in the lot's currency.
+    pub fn settle(&mut self, lots: &[Lot]) {
+        for lot in lots {
+            if let Some(pnl) = lot.realized_pnl() {
+                self.deposit(&lot.currency, pnl);
+            }
+        }
+    }
+
+    /// The account's worth in the reporting currency of `rates`, and the currencies left
+    /// out for want of a rate.
+    pub fn value(&self, rates: &Rates) -> (f64, Vec<String>) {
+        let mut missing = Vec::new();
+        let mut value = 0.0;
+        for (currency, &amount) in &self.balances {
+            match rates.convert(amount, currency) {
+                Some(converted) => value += converted,
+                None => missing.push(currency.clone()),
+            }
+        }
+        (value, missing)
+    }
+}
+
+/// Realized and unrealized PnL over a ledger's lots, in the reporting currency.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LedgerSummary {
+    pub currency: String,
+    pub closed_lots: usize,
+    pub open_lots: usize,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+    pub funding_paid: f64,
+    /// Currencies of lots left out of the totals for want of a rate.
+    pub unconverted: Vec<String>,
+}
+
+/// Totals of `lots`, each converted to the reporting currency of `rates`.
+pub fn summarize_ledger(lots: &[Lot], rates: &Rates) -> LedgerSummary {
+    let mut summary = LedgerSummary {
+        currency: rates.reporting.clone(),
+        ..Default::default()
+    };
+    for lot in lots {
+        if lot.is_open() {
+            summary.open_lots += 1;
+        } else {
+            summary.closed_lots += 1;
+        }
+        let convert = |amount: f64| rates.convert(amount, &lot.currency);
+        let Some(funding) = convert(lot.funding_paid) else {
+            if !summary.unconverted.contains(&lot.currency) {
+                summary.unconverted.push(lot.currency.clone());
+            }
+            continue;
+        };
+        summary.realized_pnl += lot.realized_pnl().and_then(convert).unwrap_or(0.0);
+        summary.unrealized_pnl += lot.unrealized_pnl().and_then(convert).unwrap_or(0.0);
+        summary.funding_paid += funding;
+    }
+    summary
+}
+
+/// Reporting-currency rates of `currencies` at the current Coinbase spot prices. A currency
+/// Coinbase has no usable rate for is left out with a warning, so what it holds is
+/// reported as unconverted rather than failing the rest.
+#[cfg(feature = "native")]
+pub async fn fetch_rates(reporting: &str, currencies: &[String]) -> Result<Rates> {
+    #[derive(Deserialize)]
+    struct ExchangeRates {
+        data: ExchangeRatesData,
+    }
+    #[derive(Deserialize)]
+    struct ExchangeRatesData {
+        /// Units of each currency one unit of the requested currency buys.
+        rates: BTreeMap<String, String>,
+    }
+
+    let response: ExchangeRates = reqwest::Client::new()
+        .get("https://api.coinbase.com/v2/exchange-rates")
+        .query(&[("currency", reporting)])
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let mut rates = Rates::new(reporting);
+    for currency in currencies.iter().filter(|c| *c != reporting) {
+        let per_unit = response
+            .data
+            .rates
+            .get(currency)
+            .and_then(|rate| rate.parse::<f64>().ok())
+            .filter(|&rate| rate > 0.0);
+        match per_unit {
+            Some(per_unit) => rates = rates.with(currency, 1.0 / per_unit),
+            None => tracing::warn!(%currency, %reporting, "No usable exchange rate"),
+        }
+    }
+    Ok(rates)
+}
+
+fn year_of(ts: f64) -> Option<i32> {
+    DateTime::from_timestamp(ts as i64, 0).map(|t| t.year())
+}
+
+fn date(ts: f64) -> String {
+    DateTime::from_timestamp(ts as i64, 0)
+        .map(|t| t.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+/// The lots closed during `year` (UTC) as CSV, one row per lot with its cost basis,
+/// proceeds and gain in the lot's currency, for a year-end tax report.
+pub fn tax_lots_csv(lots: &[Lot], year: i32) -> String {
+    let mut csv = format!("{}\n", TAX_LOT_HEADER);
+    let closed = lots
+        .iter()
+        .filter(|lot| lot.closed_at.and_then(year_of) == Some(year));
+    for lot in closed {
+        let (Some(basis), Some(proceeds), Some(closed_at)) =
+            (lot.cost_basis(), lot.proceeds(), lot.closed_at)
+        else {
+            continue;
+        };
+        let _ = writeln!(
+            csv,
+            "{},{},{:.8},{},{},{},{:.2},{:.2},{:.2},{:.2}",
+            lot.symbol,
+            if lot.side == Action::Short {
+                "short"
+            } else {
+                "long"
+            },
+            lot.quantity,
+            date(lot.opened_at),
+            date(closed_at),
+            lot.currency,
+            basis,
+            proceeds,
+            proceeds - basis,
+            lot.funding_paid
+        );
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lots_and_tax_export() {
+        let day = 86400.0;
+        let long = Trade {
+            side: Action::Long,
+            entry_time: 0.0,
+            entry_price: 100.0,
+            exit_time: 3600.0,
+            exit_price: 110.0,
+            // 10% up, 0.1% fees a side, 0.05% funding
+            return_pct: 0.1 - 0.002 - 0.0005,
+            funding: 0.0005,
+        };
+        let short = Trade {
+            side: Action::Short,
+            entry_time: 400.0 * day,
+            entry_price: 100.0,
+            exit_time: 400.0 * day + 3600.0,
+            exit_price: 95.0,
+            return_pct: 0.05 - 0.002,
+            funding: 0.0,
+        };
+        let lots = [
+            Lot::closed("ETH", &long, 1000.0, 0.001),
+            Lot::closed("ETH", &short, 1000.0, 0.001),
+            Lot::open(
+                "ETH",
+                Action::Long,
+                401.0 * day,
+                100.0,
+                102.0,
+                1000.0,
+                0.001,
+            ),
+        ];
+        assert_eq!(lots[0].cost_basis(), Some(1001.0));
+        assert!((lots[0].proceeds().unwrap() - 1098.9).abs() < 1e-9);
+        assert!((lots[0].funding_paid - 0.5).abs() < 1e-9);
+        // The closing fee is on the exit notional: 1.1 rather than the 1.0 the return assumed
+        assert!((lots[0].realized_pnl().unwrap() - 97.4).abs() < 1e-9);
+        // A short's basis is the buy-back: 10 units at 95 plus the fee on it
+        assert!((lots[1].cost_basis().unwrap() - 950.95).abs() < 1e-9);
+        assert_eq!(lots[1].proceeds(), Some(999.0));
+        assert_eq!(lots[2].realized_pnl(), None);
+        assert!((lots[2].unrealized_pnl().unwrap() - 19.0).abs() < 1e-9);
+
+        let rates = Rates::new("USD");
+        let summary = summarize_ledger(&lots, &rates);
+        assert_eq!((summary.closed_lots, summary.open_lots), (2, 1));
+        assert!((summary.unrealized_pnl - 19.0).abs() < 1e-9);
+
+        // A BTC-quoted lot converts at the BTC rate, or is left out without one
+        let mut mixed = lots.clone();
+        mixed[0] = mixed[0].clone().in_currency("BTC");
+        let summary = summarize_ledger(&mixed, &rates);
+        assert_eq!(summary.unconverted, ["BTC"]);
+        let rates = rates.with("BTC", 50_000.0).with("USDC", 1.0);
+        let summary = summarize_ledger(&mixed, &rates);
+        let short_pnl = lots[1].realized_pnl().unwrap();
+        assert!((summary.realized_pnl - (97.4 * 50_000.0 + short_pnl)).abs() < 1e-6);
+
+        let mut account = Account::default();
+        account.deposit("USDC", 1000.0);
+        account.settle(&mixed);
+        assert!((account.balances["BTC"] - 97.4).abs() < 1e-9);
+        let (value, unpriced) = account.value(&rates);
+        assert!((value - (1000.0 + 97.4 * 50_000.0 + short_pnl)).abs() < 1e-6);
+        assert!(unpriced.is_empty());
+        account.deposit("SOL", 1.0);
+        assert_eq!(account.value(&rates).1, ["SOL"]);
+
+        let csv = tax_lots_csv(&lots, 1970);
+        let mut rows = csv.lines();
+        assert_eq!(rows.next(), Some(TAX_LOT_HEADER));
+        assert_eq!(
+            rows.next(),
+            Some("ETH,long,10.00000000,1970-01-01,1970-01-01,USD,1001.00,1098.90,97.90,0.50")
+        );
+        assert_eq!(rows.next(), None);
+        assert_eq!(tax_lots_csv(&lots, 1971).lines().count(), 2);
+        let usdc = tax_lots_csv(&[lots[0].clone().in_currency("USDC")], 1970);
+        assert!(usdc.contains(",1970-01-01,USDC,1001.00,"));
+    }
+}
@@ -1,19 +1,136 @@
+// Modules without a feature gate are pure computation (no IO) and also build for wasm32.
+#[cfg(feature = "native")]
+pub mod analytics;
+#[cfg(feature = "arrow")]
+pub mod arrow_io;
+#[cfg(feature = "native")]
+pub mod artifacts;
+pub mod attribution;
+pub mod audit;
+pub mod auth;
+#[cfg(feature = "native")]
 pub mod backtest;
+pub mod baseline;
+pub mod broker;
+pub mod calendar;
+pub mod calibration;
+pub mod candidates;
+pub mod challenger;
+pub mod chart;
+pub mod comparison;
+#[cfg(feature = "native")]
+pub mod compression;
+pub mod compute;
+pub mod confusion;
+#[cfg(feature = "native")]
+pub mod daemon;
+#[cfg(feature = "native")]
+pub mod dashboard;
+pub mod data_quality;
+pub mod dedup;
+pub mod derisk;
+pub mod dev;
+pub mod drift;
+pub mod encoding;
+pub mod exposure;
+pub mod faults;
+pub mod features;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod funding;
+pub mod granularity;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod hallucination;
+#[cfg(feature = "native")]
+pub mod health;
+pub mod implied_vol;
+#[cfg(feature = "native")]
+pub mod improvement;
+pub mod indicators;
+pub mod input_drift;
+#[cfg(feature = "native")]
+pub mod jobs;
+#[cfg(feature = "native")]
+pub mod journal;
+pub mod latency;
+pub mod leaderboard;
+pub mod leakage;
+pub mod ledger;
+pub mod lint;
+pub mod liquidations;
+#[cfg(feature = "native")]
+mod live;
+#[cfg(feature = "native")]
+pub mod llm_cache;
+#[cfg(feature = "native")]
+mod llm_queue;
+pub mod manifest;
+pub mod microstructure;
+#[cfg(feature = "native")]
+pub mod out_of_sample;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod postmortem;
+pub mod prediction;
+pub mod profiles;
 pub mod prompt_builder;
+pub mod prompt_pnl;
+pub mod rationale;
+pub mod reconcile;
+#[cfg(feature = "native")]
+pub mod repl;
+#[cfg(feature = "native")]
+pub mod reporting;
+pub mod rescore;
+pub mod research_export;
+pub mod routing;
+pub mod sampling;
+pub mod scenarios;
+#[cfg(feature = "native")]
+pub mod secrets;
+pub mod sessions;
+pub mod signal_filter;
+pub mod simulator;
+pub mod snapshot;
+pub mod sse;
+#[cfg(feature = "native")]
+pub mod storage;
+#[cfg(feature = "native")]
+pub mod store;
+pub mod streaming;
+pub mod subscriptions;
+pub mod sweep;
+pub mod thresholds;
+pub mod truncation;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "native")]
+pub mod workspace;
 
-use std::{env, fs};
-
-use anyhow::{Context as _, Result};
-use chrono::{DateTime, Duration, Utc};
-use prompt_builder::build_data_section;
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
-
-// Profit threshold multipliers
-pub const LONG_THRESHOLD: f64 = 1.05;
-pub const SHORT_THRESHOLD: f64 = 0.95;
 
-#[derive(Debug, Clone, Copy)]
+pub use compute::{
+    assemble_allocation_prompt, assemble_market_prompt, assemble_multi_asset_prompt,
+    assemble_pair_prompt, assemble_prompt, assemble_target_prompt, label_candles, label_pair,
+    prepare_candles, prompt_version, window_anomaly_notes, Action, AnnotatedWindow, ANOMALY_POLICY,
+    LONG_THRESHOLD, RESPONSE_STRICTNESS, SHORT_THRESHOLD,
+};
+#[cfg(feature = "native")]
+pub use live::{
+    analyze_data_gpt, describe_prompt_cost, preview_prompt, run_live_analysis,
+    run_live_multi_asset_analysis, run_symbol_analysis, run_symbol_analysis_at, token_usage,
+    CallKind, SymbolSignal, TokenUsage,
+};
+
+// Candle granularity in seconds (hourly)
+pub const GRANULARITY: u32 = 3600;
+/// Candles in each symbol's prompt window: a day of hourly candles.
+pub const CANDLE_HOURS: usize = 24;
+/// The champion prompt, which backtests, improvement and live analysis read by default.
+pub const PROMPT_FILE: &str = "prompt.txt";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Model {
     O1Preview,
     O1Mini,
@@ -26,14 +143,139 @@ impl Model {
             Model::O1Mini => "o1-mini",
         }
     }
+
+    /// Approximate price of one request relative to o1-mini, from the list prices per
+    /// token (o1-preview is five times o1-mini for both input and output).
+    pub fn relative_cost(&self) -> f64 {
+        match self {
+            Model::O1Preview => 5.0,
+            Model::O1Mini => 1.0,
+        }
+    }
+
+    /// List price in dollars per million prompt tokens.
+    pub fn input_price_per_mtok(&self) -> f64 {
+        match self {
+            Model::O1Preview => 15.0,
+            Model::O1Mini => 3.0,
+        }
+    }
+
+    /// Prompt tokens that fit alongside the model's output allowance in its 128k context.
+    pub fn prompt_token_budget(&self) -> usize {
+        128_000 - self.max_completion_tokens() as usize
+    }
+
+    /// Output allowance, reasoning included, sent as `max_completion_tokens` unless a call
+    /// sets its own.
+    pub fn max_completion_tokens(&self) -> u32 {
+        match self {
+            Model::O1Preview => 32_768,
+            Model::O1Mini => 65_536,
+        }
+    }
+
+    /// List price in dollars per million completion tokens, reasoning included.
+    pub fn output_price_per_mtok(&self) -> f64 {
+        match self {
+            Model::O1Preview => 60.0,
+            Model::O1Mini => 12.0,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+impl std::str::FromStr for Model {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "o1-preview" => Ok(Model::O1Preview),
+            "o1-mini" => Ok(Model::O1Mini),
+            _ => anyhow::bail!("Unknown model {:?} (expected o1-mini or o1-preview)", s),
+        }
+    }
+}
+
+/// How much hidden reasoning an o-series model does before it answers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
-pub enum Action {
-    Long,
-    Short,
-    None,
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+impl ReasoningEffort {
+    pub const ALL: [ReasoningEffort; 3] = [
+        ReasoningEffort::Low,
+        ReasoningEffort::Medium,
+        ReasoningEffort::High,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReasoningEffort::Low => "low",
+            ReasoningEffort::Medium => "medium",
+            ReasoningEffort::High => "high",
+        }
+    }
+}
+
+impl std::str::FromStr for ReasoningEffort {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ReasoningEffort::ALL
+            .into_iter()
+            .find(|e| e.as_str() == s)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown reasoning effort {:?} (expected low, medium or high)",
+                    s
+                )
+            })
+    }
+}
+
+/// Settings of one chat completion request beyond the model and prompt. Unset fields are
+/// left to the model's defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatOptions {
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// Overrides [`Model::max_completion_tokens`].
+    pub max_completion_tokens: Option<u32>,
+}
+
+impl ChatOptions {
+    /// Options for live analysis, from `REASONING_EFFORT` and `MAX_COMPLETION_TOKENS`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+        Ok(Self {
+            reasoning_effort: var("REASONING_EFFORT").map(|v| v.parse()).transpose()?,
+            max_completion_tokens: var("MAX_COMPLETION_TOKENS")
+                .map(|v| v.parse())
+                .transpose()
+                .map_err(|err| anyhow::anyhow!("Invalid MAX_COMPLETION_TOKENS: {}", err))?,
+        })
+    }
+
+    /// The prompt as the LLM cache should key it: answers given with different settings
+    /// are cached apart, and default settings keep the keys of existing entries.
+    pub fn cache_key<'a>(&self, prompt: &'a str) -> std::borrow::Cow<'a, str> {
+        if *self == Self::default() {
+            return prompt.into();
+        }
+        let effort = self.reasoning_effort.map_or("", |e| e.as_str());
+        let max_tokens = self
+            .max_completion_tokens
+            .map(|t| t.to_string())
+            .unwrap_or_default();
+        format!(
+            "{}\0reasoning_effort={};max_completion_tokens={}",
+            prompt, effort, max_tokens
+        )
+        .into()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
@@ -47,195 +289,20 @@ pub struct CoinbaseCandle(
 );
 
 pub fn candles_to_array(candles: Vec<CoinbaseCandle>) -> Vec<[f64; 6]> {
-    // Coinbase returns candles most recent first, so reverse to chronological
-    let mut candles = candles;
-    candles.reverse();
-
-    candles
+    let mut candles = candles
         .into_iter()
         .map(|c| {
             let CoinbaseCandle(time, low, high, open, close, volume) = c;
             [time, open, high, low, close, volume]
         })
-        .collect()
-}
-
-async fn get_candle_data(
-    symbol: &str,
-    start: DateTime<Utc>,
-    end: DateTime<Utc>,
-) -> Result<Vec<CoinbaseCandle>> {
-    let client = reqwest::Client::new();
-    // let end = Utc::now();
-    // let start = end - chrono::Duration::hours(24);
-
-    let url = format!(
-        "https://api.exchange.coinbase.com/products/{symbol}-USD/candles\
-        ?start={}\
-        &end={}\
-        &granularity=3600",
-        start.timestamp(),
-        end.timestamp()
-    );
-
-    let response = client
-        .get(&url)
-        .header("User-Agent", "Mozilla/5.0")
-        .send()
-        .await?;
-
-    let data: Vec<CoinbaseCandle> = response.json().await?;
-    Ok(data)
-}
-
-pub async fn analyze_data_gpt(prompt: &str, model: Model) -> Result<String> {
-    let api_key =
-        env::var("OPENAI_API_KEY").context("OPENAI_API_KEY environment variable is not set")?;
-
-    let body = json!({
-        "model": model.as_str(),
-        "messages": [
-            {
-                "role": "user",
-                "content": prompt
-            }
-        ]
-    });
-
-    let client = reqwest::Client::new();
-    tracing::debug!(
-        ?model,
-        prompt_len = prompt.len(),
-        "Sending request to OpenAI API"
-    );
-
-    let resp = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .timeout(std::time::Duration::from_secs(300))
-        .json(&body)
-        .send()
-        .await
-        .context("Failed to send request to OpenAI API")?;
-
-    // Check if the response is successful
-    let status = resp.status();
-    if !status.is_success() {
-        let text = resp.text().await.unwrap_or_default();
-        tracing::error!("OpenAI API returned error: {} - {}", status, text);
-        anyhow::bail!("OpenAI API error: {} - {}", status, text);
-    }
-
-    let val: Value = resp
-        .json()
-        .await
-        .context("Failed to parse OpenAI API response as JSON")?;
-
-    tracing::debug!("Full OpenAI API response: {}", val);
-
-    // Extract the "content" field from the first choice
-    let content = val["choices"]
-        .get(0)
-        .and_then(|choice| choice["message"]["content"].as_str())
-        .map(str::to_string)
-        .ok_or_else(|| {
-            anyhow::anyhow!(
-                "Could not find 'content' field in the API response: {}",
-                val
-            )
-        })?;
-
-    Ok(content)
-}
-
-pub fn label_candles(data: &[[f64; 6]]) -> Vec<Action> {
-    use Action::*;
-    // For convenience, define indexes into the candle array
-    const HIGH: usize = 2;
-    const LOW: usize = 3;
-    const CLOSE: usize = 4;
-
-    let mut labels = data
-        .windows(2)
-        .map(|w| {
-            let current = &w[0];
-            let next = &w[1];
-
-            let c_close = current[CLOSE];
-            let next_high = next[HIGH];
-            let next_low = next[LOW];
-
-            let long_cond = next_high >= c_close * LONG_THRESHOLD;
-            let short_cond = next_low <= c_close * SHORT_THRESHOLD;
-
-            match (long_cond, short_cond) {
-                (true, true) => Short, // tie-break: choose "short"
-                (true, false) => Long,
-                (false, true) => Short,
-                (false, false) => None,
-            }
-        })
-        .collect::<Vec<_>>();
-
-    // The last candle has no future candle, so "none"
-    labels.push(None);
-
-    labels
-}
-
-const CANDLE_HOURS: usize = 24; // 24-hour window
-const PROMPT_FILE: &str = "prompt.txt";
-
-pub async fn run_live_analysis() -> Result<(Action, String)> {
-    // We'll fetch data for the last N hours
-    let end = Utc::now();
-    let start = end - Duration::hours(CANDLE_HOURS as i64);
-
-    // Fetch live data directly from the API (no caching)
-    let eth_candles = candles_to_array(get_candle_data("ETH", start, end).await?);
-    let btc_candles = candles_to_array(get_candle_data("BTC", start, end).await?);
-    let sol_candles = candles_to_array(get_candle_data("SOL", start, end).await?);
-
-    if eth_candles.len() < CANDLE_HOURS
-        || btc_candles.len() < CANDLE_HOURS
-        || sol_candles.len() < CANDLE_HOURS
-    {
-        anyhow::bail!("Not enough recent data to perform live analysis");
+        .collect();
+    // Coinbase returns candles most recent first, but input that is already chronological
+    // must not be flipped, so sort rather than reverse
+    let repair = data_quality::repair_order(&mut candles);
+    if repair.duplicates > 0 {
+        tracing::warn!(?repair, "Dropped repeated candle timestamps");
     }
-
-    let base_prompt = fs::read_to_string(PROMPT_FILE).context("Failed to read base prompt file")?;
-
-    let eth_window = &eth_candles[eth_candles.len() - CANDLE_HOURS..];
-    let btc_window = &btc_candles[btc_candles.len() - CANDLE_HOURS..];
-    let sol_window = &sol_candles[sol_candles.len() - CANDLE_HOURS..];
-
-    let data_section = build_data_section(eth_window, btc_window, sol_window);
-    let full_prompt = format!("{}\n\n{}", base_prompt, data_section);
-
-    let response = analyze_data_gpt(&full_prompt, Model::O1Mini).await?;
-    let clean_response = response.replace("```json", "").replace("```", "");
-
-    let val: Value = serde_json::from_str(&clean_response)
-        .with_context(|| format!("Response not valid JSON: {}", clean_response))?;
-    let action_str = val
-        .get("action")
-        .and_then(|a| a.as_str())
-        .context("Missing 'action' field in response")?;
-    let rationale = val
-        .get("rationale")
-        .and_then(|r| r.as_str())
-        .unwrap_or("")
-        .to_string();
-
-    let pred = match action_str {
-        "long" => Action::Long,
-        "short" => Action::Short,
-        "none" => Action::None,
-        _ => Action::None,
-    };
-
-    Ok((pred, rationale))
+    candles
 }
 
 #[cfg(test)]
@@ -368,4 +435,15 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_label_pair() {
+        let candle = |close: f64| [0.0, 0.0, 0.0, 0.0, close, 1.0];
+        let eth = [candle(100.0), candle(101.0), candle(101.0), candle(100.0)];
+        let btc = [candle(50.0), candle(50.0), candle(50.0), candle(49.9)];
+        assert_eq!(
+            label_pair(&eth, &btc),
+            vec![Action::Long, Action::None, Action::Short, Action::None]
+        );
+    }
 }
@@ -0,0 +1,147 @@
+use std::fmt;
+
+use crate::truncation::estimate_tokens;
+
+/// Longest base prompt accepted, in estimated tokens; the candle data still has to fit.
+pub const MAX_PROMPT_TOKENS: usize = 4_000;
+/// Response fields the prompt must ask for.
+pub const REQUIRED_FIELDS: [&str; 2] = ["action", "rationale"];
+/// Phrases from the improvement request's failure list that have no place in a prompt.
+const LEAKED_PHRASES: [&str; 2] = ["correct action was", "model predicted"];
+
+/// Something wrong with a candidate base prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LintViolation {
+    Empty,
+    CodeFence,
+    /// No instruction to answer in JSON.
+    NoJsonInstruction,
+    MissingField(&'static str),
+    TooLong {
+        tokens: usize,
+    },
+    /// Label information: the labeling thresholds, or failure examples copied from the
+    /// improvement request.
+    LabelLeak(String),
+}
+
+impl fmt::Display for LintViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintViolation::Empty => write!(f, "the prompt is empty"),
+            LintViolation::CodeFence => write!(f, "the prompt contains a code fence (```)"),
+            LintViolation::NoJsonInstruction => {
+                write!(f, "the prompt no longer asks for a JSON response")
+            }
+            LintViolation::MissingField(field) => {
+                write!(f, "the prompt no longer asks for the \"{}\" field", field)
+            }
+            LintViolation::TooLong { tokens } => write!(
+                f,
+                "the prompt is about {} tokens, over the {} token budget",
+                tokens, MAX_PROMPT_TOKENS
+            ),
+            LintViolation::LabelLeak(what) => {
+                write!(f, "the prompt leaks label information: {}", what)
+            }
+        }
+    }
+}
+
+/// Every violation in a candidate base prompt for a symbol labeled with the long and short
+/// `thresholds`; empty when it is fit to save.
+pub fn lint_prompt(prompt: &str, (long, short): (f64, f64)) -> Vec<LintViolation> {
+    if prompt.trim().is_empty() {
+        return vec![LintViolation::Empty];
+    }
+    let mut violations = Vec::new();
+    let lower = prompt.to_lowercase();
+    if prompt.contains("```") {
+        violations.push(LintViolation::CodeFence);
+    }
+    if !lower.contains("json") {
+        violations.push(LintViolation::NoJsonInstruction);
+    }
+    for field in REQUIRED_FIELDS {
+        if !lower.contains(&format!("\"{}\"", field)) {
+            violations.push(LintViolation::MissingField(field));
+        }
+    }
+    let tokens = estimate_tokens(prompt);
+    if tokens > MAX_PROMPT_TOKENS {
+        violations.push(LintViolation::TooLong { tokens });
+    }
+    for phrase in LEAKED_PHRASES {
+        if lower.contains(phrase) {
+            violations.push(LintViolation::LabelLeak(format!("\"{}\"", phrase)));
+        }
+    }
+    for threshold in [long, short] {
+        if uses_multiplier(prompt, &threshold.to_string()) {
+            violations.push(LintViolation::LabelLeak(format!(
+                "the labeling multiplier {}",
+                threshold
+            )));
+        }
+    }
+    violations
+}
+
+/// Whether `number` appears as a multiplier: after `×` or `*`, allowing markdown bold.
+fn uses_multiplier(prompt: &str, number: &str) -> bool {
+    prompt.match_indices(number).any(|(i, _)| {
+        let longer = prompt[i + number.len()..].starts_with(|c: char| c.is_ascii_digit());
+        let before = prompt[..i].trim_end();
+        let operator = before.strip_suffix("**").unwrap_or(before).trim_end();
+        !longer && operator.ends_with(['×', '*'])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute::{LONG_THRESHOLD, SHORT_THRESHOLD};
+
+    #[test]
+    fn test_lint_prompt() {
+        const DEFAULTS: (f64, f64) = (LONG_THRESHOLD, SHORT_THRESHOLD);
+        let good =
+            "Decide the ETH action. Return a JSON object with \"action\" and \"rationale\". \
+                    Period 2 Close >= Period 1 Close × **1.003**.";
+        assert_eq!(lint_prompt(good, DEFAULTS), []);
+        // The same multiplier leaks the label of a symbol calibrated to it
+        assert_eq!(
+            lint_prompt(good, (1.003, 0.997)),
+            [LintViolation::LabelLeak(
+                "the labeling multiplier 1.003".into()
+            )]
+        );
+        assert_eq!(lint_prompt(" \n", DEFAULTS), [LintViolation::Empty]);
+
+        let bad = "```\nReturn \"action\" only. Window 3: Model predicted Long, but the correct \
+                   action was None. Long when next High ≥ Close × **1.05**.\n```";
+        let violations = lint_prompt(bad, DEFAULTS);
+        assert_eq!(
+            violations[..3],
+            [
+                LintViolation::CodeFence,
+                LintViolation::NoJsonInstruction,
+                LintViolation::MissingField("rationale"),
+            ]
+        );
+        assert_eq!(
+            violations[3..]
+                .iter()
+                .filter(|v| matches!(v, LintViolation::LabelLeak(_)))
+                .count(),
+            3
+        );
+        assert!(violations[3].to_string().contains("correct action was"));
+
+        let long = format!("{} {}", good, "x".repeat(MAX_PROMPT_TOKENS * 4));
+        assert!(matches!(
+            lint_prompt(&long, DEFAULTS)[..],
+            [LintViolation::TooLong { .. }]
+        ));
+    }
+}
@@ -0,0 +1,229 @@
+#[cfg(feature = "native")]
+use std::path::PathBuf;
+
+#[cfg(feature = "native")]
+use anyhow::{Context, Result};
+#[cfg(feature = "native")]
+use chrono::{DateTime, Duration, Utc};
+#[cfg(feature = "native")]
+use serde::Deserialize;
+
+#[cfg(feature = "native")]
+use crate::secrets;
+use crate::snapshot::LiquidationSummary;
+#[cfg(feature = "native")]
+use crate::store::RowStore;
+
+/// One hour of forced closures across exchanges, `[time, long_usd, short_usd]`, with the
+/// hour's open time in seconds.
+pub type LiquidationBucket = [f64; 3];
+
+#[cfg(feature = "native")]
+pub const LIQUIDATION_DIR: &str = "cache/liquidations";
+/// Hours of liquidations the prompt totals.
+pub const LIQUIDATION_HOURS: usize = 24;
+const HOUR: f64 = 3600.0;
+
+/// The liquidations of the hours that closed in the [`LIQUIDATION_HOURS`] hours before
+/// `as_of`, from chronological `buckets`, or `None` when none of them are known. Hours
+/// still open at `as_of` are left out, so a backtest sees what live analysis saw at the
+/// time.
+pub fn liquidations_as_of(buckets: &[LiquidationBucket], as_of: f64) -> Option<LiquidationSummary> {
+    let from = as_of - LIQUIDATION_HOURS as f64 * HOUR;
+    let closed = buckets
+        .iter()
+        .filter(|b| b[0] + HOUR > from && b[0] + HOUR <= as_of)
+        .collect::<Vec<_>>();
+    let last = closed.last()?;
+    Some(LiquidationSummary {
+        hours: closed.len(),
+        long_usd: closed.iter().map(|b| b[1]).sum(),
+        short_usd: closed.iter().map(|b| b[2]).sum(),
+        last_hour_long_usd: last[1],
+        last_hour_short_usd: last[2],
+    })
+}
+
+/// On-disk liquidation history in a [`RowStore`] of [`LiquidationBucket`]s. Live analysis
+/// adds the hours it fetches, so backtests over the same period read them back as of each
+/// window.
+#[cfg(feature = "native")]
+#[derive(Debug, Clone)]
+pub struct LiquidationStore {
+    rows: RowStore<3>,
+}
+
+#[cfg(feature = "native")]
+impl Default for LiquidationStore {
+    fn default() -> Self {
+        Self::new(LIQUIDATION_DIR)
+    }
+}
+
+#[cfg(feature = "native")]
+impl LiquidationStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            rows: RowStore::new(dir),
+        }
+    }
+
+    /// The stored hours of `symbol`, oldest first; empty when none were fetched.
+    pub fn load(&self, symbol: &str) -> Result<Vec<LiquidationBucket>> {
+        self.rows.load(symbol)
+    }
+
+    /// Downloads the hours of `[from, to)` into the store, page by page. Returns the
+    /// number of new hours stored.
+    pub async fn fetch_history(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<usize> {
+        let page = Duration::hours(MAX_HOURS_PER_REQUEST);
+        let mut added = 0;
+        let mut page_start = from;
+        while page_start < to {
+            let page_end = (page_start + page).min(to);
+            let buckets = fetch_liquidations(symbol, page_start, page_end).await?;
+            let new = self.rows.merge(symbol, &buckets)?;
+            added += new;
+            tracing::info!(symbol, %page_start, %page_end, new, "Fetched liquidation page");
+            page_start = page_end;
+        }
+        Ok(added)
+    }
+
+    /// Fetches the latest [`LIQUIDATION_HOURS`] hours of `symbol` into the store and
+    /// summarizes them as of `now`.
+    pub async fn refresh(
+        &self,
+        symbol: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<LiquidationSummary>> {
+        let from = now - Duration::hours(LIQUIDATION_HOURS as i64 + 1);
+        self.rows
+            .merge(symbol, &fetch_liquidations(symbol, from, now).await?)?;
+        Ok(liquidations_as_of(
+            &self.load(symbol)?,
+            now.timestamp() as f64,
+        ))
+    }
+}
+
+/// The aggregator returns at most this many hours per request.
+#[cfg(feature = "native")]
+const MAX_HOURS_PER_REQUEST: i64 = 1000;
+#[cfg(feature = "native")]
+const LIQUIDATION_URL: &str =
+    "https://open-api-v4.coinglass.com/api/futures/liquidation/aggregated-history";
+/// Key for the liquidation aggregator; liquidations are left out of prompts without it.
+#[cfg(feature = "native")]
+pub const LIQUIDATION_KEY: &str = "COINGLASS_API_KEY";
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+struct AggregatedHistory {
+    code: String,
+    #[serde(default)]
+    msg: String,
+    #[serde(default)]
+    data: Vec<AggregatedHour>,
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+struct AggregatedHour {
+    /// Open time in milliseconds.
+    time: i64,
+    aggregated_long_liquidation_usd: f64,
+    aggregated_short_liquidation_usd: f64,
+}
+
+/// Hourly liquidations of `symbol` across the major exchanges over `[from, to)`, oldest
+/// first.
+#[cfg(feature = "native")]
+pub async fn fetch_liquidations(
+    symbol: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<LiquidationBucket>> {
+    let key = secrets::secret(LIQUIDATION_KEY)
+        .with_context(|| format!("{} is not set", LIQUIDATION_KEY))?;
+    let response = reqwest::Client::new()
+        .get(LIQUIDATION_URL)
+        .header("CG-API-KEY", key)
+        .query(&[
+            ("symbol", symbol.to_string()),
+            ("exchange_list", "Binance,OKX,Bybit".to_string()),
+            ("interval", "1h".to_string()),
+            ("limit", MAX_HOURS_PER_REQUEST.to_string()),
+            ("start_time", from.timestamp_millis().to_string()),
+            ("end_time", to.timestamp_millis().to_string()),
+        ])
+        .timeout(std::time::Duration::from_secs(30))
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        anyhow::bail!(
+            "Liquidation API error for {}: {} - {}",
+            symbol,
+            status,
+            text
+        );
+    }
+    let history: AggregatedHistory = response.json().await?;
+    anyhow::ensure!(
+        history.code == "0",
+        "Liquidation API error for {}: {}",
+        symbol,
+        history.msg
+    );
+
+    let (from, to) = (from.timestamp_millis(), to.timestamp_millis());
+    let mut buckets = history
+        .data
+        .into_iter()
+        .filter(|h| h.time >= from && h.time < to)
+        .map(|h| {
+            [
+                (h.time / 1000) as f64,
+                h.aggregated_long_liquidation_usd,
+                h.aggregated_short_liquidation_usd,
+            ]
+        })
+        .collect::<Vec<_>>();
+    buckets.sort_by(|a, b| a[0].total_cmp(&b[0]));
+    Ok(buckets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_liquidations_as_of_only_counts_closed_hours() {
+        let buckets = (0..30)
+            .map(|h| [h as f64 * HOUR, 1_000.0, 10.0 * h as f64])
+            .collect::<Vec<_>>();
+
+        // Deciding at the open of hour 26: hours 2 to 25 have closed
+        let summary = liquidations_as_of(&buckets, 26.0 * HOUR).unwrap();
+        assert_eq!(summary.hours, 24);
+        assert_eq!(summary.long_usd, 24_000.0);
+        assert_eq!(
+            summary.short_usd,
+            (2..26).map(|h| 10.0 * h as f64).sum::<f64>()
+        );
+        assert_eq!(summary.last_hour_short_usd, 250.0);
+
+        // Halfway through hour 26 it is still open
+        assert_eq!(liquidations_as_of(&buckets, 26.5 * HOUR), Some(summary));
+        assert_eq!(liquidations_as_of(&buckets, 0.5 * HOUR), None);
+        assert_eq!(liquidations_as_of(&[], 26.0 * HOUR), None);
+    }
+}
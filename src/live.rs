@@ -0,0 +1,1548 @@
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use std::{env, fs};
+
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::analytics::{new_run_id, AnalyticsStore, PredictionRecord};
+use crate::audit::{
+    input_hash, signal_id, AuditStore, ConfigSnapshot, ExplanationBundle, WindowMetadata,
+};
+use crate::calendar::{parse_calendar, Calendar, CalendarEvent, OptionsExpiries, CALENDAR_FILE};
+use crate::compute::{assemble_snapshot_prompt, market_snapshot};
+use crate::data_quality::CandleAnomaly;
+use crate::encoding::{decode_prediction, CandleEncoding};
+use crate::faults::{self, Fault};
+use crate::granularity::check_granularity;
+use crate::hallucination::check_claims;
+use crate::health;
+use crate::implied_vol::{implied_volatility_as_of, ImpliedVolStore, IvPoint, DVOL_CURRENCIES};
+use crate::input_drift::{describe_input_drift, InputStats};
+use crate::liquidations::{
+    liquidations_as_of, LiquidationBucket, LiquidationStore, LIQUIDATION_KEY,
+};
+use crate::llm_cache::LiveCache;
+use crate::manifest::latest_input_profile;
+use crate::microstructure::fetch_microstructure;
+use crate::prediction::{
+    corrective_allocation_prompt, corrective_multi_asset_prompt, corrective_prompt,
+    parse_allocation_response, parse_model_response, parse_multi_asset_response, Allocation,
+    ParseError, Prediction,
+};
+use crate::profiles::{SymbolProfile, DEFAULT_TARGET};
+use crate::secrets::{self, ExchangeCredentials, KeyPool};
+use crate::sessions::SessionBreakdown;
+use crate::snapshot::AuxContext;
+use crate::sse::{stream_event, JsonObjectEnd, SseDecoder, StreamEvent};
+use crate::storage;
+use crate::thresholds::{load_labeling, LABELING_FILE};
+use crate::truncation::{estimate_tokens, fit_prompt_with, Truncation};
+use crate::{
+    assemble_market_prompt, assemble_multi_asset_prompt, candles_to_array, prepare_candles,
+    prompt_version, Action, ChatOptions, CoinbaseCandle, Model, CANDLE_HOURS, GRANULARITY,
+    PROMPT_FILE, RESPONSE_STRICTNESS,
+};
+
+pub(crate) async fn get_candle_data(
+    symbol: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    granularity: u32,
+) -> Result<Vec<CoinbaseCandle>> {
+    let client = reqwest::Client::new();
+    // let end = Utc::now();
+    // let start = end - chrono::Duration::hours(24);
+
+    let url = format!(
+        "https://api.exchange.coinbase.com/products/{symbol}-USD/candles\
+        ?start={}\
+        &end={}\
+        &granularity={}",
+        start.timestamp(),
+        end.timestamp(),
+        granularity
+    );
+
+    if faults::strikes(Fault::Timeout, &url) {
+        anyhow::bail!("Coinbase request for {} timed out (injected)", symbol);
+    }
+    if faults::strikes(Fault::RateLimit, &url) {
+        anyhow::bail!(
+            "Coinbase API error for {}: 429 Too Many Requests - injected",
+            symbol
+        );
+    }
+    let mut request = client.get(&url).header("User-Agent", "Mozilla/5.0");
+    if let Some(credentials) = EXCHANGE_CREDENTIALS.get_or_init(ExchangeCredentials::lookup) {
+        let path = &url[url.find("/products").unwrap_or(0)..];
+        for (name, value) in credentials.headers(Utc::now().timestamp(), "GET", path, "")? {
+            request = request.header(name, value);
+        }
+    }
+    let response = request.send().await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        anyhow::bail!("Coinbase API error for {}: {} - {}", symbol, status, text);
+    }
+
+    let mut data: Vec<CoinbaseCandle> = response.json().await?;
+    health::record_candle_fetch();
+    data.retain(|c| !faults::strikes(Fault::CandleGap, &format!("{}-{}", symbol, c.0)));
+    Ok(data)
+}
+
+/// The close of `symbol`'s latest minute candle, about what a market order fills at now.
+pub(crate) async fn latest_price(symbol: &str) -> Result<f64> {
+    let end = Utc::now();
+    let candles = get_candle_data(symbol, end - Duration::minutes(15), end, 60).await?;
+    candles_to_array(candles)
+        .iter()
+        .max_by(|a, b| a[0].total_cmp(&b[0]))
+        .map(|c| c[4])
+        .with_context(|| format!("Coinbase has no recent {symbol} trades"))
+}
+
+const OPENAI_CHAT_URL: &str = "https://api.openai.com/v1/chat/completions";
+
+static OPENAI_KEYS: std::sync::OnceLock<KeyPool> = std::sync::OnceLock::new();
+static EXCHANGE_CREDENTIALS: std::sync::OnceLock<Option<ExchangeCredentials>> =
+    std::sync::OnceLock::new();
+
+/// What a model call is for, which decides how long it may take and how urgently it is
+/// queued.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CallKind {
+    /// Live signals, which are stale once much of the hour is gone.
+    Live,
+    /// Backtest windows, which only hold up their own run.
+    Backtest,
+    /// Prompt improvement and failure analysis, with the longest prompts and answers.
+    Improvement,
+}
+
+impl CallKind {
+    /// Deadline for one call, from queueing to answer, re-asks and key rotations included.
+    /// Set by `LIVE_TIMEOUT_SECS`, `BACKTEST_TIMEOUT_SECS` or `IMPROVEMENT_TIMEOUT_SECS`;
+    /// unset, zero or unparsable values keep the default.
+    pub fn timeout(&self) -> std::time::Duration {
+        let (var, default) = match self {
+            CallKind::Live => ("LIVE_TIMEOUT_SECS", 120),
+            CallKind::Backtest => ("BACKTEST_TIMEOUT_SECS", 300),
+            CallKind::Improvement => ("IMPROVEMENT_TIMEOUT_SECS", 900),
+        };
+        let secs = env::var(var)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&n| n > 0)
+            .unwrap_or(default);
+        std::time::Duration::from_secs(secs)
+    }
+
+    /// Rank in the model request queue; lower is served first.
+    pub fn priority(&self) -> u8 {
+        match self {
+            CallKind::Live => 0,
+            CallKind::Improvement => 1,
+            CallKind::Backtest => 2,
+        }
+    }
+}
+
+/// An error status returned by the model API.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: reqwest::StatusCode,
+    pub body: String,
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Model API error: {} - {}", self.status, self.body)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// A model behind an OpenAI-compatible chat completions endpoint.
+#[derive(Debug, Clone)]
+pub(crate) struct ChatModel {
+    pub name: String,
+    url: String,
+    keys: KeyPool,
+    /// Token allowance sent when `options` doesn't set one; `None` for models not known to
+    /// accept `max_completion_tokens`.
+    default_max_tokens: Option<u32>,
+    pub options: ChatOptions,
+    /// Stream the completion, logging it as it arrives and hanging up once the JSON answer
+    /// is complete. Only for providers that support server-sent events.
+    streaming: bool,
+    /// Deadline and queue priority of each call.
+    pub kind: CallKind,
+}
+
+static PROMPT_TOKENS: AtomicU64 = AtomicU64::new(0);
+static COMPLETION_TOKENS: AtomicU64 = AtomicU64::new(0);
+static REASONING_TOKENS: AtomicU64 = AtomicU64::new(0);
+
+tokio::task_local! {
+    /// Where the usage of calls made under [`metered`] is also counted.
+    static METER: Meter;
+}
+
+/// A running count of the usage billed to one [`metered`] future.
+pub(crate) type Meter = Arc<Mutex<TokenUsage>>;
+
+/// Tokens billed by chat completions since the process started, as reported by the API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TokenUsage {
+    pub prompt: u64,
+    /// Includes the reasoning tokens.
+    pub completion: u64,
+    pub reasoning: u64,
+}
+
+impl TokenUsage {
+    /// Usage since the `earlier` snapshot.
+    pub fn since(&self, earlier: &TokenUsage) -> TokenUsage {
+        TokenUsage {
+            prompt: self.prompt - earlier.prompt,
+            completion: self.completion - earlier.completion,
+            reasoning: self.reasoning - earlier.reasoning,
+        }
+    }
+
+    /// This usage and `other` together.
+    pub fn plus(&self, other: &TokenUsage) -> TokenUsage {
+        TokenUsage {
+            prompt: self.prompt + other.prompt,
+            completion: self.completion + other.completion,
+            reasoning: self.reasoning + other.reasoning,
+        }
+    }
+
+    /// List price of the usage in dollars, had it all gone to `model`.
+    pub fn cost(&self, model: Model) -> f64 {
+        (self.prompt as f64 * model.input_price_per_mtok()
+            + self.completion as f64 * model.output_price_per_mtok())
+            / 1_000_000.0
+    }
+}
+
+/// Runs `fut` and returns, with its output, the usage billed by the calls it made. Calls
+/// it queued count too, though a queue worker makes them.
+pub async fn metered<F: Future>(fut: F) -> (F::Output, TokenUsage) {
+    let meter = Meter::default();
+    let output = METER.scope(meter.clone(), fut).await;
+    let usage = *meter.lock().unwrap();
+    (output, usage)
+}
+
+/// The meter of the [`metered`] future running this, if any.
+pub(crate) fn current_meter() -> Option<Meter> {
+    METER.try_with(Meter::clone).ok()
+}
+
+/// Runs `fut` billing its calls to `meter` as well, for work done on another's behalf.
+pub(crate) async fn with_meter<F: Future>(meter: Option<Meter>, fut: F) -> F::Output {
+    match meter {
+        Some(meter) => METER.scope(meter, fut).await,
+        None => fut.await,
+    }
+}
+
+pub fn token_usage() -> TokenUsage {
+    TokenUsage {
+        prompt: PROMPT_TOKENS.load(Ordering::Relaxed),
+        completion: COMPLETION_TOKENS.load(Ordering::Relaxed),
+        reasoning: REASONING_TOKENS.load(Ordering::Relaxed),
+    }
+}
+
+fn record_usage(usage: &Value) {
+    let count = |v: &Value| v.as_u64().unwrap_or(0);
+    let usage = TokenUsage {
+        prompt: count(&usage["prompt_tokens"]),
+        completion: count(&usage["completion_tokens"]),
+        reasoning: count(&usage["completion_tokens_details"]["reasoning_tokens"]),
+    };
+    PROMPT_TOKENS.fetch_add(usage.prompt, Ordering::Relaxed);
+    COMPLETION_TOKENS.fetch_add(usage.completion, Ordering::Relaxed);
+    REASONING_TOKENS.fetch_add(usage.reasoning, Ordering::Relaxed);
+    let _ = METER.try_with(|meter| {
+        let mut metered = meter.lock().unwrap();
+        *metered = metered.plus(&usage);
+    });
+}
+
+/// The OpenAI key pool, looked up once so a rotation carries over to later requests.
+fn openai_keys() -> Result<KeyPool> {
+    if let Some(keys) = OPENAI_KEYS.get() {
+        return Ok(keys.clone());
+    }
+    let keys = KeyPool::openai()?;
+    Ok(OPENAI_KEYS.get_or_init(|| keys).clone())
+}
+
+impl ChatModel {
+    pub fn openai(model: Model) -> Result<Self> {
+        Ok(Self {
+            name: model.as_str().to_string(),
+            url: OPENAI_CHAT_URL.to_string(),
+            keys: openai_keys()?,
+            default_max_tokens: Some(model.max_completion_tokens()),
+            options: ChatOptions::default(),
+            streaming: false,
+            kind: CallKind::Backtest,
+        })
+    }
+
+    /// The same model, sending `options` with every request.
+    pub fn with_options(self, options: ChatOptions) -> Self {
+        Self { options, ..self }
+    }
+
+    /// The same model, streaming its completions when `streaming` is set.
+    pub fn with_streaming(self, streaming: bool) -> Self {
+        Self { streaming, ..self }
+    }
+
+    /// The same model, with the deadline and priority of `kind` calls.
+    pub fn for_call(self, kind: CallKind) -> Self {
+        Self { kind, ..self }
+    }
+
+    /// The model registered to shadow live analysis, configured by `SHADOW_MODEL`; returns
+    /// `None` when it is unset. `SHADOW_ENDPOINT` points at another provider's compatible
+    /// endpoint and `SHADOW_API_KEY` its key; both default to OpenAI's.
+    pub fn shadow_from_env() -> Result<Option<Self>> {
+        let Ok(name) = env::var("SHADOW_MODEL") else {
+            return Ok(None);
+        };
+        let keys = match secrets::secret("SHADOW_API_KEY") {
+            Some(key) => KeyPool::new(vec![key])?,
+            None => openai_keys()
+                .context("SHADOW_MODEL is set but neither SHADOW_API_KEY nor an OpenAI key is")?,
+        };
+        Ok(Some(Self {
+            name,
+            url: env::var("SHADOW_ENDPOINT").unwrap_or_else(|_| OPENAI_CHAT_URL.to_string()),
+            keys,
+            default_max_tokens: None,
+            options: ChatOptions::default(),
+            streaming: env_flag("SHADOW_STREAMING"),
+            kind: CallKind::Live,
+        }))
+    }
+
+    /// Asks for a completion of `prompt` through the model request queue, which paces,
+    /// prioritizes and retries it; see [`crate::llm_queue::LlmQueue`].
+    pub async fn complete(&self, prompt: &str) -> Result<String> {
+        crate::llm_queue::llm_queue().submit(self, prompt).await
+    }
+
+    /// Sends one completion request for `prompt` right away. At the deadline the request is
+    /// dropped, which closes its connection, so a timed-out call leaves nothing running
+    /// behind it.
+    pub(crate) async fn complete_now(&self, prompt: &str) -> Result<String> {
+        health::llm_call_started();
+        let timeout = self.kind.timeout();
+        let call = crate::compute::sha256_hex(format!("{}\n{}", self.name, prompt).as_bytes());
+        let content = if faults::strikes(Fault::Timeout, &call) {
+            Err(anyhow::anyhow!(
+                "{} call timed out after {}s (injected)",
+                self.name,
+                timeout.as_secs()
+            ))
+        } else if faults::strikes(Fault::RateLimit, &call) {
+            Err(ApiError {
+                status: reqwest::StatusCode::TOO_MANY_REQUESTS,
+                body: "Rate limit reached (injected)".to_string(),
+            }
+            .into())
+        } else {
+            match tokio::time::timeout(timeout, self.request(prompt)).await {
+                Ok(content) => content,
+                Err(_) => Err(anyhow::anyhow!(
+                    "{} call timed out after {}s",
+                    self.name,
+                    timeout.as_secs()
+                )),
+            }
+        };
+        let content = content.map(|mut content| {
+            if faults::strikes(Fault::MalformedJson, &call) {
+                // Cut off as if the connection dropped mid-answer
+                content.truncate(content.floor_char_boundary(content.len() / 2));
+            }
+            content
+        });
+        health::llm_call_finished(content.is_ok());
+        content
+    }
+
+    async fn request(&self, prompt: &str) -> Result<String> {
+        let mut body = json!({
+            "model": self.name,
+            "messages": [
+                {
+                    "role": "user",
+                    "content": prompt
+                }
+            ]
+        });
+        if let Some(max_tokens) = self
+            .options
+            .max_completion_tokens
+            .or(self.default_max_tokens)
+        {
+            body["max_completion_tokens"] = json!(max_tokens);
+        }
+        if let Some(effort) = self.options.reasoning_effort {
+            body["reasoning_effort"] = json!(effort.as_str());
+        }
+        if self.streaming {
+            body["stream"] = json!(true);
+            body["stream_options"] = json!({ "include_usage": true });
+        }
+
+        let client = reqwest::Client::new();
+        tracing::debug!(
+            model = %self.name,
+            url = %self.url,
+            prompt_len = prompt.len(),
+            "Sending chat completion request"
+        );
+
+        // Each key gets one try; a key out of quota hands the request to the next
+        let mut attempts = 0;
+        let resp = loop {
+            let (index, api_key) = self.keys.current();
+            let resp = client
+                .post(&self.url)
+                .header("Content-Type", "application/json")
+                .header("Authorization", format!("Bearer {}", api_key))
+                .json(&body)
+                .send()
+                .await
+                .with_context(|| format!("Failed to send request to {}", self.url))?;
+
+            // Check if the response is successful
+            let status = resp.status();
+            if status.is_success() {
+                break resp;
+            }
+            let text = resp.text().await.unwrap_or_default();
+            attempts += 1;
+            if secrets::is_quota_error(status.as_u16(), &text) {
+                self.keys.rotate_from(index);
+                if attempts < self.keys.len() {
+                    tracing::warn!(key = index, %status, "API key hit its quota, rotating to the next");
+                    continue;
+                }
+            }
+            tracing::error!("{} returned error: {} - {}", self.url, status, text);
+            return Err(ApiError { status, body: text }.into());
+        };
+
+        if self.streaming {
+            return self.read_stream(resp).await;
+        }
+
+        let val: Value = resp
+            .json()
+            .await
+            .context("Failed to parse model API response as JSON")?;
+
+        tracing::debug!("Full model API response: {}", val);
+        record_usage(&val["usage"]);
+
+        // Extract the "content" field from the first choice
+        let content = val["choices"]
+            .get(0)
+            .and_then(|choice| choice["message"]["content"].as_str())
+            .map(str::to_string)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Could not find 'content' field in the API response: {}",
+                    val
+                )
+            })?;
+
+        Ok(content)
+    }
+
+    /// Collects a streamed completion, logging each line as it arrives. The connection is
+    /// dropped as soon as the first JSON object in the content is complete, so anything the
+    /// model would have written after it is neither waited for nor billed; the usage of such
+    /// a call goes unrecorded.
+    async fn read_stream(&self, mut resp: reqwest::Response) -> Result<String> {
+        let timer = Instant::now();
+        let mut decoder = SseDecoder::default();
+        let mut object_end = JsonObjectEnd::default();
+        let mut content = String::new();
+        let mut logged = 0;
+        while let Some(chunk) = resp
+            .chunk()
+            .await
+            .context("Model API stream was interrupted")?
+        {
+            for data in decoder.push(&chunk) {
+                match stream_event(&data)? {
+                    Some(StreamEvent::Content(delta)) => content.push_str(&delta),
+                    Some(StreamEvent::Usage(usage)) => record_usage(&usage),
+                    Some(StreamEvent::Done) => return Ok(content),
+                    None => {}
+                }
+            }
+            while let Some(line_end) = content[logged..].find('\n') {
+                tracing::debug!(model = %self.name, "Streamed: {}", &content[logged..logged + line_end]);
+                logged += line_end + 1;
+            }
+            if let Some(end) = object_end.feed(&content) {
+                tracing::debug!(
+                    model = %self.name,
+                    elapsed_ms = timer.elapsed().as_millis() as u64,
+                    "Answer complete, ending the stream early"
+                );
+                content.truncate(end);
+                return Ok(content);
+            }
+        }
+        // The stream ended without `[DONE]`; whatever arrived is parsed as usual
+        Ok(content)
+    }
+}
+
+/// Whether the environment variable `name` is set to something other than `0`, `false`
+/// or the empty string.
+fn env_flag(name: &str) -> bool {
+    env::var(name).is_ok_and(|v| !matches!(v.trim(), "" | "0" | "false"))
+}
+
+pub async fn analyze_data_gpt(prompt: &str, model: Model, kind: CallKind) -> Result<String> {
+    ChatModel::openai(model)?
+        .for_call(kind)
+        .complete(prompt)
+        .await
+}
+
+/// Symbols fetched for live analysis, in prompt order.
+pub(crate) const LIVE_SYMBOLS: [&str; 3] = ["ETH", "BTC", "SOL"];
+/// A live signal is meant for the hourly candle that just opened; model latency beyond this
+/// leaves too little of the hour for it to be acted on.
+const LATENCY_BUDGET: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+
+/// Asks `model` and parses the response, re-asking once with the corrective prompt when
+/// it fails validation. The question and the re-ask share one deadline of the model's
+/// [`CallKind`]. Returns the parsed value and the response it came from.
+async fn request_parsed<T>(
+    prompt: &str,
+    model: &ChatModel,
+    parse: impl Fn(&str) -> Result<T, ParseError>,
+    corrective: impl Fn(&str, &ParseError) -> String,
+) -> Result<(T, String)> {
+    let ask = |prompt: String| async move { model.complete(&prompt).await };
+    ask_parsed(
+        ask,
+        &model.name,
+        model.kind.timeout(),
+        prompt,
+        parse,
+        corrective,
+    )
+    .await
+}
+
+/// [`request_parsed`] with `ask` making the calls, all within `timeout`.
+async fn ask_parsed<T, Fut: Future<Output = Result<String>>>(
+    ask: impl Fn(String) -> Fut,
+    model: &str,
+    timeout: std::time::Duration,
+    prompt: &str,
+    parse: impl Fn(&str) -> Result<T, ParseError>,
+    corrective: impl Fn(&str, &ParseError) -> String,
+) -> Result<(T, String)> {
+    let exchange = async {
+        let response = ask(prompt.to_string()).await?;
+        let err = match parse(&response) {
+            Ok(parsed) => return Ok((parsed, response)),
+            Err(err) => err,
+        };
+        tracing::warn!(kind = err.kind(), "Re-asking after unusable model response");
+
+        let retry = ask(corrective(&response, &err)).await?;
+        let parsed = parse(&retry)
+            .with_context(|| format!("Unusable model response after re-ask: {}", retry))?;
+        Ok((parsed, retry))
+    };
+    tokio::time::timeout(timeout, exchange)
+        .await
+        .unwrap_or_else(|_| {
+            Err(anyhow::anyhow!(
+                "{} call timed out after {}s, re-ask included",
+                model,
+                timeout.as_secs_f64()
+            ))
+        })
+}
+
+/// Asks `model` for a prediction, re-asking once with a corrective instruction when the
+/// response fails validation. Returns the prediction and the response it was parsed from.
+pub(crate) async fn request_prediction(
+    prompt: &str,
+    model: Model,
+    options: ChatOptions,
+) -> Result<(Prediction, String)> {
+    request_chat_prediction(prompt, &ChatModel::openai(model)?.with_options(options)).await
+}
+
+async fn request_chat_prediction(prompt: &str, model: &ChatModel) -> Result<(Prediction, String)> {
+    request_parsed(
+        prompt,
+        model,
+        |r| parse_model_response(r, RESPONSE_STRICTNESS),
+        |r, err| corrective_prompt(prompt, r, err),
+    )
+    .await
+}
+
+/// [`request_prediction`] in multi-asset mode: one prediction per symbol, in the order of
+/// `symbols`.
+pub(crate) async fn request_multi_asset_prediction(
+    prompt: &str,
+    model: Model,
+    symbols: &[&str],
+    kind: CallKind,
+) -> Result<(Vec<(String, Prediction)>, String)> {
+    request_parsed(
+        prompt,
+        &ChatModel::openai(model)?.for_call(kind),
+        |r| parse_multi_asset_response(r, symbols, RESPONSE_STRICTNESS),
+        |r, err| corrective_multi_asset_prompt(prompt, r, err, symbols),
+    )
+    .await
+}
+
+/// [`request_prediction`] in allocation mode.
+pub(crate) async fn request_allocation(
+    prompt: &str,
+    model: Model,
+    symbols: &[&str],
+) -> Result<(Allocation, String)> {
+    request_parsed(
+        prompt,
+        &ChatModel::openai(model)?,
+        |r| parse_allocation_response(r, symbols, RESPONSE_STRICTNESS),
+        |r, err| corrective_allocation_prompt(prompt, r, err, symbols),
+    )
+    .await
+}
+
+/// The latest window of every [`LIVE_SYMBOLS`] series, with its flagged candles. Fetched
+/// straight from the API, without caching.
+async fn fetch_live_windows() -> Result<Vec<(Vec<[f64; 6]>, Vec<CandleAnomaly>)>> {
+    fetch_windows(None).await
+}
+
+/// Each live symbol's window ending with the candle opening at `at`, or with the latest
+/// candle.
+async fn fetch_windows(
+    at: Option<DateTime<Utc>>,
+) -> Result<Vec<(Vec<[f64; 6]>, Vec<CandleAnomaly>)>> {
+    fetch_symbol_windows(&LIVE_SYMBOLS, at).await
+}
+
+/// [`fetch_windows`] for `symbols`, in their order.
+async fn fetch_symbol_windows(
+    symbols: &[&str],
+    at: Option<DateTime<Utc>>,
+) -> Result<Vec<(Vec<[f64; 6]>, Vec<CandleAnomaly>)>> {
+    let (start, end) = match at {
+        Some(at) => (
+            at - Duration::hours(CANDLE_HOURS as i64),
+            at + Duration::seconds(GRANULARITY as i64),
+        ),
+        None => {
+            let end = Utc::now();
+            (end - Duration::hours(CANDLE_HOURS as i64), end)
+        }
+    };
+    let mut series = Vec::new();
+    for &symbol in symbols {
+        let (mut candles, anomalies) = prepare_candles(
+            symbol,
+            candles_to_array(get_candle_data(symbol, start, end, GRANULARITY).await?),
+        );
+        if let Some(at) = at {
+            candles.retain(|c| c[0] <= at.timestamp() as f64);
+        }
+        if candles.len() < CANDLE_HOURS {
+            match at {
+                Some(at) => {
+                    anyhow::bail!("Not enough {} data for the window ending at {}", symbol, at)
+                }
+                None => anyhow::bail!("Not enough recent data to perform live analysis"),
+            }
+        }
+        candles.drain(..candles.len() - CANDLE_HOURS);
+        series.push((candles, anomalies));
+    }
+    Ok(series)
+}
+
+/// The prompt live analysis sends for the window ending with the candle opening at `at`,
+/// or for the latest window, built without calling the model. Also returns any truncation
+/// needed to fit `model`.
+pub async fn preview_prompt(
+    at: Option<DateTime<Utc>>,
+    model: Model,
+) -> Result<(String, Option<Truncation>)> {
+    let base_prompt = fs::read_to_string(PROMPT_FILE).context("Failed to read base prompt file")?;
+    let series = fetch_windows(at).await?;
+    let windows = LIVE_SYMBOLS
+        .iter()
+        .zip(&series)
+        .map(|(symbol, (window, anomalies))| (*symbol, &window[..], &anomalies[..]))
+        .collect::<Vec<_>>();
+    let events = load_calendar().upcoming(at.unwrap_or_else(Utc::now));
+    let context = market_context(&LIVE_SYMBOLS, at).await;
+    Ok(fit_prompt_with(
+        &windows,
+        model.prompt_token_budget(),
+        |w| assemble_market_prompt(&base_prompt, w, DEFAULT_TARGET, &events, &context),
+    ))
+}
+
+/// Each of `symbols`' recent liquidations and implied volatility, for the window ending
+/// with the candle opening at `at` from the stores as a backtest sees them, or refreshed
+/// from their sources for the latest window, which also gets the current spread and taker
+/// flow. Symbols without any are left out, as are liquidations without
+/// [`LIQUIDATION_KEY`] and implied volatility outside [`DVOL_CURRENCIES`].
+pub(crate) async fn market_context<'a>(
+    symbols: &[&'a str],
+    at: Option<DateTime<Utc>>,
+) -> Vec<(&'a str, AuxContext)> {
+    let (liquidation_store, iv_store) = (LiquidationStore::default(), ImpliedVolStore::default());
+    let mut context = Vec::new();
+    for &symbol in symbols {
+        let aux = match at {
+            Some(at) => {
+                let decided_at = (at + Duration::seconds(GRANULARITY as i64)).timestamp();
+                let history = liquidation_store
+                    .load(symbol)
+                    .and_then(|liquidations| Ok((liquidations, iv_store.load(symbol)?)));
+                match history {
+                    Ok((liquidations, iv)) => stored_context(&liquidations, &iv, decided_at as f64),
+                    Err(err) => {
+                        tracing::warn!(symbol, %err, "Leaving market context out of the prompt");
+                        AuxContext::default()
+                    }
+                }
+            }
+            None => {
+                let now = Utc::now();
+                let liquidations = if secrets::secret(LIQUIDATION_KEY).is_some() {
+                    liquidation_store.refresh(symbol, now).await
+                } else {
+                    Ok(None)
+                };
+                let implied_volatility = if DVOL_CURRENCIES.contains(&symbol) {
+                    iv_store.refresh(symbol, now).await
+                } else {
+                    Ok(None)
+                };
+                let microstructure = fetch_microstructure(symbol).await;
+                AuxContext {
+                    liquidations: liquidations.unwrap_or_else(|err| {
+                        tracing::warn!(symbol, %err, "Leaving liquidations out of the prompt");
+                        None
+                    }),
+                    implied_volatility: implied_volatility.unwrap_or_else(|err| {
+                        tracing::warn!(symbol, %err, "Leaving implied volatility out of the prompt");
+                        None
+                    }),
+                    microstructure: microstructure.unwrap_or_else(|err| {
+                        tracing::warn!(symbol, %err, "Leaving order flow out of the prompt");
+                        None
+                    }),
+                    ..Default::default()
+                }
+            }
+        };
+        if !aux.is_empty() {
+            context.push((symbol, aux));
+        }
+    }
+    context
+}
+
+/// A symbol's auxiliary context as of `as_of`, from its stored liquidations and implied
+/// volatility.
+pub(crate) fn stored_context(
+    liquidations: &[LiquidationBucket],
+    implied_volatility: &[IvPoint],
+    as_of: f64,
+) -> AuxContext {
+    AuxContext {
+        liquidations: liquidations_as_of(liquidations, as_of),
+        implied_volatility: implied_volatility_as_of(implied_volatility, as_of),
+        ..Default::default()
+    }
+}
+
+/// The calendar behind the prompt's upcoming events: hand-maintained events from
+/// [`CALENDAR_FILE`], when present, and the computed options expiries.
+pub(crate) fn load_calendar() -> Calendar {
+    let calendar = Calendar::default().with(OptionsExpiries);
+    let Ok(json) = fs::read_to_string(CALENDAR_FILE) else {
+        return calendar;
+    };
+    match parse_calendar(&json) {
+        Ok(events) => calendar.with(events),
+        Err(err) => {
+            tracing::warn!(%err, "Ignoring unreadable {}", CALENDAR_FILE);
+            calendar
+        }
+    }
+}
+
+/// The high-impact event whose blackout covers `now`, when `EVENT_BLACKOUT_MINUTES` sets
+/// a blackout of that many minutes either side of each event.
+fn event_blackout(now: DateTime<Utc>) -> Option<CalendarEvent> {
+    let minutes = env::var("EVENT_BLACKOUT_MINUTES")
+        .ok()?
+        .parse::<i64>()
+        .ok()
+        .filter(|&m| m > 0)?;
+    load_calendar().blackout(now, Duration::minutes(minutes))
+}
+
+/// Token count and input cost of `prompt` on `model`, for previews.
+pub fn describe_prompt_cost(prompt: &str, model: Model) -> String {
+    let tokens = estimate_tokens(prompt);
+    format!(
+        "~{} prompt tokens, ~${:.4} input on {} (output tokens, including reasoning, are extra)",
+        tokens,
+        tokens as f64 / 1e6 * model.input_price_per_mtok(),
+        model.as_str()
+    )
+}
+
+/// Whether session gating is on (`SESSION_GATE` is set) and the recorded backtest trades
+/// lost money in the hour or weekday of `time`. The prediction is still recorded as made.
+fn outside_profitable_session(time: f64) -> Result<bool> {
+    if env::var_os("SESSION_GATE").is_none() {
+        return Ok(false);
+    }
+    let mut sessions = SessionBreakdown::default();
+    for r in AnalyticsStore::default().trades()? {
+        sessions.record_trade(r.trade.entry_time, r.trade.return_pct);
+    }
+    Ok(sessions.is_unprofitable(time))
+}
+
+/// Prompts evaluated silently next to the champion in `prompt.txt`, one `*.txt` file each.
+pub(crate) const CHALLENGER_DIR: &str = "challengers";
+
+/// Every challenger prompt with the file it came from, in path order.
+pub(crate) fn challenger_prompts() -> Result<Vec<(PathBuf, String)>> {
+    let Ok(dir) = fs::read_dir(CHALLENGER_DIR) else {
+        return Ok(Vec::new());
+    };
+    let mut prompts = Vec::new();
+    for entry in dir {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "txt") {
+            let prompt = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            prompts.push((path, prompt));
+        }
+    }
+    prompts.sort();
+    Ok(prompts)
+}
+
+/// Warns when `window` is far outside the inputs the latest backtest of `base_prompt` on
+/// `symbol` was evaluated on, where its accuracy says little. Never fails the analysis.
+async fn check_input_drift(symbol: &str, window: &[[f64; 6]], base_prompt: &str) {
+    let manifests = match storage::from_env().await {
+        Ok(storage) => storage.manifests().await,
+        Err(err) => Err(err),
+    };
+    let version = prompt_version(base_prompt);
+    let profile = match manifests {
+        Ok(manifests) => latest_input_profile(&manifests, symbol, &version),
+        Err(err) => {
+            tracing::warn!(%symbol, %err, "Failed to read the backtested input regime");
+            return;
+        }
+    };
+    let Some(profile) = profile else {
+        tracing::debug!(%symbol, %version, "No backtest recorded the prompt's input regime");
+        return;
+    };
+    let drift = profile.drift(&InputStats::of(window));
+    if !drift.is_empty() {
+        tracing::warn!(
+            %symbol,
+            %version,
+            backtest_windows = profile.windows,
+            "Live inputs are far outside the backtested regime:\n{}",
+            describe_input_drift(&drift)
+        );
+    }
+}
+
+/// Stores `prompt`, asked of `model` about `window` with `base_prompt`, for audits and
+/// describes it.
+fn audited(
+    symbol: &str,
+    window: &[[f64; 6]],
+    base_prompt: &str,
+    model: &ChatModel,
+    prompt: &str,
+) -> Result<WindowMetadata> {
+    AuditStore::default().put_prompt(prompt)?;
+    Ok(WindowMetadata::new(
+        symbol,
+        window,
+        &prompt_version(base_prompt),
+        &model.name,
+        model.options,
+        prompt,
+    ))
+}
+
+fn live_record(
+    run_id: &str,
+    source: &str,
+    model: &str,
+    metadata: WindowMetadata,
+    windows: &[&[[f64; 6]]],
+    prediction: Prediction,
+    latency: std::time::Duration,
+) -> PredictionRecord {
+    let levels = [prediction.invalidation_price, prediction.target_price];
+    let claims = check_claims(&prediction.rationale, windows, &levels);
+    PredictionRecord {
+        run_id: run_id.to_string(),
+        source: source.to_string(),
+        symbol: "ETH".to_string(),
+        model: model.to_string(),
+        prompt_hash: metadata.prompt_version.clone(),
+        window_end: metadata.window_end,
+        action: prediction.action,
+        label: None,
+        correct: None,
+        rationale: prediction.rationale,
+        key_factors: prediction.key_factors,
+        invalidation_price: prediction.invalidation_price,
+        target_price: prediction.target_price,
+        confidence: prediction.confidence,
+        level_outcome: None,
+        latency_ms: Some(latency.as_secs_f64() * 1e3),
+        metadata: Some(metadata),
+        numeric_claims: Some(claims),
+    }
+}
+
+/// The record of the shadow model's `answer` and how long it took, with source
+/// `"shadow"`. A call that failed or ran past its deadline is only logged and leaves no
+/// record, nor anything in the audit store: `metadata` is only asked for an answer.
+fn shadow_record(
+    run_id: &str,
+    model: &str,
+    (result, latency): (Result<(Prediction, String)>, std::time::Duration),
+    metadata: impl FnOnce() -> Result<WindowMetadata>,
+    windows: &[&[[f64; 6]]],
+) -> Result<Option<PredictionRecord>> {
+    match result {
+        Ok((prediction, _)) => Ok(Some(live_record(
+            run_id,
+            "shadow",
+            model,
+            metadata()?,
+            windows,
+            prediction,
+            latency,
+        ))),
+        Err(err) => {
+            tracing::warn!(%model, %err, "Shadow prediction failed");
+            Ok(None)
+        }
+    }
+}
+
+/// Keeps everything behind a live decision as its [`ExplanationBundle`], under the signal
+/// id of `record`. A failed write is only logged.
+fn keep_bundle(
+    record: &PredictionRecord,
+    prompt: &str,
+    response: &str,
+    prediction: Prediction,
+    (action, rationale): (Action, &str),
+    config: ConfigSnapshot,
+    windows: &[&[[f64; 6]]],
+) {
+    let Some(metadata) = record.metadata.clone() else {
+        return;
+    };
+    let bundle = ExplanationBundle {
+        signal_id: signal_id(&record.symbol, &record.run_id),
+        symbol: record.symbol.clone(),
+        run_id: record.run_id.clone(),
+        metadata,
+        prompt: prompt.to_string(),
+        response: response.to_string(),
+        prediction,
+        action,
+        rationale: rationale.to_string(),
+        input_hash: input_hash(windows),
+        config,
+    };
+    if let Err(err) = AuditStore::default().put_bundle(&bundle) {
+        tracing::warn!(signal_id = %bundle.signal_id, %err, "Failed to keep the explanation bundle");
+    }
+}
+
+/// Asks for the champion's signal on the latest windows. Every challenger prompt is asked
+/// about the same windows at the same time and recorded with source `"challenger"`, and the
+/// shadow model, if registered, is asked the champion prompt and recorded with source
+/// `"shadow"`. Their answers and any failures never affect the returned signal.
+pub async fn run_live_analysis() -> Result<(Action, String)> {
+    let series = fetch_live_windows().await?;
+    let base_prompt = fs::read_to_string(PROMPT_FILE).context("Failed to read base prompt file")?;
+    let challengers = challenger_prompts().unwrap_or_else(|err| {
+        tracing::warn!(%err, "Skipping challengers this hour");
+        Vec::new()
+    });
+    let shadow = ChatModel::shadow_from_env().unwrap_or_else(|err| {
+        tracing::warn!(%err, "Skipping the shadow model this hour");
+        None
+    });
+    // Only the live path streams: its answers are wanted as early as possible
+    let champion_model = ChatModel::openai(Model::O1Mini)?
+        .with_options(ChatOptions::from_env()?)
+        .with_streaming(env_flag("STREAM_COMPLETIONS"))
+        .for_call(CallKind::Live);
+
+    let [(eth_window, eth_anomalies), (btc_window, btc_anomalies), (sol_window, sol_anomalies)] =
+        &series[..]
+    else {
+        unreachable!("one series per live symbol");
+    };
+    let windows = [
+        ("ETH", &eth_window[..], &eth_anomalies[..]),
+        ("BTC", &btc_window[..], &btc_anomalies[..]),
+        ("SOL", &sol_window[..], &sol_anomalies[..]),
+    ];
+
+    check_input_drift(DEFAULT_TARGET, eth_window, &base_prompt).await;
+
+    // Decided as of the newest candle's close, as replays are: a rerun within the hour
+    // sees the events and blackout the first run did, and reuses its answer
+    let window_end = eth_window[eth_window.len() - 1][0];
+    let decided_at = DateTime::from_timestamp(window_end as i64 + GRANULARITY as i64, 0)
+        .context("Invalid window end")?;
+    let events = load_calendar().upcoming(decided_at);
+    let context = market_context(&LIVE_SYMBOLS, None).await;
+    let fit = |prompt: &str| {
+        fit_prompt_with(&windows, Model::O1Mini.prompt_token_budget(), |w| {
+            assemble_market_prompt(prompt, w, DEFAULT_TARGET, &events, &context)
+        })
+    };
+    let (full_prompt, truncation) = fit(&base_prompt);
+    if let Some(truncation) = truncation {
+        tracing::info!(?truncation, "Down-sampled older candles to fit the context");
+    }
+
+    let live_cache = LiveCache::default();
+    let cache_key = champion_model.options.cache_key(&full_prompt);
+    if let Some(prediction) = live_cache
+        .get(Model::O1Mini, &cache_key, window_end)
+        .and_then(|r| parse_model_response(&r, RESPONSE_STRICTNESS).ok())
+    {
+        // Challengers and the shadow were asked, and everything recorded, the first time
+        tracing::info!(
+            window_end,
+            "Reusing the live answer already given for this candle"
+        );
+        return live_signal(prediction, window_end, decided_at);
+    }
+
+    let ask = |prompt: String, model: ChatModel| async move {
+        let timer = Instant::now();
+        let result = request_chat_prediction(&prompt, &model).await;
+        (result, timer.elapsed())
+    };
+    let challenger_prompts = challengers
+        .iter()
+        .map(|(_, prompt)| fit(prompt).0)
+        .collect::<Vec<_>>();
+    let ((champion, latency), challenged, shadowed) = futures::join!(
+        ask(full_prompt.clone(), champion_model.clone()),
+        futures::future::join_all(
+            challenger_prompts
+                .iter()
+                .map(|prompt| ask(prompt.clone(), champion_model.clone()))
+        ),
+        async {
+            match &shadow {
+                Some(model) => Some(ask(full_prompt.clone(), model.clone()).await),
+                None => None,
+            }
+        }
+    );
+    let (prediction, response) = champion?;
+    if let Err(err) = live_cache.put(Model::O1Mini, &cache_key, window_end, &response) {
+        tracing::warn!(%err, "Failed to cache the live answer");
+    }
+    if latency > LATENCY_BUDGET {
+        tracing::warn!(
+            latency_secs = latency.as_secs(),
+            "Model latency is eating into the hourly cadence; the signal may be stale"
+        );
+    }
+
+    // Live predictions are recorded unverified; the label is only known an hour later
+    let run_id = new_run_id();
+    let signal = live_signal(prediction.clone(), window_end, decided_at)?;
+    let candles = windows.map(|(_, window, _)| window);
+    let mut records = vec![live_record(
+        &run_id,
+        "live",
+        &champion_model.name,
+        audited(
+            "ETH",
+            eth_window,
+            &base_prompt,
+            &champion_model,
+            &full_prompt,
+        )?,
+        &candles,
+        prediction.clone(),
+        latency,
+    )];
+    let challenged = challengers.iter().zip(&challenger_prompts).zip(challenged);
+    for (((path, prompt), full_prompt), (result, latency)) in challenged {
+        match result {
+            Ok((prediction, _)) => records.push(live_record(
+                &run_id,
+                "challenger",
+                &champion_model.name,
+                audited("ETH", eth_window, prompt, &champion_model, full_prompt)?,
+                &candles,
+                prediction,
+                latency,
+            )),
+            Err(err) => {
+                tracing::warn!(challenger = %path.display(), %err, "Challenger prediction failed")
+            }
+        }
+    }
+    if let (Some(model), Some(answer)) = (&shadow, shadowed) {
+        let metadata = || audited("ETH", eth_window, &base_prompt, model, &full_prompt);
+        records.extend(shadow_record(
+            &run_id,
+            &model.name,
+            answer,
+            metadata,
+            &candles,
+        )?);
+    }
+    storage::from_env()
+        .await?
+        .append_predictions(&records)
+        .await?;
+    keep_bundle(
+        &records[0],
+        &full_prompt,
+        &response,
+        prediction,
+        (signal.0, &signal.1),
+        ConfigSnapshot {
+            model: champion_model.name.clone(),
+            chat: champion_model.options,
+            granularity: GRANULARITY,
+            window_candles: eth_window.len(),
+            symbols: LIVE_SYMBOLS.iter().map(|s| s.to_string()).collect(),
+            prompt_file: PROMPT_FILE.to_string(),
+        },
+        &candles,
+    );
+
+    Ok(signal)
+}
+
+/// The signal to act on for `prediction`, decided at `now`, held back outside a profitable
+/// session or during an event blackout.
+fn live_signal(
+    prediction: Prediction,
+    window_end: f64,
+    now: DateTime<Utc>,
+) -> Result<(Action, String)> {
+    let Prediction {
+        action, rationale, ..
+    } = prediction;
+    if let Some(event) = event_blackout(now).filter(|_| action != Action::None) {
+        tracing::info!(?action, event = %event.name, "Holding back the signal around a scheduled event");
+        return Ok((
+            Action::None,
+            format!(
+                "Held back around {} at {}: {}",
+                event.name,
+                event.time.format("%Y-%m-%d %H:%M UTC"),
+                rationale
+            ),
+        ));
+    }
+    if action != Action::None && outside_profitable_session(window_end)? {
+        tracing::info!(
+            ?action,
+            "Holding back the signal outside a profitable session"
+        );
+        return Ok((
+            Action::None,
+            format!("Held back outside a profitable session: {}", rationale),
+        ));
+    }
+    Ok((action, rationale))
+}
+
+/// A live analysis in multi-asset mode: one request yields an action and rationale for
+/// every [`LIVE_SYMBOLS`] entry, each recorded as its own prediction.
+pub async fn run_live_multi_asset_analysis() -> Result<Vec<(String, Action, String)>> {
+    let series = fetch_live_windows().await?;
+    let base_prompt = fs::read_to_string(PROMPT_FILE).context("Failed to read base prompt file")?;
+    let windows = LIVE_SYMBOLS
+        .iter()
+        .zip(&series)
+        .map(|(symbol, (window, anomalies))| (*symbol, &window[..], &anomalies[..]))
+        .collect::<Vec<_>>();
+    let full_prompt = assemble_multi_asset_prompt(&base_prompt, &windows);
+
+    let timer = Instant::now();
+    let (predictions, _) =
+        request_multi_asset_prediction(&full_prompt, Model::O1Mini, &LIVE_SYMBOLS, CallKind::Live)
+            .await?;
+    let latency = timer.elapsed();
+    if latency > LATENCY_BUDGET {
+        tracing::warn!(
+            latency_secs = latency.as_secs(),
+            "Model latency is eating into the hourly cadence; the signals may be stale"
+        );
+    }
+
+    let run_id = new_run_id();
+    let version = prompt_version(&base_prompt);
+    AuditStore::default().put_prompt(&full_prompt)?;
+    let candles = windows.iter().map(|(_, w, _)| *w).collect::<Vec<_>>();
+    let records = predictions
+        .iter()
+        .zip(&series)
+        .map(|((symbol, p), (window, _))| PredictionRecord {
+            run_id: run_id.clone(),
+            source: "live".to_string(),
+            symbol: symbol.clone(),
+            model: Model::O1Mini.as_str().to_string(),
+            prompt_hash: version.clone(),
+            window_end: window[window.len() - 1][0],
+            action: p.action,
+            label: None,
+            correct: None,
+            rationale: p.rationale.clone(),
+            key_factors: p.key_factors.clone(),
+            invalidation_price: p.invalidation_price,
+            target_price: p.target_price,
+            confidence: p.confidence,
+            level_outcome: None,
+            latency_ms: Some(latency.as_secs_f64() * 1e3),
+            metadata: Some(WindowMetadata::new(
+                symbol,
+                window,
+                &version,
+                Model::O1Mini.as_str(),
+                ChatOptions::default(),
+                &full_prompt,
+            )),
+            numeric_claims: Some(check_claims(
+                &p.rationale,
+                &candles,
+                &[p.invalidation_price, p.target_price],
+            )),
+        })
+        .collect::<Vec<_>>();
+    storage::from_env()
+        .await?
+        .append_predictions(&records)
+        .await?;
+
+    Ok(predictions
+        .into_iter()
+        .map(|(symbol, p)| (symbol, p.action, p.rationale))
+        .collect())
+}
+
+/// One hourly signal of a daemon symbol profile.
+#[derive(Debug, Clone)]
+pub struct SymbolSignal {
+    pub symbol: String,
+    pub action: Action,
+    pub rationale: String,
+    /// Open time of the last candle in the target's window.
+    pub window_end: f64,
+    pub prompt_version: String,
+    /// The target's window, which verifies the symbol's earlier signals.
+    pub window: Vec<[f64; 6]>,
+    /// The prediction's invalidation and target prices, which bracket a traded position.
+    pub stop: Option<f64>,
+    pub target: Option<f64>,
+    /// How sure the model said it was, from 0 to 1, if it said.
+    pub confidence: Option<f64>,
+    /// Names the decision's explanation bundle in the [`AuditStore`].
+    pub signal_id: String,
+}
+
+/// A live analysis of `profile`'s symbol with its own prompt and context symbols, recorded
+/// as a live prediction of that symbol.
+pub async fn run_symbol_analysis(profile: &SymbolProfile) -> Result<SymbolSignal> {
+    run_symbol_analysis_at(profile, None).await
+}
+
+/// [`run_symbol_analysis`] of the window ending with the candle opening at `at`, as if
+/// decided when that candle closed, or of the latest window. A past window is recorded as a
+/// replay prediction rather than a live one and isn't checked for input drift.
+pub async fn run_symbol_analysis_at(
+    profile: &SymbolProfile,
+    at: Option<DateTime<Utc>>,
+) -> Result<SymbolSignal> {
+    let context = profile.context_symbols(&LIVE_SYMBOLS);
+    let symbols = std::iter::once(profile.symbol.as_str())
+        .chain(context.iter().map(String::as_str))
+        .collect::<Vec<_>>();
+    let series = fetch_symbol_windows(&symbols, at).await?;
+    let base_prompt = fs::read_to_string(&profile.prompt)
+        .with_context(|| format!("Failed to read {}", profile.prompt.display()))?;
+    let windows = symbols
+        .iter()
+        .zip(&series)
+        .map(|(symbol, (window, anomalies))| (*symbol, &window[..], &anomalies[..]))
+        .collect::<Vec<_>>();
+    let named = windows.iter().map(|(s, w, _)| (*s, *w)).collect::<Vec<_>>();
+    let thresholds = load_labeling(Path::new(LABELING_FILE))?.thresholds(&profile.symbol);
+    for warning in check_granularity(&named, CANDLE_HOURS, &base_prompt, thresholds) {
+        tracing::warn!(symbol = %profile.symbol, "Suspicious granularity: {}", warning);
+    }
+    let now = match at {
+        Some(at) => at + Duration::seconds(GRANULARITY as i64),
+        None => {
+            check_input_drift(&profile.symbol, &series[0].0, &base_prompt).await;
+            Utc::now()
+        }
+    };
+    let events = load_calendar().upcoming(now);
+    let context = market_context(&symbols, at).await;
+    let (full_prompt, truncation) =
+        fit_prompt_with(&windows, Model::O1Mini.prompt_token_budget(), |w| {
+            let snapshot = market_snapshot(w, &profile.symbol, &events, &context);
+            let snapshot = snapshot
+                .with_encoding(profile.encoding)
+                .with_prompt_features(profile.prompt_features);
+            assemble_snapshot_prompt(&base_prompt, &snapshot)
+        });
+    if let Some(truncation) = truncation {
+        tracing::info!(symbol = %profile.symbol, ?truncation, "Down-sampled older candles to fit the context");
+    }
+
+    let model = ChatModel::openai(Model::O1Mini)?
+        .with_options(ChatOptions::from_env()?)
+        .for_call(CallKind::Live);
+    let timer = Instant::now();
+    let (prediction, response) = request_chat_prediction(&full_prompt, &model).await?;
+    let latency = timer.elapsed();
+    if latency > LATENCY_BUDGET {
+        tracing::warn!(
+            symbol = %profile.symbol,
+            latency_secs = latency.as_secs(),
+            "Model latency is eating into the hourly cadence; the signal may be stale"
+        );
+    }
+
+    let window = series[0].0.clone();
+    let window_end = window[window.len() - 1][0];
+    let prediction = decode_prediction(prediction, &window, profile.encoding);
+    let candles = windows.iter().map(|(_, w, _)| *w).collect::<Vec<_>>();
+    let mut record = PredictionRecord {
+        symbol: profile.symbol.clone(),
+        ..live_record(
+            &new_run_id(),
+            if at.is_some() { "replay" } else { "live" },
+            &model.name,
+            audited(&profile.symbol, &window, &base_prompt, &model, &full_prompt)?,
+            &candles,
+            prediction.clone(),
+            latency,
+        )
+    };
+    if profile.encoding != CandleEncoding::Raw {
+        // Encoded rationales quote encoded numbers, which the raw candles can't support
+        record.numeric_claims = None;
+    }
+    storage::from_env()
+        .await?
+        .append_predictions(std::slice::from_ref(&record))
+        .await?;
+
+    let (stop, target) = (prediction.invalidation_price, prediction.target_price);
+    let confidence = prediction.confidence;
+    let (action, rationale) = live_signal(prediction.clone(), window_end, now)?;
+    keep_bundle(
+        &record,
+        &full_prompt,
+        &response,
+        prediction,
+        (action, &rationale),
+        ConfigSnapshot {
+            model: model.name.clone(),
+            chat: model.options,
+            granularity: GRANULARITY,
+            window_candles: window.len(),
+            symbols: symbols.iter().map(|s| s.to_string()).collect(),
+            prompt_file: profile.prompt.display().to_string(),
+        },
+        &candles,
+    );
+    Ok(SymbolSignal {
+        symbol: profile.symbol.clone(),
+        action,
+        rationale,
+        window_end,
+        prompt_version: prompt_version(&base_prompt),
+        window,
+        stop,
+        target,
+        confidence,
+        signal_id: signal_id(&record.symbol, &record.run_id),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call_timeouts() {
+        use std::time::Duration as StdDuration;
+
+        env::remove_var("IMPROVEMENT_TIMEOUT_SECS");
+        assert_eq!(CallKind::Improvement.timeout(), StdDuration::from_secs(900));
+        env::set_var("IMPROVEMENT_TIMEOUT_SECS", "30");
+        assert_eq!(CallKind::Improvement.timeout(), StdDuration::from_secs(30));
+        for unusable in ["0", "soon", "-5"] {
+            env::set_var("IMPROVEMENT_TIMEOUT_SECS", unusable);
+            assert_eq!(CallKind::Improvement.timeout(), StdDuration::from_secs(900));
+        }
+        env::remove_var("IMPROVEMENT_TIMEOUT_SECS");
+    }
+
+    #[tokio::test]
+    async fn test_re_ask_shares_the_deadline() {
+        let asked = AtomicU64::new(0);
+        let ask = |_: String| {
+            let n = asked.fetch_add(1, Ordering::Relaxed);
+            async move {
+                tokio::time::sleep(std::time::Duration::from_millis(40)).await;
+                Ok(if n == 0 {
+                    "not json"
+                } else {
+                    r#"{"action": "long", "rationale": "breakout"}"#
+                }
+                .to_string())
+            }
+        };
+        let parse = |r: &str| parse_model_response(r, RESPONSE_STRICTNESS);
+        let corrective = |r: &str, err: &ParseError| corrective_prompt("prompt", r, err);
+
+        // Each call fits the deadline on its own, but not the question and re-ask together
+        let timeout = std::time::Duration::from_millis(60);
+        let err = ask_parsed(ask, "o1-mini", timeout, "prompt", parse, corrective)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{err}");
+        assert_eq!(asked.load(Ordering::Relaxed), 2);
+
+        asked.store(0, Ordering::Relaxed);
+        let timeout = std::time::Duration::from_secs(5);
+        let (prediction, response) =
+            ask_parsed(ask, "o1-mini", timeout, "prompt", parse, corrective)
+                .await
+                .unwrap();
+        assert_eq!(prediction.action, Action::Long);
+        assert_eq!(response, r#"{"action": "long", "rationale": "breakout"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_shadow_answers_are_recorded_apart() {
+        let window = [[3600.0, 99.0, 101.0, 100.0, 100.5, 10.0]; 2];
+        let candles = [&window[..]];
+        let metadata = || {
+            Ok(WindowMetadata::new(
+                "ETH",
+                &window,
+                "v1",
+                "shadow-1",
+                ChatOptions::default(),
+                "prompt",
+            ))
+        };
+        let answer = r#"{"action": "short", "rationale": "Rejected at resistance"}"#;
+        let prediction = parse_model_response(answer, RESPONSE_STRICTNESS).unwrap();
+        let latency = std::time::Duration::from_millis(1500);
+
+        let record = shadow_record(
+            "r1",
+            "shadow-1",
+            (Ok((prediction, answer.to_string())), latency),
+            metadata,
+            &candles,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            (record.source.as_str(), record.model.as_str(), record.action),
+            ("shadow", "shadow-1", Action::Short)
+        );
+        assert_eq!(record.latency_ms, Some(1500.0));
+
+        // A shadow that hangs runs into its deadline and, like one that fails outright,
+        // leaves nothing behind and no error to fail the champion's analysis with
+        let slow = |_: String| async {
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            Ok(answer.to_string())
+        };
+        let timed_out = ask_parsed(
+            slow,
+            "shadow-1",
+            std::time::Duration::from_millis(20),
+            "prompt",
+            |r| parse_model_response(r, RESPONSE_STRICTNESS),
+            |r, err| corrective_prompt("prompt", r, err),
+        )
+        .await;
+        let failed = Err(anyhow::anyhow!("502 Bad Gateway"));
+        for result in [timed_out, failed] {
+            let unasked = || -> Result<WindowMetadata> { panic!("failed shadow was audited") };
+            let record = shadow_record("r1", "shadow-1", (result, latency), unasked, &candles);
+            assert!(record.unwrap().is_none());
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+
+use crate::compute::sha256_hex;
+use crate::{compression, Model, GRANULARITY};
+
+pub const LLM_CACHE_DIR: &str = "cache/llm";
+pub const LIVE_CACHE_DIR: &str = "cache/live";
+
+/// Hex SHA-256 of the model name and prompt, used as the cache key.
+pub fn prompt_hash(model: Model, prompt: &str) -> String {
+    let mut key = model.as_str().as_bytes().to_vec();
+    key.push(0);
+    key.extend_from_slice(prompt.as_bytes());
+    sha256_hex(&key)
+}
+
+/// Size and age summary of a cache directory.
+#[derive(Debug, Clone, Default)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub bytes: u64,
+    pub oldest: Option<SystemTime>,
+    pub newest: Option<SystemTime>,
+}
+
+/// File-backed (zstd-compressed) cache of raw model responses, so re-running a backtest over the
+/// same windows with the same prompt doesn't pay for the same completions twice.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new(LLM_CACHE_DIR)
+    }
+}
+
+impl ResponseCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.txt", key))
+    }
+
+    pub fn get(&self, model: Model, prompt: &str) -> Option<String> {
+        let bytes = compression::read(&self.path(&prompt_hash(model, prompt))).ok()??;
+        String::from_utf8(bytes).ok()
+    }
+
+    pub fn put(&self, model: Model, prompt: &str, response: &str) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        compression::write(&self.path(&prompt_hash(model, prompt)), response.as_bytes())
+            .context("Failed to write cached LLM response")
+    }
+
+    fn entries(&self) -> Result<Vec<(PathBuf, fs::Metadata)>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        fs::read_dir(&self.dir)?
+            .map(|entry| {
+                let entry = entry?;
+                Ok((entry.path(), entry.metadata()?))
+            })
+            .filter(|e| !matches!(e, Ok((_, meta)) if !meta.is_file()))
+            .collect()
+    }
+
+    pub fn stats(&self) -> Result<CacheStats> {
+        let mut stats = CacheStats::default();
+        for (_, meta) in self.entries()? {
+            stats.entries += 1;
+            stats.bytes += meta.len();
+            if let Ok(modified) = meta.modified() {
+                stats.oldest = Some(stats.oldest.map_or(modified, |t| t.min(modified)));
+                stats.newest = Some(stats.newest.map_or(modified, |t| t.max(modified)));
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Removes responses written more than `max_age` ago and returns how many were removed.
+    pub fn prune(&self, max_age: Duration) -> Result<usize> {
+        let now = SystemTime::now();
+        let mut removed = 0;
+        for (path, meta) in self.entries()? {
+            let age = meta
+                .modified()
+                .ok()
+                .and_then(|m| now.duration_since(m).ok())
+                .unwrap_or_default();
+            if age > max_age {
+                fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    pub fn clear(&self) -> Result<usize> {
+        let entries = self.entries()?;
+        for (path, _) in &entries {
+            fs::remove_file(path)?;
+        }
+        Ok(entries.len())
+    }
+}
+
+/// How long shared (Redis) cache entries live unless `LLM_CACHE_TTL_SECS` overrides it.
+pub const DEFAULT_SHARED_TTL: Duration = Duration::from_secs(30 * 24 * 3600);
+#[cfg(feature = "redis")]
+const REDIS_KEY_PREFIX: &str = "happycharts:llm:";
+
+/// Response cache shared by parallel workers and instances. When `REDIS_URL` is set (and
+/// the `redis` feature enabled) responses are stored in Redis with a TTL; the local file
+/// cache is always written too and serves as the fallback when Redis is unreachable.
+#[derive(Clone)]
+pub struct LlmCache {
+    local: ResponseCache,
+    #[cfg(feature = "redis")]
+    redis: Option<redis::aio::MultiplexedConnection>,
+    #[cfg_attr(not(feature = "redis"), allow(dead_code))]
+    ttl: Duration,
+}
+
+impl LlmCache {
+    pub fn local(local: ResponseCache) -> Self {
+        Self {
+            local,
+            #[cfg(feature = "redis")]
+            redis: None,
+            ttl: DEFAULT_SHARED_TTL,
+        }
+    }
+
+    pub async fn from_env() -> Self {
+        let mut cache = Self::local(ResponseCache::default());
+        if let Some(ttl) = std::env::var("LLM_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            cache.ttl = Duration::from_secs(ttl);
+        }
+
+        if let Ok(url) = std::env::var("REDIS_URL") {
+            #[cfg(feature = "redis")]
+            match connect_redis(&url).await {
+                Ok(conn) => cache.redis = Some(conn),
+                Err(e) => {
+                    tracing::warn!(error = ?e, "Redis unavailable, using the local LLM cache only")
+                }
+            }
+            #[cfg(not(feature = "redis"))]
+            tracing::warn!(%url, "REDIS_URL is set but the `redis` feature is not enabled");
+        }
+
+        cache
+    }
+
+    pub async fn get(&self, model: Model, prompt: &str) -> Option<String> {
+        #[cfg(feature = "redis")]
+        if let Some(conn) = &self.redis {
+            let key = format!("{}{}", REDIS_KEY_PREFIX, prompt_hash(model, prompt));
+            match redis::cmd("GET")
+                .arg(&key)
+                .query_async::<Option<String>>(&mut conn.clone())
+                .await
+            {
+                Ok(Some(hit)) => return Some(hit),
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::warn!(error = ?e, "Redis GET failed, falling back to local cache")
+                }
+            }
+        }
+
+        self.local.get(model, prompt)
+    }
+
+    pub async fn put(&self, model: Model, prompt: &str, response: &str) -> Result<()> {
+        #[cfg(feature = "redis")]
+        if let Some(conn) = &self.redis {
+            let key = format!("{}{}", REDIS_KEY_PREFIX, prompt_hash(model, prompt));
+            if let Err(e) = redis::cmd("SET")
+                .arg(&key)
+                .arg(response)
+                .arg("EX")
+                .arg(self.ttl.as_secs().max(1))
+                .query_async::<()>(&mut conn.clone())
+                .await
+            {
+                tracing::warn!(error = ?e, "Redis SET failed, response cached locally only");
+            }
+        }
+
+        self.local.put(model, prompt, response)
+    }
+}
+
+/// Live answers, kept for one candle so that a second live analysis of the same candle
+/// (say, a manual run next to the scheduled one) reuses the answer instead of paying for
+/// another call.
+#[derive(Debug, Clone)]
+pub struct LiveCache {
+    responses: ResponseCache,
+    ttl: Duration,
+}
+
+impl Default for LiveCache {
+    fn default() -> Self {
+        Self::new(LIVE_CACHE_DIR, Duration::from_secs(GRANULARITY as u64))
+    }
+}
+
+impl LiveCache {
+    pub fn new(dir: impl Into<PathBuf>, ttl: Duration) -> Self {
+        Self {
+            responses: ResponseCache::new(dir),
+            ttl,
+        }
+    }
+
+    /// Keyed by the candle as well as the prompt, which may not differ between candles
+    /// when the data hasn't moved.
+    fn key(prompt: &str, candle_time: f64) -> String {
+        format!("{}\0{}", candle_time as i64, prompt)
+    }
+
+    /// The answer given to `prompt` for the candle opening at `candle_time`, unless it is
+    /// older than a candle.
+    pub fn get(&self, model: Model, prompt: &str, candle_time: f64) -> Option<String> {
+        let key = Self::key(prompt, candle_time);
+        let path = self.responses.path(&prompt_hash(model, &key));
+        let written = fs::metadata(compression::compressed_path(&path))
+            .ok()?
+            .modified()
+            .ok()?;
+        let age = SystemTime::now()
+            .duration_since(written)
+            .unwrap_or_default();
+        (age <= self.ttl)
+            .then(|| self.responses.get(model, &key))
+            .flatten()
+    }
+
+    /// Stores the answer and drops any that have expired.
+    pub fn put(&self, model: Model, prompt: &str, candle_time: f64, response: &str) -> Result<()> {
+        self.responses.prune(self.ttl)?;
+        self.responses
+            .put(model, &Self::key(prompt, candle_time), response)
+    }
+}
+
+#[cfg(feature = "redis")]
+async fn connect_redis(url: &str) -> Result<redis::aio::MultiplexedConnection> {
+    let client = redis::Client::open(url).context("Invalid REDIS_URL")?;
+    client
+        .get_multiplexed_tokio_connection()
+        .await
+        .context("Failed to connect to Redis")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_cache_roundtrip() {
+        let dir = std::env::temp_dir().join("happycharts_llm_cache_roundtrip");
+        let _ = fs::remove_dir_all(&dir);
+        let cache = ResponseCache::new(&dir);
+
+        assert!(cache.get(Model::O1Mini, "prompt").is_none());
+        cache
+            .put(Model::O1Mini, "prompt", "{\"action\":\"long\"}")
+            .unwrap();
+        assert_eq!(
+            cache.get(Model::O1Mini, "prompt").as_deref(),
+            Some("{\"action\":\"long\"}")
+        );
+        // The model is part of the key
+        assert!(cache.get(Model::O1Preview, "prompt").is_none());
+
+        let stats = cache.stats().unwrap();
+        assert_eq!(stats.entries, 1);
+        assert_eq!(cache.prune(Duration::from_secs(3600)).unwrap(), 0);
+        assert_eq!(cache.clear().unwrap(), 1);
+        assert_eq!(cache.stats().unwrap().entries, 0);
+    }
+
+    #[test]
+    fn test_prompt_hash_is_stable() {
+        assert_eq!(prompt_hash(Model::O1Mini, "abc").len(), 64);
+        assert_eq!(
+            prompt_hash(Model::O1Mini, "abc"),
+            prompt_hash(Model::O1Mini, "abc")
+        );
+        assert_ne!(
+            prompt_hash(Model::O1Mini, "abc"),
+            prompt_hash(Model::O1Mini, "abd")
+        );
+    }
+
+    #[test]
+    fn test_live_cache_keeps_answers_for_one_candle() {
+        let dir = std::env::temp_dir().join("happycharts_live_cache");
+        let _ = fs::remove_dir_all(&dir);
+        let cache = LiveCache::new(&dir, Duration::from_secs(3600));
+        cache.put(Model::O1Mini, "p", 7200.0, "r").unwrap();
+        assert_eq!(cache.get(Model::O1Mini, "p", 7200.0).as_deref(), Some("r"));
+        assert!(cache.get(Model::O1Mini, "p", 10800.0).is_none());
+        assert!(cache.get(Model::O1Mini, "q", 7200.0).is_none());
+
+        let expired = LiveCache::new(&dir, Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(expired.get(Model::O1Mini, "p", 7200.0).is_none());
+        expired.put(Model::O1Mini, "q", 7200.0, "r").unwrap();
+        assert_eq!(cache.responses.stats().unwrap().entries, 1);
+    }
+
+    #[tokio::test]
+    async fn test_llm_cache_local_fallback() {
+        let dir = std::env::temp_dir().join("happycharts_llm_cache_shared");
+        let _ = fs::remove_dir_all(&dir);
+        let cache = LlmCache::local(ResponseCache::new(&dir));
+
+        assert!(cache.get(Model::O1Mini, "p").await.is_none());
+        cache.put(Model::O1Mini, "p", "r").await.unwrap();
+        assert_eq!(cache.get(Model::O1Mini, "p").await.as_deref(), Some("r"));
+    }
+}
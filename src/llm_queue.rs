@@ -0,0 +1,563 @@
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::BinaryHeap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{oneshot, Notify};
+use tokio::time::Instant;
+
+use crate::compute::sha256_hex;
+use crate::live::{current_meter, with_meter, ApiError, CallKind, ChatModel, Meter};
+use crate::{ChatOptions, Model};
+
+pub const LLM_QUEUE_DIR: &str = "cache/llm_queue";
+/// Workers calling the model API unless `LLM_WORKERS` overrides it; matches the backtest's
+/// requests in flight.
+const DEFAULT_WORKERS: usize = 20;
+/// Tries per job, the first included, for errors worth retrying.
+const MAX_ATTEMPTS: u32 = 3;
+/// Wait before the first retry, doubled for each one after.
+const RETRY_BACKOFF: Duration = Duration::from_secs(2);
+/// Answers recovered for a crashed process are kept this long for its rerun to collect.
+const RECOVERED_RETENTION: Duration = Duration::from_secs(24 * 3600);
+/// Priority of jobs recovered from a crashed process, below every live caller's.
+const RECOVERY_PRIORITY: u8 = 3;
+
+/// A queued request as persisted on disk while it is pending.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Hash of the model, settings and prompt; identical requests share it.
+    pub request: String,
+    pub model: String,
+    pub options: ChatOptions,
+    pub kind: CallKind,
+    pub prompt: String,
+    /// Process that queued the request.
+    pub pid: u32,
+    pub enqueued_at: DateTime<Utc>,
+}
+
+/// Hash identifying a request by what is sent, so a rerun recognizes it.
+pub fn request_id(model: &str, options: &ChatOptions, prompt: &str) -> String {
+    let mut key = model.as_bytes().to_vec();
+    key.push(0);
+    key.extend_from_slice(options.cache_key(prompt).as_bytes());
+    sha256_hex(&key)
+}
+
+/// Waits up to `timeout` for the answer to a queued request. Giving up drops `rx`, which
+/// withdraws the request from the queue or cancels it mid-call.
+async fn answer(
+    rx: oneshot::Receiver<Result<String>>,
+    model: &str,
+    timeout: Duration,
+) -> Result<String> {
+    match tokio::time::timeout(timeout, rx).await {
+        Ok(answer) => {
+            answer.unwrap_or_else(|_| Err(anyhow::anyhow!("Model request queue shut down")))
+        }
+        Err(_) => Err(anyhow::anyhow!(
+            "{} call timed out after {}s, time queued included",
+            model,
+            timeout.as_secs_f64()
+        )),
+    }
+}
+
+/// Whether a failed call may succeed if simply tried again: rate limits that are not an
+/// exhausted quota, server errors and dropped connections. Timeouts are not retried, since
+/// the deadline already covers the caller's patience.
+pub fn is_retryable(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| {
+        if let Some(api) = e.downcast_ref::<ApiError>() {
+            api.status.is_server_error()
+                || (api.status.as_u16() == 429 && !api.body.contains("insufficient_quota"))
+        } else if let Some(e) = e.downcast_ref::<reqwest::Error>() {
+            e.is_connect() || e.is_request()
+        } else {
+            false
+        }
+    })
+}
+
+/// Lower runs first; ties keep submission order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Ticket {
+    priority: u8,
+    seq: u64,
+}
+
+impl Ord for Ticket {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        // BinaryHeap pops the greatest, so the comparison is reversed
+        (other.priority, other.seq).cmp(&(self.priority, self.seq))
+    }
+}
+
+impl PartialOrd for Ticket {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+enum Reply {
+    Caller(oneshot::Sender<Result<String>>),
+    /// A recovered job, whose answer is saved for its rerun to collect.
+    Recovered(PathBuf),
+}
+
+struct Job {
+    ticket: Ticket,
+    model: ChatModel,
+    prompt: String,
+    /// Journal file, removed once the job is answered or abandoned.
+    journal: Option<PathBuf>,
+    reply: Reply,
+    /// The caller's [`metered`](crate::live::metered) count, billed for the call.
+    meter: Option<Meter>,
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Self) -> bool {
+        self.ticket == other.ticket
+    }
+}
+
+impl Eq for Job {}
+
+impl Ord for Job {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.ticket.cmp(&other.ticket)
+    }
+}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Every model call goes through this queue. Workers take the most urgent pending request
+/// (live before improvement before backtest), pace themselves to `LLM_REQUESTS_PER_MINUTE`
+/// when set and retry transient failures. Pending requests are journaled to disk; those
+/// left behind by a process that died are run again at the lowest priority and their
+/// answers kept, so rerunning the interrupted command collects them without a new call.
+pub struct LlmQueue {
+    dir: PathBuf,
+    pending: Mutex<BinaryHeap<Job>>,
+    ready: Notify,
+    seq: AtomicU64,
+    /// Minimum time between two calls starting, from `LLM_REQUESTS_PER_MINUTE`.
+    spacing: Option<Duration>,
+    next_start: tokio::sync::Mutex<Instant>,
+    /// Worker tasks, on whichever runtimes started them.
+    workers: Mutex<Vec<tokio::task::JoinHandle<()>>>,
+}
+
+static QUEUE: OnceLock<Arc<LlmQueue>> = OnceLock::new();
+
+/// The process-wide queue, recovering orphaned requests on first use and starting workers
+/// on the current runtime whenever the ones before went down with theirs. Must be called
+/// within a Tokio runtime.
+pub fn llm_queue() -> Arc<LlmQueue> {
+    let queue = QUEUE
+        .get_or_init(|| {
+            let queue = Arc::new(LlmQueue::new(LLM_QUEUE_DIR));
+            if let Err(err) = queue.recover() {
+                tracing::warn!(%err, "Failed to recover queued model requests");
+            }
+            queue
+        })
+        .clone();
+    queue.start_workers();
+    queue
+}
+
+impl LlmQueue {
+    fn new(dir: impl Into<PathBuf>) -> Self {
+        let spacing = env::var("LLM_REQUESTS_PER_MINUTE")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|&rpm| rpm > 0.0)
+            .map(|rpm| Duration::from_secs_f64(60.0 / rpm));
+        Self {
+            dir: dir.into(),
+            pending: Mutex::new(BinaryHeap::new()),
+            ready: Notify::new(),
+            seq: AtomicU64::new(0),
+            spacing,
+            next_start: tokio::sync::Mutex::new(Instant::now()),
+            workers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Tops the workers up to `LLM_WORKERS` on the current runtime. Workers end only when
+    /// their runtime shuts down, so a queue outliving one runtime is served by the next.
+    fn start_workers(self: &Arc<Self>) {
+        let count = env::var("LLM_WORKERS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&n| n > 0)
+            .unwrap_or(DEFAULT_WORKERS);
+        let mut workers = self.workers.lock().unwrap();
+        workers.retain(|w| !w.is_finished());
+        while workers.len() < count {
+            workers.push(tokio::spawn(self.clone().work()));
+        }
+    }
+
+    fn recovered_path(&self, request: &str) -> PathBuf {
+        self.dir.join(format!("{}.recovered", request))
+    }
+
+    /// Queues `prompt` for `model` and waits for the answer. The deadline of the model's
+    /// [`CallKind`] runs from here, so time spent queued behind other calls counts against
+    /// it. Dropping the returned future withdraws the request, cancelling it if a worker
+    /// already started it.
+    pub async fn submit(&self, model: &ChatModel, prompt: &str) -> Result<String> {
+        let request = request_id(&model.name, &model.options, prompt);
+        let recovered = self.recovered_path(&request);
+        if let Ok(response) = fs::read_to_string(&recovered) {
+            let _ = fs::remove_file(&recovered);
+            tracing::info!(%request, "Using an answer recovered from an interrupted run");
+            return Ok(response);
+        }
+
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let entry = JournalEntry {
+            request,
+            model: model.name.clone(),
+            options: model.options,
+            kind: model.kind,
+            prompt: prompt.to_string(),
+            pid: process::id(),
+            enqueued_at: Utc::now(),
+        };
+        // A request that can't be journaled still runs; it just can't be recovered
+        let journal = self
+            .journal(&entry, seq)
+            .map_err(|err| tracing::warn!(%err, "Failed to journal a model request"))
+            .ok();
+
+        let timeout = model.kind.timeout();
+        let (tx, rx) = oneshot::channel();
+        self.push(Job {
+            ticket: Ticket {
+                priority: model.kind.priority(),
+                seq,
+            },
+            model: model.clone(),
+            prompt: prompt.to_string(),
+            journal,
+            reply: Reply::Caller(tx),
+            meter: current_meter(),
+        });
+        answer(rx, &model.name, timeout).await
+    }
+
+    fn journal(&self, entry: &JournalEntry, seq: u64) -> Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self
+            .dir
+            .join(format!("{}-{}-{}.json", entry.request, entry.pid, seq));
+        fs::write(&path, serde_json::to_string(entry)?)
+            .context("Failed to write the model request journal")?;
+        Ok(path)
+    }
+
+    fn push(&self, job: Job) {
+        self.pending.lock().unwrap().push(job);
+        self.ready.notify_one();
+    }
+
+    async fn next_job(&self) -> Job {
+        loop {
+            if let Some(job) = self.pending.lock().unwrap().pop() {
+                return job;
+            }
+            self.ready.notified().await;
+        }
+    }
+
+    /// Waits until the rate limit allows another call to start.
+    async fn pace(&self) {
+        let Some(spacing) = self.spacing else {
+            return;
+        };
+        let start = {
+            let mut next = self.next_start.lock().await;
+            let start = (*next).max(Instant::now());
+            *next = start + spacing;
+            start
+        };
+        tokio::time::sleep_until(start).await;
+    }
+
+    /// Runs `prompt`, retrying transient failures, all within the deadline of one call of
+    /// the model's kind so retries can't stretch a caller's wait past it.
+    async fn attempt(&self, model: &ChatModel, prompt: &str) -> Result<String> {
+        let timeout = model.kind.timeout();
+        tokio::time::timeout(timeout, self.retry(model, prompt))
+            .await
+            .unwrap_or_else(|_| {
+                Err(anyhow::anyhow!(
+                    "{} call timed out after {}s, retries included",
+                    model.name,
+                    timeout.as_secs()
+                ))
+            })
+    }
+
+    async fn retry(&self, model: &ChatModel, prompt: &str) -> Result<String> {
+        let mut attempt = 1;
+        loop {
+            self.pace().await;
+            match model.complete_now(prompt).await {
+                Err(err) if attempt < MAX_ATTEMPTS && is_retryable(&err) => {
+                    let backoff = RETRY_BACKOFF * 2u32.pow(attempt - 1);
+                    tracing::warn!(
+                        model = %model.name,
+                        attempt,
+                        backoff_secs = backoff.as_secs(),
+                        error = %err,
+                        "Retrying model request"
+                    );
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn work(self: Arc<Self>) {
+        loop {
+            let job = self.next_job().await;
+            match job.reply {
+                Reply::Caller(mut tx) => {
+                    if !tx.is_closed() {
+                        let call = with_meter(job.meter, self.attempt(&job.model, &job.prompt));
+                        tokio::select! {
+                            result = call => {
+                                let _ = tx.send(result);
+                            }
+                            // The caller gave up; dropping the call closes its connection
+                            _ = tx.closed() => {}
+                        }
+                    }
+                }
+                Reply::Recovered(path) => match self.attempt(&job.model, &job.prompt).await {
+                    Ok(response) => {
+                        if let Err(err) = fs::write(&path, response) {
+                            tracing::warn!(%err, "Failed to save a recovered answer");
+                        }
+                    }
+                    Err(err) => tracing::warn!(%err, "Recovered model request failed"),
+                },
+            }
+            if let Some(journal) = &job.journal {
+                let _ = fs::remove_file(journal);
+            }
+        }
+    }
+
+    /// Requeues the requests of processes that are gone and drops recovered answers nobody
+    /// collected in time.
+    fn recover(&self) -> Result<()> {
+        for (path, entry) in orphaned_entries(&self.dir)? {
+            let model = match entry.model.parse::<Model>() {
+                Ok(model) => ChatModel::openai(model)?
+                    .with_options(entry.options)
+                    .for_call(entry.kind),
+                // Other providers' settings aren't journaled
+                Err(_) => {
+                    fs::remove_file(&path)?;
+                    continue;
+                }
+            };
+            tracing::info!(
+                request = %entry.request,
+                model = %entry.model,
+                enqueued_at = %entry.enqueued_at,
+                "Recovering a model request from an interrupted run"
+            );
+            self.push(Job {
+                ticket: Ticket {
+                    priority: RECOVERY_PRIORITY,
+                    seq: self.seq.fetch_add(1, Ordering::Relaxed),
+                },
+                model,
+                prompt: entry.prompt,
+                journal: Some(path),
+                reply: Reply::Recovered(self.recovered_path(&entry.request)),
+                meter: None,
+            });
+        }
+
+        let now = SystemTime::now();
+        for entry in fs::read_dir(&self.dir).into_iter().flatten() {
+            let path = entry?.path();
+            let age = fs::metadata(&path)?
+                .modified()
+                .ok()
+                .and_then(|m| now.duration_since(m).ok())
+                .unwrap_or_default();
+            if path.extension().is_some_and(|e| e == "recovered") && age > RECOVERED_RETENTION {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Journaled requests in `dir` whose process is no longer running.
+pub fn orphaned_entries(dir: &Path) -> Result<Vec<(PathBuf, JournalEntry)>> {
+    let mut orphans = Vec::new();
+    if !dir.exists() {
+        return Ok(orphans);
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|e| e != "json") {
+            continue;
+        }
+        let Ok(journal) = serde_json::from_str::<JournalEntry>(&fs::read_to_string(&path)?) else {
+            tracing::warn!(path = %path.display(), "Dropping a corrupt model request journal");
+            fs::remove_file(&path)?;
+            continue;
+        };
+        if journal.pid != process::id() && !process_alive(journal.pid) {
+            orphans.push((path, journal));
+        }
+    }
+    orphans.sort_by_key(|(_, e)| e.enqueued_at);
+    Ok(orphans)
+}
+
+/// Whether a process with this id is running. Only Linux can tell; elsewhere every process
+/// counts as running, so nothing is recovered.
+fn process_alive(pid: u32) -> bool {
+    if cfg!(target_os = "linux") {
+        Path::new("/proc").join(pid.to_string()).exists()
+    } else {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_deadline_counts_time_queued() {
+        let (tx, rx) = oneshot::channel();
+        tx.send(Ok("answer".to_string())).unwrap();
+        let answered = answer(rx, "o1-mini", Duration::from_millis(50)).await;
+        assert_eq!(answered.unwrap(), "answer");
+
+        // No worker ever picks the request up, yet the caller still gets its answer in time
+        let (tx, rx) = oneshot::channel();
+        let started = Instant::now();
+        let err = answer(rx, "o1-mini", Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(err.to_string().contains("timed out"), "{err}");
+        // and the request is withdrawn, so a worker reaching it later skips it
+        assert!(tx.is_closed());
+    }
+
+    #[test]
+    fn test_tickets_run_by_priority_then_order() {
+        let mut heap = BinaryHeap::new();
+        let kinds = [
+            CallKind::Backtest,
+            CallKind::Live,
+            CallKind::Backtest,
+            CallKind::Improvement,
+        ];
+        for (seq, kind) in kinds.iter().enumerate() {
+            heap.push(Ticket {
+                priority: kind.priority(),
+                seq: seq as u64,
+            });
+        }
+        let order = std::iter::from_fn(|| heap.pop().map(|t| t.seq)).collect::<Vec<_>>();
+        assert_eq!(order, [1, 3, 0, 2]);
+    }
+
+    #[test]
+    fn test_only_dead_processes_requests_are_orphaned() {
+        let dir = env::temp_dir().join("happycharts_llm_queue");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let entry = |pid| JournalEntry {
+            request: request_id("o1-mini", &ChatOptions::default(), "prompt"),
+            model: "o1-mini".to_string(),
+            options: ChatOptions::default(),
+            kind: CallKind::Backtest,
+            prompt: "prompt".to_string(),
+            pid,
+            enqueued_at: Utc::now(),
+        };
+        for (name, pid) in [("ours.json", process::id()), ("dead.json", u32::MAX)] {
+            fs::write(dir.join(name), serde_json::to_string(&entry(pid)).unwrap()).unwrap();
+        }
+        fs::write(dir.join("corrupt.json"), "{").unwrap();
+
+        let orphans = orphaned_entries(&dir).unwrap();
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(orphans[0].0, dir.join("dead.json"));
+        assert_eq!(orphans[0].1.pid, u32::MAX);
+        assert!(!dir.join("corrupt.json").exists());
+    }
+
+    #[test]
+    fn test_workers_restart_on_a_new_runtime() {
+        let queue = Arc::new(LlmQueue::new(
+            env::temp_dir().join("happycharts_llm_workers"),
+        ));
+        let first = tokio::runtime::Runtime::new().unwrap();
+        first.block_on(async { queue.start_workers() });
+        drop(first);
+        assert!(queue
+            .workers
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|w| w.is_finished()));
+
+        let second = tokio::runtime::Runtime::new().unwrap();
+        second.block_on(async {
+            queue.start_workers();
+            let workers = queue.workers.lock().unwrap();
+            assert_eq!(workers.len(), DEFAULT_WORKERS);
+            assert!(workers.iter().all(|w| !w.is_finished()));
+        });
+    }
+
+    #[test]
+    fn test_retryable_errors() {
+        let api = |status: u16, body: &str| {
+            anyhow::Error::new(ApiError {
+                status: reqwest::StatusCode::from_u16(status).unwrap(),
+                body: body.to_string(),
+            })
+        };
+        assert!(is_retryable(&api(503, "overloaded")));
+        assert!(is_retryable(&api(429, "rate limit reached")));
+        assert!(!is_retryable(&api(429, "insufficient_quota")));
+        assert!(!is_retryable(&api(400, "bad request")));
+        assert!(!is_retryable(&anyhow::anyhow!(
+            "o1-mini call timed out after 300s"
+        )));
+    }
+}
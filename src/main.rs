@@ -1,4 +1,1096 @@
-use happychartsv2::run_live_analysis;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{Args, Parser, Subcommand};
+use happychartsv2::analytics::AnalyticsStore;
+use happychartsv2::audit::{reconstruct_predictions, AuditStore};
+use happychartsv2::auth::{check_exposure, load_tokens, new_token, Role, API_TOKENS_FILE};
+use happychartsv2::backtest::{
+    attribute_prompt_sections, backtest_allocation, backtest_multi_asset, backtest_pair,
+    benchmark_signals, challenger_standings, check_cached_data, compare_prompts, entropy_seed,
+    evaluate_stored_range, explain_failure, export_research, grade_rationales,
+    hallucinations_by_prompt, live_calibration, live_pnl_by_prompt, paper_ledger,
+    perturbation_sensitivity, promote_challenger, prompt_leaderboard, rescore_predictions,
+    run_due_out_of_sample, session_breakdowns, shadow_standings, stress_test,
+    sweep_reasoning_effort, trade_journal, BacktestOptions, DevSession, NON_OVERLAPPING,
+};
+use happychartsv2::broker::{broker_from_name, RiskLimits};
+use happychartsv2::challenger::{HeadToHead, PROMOTION_HOURS};
+use happychartsv2::comparison::describe_comparison;
+use happychartsv2::compute::{label_candles_with, prompt_version};
+use happychartsv2::daemon;
+use happychartsv2::dashboard;
+use happychartsv2::derisk::{DeriskAction, DrawdownRule, DERISK_RESET_FILE};
+use happychartsv2::dev::{DevScore, DEV_FRESH_CALLS, DEV_WINDOWS};
+use happychartsv2::encoding::CandleEncoding;
+use happychartsv2::faults::{inject_faults, load_faults};
+use happychartsv2::funding::FundingStore;
+use happychartsv2::hallucination::describe_hallucination_rates;
+use happychartsv2::implied_vol::ImpliedVolStore;
+use happychartsv2::improvement::ImprovementLoop;
+use happychartsv2::jobs::{run_job, run_jobs, JobQueue};
+use happychartsv2::journal::{self, JOURNAL_NOTIONAL};
+use happychartsv2::leaderboard::LeaderboardEntry;
+use happychartsv2::ledger::{
+    fetch_rates, summarize_ledger, tax_lots_csv, Account, DEFAULT_CURRENCY,
+};
+use happychartsv2::liquidations::LiquidationStore;
+use happychartsv2::llm_cache::ResponseCache;
+use happychartsv2::out_of_sample::{describe_out_of_sample, OutOfSampleQueue};
+use happychartsv2::profiles::{SymbolProfile, PROFILES_FILE};
+use happychartsv2::prompt_pnl::describe_prompt_pnl;
+use happychartsv2::rationale::describe_rationale_quality;
+use happychartsv2::reconcile::{ExecutionState, ReconcilePolicy, EXECUTION_STATE_FILE};
+use happychartsv2::repl::parse_time;
+use happychartsv2::reporting;
+use happychartsv2::rescore::{describe_benchmark, describe_rescores, RescoreConfig};
+use happychartsv2::research_export::ResearchFormat;
+use happychartsv2::routing::ModelRouter;
+use happychartsv2::scenarios::{describe_stress, find_scenario, SCENARIOS};
+use happychartsv2::secrets::{self, SecretSource};
+use happychartsv2::sessions::{SessionBreakdown, WEEKDAYS};
+use happychartsv2::simulator::{ExitRule, FillModel, ImpactModel, MarginModel, FEE_RATE};
+use happychartsv2::store::{CandleStore, ARCHIVE_DIR};
+use happychartsv2::subscriptions::{load_subscriptions, SUBSCRIPTIONS_FILE};
+use happychartsv2::sweep::describe_sweep;
+use happychartsv2::thresholds::{
+    calibrate_thresholds, load_labeling, save_labeling, LabelMix, LABELING_FILE,
+};
+use happychartsv2::workspace;
+use happychartsv2::{
+    describe_prompt_cost, preview_prompt, run_live_analysis, run_live_multi_asset_analysis,
+    ChatOptions, Model, ReasoningEffort, GRANULARITY, PROMPT_FILE,
+};
+
+#[derive(Parser)]
+#[command(about = "LLM-driven crypto signal analysis and backtesting")]
+struct Cli {
+    /// Run inside workspaces/<name>, with its own prompt.txt, history, caches and
+    /// challengers; a new workspace starts from the project's prompt.txt
+    #[arg(long, global = true)]
+    workspace: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a one-off live analysis on the latest candles (default)
+    Live {
+        /// Ask for an action on every tracked symbol in one request
+        #[arg(long)]
+        multi_asset: bool,
+    },
+    /// Analyze every symbol in a profiles file on a fixed schedule, each with its own prompt
+    /// and webhook, tracking how its signals verify
+    Daemon {
+        /// JSON array of symbol profiles: symbol, and optionally prompt, context and webhook
+        #[arg(long, default_value = PROFILES_FILE)]
+        profiles: std::path::PathBuf,
+        /// JSON array of signal recipients: name and webhook, and optionally the symbols,
+        /// actions, min_confidence and UTC quiet_hours ({"start": 22, "end": 7}) each wants.
+        /// Only the profiles' webhooks are posted to without the file
+        #[arg(long, default_value = SUBSCRIPTIONS_FILE)]
+        subscriptions: std::path::PathBuf,
+        /// Minutes between runs; runs start on multiples of the interval
+        #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+        interval_minutes: u64,
+        /// Trade each signal through this broker: paper, coinbase or binance. Signals are
+        /// only logged and posted when omitted
+        #[arg(long)]
+        broker: Option<String>,
+        /// Log the orders the broker would be sent instead of placing them
+        #[arg(long)]
+        dry_run: bool,
+        /// Notional each signal's position is sized to, in USD
+        #[arg(long, default_value_t = RiskLimits::default().notional)]
+        notional: f64,
+        /// Largest position per symbol, long or short, in USD
+        #[arg(long, default_value_t = RiskLimits::default().max_position)]
+        max_position: f64,
+        /// Largest net exposure along any symbol, counting correlated positions, in USD
+        #[arg(long, default_value_t = RiskLimits::default().max_correlated)]
+        max_correlated: f64,
+        /// Starting cash of the paper broker, in USD
+        #[arg(long, default_value_t = 10_000.0)]
+        paper_cash: f64,
+        /// On startup, when the venue differs from the recorded positions and orders:
+        /// adopt the venue's, flatten everything, or halt
+        #[arg(long, default_value = "adopt")]
+        on_mismatch: ReconcilePolicy,
+        /// Cut sizing once equity falls this fraction below its peak, e.g. 0.2
+        #[arg(long)]
+        max_drawdown: Option<f64>,
+        /// What cutting sizing means: halve positions, or pause and close them
+        #[arg(long, default_value = "halve")]
+        on_drawdown: DeriskAction,
+        /// Drawdown equity must recover to before sizing returns to normal; half the
+        /// maximum drawdown when omitted
+        #[arg(long)]
+        resume_drawdown: Option<f64>,
+        /// Also serve the web dashboard on this address, e.g. 127.0.0.1:8080, and run the
+        /// backtest and sweep jobs submitted to it. Beyond localhost it needs API tokens
+        /// (see `tokens add`)
+        #[arg(long)]
+        dashboard_addr: Option<std::net::SocketAddr>,
+    },
+    /// Replay a past day through the daemon hour by hour in accelerated time: analysis,
+    /// predictions, paper trades and notifications to a sandbox webhook, to validate it
+    /// end to end before going live
+    Replay {
+        /// Day to replay (YYYY-MM-DD), in UTC
+        #[arg(long)]
+        date: NaiveDate,
+        /// How much faster than real time to run, e.g. 60x for an hour a minute
+        #[arg(long, default_value = "60x")]
+        speed: daemon::Speed,
+        /// JSON array of symbol profiles, as the daemon takes. Their webhooks are not posted to
+        #[arg(long, default_value = PROFILES_FILE)]
+        profiles: std::path::PathBuf,
+        /// Sandbox webhook every signal is posted to; signals are only logged when omitted
+        #[arg(long)]
+        webhook: Option<String>,
+        /// Notional each signal's position is sized to, in USD
+        #[arg(long, default_value_t = RiskLimits::default().notional)]
+        notional: f64,
+        /// Starting cash of the paper broker, in USD
+        #[arg(long, default_value_t = 10_000.0)]
+        paper_cash: f64,
+        /// Inject faults into the model and exchange calls per this JSON file: a seed and
+        /// the probabilities of timeout, rate_limit, malformed_json and candle_gap, e.g.
+        /// {"seed": 7, "rate_limit": 0.2}. The same seed injects the same faults
+        #[arg(long)]
+        faults: Option<std::path::PathBuf>,
+    },
+    /// Show the daemon's equity peak and drawdown de-risking, or lift it
+    Derisk {
+        /// Lift the de-risking and start the peak over on the daemon's next run
+        #[arg(long)]
+        reset: bool,
+    },
+    /// Backtest the current prompt and let the model improve it
+    Backtest {
+        /// Stop once a prompt reaches this accuracy
+        #[arg(long, default_value_t = 0.7)]
+        target: f64,
+        /// Maximum backtest-and-improve iterations
+        #[arg(long, default_value_t = 10)]
+        max_iterations: usize,
+        /// Wall-clock budget in minutes; no new iteration starts once spent
+        #[arg(long)]
+        budget_minutes: Option<u64>,
+        /// Keep a rewritten prompt only if it beats the best score so far
+        #[arg(long)]
+        validate: bool,
+        /// Seed for window selection, to reproduce an earlier run (see its manifest)
+        #[arg(long)]
+        seed: Option<u64>,
+        #[command(flatten)]
+        stride: StrideArgs,
+        /// Score a seeded sample of this many windows per iteration, confirming prompts
+        /// that reach the target on all windows
+        #[arg(long)]
+        sample: Option<usize>,
+        /// Ask o1-mini first and escalate to o1-preview on low confidence or high volatility
+        #[arg(long)]
+        route: bool,
+        /// Confidence below which a routed o1-mini answer is escalated
+        #[arg(long, default_value_t = 0.6, requires = "route")]
+        min_confidence: f64,
+        /// Leverage of simulated trades; shorts pay funding and positions can be liquidated
+        #[arg(long, default_value_t = 1.0, value_parser = parse_leverage)]
+        leverage: f64,
+        /// Seconds between a window's close and its simulated fill, priced from minute
+        /// candles, so PnL reflects the deployment's notification and execution delay
+        #[arg(long)]
+        execution_delay: Option<u64>,
+        /// Size of simulated positions in USD, filled with square-root market impact on
+        /// each candle's volume and at most a tenth of it, rather than whole at the close
+        #[arg(long)]
+        notional: Option<f64>,
+        /// Show the improver the model's analyses of the prompt's failures (see `explain`)
+        #[arg(long)]
+        postmortems: bool,
+        /// Run one loop per symbol profile in this file (see `daemon`), each improving its
+        /// own prompt against its own target and history
+        #[arg(long, num_args = 0..=1, default_missing_value = PROFILES_FILE)]
+        profiles: Option<std::path::PathBuf>,
+        #[command(flatten)]
+        chat: ChatArgs,
+    },
+    /// Backtest the current prompt at each reasoning effort and candle encoding over the
+    /// same windows, and compare accuracy and return with token cost
+    Sweep {
+        /// Efforts to compare
+        #[arg(long, value_delimiter = ',', default_value = "low,medium,high")]
+        efforts: Vec<ReasoningEffort>,
+        /// Candle encodings to compare: raw prices, percent returns or z-scores
+        #[arg(long, value_delimiter = ',', default_value = "raw")]
+        encodings: Vec<CandleEncoding>,
+        /// Seed for window selection, shared by every effort
+        #[arg(long)]
+        seed: Option<u64>,
+        #[command(flatten)]
+        stride: StrideArgs,
+        /// Score a seeded sample of this many windows per effort
+        #[arg(long)]
+        sample: Option<usize>,
+        /// Completion token allowance per call, reasoning included
+        #[arg(long)]
+        max_completion_tokens: Option<u32>,
+    },
+    /// Backtest the current prompt in multi-asset mode, scoring every symbol's action
+    /// against its own labels
+    MultiAsset {
+        /// Seed for window selection, to reproduce an earlier run (see its manifest)
+        #[arg(long)]
+        seed: Option<u64>,
+        #[command(flatten)]
+        stride: StrideArgs,
+        /// Score a seeded sample of this many windows
+        #[arg(long)]
+        sample: Option<usize>,
+    },
+    /// Backtest the current prompt in allocation mode, rebalancing a portfolio of the
+    /// tracked symbols and cash to the model's weights every hour
+    Portfolio,
+    /// Backtest the current prompt in pair mode, calling ETH against BTC and trading the
+    /// spread
+    Pair {
+        /// Seed for window selection, to reproduce an earlier run (see its manifest)
+        #[arg(long)]
+        seed: Option<u64>,
+        #[command(flatten)]
+        stride: StrideArgs,
+        /// Score a seeded sample of this many windows
+        #[arg(long)]
+        sample: Option<usize>,
+    },
+    /// Measure each prompt section's contribution by rescoring the backtest without it,
+    /// writing the result to report.html
+    Attribution {
+        /// Seed for window selection, to reproduce an earlier run (see its manifest)
+        #[arg(long)]
+        seed: Option<u64>,
+        #[command(flatten)]
+        stride: StrideArgs,
+        /// Score a seeded sample of this many windows per variant
+        #[arg(long)]
+        sample: Option<usize>,
+    },
+    /// Measure how often the model keeps its answer when one section or feature of the
+    /// prompt is removed, zeroed or shuffled, over a sample of backtest windows
+    Perturb {
+        /// Seed for window selection and shuffling, to reproduce an earlier run
+        #[arg(long)]
+        seed: Option<u64>,
+        #[command(flatten)]
+        stride: StrideArgs,
+        /// Perturb a seeded sample of this many windows
+        #[arg(long, default_value_t = 20)]
+        sample: usize,
+        /// Print the report as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Score several prompt files over the same backtest windows in one pass, sharing each
+    /// window's data and any identical requests
+    Compare {
+        /// Prompt files to compare, e.g. prompt.txt challengers/*.txt
+        #[arg(required = true)]
+        prompts: Vec<std::path::PathBuf>,
+        /// Seed for window selection, to reproduce an earlier run (see its manifest)
+        #[arg(long)]
+        seed: Option<u64>,
+        #[command(flatten)]
+        stride: StrideArgs,
+        /// Score a seeded sample of this many windows
+        #[arg(long)]
+        sample: Option<usize>,
+        /// Print the scores as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Score the current prompt over a stored date range, streaming windows from the store
+    Evaluate {
+        /// First day to evaluate (YYYY-MM-DD)
+        #[arg(long)]
+        from: NaiveDate,
+        /// Last day to evaluate, inclusive (YYYY-MM-DD)
+        #[arg(long)]
+        to: NaiveDate,
+        #[command(flatten)]
+        stride: StrideArgs,
+    },
+    /// Score the current prompt on curated historical crashes and breakouts, fetching any
+    /// candles the store lacks
+    Stress {
+        /// Scenario to run; repeat for several. Runs every scenario when omitted
+        #[arg(long = "scenario")]
+        scenarios: Vec<String>,
+        /// List the scenarios instead of running them
+        #[arg(long)]
+        list: bool,
+        /// Print the results as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Score improved prompts on the period after their improvement once its candles have
+    /// closed, recording the accuracy in the prompt history
+    OutOfSample {
+        /// List the queued evaluations instead of running the due ones
+        #[arg(long)]
+        list: bool,
+        /// Print the results as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Download candle history into the local store (resumable)
+    Fetch {
+        /// Symbol to fetch, e.g. ETH
+        #[arg(long)]
+        symbol: String,
+        /// First day to fetch (YYYY-MM-DD)
+        #[arg(long)]
+        from: NaiveDate,
+        /// Last day to fetch, inclusive (YYYY-MM-DD)
+        #[arg(long)]
+        to: NaiveDate,
+        /// Candle granularity in seconds
+        #[arg(long, default_value_t = GRANULARITY)]
+        granularity: u32,
+        /// Also download hourly liquidations for the range (needs COINGLASS_API_KEY), so
+        /// backtests include them as of each window
+        #[arg(long)]
+        liquidations: bool,
+        /// Also download the hourly Deribit DVOL index for the range (ETH and BTC only)
+        #[arg(long)]
+        implied_vol: bool,
+        /// Also download the perpetual's funding settlements for the range, so simulated
+        /// positions pay or receive the funding actually charged while they were held
+        #[arg(long)]
+        funding: bool,
+    },
+    /// Inspect the cached candle data
+    Data {
+        #[command(subcommand)]
+        command: DataCommand,
+    },
+    /// Write the DuckDB init script exposing predictions and trades as SQL views
+    Analytics,
+    /// Export every simulated and paper trade as a journal for trade-analysis tools
+    Journal {
+        /// Output file; the format follows its extension (.csv or .json)
+        #[arg(long, default_value = "journal.csv")]
+        out: std::path::PathBuf,
+        /// Entry notional every trade is sized to, in USD
+        #[arg(long, default_value_t = JOURNAL_NOTIONAL)]
+        notional: f64,
+    },
+    /// Summarize the paper ledger's realized and unrealized PnL and the account's worth in
+    /// a reporting currency, or export a year's closed lots with their cost basis and proceeds
+    Ledger {
+        /// Write the lots closed in this year (UTC) as CSV instead of summarizing
+        #[arg(long)]
+        tax_year: Option<i32>,
+        /// Output file of the tax-lot export
+        #[arg(long, default_value = "tax_lots.csv")]
+        out: std::path::PathBuf,
+        /// Entry notional every lot is sized to, in the quote currency
+        #[arg(long, default_value_t = JOURNAL_NOTIONAL)]
+        notional: f64,
+        /// Currency the paper lots are quoted and taxed in, e.g. USDC for a stablecoin account
+        #[arg(long, default_value = DEFAULT_CURRENCY)]
+        quote: String,
+        /// Currency to report PnL and balances in, converted at current Coinbase prices
+        #[arg(long, default_value = "USD")]
+        currency: String,
+        /// Starting balance as CURRENCY=AMOUNT, e.g. USDC=5000 or BTC=0.1; repeat for several
+        #[arg(long = "balance", value_parser = parse_balance)]
+        balances: Vec<(String, f64)>,
+    },
+    /// Summaries across recorded runs
+    Report {
+        #[command(subcommand)]
+        command: ReportCommand,
+    },
+    /// Compare each challenger prompt's live record with the champion's
+    Challengers {
+        /// Promote the strongest challenger if it has significantly beaten the champion
+        #[arg(long)]
+        promote: bool,
+        /// Live hours a challenger needs before it can be promoted
+        #[arg(long, default_value_t = PROMOTION_HOURS)]
+        min_hours: usize,
+    },
+    /// Compare the shadow model's live record (see SHADOW_MODEL) with the champion's
+    Shadow,
+    /// Break down backtest and live accuracy and PnL by UTC hour and weekday
+    Sessions,
+    /// Verify recorded live predictions and report target hit rate and confidence calibration
+    Calibration,
+    /// Suggest long and short labeling thresholds that label the stored history of a symbol
+    /// in the given mix, e.g. 20% long, 20% short and the rest none
+    Thresholds {
+        #[arg(long, default_value = "ETH")]
+        symbol: String,
+        /// Share of candles to label long
+        #[arg(long, default_value_t = 0.2)]
+        long: f64,
+        /// Share of candles to label short
+        #[arg(long, default_value_t = 0.2)]
+        short: f64,
+        /// Days of history to calibrate on, up to now; all stored candles when omitted
+        #[arg(long)]
+        days: Option<i64>,
+        /// Save the thresholds in the labeling config, where backtests label the symbol
+        /// with them
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Score a predictions JSONL file again under other label thresholds, fees and exit
+    /// rules, without calling the model
+    Rescore {
+        /// Predictions file, e.g. cache/analytics/predictions.jsonl
+        file: PathBuf,
+        #[arg(long, default_value = "ETH")]
+        symbol: String,
+        #[command(flatten)]
+        scoring: RescoreArgs,
+        /// Print the reports as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Score a CSV of another strategy's signals (timestamp,action) next to the recorded
+    /// predictions, on the same candles and over the period both cover
+    Benchmark {
+        /// Signals file, one timestamp,action row per signal
+        signals: PathBuf,
+        #[arg(long, default_value = "cache/analytics/predictions.jsonl")]
+        predictions: PathBuf,
+        #[arg(long, default_value = "ETH")]
+        symbol: String,
+        #[command(flatten)]
+        scoring: RescoreArgs,
+        /// Print the reports as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Write a predictions JSONL file's signals with their candles as CSV for vectorbt or
+    /// Backtrader, to cross-check the simulator
+    ExportSignals {
+        /// Predictions file, e.g. cache/analytics/predictions.jsonl
+        file: PathBuf,
+        #[arg(long, default_value = "ETH")]
+        symbol: String,
+        /// vectorbt or backtrader
+        #[arg(long)]
+        format: ResearchFormat,
+        /// Output file, e.g. eth_signals.csv
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Inspect and prune the candle store and LLM response cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    /// Score prompt.txt on a few fixed windows, replaying cached answers where possible
+    Dev {
+        /// Re-score whenever prompt.txt changes, printing how the metrics moved
+        #[arg(long)]
+        watch: bool,
+        /// Windows to score
+        #[arg(long, default_value_t = DEV_WINDOWS)]
+        windows: usize,
+        /// Model calls allowed per scoring for windows without a cached answer
+        #[arg(long, default_value_t = DEV_FRESH_CALLS)]
+        fresh_calls: usize,
+    },
+    /// Ask the model why it got a recorded backtest window wrong, and store its analysis
+    Explain {
+        /// Run to take the failure from; the latest failure of any run when omitted
+        #[arg(long)]
+        run_id: Option<String>,
+        /// Open time of the window's last candle (unix secs, RFC 3339 or YYYY-MM-DD HH:MM)
+        #[arg(long, value_parser = parse_time)]
+        window: Option<DateTime<Utc>>,
+    },
+    /// Have a second model grade recorded backtest rationales for consistency with their
+    /// action and with the data, and report rationale quality per prompt version
+    GradeRationales {
+        /// Only grade predictions of this run
+        #[arg(long)]
+        run_id: Option<String>,
+        /// Grade a seeded sample of this many predictions not graded before
+        #[arg(long, default_value_t = 50)]
+        sample: usize,
+        /// Seed for the sample
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Print the report as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Inspect what the model is sent
+    Prompt {
+        #[command(subcommand)]
+        command: PromptCommand,
+    },
+    /// Interactive session for fetching, labeling and asking about individual windows
+    Repl,
+    /// Store API keys in the OS keyring instead of the environment
+    Keys {
+        #[command(subcommand)]
+        command: KeysCommand,
+    },
+    /// Manage the API tokens the dashboard and gRPC API accept
+    Tokens {
+        #[command(subcommand)]
+        command: TokensCommand,
+    },
+    /// Run a started job of the dashboard's queue; the daemon's job runner starts one
+    /// process per job with it
+    #[command(hide = true)]
+    RunJob { id: u64 },
+    /// Serve the analysis, backtest and prompt history API over gRPC
+    #[cfg(feature = "grpc")]
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:50051")]
+        addr: std::net::SocketAddr,
+        /// Also serve GET /healthz over HTTP on this address
+        #[arg(long)]
+        health_addr: Option<std::net::SocketAddr>,
+    },
+    /// Exchange candles and results with Arrow tooling (polars, pyarrow)
+    #[cfg(feature = "arrow")]
+    Arrow {
+        #[command(subcommand)]
+        command: ArrowCommand,
+    },
+}
+
+#[derive(Args)]
+struct StrideArgs {
+    /// Candles between consecutive windows
+    #[arg(long, default_value_t = 1)]
+    stride: usize,
+    /// Use disjoint windows (a stride of one full window)
+    #[arg(long, conflicts_with = "stride")]
+    non_overlapping: bool,
+}
+
+impl StrideArgs {
+    fn get(&self) -> usize {
+        if self.non_overlapping {
+            NON_OVERLAPPING
+        } else {
+            self.stride
+        }
+    }
+}
+
+#[derive(Args)]
+struct ChatArgs {
+    /// Reasoning effort of every model call: low, medium or high
+    #[arg(long)]
+    reasoning_effort: Option<ReasoningEffort>,
+    /// Completion token allowance per call, reasoning included
+    #[arg(long)]
+    max_completion_tokens: Option<u32>,
+}
+
+impl ChatArgs {
+    fn get(&self) -> ChatOptions {
+        ChatOptions {
+            reasoning_effort: self.reasoning_effort,
+            max_completion_tokens: self.max_completion_tokens,
+        }
+    }
+}
+
+#[derive(Args)]
+struct RescoreArgs {
+    /// Next-candle move, as a fraction, that labels a long or short; repeat to compare
+    #[arg(long = "threshold")]
+    thresholds: Vec<f64>,
+    /// Fee per side, as a fraction of notional
+    #[arg(long, default_value_t = FEE_RATE)]
+    fee: f64,
+    /// next_candle, levels, fixed_hold:N, opposite_signal or trailing_stop:FRACTION; repeat
+    /// to compare
+    #[arg(long = "exit")]
+    exits: Vec<ExitRule>,
+}
+
+impl RescoreArgs {
+    /// Every threshold with every exit rule, the defaults standing in for either left out.
+    fn get(&self) -> Vec<RescoreConfig> {
+        let default = RescoreConfig::default();
+        let thresholds = if self.thresholds.is_empty() {
+            vec![default.threshold]
+        } else {
+            self.thresholds.clone()
+        };
+        let exits = if self.exits.is_empty() {
+            vec![default.exit]
+        } else {
+            self.exits.clone()
+        };
+        thresholds
+            .iter()
+            .flat_map(|&threshold| {
+                exits.iter().map(move |&exit| RescoreConfig {
+                    threshold,
+                    fee: self.fee,
+                    exit,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Subcommand)]
+enum DataCommand {
+    /// Scan cached candles for gaps, duplicates, out-of-order timestamps and outliers
+    Check,
+}
+
+#[derive(Subcommand)]
+enum ReportCommand {
+    /// Rank every evaluated prompt version by accuracy, simulated return and cost, with
+    /// its runs and its diff from the current prompt
+    Leaderboard {
+        /// Print the full leaderboard as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Attribute verified live signals and their paper trades to the prompt version that
+    /// produced them, flagging versions that trade worse than their predecessor
+    LivePnl {
+        /// Print the records as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// How often each prompt version's rationales cited prices absent from their data
+    Hallucinations {
+        /// Print the rates as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print every recorded prediction about a window with the exact prompt and response
+    /// behind it, as JSON
+    Audit {
+        /// Window id from a prediction record's metadata, e.g. ETH:1710892800-1710975600
+        window_id: String,
+        /// Only the predictions of this run
+        #[arg(long)]
+        run_id: Option<String>,
+    },
+    /// Print everything behind a live signal as JSON: the exact prompt, the raw response,
+    /// the parsed prediction, the input data hash and the settings
+    Explain {
+        /// Signal id, e.g. ETH-20240320T140000.123, as linked from the dashboard
+        signal_id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// List cached candle series and LLM responses with their size and freshness
+    Ls,
+    /// Remove LLM responses older than the given age
+    Prune {
+        /// Maximum age to keep, in days
+        #[arg(long)]
+        older_than_days: u64,
+        /// Also drop candles older than the cutoff from the candle store
+        #[arg(long)]
+        candles: bool,
+    },
+    /// Roll stored candles older than the retention horizon into daily candles, keeping
+    /// the originals in the archive (export them to Parquet with `arrow export --archive`)
+    Compact {
+        /// Whole days of intraday candles to keep
+        #[arg(long, default_value_t = 365)]
+        retention_days: u64,
+        /// Drop the rolled-up candles instead of archiving them
+        #[arg(long)]
+        no_archive: bool,
+    },
+    /// Delete cached data (both caches unless one is selected)
+    Clear {
+        /// Only clear the LLM response cache
+        #[arg(long, conflicts_with = "candles")]
+        llm: bool,
+        /// Only clear the candle store
+        #[arg(long)]
+        candles: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum PromptCommand {
+    /// Print the exact prompt for a window, with its token count and cost, without asking
+    /// the model
+    Preview {
+        /// Open time of the window's last candle (unix secs, RFC 3339 or YYYY-MM-DD HH:MM);
+        /// the latest window when omitted
+        #[arg(long, value_parser = parse_time)]
+        at: Option<DateTime<Utc>>,
+        /// Model whose context and price to assume
+        #[arg(long, default_value = "o1-mini")]
+        model: Model,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeysCommand {
+    /// Show where each key the app reads is found, without printing it
+    Ls,
+    /// Save a key to the keyring, reading its value from stdin
+    Set {
+        /// e.g. OPENAI_API_KEY, or OPENAI_API_KEYS with several comma-separated keys
+        name: String,
+    },
+    /// Remove a key from the keyring
+    Delete { name: String },
+}
+
+#[derive(Subcommand)]
+enum TokensCommand {
+    /// Create a token and print it; only its hash is kept
+    Add {
+        name: String,
+        /// read, or admin to also trigger analyses and backtests and promote prompts
+        #[arg(long, default_value = "read")]
+        role: Role,
+    },
+    /// List the tokens' names and roles
+    Ls,
+    /// Remove a token
+    Revoke { name: String },
+}
+
+#[cfg(feature = "arrow")]
+#[derive(Subcommand)]
+enum ArrowCommand {
+    /// Write a stored candle series to an Arrow IPC or, by its extension, Parquet file
+    Export {
+        #[arg(long)]
+        symbol: String,
+        #[arg(long, default_value_t = GRANULARITY)]
+        granularity: u32,
+        /// Export the candles compaction archived rather than the store's
+        #[arg(long)]
+        archive: bool,
+        /// Output file, e.g. eth.arrow or eth.parquet
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Merge candles from an Arrow IPC or Parquet file into the candle store
+    Import {
+        #[arg(long)]
+        symbol: String,
+        #[arg(long, default_value_t = GRANULARITY)]
+        granularity: u32,
+        /// Input file with time/open/high/low/close/volume columns
+        #[arg(long)]
+        path: PathBuf,
+    },
+    /// Write all recorded predictions and trades as predictions.arrow and trades.arrow
+    Results {
+        /// Output directory
+        #[arg(long)]
+        out: PathBuf,
+    },
+}
+
+#[cfg(feature = "arrow")]
+fn run_arrow_command(command: ArrowCommand) -> anyhow::Result<()> {
+    use happychartsv2::arrow_io;
+
+    match command {
+        ArrowCommand::Export {
+            symbol,
+            granularity,
+            archive,
+            out,
+        } => {
+            let store = if archive {
+                CandleStore::new(ARCHIVE_DIR)
+            } else {
+                CandleStore::default()
+            };
+            let candles = store.load(&symbol, granularity)?;
+            arrow_io::write_batches(&out, &[arrow_io::candles_to_batch(&symbol, &candles)?])?;
+            tracing::info!(%symbol, candles = candles.len(), out = %out.display(), "Exported candles");
+        }
+        ArrowCommand::Import {
+            symbol,
+            granularity,
+            path,
+        } => {
+            let mut candles = Vec::new();
+            for batch in arrow_io::read_batches(&path)? {
+                candles.extend(arrow_io::batch_to_candles(&batch)?);
+            }
+            let added = CandleStore::default().merge(&symbol, granularity, &candles)?;
+            tracing::info!(%symbol, added, "Imported candles");
+        }
+        ArrowCommand::Results { out } => {
+            let store = AnalyticsStore::default();
+            std::fs::create_dir_all(&out)?;
+            arrow_io::write_ipc(
+                &out.join("predictions.arrow"),
+                &[arrow_io::predictions_to_batch(&store.predictions()?)?],
+            )?;
+            arrow_io::write_ipc(
+                &out.join("trades.arrow"),
+                &[arrow_io::trades_to_batch(&store.trades()?)?],
+            )?;
+            tracing::info!(out = %out.display(), "Exported predictions and trades");
+        }
+    }
+    Ok(())
+}
+
+fn format_time(ts: Option<f64>) -> String {
+    ts.and_then(|t| DateTime::from_timestamp(t as i64, 0))
+        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn format_age(modified: Option<SystemTime>) -> String {
+    modified
+        .and_then(|m| SystemTime::now().duration_since(m).ok())
+        .map(|age| format!("{:.1}h ago", age.as_secs_f64() / 3600.0))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// How often `dev --watch` checks prompt.txt for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+async fn run_dev(watch: bool, windows: usize, fresh_calls: usize) -> anyhow::Result<()> {
+    let session = DevSession::load(windows).await?;
+    let mut previous: Option<DevScore> = None;
+    let mut seen = None;
+    loop {
+        let modified = std::fs::metadata(PROMPT_FILE)?.modified()?;
+        if seen != Some(modified) {
+            seen = Some(modified);
+            let prompt = std::fs::read_to_string(PROMPT_FILE)?;
+            if previous
+                .as_ref()
+                .is_none_or(|p| p.prompt_version != prompt_version(&prompt))
+            {
+                // A failed scoring shouldn't end the session; the next save tries again
+                match session.score(&prompt, fresh_calls).await {
+                    Ok(score) => {
+                        println!("{}", score.describe(previous.as_ref()));
+                        previous = Some(score);
+                    }
+                    Err(err) if watch => eprintln!("Scoring failed: {:#}", err),
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+        if !watch {
+            return Ok(());
+        }
+        tokio::time::sleep(WATCH_INTERVAL).await;
+    }
+}
+
+fn run_keys_command(command: KeysCommand) -> anyhow::Result<()> {
+    match command {
+        KeysCommand::Ls => {
+            for name in secrets::KNOWN_KEYS {
+                let source = match secrets::secret_with_source(name) {
+                    Some((_, SecretSource::Keyring)) => "keyring",
+                    Some((_, SecretSource::Env)) => "environment",
+                    None => "not set",
+                };
+                println!("{:<24} {}", name, source);
+            }
+        }
+        KeysCommand::Set { name } => {
+            let mut value = String::new();
+            std::io::stdin().read_line(&mut value)?;
+            let value = value.trim();
+            anyhow::ensure!(!value.is_empty(), "No key given on stdin");
+            secrets::store_secret(&name, value)?;
+            println!("Stored {} in the keyring", name);
+        }
+        KeysCommand::Delete { name } => {
+            secrets::delete_secret(&name)?;
+            println!("Removed {} from the keyring", name);
+        }
+    }
+    Ok(())
+}
+
+fn run_tokens_command(command: TokensCommand) -> anyhow::Result<()> {
+    let path = std::path::Path::new(API_TOKENS_FILE);
+    let mut tokens = load_tokens(path)?.unwrap_or_default();
+    match command {
+        TokensCommand::Add { name, role } => {
+            let token = new_token()?;
+            tokens.add(&name, &token, role);
+            std::fs::write(path, serde_json::to_string_pretty(&tokens)?)?;
+            println!("{}", token);
+            eprintln!(
+                "Saved {} as a {:?} token; it is not shown again",
+                name, role
+            );
+        }
+        TokensCommand::Ls => {
+            for t in &tokens.0 {
+                println!("{:<24} {:?}", t.name, t.role);
+            }
+        }
+        TokensCommand::Revoke { name } => {
+            let before = tokens.0.len();
+            tokens.0.retain(|t| t.name != name);
+            anyhow::ensure!(tokens.0.len() < before, "No token named {:?}", name);
+            std::fs::write(path, serde_json::to_string_pretty(&tokens)?)?;
+        }
+    }
+    Ok(())
+}
+
+fn list_caches() -> anyhow::Result<()> {
+    let store = CandleStore::default();
+    println!("Candle store ({}):", store.dir().display());
+    for s in store.summaries()? {
+        println!(
+            "  {:<6} {:>6}s {:>7} candles  {} -> {}  {:>9} bytes  updated {}",
+            s.symbol,
+            s.granularity,
+            s.candles,
+            format_time(s.first),
+            format_time(s.last),
+            s.bytes,
+            format_age(s.modified)
+        );
+    }
+
+    let cache = ResponseCache::default();
+    let stats = cache.stats()?;
+    println!("LLM response cache ({}):", cache.dir().display());
+    println!(
+        "  {} responses  {} bytes  oldest {}  newest {}",
+        stats.entries,
+        stats.bytes,
+        format_age(stats.oldest),
+        format_age(stats.newest)
+    );
+
+    Ok(())
+}
+
+fn print_leaderboard(board: &[LeaderboardEntry]) {
+    println!(
+        "{:>4}  {:<16} {:<8} {:>8} {:>8} {:>9} {:>10}  {:<9}  runs",
+        "rank", "prompt", "encoding", "windows", "accuracy", "return", "cost/win", "diff"
+    );
+    for e in board {
+        let diff = match &e.diff {
+            Some(d) => format!("+{} -{}", d.added.len(), d.removed.len()),
+            None if e.champion => "champion".to_string(),
+            None => "-".to_string(),
+        };
+        println!(
+            "{:>4}  {:<16} {:<8} {:>8} {:>7.2}% {:>9} {:>10}  {:<9}  {}",
+            e.rank,
+            e.prompt_version,
+            e.encoding.as_str(),
+            e.windows,
+            e.accuracy * 100.0,
+            e.total_return
+                .map(|r| format!("{:+.2}%", r * 100.0))
+                .unwrap_or_else(|| "-".to_string()),
+            e.cost_per_window
+                .map(|c| format!("{:.2}", c))
+                .unwrap_or_else(|| "-".to_string()),
+            diff,
+            if e.links.is_empty() {
+                e.runs.join(", ")
+            } else {
+                e.links.join(", ")
+            }
+        );
+    }
+}
+
+fn print_standings(standings: &[HeadToHead], min_hours: usize) {
+    for h in standings {
+        println!(
+            "{}  {:>4}h  champion {:>4}  challenger {:>4}  z {:+.2}{}",
+            h.challenger,
+            h.hours,
+            h.champion_correct,
+            h.challenger_correct,
+            h.z_score(),
+            if h.beats_champion(min_hours) {
+                "  ready"
+            } else {
+                ""
+            }
+        );
+    }
+}
+
+fn print_sessions(title: &str, sessions: &SessionBreakdown) {
+    println!("{} by UTC hour / weekday:", title);
+    let labels = (0..24)
+        .map(|h| format!("{:02}h", h))
+        .chain(WEEKDAYS.iter().map(|d| d.to_string()));
+    for (label, s) in labels.zip(sessions.by_hour.iter().chain(&sessions.by_weekday)) {
+        if s.windows == 0 && s.trades == 0 {
+            continue;
+        }
+        println!(
+            "  {:<4} {:>5} windows {:>6.2}%  {:>5} trades {:>+8.2}%",
+            label,
+            s.windows,
+            s.accuracy * 100.0,
+            s.trades,
+            s.total_return * 100.0
+        );
+    }
+}
+
+fn parse_leverage(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(l) if l >= 1.0 => Ok(l),
+        _ => Err("leverage must be a number of at least 1".to_string()),
+    }
+}
+
+fn parse_balance(s: &str) -> Result<(String, f64), String> {
+    let (currency, amount) = s
+        .split_once('=')
+        .ok_or_else(|| "balance must be CURRENCY=AMOUNT".to_string())?;
+    let amount = amount
+        .parse()
+        .map_err(|_| format!("invalid amount for {}: {}", currency, amount))?;
+    Ok((currency.to_uppercase(), amount))
+}
+
+/// `[from 00:00, day after to 00:00)` in UTC.
+fn day_range(from: NaiveDate, to: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    let start = from.and_hms_opt(0, 0, 0).unwrap().and_utc();
+    let end = to
+        .succ_opt()
+        .unwrap_or(to)
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc();
+    (start, end)
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -10,26 +1102,753 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with_env_filter("happychartsv2=debug")
         .init();
 
-    tracing::info!("Starting backtest and improvement process...");
-
-    // Run the backtesting and prompt improvement
-    // let mut counter = 0;
-    // while {
-    //     let res = happychartsv2::backtest::run_backtest_and_improve()
-    //         .await
-    //         .map_err(|e| {
-    //             tracing::error!(error=?e, "Backtest and improvement failed");
-    //             e
-    //         })?;
-    //     counter += 1;
-    //     tracing::info!(score=?res, %counter, "Backtest and improvement completed successfully");
-    //     res < 0.7 && counter < 10
-    // } {}
-
-    // tracing::info!("Backtest and improvement completed successfully.");
-
-    let res = run_live_analysis().await?;
-    tracing::info!(score=?res, "Live analysis completed successfully");
+    reporting::init()?;
+
+    let cli = Cli::parse();
+    if let Some(name) = &cli.workspace {
+        let dir = workspace::enter(name)?;
+        tracing::info!(workspace = %name, dir = %dir.display(), "Using workspace");
+    }
+    let workspace = cli.workspace.unwrap_or_default();
+    let result = run_command(cli.command.unwrap_or(Command::Live { multi_asset: false })).await;
+    if let Err(err) = &result {
+        let prompt_version = std::fs::read_to_string(PROMPT_FILE)
+            .map(|p| prompt_version(&p))
+            .unwrap_or_default();
+        let command = std::env::args()
+            .skip(1)
+            .find(|a| !a.starts_with('-') && *a != workspace)
+            .unwrap_or_else(|| "live".to_string());
+        reporting::capture_error(
+            err.as_ref(),
+            &[
+                ("command", command),
+                ("prompt_version", prompt_version),
+                ("workspace", workspace),
+            ],
+        )
+        .await;
+    }
+    result
+}
+
+async fn run_command(command: Command) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::Live { multi_asset: false } => {
+            let res = run_live_analysis().await?;
+            tracing::info!(score=?res, "Live analysis completed successfully");
+        }
+        Command::Daemon {
+            profiles,
+            subscriptions,
+            interval_minutes,
+            broker,
+            dry_run,
+            notional,
+            max_position,
+            max_correlated,
+            paper_cash,
+            on_mismatch,
+            max_drawdown,
+            on_drawdown,
+            resume_drawdown,
+            dashboard_addr,
+        } => {
+            let profiles = daemon::load_profiles(&profiles)?;
+            let subscriptions = load_subscriptions(&subscriptions)?;
+            if let Some(addr) = dashboard_addr {
+                let tokens = load_tokens(std::path::Path::new(API_TOKENS_FILE))?;
+                check_exposure(addr, tokens.as_ref())?;
+                tokio::spawn(async move {
+                    if let Err(err) = dashboard::serve_dashboard(addr, tokens).await {
+                        tracing::error!(%err, "Dashboard stopped");
+                    }
+                });
+                tokio::spawn(async {
+                    if let Err(err) = run_jobs(JobQueue::default()).await {
+                        tracing::error!(%err, "Job runner stopped");
+                    }
+                });
+            }
+            let execution = match broker {
+                Some(name) => Some(daemon::Execution {
+                    broker: broker_from_name(&name, dry_run, paper_cash)?,
+                    limits: RiskLimits {
+                        notional,
+                        max_position,
+                        max_correlated,
+                        ..Default::default()
+                    },
+                    policy: on_mismatch,
+                    drawdown: max_drawdown.map(|threshold| DrawdownRule {
+                        threshold,
+                        resume_below: resume_drawdown.unwrap_or(threshold / 2.0),
+                        action: on_drawdown,
+                    }),
+                }),
+                None => None,
+            };
+            let interval = Duration::from_secs(interval_minutes * 60);
+            daemon::run_daemon(profiles, subscriptions, interval, execution).await?;
+        }
+        Command::Replay {
+            date,
+            speed,
+            profiles,
+            webhook,
+            notional,
+            paper_cash,
+            faults,
+        } => {
+            let profiles = daemon::load_profiles(&profiles)?;
+            if let Some(path) = faults {
+                inject_faults(load_faults(&path)?);
+            }
+            let execution = daemon::Execution {
+                broker: broker_from_name("paper", false, paper_cash)?,
+                limits: RiskLimits {
+                    notional,
+                    ..Default::default()
+                },
+                policy: ReconcilePolicy::Adopt,
+                drawdown: None,
+            };
+            daemon::run_replay(profiles, date, speed, webhook, execution).await?;
+        }
+        Command::Derisk { reset } => {
+            if reset {
+                let path = std::path::Path::new(DERISK_RESET_FILE);
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::write(path, "")?;
+                println!("De-risking will be lifted on the daemon's next run");
+            } else {
+                let state = ExecutionState::load(std::path::Path::new(EXECUTION_STATE_FILE))?;
+                println!("{}", serde_json::to_string_pretty(&state.drawdown)?);
+            }
+        }
+        Command::Live { multi_asset: true } => {
+            for (symbol, action, rationale) in run_live_multi_asset_analysis().await? {
+                tracing::info!(%symbol, ?action, %rationale, "Live signal");
+            }
+        }
+        Command::Pair {
+            seed,
+            stride,
+            sample,
+        } => {
+            let report = backtest_pair(&BacktestOptions {
+                seed,
+                stride: stride.get(),
+                sample,
+                ..Default::default()
+            })
+            .await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Command::Portfolio => {
+            let summary = backtest_allocation().await?;
+            println!("{}", serde_json::to_string_pretty(&summary)?);
+        }
+        Command::MultiAsset {
+            seed,
+            stride,
+            sample,
+        } => {
+            let scores = backtest_multi_asset(&BacktestOptions {
+                seed,
+                stride: stride.get(),
+                sample,
+                ..Default::default()
+            })
+            .await?;
+            println!("{}", serde_json::to_string_pretty(&scores)?);
+        }
+        Command::Backtest {
+            target,
+            max_iterations,
+            budget_minutes,
+            validate,
+            seed,
+            stride,
+            sample,
+            route,
+            min_confidence,
+            leverage,
+            execution_delay,
+            notional,
+            postmortems,
+            profiles,
+            chat,
+        } => {
+            // Earlier improvements whose unseen period has closed since the last run
+            match run_due_out_of_sample().await {
+                Ok(results) if !results.is_empty() => {
+                    print!("{}", describe_out_of_sample(&results))
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!(error = ?e, "Out-of-sample evaluation failed"),
+            }
+            tracing::info!("Starting backtest and improvement process...");
+            let profiles = match profiles {
+                Some(path) => daemon::load_profiles(&path)?,
+                None => vec![SymbolProfile::default()],
+            };
+            let improvement = ImprovementLoop {
+                target_accuracy: target,
+                max_iterations,
+                budget: budget_minutes.map(|m| Duration::from_secs(m * 60)),
+                validation_gating: validate,
+                backtest: BacktestOptions {
+                    seed,
+                    stride: stride.get(),
+                    sample,
+                    routing: route.then(|| ModelRouter {
+                        min_confidence,
+                        ..Default::default()
+                    }),
+                    margin: MarginModel {
+                        leverage,
+                        ..Default::default()
+                    },
+                    chat: chat.get(),
+                    execution_delay: execution_delay.map(Duration::from_secs),
+                    fills: notional.map(|n| FillModel::new(n, ImpactModel::default())),
+                    ..Default::default()
+                },
+                postmortems,
+            };
+            let single = profiles.len() == 1;
+            let mut summaries = serde_json::Map::new();
+            for profile in profiles {
+                tracing::info!(symbol = %profile.symbol, prompt = %profile.prompt.display(), "Improving symbol prompt");
+                let mut improvement = improvement.clone();
+                improvement.backtest.profile = profile;
+                let summary = improvement.run().await.map_err(|e| {
+                    tracing::error!(error=?e, "Backtest and improvement failed");
+                    e
+                })?;
+                tracing::info!(?summary, "Backtest and improvement completed successfully.");
+                if single {
+                    println!("{}", serde_json::to_string_pretty(&summary)?);
+                } else {
+                    summaries.insert(
+                        improvement.backtest.profile.symbol,
+                        serde_json::to_value(&summary)?,
+                    );
+                }
+            }
+            if !single {
+                println!("{}", serde_json::to_string_pretty(&summaries)?);
+            }
+        }
+        Command::Sweep {
+            efforts,
+            encodings,
+            seed,
+            stride,
+            sample,
+            max_completion_tokens,
+        } => {
+            let options = BacktestOptions {
+                seed,
+                stride: stride.get(),
+                sample,
+                chat: ChatOptions {
+                    max_completion_tokens,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let points = sweep_reasoning_effort(&options, &efforts, &encodings).await?;
+            print!("{}", describe_sweep(&points));
+        }
+        Command::Attribution {
+            seed,
+            stride,
+            sample,
+        } => {
+            let report = attribute_prompt_sections(&BacktestOptions {
+                seed,
+                stride: stride.get(),
+                sample,
+                ..Default::default()
+            })
+            .await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Command::Perturb {
+            seed,
+            stride,
+            sample,
+            json,
+        } => {
+            let report = perturbation_sensitivity(&BacktestOptions {
+                seed,
+                stride: stride.get(),
+                sample: Some(sample),
+                ..Default::default()
+            })
+            .await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{}", report.describe());
+            }
+        }
+        Command::Compare {
+            prompts,
+            seed,
+            stride,
+            sample,
+            json,
+        } => {
+            let prompts = prompts
+                .iter()
+                .map(|path| Ok((path.display().to_string(), std::fs::read_to_string(path)?)))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            let scores = compare_prompts(
+                &prompts,
+                &BacktestOptions {
+                    seed,
+                    stride: stride.get(),
+                    sample,
+                    ..Default::default()
+                },
+            )
+            .await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&scores)?);
+            } else {
+                print!("{}", describe_comparison(&scores));
+            }
+        }
+        Command::Evaluate { from, to, stride } => {
+            let (start, end) = day_range(from, to);
+            let metrics = evaluate_stored_range(start, end, stride.get()).await?;
+            println!(
+                "{} windows  accuracy {:.2}%  baseline {:.2}%  {:?}",
+                metrics.windows,
+                metrics.accuracy() * 100.0,
+                metrics.baseline_accuracy() * 100.0,
+                metrics.simulation()
+            );
+            println!(
+                "{:.2} windows/s  latency {:?}",
+                metrics.throughput(),
+                metrics.latency.summary()
+            );
+            println!("~{:.1} independent samples", metrics.effective_samples);
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&metrics.calibration.report())?
+            );
+        }
+        Command::Stress {
+            scenarios,
+            list,
+            json,
+        } => {
+            if list {
+                for s in &SCENARIOS {
+                    println!("{:<28} {} to {}  {}", s.name, s.start, s.end, s.description);
+                }
+                return Ok(());
+            }
+            let selected = if scenarios.is_empty() {
+                SCENARIOS.iter().collect()
+            } else {
+                scenarios
+                    .iter()
+                    .map(|name| {
+                        find_scenario(name).ok_or_else(|| {
+                            format!("Unknown scenario {:?}; see `stress --list`", name)
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+            };
+            let results = stress_test(&selected).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&results)?);
+            } else {
+                print!("{}", describe_stress(&results));
+            }
+        }
+        Command::OutOfSample { list, json } => {
+            if list {
+                for job in OutOfSampleQueue::default().jobs()? {
+                    println!(
+                        "{:<6} {:<16} in-sample {:>5.1}%  due {}",
+                        job.profile.symbol,
+                        prompt_version(&job.prompt),
+                        job.in_sample * 100.0,
+                        job.due_at().format("%Y-%m-%d %H:%M")
+                    );
+                }
+                return Ok(());
+            }
+            let results = run_due_out_of_sample().await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&results)?);
+            } else {
+                print!("{}", describe_out_of_sample(&results));
+            }
+        }
+        Command::Fetch {
+            symbol,
+            from,
+            to,
+            granularity,
+            liquidations,
+            implied_vol,
+            funding,
+        } => {
+            let (start, end) = day_range(from, to);
+            let added = CandleStore::default()
+                .fetch_history(&symbol, granularity, start, end)
+                .await?;
+            tracing::info!(%symbol, added, "Fetch completed");
+            if liquidations {
+                let added = LiquidationStore::default()
+                    .fetch_history(&symbol, start, end)
+                    .await?;
+                tracing::info!(%symbol, added, "Liquidation fetch completed");
+            }
+            if implied_vol {
+                let added = ImpliedVolStore::default()
+                    .fetch_history(&symbol, start, end)
+                    .await?;
+                tracing::info!(%symbol, added, "DVOL fetch completed");
+            }
+            if funding {
+                let added = FundingStore::default()
+                    .fetch_history(&symbol, start, end)
+                    .await?;
+                tracing::info!(%symbol, added, "Funding fetch completed");
+            }
+        }
+        Command::Data {
+            command: DataCommand::Check,
+        } => {
+            let reports = check_cached_data()?;
+            for report in &reports {
+                if report.is_clean() {
+                    tracing::info!(symbol = %report.symbol, candles = report.candles, "No data issues found");
+                } else {
+                    tracing::warn!(
+                        symbol = %report.symbol,
+                        gaps = report.gaps.len(),
+                        duplicates = report.duplicates.len(),
+                        out_of_order = report.out_of_order,
+                        anomalies = report.anomalies.len(),
+                        "Data issues found"
+                    );
+                }
+            }
+            println!("{}", serde_json::to_string_pretty(&reports)?);
+        }
+        Command::Journal { out, notional } => {
+            let journal = trade_journal(notional).await?;
+            let body = match out.extension().and_then(|e| e.to_str()) {
+                Some("json") => serde_json::to_string_pretty(&journal)?,
+                _ => journal::to_csv(&journal),
+            };
+            std::fs::write(&out, body)?;
+            tracing::info!(trades = journal.len(), out = %out.display(), "Wrote trade journal");
+        }
+        Command::Ledger {
+            tax_year,
+            out,
+            notional,
+            quote,
+            currency,
+            balances,
+        } => {
+            let lots = paper_ledger(notional, &quote.to_uppercase()).await?;
+            if let Some(year) = tax_year {
+                std::fs::write(&out, tax_lots_csv(&lots, year))?;
+                tracing::info!(year, out = %out.display(), "Wrote tax lots");
+                return Ok(());
+            }
+            let mut account = Account::default();
+            for (held, amount) in &balances {
+                account.deposit(held, *amount);
+            }
+            account.settle(&lots);
+            let mut currencies = account.balances.keys().cloned().collect::<Vec<_>>();
+            for lot in &lots {
+                if !currencies.contains(&lot.currency) {
+                    currencies.push(lot.currency.clone());
+                }
+            }
+            let rates = fetch_rates(&currency.to_uppercase(), &currencies).await?;
+            let (value, unpriced) = account.value(&rates);
+            let report = serde_json::json!({
+                "summary": summarize_ledger(&lots, &rates),
+                "balances": account.balances,
+                "value": value,
+                "currency": rates.reporting,
+                "unpriced": unpriced,
+            });
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Command::Report {
+            command: ReportCommand::Leaderboard { json },
+        } => {
+            let board = prompt_leaderboard().await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&board)?);
+            } else {
+                print_leaderboard(&board);
+            }
+        }
+        Command::Report {
+            command: ReportCommand::LivePnl { json },
+        } => {
+            let records = live_pnl_by_prompt().await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&records)?);
+            } else {
+                print!("{}", describe_prompt_pnl(&records));
+            }
+        }
+        Command::Report {
+            command: ReportCommand::Hallucinations { json },
+        } => {
+            let rates = hallucinations_by_prompt()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&rates)?);
+            } else {
+                print!("{}", describe_hallucination_rates(&rates));
+            }
+        }
+        Command::Report {
+            command: ReportCommand::Audit { window_id, run_id },
+        } => {
+            let reconstructions = reconstruct_predictions(&window_id, run_id.as_deref()).await?;
+            println!("{}", serde_json::to_string_pretty(&reconstructions)?);
+        }
+        Command::Report {
+            command: ReportCommand::Explain { signal_id },
+        } => match AuditStore::default().bundle(&signal_id)? {
+            Some(bundle) => println!("{}", serde_json::to_string_pretty(&bundle)?),
+            None => println!("No explanation bundle for signal {}", signal_id),
+        },
+        Command::Challengers { promote, min_hours } => {
+            print_standings(&challenger_standings().await?, min_hours);
+            if promote {
+                match promote_challenger(min_hours).await? {
+                    Some(h) => println!("Promoted {} to champion", h.challenger),
+                    None => println!("No challenger has beaten the champion yet"),
+                }
+            }
+        }
+        Command::Shadow => print_standings(&shadow_standings().await?, PROMOTION_HOURS),
+        Command::Sessions => {
+            let (backtest, live) = session_breakdowns().await?;
+            print_sessions("Backtest", &backtest);
+            print_sessions("Live", &live);
+        }
+        Command::Analytics => {
+            let path = AnalyticsStore::default().write_duckdb_init()?;
+            println!(
+                "Run `duckdb -init {}` to query predictions and trades",
+                path.display()
+            );
+        }
+        Command::Calibration => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&live_calibration().await?)?
+            );
+        }
+        Command::Thresholds {
+            symbol,
+            long,
+            short,
+            days,
+            apply,
+        } => {
+            let store = CandleStore::default();
+            let candles = match days {
+                Some(days) => {
+                    let now = Utc::now();
+                    store.load_range(
+                        &symbol,
+                        GRANULARITY,
+                        now - chrono::Duration::days(days),
+                        now,
+                    )?
+                }
+                None => store.load(&symbol, GRANULARITY)?,
+            };
+            let calibration = calibrate_thresholds(&symbol, &candles, long, short)?;
+            let path = std::path::Path::new(LABELING_FILE);
+            let mut labeling = load_labeling(path)?;
+            let (current_long, current_short) = labeling.thresholds(&symbol);
+            let current = LabelMix::of(&label_candles_with(&candles, current_long, current_short));
+            println!(
+                "{} over {} candles: long at {:.4}, short at {:.4}\n\
+                 labels {:.1}% long, {:.1}% short, {:.1}% none \
+                 (now {:.4}/{:.4}: {:.1}% long, {:.1}% short, {:.1}% none)",
+                symbol,
+                calibration.candles,
+                calibration.long_threshold,
+                calibration.short_threshold,
+                calibration.achieved.long * 100.0,
+                calibration.achieved.short * 100.0,
+                calibration.achieved.none * 100.0,
+                current_long,
+                current_short,
+                current.long * 100.0,
+                current.short * 100.0,
+                current.none * 100.0
+            );
+            if apply {
+                labeling.set(calibration);
+                save_labeling(path, &labeling)?;
+                println!("Saved to {}", LABELING_FILE);
+            }
+        }
+        Command::Rescore {
+            file,
+            symbol,
+            scoring,
+            json,
+        } => {
+            let reports = rescore_predictions(&file, &symbol, &scoring.get()).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&reports)?);
+            } else {
+                print!("{}", describe_rescores(&reports));
+            }
+        }
+        Command::Benchmark {
+            signals,
+            predictions,
+            symbol,
+            scoring,
+            json,
+        } => {
+            let (recorded, external) =
+                benchmark_signals(&predictions, &signals, &symbol, &scoring.get()).await?;
+            if json {
+                let report = serde_json::json!({ "predictions": recorded, "signals": external });
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!(
+                    "{}",
+                    describe_benchmark(&[("predictions", recorded), ("signals", external)])
+                );
+            }
+        }
+        Command::ExportSignals {
+            file,
+            symbol,
+            format,
+            out,
+        } => {
+            std::fs::write(&out, export_research(&file, &symbol, format).await?)?;
+            tracing::info!(%symbol, ?format, out = %out.display(), "Exported signals");
+        }
+        Command::Cache { command } => match command {
+            CacheCommand::Ls => list_caches()?,
+            CacheCommand::Prune {
+                older_than_days,
+                candles,
+            } => {
+                let max_age = Duration::from_secs(older_than_days * 24 * 3600);
+                let removed = ResponseCache::default().prune(max_age)?;
+                tracing::info!(removed, "Pruned LLM response cache");
+                if candles {
+                    let cutoff = Utc::now() - chrono::Duration::days(older_than_days as i64);
+                    let removed = CandleStore::default().prune_before(cutoff)?;
+                    tracing::info!(removed, %cutoff, "Pruned candle store");
+                }
+            }
+            CacheCommand::Compact {
+                retention_days,
+                no_archive,
+            } => {
+                let cutoff = Utc::now() - chrono::Duration::days(retention_days as i64);
+                let archive = (!no_archive).then(|| CandleStore::new(ARCHIVE_DIR));
+                let compaction = CandleStore::default().compact(cutoff, archive.as_ref())?;
+                println!(
+                    "Rolled {} candles of {} series into {} daily candles{}",
+                    compaction.rolled,
+                    compaction.series,
+                    compaction.days,
+                    if no_archive {
+                        String::new()
+                    } else {
+                        format!("; the originals are archived in {}", ARCHIVE_DIR)
+                    }
+                );
+            }
+            CacheCommand::Clear { llm, candles } => {
+                if !candles {
+                    let removed = ResponseCache::default().clear()?;
+                    tracing::info!(removed, "Cleared LLM response cache");
+                }
+                if !llm {
+                    let removed = CandleStore::default().clear()?;
+                    tracing::info!(removed, "Cleared candle store");
+                }
+            }
+        },
+        Command::Dev {
+            watch,
+            windows,
+            fresh_calls,
+        } => run_dev(watch, windows, fresh_calls).await?,
+        Command::Explain { run_id, window } => {
+            let analysis =
+                explain_failure(run_id.as_deref(), window.map(|t| t.timestamp() as f64)).await?;
+            println!(
+                "Run {} window {}: answered {:?}, correct was {:?}\n\n{}",
+                analysis.run_id,
+                format_time(Some(analysis.window_end)),
+                analysis.action,
+                analysis.label,
+                analysis.analysis
+            );
+        }
+        Command::GradeRationales {
+            run_id,
+            sample,
+            seed,
+            json,
+        } => {
+            let seed = seed.unwrap_or_else(entropy_seed);
+            let quality = grade_rationales(run_id.as_deref(), sample, seed).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&quality)?);
+            } else {
+                print!("{}", describe_rationale_quality(&quality));
+            }
+        }
+        Command::Prompt {
+            command: PromptCommand::Preview { at, model },
+        } => {
+            let (prompt, truncation) = preview_prompt(at, model).await?;
+            println!("{}", prompt);
+            eprintln!("{}", describe_prompt_cost(&prompt, model));
+            if let Some(truncation) = truncation {
+                eprintln!("Older candles down-sampled to fit: {:?}", truncation);
+            }
+        }
+        Command::Repl => happychartsv2::repl::run_repl().await?,
+        Command::Keys { command } => run_keys_command(command)?,
+        Command::Tokens { command } => run_tokens_command(command)?,
+        Command::RunJob { id } => run_job(&JobQueue::default(), id).await?,
+        #[cfg(feature = "grpc")]
+        Command::Serve { addr, health_addr } => {
+            happychartsv2::grpc::serve(addr, health_addr).await?
+        }
+        #[cfg(feature = "arrow")]
+        Command::Arrow { command } => run_arrow_command(command)?,
+    }
 
     Ok(())
 }
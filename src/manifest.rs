@@ -0,0 +1,86 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::encoding::CandleEncoding;
+use crate::input_drift::InputProfile;
+use crate::truncation::Truncation;
+use crate::ChatOptions;
+
+/// Everything needed to identify and reproduce a backtest run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunManifest {
+    pub run_id: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub model: String,
+    pub prompt_version: String,
+    pub symbols: Vec<String>,
+    pub granularity: u32,
+    /// Candles per prompt window.
+    pub window_candles: usize,
+    pub data_start: DateTime<Utc>,
+    pub data_end: DateTime<Utc>,
+    pub windows: usize,
+    pub accuracy: Option<f64>,
+    /// Seed for window selection and sampling; rerunning with it (and the LLM response
+    /// cache) reproduces the run.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Candles between consecutive windows.
+    #[serde(default = "default_stride")]
+    pub stride: usize,
+    /// Number of windows sampled with `seed`, when not all were scored.
+    #[serde(default)]
+    pub sample: Option<usize>,
+    /// Down-sampling applied to fit windows in the model's context, if any was needed.
+    #[serde(default)]
+    pub truncation: Option<Truncation>,
+    /// Full text of the prompt tested, so any two runs can be diffed.
+    #[serde(default)]
+    pub prompt: Option<String>,
+    /// Compounded simulated return of the run's trades.
+    #[serde(default)]
+    pub total_return: Option<f64>,
+    /// Reasoning effort and token allowance the model was asked with.
+    #[serde(default)]
+    pub chat: ChatOptions,
+    /// Nominal model cost in [`Model::relative_cost`](crate::Model::relative_cost) units,
+    /// counting cached responses as requests.
+    #[serde(default)]
+    pub cost: Option<f64>,
+    /// [`config_hash`](crate::audit::config_hash) of the run's model, chat options and
+    /// window length.
+    #[serde(default)]
+    pub config_hash: Option<String>,
+    /// Statistics of the target's windows, the regime live inputs are compared against.
+    #[serde(default)]
+    pub inputs: Option<InputProfile>,
+    /// Seconds between a window's close and its simulated fill, when trades were delayed.
+    #[serde(default)]
+    pub execution_delay_secs: Option<f64>,
+    /// Long and short labeling multipliers, when a calibration replaced the defaults.
+    #[serde(default)]
+    pub label_thresholds: Option<(f64, f64)>,
+    /// How the candles were scaled in the prompts.
+    #[serde(default)]
+    pub encoding: CandleEncoding,
+}
+
+/// The input profile of the latest finished backtest of `prompt_version` on `symbol`.
+pub fn latest_input_profile(
+    manifests: &[RunManifest],
+    symbol: &str,
+    prompt_version: &str,
+) -> Option<InputProfile> {
+    manifests
+        .iter()
+        .filter(|m| m.finished_at.is_some() && m.prompt_version == prompt_version)
+        .filter(|m| m.symbols.first().is_some_and(|s| s == symbol))
+        .filter_map(|m| Some((m.started_at, m.inputs?)))
+        .max_by_key(|(started_at, _)| *started_at)
+        .map(|(_, inputs)| inputs)
+}
+
+fn default_stride() -> usize {
+    1
+}
@@ -0,0 +1,147 @@
+#[cfg(feature = "native")]
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+#[cfg(feature = "native")]
+use serde::Deserialize;
+
+use crate::snapshot::Microstructure;
+
+/// How far back the taker flow looks from the newest trade.
+pub const TAKER_FLOW_WINDOW: Duration = Duration::minutes(15);
+
+/// One exchange trade, from the taker's side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TakerTrade {
+    pub time: DateTime<Utc>,
+    pub size: f64,
+    /// Whether the taker bought, lifting the ask.
+    pub buy: bool,
+}
+
+/// The spread from the best `bid` and `ask`, and the taker buy share of the volume traded
+/// in the [`TAKER_FLOW_WINDOW`] up to the newest of `trades`. `None` without a positive
+/// bid or without traded volume.
+pub fn microstructure(bid: f64, ask: f64, trades: &[TakerTrade]) -> Option<Microstructure> {
+    if bid <= 0.0 || ask < bid {
+        return None;
+    }
+    let newest = trades.iter().map(|t| t.time).max()?;
+    let recent = trades
+        .iter()
+        .filter(|t| t.time >= newest - TAKER_FLOW_WINDOW)
+        .collect::<Vec<_>>();
+    let volume = recent.iter().map(|t| t.size).sum::<f64>();
+    if volume <= 0.0 {
+        return None;
+    }
+    let oldest = recent.iter().map(|t| t.time).min().unwrap_or(newest);
+    Some(Microstructure {
+        spread: ask / bid - 1.0,
+        taker_buy_share: recent.iter().filter(|t| t.buy).map(|t| t.size).sum::<f64>() / volume,
+        trades: recent.len(),
+        minutes: (newest - oldest).num_seconds() as f64 / 60.0,
+    })
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+struct Ticker {
+    #[serde(deserialize_with = "decimal")]
+    bid: f64,
+    #[serde(deserialize_with = "decimal")]
+    ask: f64,
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug, Deserialize)]
+struct Trade {
+    time: DateTime<Utc>,
+    #[serde(deserialize_with = "decimal")]
+    size: f64,
+    /// The maker's side: a `sell` maker means the taker bought.
+    side: String,
+}
+
+/// Coinbase quotes numbers as strings.
+#[cfg(feature = "native")]
+fn decimal<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(serde::de::Error::custom)
+}
+
+#[cfg(feature = "native")]
+async fn get_public<T: serde::de::DeserializeOwned>(path: &str) -> Result<T> {
+    let response = reqwest::Client::new()
+        .get(format!("https://api.exchange.coinbase.com{}", path))
+        .header("User-Agent", "Mozilla/5.0")
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        anyhow::bail!("Coinbase API error for {}: {} - {}", path, status, text);
+    }
+    Ok(response.json().await?)
+}
+
+/// The current spread and recent taker flow of `symbol` against USD, from the exchange's
+/// ticker and latest trades. Only meaningful live: the exchange keeps no history of either
+/// that a backtest could replay.
+#[cfg(feature = "native")]
+pub async fn fetch_microstructure(symbol: &str) -> Result<Option<Microstructure>> {
+    let ticker: Ticker = get_public(&format!("/products/{}-USD/ticker", symbol)).await?;
+    let trades: Vec<Trade> =
+        get_public(&format!("/products/{}-USD/trades?limit=1000", symbol)).await?;
+    let trades = trades
+        .into_iter()
+        .map(|t| TakerTrade {
+            time: t.time,
+            size: t.size,
+            buy: t.side == "sell",
+        })
+        .collect::<Vec<_>>();
+    Ok(microstructure(ticker.bid, ticker.ask, &trades))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_microstructure() {
+        let at = |minute: i64| {
+            "2024-03-20T12:00:00Z".parse::<DateTime<Utc>>().unwrap() + Duration::minutes(minute)
+        };
+        let trades = [
+            TakerTrade {
+                time: at(0),
+                size: 100.0,
+                buy: false,
+            },
+            TakerTrade {
+                time: at(10),
+                size: 3.0,
+                buy: true,
+            },
+            TakerTrade {
+                time: at(20),
+                size: 1.0,
+                buy: false,
+            },
+            TakerTrade {
+                time: at(25),
+                size: 1.0,
+                buy: true,
+            },
+        ];
+        let m = microstructure(2000.0, 2001.0, &trades).unwrap();
+        assert!((m.spread - 0.0005).abs() < 1e-12);
+        // The first trade is older than the flow window
+        assert_eq!((m.trades, m.minutes), (3, 15.0));
+        assert_eq!(m.taker_buy_share, 0.8);
+
+        assert_eq!(microstructure(0.0, 2001.0, &trades), None);
+        assert_eq!(microstructure(2000.0, 2001.0, &[]), None);
+    }
+}
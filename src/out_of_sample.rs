@@ -0,0 +1,230 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::compute::prompt_version;
+use crate::profiles::SymbolProfile;
+use crate::storage::DATA_DIR;
+use crate::GRANULARITY;
+
+const JOBS_FILE: &str = "out_of_sample_jobs.json";
+/// Length of the period after an improvement that its prompt is evaluated on.
+pub const OUT_OF_SAMPLE_HOURS: i64 = 48;
+
+/// A pending evaluation of an improved prompt on the period that followed its improvement,
+/// which no backtest could have seen.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutOfSampleJob {
+    /// The profile the prompt was improved for; jobs queued before profiles were recorded
+    /// ran on the default target.
+    #[serde(default)]
+    pub profile: SymbolProfile,
+    pub prompt: String,
+    /// Accuracy on the windows the prompt was improved on.
+    pub in_sample: f64,
+    /// The scored range `[start, end)`, starting when the improvement finished.
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl OutOfSampleJob {
+    pub fn new(
+        profile: SymbolProfile,
+        prompt: String,
+        in_sample: f64,
+        improved_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            profile,
+            prompt,
+            in_sample,
+            start: improved_at,
+            end: improved_at + Duration::hours(OUT_OF_SAMPLE_HOURS),
+        }
+    }
+
+    /// When the last candle of the period has closed.
+    pub fn due_at(&self) -> DateTime<Utc> {
+        self.end + Duration::seconds(GRANULARITY as i64)
+    }
+
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        now >= self.due_at()
+    }
+}
+
+/// How an improved prompt did on its unseen period, next to its in-sample score.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutOfSampleResult {
+    pub symbol: String,
+    pub prompt_version: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub windows: usize,
+    pub in_sample: f64,
+    pub out_of_sample: f64,
+}
+
+impl OutOfSampleResult {
+    pub fn new(job: &OutOfSampleJob, windows: usize, out_of_sample: f64) -> Self {
+        Self {
+            symbol: job.profile.symbol.clone(),
+            prompt_version: prompt_version(&job.prompt),
+            start: job.start,
+            end: job.end,
+            windows,
+            in_sample: job.in_sample,
+            out_of_sample,
+        }
+    }
+
+    /// How much accuracy the prompt lost on data it was not tuned on.
+    pub fn decay(&self) -> f64 {
+        self.in_sample - self.out_of_sample
+    }
+}
+
+/// The evaluations scheduled by the improvement loop, kept in one JSON file until they run.
+#[derive(Debug, Clone)]
+pub struct OutOfSampleQueue {
+    path: PathBuf,
+}
+
+impl Default for OutOfSampleQueue {
+    fn default() -> Self {
+        Self::new(DATA_DIR)
+    }
+}
+
+impl OutOfSampleQueue {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            path: dir.as_ref().join(JOBS_FILE),
+        }
+    }
+
+    pub fn jobs(&self) -> Result<Vec<OutOfSampleJob>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let data = fs::read_to_string(&self.path)?;
+        serde_json::from_str(&data).context("Corrupt out-of-sample job queue")
+    }
+
+    fn write(&self, jobs: &[OutOfSampleJob]) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(jobs)?;
+        fs::write(&self.path, json).context("Failed to write out-of-sample job queue")
+    }
+
+    /// Queues `job`, replacing any pending job for the same prompt and symbol.
+    pub fn schedule(&self, job: OutOfSampleJob) -> Result<()> {
+        let mut jobs = self.jobs()?;
+        jobs.retain(|j| j.prompt != job.prompt || j.profile.symbol != job.profile.symbol);
+        jobs.push(job);
+        self.write(&jobs)
+    }
+
+    /// Jobs whose period has fully closed by `now`, oldest first.
+    pub fn due(&self, now: DateTime<Utc>) -> Result<Vec<OutOfSampleJob>> {
+        let mut due = self
+            .jobs()?
+            .into_iter()
+            .filter(|j| j.is_due(now))
+            .collect::<Vec<_>>();
+        due.sort_by_key(|j| j.start);
+        Ok(due)
+    }
+
+    /// Removes a job once its result is recorded.
+    pub fn complete(&self, job: &OutOfSampleJob) -> Result<()> {
+        let mut jobs = self.jobs()?;
+        jobs.retain(|j| j != job);
+        self.write(&jobs)
+    }
+}
+
+/// The results as a table, one line per evaluated prompt.
+pub fn describe_out_of_sample(results: &[OutOfSampleResult]) -> String {
+    let mut out = format!(
+        "{:<6} {:<16} {:<16} {:>7} {:>9} {:>9} {:>8}\n",
+        "symbol", "prompt", "period start", "windows", "in-sample", "out", "decay"
+    );
+    for r in results {
+        let _ = writeln!(
+            out,
+            "{:<6} {:<16} {:<16} {:>7} {:>8.1}% {:>8.1}% {:>+7.1}%",
+            r.symbol,
+            r.prompt_version,
+            r.start.format("%Y-%m-%d %H:%M"),
+            r.windows,
+            r.in_sample * 100.0,
+            r.out_of_sample * 100.0,
+            r.decay() * 100.0
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_runs_jobs_once_their_period_has_closed() {
+        let dir = std::env::temp_dir().join("happycharts_out_of_sample");
+        let _ = fs::remove_dir_all(&dir);
+        let queue = OutOfSampleQueue::new(&dir);
+        let improved_at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let eth = SymbolProfile::default;
+        let sol = SymbolProfile {
+            symbol: "SOL".into(),
+            prompt: "prompts/sol.txt".into(),
+            ..SymbolProfile::default()
+        };
+
+        queue
+            .schedule(OutOfSampleJob::new(eth(), "a".into(), 0.5, improved_at))
+            .unwrap();
+        // Improving the same prompt again restarts its period
+        let later = improved_at + Duration::hours(1);
+        queue
+            .schedule(OutOfSampleJob::new(eth(), "a".into(), 0.6, later))
+            .unwrap();
+        queue
+            .schedule(OutOfSampleJob::new(sol, "b".into(), 0.7, improved_at))
+            .unwrap();
+        assert_eq!(queue.jobs().unwrap().len(), 2);
+
+        let closed = improved_at + Duration::hours(OUT_OF_SAMPLE_HOURS);
+        assert!(queue.due(closed).unwrap().is_empty());
+        let due = queue.due(closed + Duration::hours(1)).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(
+            (due[0].prompt.as_str(), due[0].profile.symbol.as_str()),
+            ("b", "SOL")
+        );
+
+        queue.complete(&due[0]).unwrap();
+        let remaining = queue.jobs().unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].in_sample, 0.6);
+
+        // Jobs queued before profiles were recorded belong to the default target
+        let legacy: OutOfSampleJob = serde_json::from_str(
+            r#"{"prompt": "c", "in_sample": 0.5, "start": "2023-11-14T22:13:20Z",
+                "end": "2023-11-16T22:13:20Z"}"#,
+        )
+        .unwrap();
+        assert_eq!(legacy.profile, SymbolProfile::default());
+
+        let result = OutOfSampleResult::new(&remaining[0], 48, 0.45);
+        assert!((result.decay() - 0.15).abs() < 1e-9);
+    }
+}
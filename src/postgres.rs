@@ -0,0 +1,280 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use tokio_postgres::{Client, NoTls};
+
+use crate::analytics::{PredictionRecord, TradeRecord};
+use crate::backtest::PromptRecord;
+use crate::manifest::RunManifest;
+use crate::storage::Storage;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS candles (
+    symbol TEXT NOT NULL,
+    granularity INTEGER NOT NULL,
+    time DOUBLE PRECISION NOT NULL,
+    open DOUBLE PRECISION NOT NULL,
+    high DOUBLE PRECISION NOT NULL,
+    low DOUBLE PRECISION NOT NULL,
+    close DOUBLE PRECISION NOT NULL,
+    volume DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (symbol, granularity, time)
+);
+CREATE TABLE IF NOT EXISTS predictions (
+    id BIGSERIAL PRIMARY KEY,
+    run_id TEXT NOT NULL,
+    record JSONB NOT NULL
+);
+CREATE TABLE IF NOT EXISTS trades (
+    id BIGSERIAL PRIMARY KEY,
+    run_id TEXT NOT NULL,
+    record JSONB NOT NULL
+);
+CREATE TABLE IF NOT EXISTS prompt_history (
+    id BIGSERIAL PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    prompt TEXT NOT NULL,
+    score DOUBLE PRECISION NOT NULL
+);
+ALTER TABLE prompt_history ADD COLUMN IF NOT EXISTS out_of_sample DOUBLE PRECISION;
+ALTER TABLE prompt_history ADD COLUMN IF NOT EXISTS symbol TEXT NOT NULL DEFAULT 'ETH';
+CREATE TABLE IF NOT EXISTS run_manifests (
+    run_id TEXT PRIMARY KEY,
+    manifest JSONB NOT NULL
+);
+";
+
+/// Postgres backend. Unlike the file backend it keeps the full prompt history.
+pub struct PostgresStorage {
+    client: Client,
+}
+
+impl PostgresStorage {
+    /// Connects, creating the schema if it doesn't exist yet.
+    pub async fn connect(url: &str) -> Result<Self> {
+        let (client, connection) = tokio_postgres::connect(url, NoTls)
+            .await
+            .context("Failed to connect to Postgres")?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                tracing::error!(error = ?e, "Postgres connection error");
+            }
+        });
+
+        client
+            .batch_execute(SCHEMA)
+            .await
+            .context("Failed to create Postgres schema")?;
+        Ok(Self { client })
+    }
+}
+
+impl Storage for PostgresStorage {
+    fn load_candles<'a>(
+        &'a self,
+        symbol: &'a str,
+        granularity: u32,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> BoxFuture<'a, Result<Vec<[f64; 6]>>> {
+        async move {
+            let rows = self
+                .client
+                .query(
+                    "SELECT time, open, high, low, close, volume FROM candles \
+                     WHERE symbol = $1 AND granularity = $2 AND time >= $3 AND time < $4 \
+                     ORDER BY time",
+                    &[
+                        &symbol,
+                        &(granularity as i32),
+                        &(start.timestamp() as f64),
+                        &(end.timestamp() as f64),
+                    ],
+                )
+                .await?;
+            Ok(rows
+                .iter()
+                .map(|r| [r.get(0), r.get(1), r.get(2), r.get(3), r.get(4), r.get(5)])
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn save_candles<'a>(
+        &'a self,
+        symbol: &'a str,
+        granularity: u32,
+        candles: &'a [[f64; 6]],
+    ) -> BoxFuture<'a, Result<usize>> {
+        async move {
+            let stmt = self
+                .client
+                .prepare(
+                    "INSERT INTO candles (symbol, granularity, time, open, high, low, close, volume) \
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+                     ON CONFLICT (symbol, granularity, time) DO UPDATE SET \
+                     open = EXCLUDED.open, high = EXCLUDED.high, low = EXCLUDED.low, \
+                     close = EXCLUDED.close, volume = EXCLUDED.volume \
+                     RETURNING (xmax = 0)",
+                )
+                .await?;
+
+            let mut added = 0;
+            for c in candles {
+                let row = self
+                    .client
+                    .query_one(
+                        &stmt,
+                        &[
+                            &symbol,
+                            &(granularity as i32),
+                            &c[0],
+                            &c[1],
+                            &c[2],
+                            &c[3],
+                            &c[4],
+                            &c[5],
+                        ],
+                    )
+                    .await?;
+                // xmax = 0 means the row was inserted rather than updated
+                if row.get::<_, bool>(0) {
+                    added += 1;
+                }
+            }
+            Ok(added)
+        }
+        .boxed()
+    }
+
+    fn append_predictions<'a>(&'a self, rows: &'a [PredictionRecord]) -> BoxFuture<'a, Result<()>> {
+        async move {
+            for row in rows {
+                self.client
+                    .execute(
+                        "INSERT INTO predictions (run_id, record) VALUES ($1, $2)",
+                        &[&row.run_id, &serde_json::to_value(row)?],
+                    )
+                    .await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn append_trades<'a>(&'a self, rows: &'a [TradeRecord]) -> BoxFuture<'a, Result<()>> {
+        async move {
+            for row in rows {
+                self.client
+                    .execute(
+                        "INSERT INTO trades (run_id, record) VALUES ($1, $2)",
+                        &[&row.run_id, &serde_json::to_value(row)?],
+                    )
+                    .await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn append_prompt_record<'a>(&'a self, record: &'a PromptRecord) -> BoxFuture<'a, Result<()>> {
+        async move {
+            self.client
+                .execute(
+                    "INSERT INTO prompt_history (prompt, score, symbol) VALUES ($1, $2, $3)",
+                    &[&record.prompt, &record.score, &record.symbol],
+                )
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn record_out_of_sample<'a>(
+        &'a self,
+        symbol: &'a str,
+        prompt: &'a str,
+        in_sample: f64,
+        accuracy: f64,
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let updated = self
+                .client
+                .execute(
+                    "UPDATE prompt_history SET out_of_sample = $2 WHERE id = (\
+                       SELECT max(id) FROM prompt_history WHERE prompt = $1 AND symbol = $3\
+                     )",
+                    &[&prompt, &accuracy, &symbol],
+                )
+                .await?;
+            if updated == 0 {
+                self.client
+                    .execute(
+                        "INSERT INTO prompt_history (prompt, score, out_of_sample, symbol) \
+                         VALUES ($1, $2, $3, $4)",
+                        &[&prompt, &in_sample, &accuracy, &symbol],
+                    )
+                    .await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn prompt_history<'a>(
+        &'a self,
+        symbol: &'a str,
+        limit: usize,
+    ) -> BoxFuture<'a, Result<Vec<PromptRecord>>> {
+        async move {
+            let rows = self
+                .client
+                .query(
+                    "SELECT prompt, score, out_of_sample FROM (\
+                       SELECT id, prompt, score, out_of_sample FROM prompt_history \
+                       WHERE symbol = $2 ORDER BY id DESC LIMIT $1\
+                     ) recent ORDER BY id",
+                    &[&(limit.min(i64::MAX as usize) as i64), &symbol],
+                )
+                .await?;
+            Ok(rows
+                .iter()
+                .map(|r| PromptRecord {
+                    prompt: r.get(0),
+                    score: r.get(1),
+                    out_of_sample: r.get(2),
+                    symbol: symbol.to_string(),
+                })
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn save_manifest<'a>(&'a self, manifest: &'a RunManifest) -> BoxFuture<'a, Result<()>> {
+        async move {
+            self.client
+                .execute(
+                    "INSERT INTO run_manifests (run_id, manifest) VALUES ($1, $2) \
+                     ON CONFLICT (run_id) DO UPDATE SET manifest = EXCLUDED.manifest",
+                    &[&manifest.run_id, &serde_json::to_value(manifest)?],
+                )
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn manifests(&self) -> BoxFuture<'_, Result<Vec<RunManifest>>> {
+        async move {
+            let rows = self
+                .client
+                .query("SELECT manifest FROM run_manifests", &[])
+                .await?;
+            rows.iter()
+                .map(|r| Ok(serde_json::from_value(r.get(0))?))
+                .collect()
+        }
+        .boxed()
+    }
+}
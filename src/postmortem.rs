@@ -0,0 +1,97 @@
+use std::fmt::Write as _;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Action;
+
+/// Analyses of this many failures of a prompt are passed on to the improver.
+pub const IMPROVER_POSTMORTEMS: usize = 5;
+/// Longest summary kept from an analysis that doesn't end with a `Summary:` line.
+const SUMMARY_CHARS: usize = 300;
+
+/// The model's own account of why it got one window wrong.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FailureAnalysis {
+    pub run_id: String,
+    pub prompt_hash: String,
+    pub symbol: String,
+    /// Open time of the last candle in the window.
+    pub window_end: f64,
+    pub action: Action,
+    pub label: Action,
+    /// Model that explained the failure.
+    pub model: String,
+    pub analysis: String,
+    /// One or two sentences on what the prompt should change, for the improver.
+    pub summary: String,
+}
+
+/// Follow-up prompt asking why the answer to `prompt` was wrong, given the candle that
+/// decided the label.
+pub fn build_explanation_prompt(
+    prompt: &str,
+    action: Action,
+    rationale: &str,
+    label: Action,
+    next: &[f64; 6],
+) -> String {
+    let mut out = String::new();
+    out.push_str("You were given the following prompt and market data:\n\n");
+    out.push_str(prompt);
+    let _ = write!(
+        out,
+        "\n\nYou answered {:?} with this rationale:\n{}\n\nThe correct action was {:?}. The next hourly candle opened at {:.2}, reached a high of {:.2} and a low of {:.2}, and closed at {:.2}.\n\n",
+        action, rationale, label, next[1], next[2], next[3], next[4]
+    );
+    out.push_str("Analyze why your answer was wrong: which signals in the data you over- or under-weighted, what in the data pointed to the correct action, and whether the prompt's instructions pushed you towards the mistake.\n");
+    out.push_str("End with one line starting with \"Summary:\" stating, in at most two sentences, what the prompt should change to avoid this mistake.\n");
+    out
+}
+
+/// The `Summary:` line of an analysis, or its opening when there is none.
+pub fn summary_of(analysis: &str) -> String {
+    if let Some(summary) = analysis
+        .lines()
+        .rev()
+        .find_map(|l| l.trim().strip_prefix("Summary:"))
+    {
+        return summary.trim().to_string();
+    }
+    let flat = analysis.split_whitespace().collect::<Vec<_>>().join(" ");
+    match flat.char_indices().nth(SUMMARY_CHARS) {
+        Some((cut, _)) => format!("{}...", &flat[..cut]),
+        None => flat,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explanation_prompt_and_summary() {
+        let next = [3600.0, 100.0, 101.5, 97.0, 97.5, 10.0];
+        let prompt = build_explanation_prompt(
+            "Base prompt\nETH: [[0,1,2,3,4,5]]",
+            Action::Long,
+            "Higher lows",
+            Action::Short,
+            &next,
+        );
+        assert!(prompt.starts_with(
+            "You were given the following prompt and market data:\n\nBase prompt\nETH"
+        ));
+        assert!(prompt.contains("You answered Long with this rationale:\nHigher lows"));
+        assert!(prompt.contains("The correct action was Short"));
+        assert!(prompt.contains("closed at 97.50"));
+
+        let analysis = "I leaned on the higher lows.\nVolume was fading.\nSummary: Weigh volume divergence before trend.";
+        assert_eq!(
+            summary_of(analysis),
+            "Weigh volume divergence before trend."
+        );
+        assert_eq!(summary_of("Short\n  answer"), "Short answer");
+        let long = "word ".repeat(100);
+        assert_eq!(summary_of(&long).len(), SUMMARY_CHARS + 3);
+    }
+}
@@ -0,0 +1,450 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::Action;
+
+/// A model's answer for one window.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Prediction {
+    pub action: Action,
+    pub rationale: String,
+    /// Short list of the observations the decision rests on.
+    #[serde(default)]
+    pub key_factors: Vec<String>,
+    /// Price at which the model considers its call wrong; the stop for a trade.
+    #[serde(default)]
+    pub invalidation_price: Option<f64>,
+    /// Price the model expects to be reached if the call is right.
+    #[serde(default)]
+    pub target_price: Option<f64>,
+    /// The model's probability, in `[0, 1]`, that the action is right.
+    #[serde(default)]
+    pub confidence: Option<f64>,
+}
+
+/// A model's target portfolio for the next hour: long-only weights per asset, with the
+/// remainder held in cash.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Allocation {
+    /// `(symbol, weight)` in the order the symbols were requested.
+    pub weights: Vec<(String, f64)>,
+    pub cash: f64,
+    pub rationale: String,
+}
+
+// Slack allowed on weights plus cash summing to one, for rounding in the response
+const WEIGHT_TOLERANCE: f64 = 0.01;
+
+/// How forgiving [`parse_model_response`] is about the content of a well-formed response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /// Unknown actions count as `none` and a missing rationale as empty.
+    #[default]
+    Lenient,
+    /// Unknown actions and missing rationales are parse failures.
+    Strict,
+}
+
+/// Why a model response could not be turned into a [`Prediction`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// Not JSON at all, even after stripping code fences.
+    InvalidJson(String),
+    /// JSON, but not an object.
+    NotAnObject,
+    /// No string `action` field.
+    MissingAction,
+    /// An `action` other than long/short/none (strict mode only).
+    UnknownAction(String),
+    /// No string `rationale` field (strict mode only).
+    MissingRationale,
+    /// An optional field of the wrong type, a non-positive price or a confidence outside
+    /// `[0, 1]` (strict mode only).
+    InvalidField(&'static str),
+    /// Invalidation and target on the wrong sides of each other for the action (strict
+    /// mode only).
+    InconsistentLevels,
+    /// A multi-asset response without an entry for this symbol.
+    MissingSymbol(String),
+}
+
+impl ParseError {
+    /// Short stable name of the failure category, for logs and metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ParseError::InvalidJson(_) => "invalid_json",
+            ParseError::NotAnObject => "not_an_object",
+            ParseError::MissingAction => "missing_action",
+            ParseError::UnknownAction(_) => "unknown_action",
+            ParseError::MissingRationale => "missing_rationale",
+            ParseError::InvalidField(_) => "invalid_field",
+            ParseError::InconsistentLevels => "inconsistent_levels",
+            ParseError::MissingSymbol(_) => "missing_symbol",
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::InvalidJson(e) => write!(f, "Response not valid JSON: {}", e),
+            ParseError::NotAnObject => write!(f, "Response is not a JSON object"),
+            ParseError::MissingAction => write!(f, "Missing 'action' field in response"),
+            ParseError::UnknownAction(a) => write!(f, "Unknown action '{}' in response", a),
+            ParseError::MissingRationale => write!(f, "Missing 'rationale' field in response"),
+            ParseError::InvalidField(name) => write!(f, "Invalid '{}' field in response", name),
+            ParseError::InconsistentLevels => write!(
+                f,
+                "Invalidation and target prices are on the wrong sides for the action"
+            ),
+            ParseError::MissingSymbol(s) => write!(f, "No prediction for {} in response", s),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Extracts a [`Prediction`] from a model response, which may be wrapped in markdown code
+/// fences. `key_factors`, `invalidation_price`, `target_price` and `confidence` are
+/// optional; in lenient
+/// mode malformed values are dropped rather than rejected.
+pub fn parse_model_response(
+    response: &str,
+    strictness: Strictness,
+) -> Result<Prediction, ParseError> {
+    let val = parse_json(response)?;
+    prediction_from_object(val.as_object().ok_or(ParseError::NotAnObject)?, strictness)
+}
+
+/// Extracts one [`Prediction`] per symbol, in the order of `symbols`, from a multi-asset
+/// response: a JSON object keyed by symbol whose values each follow the single-asset
+/// format.
+pub fn parse_multi_asset_response(
+    response: &str,
+    symbols: &[&str],
+    strictness: Strictness,
+) -> Result<Vec<(String, Prediction)>, ParseError> {
+    let val = parse_json(response)?;
+    let obj = val.as_object().ok_or(ParseError::NotAnObject)?;
+    symbols
+        .iter()
+        .map(|symbol| {
+            let entry = obj
+                .get(*symbol)
+                .ok_or_else(|| ParseError::MissingSymbol(symbol.to_string()))?
+                .as_object()
+                .ok_or(ParseError::NotAnObject)?;
+            Ok((
+                symbol.to_string(),
+                prediction_from_object(entry, strictness)?,
+            ))
+        })
+        .collect()
+}
+
+/// Extracts an [`Allocation`] over `symbols` from a response of the form
+/// `{"weights": {"ETH": 0.5, ...}, "cash": 0.5, "rationale": "..."}`. `cash` is optional
+/// and derived from the weights. In lenient mode missing or malformed weights count as
+/// zero and weights summing past one are scaled down.
+pub fn parse_allocation_response(
+    response: &str,
+    symbols: &[&str],
+    strictness: Strictness,
+) -> Result<Allocation, ParseError> {
+    let strict = strictness == Strictness::Strict;
+    let val = parse_json(response)?;
+    let obj = val.as_object().ok_or(ParseError::NotAnObject)?;
+    let weights_obj = match obj.get("weights").and_then(Value::as_object) {
+        Some(w) => Some(w),
+        None if strict => return Err(ParseError::InvalidField("weights")),
+        None => None,
+    };
+
+    let mut weights = Vec::new();
+    for symbol in symbols {
+        let weight = match weights_obj.and_then(|w| w.get(*symbol)) {
+            None if strict => return Err(ParseError::MissingSymbol(symbol.to_string())),
+            None => 0.0,
+            Some(v) => match v.as_f64().filter(|w| (0.0..=1.0).contains(w)) {
+                Some(w) => w,
+                None if strict => return Err(ParseError::InvalidField("weights")),
+                None => v.as_f64().unwrap_or(0.0).clamp(0.0, 1.0),
+            },
+        };
+        weights.push((symbol.to_string(), weight));
+    }
+
+    let invested = weights.iter().map(|(_, w)| w).sum::<f64>();
+    if invested > 1.0 + WEIGHT_TOLERANCE {
+        if strict {
+            return Err(ParseError::InvalidField("weights"));
+        }
+        weights.iter_mut().for_each(|(_, w)| *w /= invested);
+    }
+    let cash = (1.0 - weights.iter().map(|(_, w)| w).sum::<f64>()).max(0.0);
+    if strict {
+        if let Some(stated) = obj.get("cash").and_then(Value::as_f64) {
+            if (stated - cash).abs() > WEIGHT_TOLERANCE {
+                return Err(ParseError::InvalidField("cash"));
+            }
+        }
+    }
+
+    let rationale = match obj.get("rationale").and_then(|r| r.as_str()) {
+        Some(r) => r.to_string(),
+        None if strict => return Err(ParseError::MissingRationale),
+        None => String::new(),
+    };
+    Ok(Allocation {
+        weights,
+        cash,
+        rationale,
+    })
+}
+
+pub(crate) fn parse_json(response: &str) -> Result<Value, ParseError> {
+    // Clean up the response to remove code fences if present
+    let clean_response = response.replace("```json", "").replace("```", "");
+    serde_json::from_str(&clean_response).map_err(|e| ParseError::InvalidJson(e.to_string()))
+}
+
+fn prediction_from_object(
+    obj: &serde_json::Map<String, Value>,
+    strictness: Strictness,
+) -> Result<Prediction, ParseError> {
+    let action_str = obj
+        .get("action")
+        .and_then(|a| a.as_str())
+        .ok_or(ParseError::MissingAction)?;
+    let action = match action_str.trim().to_lowercase().as_str() {
+        "long" => Action::Long,
+        "short" => Action::Short,
+        "none" => Action::None,
+        _ if strictness == Strictness::Strict => {
+            return Err(ParseError::UnknownAction(action_str.to_string()))
+        }
+        _ => Action::None,
+    };
+
+    let rationale = match obj.get("rationale").and_then(|r| r.as_str()) {
+        Some(r) => r.to_string(),
+        None if strictness == Strictness::Strict => return Err(ParseError::MissingRationale),
+        None => String::new(),
+    };
+
+    let strict = strictness == Strictness::Strict;
+    let key_factors = match obj.get("key_factors") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(items)) if items.iter().all(Value::is_string) => items
+            .iter()
+            .filter_map(|i| i.as_str().map(str::to_string))
+            .collect(),
+        Some(_) if strict => return Err(ParseError::InvalidField("key_factors")),
+        Some(_) => Vec::new(),
+    };
+    let invalidation_price = price_field(obj, "invalidation_price", strict)?;
+    let target_price = price_field(obj, "target_price", strict)?;
+    let confidence = match obj.get("confidence") {
+        None | Some(Value::Null) => None,
+        Some(v) => match v.as_f64().filter(|c| (0.0..=1.0).contains(c)) {
+            Some(c) => Some(c),
+            None if strict => return Err(ParseError::InvalidField("confidence")),
+            None => None,
+        },
+    };
+
+    if strict {
+        if let (Some(stop), Some(target)) = (invalidation_price, target_price) {
+            let consistent = match action {
+                Action::Long => stop < target,
+                Action::Short => stop > target,
+                Action::None => true,
+            };
+            if !consistent {
+                return Err(ParseError::InconsistentLevels);
+            }
+        }
+    }
+
+    Ok(Prediction {
+        action,
+        rationale,
+        key_factors,
+        invalidation_price,
+        target_price,
+        confidence,
+    })
+}
+
+fn price_field(
+    obj: &serde_json::Map<String, Value>,
+    name: &'static str,
+    strict: bool,
+) -> Result<Option<f64>, ParseError> {
+    match obj.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => match v.as_f64().filter(|p| p.is_finite() && *p > 0.0) {
+            Some(price) => Ok(Some(price)),
+            None if strict => Err(ParseError::InvalidField(name)),
+            None => Ok(None),
+        },
+    }
+}
+
+/// Follow-up prompt for a response that failed to parse: the original prompt, the rejected
+/// response and an instruction naming what was wrong with it.
+pub fn corrective_prompt(prompt: &str, response: &str, error: &ParseError) -> String {
+    format!(
+        "{}\n\nYour previous response was:\n{}\n\nIt could not be used: {}. Respond again with only a JSON object of the form {{\"action\": \"long\" | \"short\" | \"none\", \"rationale\": \"...\", \"key_factors\": [\"...\"], \"invalidation_price\": number, \"target_price\": number, \"confidence\": number between 0 and 1}} and nothing else.",
+        prompt, response, error
+    )
+}
+
+/// [`corrective_prompt`] for an allocation response.
+pub fn corrective_allocation_prompt(
+    prompt: &str,
+    response: &str,
+    error: &ParseError,
+    symbols: &[&str],
+) -> String {
+    format!(
+        "{}\n\nYour previous response was:\n{}\n\nIt could not be used: {}. Respond again with only a JSON object of the form {{\"weights\": {{{}}}, \"cash\": number, \"rationale\": \"...\"}}, where every weight and the cash are between 0 and 1 and together sum to 1, and nothing else.",
+        prompt,
+        response,
+        error,
+        symbols
+            .iter()
+            .map(|s| format!("\"{}\": number", s))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// [`corrective_prompt`] for a multi-asset response.
+pub fn corrective_multi_asset_prompt(
+    prompt: &str,
+    response: &str,
+    error: &ParseError,
+    symbols: &[&str],
+) -> String {
+    format!(
+        "{}\n\nYour previous response was:\n{}\n\nIt could not be used: {}. Respond again with only a JSON object with the keys {}, each mapping to an object of the form {{\"action\": \"long\" | \"short\" | \"none\", \"rationale\": \"...\", \"key_factors\": [\"...\"], \"invalidation_price\": number, \"target_price\": number, \"confidence\": number between 0 and 1}}, and nothing else.",
+        prompt,
+        response,
+        error,
+        symbols.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_model_response() {
+        let fenced = "```json\n{\"action\": \"Long\", \"rationale\": \"breakout\"}\n```";
+        let p = parse_model_response(fenced, Strictness::Strict).unwrap();
+        assert_eq!(p.action, Action::Long);
+        assert_eq!(p.rationale, "breakout");
+
+        let unknown = r#"{"action": "buy"}"#;
+        assert_eq!(
+            parse_model_response(unknown, Strictness::Lenient).unwrap(),
+            Prediction {
+                action: Action::None,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            parse_model_response(unknown, Strictness::Strict)
+                .unwrap_err()
+                .kind(),
+            "unknown_action"
+        );
+        assert_eq!(
+            parse_model_response(r#"{"action": "none"}"#, Strictness::Strict),
+            Err(ParseError::MissingRationale)
+        );
+
+        let lenient = Strictness::default();
+        assert_eq!(
+            parse_model_response("not json", lenient)
+                .unwrap_err()
+                .kind(),
+            "invalid_json"
+        );
+        assert_eq!(
+            parse_model_response("[1]", lenient),
+            Err(ParseError::NotAnObject)
+        );
+        assert_eq!(
+            parse_model_response(r#"{"rationale": "x"}"#, lenient),
+            Err(ParseError::MissingAction)
+        );
+
+        let levels = r#"{"action": "long", "rationale": "r", "key_factors": ["higher closes"],
+            "invalidation_price": 95.5, "target_price": 110, "confidence": 0.7}"#;
+        let p = parse_model_response(levels, Strictness::Strict).unwrap();
+        assert_eq!(p.key_factors, vec!["higher closes"]);
+        assert_eq!(p.invalidation_price, Some(95.5));
+        assert_eq!(p.target_price, Some(110.0));
+        assert_eq!(p.confidence, Some(0.7));
+        let overconfident = r#"{"action": "none", "rationale": "r", "confidence": 70}"#;
+        assert_eq!(
+            parse_model_response(overconfident, Strictness::Strict),
+            Err(ParseError::InvalidField("confidence"))
+        );
+        let inverted = r#"{"action": "short", "rationale": "r", "invalidation_price": 90, "target_price": 110}"#;
+        assert_eq!(
+            parse_model_response(inverted, Strictness::Strict),
+            Err(ParseError::InconsistentLevels)
+        );
+        let multi = r#"{"ETH": {"action": "long", "rationale": "a"},
+            "BTC": {"action": "short", "rationale": "b"}}"#;
+        let both = parse_multi_asset_response(multi, &["BTC", "ETH"], Strictness::Strict).unwrap();
+        assert_eq!(both[0].0, "BTC");
+        assert_eq!(both[0].1.action, Action::Short);
+        assert_eq!(both[1].1.rationale, "a");
+        assert_eq!(
+            parse_multi_asset_response(multi, &["ETH", "SOL"], Strictness::Strict),
+            Err(ParseError::MissingSymbol("SOL".to_string()))
+        );
+        let allocation = r#"{"weights": {"ETH": 0.5, "BTC": 0.2}, "cash": 0.3, "rationale": "r"}"#;
+        let a = parse_allocation_response(allocation, &["ETH", "BTC"], Strictness::Strict).unwrap();
+        assert_eq!(a.weights[1], ("BTC".to_string(), 0.2));
+        assert!((a.cash - 0.3).abs() < 1e-9);
+        assert_eq!(
+            parse_allocation_response(allocation, &["ETH", "SOL"], Strictness::Strict),
+            Err(ParseError::MissingSymbol("SOL".to_string()))
+        );
+        let overweight = r#"{"weights": {"ETH": 0.8, "BTC": 0.8}, "rationale": "r"}"#;
+        assert_eq!(
+            parse_allocation_response(overweight, &["ETH", "BTC"], Strictness::Strict),
+            Err(ParseError::InvalidField("weights"))
+        );
+        let scaled =
+            parse_allocation_response(overweight, &["ETH", "BTC"], Strictness::Lenient).unwrap();
+        assert_eq!((scaled.weights[0].1, scaled.cash), (0.5, 0.0));
+        let bad_price = r#"{"action": "long", "rationale": "r", "target_price": "high"}"#;
+        assert_eq!(
+            parse_model_response(bad_price, Strictness::Strict),
+            Err(ParseError::InvalidField("target_price"))
+        );
+        assert_eq!(
+            parse_model_response(bad_price, lenient)
+                .unwrap()
+                .target_price,
+            None
+        );
+
+        let retry = corrective_prompt(
+            "base",
+            r#"{"action": "buy"}"#,
+            &ParseError::UnknownAction("buy".into()),
+        );
+        assert!(retry.starts_with("base\n\n"));
+        assert!(retry.contains("Unknown action 'buy' in response"));
+    }
+}
@@ -0,0 +1,256 @@
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::compute::{label_candles_with, Action};
+use crate::encoding::CandleEncoding;
+use crate::signal_filter::RepeatPolicy;
+use crate::PROMPT_FILE;
+
+/// Symbol profiles the daemon runs, relative to the working directory.
+pub const PROFILES_FILE: &str = "profiles.json";
+/// Symbol decided on when no profile says otherwise, with `prompt.txt` as its prompt.
+pub const DEFAULT_TARGET: &str = "ETH";
+
+/// How the daemon analyzes one target symbol.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SymbolProfile {
+    /// Symbol the model decides on, e.g. BTC.
+    pub symbol: String,
+    /// Base prompt written for this symbol, re-read every hour.
+    #[serde(default = "default_prompt")]
+    pub prompt: PathBuf,
+    /// Symbols shown alongside the target; the other live symbols when omitted.
+    #[serde(default)]
+    pub context: Option<Vec<String>>,
+    /// URL every signal of this symbol is POSTed to as JSON; signals are only logged and
+    /// recorded when omitted.
+    #[serde(default)]
+    pub webhook: Option<String>,
+    /// How the candles are scaled in this symbol's prompts.
+    #[serde(default)]
+    pub encoding: CandleEncoding,
+    /// Whether this symbol's prompts list the computed features; off by default, as most
+    /// of them restate the market context lines.
+    #[serde(default)]
+    pub prompt_features: bool,
+    /// Which repeats of the open position are posted to the webhook; every signal when
+    /// omitted.
+    #[serde(default)]
+    pub repeat: RepeatPolicy,
+}
+
+fn default_prompt() -> PathBuf {
+    PathBuf::from(PROMPT_FILE)
+}
+
+impl Default for SymbolProfile {
+    fn default() -> Self {
+        Self {
+            symbol: DEFAULT_TARGET.to_string(),
+            prompt: default_prompt(),
+            context: None,
+            webhook: None,
+            encoding: CandleEncoding::Raw,
+            prompt_features: false,
+            repeat: RepeatPolicy::Always,
+        }
+    }
+}
+
+impl SymbolProfile {
+    /// The symbols whose windows accompany the target, from `live` unless the profile
+    /// lists its own.
+    pub fn context_symbols(&self, live: &[&str]) -> Vec<String> {
+        match &self.context {
+            Some(context) => context.clone(),
+            None => live
+                .iter()
+                .filter(|s| **s != self.symbol)
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Parses a profiles file: a JSON array with one profile per target symbol.
+pub fn parse_profiles(json: &str) -> Result<Vec<SymbolProfile>> {
+    let profiles: Vec<SymbolProfile> = serde_json::from_str(json)?;
+    anyhow::ensure!(!profiles.is_empty(), "No symbol profiles configured");
+    for (i, p) in profiles.iter().enumerate() {
+        anyhow::ensure!(
+            !profiles[..i].iter().any(|q| q.symbol == p.symbol),
+            "Symbol {} has more than one profile",
+            p.symbol
+        );
+        anyhow::ensure!(
+            !p.context.as_ref().is_some_and(|c| c.contains(&p.symbol)),
+            "Profile {} lists its own symbol as context",
+            p.symbol
+        );
+    }
+    Ok(profiles)
+}
+
+/// The profile of `symbol` in [`PROFILES_FILE`]; without that file only the default
+/// target has one, on `prompt.txt`.
+#[cfg(feature = "native")]
+pub fn profile_for(symbol: &str) -> Result<SymbolProfile> {
+    use anyhow::Context;
+
+    let path = std::path::Path::new(PROFILES_FILE);
+    let profiles = if path.exists() {
+        let json = std::fs::read_to_string(path)?;
+        parse_profiles(&json).with_context(|| format!("Invalid profiles in {}", path.display()))?
+    } else {
+        vec![SymbolProfile::default()]
+    };
+    profiles
+        .into_iter()
+        .find(|p| p.symbol == symbol)
+        .with_context(|| format!("No profile for {symbol} in {PROFILES_FILE}"))
+}
+
+/// One symbol's live signals as the daemon sees them verified, hour by hour.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct VerificationTracker {
+    /// Window ends and actions of signals whose next candle hasn't closed yet.
+    pending: Vec<(f64, Action)>,
+    pub signals: usize,
+    pub verified: usize,
+    pub correct: usize,
+}
+
+impl VerificationTracker {
+    pub fn record_signal(&mut self, window_end: f64, action: Action) {
+        self.pending.push((window_end, action));
+        self.signals += 1;
+    }
+
+    /// Verifies pending signals against the symbol's latest window, whose newest candle is
+    /// taken to still be open, labeling with the symbol's long and short `thresholds`.
+    /// Returns how many were verified; signals whose candles fell out of the window are
+    /// dropped unverified.
+    pub fn verify(&mut self, candles: &[[f64; 6]], (long, short): (f64, f64)) -> usize {
+        let Some(first) = candles.first() else {
+            return 0;
+        };
+        let closed = &candles[..candles.len() - 1];
+        let before = self.verified;
+        self.pending.retain(|&(window_end, action)| {
+            let i = closed.iter().position(|c| c[0] == window_end);
+            match i.filter(|&i| i + 1 < closed.len()) {
+                Some(i) => {
+                    self.verified += 1;
+                    if label_candles_with(&closed[i..i + 2], long, short)[0] == action {
+                        self.correct += 1;
+                    }
+                    false
+                }
+                None => window_end >= first[0],
+            }
+        });
+        self.verified - before
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn accuracy(&self) -> Option<f64> {
+        (self.verified > 0).then(|| self.correct as f64 / self.verified as f64)
+    }
+}
+
+/// Every symbol's tracker as a table, in profile order.
+pub fn describe_trackers(trackers: &[(String, VerificationTracker)]) -> String {
+    let mut out = format!(
+        "{:<8} {:>7} {:>8} {:>7} {:>9}\n",
+        "symbol", "signals", "verified", "pending", "accuracy"
+    );
+    for (symbol, t) in trackers {
+        let accuracy = t
+            .accuracy()
+            .map(|a| format!("{:.1}%", a * 100.0))
+            .unwrap_or_else(|| "-".to_string());
+        let _ = writeln!(
+            out,
+            "{:<8} {:>7} {:>8} {:>7} {:>9}",
+            symbol,
+            t.signals,
+            t.verified,
+            t.pending(),
+            accuracy
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute::{LONG_THRESHOLD, SHORT_THRESHOLD};
+
+    #[test]
+    fn test_parse_profiles() {
+        let profiles = parse_profiles(
+            r#"[
+                {"symbol": "ETH", "webhook": "https://hooks.example/eth"},
+                {"symbol": "BTC", "prompt": "prompts/btc.txt", "context": ["ETH"]}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(profiles[0].prompt, PathBuf::from("prompt.txt"));
+        assert_eq!(
+            profiles[0].context_symbols(&["ETH", "BTC", "SOL"]),
+            ["BTC", "SOL"]
+        );
+        assert_eq!(profiles[1].context_symbols(&["ETH", "BTC", "SOL"]), ["ETH"]);
+        assert_eq!(profiles[1].webhook, None);
+
+        assert!(parse_profiles("[]").is_err());
+        assert!(parse_profiles(r#"[{"symbol": "ETH"}, {"symbol": "ETH"}]"#).is_err());
+        assert!(parse_profiles(r#"[{"symbol": "ETH", "context": ["ETH"]}]"#).is_err());
+    }
+
+    #[test]
+    fn test_signals_verify_once_the_next_candle_closes() {
+        let candle =
+            |hour: f64, close: f64| [hour * 3600.0, close, close * 1.06, close, close, 1.0];
+        let defaults = (LONG_THRESHOLD, SHORT_THRESHOLD);
+        let mut tracker = VerificationTracker::default();
+        tracker.record_signal(0.0, Action::Long);
+
+        // The candle after the signal is still open
+        assert_eq!(
+            tracker.verify(&[candle(0.0, 100.0), candle(1.0, 100.0)], defaults),
+            0
+        );
+        assert_eq!(tracker.pending(), 1);
+
+        tracker.record_signal(3600.0, Action::Short);
+        let window = [candle(0.0, 100.0), candle(1.0, 100.0), candle(2.0, 100.0)];
+        let mut calibrated = tracker.clone();
+        assert_eq!(tracker.verify(&window, defaults), 1);
+        assert_eq!((tracker.verified, tracker.correct), (1, 1));
+        assert_eq!(tracker.pending(), 1);
+        // A calibrated long threshold the 6% rise doesn't reach labels it differently
+        assert_eq!(calibrated.verify(&window, (1.08, 0.92)), 1);
+        assert_eq!(calibrated.correct, 0);
+
+        // A signal older than the window can no longer be verified
+        tracker.verify(&[candle(5.0, 100.0)], defaults);
+        assert_eq!(
+            (tracker.signals, tracker.verified, tracker.pending()),
+            (2, 1, 0)
+        );
+        assert_eq!(tracker.accuracy(), Some(1.0));
+        assert!(describe_trackers(&[("ETH".to_string(), tracker)])
+            .lines()
+            .nth(1)
+            .unwrap()
+            .ends_with("100.0%"));
+    }
+}
@@ -1,5 +1,11 @@
 use std::fmt::Write;
 
+use crate::data_quality::CandleAnomaly;
+use crate::encoding::{encode_candles, CandleEncoding};
+use crate::granularity::{candle_granularity, candle_wording};
+use crate::snapshot::MarketSnapshot;
+use crate::GRANULARITY;
+
 // pub fn build_prompt(eth_data: &[[f64; 6]], btc_data: &[[f64; 6]], sol_data: &[[f64; 6]]) -> String {
 //     // Helper function to format a slice of candles as JSON arrays.
 //     // This will avoid unnecessary cloning by writing directly to a String via `write!`.
@@ -66,53 +72,306 @@ use std::fmt::Write;
 //     prompt
 // }
 
-pub fn build_data_section(
-    eth_data: &[[f64; 6]],
-    btc_data: &[[f64; 6]],
-    sol_data: &[[f64; 6]],
+/// What a symbol's data is for in the prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolRole {
+    /// The symbol the model decides on.
+    Target,
+    /// Supporting data the decision may draw on.
+    Context,
+}
+
+/// How one symbol is introduced in the data section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SymbolSpec<'a> {
+    pub symbol: &'a str,
+    pub role: SymbolRole,
+    /// Short hint on how to read the series, e.g. "market leader".
+    pub description: Option<&'static str>,
+}
+
+impl SymbolSpec<'_> {
+    /// The header line for the symbol's section, e.g. `CONTEXT: BTC (market leader)`.
+    pub fn header(&self) -> String {
+        let role = match self.role {
+            SymbolRole::Target => "TARGET",
+            SymbolRole::Context => "CONTEXT",
+        };
+        match self.description {
+            Some(d) => format!("{}: {} ({})", role, self.symbol, d),
+            None => format!("{}: {}", role, self.symbol),
+        }
+    }
+}
+
+/// Renders a snapshot: one role-annotated candle section per symbol, in snapshot order,
+/// then the volume features, any auxiliary market context and the data-quality notes.
+pub fn build_data_section(snapshot: &MarketSnapshot) -> String {
+    let series = snapshot
+        .symbols
+        .iter()
+        .map(|s| (s.spec, s.candles))
+        .collect::<Vec<_>>();
+    let mut data_section = build_candle_section(&series, snapshot.encoding);
+    data_section.push_str(&build_volume_section(snapshot));
+    data_section.push_str(&build_context_section(snapshot));
+    data_section.push_str(&build_event_section(snapshot));
+    data_section.push_str(&build_feature_section(snapshot));
+    for s in &snapshot.symbols {
+        data_section.push_str(&build_anomaly_notes(s.spec.symbol, &s.anomalies));
+    }
+    data_section
+}
+
+/// The role-annotated candles of every symbol in `encoding`, without volume features.
+pub fn build_candle_section(
+    series: &[(SymbolSpec<'_>, &[[f64; 6]])],
+    encoding: CandleEncoding,
 ) -> String {
-    fn format_candles(data: &[[f64; 6]]) -> String {
+    let format_candles = |data: &[[f64; 6]]| {
         let mut s = String::from("[");
-        data.iter().enumerate().for_each(|(i, c)| {
+        encode_candles(data, encoding)
+            .iter()
+            .enumerate()
+            .for_each(|(i, c)| {
+                if i > 0 {
+                    s.push(',');
+                }
+                // c: [time, open, high, low, close, vol]
+                let _ = match encoding {
+                    CandleEncoding::Raw => write!(
+                        s,
+                        "[{:.2},{:.2},{:.2},{:.2},{:.2},{:.6}]",
+                        c[0], c[1], c[2], c[3], c[4], c[5]
+                    ),
+                    // Encoded values are small, so keep more of their digits
+                    _ => write!(
+                        s,
+                        "[{:.2},{:.3},{:.3},{:.3},{:.3},{:.3}]",
+                        c[0], c[1], c[2], c[3], c[4], c[5]
+                    ),
+                };
+            });
+        s.push(']');
+        s
+    };
+
+    // Now we only return the data portion, naming the candles as spaced in the first series
+    let granularity = series
+        .first()
+        .and_then(|(_, data)| candle_granularity(data))
+        .unwrap_or(GRANULARITY);
+    let mut data_section = String::new();
+    let _ = write!(
+        data_section,
+        "Data provided ({} candles, format: [timestamp, open, high, low, close, volume]",
+        candle_wording(granularity)
+    );
+    let _ = match encoding.note() {
+        Some(note) => writeln!(data_section, "; {}):", note),
+        None => writeln!(data_section, "):"),
+    };
+    for (spec, data) in series {
+        let _ = writeln!(data_section, "{}", spec.header());
+        let _ = writeln!(data_section, "{}: {}", spec.symbol, format_candles(data));
+    }
+    data_section
+}
+
+/// Renders VWAP, distance from VWAP and a coarse volume-by-price profile per symbol, or
+/// nothing when no symbol has volume features.
+pub fn build_volume_section(snapshot: &MarketSnapshot) -> String {
+    if snapshot.symbols.iter().all(|s| s.volume.is_none()) {
+        return String::new();
+    }
+    let mut section = String::from(
+        "\nVolume features (VWAP over the window, last close vs VWAP, volume by price band):\n",
+    );
+    for s in &snapshot.symbols {
+        let (symbol, Some(features)) = (s.spec.symbol, &s.volume) else {
+            continue;
+        };
+        let _ = write!(
+            section,
+            "{}: VWAP {:.2}, close vs VWAP {:+.2}%, profile ",
+            symbol,
+            features.vwap,
+            features.vwap_distance * 100.0
+        );
+        features.profile.iter().enumerate().for_each(|(i, b)| {
             if i > 0 {
-                s.push(',');
+                section.push_str(", ");
             }
-            // c: [time, open, high, low, close, vol]
-            let _ = write!(
-                s,
-                "[{:.2},{:.2},{:.2},{:.2},{:.2},{:.6}]",
-                c[0], c[1], c[2], c[3], c[4], c[5]
-            );
+            let _ = write!(section, "[{:.2}-{:.2}: {:.2}]", b.low, b.high, b.volume);
         });
-        s.push(']');
-        s
+        section.push('\n');
     }
+    section
+}
 
-    let eth_json = format_candles(eth_data);
-    let btc_json = format_candles(btc_data);
-    let sol_json = format_candles(sol_data);
+/// Renders funding, order book depth, liquidations, implied volatility, order flow and
+/// headlines for the symbols that have any.
+pub fn build_context_section(snapshot: &MarketSnapshot) -> String {
+    let with_context = snapshot
+        .symbols
+        .iter()
+        .filter(|s| !s.context.is_empty())
+        .collect::<Vec<_>>();
+    if with_context.is_empty() {
+        return String::new();
+    }
+    let mut section = String::from("\nMarket context:\n");
+    for s in with_context {
+        let mut facts = Vec::new();
+        if let Some(rate) = s.context.funding_rate {
+            facts.push(format!("funding rate {:+.4}% per interval", rate * 100.0));
+        }
+        if let Some(depth) = s.context.depth {
+            facts.push(format!(
+                "order book depth within 1% {:.2} bid / {:.2} ask, spread {:.4}%",
+                depth.bid_depth,
+                depth.ask_depth,
+                depth.spread * 100.0
+            ));
+        }
+        if let Some(liq) = s.context.liquidations {
+            facts.push(format!(
+                "liquidations over the last {}h ${:.2}M long / ${:.2}M short, \
+                 last hour ${:.2}M long / ${:.2}M short",
+                liq.hours,
+                liq.long_usd / 1e6,
+                liq.short_usd / 1e6,
+                liq.last_hour_long_usd / 1e6,
+                liq.last_hour_short_usd / 1e6
+            ));
+        }
+        if let Some(iv) = s.context.implied_volatility {
+            let mut fact = format!("implied volatility (DVOL) {:.1}", iv.level);
+            if let Some(change) = iv.change_24h {
+                let _ = write!(fact, ", {:+.1}% over 24h", change * 100.0);
+            }
+            let _ = write!(fact, ", {} regime", iv.regime.as_str());
+            facts.push(fact);
+        }
+        if let Some(m) = s.context.microstructure {
+            facts.push(format!(
+                "current spread {:.4}%, takers bought {:.1}% of the volume of the last {} trades \
+                 ({:.0} min)",
+                m.spread * 100.0,
+                m.taker_buy_share * 100.0,
+                m.trades,
+                m.minutes
+            ));
+        }
+        if !facts.is_empty() {
+            let _ = writeln!(section, "{}: {}", s.spec.symbol, facts.join("; "));
+        }
+        for headline in &s.context.headlines {
+            let _ = writeln!(section, "{} news: {}", s.spec.symbol, headline);
+        }
+    }
+    section
+}
 
-    // Now we only return the data portion:
-    let mut data_section = String::new();
-    data_section.push_str(
-        "Data provided (hourly candles, format: [timestamp, open, high, low, close, volume]):\n",
-    );
-    data_section.push_str("ETH: ");
-    data_section.push_str(&eth_json);
-    data_section.push('\n');
-    data_section.push_str("BTC: ");
-    data_section.push_str(&btc_json);
-    data_section.push('\n');
-    data_section.push_str("SOL: ");
-    data_section.push_str(&sol_json);
-    data_section.push('\n');
+/// Renders the snapshot's computed features, one line per symbol, or nothing without any or
+/// unless the snapshot opts in to showing them.
+pub fn build_feature_section(snapshot: &MarketSnapshot) -> String {
+    if !snapshot.prompt_features || snapshot.features.is_empty() {
+        return String::new();
+    }
+    let mut section = String::from("\nFeatures:\n");
+    for s in &snapshot.symbols {
+        let values = snapshot
+            .features
+            .0
+            .iter()
+            .filter(|v| v.symbol == s.spec.symbol)
+            .map(|v| format!("{} {:.4}", v.name, v.value))
+            .collect::<Vec<_>>();
+        if !values.is_empty() {
+            let _ = writeln!(section, "{}: {}", s.spec.symbol, values.join(", "));
+        }
+    }
+    section
+}
 
-    data_section
+/// Renders the scheduled events after the window, with how many hours after the newest
+/// candle's open each is due, or nothing without events.
+pub fn build_event_section(snapshot: &MarketSnapshot) -> String {
+    if snapshot.events.is_empty() {
+        return String::new();
+    }
+    let newest = snapshot
+        .symbols
+        .first()
+        .and_then(|s| s.candles.last())
+        .map(|c| c[0]);
+    let mut section = String::from("\nUpcoming high-impact events:\n");
+    for event in &snapshot.events {
+        let _ = write!(
+            section,
+            "{} at {}",
+            event.name,
+            event.time.format("%Y-%m-%d %H:%M UTC")
+        );
+        if let Some(newest) = newest {
+            let hours = (event.time.timestamp() as f64 - newest) / 3600.0;
+            let _ = write!(section, " ({:.1}h after the last candle opens)", hours);
+        }
+        section.push('\n');
+    }
+    section
+}
+
+/// Overrides the single-asset output format: asks for one decision per symbol, each judged
+/// on that symbol's own next candle.
+pub fn build_multi_asset_instruction(symbols: &[&str]) -> String {
+    format!(
+        "\nMulti-asset mode: apply the analysis to each of {} as the target in turn, judging each on its own next candle. Return a single JSON object with the keys {}, each mapping to an object in the output format above for that symbol.\n",
+        symbols.join(", "),
+        symbols.join(", ")
+    )
+}
+
+/// Replaces the long/short decision with a target portfolio over `symbols` and cash.
+pub fn build_allocation_instruction(symbols: &[&str]) -> String {
+    format!(
+        "\nAllocation mode: instead of a single action, decide how to allocate a long-only portfolio across {} and cash for the next hour. Return a JSON object {{\"weights\": {{{}}}, \"cash\": number, \"rationale\": \"...\"}} where every weight and the cash are between 0 and 1 and together sum to 1.\n",
+        symbols.join(", "),
+        symbols
+            .iter()
+            .map(|s| format!("\"{}\": number", s))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// Turns the decision into a relative-value call on `target` against `hedge`.
+pub fn build_pair_instruction(target: &str, hedge: &str, threshold: f64) -> String {
+    format!(
+        "\nPair mode: instead of the direction of {t}/USD, decide whether {t} will outperform {h} over the next hour. \"long\" means long {t} and short {h}, expecting {t} to beat {h} by at least {pct:.1}%; \"short\" means short {t} and long {h}, expecting {t} to lag {h} by at least {pct:.1}%; otherwise \"none\". Omit invalidation_price and target_price.\n",
+        t = target,
+        h = hedge,
+        pct = threshold * 100.0
+    )
+}
+
+/// Lists candles flagged by the data-quality pass so the model can discount them.
+pub fn build_anomaly_notes(symbol: &str, anomalies: &[&CandleAnomaly]) -> String {
+    let mut notes = String::new();
+    for a in anomalies {
+        let _ = writeln!(
+            notes,
+            "Data quality note: {} candle at {:.0} flagged as {:?}",
+            symbol, a.time, a.kind
+        );
+    }
+    notes
 }
 
 #[cfg(test)]
 mod tests {
-    use super::build_data_section;
+    use super::*;
 
     #[test]
     fn test_build_prompt() {
@@ -144,7 +403,11 @@ mod tests {
             [1732845600.0, 151.0, 153.0, 150.0, 152.0, 8000.0],
         ];
 
-        let prompt = build_data_section(&eth_data, &btc_data, &sol_data);
+        let prompt = build_data_section(&MarketSnapshot::from_windows(&[
+            ("ETH", &eth_data[..], &[]),
+            ("BTC", &btc_data[..], &[]),
+            ("SOL", &sol_data[..], &[]),
+        ]));
         tracing::info!(%prompt);
         assert!(prompt.contains("\"action\":"));
         assert!(prompt.contains("\"rationale\":"));
@@ -154,4 +417,116 @@ mod tests {
         );
         assert!(prompt.contains("SOL: [[1732849200.00,150.00,152.00,149.50,151.00,10000.000000"));
     }
+
+    #[test]
+    fn test_role_annotated_sections() {
+        let data = [[0.0, 1.0, 1.0, 1.0, 1.0, 1.0]];
+        let section = build_data_section(&MarketSnapshot::from_windows(&[
+            ("ETH", &data[..], &[]),
+            ("BTC", &data[..], &[]),
+            ("DOGE", &data[..], &[]),
+        ]));
+        let headers = section
+            .lines()
+            .filter(|l| l.starts_with("TARGET") || l.starts_with("CONTEXT"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            headers,
+            [
+                "TARGET: ETH",
+                "CONTEXT: BTC (market leader)",
+                "CONTEXT: DOGE"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snapshot_context_section() {
+        use crate::implied_vol::IvRegime;
+        use crate::snapshot::{
+            AuxContext, DepthSummary, ImpliedVolatility, LiquidationSummary, Microstructure,
+        };
+
+        let data = [
+            [0.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+            [3600.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+        ];
+        let windows = [("ETH", &data[..], &[][..]), ("BTC", &data[..], &[][..])];
+        let plain = build_data_section(&MarketSnapshot::from_windows(&windows));
+        assert!(!plain.contains("Market context"));
+
+        let snapshot = MarketSnapshot::from_windows(&windows).with_context(
+            "BTC",
+            AuxContext {
+                funding_rate: Some(0.0001),
+                depth: Some(DepthSummary {
+                    bid_depth: 120.0,
+                    ask_depth: 80.5,
+                    spread: 0.0002,
+                }),
+                liquidations: None,
+                implied_volatility: None,
+                microstructure: None,
+                headlines: vec!["ETF inflows hit a record".to_string()],
+            },
+        );
+        let section = build_data_section(&snapshot);
+        assert!(section.starts_with(&plain));
+        assert!(section.ends_with(
+            "\nMarket context:\nBTC: funding rate +0.0100% per interval; order book depth within 1% 120.00 bid / 80.50 ask, spread 0.0200%\nBTC news: ETF inflows hit a record\n"
+        ));
+
+        let liquidations = LiquidationSummary {
+            hours: 24,
+            long_usd: 12_345_678.0,
+            short_usd: 4_100_000.0,
+            last_hour_long_usd: 1_200_000.0,
+            last_hour_short_usd: 300_000.0,
+        };
+        let section = build_data_section(&MarketSnapshot::from_windows(&windows).with_context(
+            "ETH",
+            AuxContext {
+                liquidations: Some(liquidations),
+                implied_volatility: Some(ImpliedVolatility {
+                    level: 71.24,
+                    change_24h: Some(0.184),
+                    regime: IvRegime::Spike,
+                }),
+                microstructure: Some(Microstructure {
+                    spread: 0.00005,
+                    taker_buy_share: 0.625,
+                    trades: 412,
+                    minutes: 14.6,
+                }),
+                ..Default::default()
+            },
+        ));
+        assert!(section.ends_with(
+            "\nMarket context:\nETH: liquidations over the last 24h $12.35M long / $4.10M short, last hour $1.20M long / $0.30M short; implied volatility (DVOL) 71.2, +18.4% over 24h, spike regime; current spread 0.0050%, takers bought 62.5% of the volume of the last 412 trades (15 min)\n"
+        ));
+
+        let quiet = build_data_section(&MarketSnapshot::from_windows(&windows).without_volume());
+        assert!(!quiet.contains("Volume features"));
+
+        let fomc = crate::calendar::CalendarEvent {
+            time: "1970-01-01T07:00:00Z".parse().unwrap(),
+            name: "FOMC rate decision".to_string(),
+            kind: crate::calendar::EventKind::Fomc,
+            impact: crate::calendar::Impact::High,
+        };
+        let section =
+            build_data_section(&MarketSnapshot::from_windows(&windows).with_events(vec![fomc]));
+        assert!(section.ends_with(
+            "\nUpcoming high-impact events:\nFOMC rate decision at 1970-01-01 07:00 UTC (6.0h after the last candle opens)\n"
+        ));
+
+        let registry = crate::features::FeatureRegistry::default()
+            .with(crate::features::VwapDistance)
+            .with(crate::features::TargetCorrelation);
+        let snapshot = MarketSnapshot::from_windows(&windows).with_features(&registry);
+        assert!(!build_data_section(&snapshot).contains("Features:"));
+        let section = build_data_section(&snapshot.with_prompt_features(true));
+        assert!(section
+            .ends_with("\nFeatures:\nETH: vwap_distance 0.0000\nBTC: vwap_distance 0.0000\n"));
+    }
 }
@@ -0,0 +1,171 @@
+use std::fmt::Write as _;
+
+use chrono::DateTime;
+use serde::Serialize;
+
+use crate::simulator::{summarize, SimulationSummary, Trade};
+
+/// Paper trades behind each version's rolling return: a day of hourly signals.
+pub const ROLLING_TRADES: usize = 24;
+
+/// A verified live signal and the paper trade it produced, if any.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveOutcome {
+    pub prompt_version: String,
+    /// Open time of the last candle in the window.
+    pub window_end: f64,
+    pub correct: bool,
+    pub trade: Option<Trade>,
+}
+
+/// The live record of one prompt version while it was champion.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PromptPnl {
+    pub prompt_version: String,
+    /// Window ends of the version's first and last verified signal.
+    pub first_signal: f64,
+    pub last_signal: f64,
+    pub signals: usize,
+    pub accuracy: f64,
+    pub simulation: SimulationSummary,
+    /// Compounded return of the last [`ROLLING_TRADES`] paper trades.
+    pub rolling_return: f64,
+    /// Mean return per trade minus that of the version live before this one; negative
+    /// after a swap that made things worse.
+    pub change_per_trade: Option<f64>,
+}
+
+impl PromptPnl {
+    /// Geometric mean return per paper trade; `None` without trades.
+    pub fn mean_return(&self) -> Option<f64> {
+        (self.simulation.trades > 0).then(|| {
+            (1.0 + self.simulation.total_return).powf(1.0 / self.simulation.trades as f64) - 1.0
+        })
+    }
+
+    /// Whether this version trades worse than the one it replaced.
+    pub fn regressed(&self) -> bool {
+        self.change_per_trade.is_some_and(|c| c < 0.0)
+    }
+}
+
+/// Groups `outcomes` by prompt version, ordered by when each version went live, and compares
+/// every version's return per trade with its predecessor's.
+pub fn prompt_pnl(outcomes: &[LiveOutcome]) -> Vec<PromptPnl> {
+    let mut sorted = outcomes.iter().collect::<Vec<_>>();
+    sorted.sort_by(|a, b| a.window_end.total_cmp(&b.window_end));
+
+    let mut versions: Vec<(&str, Vec<&LiveOutcome>)> = Vec::new();
+    for o in sorted {
+        match versions.iter_mut().find(|(v, _)| *v == o.prompt_version) {
+            Some((_, group)) => group.push(o),
+            None => versions.push((&o.prompt_version, vec![o])),
+        }
+    }
+
+    let mut previous: Option<f64> = None;
+    versions
+        .into_iter()
+        .map(|(version, group)| {
+            let trades = group.iter().filter_map(|o| o.trade).collect::<Vec<_>>();
+            let rolling = &trades[trades.len().saturating_sub(ROLLING_TRADES)..];
+            let mut pnl = PromptPnl {
+                prompt_version: version.to_string(),
+                first_signal: group[0].window_end,
+                last_signal: group[group.len() - 1].window_end,
+                signals: group.len(),
+                accuracy: group.iter().filter(|o| o.correct).count() as f64 / group.len() as f64,
+                simulation: summarize(&trades),
+                rolling_return: summarize(rolling).total_return,
+                change_per_trade: None,
+            };
+            let mean = pnl.mean_return();
+            pnl.change_per_trade = mean.zip(previous).map(|(m, p)| m - p);
+            previous = mean.or(previous);
+            pnl
+        })
+        .collect()
+}
+
+/// The records as a table, one line per version in the order they went live.
+pub fn describe_prompt_pnl(records: &[PromptPnl]) -> String {
+    let mut out = format!(
+        "{:<16} {:<16} {:>7} {:>9} {:>6} {:>9} {:>9} {:>10}\n",
+        "prompt", "live since", "signals", "accuracy", "trades", "return", "rolling", "vs prev"
+    );
+    for r in records {
+        let since = DateTime::from_timestamp(r.first_signal as i64, 0)
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        let change = r
+            .change_per_trade
+            .map(|c| format!("{:+.3}%", c * 100.0))
+            .unwrap_or_else(|| "-".to_string());
+        let _ = writeln!(
+            out,
+            "{:<16} {:<16} {:>7} {:>8.1}% {:>6} {:>+8.2}% {:>+8.2}% {:>10}{}",
+            r.prompt_version,
+            since,
+            r.signals,
+            r.accuracy * 100.0,
+            r.simulation.trades,
+            r.simulation.total_return * 100.0,
+            r.rolling_return * 100.0,
+            change,
+            if r.regressed() { "  regressed" } else { "" }
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Action;
+
+    fn outcome(version: &str, hour: usize, return_pct: f64) -> LiveOutcome {
+        let entry_time = hour as f64 * 3600.0;
+        LiveOutcome {
+            prompt_version: version.to_string(),
+            window_end: entry_time,
+            correct: return_pct > 0.0,
+            trade: Some(Trade {
+                side: Action::Long,
+                entry_time,
+                entry_price: 100.0,
+                exit_time: entry_time + 3600.0,
+                exit_price: 100.0 * (1.0 + return_pct),
+                return_pct,
+                funding: 0.0,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_prompt_pnl_flags_a_worse_swap() {
+        let mut outcomes = (0..30).map(|h| outcome("old", h, 0.01)).collect::<Vec<_>>();
+        outcomes.extend((30..33).map(|h| outcome("new", h, -0.01)));
+        outcomes.push(LiveOutcome {
+            trade: None,
+            ..outcome("new", 33, 0.0)
+        });
+        outcomes.reverse();
+
+        let records = prompt_pnl(&outcomes);
+        assert_eq!(records.len(), 2);
+        let (old, new) = (&records[0], &records[1]);
+        assert_eq!((old.prompt_version.as_str(), old.signals), ("old", 30));
+        assert!((old.rolling_return - (1.01f64.powi(24) - 1.0)).abs() < 1e-9);
+        assert!(!old.regressed());
+
+        assert_eq!((new.signals, new.simulation.trades), (4, 3));
+        assert_eq!(new.accuracy, 0.0);
+        assert!((new.change_per_trade.unwrap() + 0.02).abs() < 1e-9);
+        assert!(new.regressed());
+        assert!(describe_prompt_pnl(&records)
+            .lines()
+            .nth(2)
+            .unwrap()
+            .ends_with("regressed"));
+    }
+}
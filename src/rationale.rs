@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use serde::{Deserialize, Serialize};
+
+use crate::prediction::{parse_json, ParseError};
+use crate::Action;
+
+/// Consistency or grounding below this counts a rationale as nonsense.
+pub const SOUND_REASONING: f64 = 0.5;
+
+/// A grader's scores for one rationale, each in `[0, 1]`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RationaleScores {
+    /// How well the rationale argues for the action chosen rather than another one.
+    pub consistency: f64,
+    /// How well its claims match the data in the prompt.
+    pub grounding: f64,
+    /// Prices it cites that the data neither contains nor implies.
+    #[serde(default)]
+    pub unsupported_prices: Vec<f64>,
+    #[serde(default)]
+    pub notes: String,
+}
+
+impl RationaleScores {
+    pub fn is_sound(&self) -> bool {
+        self.consistency >= SOUND_REASONING
+            && self.grounding >= SOUND_REASONING
+            && self.unsupported_prices.is_empty()
+    }
+}
+
+/// A second-pass grade of one recorded prediction's rationale.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RationaleGrade {
+    pub run_id: String,
+    pub prompt_hash: String,
+    pub symbol: String,
+    /// Open time of the last candle in the window.
+    pub window_end: f64,
+    pub action: Action,
+    pub correct: Option<bool>,
+    /// Model that graded the rationale.
+    pub model: String,
+    #[serde(flatten)]
+    pub scores: RationaleScores,
+}
+
+/// Prompt asking a grader to score the `rationale` given for `action` in answer to
+/// `prompt`. The grader judges the reasoning only, never whether the call came true.
+pub fn build_grading_prompt(
+    prompt: &str,
+    action: Action,
+    rationale: &str,
+    key_factors: &[String],
+) -> String {
+    let mut out = String::new();
+    out.push_str("A trading model was given the following prompt and market data:\n\n");
+    out.push_str(prompt);
+    let _ = write!(
+        out,
+        "\n\nIt answered {:?} with this rationale:\n{}\n",
+        action, rationale
+    );
+    if !key_factors.is_empty() {
+        let _ = writeln!(out, "Key factors: {}", key_factors.join("; "));
+    }
+    out.push_str("\nGrade the reasoning only, not whether the action will turn out right:\n");
+    out.push_str("- consistency: from 0 to 1, how well the rationale supports the chosen action rather than arguing for another one\n");
+    out.push_str("- grounding: from 0 to 1, how well its claims about prices, trends and volume match the data above\n");
+    out.push_str("- unsupported_prices: every price the rationale cites that appears nowhere in the data and cannot be derived from it\n");
+    out.push_str("Respond with only a JSON object: {\"consistency\": 0.0, \"grounding\": 0.0, \"unsupported_prices\": [], \"notes\": \"one sentence\"}\n");
+    out
+}
+
+/// The scores in a grader's response, clamped to `[0, 1]`.
+pub fn parse_grade(response: &str) -> Result<RationaleScores, ParseError> {
+    let value = parse_json(response)?;
+    if !value.is_object() {
+        return Err(ParseError::NotAnObject);
+    }
+    let mut scores: RationaleScores =
+        serde_json::from_value(value).map_err(|e| ParseError::InvalidJson(e.to_string()))?;
+    scores.consistency = scores.consistency.clamp(0.0, 1.0);
+    scores.grounding = scores.grounding.clamp(0.0, 1.0);
+    Ok(scores)
+}
+
+/// Rationale quality of one prompt version's graded predictions.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RationaleQuality {
+    pub prompt_version: String,
+    pub graded: usize,
+    pub mean_consistency: f64,
+    pub mean_grounding: f64,
+    /// Share of rationales citing at least one unsupported price.
+    pub hallucination_rate: f64,
+    /// Correct predictions whose rationale is not [sound](RationaleScores::is_sound).
+    pub right_for_wrong_reasons: usize,
+}
+
+/// Aggregates `grades` per prompt version, in version order.
+pub fn rationale_quality(grades: &[RationaleGrade]) -> Vec<RationaleQuality> {
+    let mut by_version = BTreeMap::<&str, Vec<&RationaleGrade>>::new();
+    for grade in grades {
+        by_version
+            .entry(&grade.prompt_hash)
+            .or_default()
+            .push(grade);
+    }
+    by_version
+        .into_iter()
+        .map(|(version, grades)| {
+            let n = grades.len() as f64;
+            let mean = |f: fn(&RationaleScores) -> f64| {
+                grades.iter().map(|g| f(&g.scores)).sum::<f64>() / n
+            };
+            RationaleQuality {
+                prompt_version: version.to_string(),
+                graded: grades.len(),
+                mean_consistency: mean(|s| s.consistency),
+                mean_grounding: mean(|s| s.grounding),
+                hallucination_rate: mean(|s| !s.unsupported_prices.is_empty() as u8 as f64),
+                right_for_wrong_reasons: grades
+                    .iter()
+                    .filter(|g| g.correct == Some(true) && !g.scores.is_sound())
+                    .count(),
+            }
+        })
+        .collect()
+}
+
+pub fn describe_rationale_quality(quality: &[RationaleQuality]) -> String {
+    let mut out = format!(
+        "{:<16} {:>7} {:>12} {:>10} {:>13} {:>15}\n",
+        "prompt", "graded", "consistency", "grounding", "hallucinated", "right/unsound"
+    );
+    for q in quality {
+        let _ = writeln!(
+            out,
+            "{:<16} {:>7} {:>12.2} {:>10.2} {:>12.1}% {:>15}",
+            q.prompt_version,
+            q.graded,
+            q.mean_consistency,
+            q.mean_grounding,
+            q.hallucination_rate * 100.0,
+            q.right_for_wrong_reasons
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grade(version: &str, correct: bool, scores: RationaleScores) -> RationaleGrade {
+        RationaleGrade {
+            run_id: "run".to_string(),
+            prompt_hash: version.to_string(),
+            symbol: "ETH".to_string(),
+            window_end: 3600.0,
+            action: Action::Long,
+            correct: Some(correct),
+            model: "o1-mini".to_string(),
+            scores,
+        }
+    }
+
+    #[test]
+    fn test_grading_prompt_and_quality() {
+        let prompt = build_grading_prompt(
+            "Base prompt\nETH: [[0,1,2,3,4,5]]",
+            Action::Long,
+            "Higher lows above 3400",
+            &["volume rising".to_string()],
+        );
+        assert!(prompt.contains("It answered Long with this rationale:\nHigher lows above 3400"));
+        assert!(prompt.contains("Key factors: volume rising"));
+
+        let scores = parse_grade(
+            "```json\n{\"consistency\": 1.2, \"grounding\": 0.3, \"unsupported_prices\": [3400], \"notes\": \"No such low\"}\n```",
+        )
+        .unwrap();
+        assert_eq!((scores.consistency, scores.grounding), (1.0, 0.3));
+        assert_eq!(scores.unsupported_prices, vec![3400.0]);
+        assert!(!scores.is_sound());
+        assert_eq!(parse_grade("[1, 2]"), Err(ParseError::NotAnObject));
+
+        let sound = RationaleScores {
+            consistency: 0.9,
+            grounding: 0.8,
+            ..Default::default()
+        };
+        let grades = [
+            grade("aaa", true, scores),
+            grade("aaa", false, sound.clone()),
+            grade("bbb", true, sound),
+        ];
+        let quality = rationale_quality(&grades);
+        assert_eq!(quality.len(), 2);
+        assert_eq!(quality[0].graded, 2);
+        assert!((quality[0].mean_consistency - 0.95).abs() < 1e-9);
+        assert_eq!(quality[0].hallucination_rate, 0.5);
+        assert_eq!(quality[0].right_for_wrong_reasons, 1);
+        assert_eq!(quality[1].right_for_wrong_reasons, 0);
+        assert!(describe_rationale_quality(&quality).contains("aaa"));
+    }
+}
@@ -0,0 +1,557 @@
+use std::fmt::Write as _;
+#[cfg(feature = "native")]
+use std::fs;
+#[cfg(feature = "native")]
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::broker::{owned_positions, Broker, OpenOrder, Order, Position, Side};
+use crate::derisk::DrawdownState;
+use crate::simulator::{level_outcome, LevelOutcome};
+use crate::Action;
+
+/// Where the daemon records what it holds at its broker, relative to the working directory.
+#[cfg(feature = "native")]
+pub const EXECUTION_STATE_FILE: &str = "cache/execution_state.json";
+/// Positions differing by less than this share are the same, since venues round order
+/// sizes to their own step.
+const QUANTITY_TOLERANCE: f64 = 0.001;
+
+/// The stop and target a held position is closed at, watched by the daemon rather than
+/// the venue so they work the same everywhere.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bracket {
+    pub symbol: String,
+    pub side: Action,
+    pub entry_price: f64,
+    pub stop: Option<f64>,
+    pub target: Option<f64>,
+}
+
+impl Bracket {
+    /// The exit price when `candle` traded through the stop or target, as
+    /// [`level_outcome`] decides.
+    pub fn triggered(&self, candle: &[f64; 6]) -> Option<f64> {
+        match level_outcome(self.side, self.entry_price, self.stop, self.target, candle)? {
+            LevelOutcome::Invalidated => self.stop,
+            LevelOutcome::Target => self.target,
+            LevelOutcome::Open => None,
+        }
+    }
+}
+
+/// Net quantity of `symbol` over `positions`, `0.0` rather than `-0.0` when none.
+fn net(positions: &[Position], symbol: &str) -> f64 {
+    positions
+        .iter()
+        .filter(|p| p.symbol == symbol)
+        .fold(0.0, |net, p| net + p.quantity)
+}
+
+pub(crate) fn side_of(quantity: f64) -> Action {
+    if quantity > 0.0 {
+        Action::Long
+    } else if quantity < 0.0 {
+        Action::Short
+    } else {
+        Action::None
+    }
+}
+
+/// What the daemon believes it holds at its broker, saved after every change so a restart
+/// can check it against the venue.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionState {
+    /// Name of the broker the state was recorded at.
+    pub broker: String,
+    pub positions: Vec<Position>,
+    pub orders: Vec<OpenOrder>,
+    pub brackets: Vec<Bracket>,
+    #[serde(default)]
+    pub drawdown: DrawdownState,
+    /// Holdings at a spot venue the daemon didn't buy, such as the account's balance before
+    /// it started. Never traded; see [`Owned`](crate::broker::Owned).
+    #[serde(default)]
+    pub external: Vec<Position>,
+}
+
+impl ExecutionState {
+    /// The recorded state, empty when none was saved.
+    #[cfg(feature = "native")]
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(path)?;
+        serde_json::from_str(&data).context("Corrupt execution state")
+    }
+
+    /// Writes the state through a temporary file, so a crash never leaves half of it.
+    #[cfg(feature = "native")]
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let partial = path.with_extension("json.partial");
+        fs::write(&partial, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&partial, path).context("Failed to write execution state")
+    }
+
+    /// The symbols the daemon recorded holding, ordering or bracketing: the ones it traded
+    /// itself rather than whatever else the account holds.
+    pub fn owned(&self) -> Vec<String> {
+        let mut symbols = self
+            .positions
+            .iter()
+            .map(|p| &p.symbol)
+            .chain(self.orders.iter().map(|o| &o.symbol))
+            .chain(self.brackets.iter().map(|b| &b.symbol))
+            .cloned()
+            .collect::<Vec<_>>();
+        symbols.sort_unstable();
+        symbols.dedup();
+        symbols
+    }
+
+    pub fn held(&self, symbol: &str) -> f64 {
+        net(&self.positions, symbol)
+    }
+
+    pub fn bracket(&self, symbol: &str) -> Option<&Bracket> {
+        self.brackets.iter().find(|b| b.symbol == symbol)
+    }
+
+    /// Replaces `symbol`'s bracket, removing it when `bracket` is `None`.
+    pub fn set_bracket(&mut self, symbol: &str, bracket: Option<Bracket>) {
+        self.brackets.retain(|b| b.symbol != symbol);
+        self.brackets.extend(bracket);
+    }
+
+    /// Counts whatever `venue` holds long beyond the recorded positions as the account's
+    /// own rather than the daemon's.
+    pub fn set_external(&mut self, venue: &[Position]) {
+        let mut symbols = venue.iter().map(|p| p.symbol.as_str()).collect::<Vec<_>>();
+        symbols.sort_unstable();
+        symbols.dedup();
+        self.external = symbols
+            .into_iter()
+            .filter_map(|symbol| {
+                let surplus = net(venue, symbol) - self.held(symbol).max(0.0);
+                (surplus > 0.0).then(|| Position {
+                    symbol: symbol.to_string(),
+                    quantity: surplus,
+                    entry_price: None,
+                })
+            })
+            .collect();
+    }
+
+    /// Takes the venue's positions and open orders as the truth, keeping only the
+    /// brackets of positions still held on the same side.
+    pub fn adopt(&mut self, positions: Vec<Position>, orders: Vec<OpenOrder>) {
+        self.brackets.retain(|b| {
+            positions
+                .iter()
+                .any(|p| p.symbol == b.symbol && side_of(p.quantity) == b.side)
+        });
+        self.positions = positions;
+        self.orders = orders;
+    }
+}
+
+/// A way the venue differs from the recorded state.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Discrepancy {
+    /// The venue holds another quantity than recorded.
+    Position {
+        symbol: String,
+        recorded: f64,
+        venue: f64,
+    },
+    /// An order open at the venue that was never recorded, such as one placed by hand.
+    UnknownOrder { id: String, symbol: String },
+    /// A recorded order no longer open: filled or cancelled while the daemon was down.
+    ClosedOrder { id: String, symbol: String },
+}
+
+impl Discrepancy {
+    pub fn describe(&self) -> String {
+        match self {
+            Discrepancy::Position {
+                symbol,
+                recorded,
+                venue,
+            } => format!("{symbol}: recorded {recorded:.8}, venue holds {venue:.8}"),
+            Discrepancy::UnknownOrder { id, symbol } => {
+                format!("{symbol}: order {id} is open at the venue but was never recorded")
+            }
+            Discrepancy::ClosedOrder { id, symbol } => {
+                format!("{symbol}: recorded order {id} is no longer open")
+            }
+        }
+    }
+}
+
+pub fn describe_discrepancies(found: &[Discrepancy]) -> String {
+    let mut out = String::new();
+    for d in found {
+        let _ = writeln!(out, "  {}", d.describe());
+    }
+    out
+}
+
+/// How `recorded` differs from the venue's `positions` and open `orders`.
+pub fn reconcile(
+    recorded: &ExecutionState,
+    positions: &[Position],
+    orders: &[OpenOrder],
+) -> Vec<Discrepancy> {
+    let mut symbols = recorded
+        .positions
+        .iter()
+        .chain(positions)
+        .map(|p| p.symbol.as_str())
+        .collect::<Vec<_>>();
+    symbols.sort_unstable();
+    symbols.dedup();
+
+    let mut found = Vec::new();
+    for symbol in symbols {
+        let held = recorded.held(symbol);
+        let venue = net(positions, symbol);
+        if (held - venue).abs() > QUANTITY_TOLERANCE * held.abs().max(venue.abs()) {
+            found.push(Discrepancy::Position {
+                symbol: symbol.to_string(),
+                recorded: held,
+                venue,
+            });
+        }
+    }
+    for order in orders {
+        if !recorded.orders.iter().any(|o| o.id == order.id) {
+            found.push(Discrepancy::UnknownOrder {
+                id: order.id.clone(),
+                symbol: order.symbol.clone(),
+            });
+        }
+    }
+    for order in &recorded.orders {
+        if !orders.iter().any(|o| o.id == order.id) {
+            found.push(Discrepancy::ClosedOrder {
+                id: order.id.clone(),
+                symbol: order.symbol.clone(),
+            });
+        }
+    }
+    found
+}
+
+/// What to do on startup when the venue differs from the recorded state.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReconcilePolicy {
+    /// Take the venue's positions and orders as they are and carry on managing them.
+    #[default]
+    Adopt,
+    /// Cancel every open order and close every position, starting flat.
+    Flatten,
+    /// Refuse to start until someone has looked.
+    Halt,
+}
+
+impl std::str::FromStr for ReconcilePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "adopt" => Ok(ReconcilePolicy::Adopt),
+            "flatten" => Ok(ReconcilePolicy::Flatten),
+            "halt" => Ok(ReconcilePolicy::Halt),
+            _ => anyhow::bail!(
+                "Unknown reconcile policy {:?} (expected adopt, flatten or halt)",
+                s
+            ),
+        }
+    }
+}
+
+/// The market order closing `position`, sized at `price`.
+pub fn closing_order(position: &Position, price: f64, client_id: &str) -> Order {
+    Order {
+        client_id: client_id.to_string(),
+        symbol: position.symbol.clone(),
+        side: if position.quantity > 0.0 {
+            Side::Sell
+        } else {
+            Side::Buy
+        },
+        quantity: position.quantity.abs(),
+        limit: None,
+        reference_price: price,
+    }
+}
+
+/// Checks `recorded` against `broker` on startup and resolves what differs per `policy`,
+/// leaving `recorded` as the venue now stands. Returns what differed. Flattening only
+/// touches the symbols `recorded` [owns](ExecutionState::owned), closing their positions at
+/// the current market `prices`.
+pub async fn recover(
+    broker: &dyn Broker,
+    recorded: &mut ExecutionState,
+    policy: ReconcilePolicy,
+    prices: &[(String, f64)],
+) -> Result<Vec<Discrepancy>> {
+    if !recorded.broker.is_empty() && recorded.broker != broker.name() {
+        tracing::warn!(
+            recorded = %recorded.broker,
+            broker = broker.name(),
+            "Execution state was recorded at another broker; starting from the venue's"
+        );
+        *recorded = ExecutionState::default();
+    }
+    recorded.broker = broker.name().to_string();
+
+    let mut positions = broker.positions().await?;
+    if !broker.can_short() {
+        // A spot account's holdings are its owner's too: whatever exceeds the record is theirs
+        recorded.set_external(&positions);
+        positions = owned_positions(&positions, &recorded.external);
+    }
+    let orders = broker.open_orders().await?;
+    let found = reconcile(recorded, &positions, &orders);
+    if found.is_empty() {
+        recorded.adopt(positions, orders);
+        return Ok(found);
+    }
+    match policy {
+        ReconcilePolicy::Adopt => recorded.adopt(positions, orders),
+        ReconcilePolicy::Flatten => {
+            let owned = recorded.owned();
+            for order in orders.iter().filter(|o| owned.contains(&o.symbol)) {
+                broker.cancel(&order.symbol, &order.id).await?;
+            }
+            for position in positions.iter().filter(|p| owned.contains(&p.symbol)) {
+                let price = prices
+                    .iter()
+                    .find(|(symbol, _)| *symbol == position.symbol)
+                    .map(|(_, price)| *price)
+                    .with_context(|| format!("No market price to close {} at", position.symbol))?;
+                let client_id = format!("recover-{}", position.symbol);
+                broker
+                    .place(&closing_order(position, price, &client_id))
+                    .await?;
+            }
+            recorded.brackets.clear();
+            let positions = owned_positions(&broker.positions().await?, &recorded.external);
+            recorded.adopt(positions, broker.open_orders().await?);
+        }
+        ReconcilePolicy::Halt => anyhow::bail!(
+            "{} differs from the recorded execution state:\n{}",
+            broker.name(),
+            describe_discrepancies(&found)
+        ),
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::BoxFuture;
+
+    use crate::broker::{follow_signal, OrderAck, Owned, PaperBroker, RiskLimits};
+    use crate::ledger::Account;
+
+    #[test]
+    fn test_recover_per_policy() {
+        let limits = RiskLimits::default();
+        let paper = PaperBroker::new(10_000.0);
+        let prices = [("ETH".to_string(), 100.0)];
+        futures::executor::block_on(async {
+            follow_signal(&paper, "ETH", Action::Long, 100.0, &limits, None, "a")
+                .await
+                .unwrap();
+            let mut recorded = ExecutionState::default();
+            recover(&paper, &mut recorded, ReconcilePolicy::Adopt, &prices)
+                .await
+                .unwrap();
+            recorded.set_bracket(
+                "ETH",
+                Some(Bracket {
+                    symbol: "ETH".into(),
+                    side: Action::Long,
+                    entry_price: 100.0,
+                    stop: Some(95.0),
+                    target: Some(110.0),
+                }),
+            );
+            let bracket = recorded.bracket("ETH").unwrap().clone();
+            assert_eq!(
+                bracket.triggered(&[0.0, 100.0, 104.0, 94.0, 99.0, 1.0]),
+                Some(95.0)
+            );
+            assert_eq!(
+                bracket.triggered(&[0.0, 100.0, 104.0, 97.0, 99.0, 1.0]),
+                None
+            );
+
+            // Nothing changed while down: the bracket is resumed as it was
+            let found = recover(&paper, &mut recorded, ReconcilePolicy::Halt, &prices)
+                .await
+                .unwrap();
+            assert!(found.is_empty());
+            assert_eq!(recorded.bracket("ETH"), Some(&bracket));
+
+            // Closed by hand while down: halting refuses, adopting drops the bracket
+            follow_signal(&paper, "ETH", Action::None, 100.0, &limits, None, "b")
+                .await
+                .unwrap();
+            let halted = recover(
+                &paper,
+                &mut recorded.clone(),
+                ReconcilePolicy::Halt,
+                &prices,
+            )
+            .await;
+            assert!(halted.unwrap_err().to_string().contains("venue holds 0.0"));
+            let found = recover(
+                &paper,
+                &mut recorded.clone(),
+                ReconcilePolicy::Adopt,
+                &prices,
+            )
+            .await
+            .unwrap();
+            assert_eq!(found.len(), 1);
+
+            // Reopened short with a resting order nobody recorded: flattening clears both
+            follow_signal(&paper, "ETH", Action::Short, 100.0, &limits, None, "c")
+                .await
+                .unwrap();
+            let mut bid = closing_order(&paper.positions().await.unwrap()[0], 100.0, "d");
+            bid.limit = Some(90.0);
+            paper.place(&bid).await.unwrap();
+            // SOL bought by hand isn't the daemon's to close
+            let sol = Order {
+                client_id: "e".into(),
+                symbol: "SOL".into(),
+                side: Side::Buy,
+                quantity: 2.0,
+                limit: None,
+                reference_price: 150.0,
+            };
+            paper.place(&sol).await.unwrap();
+            let found = recover(&paper, &mut recorded, ReconcilePolicy::Flatten, &prices)
+                .await
+                .unwrap();
+            assert!(found
+                .iter()
+                .any(|d| matches!(d, Discrepancy::UnknownOrder { .. })));
+            assert!(recorded.orders.is_empty() && recorded.brackets.is_empty());
+            assert_eq!(recorded.owned(), ["SOL"]);
+            let held = paper.positions().await.unwrap();
+            assert_eq!((held.len(), held[0].symbol.as_str()), (1, "SOL"));
+            // Once adopted it is, but there is no SOL price to close it at
+            paper
+                .place(&Order {
+                    client_id: "f".into(),
+                    ..sol
+                })
+                .await
+                .unwrap();
+            let unpriced = recover(&paper, &mut recorded, ReconcilePolicy::Flatten, &prices).await;
+            assert!(unpriced.unwrap_err().to_string().contains("close SOL"));
+        });
+    }
+
+    /// A spot account, which reports its owner's coins as positions.
+    struct MockBroker(PaperBroker);
+
+    impl Broker for MockBroker {
+        fn name(&self) -> &str {
+            "mock"
+        }
+
+        fn can_short(&self) -> bool {
+            false
+        }
+
+        fn place<'a>(&'a self, order: &'a Order) -> BoxFuture<'a, Result<OrderAck>> {
+            self.0.place(order)
+        }
+
+        fn cancel<'a>(&'a self, symbol: &'a str, order_id: &'a str) -> BoxFuture<'a, Result<()>> {
+            self.0.cancel(symbol, order_id)
+        }
+
+        fn open_orders(&self) -> BoxFuture<'_, Result<Vec<OpenOrder>>> {
+            self.0.open_orders()
+        }
+
+        fn positions(&self) -> BoxFuture<'_, Result<Vec<Position>>> {
+            self.0.positions()
+        }
+
+        fn balances(&self) -> BoxFuture<'_, Result<Account>> {
+            self.0.balances()
+        }
+    }
+
+    #[test]
+    fn test_existing_spot_balance_is_never_sold() {
+        let limits = RiskLimits::default();
+        let mock = MockBroker(PaperBroker::new(10_000.0));
+        let venue_eth = || async { mock.positions().await.unwrap()[0].quantity };
+        futures::executor::block_on(async {
+            // The owner's own 3 ETH, there before the daemon started
+            let own = Order {
+                client_id: "own".into(),
+                symbol: "ETH".into(),
+                side: Side::Buy,
+                quantity: 3.0,
+                limit: None,
+                reference_price: 100.0,
+            };
+            mock.place(&own).await.unwrap();
+            let mut recorded = ExecutionState::default();
+            let found = recover(&mock, &mut recorded, ReconcilePolicy::Halt, &[])
+                .await
+                .unwrap();
+            assert!(found.is_empty());
+            assert_eq!(recorded.held("ETH"), 0.0);
+
+            let external = recorded.external.clone();
+            let owned = Owned {
+                broker: &mock,
+                external: &external,
+            };
+            let none = follow_signal(&owned, "ETH", Action::None, 100.0, &limits, None, "a");
+            assert_eq!(none.await.unwrap(), None);
+            let short = follow_signal(&owned, "ETH", Action::Short, 100.0, &limits, None, "b");
+            assert_eq!(short.await.unwrap(), None);
+            assert_eq!(venue_eth().await, 3.0);
+
+            // What the daemon buys is its to sell, and only that
+            follow_signal(&owned, "ETH", Action::Long, 100.0, &limits, None, "c")
+                .await
+                .unwrap();
+            recorded.adopt(owned.positions().await.unwrap(), Vec::new());
+            // Still told apart after a restart
+            recover(&mock, &mut recorded, ReconcilePolicy::Halt, &[])
+                .await
+                .unwrap();
+            assert_eq!(
+                (recorded.held("ETH"), recorded.external.clone()),
+                (10.0, external.clone())
+            );
+            follow_signal(&owned, "ETH", Action::None, 100.0, &limits, None, "d")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(venue_eth().await, 3.0);
+        });
+    }
+}
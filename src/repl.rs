@@ -0,0 +1,296 @@
+use std::fs;
+use std::io::Write as _;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::data_quality::CandleAnomaly;
+use crate::prediction::parse_model_response;
+use crate::store::fetch_range;
+use crate::thresholds::{load_labeling, LABELING_FILE};
+use crate::truncation::fit_prompt;
+use crate::{
+    analyze_data_gpt, describe_prompt_cost, prepare_candles, CallKind, Model, CANDLE_HOURS,
+    GRANULARITY, PROMPT_FILE, RESPONSE_STRICTNESS,
+};
+
+const HELP: &str = "\
+fetch <SYMBOL> <N>h    load the last N hours of candles (ETH first: it is the one labeled)
+at <time>|latest       end the window at the candle opening at <time> (unix secs, RFC 3339 or YYYY-MM-DD HH:MM)
+label [SYMBOL]         show the window's candles with their labels
+prompt preview         build and print the exact prompt for the window
+ask <model>            send that prompt to o1-mini or o1-preview and show the raw and parsed answer
+help                   show this
+quit                   leave";
+
+/// One line typed at the REPL.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplCommand {
+    Fetch {
+        symbol: String,
+        hours: i64,
+    },
+    /// `None` goes back to the latest candle.
+    At(Option<DateTime<Utc>>),
+    Label(Option<String>),
+    PromptPreview,
+    Ask(Model),
+    Help,
+    Quit,
+}
+
+/// A unix timestamp in seconds, an RFC 3339 time, or `YYYY-MM-DD HH:MM` in UTC.
+pub fn parse_time(s: &str) -> Result<DateTime<Utc>> {
+    if let Ok(secs) = s.parse::<i64>() {
+        return DateTime::from_timestamp(secs, 0).context("Timestamp out of range");
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    NaiveDateTime::parse_from_str(&s.replace('T', " "), "%Y-%m-%d %H:%M")
+        .map(|t| t.and_utc())
+        .with_context(|| format!("Can't read {:?} as a time", s))
+}
+
+pub fn parse_command(line: &str) -> Result<ReplCommand> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    Ok(match words[..] {
+        ["fetch", symbol, hours] => {
+            let hours = hours
+                .strip_suffix('h')
+                .unwrap_or(hours)
+                .parse()
+                .with_context(|| format!("Can't read {:?} as hours", hours))?;
+            ReplCommand::Fetch {
+                symbol: symbol.to_uppercase(),
+                hours,
+            }
+        }
+        ["at", "latest"] => ReplCommand::At(None),
+        ["at", ref time @ ..] if !time.is_empty() => {
+            ReplCommand::At(Some(parse_time(&time.join(" "))?))
+        }
+        ["label"] => ReplCommand::Label(None),
+        ["label", symbol] => ReplCommand::Label(Some(symbol.to_uppercase())),
+        ["prompt", "preview"] => ReplCommand::PromptPreview,
+        ["ask", model] => ReplCommand::Ask(model.parse()?),
+        ["help"] | ["?"] => ReplCommand::Help,
+        ["quit"] | ["exit"] => ReplCommand::Quit,
+        _ => anyhow::bail!("Unknown command {:?}; try `help`", line.trim()),
+    })
+}
+
+/// End (exclusive) of the window that closes with the candle opening at `at`, or with the
+/// latest candle.
+pub fn window_end(candles: &[[f64; 6]], at: Option<DateTime<Utc>>) -> Option<usize> {
+    let end = match at {
+        Some(at) => candles.partition_point(|c| c[0] <= at.timestamp() as f64),
+        None => candles.len(),
+    };
+    (end >= CANDLE_HOURS).then_some(end)
+}
+
+/// A fetched symbol with its cleaned candles and their anomalies.
+type Series = (String, Vec<[f64; 6]>, Vec<CandleAnomaly>);
+
+#[derive(Default)]
+struct Session {
+    /// Fetched series in fetch order, which is also prompt order.
+    series: Vec<Series>,
+    at: Option<DateTime<Utc>>,
+    prompt: Option<String>,
+}
+
+impl Session {
+    fn series(&self, symbol: &str) -> Option<&Series> {
+        self.series.iter().find(|(s, _, _)| s == symbol)
+    }
+
+    fn build_prompt(&self) -> Result<String> {
+        anyhow::ensure!(
+            !self.series.is_empty(),
+            "Nothing fetched yet; try `fetch ETH 48h`"
+        );
+        let base_prompt =
+            fs::read_to_string(PROMPT_FILE).context("Failed to read base prompt file")?;
+        let mut windows = Vec::new();
+        for (symbol, candles, anomalies) in self.series.iter() {
+            let end = window_end(candles, self.at).with_context(|| {
+                format!(
+                    "{} has fewer than {} candles up to then",
+                    symbol, CANDLE_HOURS
+                )
+            })?;
+            windows.push((
+                symbol.as_str(),
+                &candles[end - CANDLE_HOURS..end],
+                &anomalies[..],
+            ));
+        }
+        Ok(fit_prompt(&base_prompt, &windows, Model::O1Mini.prompt_token_budget()).0)
+    }
+
+    /// The label of the window's last candle of the first fetched symbol, once the candle
+    /// after it is known.
+    fn window_label(&self) -> Result<Option<(String, crate::Action)>> {
+        let Some((symbol, candles, _)) = self.series.first() else {
+            return Ok(None);
+        };
+        let labels = load_labeling(Path::new(LABELING_FILE))?.label(symbol, candles);
+        Ok(window_end(candles, self.at)
+            .filter(|&end| end < candles.len())
+            .map(|end| (symbol.clone(), labels[end - 1])))
+    }
+
+    async fn run(&mut self, command: ReplCommand) -> Result<()> {
+        match command {
+            ReplCommand::Fetch { symbol, hours } => {
+                let end = Utc::now();
+                let raw =
+                    fetch_range(&symbol, GRANULARITY, end - Duration::hours(hours), end).await?;
+                let (candles, anomalies) = prepare_candles(&symbol, raw);
+                println!(
+                    "{}: {} candles, {} flagged by the data-quality pass",
+                    symbol,
+                    candles.len(),
+                    anomalies.len()
+                );
+                match self.series.iter_mut().find(|(s, _, _)| *s == symbol) {
+                    Some(series) => *series = (symbol, candles, anomalies),
+                    None => self.series.push((symbol, candles, anomalies)),
+                }
+                self.prompt = None;
+            }
+            ReplCommand::At(at) => {
+                self.at = at;
+                self.prompt = None;
+                match at {
+                    Some(at) => println!("Window ends at the candle opening at {}", at),
+                    None => println!("Window ends at the latest candle"),
+                }
+            }
+            ReplCommand::Label(symbol) => {
+                let (symbol, candles, _) = match &symbol {
+                    Some(symbol) => self.series(symbol),
+                    None => self.series.first(),
+                }
+                .context("That symbol hasn't been fetched")?;
+                let end =
+                    window_end(candles, self.at).context("Not enough candles for a window")?;
+                let labels = load_labeling(Path::new(LABELING_FILE))?.label(symbol, candles);
+                for (candle, label) in candles[end - CANDLE_HOURS..end]
+                    .iter()
+                    .zip(&labels[end - CANDLE_HOURS..end])
+                {
+                    let time = DateTime::from_timestamp(candle[0] as i64, 0).unwrap_or_default();
+                    println!("{} {} close {:>10.2}  {:?}", symbol, time, candle[4], label);
+                }
+                if end == candles.len() {
+                    println!("(the last candle has no successor yet, so its label is provisional)");
+                }
+            }
+            ReplCommand::PromptPreview => {
+                let prompt = self.build_prompt()?;
+                println!("{}", prompt);
+                println!("--- {} ---", describe_prompt_cost(&prompt, Model::O1Mini));
+                self.prompt = Some(prompt);
+            }
+            ReplCommand::Ask(model) => {
+                let prompt = match &self.prompt {
+                    Some(prompt) => prompt.clone(),
+                    None => self.build_prompt()?,
+                };
+                let response = analyze_data_gpt(&prompt, model, CallKind::Live).await?;
+                println!("--- raw response ---\n{}", response);
+                match parse_model_response(&response, RESPONSE_STRICTNESS) {
+                    Ok(prediction) => println!("--- parsed ---\n{:#?}", prediction),
+                    Err(err) => println!("--- unusable response ---\n{}", err),
+                }
+                if let Some((symbol, label)) = self.window_label()? {
+                    println!("--- label ---\n{} {:?}", symbol, label);
+                }
+                self.prompt = Some(prompt);
+            }
+            ReplCommand::Help => println!("{}", HELP),
+            ReplCommand::Quit => {}
+        }
+        Ok(())
+    }
+}
+
+/// Reads commands from stdin until `quit` or end of input. A failing command is reported
+/// and the session carries on.
+pub async fn run_repl() -> Result<()> {
+    println!("happychartsv2 REPL; `help` lists commands");
+    let mut session = Session::default();
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        print!("> ");
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next_line().await? else {
+            return Ok(());
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let command = match parse_command(&line) {
+            Ok(ReplCommand::Quit) => return Ok(()),
+            Ok(command) => command,
+            Err(err) => {
+                println!("{}", err);
+                continue;
+            }
+        };
+        if let Err(err) = session.run(command).await {
+            println!("Error: {:#}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands_and_window() {
+        assert_eq!(
+            parse_command("fetch eth 48h").unwrap(),
+            ReplCommand::Fetch {
+                symbol: "ETH".into(),
+                hours: 48
+            }
+        );
+        assert_eq!(
+            parse_command("ask o1-preview").unwrap(),
+            ReplCommand::Ask(Model::O1Preview)
+        );
+        assert_eq!(
+            parse_command("  prompt   preview ").unwrap(),
+            ReplCommand::PromptPreview
+        );
+        assert!(parse_command("ask gpt-2").is_err());
+        assert!(parse_command("fetch ETH lots").is_err());
+
+        let t = parse_time("2024-12-02T14:00:00Z").unwrap();
+        assert_eq!(parse_time("1733148000").unwrap(), t);
+        assert_eq!(parse_time("2024-12-02 14:00").unwrap(), t);
+        assert_eq!(parse_command("at latest").unwrap(), ReplCommand::At(None));
+        assert_eq!(
+            parse_command("at 2024-12-02 14:00").unwrap(),
+            ReplCommand::At(Some(t))
+        );
+
+        let candles = (0..30)
+            .map(|h| [(h * 3600) as f64, 1.0, 1.0, 1.0, 1.0, 1.0])
+            .collect::<Vec<_>>();
+        assert_eq!(window_end(&candles, None), Some(30));
+        let at = DateTime::from_timestamp(25 * 3600, 0);
+        assert_eq!(window_end(&candles, at), Some(26));
+        assert_eq!(
+            window_end(&candles, DateTime::from_timestamp(3600, 0)),
+            None
+        );
+    }
+}
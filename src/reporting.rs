@@ -0,0 +1,276 @@
+use std::env;
+use std::error::Error;
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+static REPORTER: OnceLock<Option<ErrorReporter>> = OnceLock::new();
+
+/// Sends errors and panics to a Sentry-compatible store endpoint, so unattended runs surface
+/// failures without anyone reading the logs.
+#[derive(Debug, Clone)]
+pub struct ErrorReporter {
+    store_url: String,
+    key: String,
+    environment: String,
+    client: reqwest::Client,
+}
+
+/// The store endpoint and public key of a DSN like `https://<key>@<host>/<project>`.
+pub fn parse_dsn(dsn: &str) -> Result<(String, String)> {
+    let (scheme, rest) = dsn.split_once("://").context("DSN has no scheme")?;
+    let (key, rest) = rest.split_once('@').context("DSN has no public key")?;
+    let (host, project) = rest.rsplit_once('/').context("DSN has no project id")?;
+    if key.is_empty() || project.is_empty() {
+        anyhow::bail!("DSN is missing its key or project id");
+    }
+    let key = key.split(':').next().unwrap_or(key);
+    Ok((
+        format!("{}://{}/api/{}/store/", scheme, host, project),
+        key.to_string(),
+    ))
+}
+
+/// A coarse failure category from the error chain, used to group reports.
+pub fn categorize(err: &(dyn Error + 'static)) -> &'static str {
+    let chain = error_chain(err).join(": ").to_lowercase();
+    if chain.contains("coinbase") {
+        "exchange"
+    } else if chain.contains("unusable model response") {
+        "model_response"
+    } else if chain.contains("model api") || chain.contains("openai") {
+        "model"
+    } else if ["postgres", "upload", "storage", "cache"]
+        .iter()
+        .any(|w| chain.contains(w))
+    {
+        "storage"
+    } else if chain.contains("prompt") {
+        "prompt"
+    } else if sources(err).any(|e| e.is::<reqwest::Error>()) {
+        "network"
+    } else {
+        "other"
+    }
+}
+
+fn sources<'a>(err: &'a (dyn Error + 'static)) -> impl Iterator<Item = &'a (dyn Error + 'static)> {
+    std::iter::successors(Some(err), |&e| e.source())
+}
+
+fn error_chain(err: &(dyn Error + 'static)) -> Vec<String> {
+    sources(err).map(|e| e.to_string()).collect()
+}
+
+/// A Sentry store event. `context` becomes tags, next to the category.
+pub fn error_event(
+    level: &str,
+    category: &str,
+    chain: &[String],
+    context: &[(&str, String)],
+    environment: &str,
+) -> Value {
+    let now = Utc::now();
+    let message = chain.join(": ");
+    let event_id = Sha256::digest(format!("{:?}{}", now.timestamp_nanos_opt(), message));
+    let mut tags = Map::new();
+    tags.insert("category".to_string(), json!(category));
+    for (key, value) in context {
+        tags.insert(key.to_string(), json!(value));
+    }
+    json!({
+        "event_id": format!("{:x}", event_id)[..32],
+        "timestamp": now.to_rfc3339(),
+        "platform": "other",
+        "level": level,
+        "logger": env!("CARGO_PKG_NAME"),
+        "release": concat!(env!("CARGO_PKG_NAME"), "@", env!("CARGO_PKG_VERSION")),
+        "environment": environment,
+        "message": { "formatted": message },
+        "exception": {
+            "values": [{ "type": category, "value": chain.first().cloned().unwrap_or_default() }]
+        },
+        "tags": tags,
+        "extra": { "chain": chain },
+    })
+}
+
+impl ErrorReporter {
+    /// Configured by `SENTRY_DSN`; returns `None` when it is unset. `SENTRY_ENVIRONMENT`
+    /// tags every event (default `production`).
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(dsn) = env::var("SENTRY_DSN") else {
+            return Ok(None);
+        };
+        let (store_url, key) = parse_dsn(&dsn).context("Invalid SENTRY_DSN")?;
+        Ok(Some(Self {
+            store_url,
+            key,
+            environment: env::var("SENTRY_ENVIRONMENT").unwrap_or_else(|_| "production".into()),
+            client: reqwest::Client::new(),
+        }))
+    }
+
+    async fn send(&self, event: &Value) -> Result<()> {
+        self.client
+            .post(&self.store_url)
+            .header(
+                "X-Sentry-Auth",
+                format!(
+                    "Sentry sentry_version=7, sentry_key={}, sentry_client={}/{}",
+                    self.key,
+                    env!("CARGO_PKG_NAME"),
+                    env!("CARGO_PKG_VERSION")
+                ),
+            )
+            .timeout(std::time::Duration::from_secs(10))
+            .json(event)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Sets up the reporter from the environment and reports panics through it. Call once at
+/// startup; without `SENTRY_DSN` nothing is reported.
+pub fn init() -> Result<()> {
+    let reporter = ErrorReporter::from_env()?;
+    let enabled = reporter.is_some();
+    let _ = REPORTER.set(reporter);
+    if enabled {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            report_panic(info);
+            previous(info);
+        }));
+    }
+    Ok(())
+}
+
+fn report_panic(info: &std::panic::PanicHookInfo<'_>) {
+    let Some(Some(reporter)) = REPORTER.get() else {
+        return;
+    };
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panic".to_string());
+    let location = info
+        .location()
+        .map(|l| format!("{}:{}", l.file(), l.line()))
+        .unwrap_or_default();
+    let event = error_event(
+        "fatal",
+        "panic",
+        &[message],
+        &[("location", location)],
+        &reporter.environment,
+    );
+    // The panicking thread may be inside a runtime, so send from a fresh one
+    let reporter = reporter.clone();
+    let _ = std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .ok()?;
+        runtime.block_on(reporter.send(&event)).ok()
+    })
+    .join();
+}
+
+/// Reports `message`, something wrong that is not an error, at warning level under
+/// `category`. Failing to report is only logged.
+pub fn capture_warning(
+    category: &str,
+    message: &str,
+    context: &[(&str, String)],
+) -> impl std::future::Future<Output = ()> + Send {
+    let event = match REPORTER.get() {
+        Some(Some(reporter)) => Some((
+            reporter,
+            error_event(
+                "warning",
+                category,
+                &[message.to_string()],
+                context,
+                &reporter.environment,
+            ),
+        )),
+        _ => None,
+    };
+    async move {
+        let Some((reporter, event)) = event else {
+            return;
+        };
+        if let Err(report_err) = reporter.send(&event).await {
+            tracing::warn!(%report_err, "Failed to report warning");
+        }
+    }
+}
+
+/// Reports `err` with `context` (run id, command, ...) attached. The event is built before
+/// the returned future runs, so it does not borrow `err`. Failing to report is only logged.
+pub fn capture_error(
+    err: &(dyn Error + 'static),
+    context: &[(&str, String)],
+) -> impl std::future::Future<Output = ()> + Send {
+    let event = match REPORTER.get() {
+        Some(Some(reporter)) => Some((
+            reporter,
+            error_event(
+                "error",
+                categorize(err),
+                &error_chain(err),
+                context,
+                &reporter.environment,
+            ),
+        )),
+        _ => None,
+    };
+    async move {
+        let Some((reporter, event)) = event else {
+            return;
+        };
+        if let Err(report_err) = reporter.send(&event).await {
+            tracing::warn!(%report_err, "Failed to report error");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dsn_and_event() {
+        let (url, key) = parse_dsn("https://abc123@o1.ingest.sentry.io/42").unwrap();
+        assert_eq!(url, "https://o1.ingest.sentry.io/api/42/store/");
+        assert_eq!(key, "abc123");
+        assert!(parse_dsn("https://o1.ingest.sentry.io/42").is_err());
+
+        let err =
+            anyhow::anyhow!("Coinbase API error for ETH: 429").context("Live analysis failed");
+        let err: &(dyn Error + 'static) = err.as_ref();
+        assert_eq!(categorize(err), "exchange");
+        let event = error_event(
+            "error",
+            categorize(err),
+            &error_chain(err),
+            &[("command", "serve".to_string())],
+            "test",
+        );
+        assert_eq!(event["event_id"].as_str().unwrap().len(), 32);
+        assert_eq!(event["tags"]["category"], "exchange");
+        assert_eq!(event["tags"]["command"], "serve");
+        assert_eq!(
+            event["message"]["formatted"],
+            "Live analysis failed: Coinbase API error for ETH: 429"
+        );
+    }
+}
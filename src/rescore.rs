@@ -0,0 +1,349 @@
+use chrono::{DateTime, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as FmtWrite;
+
+use crate::compute::{label_candles_with, LONG_THRESHOLD};
+use crate::simulator::{
+    simulate_exits, summarize, ExitRule, Signal, SimulationSummary, FEE_RATE, TRAILING_MAX_HOURS,
+};
+use crate::{Action, GRANULARITY};
+
+/// The fields of a recorded prediction re-scoring reads. Rows of the analytics predictions
+/// table deserialize into it, as does any JSONL with the same field names.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RecordedPrediction {
+    #[serde(default)]
+    pub symbol: String,
+    /// Open time of the last candle in the window.
+    pub window_end: f64,
+    pub action: Action,
+    #[serde(default)]
+    pub invalidation_price: Option<f64>,
+    #[serde(default)]
+    pub target_price: Option<f64>,
+    /// Whether the prediction matched its label when it was recorded.
+    #[serde(default)]
+    pub correct: Option<bool>,
+}
+
+/// Labeling, fees and exit to score the predictions under.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RescoreConfig {
+    /// Next-candle move, as a fraction of the close, that labels a long or a short.
+    pub threshold: f64,
+    /// Fee per side, as a fraction of notional.
+    pub fee: f64,
+    pub exit: ExitRule,
+}
+
+impl Default for RescoreConfig {
+    fn default() -> Self {
+        Self {
+            threshold: LONG_THRESHOLD - 1.0,
+            fee: FEE_RATE,
+            exit: ExitRule::NextCandle,
+        }
+    }
+}
+
+impl RescoreConfig {
+    /// Candles after the last prediction its exit can need.
+    pub fn horizon(&self) -> usize {
+        match self.exit {
+            ExitRule::NextCandle | ExitRule::Levels => 1,
+            ExitRule::FixedHold(n) => n.max(1),
+            ExitRule::OppositeSignal | ExitRule::TrailingStop(_) => TRAILING_MAX_HOURS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RescoreReport {
+    pub config: RescoreConfig,
+    pub predictions: usize,
+    /// Predictions whose window and next candle are both in the candles.
+    pub scored: usize,
+    pub accuracy: f64,
+    /// Accuracy as recorded, over the scored predictions that kept it.
+    pub recorded_accuracy: Option<f64>,
+    pub simulation: SimulationSummary,
+}
+
+/// Scores `predictions` again against `candles` (in time order) under `config`: labels from
+/// its threshold, and trades from its exit rule and fee. No model is called.
+pub fn rescore(
+    predictions: &[RecordedPrediction],
+    candles: &[[f64; 6]],
+    config: &RescoreConfig,
+) -> RescoreReport {
+    let labels = label_candles_with(candles, 1.0 + config.threshold, 1.0 - config.threshold);
+    let mut scored = predictions
+        .iter()
+        .filter_map(|p| {
+            let i = candles
+                .binary_search_by(|c| c[0].total_cmp(&p.window_end))
+                .ok()?;
+            (i + 1 < candles.len()).then_some((i, p))
+        })
+        .collect::<Vec<_>>();
+    scored.sort_by_key(|(i, _)| *i);
+
+    let correct = scored
+        .iter()
+        .filter(|(i, p)| p.action == labels[*i])
+        .count();
+    let recorded = scored
+        .iter()
+        .filter_map(|(_, p)| p.correct)
+        .collect::<Vec<_>>();
+    let signals = scored
+        .iter()
+        .map(|(i, p)| Signal {
+            entry: *i,
+            side: p.action,
+            stop: p.invalidation_price,
+            target: p.target_price,
+        })
+        .collect::<Vec<_>>();
+    let trades = simulate_exits(candles, &signals, config.exit, config.fee);
+
+    RescoreReport {
+        config: *config,
+        predictions: predictions.len(),
+        scored: scored.len(),
+        accuracy: share(correct, scored.len()),
+        recorded_accuracy: (!recorded.is_empty())
+            .then(|| share(recorded.iter().filter(|c| **c).count(), recorded.len())),
+        simulation: summarize(&trades),
+    }
+}
+
+fn share(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+fn report_row(r: &RescoreReport) -> String {
+    let recorded = r
+        .recorded_accuracy
+        .map_or("-".to_string(), |a| format!("{:.1}%", a * 100.0));
+    format!(
+        "{:>8.2}% {:<20} {:>6.3}% {:>7} {:>8.1}% {:>9} {:>7} {:>+8.2}% {:>8.2}%",
+        r.config.threshold * 100.0,
+        r.config.exit.to_string(),
+        r.config.fee * 100.0,
+        r.scored,
+        r.accuracy * 100.0,
+        recorded,
+        r.simulation.trades,
+        r.simulation.total_return * 100.0,
+        r.simulation.max_drawdown * 100.0
+    )
+}
+
+fn header_row() -> String {
+    format!(
+        "{:>9} {:<20} {:>7} {:>7} {:>9} {:>9} {:>7} {:>9} {:>9}",
+        "threshold",
+        "exit",
+        "fee",
+        "scored",
+        "accuracy",
+        "recorded",
+        "trades",
+        "return",
+        "drawdown"
+    )
+}
+
+pub fn describe_rescores(reports: &[RescoreReport]) -> String {
+    let mut out = format!("{}\n", header_row());
+    for r in reports {
+        let _ = writeln!(out, "{}", report_row(r));
+    }
+    out
+}
+
+/// Reports of several signal sources side by side, each config's rows together.
+pub fn describe_benchmark(sources: &[(&str, Vec<RescoreReport>)]) -> String {
+    let width = sources
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0)
+        .max(6);
+    let mut out = format!("{:<width$} {}\n", "source", header_row());
+    let configs = sources.iter().map(|(_, r)| r.len()).max().unwrap_or(0);
+    for i in 0..configs {
+        for (name, reports) in sources {
+            if let Some(r) = reports.get(i) {
+                let _ = writeln!(out, "{:<width$} {}", name, report_row(r));
+            }
+        }
+    }
+    out
+}
+
+/// Recorded predictions from a CSV of another strategy's signals, one `timestamp,action`
+/// row each under an optional header. A timestamp is a unix time in seconds, RFC 3339, or
+/// `YYYY-MM-DD HH:MM:SS` in UTC. Each signal is taken at the close of the last candle
+/// closed by its time, so it is scored only on candles it could not have seen. Actions are
+/// long, short or none, or buy, sell or flat, or 1, -1 or 0.
+pub fn parse_signals_csv(csv: &str) -> anyhow::Result<Vec<RecordedPrediction>> {
+    let step = GRANULARITY as f64;
+    let mut rows = csv
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .peekable();
+    if let Some((_, header)) = rows.peek() {
+        if header.to_lowercase().starts_with("timestamp") {
+            rows.next();
+        }
+    }
+    rows.map(|(i, line)| {
+        let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+        let [time, action] = fields[..] else {
+            anyhow::bail!("Line {} is not timestamp,action: {:?}", i + 1, line);
+        };
+        let time = parse_signal_time(time)
+            .ok_or_else(|| anyhow::anyhow!("Can't read {:?} on line {} as a time", time, i + 1))?;
+        let action = match action.to_lowercase().as_str() {
+            "long" | "buy" | "1" => Action::Long,
+            "short" | "sell" | "-1" => Action::Short,
+            "none" | "flat" | "0" => Action::None,
+            _ => anyhow::bail!("Unknown action {:?} on line {}", action, i + 1),
+        };
+        Ok(RecordedPrediction {
+            symbol: String::new(),
+            window_end: (time / step).floor() * step - step,
+            action,
+            invalidation_price: None,
+            target_price: None,
+            correct: None,
+        })
+    })
+    .collect()
+}
+
+fn parse_signal_time(s: &str) -> Option<f64> {
+    if let Ok(secs) = s.parse::<i64>() {
+        return Some(secs as f64);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Some(time.timestamp() as f64);
+    }
+    NaiveDateTime::parse_from_str(&s.replace('T', " "), "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|t| t.and_utc().timestamp() as f64)
+}
+
+/// Reads a predictions JSONL file, skipping blank lines.
+#[cfg(feature = "native")]
+pub fn load_predictions(path: &std::path::Path) -> anyhow::Result<Vec<RecordedPrediction>> {
+    use anyhow::Context;
+
+    std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .enumerate()
+        .map(|(i, l)| {
+            serde_json::from_str(l).with_context(|| format!("Corrupt prediction on line {}", i + 1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rescore_under_a_lower_threshold() {
+        let candle = |t: f64, high: f64, low: f64, close: f64| [t, close, high, low, close, 1.0];
+        let candles = [
+            candle(0.0, 100.0, 100.0, 100.0),
+            candle(3600.0, 103.0, 99.0, 102.0),
+            candle(7200.0, 103.0, 98.0, 99.0),
+            candle(10800.0, 100.0, 99.0, 99.5),
+        ];
+        let row = |t: f64, action: &str, correct: bool| {
+            serde_json::from_value::<RecordedPrediction>(serde_json::json!({
+                "run_id": "r1",
+                "window_end": t,
+                "action": action,
+                "correct": correct,
+                "rationale": "ignored",
+            }))
+            .unwrap()
+        };
+        // Neither move reaches 5%, so both longs were recorded wrong; the last window has
+        // no next candle
+        let predictions = [
+            row(0.0, "long", false),
+            row(3600.0, "long", false),
+            row(10800.0, "short", false),
+        ];
+
+        let report = rescore(&predictions, &candles, &RescoreConfig::default());
+        assert_eq!((report.predictions, report.scored), (3, 2));
+        assert_eq!(report.accuracy, 0.0);
+        assert_eq!(report.recorded_accuracy, Some(0.0));
+
+        let lower = RescoreConfig {
+            threshold: 0.02,
+            fee: 0.0,
+            exit: "fixed_hold:3".parse().unwrap(),
+        };
+        let report = rescore(&predictions, &candles, &lower);
+        assert_eq!(report.accuracy, 0.5);
+        // Only the first long has three candles after it
+        assert_eq!(report.simulation.trades, 1);
+        assert!(describe_rescores(&[report]).contains("fixed_hold:3"));
+        assert_eq!(
+            lower.exit.to_string().parse::<ExitRule>().unwrap(),
+            lower.exit
+        );
+        assert!("trailing_stop".parse::<ExitRule>().is_err());
+    }
+
+    #[test]
+    fn test_signals_csv_is_taken_at_the_last_closed_candle() {
+        let csv = "timestamp,action\n\
+                   7200,long\n\
+                   1970-01-01T02:30:00Z,SELL\n\
+                   \n\
+                   1970-01-01 03:00:00,0\n";
+        let signals = parse_signals_csv(csv).unwrap();
+        assert_eq!(
+            signals
+                .iter()
+                .map(|s| (s.window_end, s.action))
+                .collect::<Vec<_>>(),
+            [
+                (3600.0, Action::Long),
+                (3600.0, Action::Short),
+                (7200.0, Action::None)
+            ]
+        );
+        let err = parse_signals_csv("7200,hold\n").unwrap_err();
+        assert!(err.to_string().contains("line 1"));
+        assert!(parse_signals_csv("7200\n").is_err());
+
+        let report = RescoreReport {
+            config: RescoreConfig::default(),
+            predictions: 3,
+            scored: 2,
+            accuracy: 0.5,
+            recorded_accuracy: None,
+            simulation: SimulationSummary::default(),
+        };
+        let table =
+            describe_benchmark(&[("predictions", vec![report.clone()]), ("bot", vec![report])]);
+        let rows = table.lines().collect::<Vec<_>>();
+        assert!(rows[1].starts_with("predictions ") && rows[2].starts_with("bot "));
+    }
+}
@@ -0,0 +1,187 @@
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as FmtWrite;
+
+use crate::rescore::RecordedPrediction;
+use crate::Action;
+
+/// Backtesting framework layouts candles and signals can be exported in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResearchFormat {
+    /// `pd.read_csv(path, index_col="datetime", parse_dates=True)`, then
+    /// `vbt.Portfolio.from_signals(df.close, df.entries, df.exits, df.short_entries,
+    /// df.short_exits)`.
+    Vectorbt,
+    /// `bt.feeds.GenericCSVData` with its default column layout, subclassed with a `signal`
+    /// line at column 7: 1 long, -1 short, 0 flat.
+    Backtrader,
+}
+
+impl std::str::FromStr for ResearchFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vectorbt" => Ok(ResearchFormat::Vectorbt),
+            "backtrader" => Ok(ResearchFormat::Backtrader),
+            _ => anyhow::bail!(
+                "Unknown research format {:?} (expected vectorbt or backtrader)",
+                s
+            ),
+        }
+    }
+}
+
+/// The action predicted at the close of each candle, `Action::None` where nothing was.
+/// The last prediction for a candle wins.
+pub fn align_signals(predictions: &[RecordedPrediction], candles: &[[f64; 6]]) -> Vec<Action> {
+    let mut signals = vec![Action::None; candles.len()];
+    for p in predictions {
+        if let Ok(i) = candles.binary_search_by(|c| c[0].total_cmp(&p.window_end)) {
+            signals[i] = p.action;
+        }
+    }
+    signals
+}
+
+fn datetime(ts: f64) -> String {
+    DateTime::from_timestamp(ts as i64, 0)
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+fn flag(set: bool) -> &'static str {
+    if set {
+        "True"
+    } else {
+        "False"
+    }
+}
+
+/// Candles with entry and exit flags per side, exiting as [`ExitRule::NextCandle`] does: a
+/// position opens at the close a signal is given and exits at the next close. A repeated
+/// signal flags both on one candle, which vectorbt's default conflict handling holds
+/// through, so its trades are the simulator's but for the fees of reopening.
+///
+/// [`ExitRule::NextCandle`]: crate::simulator::ExitRule::NextCandle
+pub fn vectorbt_csv(candles: &[[f64; 6]], signals: &[Action]) -> String {
+    let mut csv =
+        "datetime,open,high,low,close,volume,entries,exits,short_entries,short_exits\n".to_string();
+    for (i, c) in candles.iter().enumerate() {
+        let side = signals.get(i).copied().unwrap_or_default();
+        let previous = i
+            .checked_sub(1)
+            .and_then(|p| signals.get(p).copied())
+            .unwrap_or_default();
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{},{},{}",
+            datetime(c[0]),
+            c[1],
+            c[2],
+            c[3],
+            c[4],
+            c[5],
+            flag(side == Action::Long),
+            flag(previous == Action::Long),
+            flag(side == Action::Short),
+            flag(previous == Action::Short)
+        );
+    }
+    csv
+}
+
+/// Candles in `GenericCSVData`'s default columns, open interest 0, with the signal last.
+pub fn backtrader_csv(candles: &[[f64; 6]], signals: &[Action]) -> String {
+    let mut csv = "datetime,open,high,low,close,volume,openinterest,signal\n".to_string();
+    for (i, c) in candles.iter().enumerate() {
+        let signal = match signals.get(i).copied().unwrap_or_default() {
+            Action::Long => 1,
+            Action::Short => -1,
+            Action::None => 0,
+        };
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},0,{}",
+            datetime(c[0]),
+            c[1],
+            c[2],
+            c[3],
+            c[4],
+            c[5],
+            signal
+        );
+    }
+    csv
+}
+
+pub fn research_csv(format: ResearchFormat, candles: &[[f64; 6]], signals: &[Action]) -> String {
+    match format {
+        ResearchFormat::Vectorbt => vectorbt_csv(candles, signals),
+        ResearchFormat::Backtrader => backtrader_csv(candles, signals),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signals_export_per_framework() {
+        let candles = (0..4)
+            .map(|i| {
+                let close = 100.0 + i as f64;
+                [
+                    i as f64 * 3600.0,
+                    close,
+                    close + 1.0,
+                    close - 1.0,
+                    close,
+                    10.0,
+                ]
+            })
+            .collect::<Vec<_>>();
+        let prediction = |t: f64, action: Action| RecordedPrediction {
+            symbol: "ETH".into(),
+            window_end: t,
+            action,
+            invalidation_price: None,
+            target_price: None,
+            correct: None,
+        };
+        let signals = align_signals(
+            &[
+                prediction(0.0, Action::Long),
+                prediction(3600.0, Action::Long),
+                prediction(7200.0, Action::Short),
+                // Not a candle in the export
+                prediction(1800.0, Action::Short),
+            ],
+            &candles,
+        );
+        assert_eq!(
+            signals,
+            [Action::Long, Action::Long, Action::Short, Action::None]
+        );
+
+        let vectorbt = vectorbt_csv(&candles, &signals);
+        let rows = vectorbt.lines().collect::<Vec<_>>();
+        assert_eq!(rows.len(), 5);
+        assert_eq!(
+            rows[1],
+            "1970-01-01 00:00:00,100,101,99,100,10,True,False,False,False"
+        );
+        // Every position exits on the next close, the repeated long as it reopens
+        assert!(rows[2].ends_with("True,True,False,False"));
+        assert!(rows[3].ends_with("False,True,True,False"));
+        assert!(rows[4].ends_with("False,False,False,True"));
+
+        let backtrader = research_csv(ResearchFormat::Backtrader, &candles, &signals);
+        assert_eq!(
+            backtrader.lines().nth(3),
+            Some("1970-01-01 02:00:00,102,103,101,102,10,0,-1")
+        );
+        assert!("zipline".parse::<ResearchFormat>().is_err());
+    }
+}
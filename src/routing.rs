@@ -0,0 +1,191 @@
+use serde::Serialize;
+
+use crate::prediction::Prediction;
+use crate::Model;
+
+const CLOSE: usize = 4;
+
+/// Why a window went to the expensive model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Escalation {
+    /// The window was too volatile to trust the cheap model with; it was never asked.
+    HighVolatility,
+    /// The cheap model answered, but without enough confidence.
+    LowConfidence,
+}
+
+/// Sends windows to a cheap model and escalates to an expensive one only when needed.
+#[derive(Debug, Clone, Copy)]
+pub struct ModelRouter {
+    pub cheap: Model,
+    pub expensive: Model,
+    /// Cheap answers below this confidence, or without one, are escalated.
+    pub min_confidence: f64,
+    /// Windows whose [`realized_volatility`] exceeds this go straight to the expensive
+    /// model.
+    pub max_volatility: f64,
+}
+
+impl Default for ModelRouter {
+    fn default() -> Self {
+        Self {
+            cheap: Model::O1Mini,
+            expensive: Model::O1Preview,
+            min_confidence: 0.6,
+            // Roughly twice a typical hourly ETH move
+            max_volatility: 0.015,
+        }
+    }
+}
+
+impl ModelRouter {
+    /// Whether `window` should skip the cheap model.
+    pub fn escalate_before(&self, window: &[[f64; 6]]) -> Option<Escalation> {
+        realized_volatility(window)
+            .filter(|v| *v > self.max_volatility)
+            .map(|_| Escalation::HighVolatility)
+    }
+
+    /// Whether the cheap model's answer should be replaced by the expensive model's.
+    pub fn escalate_after(&self, cheap: &Prediction) -> Option<Escalation> {
+        match cheap.confidence {
+            Some(c) if c >= self.min_confidence => None,
+            _ => Some(Escalation::LowConfidence),
+        }
+    }
+}
+
+/// Standard deviation of close-to-close returns over the window.
+pub fn realized_volatility(window: &[[f64; 6]]) -> Option<f64> {
+    let returns = window
+        .windows(2)
+        .map(|w| w[1][CLOSE] / w[0][CLOSE] - 1.0)
+        .collect::<Vec<_>>();
+    if returns.len() < 2 {
+        return None;
+    }
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let var = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+    Some(var.sqrt())
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RoutingReport {
+    pub windows: usize,
+    /// Windows answered by the cheap model alone.
+    pub cheap_answered: usize,
+    pub escalated_volatility: usize,
+    pub escalated_confidence: usize,
+    pub cheap_accuracy: f64,
+    pub escalated_accuracy: f64,
+    pub blended_accuracy: f64,
+    /// Nominal cost in [`Model::relative_cost`] units; cache hits are counted as if the
+    /// request had been made, so the figure describes the routing policy.
+    pub cost: f64,
+    /// What sending every window to the expensive model would have cost.
+    pub cost_all_expensive: f64,
+}
+
+/// Accumulates routed answers into a [`RoutingReport`].
+#[derive(Debug, Clone, Default)]
+pub struct RoutingStats {
+    report: RoutingReport,
+    cheap_correct: usize,
+    escalated_correct: usize,
+}
+
+impl RoutingStats {
+    pub fn record(&mut self, router: &ModelRouter, escalation: Option<Escalation>, correct: bool) {
+        let r = &mut self.report;
+        r.windows += 1;
+        r.cost_all_expensive += router.expensive.relative_cost();
+        match escalation {
+            None => {
+                r.cheap_answered += 1;
+                r.cost += router.cheap.relative_cost();
+                self.cheap_correct += correct as usize;
+            }
+            Some(reason) => {
+                if reason == Escalation::HighVolatility {
+                    r.escalated_volatility += 1;
+                } else {
+                    r.escalated_confidence += 1;
+                    r.cost += router.cheap.relative_cost();
+                }
+                r.cost += router.expensive.relative_cost();
+                self.escalated_correct += correct as usize;
+            }
+        }
+    }
+
+    pub fn report(&self) -> RoutingReport {
+        let r = &self.report;
+        let escalated = r.escalated_volatility + r.escalated_confidence;
+        RoutingReport {
+            cheap_accuracy: ratio(self.cheap_correct, r.cheap_answered),
+            escalated_accuracy: ratio(self.escalated_correct, escalated),
+            blended_accuracy: ratio(self.cheap_correct + self.escalated_correct, r.windows),
+            ..r.clone()
+        }
+    }
+}
+
+fn ratio(n: usize, d: usize) -> f64 {
+    if d == 0 {
+        0.0
+    } else {
+        n as f64 / d as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routing_decisions_and_costs() {
+        let router = ModelRouter::default();
+        let calm = [
+            [0.0, 0.0, 0.0, 0.0, 100.0, 1.0],
+            [1.0, 0.0, 0.0, 0.0, 100.5, 1.0],
+            [2.0, 0.0, 0.0, 0.0, 100.0, 1.0],
+        ];
+        let wild = [
+            [0.0, 0.0, 0.0, 0.0, 100.0, 1.0],
+            [1.0, 0.0, 0.0, 0.0, 105.0, 1.0],
+            [2.0, 0.0, 0.0, 0.0, 98.0, 1.0],
+        ];
+        assert_eq!(router.escalate_before(&calm), None);
+        assert_eq!(
+            router.escalate_before(&wild),
+            Some(Escalation::HighVolatility)
+        );
+
+        let sure = Prediction {
+            confidence: Some(0.8),
+            ..Default::default()
+        };
+        assert_eq!(router.escalate_after(&sure), None);
+        assert_eq!(
+            router.escalate_after(&Prediction::default()),
+            Some(Escalation::LowConfidence)
+        );
+
+        let mut stats = RoutingStats::default();
+        stats.record(&router, None, true);
+        stats.record(&router, None, false);
+        stats.record(&router, Some(Escalation::LowConfidence), true);
+        stats.record(&router, Some(Escalation::HighVolatility), true);
+        let report = stats.report();
+        assert_eq!(report.cheap_accuracy, 0.5);
+        assert_eq!(report.escalated_accuracy, 1.0);
+        assert_eq!(report.blended_accuracy, 0.75);
+        let (cheap, expensive) = (
+            Model::O1Mini.relative_cost(),
+            Model::O1Preview.relative_cost(),
+        );
+        assert_eq!(report.cost, 3.0 * cheap + 2.0 * expensive);
+        assert_eq!(report.cost_all_expensive, 4.0 * expensive);
+    }
+}
@@ -0,0 +1,75 @@
+// Seeded randomness for window selection and sampling. The generator is SplitMix64,
+// written out here rather than taken from `rand` because its output must stay identical
+// across dependency upgrades for a recorded seed to reproduce a run.
+
+/// Small deterministic PRNG; the same seed always yields the same sequence.
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform integer in `0..n`. `n` must be non-zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        // Multiply-shift keeps the bias negligible for any realistic `n`
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+
+    /// `k` distinct indices from `0..n`, in ascending order; all of them when `k >= n`.
+    pub fn sample_indices(&mut self, n: usize, k: usize) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..n).collect();
+        let k = k.min(n);
+        // Partial Fisher-Yates: only the first `k` slots need to be drawn
+        for i in 0..k {
+            let j = i + self.below(n - i);
+            indices.swap(i, j);
+        }
+        indices.truncate(k);
+        indices.sort_unstable();
+        indices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_sampling_is_reproducible() {
+        // Reference values of SplitMix64 seeded with 0
+        let mut rng = SeededRng::new(0);
+        assert_eq!(rng.next_u64(), 0xE220_A839_7B1D_CDAF);
+        assert_eq!(rng.next_u64(), 0x6E78_9E6A_A1B9_65F4);
+
+        let a = SeededRng::new(42).sample_indices(100, 10);
+        assert_eq!(a, SeededRng::new(42).sample_indices(100, 10));
+        assert_ne!(a, SeededRng::new(43).sample_indices(100, 10));
+        assert_eq!(a.len(), 10);
+        assert!(a.windows(2).all(|w| w[0] < w[1]) && a[9] < 100);
+        assert_eq!(SeededRng::new(1).sample_indices(3, 10), vec![0, 1, 2]);
+
+        let mut items: Vec<u32> = (0..20).collect();
+        SeededRng::new(7).shuffle(&mut items);
+        let mut sorted = items.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,180 @@
+use std::fmt::Write as _;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScenarioKind {
+    Crash,
+    Breakout,
+    /// A sharp move that reversed within the scenario.
+    Whipsaw,
+}
+
+/// A notable stretch of market history the prompt is stress-tested on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scenario {
+    pub name: &'static str,
+    pub kind: ScenarioKind,
+    pub description: &'static str,
+    /// First and last candle (exclusive) scored, RFC 3339 in UTC.
+    pub start: &'static str,
+    pub end: &'static str,
+}
+
+impl Scenario {
+    /// The scored range `[start, end)`.
+    pub fn range(&self) -> (DateTime<Utc>, DateTime<Utc>) {
+        let parse = |s: &str| {
+            DateTime::parse_from_rfc3339(s)
+                .expect("scenario times are valid RFC 3339")
+                .with_timezone(&Utc)
+        };
+        (parse(self.start), parse(self.end))
+    }
+}
+
+/// The curated stress scenarios, oldest first. All fall after SOL-USD was listed, so every
+/// prompt symbol has data.
+pub const SCENARIOS: [Scenario; 9] = [
+    Scenario {
+        name: "el-salvador-flash-crash",
+        kind: ScenarioKind::Crash,
+        description:
+            "BTC loses over 15% within hours on the day it becomes legal tender in El Salvador",
+        start: "2021-09-07T00:00:00Z",
+        end: "2021-09-08T12:00:00Z",
+    },
+    Scenario {
+        name: "december-2021-liquidations",
+        kind: ScenarioKind::Crash,
+        description: "Weekend leverage flush takes BTC down about 20% overnight",
+        start: "2021-12-03T18:00:00Z",
+        end: "2021-12-05T00:00:00Z",
+    },
+    Scenario {
+        name: "terra-collapse",
+        kind: ScenarioKind::Crash,
+        description: "UST loses its peg and LUNA collapses, dragging the market down for days",
+        start: "2022-05-09T00:00:00Z",
+        end: "2022-05-13T00:00:00Z",
+    },
+    Scenario {
+        name: "celsius-freeze",
+        kind: ScenarioKind::Crash,
+        description: "Celsius halts withdrawals and ETH falls below $1,200",
+        start: "2022-06-12T18:00:00Z",
+        end: "2022-06-15T00:00:00Z",
+    },
+    Scenario {
+        name: "ftx-collapse",
+        kind: ScenarioKind::Crash,
+        description: "FTX halts withdrawals and the Binance rescue falls through",
+        start: "2022-11-08T00:00:00Z",
+        end: "2022-11-10T12:00:00Z",
+    },
+    Scenario {
+        name: "usdc-depeg-rebound",
+        kind: ScenarioKind::Whipsaw,
+        description:
+            "USDC depegs over the SVB weekend, then the market rips once deposits are backstopped",
+        start: "2023-03-10T00:00:00Z",
+        end: "2023-03-14T00:00:00Z",
+    },
+    Scenario {
+        name: "etf-inflow-breakout",
+        kind: ScenarioKind::Breakout,
+        description: "Spot ETF inflows push BTC from $51k to $64k in three days",
+        start: "2024-02-26T00:00:00Z",
+        end: "2024-02-29T00:00:00Z",
+    },
+    Scenario {
+        name: "yen-carry-unwind",
+        kind: ScenarioKind::Whipsaw,
+        description:
+            "Global risk-off as the yen carry trade unwinds; ETH falls over 20% and bounces",
+        start: "2024-08-04T12:00:00Z",
+        end: "2024-08-06T12:00:00Z",
+    },
+    Scenario {
+        name: "election-breakout",
+        kind: ScenarioKind::Breakout,
+        description: "BTC breaks to a new all-time high on the US election result",
+        start: "2024-11-05T18:00:00Z",
+        end: "2024-11-07T12:00:00Z",
+    },
+];
+
+pub fn find_scenario(name: &str) -> Option<&'static Scenario> {
+    SCENARIOS.iter().find(|s| s.name == name)
+}
+
+/// How the prompt did over one scenario.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScenarioResult {
+    pub name: String,
+    pub kind: ScenarioKind,
+    pub windows: usize,
+    pub accuracy: f64,
+    pub baseline_accuracy: f64,
+    pub total_return: f64,
+    pub max_drawdown: f64,
+    pub liquidations: usize,
+}
+
+/// The results as a table, one line per scenario.
+pub fn describe_stress(results: &[ScenarioResult]) -> String {
+    let mut out = format!(
+        "{:<28} {:<9} {:>7} {:>9} {:>9} {:>9} {:>9}\n",
+        "scenario", "kind", "windows", "accuracy", "baseline", "return", "drawdown"
+    );
+    for r in results {
+        let kind = serde_json::to_value(r.kind).unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "{:<28} {:<9} {:>7} {:>8.1}% {:>8.1}% {:>+8.2}% {:>8.2}%",
+            r.name,
+            kind.as_str().unwrap_or_default(),
+            r.windows,
+            r.accuracy * 100.0,
+            r.baseline_accuracy * 100.0,
+            r.total_return * 100.0,
+            r.max_drawdown * 100.0
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scenarios_are_well_formed() {
+        let mut previous_end = DateTime::<Utc>::MIN_UTC;
+        for scenario in &SCENARIOS {
+            let (start, end) = scenario.range();
+            assert!(start < end, "{} is empty", scenario.name);
+            assert!(start >= previous_end, "{} is out of order", scenario.name);
+            assert_eq!(find_scenario(scenario.name), Some(scenario));
+            previous_end = end;
+        }
+        assert!(find_scenario("moon").is_none());
+
+        let table = describe_stress(&[ScenarioResult {
+            name: "ftx-collapse".into(),
+            kind: ScenarioKind::Crash,
+            windows: 60,
+            accuracy: 0.45,
+            baseline_accuracy: 0.5,
+            total_return: -0.031,
+            max_drawdown: 0.042,
+            liquidations: 0,
+        }]);
+        assert_eq!(
+            table.lines().nth(1).unwrap(),
+            "ftx-collapse                 crash          60     45.0%     50.0%    -3.10%     4.20%"
+        );
+    }
+}
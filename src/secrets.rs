@@ -0,0 +1,342 @@
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+
+use crate::artifacts::hmac_sha256;
+
+/// Service name the keys are filed under in the OS keyring.
+pub const KEYRING_SERVICE: &str = "happychartsv2";
+/// Every key the app reads, as listed by `keys ls`. `OPENAI_API_KEYS` holds several
+/// comma-separated keys to rotate through.
+pub const KNOWN_KEYS: [&str; 9] = [
+    "OPENAI_API_KEY",
+    "OPENAI_API_KEYS",
+    "SHADOW_API_KEY",
+    "COINBASE_API_KEY",
+    "COINBASE_API_SECRET",
+    "COINBASE_API_PASSPHRASE",
+    "BINANCE_API_KEY",
+    "BINANCE_API_SECRET",
+    "COINGLASS_API_KEY",
+];
+
+/// Where a key was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretSource {
+    Keyring,
+    Env,
+}
+
+/// Looks `name` up in the OS keyring (the macOS keychain or the Secret Service on Linux),
+/// falling back to the environment variable of the same name.
+pub fn secret(name: &str) -> Option<String> {
+    secret_with_source(name).map(|(value, _)| value)
+}
+
+pub fn secret_with_source(name: &str) -> Option<(String, SecretSource)> {
+    if let Some(value) = keyring_lookup(name) {
+        return Some((value, SecretSource::Keyring));
+    }
+    env::var(name)
+        .ok()
+        .filter(|v| !v.is_empty())
+        .map(|v| (v, SecretSource::Env))
+}
+
+fn keyring_lookup(name: &str) -> Option<String> {
+    let output = if cfg!(target_os = "macos") {
+        Command::new("security")
+            .args([
+                "find-generic-password",
+                "-s",
+                KEYRING_SERVICE,
+                "-a",
+                name,
+                "-w",
+            ])
+            .stderr(Stdio::null())
+            .output()
+    } else if cfg!(target_os = "linux") {
+        Command::new("secret-tool")
+            .args(["lookup", "service", KEYRING_SERVICE, "account", name])
+            .stderr(Stdio::null())
+            .output()
+    } else {
+        return None;
+    };
+    // A missing keyring tool means the keyring is simply not in use
+    let output = output.ok().filter(|o| o.status.success())?;
+    let value = String::from_utf8(output.stdout)
+        .ok()?
+        .trim_end()
+        .to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+/// Runs `command` with `input` written to its stdin, so a key never shows up in the
+/// process list.
+fn run_with_stdin(command: &mut Command, input: &str) -> Result<std::process::ExitStatus> {
+    let mut child = command.stdin(Stdio::piped()).spawn()?;
+    child
+        .stdin
+        .take()
+        .context("The keyring tool has no stdin")?
+        .write_all(input.as_bytes())?;
+    Ok(child.wait()?)
+}
+
+/// Saves `value` as `name` in the OS keyring, replacing any previous value.
+pub fn store_secret(name: &str, value: &str) -> Result<()> {
+    let status = if cfg!(target_os = "macos") {
+        // A trailing `-w` without a value makes `security` prompt for the password, and
+        // again to confirm it
+        let mut security = Command::new("security");
+        security.args([
+            "add-generic-password",
+            "-U",
+            "-s",
+            KEYRING_SERVICE,
+            "-a",
+            name,
+            "-w",
+        ]);
+        run_with_stdin(&mut security, &format!("{}\n{}\n", value, value))
+            .context("Failed to run `security`")?
+    } else if cfg!(target_os = "linux") {
+        let mut secret_tool = Command::new("secret-tool");
+        secret_tool
+            .args(["store", "--label", &format!("{} {}", KEYRING_SERVICE, name)])
+            .args(["service", KEYRING_SERVICE, "account", name]);
+        run_with_stdin(&mut secret_tool, value)
+            .context("Failed to run `secret-tool` (install libsecret-tools)")?
+    } else {
+        anyhow::bail!(
+            "No OS keyring support on this platform; set {} in the environment",
+            name
+        );
+    };
+    anyhow::ensure!(status.success(), "Keyring refused to store {}", name);
+    Ok(())
+}
+
+pub fn delete_secret(name: &str) -> Result<()> {
+    let status = if cfg!(target_os = "macos") {
+        Command::new("security")
+            .args(["delete-generic-password", "-s", KEYRING_SERVICE, "-a", name])
+            .status()
+            .context("Failed to run `security`")?
+    } else if cfg!(target_os = "linux") {
+        Command::new("secret-tool")
+            .args(["clear", "service", KEYRING_SERVICE, "account", name])
+            .status()
+            .context("Failed to run `secret-tool`")?
+    } else {
+        anyhow::bail!("No OS keyring support on this platform");
+    };
+    anyhow::ensure!(status.success(), "Keyring has no {}", name);
+    Ok(())
+}
+
+/// Keys tried in turn for one provider. A key that hits its quota hands over to the next;
+/// clones share the position, so every request after a rotation starts from the new key.
+#[derive(Clone)]
+pub struct KeyPool {
+    keys: Vec<String>,
+    current: Arc<AtomicUsize>,
+}
+
+impl KeyPool {
+    pub fn new(keys: Vec<String>) -> Result<Self> {
+        anyhow::ensure!(!keys.is_empty(), "Key pool needs at least one key");
+        Ok(Self {
+            keys,
+            current: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// `OPENAI_API_KEYS` (comma-separated) when set, otherwise `OPENAI_API_KEY`.
+    pub fn openai() -> Result<Self> {
+        let keys = secret("OPENAI_API_KEYS")
+            .map(|keys| split_keys(&keys))
+            .filter(|keys| !keys.is_empty())
+            .or_else(|| secret("OPENAI_API_KEY").map(|key| vec![key]))
+            .context(
+                "Neither OPENAI_API_KEYS nor OPENAI_API_KEY is in the keyring or environment",
+            )?;
+        Self::new(keys)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// The key to use now and its position in the pool.
+    pub fn current(&self) -> (usize, &str) {
+        let index = self.current.load(Ordering::Relaxed) % self.keys.len();
+        (index, &self.keys[index])
+    }
+
+    /// Moves past the key at `exhausted`. Does nothing when another request already rotated
+    /// away from it.
+    pub fn rotate_from(&self, exhausted: usize) {
+        let next = (exhausted + 1) % self.keys.len();
+        let _ =
+            self.current
+                .compare_exchange(exhausted, next, Ordering::Relaxed, Ordering::Relaxed);
+    }
+}
+
+impl std::fmt::Debug for KeyPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyPool")
+            .field("keys", &self.keys.len())
+            .field("current", &self.current)
+            .finish()
+    }
+}
+
+fn split_keys(keys: &str) -> Vec<String> {
+    keys.split(',')
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether a provider error means the key is out of quota or rate limited, so another key
+/// may still succeed.
+pub fn is_quota_error(status: u16, body: &str) -> bool {
+    status == 429 || body.contains("insufficient_quota")
+}
+
+/// Coinbase Exchange API credentials. Candle requests are public, but signed ones get the
+/// higher rate limit of an authenticated profile.
+#[derive(Clone)]
+pub struct ExchangeCredentials {
+    key: String,
+    secret: String,
+    passphrase: String,
+}
+
+impl ExchangeCredentials {
+    /// From `COINBASE_API_KEY`, `COINBASE_API_SECRET` and `COINBASE_API_PASSPHRASE`; `None`
+    /// unless all three are set.
+    pub fn lookup() -> Option<Self> {
+        Some(Self {
+            key: secret("COINBASE_API_KEY")?,
+            secret: secret("COINBASE_API_SECRET")?,
+            passphrase: secret("COINBASE_API_PASSPHRASE")?,
+        })
+    }
+
+    /// `CB-ACCESS-*` headers for a request to `path` (including the query string).
+    pub fn headers(
+        &self,
+        timestamp: i64,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> Result<[(&'static str, String); 4]> {
+        let secret = base64_decode(&self.secret).context("COINBASE_API_SECRET is not base64")?;
+        let message = format!("{}{}{}{}", timestamp, method, path, body);
+        let signature = base64_encode(&hmac_sha256(&secret, message.as_bytes()));
+        Ok([
+            ("CB-ACCESS-KEY", self.key.clone()),
+            ("CB-ACCESS-SIGN", signature),
+            ("CB-ACCESS-TIMESTAMP", timestamp.to_string()),
+            ("CB-ACCESS-PASSPHRASE", self.passphrase.clone()),
+        ])
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn base64_decode(s: &str) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len() / 4 * 3);
+    let (mut n, mut bits) = (0u32, 0);
+    for c in s.trim_end_matches('=').bytes() {
+        let v = BASE64
+            .iter()
+            .position(|&b| b == c)
+            .with_context(|| format!("Invalid base64 character {:?}", c as char))?;
+        n = n << 6 | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_rotation_and_signing() {
+        assert_eq!(split_keys("sk-a, sk-b,,"), ["sk-a", "sk-b"]);
+        let pool = KeyPool::new(split_keys("sk-a,sk-b,sk-c")).unwrap();
+        let shared = pool.clone();
+        assert_eq!(pool.current(), (0, "sk-a"));
+        pool.rotate_from(0);
+        // A second request that also saw key 0 fail must not skip key 1
+        shared.rotate_from(0);
+        assert_eq!(shared.current(), (1, "sk-b"));
+        pool.rotate_from(1);
+        pool.rotate_from(2);
+        assert_eq!(pool.current(), (0, "sk-a"));
+        assert!(KeyPool::new(Vec::new()).is_err());
+
+        assert!(is_quota_error(429, ""));
+        assert!(is_quota_error(
+            403,
+            r#"{"error":{"code":"insufficient_quota"}}"#
+        ));
+        assert!(!is_quota_error(500, "server error"));
+
+        for bytes in [&b""[..], b"f", b"fo", b"foo", b"foob"] {
+            assert_eq!(base64_decode(&base64_encode(bytes)).unwrap(), bytes);
+        }
+        assert_eq!(base64_encode(b"foob"), "Zm9vYg==");
+        let creds = ExchangeCredentials {
+            key: "key".into(),
+            secret: base64_encode(b"secret"),
+            passphrase: "pass".into(),
+        };
+        let headers = creds
+            .headers(1_700_000_000, "GET", "/products", "")
+            .unwrap();
+        assert_eq!(
+            headers[2],
+            ("CB-ACCESS-TIMESTAMP", "1700000000".to_string())
+        );
+        assert_eq!(headers[1].1.len(), 44);
+    }
+}
@@ -0,0 +1,101 @@
+use serde::Serialize;
+
+/// Trades an hour or weekday needs before its record is trusted to gate live signals.
+pub const SESSION_MIN_TRADES: usize = 20;
+pub const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SessionStats {
+    pub windows: usize,
+    pub correct: usize,
+    pub accuracy: f64,
+    pub trades: usize,
+    /// Sum of the trades' returns.
+    pub total_return: f64,
+}
+
+/// Accuracy and PnL by UTC hour of day and by weekday of the candle a signal was entered on.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionBreakdown {
+    /// Indexed by UTC hour, 0 to 23.
+    pub by_hour: Vec<SessionStats>,
+    /// Indexed like [`WEEKDAYS`], Monday first.
+    pub by_weekday: Vec<SessionStats>,
+}
+
+impl Default for SessionBreakdown {
+    fn default() -> Self {
+        Self {
+            by_hour: vec![SessionStats::default(); 24],
+            by_weekday: vec![SessionStats::default(); 7],
+        }
+    }
+}
+
+/// UTC hour and weekday (Monday = 0) of a unix timestamp.
+pub fn session_of(time: f64) -> (usize, usize) {
+    let secs = time as i64;
+    let hour = secs.rem_euclid(86_400) / 3600;
+    // 1970-01-01 was a Thursday
+    let weekday = (secs.div_euclid(86_400) + 3).rem_euclid(7);
+    (hour as usize, weekday as usize)
+}
+
+impl SessionBreakdown {
+    fn sessions(&mut self, time: f64) -> [&mut SessionStats; 2] {
+        let (hour, weekday) = session_of(time);
+        [&mut self.by_hour[hour], &mut self.by_weekday[weekday]]
+    }
+
+    pub fn record_prediction(&mut self, time: f64, correct: bool) {
+        for s in self.sessions(time) {
+            s.windows += 1;
+            s.correct += correct as usize;
+            s.accuracy = s.correct as f64 / s.windows as f64;
+        }
+    }
+
+    pub fn record_trade(&mut self, entry_time: f64, return_pct: f64) {
+        for s in self.sessions(entry_time) {
+            s.trades += 1;
+            s.total_return += return_pct;
+        }
+    }
+
+    /// Whether the hour or the weekday of `time` has lost money over at least
+    /// [`SESSION_MIN_TRADES`] trades.
+    pub fn is_unprofitable(&self, time: f64) -> bool {
+        let (hour, weekday) = session_of(time);
+        [&self.by_hour[hour], &self.by_weekday[weekday]]
+            .iter()
+            .any(|s| s.trades >= SESSION_MIN_TRADES && s.total_return <= 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_breakdown() {
+        // Monday 2024-12-02 14:00 UTC
+        let monday_afternoon = 1_733_148_000.0;
+        assert_eq!(session_of(monday_afternoon), (14, 0));
+        assert_eq!(session_of(monday_afternoon + 5.0 * 86_400.0), (14, 5));
+
+        let mut sessions = SessionBreakdown::default();
+        sessions.record_prediction(monday_afternoon, true);
+        sessions.record_prediction(monday_afternoon + 3600.0, false);
+        assert_eq!(sessions.by_hour[14].accuracy, 1.0);
+        assert_eq!(sessions.by_weekday[0].windows, 2);
+        assert_eq!(sessions.by_weekday[0].accuracy, 0.5);
+
+        for _ in 0..SESSION_MIN_TRADES {
+            sessions.record_trade(monday_afternoon, -0.01);
+        }
+        assert!(sessions.is_unprofitable(monday_afternoon));
+        // A different hour on a Monday still falls in the losing weekday
+        assert!(sessions.is_unprofitable(monday_afternoon + 3600.0));
+        assert!(!sessions.is_unprofitable(monday_afternoon + 86_400.0 + 3600.0));
+    }
+}
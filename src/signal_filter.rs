@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Action;
+
+/// How signals that repeat the open paper position are thinned out before they are sent.
+/// Configured as `"always"`, `"on_change"` or `{"cooldown": <seconds>}`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepeatPolicy {
+    /// Send every signal.
+    #[default]
+    Always,
+    /// Resend the open position's side only once this many seconds have passed since it was
+    /// last sent.
+    Cooldown(f64),
+    /// Send only signals that change the position.
+    OnChange,
+}
+
+/// Tracks the paper position each symbol's sent signals imply, and decides which new
+/// signals are worth sending. A long or short signal opens (or flips) a position and a
+/// `none` signal closes it.
+#[derive(Debug, Clone)]
+pub struct SignalFilter {
+    policy: RepeatPolicy,
+    /// Side and send time of the last signal sent per symbol.
+    positions: HashMap<String, (Action, f64)>,
+}
+
+impl SignalFilter {
+    pub fn new(policy: RepeatPolicy) -> Self {
+        Self {
+            policy,
+            positions: HashMap::new(),
+        }
+    }
+
+    /// The paper position of `symbol`; flat until a signal is sent.
+    pub fn position(&self, symbol: &str) -> Action {
+        self.positions
+            .get(symbol)
+            .map_or(Action::None, |(side, _)| *side)
+    }
+
+    /// Corrects `symbol`'s position to `held`, the one actually open, as when a stop closed
+    /// it or a restart forgot it. A corrected position counts as never sent.
+    pub fn sync(&mut self, symbol: &str, held: Action) {
+        if self.position(symbol) != held {
+            self.positions
+                .insert(symbol.to_string(), (held, f64::NEG_INFINITY));
+        }
+    }
+
+    /// Whether to send `action` for `symbol` at `time` (unix seconds). Sending it updates
+    /// the position.
+    pub fn admit(&mut self, symbol: &str, action: Action, time: f64) -> bool {
+        let send = match self.positions.get(symbol) {
+            Some(&(side, _)) if side != action => true,
+            None if action != Action::None => true,
+            Some(&(_, sent)) => match self.policy {
+                RepeatPolicy::Always => true,
+                RepeatPolicy::Cooldown(secs) => time - sent >= secs,
+                RepeatPolicy::OnChange => false,
+            },
+            // Flat with nothing sent yet: a `none` only confirms it
+            None => self.policy == RepeatPolicy::Always,
+        };
+        if send {
+            self.positions.insert(symbol.to_string(), (action, time));
+        }
+        send
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Action::*;
+
+    #[test]
+    fn test_signal_filter_policies() {
+        let hour = 3600.0;
+        let run = |policy| {
+            let mut filter = SignalFilter::new(policy);
+            [None, Long, Long, Long, Short, Short, None]
+                .iter()
+                .enumerate()
+                .map(|(i, &a)| filter.admit("ETH", a, i as f64 * hour))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(RepeatPolicy::Always), [true; 7]);
+        assert_eq!(
+            run(RepeatPolicy::OnChange),
+            [false, true, false, false, true, false, true]
+        );
+        assert_eq!(
+            run(RepeatPolicy::Cooldown(2.0 * hour)),
+            [false, true, false, true, true, false, true]
+        );
+
+        let mut filter = SignalFilter::new(RepeatPolicy::OnChange);
+        assert!(filter.admit("ETH", Long, 0.0));
+        assert!(filter.admit("BTC", Short, 0.0));
+        assert_eq!(
+            (filter.position("ETH"), filter.position("SOL")),
+            (Long, None)
+        );
+        // Stopped out: the next long opens a position again
+        filter.sync("ETH", None);
+        assert!(filter.admit("ETH", Long, 1.0));
+        filter.sync("ETH", Long);
+        assert!(!filter.admit("ETH", Long, 2.0));
+
+        let policy = |json| serde_json::from_str::<RepeatPolicy>(json).unwrap();
+        assert_eq!(
+            policy(r#"{"cooldown": 7200}"#),
+            RepeatPolicy::Cooldown(7200.0)
+        );
+        assert_eq!(policy(r#""on_change""#), RepeatPolicy::OnChange);
+    }
+}
@@ -0,0 +1,919 @@
+use serde::{Deserialize, Serialize};
+
+use crate::funding::{funding_paid, FundingPoint};
+use crate::Action;
+
+const TIME: usize = 0;
+const OPEN: usize = 1;
+const HIGH: usize = 2;
+const LOW: usize = 3;
+const CLOSE: usize = 4;
+const VOLUME: usize = 5;
+
+/// Taker fee charged on entry and on exit, as a fraction of notional.
+pub const FEE_RATE: f64 = 0.0006;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Trade {
+    pub side: Action,
+    pub entry_time: f64,
+    pub entry_price: f64,
+    pub exit_time: f64,
+    pub exit_price: f64,
+    /// Net return after fees, as a fraction of the entry notional.
+    pub return_pct: f64,
+    /// Perpetual funding paid while held, in the units of `return_pct` and already
+    /// deducted from it; negative when received.
+    #[serde(default)]
+    pub funding: f64,
+}
+
+impl Trade {
+    /// Whether the position lost its whole margin.
+    pub fn is_liquidation(&self) -> bool {
+        self.return_pct <= -1.0
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SimulationSummary {
+    pub trades: usize,
+    /// Compounded net return over all trades.
+    pub total_return: f64,
+    pub win_rate: f64,
+    pub max_drawdown: f64,
+    pub liquidations: usize,
+}
+
+/// Leverage and financing applied by [`margin_trade`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MarginModel {
+    /// Position notional per unit of margin; the initial margin requirement is its inverse.
+    pub leverage: f64,
+    /// Borrow or funding cost of holding a short, per hour, as a fraction of notional.
+    pub short_cost_per_hour: f64,
+    /// A position whose equity falls below this fraction of its notional is liquidated.
+    pub maintenance_margin: f64,
+}
+
+impl Default for MarginModel {
+    fn default() -> Self {
+        Self {
+            leverage: 1.0,
+            // 0.01% per 8 hours, a typical perpetual funding rate
+            short_cost_per_hour: 0.0001 / 8.0,
+            maintenance_margin: 0.005,
+        }
+    }
+}
+
+impl MarginModel {
+    /// Price at which a `side` position entered at `entry_price` is liquidated; `None` when
+    /// it can't be, as for an unlevered long.
+    pub fn liquidation_price(&self, side: Action, entry_price: f64) -> Option<f64> {
+        let cushion = 1.0 / self.leverage - self.maintenance_margin;
+        match side {
+            Action::Long if cushion < 1.0 => Some(entry_price * (1.0 - cushion)),
+            Action::Short => Some(entry_price * (1.0 + cushion)),
+            _ => None,
+        }
+    }
+}
+
+/// How far a market order's average fill lands from the quoted price.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum ImpactModel {
+    /// Half the `spread`, plus `coefficient` times the square root of the order's share of
+    /// the candle's volume, as fractions of price.
+    SquareRoot { coefficient: f64, spread: f64 },
+    /// A depth snapshot: `bid_depth` and `ask_depth` base size spread evenly within 1% of
+    /// the mid, walked through by the order. Orders beyond the depth on their side fill
+    /// only up to it.
+    Depth {
+        bid_depth: f64,
+        ask_depth: f64,
+        spread: f64,
+    },
+}
+
+impl Default for ImpactModel {
+    fn default() -> Self {
+        // Roughly an hour's volatility of a major pair at full participation, and 2bp spread
+        Self::SquareRoot {
+            coefficient: 0.01,
+            spread: 0.0002,
+        }
+    }
+}
+
+/// Sizes trades to `notional` and prices their fills with `impact`, instead of assuming
+/// any size fills at the close.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FillModel {
+    /// Intended position size, in quote currency.
+    pub notional: f64,
+    pub impact: ImpactModel,
+    /// Largest share of a candle's volume an order may take; the rest goes unfilled.
+    pub max_participation: f64,
+}
+
+impl FillModel {
+    pub fn new(notional: f64, impact: ImpactModel) -> Self {
+        Self {
+            notional,
+            impact,
+            max_participation: 0.1,
+        }
+    }
+
+    /// Share of a `size` (base currency) order that fills in a candle trading `volume`, on
+    /// the bid side when selling.
+    pub fn filled(&self, size: f64, volume: f64, selling: bool) -> f64 {
+        if size <= 0.0 {
+            return 1.0;
+        }
+        let available = match self.impact {
+            ImpactModel::Depth {
+                bid_depth,
+                ask_depth,
+                ..
+            } => {
+                let depth = if selling { bid_depth } else { ask_depth };
+                depth.min(self.max_participation * volume)
+            }
+            ImpactModel::SquareRoot { .. } => self.max_participation * volume,
+        };
+        (available / size).clamp(0.0, 1.0)
+    }
+
+    /// Average adverse slippage of a filled `size` order, as a fraction of price.
+    pub fn slippage(&self, size: f64, volume: f64, selling: bool) -> f64 {
+        match self.impact {
+            ImpactModel::SquareRoot {
+                coefficient,
+                spread,
+            } => spread / 2.0 + coefficient * (size / volume.max(f64::EPSILON)).sqrt(),
+            ImpactModel::Depth {
+                bid_depth,
+                ask_depth,
+                spread,
+            } => {
+                let depth = if selling { bid_depth } else { ask_depth };
+                // Walking an even book moves the price linearly, so fills average half way
+                spread / 2.0 + 0.01 * (size / depth.max(f64::EPSILON)).min(1.0) / 2.0
+            }
+        }
+    }
+
+    /// `trade`, opened in the `entry` candle and closed in `exit` with `leverage`, after
+    /// slippage on both fills and scaled to the share of its size that filled; the unfilled
+    /// rest stays in cash. A liquidation still loses the whole margin.
+    pub fn apply(&self, trade: Trade, entry: &[f64; 6], exit: &[f64; 6], leverage: f64) -> Trade {
+        let size = self.notional * leverage / trade.entry_price;
+        let opening_sells = trade.side == Action::Short;
+        let filled = self
+            .filled(size, entry[VOLUME], opening_sells)
+            .min(self.filled(size, exit[VOLUME], !opening_sells));
+        if trade.is_liquidation() || filled == 0.0 {
+            return Trade {
+                return_pct: if filled == 0.0 { 0.0 } else { -1.0 },
+                funding: if filled == 0.0 { 0.0 } else { trade.funding },
+                ..trade
+            };
+        }
+        let size = size * filled;
+        let slip_in = self.slippage(size, entry[VOLUME], opening_sells);
+        let slip_out = self.slippage(size, exit[VOLUME], !opening_sells);
+        let direction = if opening_sells { -1.0 } else { 1.0 };
+        Trade {
+            entry_price: trade.entry_price * (1.0 + direction * slip_in),
+            exit_price: trade.exit_price * (1.0 - direction * slip_out),
+            return_pct: (trade.return_pct - (slip_in + slip_out) * leverage).max(-1.0) * filled,
+            funding: trade.funding * filled,
+            ..trade
+        }
+    }
+}
+
+/// Opens a position at the close of `data[entry]` and exits at the close of the next
+/// candle. Returns `None` for `Action::None` or when there is no next candle.
+pub fn next_candle_trade(data: &[[f64; 6]], entry: usize, side: Action, fee: f64) -> Option<Trade> {
+    bracket_trade(data, entry, side, None, None, fee)
+}
+
+/// Granularity of the candles delayed orders are filled from, in seconds.
+pub const FILL_GRANULARITY: u32 = 60;
+/// Longest an order waits for a fill candle, in seconds, before the fill data counts as
+/// missing.
+const MAX_FILL_WAIT: f64 = 3600.0;
+
+/// Finer candles that fill orders sent `delay_secs` after the close of the candle their
+/// signal was computed on, rather than at that close.
+#[derive(Debug, Clone, Copy)]
+pub struct DelayedFills<'a> {
+    /// Candles of [`FILL_GRANULARITY`], in time order.
+    pub candles: &'a [[f64; 6]],
+    pub delay_secs: f64,
+}
+
+impl DelayedFills<'_> {
+    /// Price an order arriving at `time` fills at: the open of the first fill candle at or
+    /// after it, which rounds the delay up to the fill granularity.
+    pub fn price(&self, time: f64) -> Option<f64> {
+        let i = self.candles.partition_point(|c| c[TIME] < time);
+        let candle = self.candles.get(i)?;
+        (candle[TIME] - time <= MAX_FILL_WAIT).then_some(candle[OPEN])
+    }
+
+    /// Candles `entry` and the one after it from `data`, with the entry's close replaced
+    /// by the delayed fill price, so trades opened on them enter at that price. `None` when
+    /// there is no next candle or no fill.
+    pub fn reprice(&self, data: &[[f64; 6]], entry: usize) -> Option<[[f64; 6]; 2]> {
+        let (mut open, close) = (*data.get(entry)?, *data.get(entry + 1)?);
+        open[CLOSE] = self.price(close[TIME] + self.delay_secs)?;
+        Some([open, close])
+    }
+}
+
+/// Which of a prediction's levels the following candle reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LevelOutcome {
+    Target,
+    Invalidated,
+    /// Neither level was touched before the candle closed.
+    Open,
+}
+
+/// Checks a `side` position entered at `entry_price` against the `next` candle's range.
+/// Hourly candles don't say which level was touched first, so a candle that spans both is
+/// counted as invalidated. Levels on the wrong side of the entry are ignored; `None` when
+/// the side is `None` or no usable level remains.
+pub fn level_outcome(
+    side: Action,
+    entry_price: f64,
+    stop: Option<f64>,
+    target: Option<f64>,
+    next: &[f64; 6],
+) -> Option<LevelOutcome> {
+    let direction = match side {
+        Action::Long => 1.0,
+        Action::Short => -1.0,
+        Action::None => return None,
+    };
+    // Worst and best prices the position saw during the candle
+    let (adverse, favourable) = match side {
+        Action::Long => (next[LOW], next[HIGH]),
+        _ => (next[HIGH], next[LOW]),
+    };
+    let stop = stop.filter(|s| (entry_price - s) * direction > 0.0);
+    let target = target.filter(|t| (t - entry_price) * direction > 0.0);
+    if stop.is_none() && target.is_none() {
+        return None;
+    }
+    Some(match (stop, target) {
+        (Some(s), _) if (s - adverse) * direction >= 0.0 => LevelOutcome::Invalidated,
+        (_, Some(t)) if (favourable - t) * direction >= 0.0 => LevelOutcome::Target,
+        _ => LevelOutcome::Open,
+    })
+}
+
+/// Like [`next_candle_trade`], but exits at `stop` or `target` when the next candle trades
+/// through them, as [`level_outcome`] decides.
+pub fn bracket_trade(
+    data: &[[f64; 6]],
+    entry: usize,
+    side: Action,
+    stop: Option<f64>,
+    target: Option<f64>,
+    fee: f64,
+) -> Option<Trade> {
+    let direction = match side {
+        Action::Long => 1.0,
+        Action::Short => -1.0,
+        Action::None => return None,
+    };
+    let open = data.get(entry)?;
+    let close = data.get(entry + 1)?;
+    let entry_price = open[CLOSE];
+
+    let exit_price = match level_outcome(side, entry_price, stop, target, close) {
+        Some(LevelOutcome::Invalidated) => stop.expect("invalidated implies a stop"),
+        Some(LevelOutcome::Target) => target.expect("target hit implies a target"),
+        _ => close[CLOSE],
+    };
+
+    let gross = (exit_price / entry_price - 1.0) * direction;
+    Some(Trade {
+        side,
+        entry_time: open[TIME],
+        entry_price,
+        exit_time: close[TIME],
+        exit_price,
+        return_pct: gross - 2.0 * fee,
+        funding: 0.0,
+    })
+}
+
+/// [`bracket_trade`] on margin: returns are on the margin posted, so scaled by leverage,
+/// shorts pay [`MarginModel::short_cost_per_hour`], and a candle that trades through the
+/// liquidation price before any stop loses the whole margin.
+pub fn margin_trade(
+    data: &[[f64; 6]],
+    entry: usize,
+    side: Action,
+    stop: Option<f64>,
+    target: Option<f64>,
+    fee: f64,
+    margin: &MarginModel,
+) -> Option<Trade> {
+    let mut trade = bracket_trade(data, entry, side, stop, target, fee)?;
+    let next = &data[entry + 1];
+    let (direction, adverse) = match side {
+        Action::Long => (1.0, next[LOW]),
+        _ => (-1.0, next[HIGH]),
+    };
+
+    if let Some(liquidation) = margin.liquidation_price(side, trade.entry_price) {
+        let reached = (liquidation - adverse) * direction >= 0.0;
+        // A stop between the entry and the liquidation price closes the position first
+        let stopped_first = stop.is_some_and(|s| {
+            (trade.entry_price - s) * direction > 0.0 && (s - liquidation) * direction > 0.0
+        });
+        if reached && !stopped_first {
+            trade.exit_price = liquidation;
+            trade.return_pct = -1.0;
+            return Some(trade);
+        }
+    }
+
+    let hours = (trade.exit_time - trade.entry_time) / 3600.0;
+    let financing = match side {
+        Action::Short => margin.short_cost_per_hour * hours,
+        _ => 0.0,
+    };
+    trade.return_pct = ((trade.return_pct - financing) * margin.leverage).max(-1.0);
+    Some(trade)
+}
+
+/// `trade` after the perpetual funding it paid or received at the settlements in
+/// `funding` while held: from the close of its entry candle to the close of its exit
+/// candle, `interval` seconds after their open times. Returns are on margin, so funding
+/// scales with `leverage`; a liquidation has already lost the whole margin.
+pub fn accrue_funding(
+    trade: Trade,
+    funding: &[FundingPoint],
+    interval: f64,
+    leverage: f64,
+) -> Trade {
+    if trade.is_liquidation() {
+        return trade;
+    }
+    let paid = funding_paid(
+        funding,
+        trade.side,
+        trade.entry_time + interval,
+        trade.exit_time + interval,
+    );
+    Trade {
+        return_pct: (trade.return_pct - paid * leverage).max(-1.0),
+        funding: trade.funding + paid * leverage,
+        ..trade
+    }
+}
+
+/// Trades the target/hedge spread from the close of candle `entry` to the next close:
+/// long target and short hedge for `Action::Long`, the reverse for `Action::Short`, with
+/// equal notional on each leg. Prices are the target/hedge ratio; the return is per unit
+/// of notional per leg and pays `fee` on both sides of both legs.
+pub fn pair_trade(
+    target: &[[f64; 6]],
+    hedge: &[[f64; 6]],
+    entry: usize,
+    side: Action,
+    fee: f64,
+) -> Option<Trade> {
+    let direction = match side {
+        Action::Long => 1.0,
+        Action::Short => -1.0,
+        Action::None => return None,
+    };
+    let (t0, t1) = (target.get(entry)?, target.get(entry + 1)?);
+    let (h0, h1) = (hedge.get(entry)?, hedge.get(entry + 1)?);
+    let spread = (t1[CLOSE] / t0[CLOSE] - 1.0) - (h1[CLOSE] / h0[CLOSE] - 1.0);
+    Some(Trade {
+        side,
+        entry_time: t0[TIME],
+        entry_price: t0[CLOSE] / h0[CLOSE],
+        exit_time: t1[TIME],
+        exit_price: t1[CLOSE] / h1[CLOSE],
+        return_pct: spread * direction - 4.0 * fee,
+        funding: 0.0,
+    })
+}
+
+/// How a simulated position is closed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitRule {
+    /// At the close of the next candle.
+    NextCandle,
+    /// At the prediction's invalidation or target level, else the next close, as
+    /// [`bracket_trade`] does.
+    Levels,
+    /// At the close `n` candles after entry.
+    FixedHold(usize),
+    /// When a later signal points the other way, which opens the reverse position. Signals
+    /// on the side already held are ignored.
+    OppositeSignal,
+    /// When price gives back this fraction from its best level since entry, or after
+    /// [`TRAILING_MAX_HOURS`].
+    TrailingStop(f64),
+}
+
+impl std::fmt::Display for ExitRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExitRule::NextCandle => write!(f, "next_candle"),
+            ExitRule::Levels => write!(f, "levels"),
+            ExitRule::FixedHold(n) => write!(f, "fixed_hold:{n}"),
+            ExitRule::OppositeSignal => write!(f, "opposite_signal"),
+            ExitRule::TrailingStop(x) => write!(f, "trailing_stop:{x}"),
+        }
+    }
+}
+
+impl std::str::FromStr for ExitRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = s.split_once(':').unwrap_or((s, ""));
+        match (name, arg) {
+            ("next_candle", "") => Ok(ExitRule::NextCandle),
+            ("levels", "") => Ok(ExitRule::Levels),
+            ("fixed_hold", n) if !n.is_empty() => Ok(ExitRule::FixedHold(n.parse()?)),
+            ("opposite_signal", "") => Ok(ExitRule::OppositeSignal),
+            ("trailing_stop", x) if !x.is_empty() => Ok(ExitRule::TrailingStop(x.parse()?)),
+            _ => anyhow::bail!(
+                "Unknown exit rule {:?} (expected next_candle, levels, fixed_hold:N, \
+                 opposite_signal or trailing_stop:FRACTION)",
+                s
+            ),
+        }
+    }
+}
+
+/// Longest a trailing-stop position is held.
+pub const TRAILING_MAX_HOURS: usize = 24;
+
+/// The exit styles a backtest reports on.
+pub const EXIT_RULES: [ExitRule; 5] = [
+    ExitRule::NextCandle,
+    ExitRule::Levels,
+    ExitRule::FixedHold(4),
+    ExitRule::OppositeSignal,
+    ExitRule::TrailingStop(0.01),
+];
+
+/// A prediction to trade: entered at the close of `data[entry]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Signal {
+    pub entry: usize,
+    pub side: Action,
+    pub stop: Option<f64>,
+    pub target: Option<f64>,
+}
+
+/// Trades `signals` (in entry order) over `data` with `rule`. Apart from
+/// [`ExitRule::OppositeSignal`], every signal is its own position, so positions of longer
+/// holds can overlap.
+pub fn simulate_exits(
+    data: &[[f64; 6]],
+    signals: &[Signal],
+    rule: ExitRule,
+    fee: f64,
+) -> Vec<Trade> {
+    if rule == ExitRule::OppositeSignal {
+        return hold_until_reversed(data, signals, fee);
+    }
+    signals
+        .iter()
+        .filter_map(|s| match rule {
+            ExitRule::NextCandle => next_candle_trade(data, s.entry, s.side, fee),
+            ExitRule::Levels => bracket_trade(data, s.entry, s.side, s.stop, s.target, fee),
+            ExitRule::FixedHold(n) => {
+                let exit = s.entry + n.max(1);
+                let price = data.get(exit)?[CLOSE];
+                closed_trade(data, s.entry, exit, price, s.side, fee)
+            }
+            ExitRule::TrailingStop(distance) => {
+                let (exit, price) = trailing_exit(data, s.entry, s.side, distance)?;
+                closed_trade(data, s.entry, exit, price, s.side, fee)
+            }
+            ExitRule::OppositeSignal => unreachable!("handled above"),
+        })
+        .collect()
+}
+
+fn hold_until_reversed(data: &[[f64; 6]], signals: &[Signal], fee: f64) -> Vec<Trade> {
+    let mut trades = Vec::new();
+    let mut open: Option<(usize, Action)> = None;
+    for s in signals.iter().filter(|s| s.side != Action::None) {
+        match open {
+            Some((_, side)) if side == s.side => {}
+            Some((entry, side)) => {
+                trades.extend(closed_trade(
+                    data,
+                    entry,
+                    s.entry,
+                    data[s.entry][CLOSE],
+                    side,
+                    fee,
+                ));
+                open = Some((s.entry, s.side));
+            }
+            None => open = Some((s.entry, s.side)),
+        }
+    }
+    // Whatever is still open is closed at the end of the data
+    if let (Some((entry, side)), Some(last)) = (open, data.len().checked_sub(1)) {
+        trades.extend(closed_trade(
+            data,
+            entry,
+            last,
+            data[last][CLOSE],
+            side,
+            fee,
+        ));
+    }
+    trades
+}
+
+/// Exit candle and price of a trailing stop trailing `distance` behind the best price
+/// since entry. The stop only moves on completed candles.
+fn trailing_exit(
+    data: &[[f64; 6]],
+    entry: usize,
+    side: Action,
+    distance: f64,
+) -> Option<(usize, f64)> {
+    let direction = match side {
+        Action::Long => 1.0,
+        Action::Short => -1.0,
+        Action::None => return None,
+    };
+    let last = (entry + TRAILING_MAX_HOURS).min(data.len().checked_sub(1)?);
+    let mut best = data.get(entry)?[CLOSE];
+    for (j, c) in data.iter().enumerate().take(last + 1).skip(entry + 1) {
+        let stop = best * (1.0 - direction * distance);
+        let (adverse, favourable) = match side {
+            Action::Long => (c[LOW], c[HIGH]),
+            _ => (c[HIGH], c[LOW]),
+        };
+        if (stop - adverse) * direction >= 0.0 {
+            return Some((j, stop));
+        }
+        if (favourable - best) * direction > 0.0 {
+            best = favourable;
+        }
+    }
+    (last > entry).then(|| (last, data[last][CLOSE]))
+}
+
+fn closed_trade(
+    data: &[[f64; 6]],
+    entry: usize,
+    exit: usize,
+    exit_price: f64,
+    side: Action,
+    fee: f64,
+) -> Option<Trade> {
+    let direction = match side {
+        Action::Long => 1.0,
+        Action::Short => -1.0,
+        Action::None => return None,
+    };
+    if exit <= entry {
+        return None;
+    }
+    let entry_price = data.get(entry)?[CLOSE];
+    Some(Trade {
+        side,
+        entry_time: data[entry][TIME],
+        entry_price,
+        exit_time: data.get(exit)?[TIME],
+        exit_price,
+        return_pct: (exit_price / entry_price - 1.0) * direction - 2.0 * fee,
+        funding: 0.0,
+    })
+}
+
+/// Aggregates trades (in chronological order) into an equity curve summary.
+pub fn summarize(trades: &[Trade]) -> SimulationSummary {
+    let mut equity = 1.0f64;
+    let mut peak = 1.0f64;
+    let mut max_drawdown = 0.0f64;
+    for t in trades {
+        equity *= 1.0 + t.return_pct;
+        peak = peak.max(equity);
+        max_drawdown = max_drawdown.max(1.0 - equity / peak);
+    }
+
+    let wins = trades.iter().filter(|t| t.return_pct > 0.0).count();
+    SimulationSummary {
+        liquidations: trades.iter().filter(|t| t.is_liquidation()).count(),
+        trades: trades.len(),
+        total_return: equity - 1.0,
+        win_rate: if trades.is_empty() {
+            0.0
+        } else {
+            wins as f64 / trades.len() as f64
+        },
+        max_drawdown,
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PortfolioSummary {
+    pub rebalances: usize,
+    /// Compounded net return of the portfolio.
+    pub total_return: f64,
+    /// Sum over rebalances of the fraction of the portfolio traded.
+    pub turnover: f64,
+    pub max_drawdown: f64,
+}
+
+/// Rebalances to each step's target weights (one per asset, the rest in cash) and then
+/// holds through the step's asset returns. Weights drift with prices between rebalances,
+/// and each rebalance pays `fee` on the fraction of the portfolio it trades.
+pub fn rebalance_portfolio(steps: &[(Vec<f64>, Vec<f64>)], fee: f64) -> PortfolioSummary {
+    let mut equity = 1.0f64;
+    let mut peak = 1.0f64;
+    let mut summary = PortfolioSummary::default();
+    // Weights held going into the next rebalance; all cash to begin with
+    let mut held: Vec<f64> = Vec::new();
+    for (target, returns) in steps {
+        held.resize(target.len(), 0.0);
+        let traded = target
+            .iter()
+            .zip(&held)
+            .map(|(t, h)| (t - h).abs())
+            .sum::<f64>();
+        summary.turnover += traded;
+        equity *= 1.0 - traded * fee;
+
+        let growth = 1.0 + target.iter().zip(returns).map(|(w, r)| w * r).sum::<f64>();
+        held = target
+            .iter()
+            .zip(returns)
+            .map(|(w, r)| w * (1.0 + r) / growth)
+            .collect();
+        equity *= growth;
+        peak = peak.max(equity);
+        summary.max_drawdown = summary.max_drawdown.max(1.0 - equity / peak);
+        summary.rebalances += 1;
+    }
+    summary.total_return = equity - 1.0;
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_candle_trade_and_summary() {
+        let data = [
+            [0.0, 100.0, 100.0, 100.0, 100.0, 1.0],
+            [3600.0, 100.0, 110.0, 100.0, 110.0, 1.0],
+            [7200.0, 110.0, 110.0, 99.0, 99.0, 1.0],
+        ];
+
+        let long = next_candle_trade(&data, 0, Action::Long, 0.0).unwrap();
+        assert!((long.return_pct - 0.1).abs() < 1e-12);
+        let short = next_candle_trade(&data, 1, Action::Short, 0.0).unwrap();
+        assert!((short.return_pct - 0.1).abs() < 1e-12);
+        assert!(next_candle_trade(&data, 0, Action::None, 0.0).is_none());
+        assert!(next_candle_trade(&data, 2, Action::Long, 0.0).is_none());
+
+        let losing = next_candle_trade(&data, 1, Action::Long, 0.0).unwrap();
+        let summary = summarize(&[long, losing]);
+        assert_eq!(summary.trades, 2);
+        assert_eq!(summary.win_rate, 0.5);
+        assert!((summary.total_return - (1.1 * 0.9 - 1.0)).abs() < 1e-12);
+        assert!((summary.max_drawdown - 0.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_bracket_trade_exits() {
+        let data = [
+            [0.0, 100.0, 100.0, 100.0, 100.0, 1.0],
+            [3600.0, 100.0, 110.0, 97.0, 105.0, 1.0],
+        ];
+        let exit = |side, stop, target| {
+            bracket_trade(&data, 0, side, stop, target, 0.0)
+                .unwrap()
+                .exit_price
+        };
+
+        assert_eq!(exit(Action::Long, None, None), 105.0);
+        assert_eq!(exit(Action::Long, Some(98.0), Some(108.0)), 98.0);
+        assert_eq!(exit(Action::Long, Some(95.0), Some(108.0)), 108.0);
+        assert_eq!(exit(Action::Long, Some(95.0), Some(120.0)), 105.0);
+        assert_eq!(exit(Action::Short, Some(108.0), Some(98.0)), 108.0);
+        assert_eq!(exit(Action::Short, Some(112.0), Some(98.0)), 98.0);
+        // A "stop" above a long's entry is meaningless and ignored
+        assert_eq!(exit(Action::Long, Some(102.0), None), 105.0);
+
+        let next = &data[1];
+        assert_eq!(
+            level_outcome(Action::Long, 100.0, Some(95.0), Some(120.0), next),
+            Some(LevelOutcome::Open)
+        );
+        assert_eq!(
+            level_outcome(Action::Short, 100.0, Some(112.0), Some(98.0), next),
+            Some(LevelOutcome::Target)
+        );
+        assert_eq!(
+            level_outcome(Action::Long, 100.0, Some(102.0), None, next),
+            None
+        );
+        assert_eq!(
+            level_outcome(Action::None, 100.0, Some(95.0), None, next),
+            None
+        );
+    }
+
+    #[test]
+    fn test_margin_trade() {
+        let data = [
+            [0.0, 100.0, 100.0, 100.0, 100.0, 1.0],
+            [3600.0, 100.0, 101.0, 90.0, 98.0, 1.0],
+        ];
+        let unlevered = MarginModel::default();
+        let long = margin_trade(&data, 0, Action::Long, None, None, 0.0, &unlevered).unwrap();
+        assert!((long.return_pct + 0.02).abs() < 1e-12);
+        let short = margin_trade(&data, 0, Action::Short, None, None, 0.0, &unlevered).unwrap();
+        assert!((short.return_pct - (0.02 - unlevered.short_cost_per_hour)).abs() < 1e-12);
+
+        // At 5x a long is liquidated around 80.5, below this candle's low
+        let five_x = MarginModel {
+            leverage: 5.0,
+            ..unlevered
+        };
+        let long = margin_trade(&data, 0, Action::Long, None, None, 0.0, &five_x).unwrap();
+        assert!((long.return_pct + 0.1).abs() < 1e-12);
+        // A 0.01% settlement while held costs 0.05% of the margin, recorded on the trade
+        let funded = accrue_funding(long, &[[1800.0, 0.0001]], 0.0, 5.0);
+        assert!((funded.return_pct + 0.1005).abs() < 1e-12);
+        assert!((funded.funding - 0.0005).abs() < 1e-12);
+        // At 10x the liquidation price is 90.5, which the candle trades through
+        let ten_x = MarginModel {
+            leverage: 10.0,
+            ..unlevered
+        };
+        let liquidated = margin_trade(&data, 0, Action::Long, None, None, 0.0, &ten_x).unwrap();
+        assert!(liquidated.is_liquidation());
+        assert!((liquidated.exit_price - 90.5).abs() < 1e-9);
+        // unless a stop closer to the entry closes the position first
+        let stopped = margin_trade(&data, 0, Action::Long, Some(95.0), None, 0.0, &ten_x).unwrap();
+        assert!((stopped.return_pct + 0.5).abs() < 1e-12);
+        assert_eq!(summarize(&[stopped, liquidated]).liquidations, 1);
+    }
+
+    #[test]
+    fn test_delayed_fills() {
+        let data = [
+            [0.0, 100.0, 100.0, 100.0, 100.0, 1.0],
+            [3600.0, 100.0, 101.0, 99.0, 98.0, 1.0],
+        ];
+        // The price keeps rising for a few minutes after the signal's candle closes
+        let minutes = [(3600.0, 100.0), (3660.0, 100.5), (3720.0, 101.0)]
+            .map(|(t, open)| [t, open, open, open, open, 1.0]);
+        let fills = DelayedFills {
+            candles: &minutes,
+            delay_secs: 90.0,
+        };
+        assert_eq!(fills.price(3600.0), Some(100.0));
+        assert_eq!(fills.price(3690.0), Some(101.0));
+        assert_eq!(fills.price(3721.0), None);
+
+        let repriced = fills.reprice(&data, 0).unwrap();
+        assert_eq!(repriced[0][TIME], 0.0);
+        let trade = next_candle_trade(&repriced, 0, Action::Long, 0.0).unwrap();
+        assert_eq!(trade.entry_price, 101.0);
+        assert!((trade.return_pct - (98.0 / 101.0 - 1.0)).abs() < 1e-12);
+        assert_eq!(fills.reprice(&data, 1), None);
+    }
+
+    #[test]
+    fn test_fill_model() {
+        let entry = [0.0, 100.0, 100.0, 100.0, 100.0, 1000.0];
+        let exit = [3600.0, 100.0, 102.0, 100.0, 102.0, 1000.0];
+        let trade = next_candle_trade(&[entry, exit], 0, Action::Long, 0.0).unwrap();
+
+        // 10 units are 1% of each candle's volume: 10bp of impact and 1bp of spread a side
+        let small = FillModel::new(1000.0, ImpactModel::default()).apply(trade, &entry, &exit, 1.0);
+        assert!((small.return_pct - (0.02 - 0.0022)).abs() < 1e-12);
+        assert!((small.entry_price - 100.11).abs() < 1e-9);
+
+        // 200 units can only take 100 of either candle: half stays in cash
+        let large = FillModel::new(20_000.0, ImpactModel::default());
+        assert_eq!(large.filled(200.0, 1000.0, false), 0.5);
+        let halved = large.apply(trade, &entry, &exit, 1.0);
+        assert!(halved.return_pct < small.return_pct / 2.0);
+
+        let book = FillModel::new(
+            1000.0,
+            ImpactModel::Depth {
+                bid_depth: 5.0,
+                ask_depth: 20.0,
+                spread: 0.0,
+            },
+        );
+        // Half the asks within 1% move the average buy by a quarter percent
+        assert!((book.slippage(10.0, 1000.0, false) - 0.0025).abs() < 1e-12);
+        assert_eq!(book.filled(10.0, 1000.0, true), 0.5);
+    }
+
+    #[test]
+    fn test_exit_rules() {
+        let closes = [100.0, 102.0, 104.0, 103.0, 101.0, 100.0];
+        let data = closes
+            .iter()
+            .enumerate()
+            .map(|(h, &c)| [h as f64 * 3600.0, c, c + 0.5, c - 0.5, c, 1.0])
+            .collect::<Vec<_>>();
+        let signal = |entry, side| Signal {
+            entry,
+            side,
+            stop: None,
+            target: None,
+        };
+        let signals = [
+            signal(0, Action::Long),
+            signal(1, Action::Long),
+            signal(3, Action::Short),
+        ];
+
+        let next = simulate_exits(&data, &signals, ExitRule::NextCandle, 0.0);
+        assert_eq!(next.len(), 3);
+        let held = simulate_exits(&data, &signals, ExitRule::FixedHold(2), 0.0);
+        assert_eq!(held[0].exit_price, 104.0);
+        assert_eq!(held[2].exit_price, 100.0);
+
+        // Long from 0 to the short at 3, then short to the end of the data
+        let reversed = simulate_exits(&data, &signals, ExitRule::OppositeSignal, 0.0);
+        assert_eq!(reversed.len(), 2);
+        assert_eq!(
+            (reversed[0].entry_price, reversed[0].exit_price),
+            (100.0, 103.0)
+        );
+        assert_eq!(reversed[1].side, Action::Short);
+        assert_eq!(reversed[1].exit_price, 100.0);
+
+        // Best high 104.5 by candle 2; a 1% trail is hit by candle 3's low of 102.5
+        let trailed = simulate_exits(&data, &signals[..1], ExitRule::TrailingStop(0.01), 0.0);
+        assert_eq!(trailed[0].exit_time, 3.0 * 3600.0);
+        assert!((trailed[0].exit_price - 104.5 * 0.99).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_pair_trade() {
+        let eth = [
+            [0.0, 0.0, 0.0, 0.0, 100.0, 1.0],
+            [3600.0, 0.0, 0.0, 0.0, 102.0, 1.0],
+        ];
+        let btc = [
+            [0.0, 0.0, 0.0, 0.0, 50.0, 1.0],
+            [3600.0, 0.0, 0.0, 0.0, 50.5, 1.0],
+        ];
+        let long = pair_trade(&eth, &btc, 0, Action::Long, 0.0).unwrap();
+        assert!((long.return_pct - 0.01).abs() < 1e-12);
+        assert_eq!((long.entry_price, long.exit_price), (2.0, 102.0 / 50.5));
+        let short = pair_trade(&eth, &btc, 0, Action::Short, 0.001).unwrap();
+        assert!((short.return_pct + 0.014).abs() < 1e-12);
+        assert!(pair_trade(&eth, &btc, 1, Action::Long, 0.0).is_none());
+    }
+
+    #[test]
+    fn test_portfolio_rebalancing() {
+        let steps = [
+            (vec![0.5, 0.0], vec![0.1, 0.5]),
+            // Drifted to 0.55/1.05 in the first asset; rebalance back to half
+            (vec![0.5, 0.0], vec![-0.2, 0.0]),
+            (vec![0.0, 0.0], vec![0.3, 0.3]),
+        ];
+        let summary = rebalance_portfolio(&steps, 0.0);
+        assert_eq!(summary.rebalances, 3);
+        assert!((summary.total_return - (1.05 * 0.9 - 1.0)).abs() < 1e-12);
+        assert!((summary.max_drawdown - 0.1).abs() < 1e-12);
+        let drifted = 0.55 / 1.05;
+        let expected = 0.5 + (drifted - 0.5) + 0.4 / 0.9;
+        assert!((summary.turnover - expected).abs() < 1e-12);
+
+        let with_fees = rebalance_portfolio(&steps, 0.01);
+        assert!(with_fees.total_return < summary.total_return);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::calendar::CalendarEvent;
+use crate::compute::{symbol_spec, AnnotatedWindow};
+use crate::data_quality::{anomalies_in_window, CandleAnomaly};
+use crate::encoding::CandleEncoding;
+use crate::features::{FeatureRegistry, FeatureSet};
+use crate::implied_vol::IvRegime;
+use crate::indicators::{volume_features, VolumeFeatures};
+use crate::prompt_builder::{SymbolRole, SymbolSpec};
+
+/// Order book depth near the mid price.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthSummary {
+    /// Base-currency size bid within 1% of the mid.
+    pub bid_depth: f64,
+    /// Base-currency size offered within 1% of the mid.
+    pub ask_depth: f64,
+    /// Best ask over best bid, minus one.
+    pub spread: f64,
+}
+
+/// Forced closures across exchanges over the hours before the decision.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LiquidationSummary {
+    /// Closed hours the totals cover.
+    pub hours: usize,
+    /// USD value of long positions liquidated.
+    pub long_usd: f64,
+    /// USD value of short positions liquidated.
+    pub short_usd: f64,
+    pub last_hour_long_usd: f64,
+    pub last_hour_short_usd: f64,
+}
+
+/// An options implied-volatility index, such as Deribit's DVOL.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImpliedVolatility {
+    /// Annualized implied volatility, in percent.
+    pub level: f64,
+    /// Relative change over the last day, as a fraction.
+    pub change_24h: Option<f64>,
+    pub regime: IvRegime,
+}
+
+/// Short-term order flow at the time of a live decision.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Microstructure {
+    /// Best ask over best bid, minus one.
+    pub spread: f64,
+    /// Fraction of the recent traded volume bought by takers.
+    pub taker_buy_share: f64,
+    /// Trades the share covers.
+    pub trades: usize,
+    /// Minutes between the oldest and newest of those trades.
+    pub minutes: f64,
+}
+
+/// Data beyond candles that a source can attach to a symbol. Nothing is rendered for
+/// what is left unset.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuxContext {
+    /// Perpetual funding rate per funding interval, as a fraction.
+    pub funding_rate: Option<f64>,
+    pub depth: Option<DepthSummary>,
+    pub liquidations: Option<LiquidationSummary>,
+    pub implied_volatility: Option<ImpliedVolatility>,
+    pub microstructure: Option<Microstructure>,
+    pub headlines: Vec<String>,
+}
+
+impl AuxContext {
+    pub fn is_empty(&self) -> bool {
+        self.funding_rate.is_none()
+            && self.depth.is_none()
+            && self.liquidations.is_none()
+            && self.implied_volatility.is_none()
+            && self.microstructure.is_none()
+            && self.headlines.is_empty()
+    }
+}
+
+/// One symbol's part of a [`MarketSnapshot`].
+#[derive(Debug, Clone)]
+pub struct SymbolSnapshot<'a> {
+    pub spec: SymbolSpec<'a>,
+    pub candles: &'a [[f64; 6]],
+    /// Candles of the window flagged by the data-quality pass.
+    pub anomalies: Vec<&'a CandleAnomaly>,
+    pub volume: Option<VolumeFeatures>,
+    pub context: AuxContext,
+}
+
+/// Everything the prompt shows about the market for one window, per symbol in prompt
+/// order. New data sources attach here rather than to the callers that build prompts.
+#[derive(Debug, Clone, Default)]
+pub struct MarketSnapshot<'a> {
+    pub symbols: Vec<SymbolSnapshot<'a>>,
+    /// Scheduled high-impact events in the day after the window.
+    pub events: Vec<CalendarEvent>,
+    /// Values of a [`FeatureRegistry`], once computed.
+    pub features: FeatureSet,
+    /// How the candles are scaled in the prompt.
+    pub encoding: CandleEncoding,
+    /// Whether the prompt lists the computed features; they feed rule strategies and
+    /// reports either way.
+    pub prompt_features: bool,
+}
+
+impl<'a> MarketSnapshot<'a> {
+    /// The candles, flagged candles and indicators of each window, without auxiliary
+    /// context.
+    pub fn from_windows(windows: &[AnnotatedWindow<'a>]) -> Self {
+        Self {
+            symbols: windows
+                .iter()
+                .map(|&(symbol, candles, anomalies)| SymbolSnapshot {
+                    spec: symbol_spec(symbol),
+                    candles,
+                    anomalies: anomalies_in_window(anomalies, candles),
+                    volume: volume_features(candles),
+                    context: AuxContext::default(),
+                })
+                .collect(),
+            events: Vec::new(),
+            features: FeatureSet::default(),
+            encoding: CandleEncoding::Raw,
+            prompt_features: false,
+        }
+    }
+
+    /// Attaches the upcoming calendar `events`.
+    pub fn with_events(mut self, events: Vec<CalendarEvent>) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Computes and attaches the `registry`'s features, from the data attached so far.
+    pub fn with_features(mut self, registry: &FeatureRegistry) -> Self {
+        self.features = registry.compute(&self);
+        self
+    }
+
+    /// The snapshot rendering its candles in `encoding`.
+    pub fn with_encoding(mut self, encoding: CandleEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// The snapshot listing its features in the prompt when `show` is set.
+    pub fn with_prompt_features(mut self, show: bool) -> Self {
+        self.prompt_features = show;
+        self
+    }
+
+    /// Attaches `context` to `symbol`, if the snapshot has it.
+    pub fn with_context(mut self, symbol: &str, context: AuxContext) -> Self {
+        if let Some(s) = self.symbols.iter_mut().find(|s| s.spec.symbol == symbol) {
+            s.context = context;
+        }
+        self
+    }
+
+    /// The snapshot without volume features.
+    pub fn without_volume(mut self) -> Self {
+        for s in &mut self.symbols {
+            s.volume = None;
+        }
+        self
+    }
+
+    /// The snapshot deciding on `symbol`, which moves to the front as the target while every
+    /// other symbol becomes context.
+    pub fn with_target(mut self, symbol: &str) -> Self {
+        for s in &mut self.symbols {
+            s.spec.role = if s.spec.symbol == symbol {
+                SymbolRole::Target
+            } else {
+                SymbolRole::Context
+            };
+        }
+        self.symbols
+            .sort_by_key(|s| s.spec.role != SymbolRole::Target);
+        self
+    }
+
+    /// The snapshot without data-quality notes.
+    pub fn without_anomalies(mut self) -> Self {
+        for s in &mut self.symbols {
+            s.anomalies.clear();
+        }
+        self
+    }
+}
@@ -0,0 +1,166 @@
+use serde_json::Value;
+
+/// Splits a server-sent event stream into the `data` payloads of its events, however the
+/// bytes are chunked.
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: String,
+    /// Bytes of a UTF-8 character split across chunks.
+    partial: Vec<u8>,
+}
+
+impl SseDecoder {
+    /// Adds `chunk` and returns the data of every event it completed.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.partial.extend_from_slice(chunk);
+        let valid = match std::str::from_utf8(&self.partial) {
+            Ok(s) => s.len(),
+            Err(err) => err.valid_up_to(),
+        };
+        let rest = self.partial.split_off(valid);
+        self.buffer
+            .push_str(std::str::from_utf8(&self.partial).expect("checked above"));
+        self.partial = rest;
+        if self.buffer.contains('\r') {
+            self.buffer = self.buffer.replace("\r\n", "\n");
+        }
+
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.find("\n\n") {
+            let event = self.buffer[..end].to_string();
+            self.buffer.drain(..end + 2);
+            let data = event
+                .lines()
+                .filter_map(|l| l.strip_prefix("data:"))
+                .map(|d| d.strip_prefix(' ').unwrap_or(d))
+                .collect::<Vec<_>>();
+            if !data.is_empty() {
+                events.push(data.join("\n"));
+            }
+        }
+        events
+    }
+}
+
+/// What one streamed chat completion chunk carries.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    Content(String),
+    /// The final chunk's token counts, when the request asked for them.
+    Usage(Value),
+    Done,
+}
+
+/// Reads the data of one event of an OpenAI-compatible chat completion stream. Chunks
+/// without content, such as the role announcement, give `None`.
+pub fn stream_event(data: &str) -> anyhow::Result<Option<StreamEvent>> {
+    if data.trim() == "[DONE]" {
+        return Ok(Some(StreamEvent::Done));
+    }
+    let val: Value = serde_json::from_str(data)
+        .map_err(|err| anyhow::anyhow!("Bad stream chunk {:?}: {}", data, err))?;
+    if let Some(err) = val.get("error") {
+        anyhow::bail!("Model API error mid-stream: {}", err);
+    }
+    if let Some(content) = val["choices"]
+        .get(0)
+        .and_then(|choice| choice["delta"]["content"].as_str())
+        .filter(|c| !c.is_empty())
+    {
+        return Ok(Some(StreamEvent::Content(content.to_string())));
+    }
+    Ok(val
+        .get("usage")
+        .filter(|u| u.is_object())
+        .map(|u| StreamEvent::Usage(u.clone())))
+}
+
+/// Watches text as it grows for the end of the first top-level JSON object in it.
+#[derive(Debug, Default)]
+pub struct JsonObjectEnd {
+    /// Bytes of the text already scanned.
+    scanned: usize,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl JsonObjectEnd {
+    /// Scans what was appended to `text` since the last call. Returns the length of the
+    /// text up to and including the object's closing brace once it has arrived.
+    pub fn feed(&mut self, text: &str) -> Option<usize> {
+        for (i, c) in text[self.scanned..].char_indices() {
+            let at = self.scanned + i;
+            if self.in_string {
+                match c {
+                    _ if self.escaped => self.escaped = false,
+                    '\\' => self.escaped = true,
+                    '"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match c {
+                '"' if self.depth > 0 => self.in_string = true,
+                '{' => self.depth += 1,
+                '}' if self.depth > 0 => {
+                    self.depth -= 1;
+                    if self.depth == 0 {
+                        self.scanned = at + 1;
+                        return Some(at + 1);
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.scanned = text.len();
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_decoding_and_object_end() {
+        let stream = "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\r\n\r\n\
+            data: {\"choices\":[{\"delta\":{\"content\":\"```json\\n{\\\"action\\\": \\\"long\\\", \"}}]}\n\n\
+            data: {\"choices\":[{\"delta\":{\"content\":\"\\\"rationale\\\": \\\"a } in {text\\\\\\\"\\\"}\"}}]}\n\n\
+            data: {\"choices\":[],\"usage\":{\"completion_tokens\":9}}\n\n\
+            data: [DONE]\n\n";
+        // Split mid-event and mid-character
+        let bytes = stream.replace("long", "lông").into_bytes();
+        let split = bytes.iter().position(|&b| b == 0xC3).unwrap() + 1;
+        let mut decoder = SseDecoder::default();
+        let mut events = decoder.push(&bytes[..split]);
+        events.extend(decoder.push(&bytes[split..]));
+        assert_eq!(events.len(), 5);
+
+        let events = events
+            .iter()
+            .filter_map(|e| stream_event(e).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[3], StreamEvent::Done);
+        assert!(matches!(&events[2], StreamEvent::Usage(u) if u["completion_tokens"] == 9));
+
+        let mut text = String::new();
+        let mut end = JsonObjectEnd::default();
+        let StreamEvent::Content(first) = &events[0] else {
+            panic!("expected content");
+        };
+        text.push_str(first);
+        assert_eq!(end.feed(&text), None);
+        let StreamEvent::Content(second) = &events[1] else {
+            panic!("expected content");
+        };
+        text.push_str(second);
+        text.push_str("\n```");
+        let end = end.feed(&text).unwrap();
+        assert!(text[..end].ends_with("text\\\"\"}"));
+        assert_eq!(&text[end..], "\n```");
+
+        assert!(stream_event("{\"error\":{\"message\":\"overloaded\"}}").is_err());
+    }
+}
@@ -0,0 +1,335 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+
+use crate::analytics::{AnalyticsStore, PredictionRecord, TradeRecord};
+use crate::backtest::PromptRecord;
+use crate::manifest::RunManifest;
+use crate::profiles::DEFAULT_TARGET;
+use crate::store::CandleStore;
+
+pub const DATA_DIR: &str = "cache";
+const HISTORY_FILE: &str = "prompt_history.json";
+const RUNS_DIR: &str = "runs";
+/// The file backend keeps only this many prompt history entries.
+pub const HISTORY_LIMIT: usize = 10;
+
+/// Persistence for candles, predictions, prompt history and run manifests. The local
+/// file backend is the default; a Postgres backend is available behind the `postgres`
+/// feature for daemons running on a server.
+pub trait Storage: Send + Sync {
+    fn load_candles<'a>(
+        &'a self,
+        symbol: &'a str,
+        granularity: u32,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> BoxFuture<'a, Result<Vec<[f64; 6]>>>;
+
+    /// Upserts candles and returns how many were previously unknown.
+    fn save_candles<'a>(
+        &'a self,
+        symbol: &'a str,
+        granularity: u32,
+        candles: &'a [[f64; 6]],
+    ) -> BoxFuture<'a, Result<usize>>;
+
+    fn append_predictions<'a>(&'a self, rows: &'a [PredictionRecord]) -> BoxFuture<'a, Result<()>>;
+
+    fn append_trades<'a>(&'a self, rows: &'a [TradeRecord]) -> BoxFuture<'a, Result<()>>;
+
+    fn append_prompt_record<'a>(&'a self, record: &'a PromptRecord) -> BoxFuture<'a, Result<()>>;
+
+    /// Sets the out-of-sample accuracy of the latest record of `prompt` in target `symbol`'s
+    /// history, appending a record with the `in_sample` score if there is none.
+    fn record_out_of_sample<'a>(
+        &'a self,
+        symbol: &'a str,
+        prompt: &'a str,
+        in_sample: f64,
+        accuracy: f64,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    /// The most recent `limit` prompt records of target `symbol`, oldest first.
+    fn prompt_history<'a>(
+        &'a self,
+        symbol: &'a str,
+        limit: usize,
+    ) -> BoxFuture<'a, Result<Vec<PromptRecord>>>;
+
+    fn save_manifest<'a>(&'a self, manifest: &'a RunManifest) -> BoxFuture<'a, Result<()>>;
+
+    /// Every saved run manifest, in no particular order.
+    fn manifests(&self) -> BoxFuture<'_, Result<Vec<RunManifest>>>;
+}
+
+/// Selects the storage backend from `DATABASE_URL`, falling back to local files.
+pub async fn from_env() -> Result<Box<dyn Storage>> {
+    match env::var("DATABASE_URL") {
+        Ok(url) if url.starts_with("postgres") => {
+            #[cfg(feature = "postgres")]
+            {
+                let storage = crate::postgres::PostgresStorage::connect(&url).await?;
+                Ok(Box::new(storage))
+            }
+            #[cfg(not(feature = "postgres"))]
+            {
+                anyhow::bail!(
+                    "DATABASE_URL points at Postgres but the `postgres` feature is not enabled"
+                )
+            }
+        }
+        _ => Ok(Box::new(FileStorage::default())),
+    }
+}
+
+/// Local backend: the zstd candle store, JSON Lines analytics tables, a JSON prompt
+/// history and one JSON manifest per run.
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    dir: PathBuf,
+    candles: CandleStore,
+    analytics: AnalyticsStore,
+}
+
+impl Default for FileStorage {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from(DATA_DIR),
+            candles: CandleStore::default(),
+            analytics: AnalyticsStore::default(),
+        }
+    }
+}
+
+impl FileStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        Self {
+            candles: CandleStore::new(dir.join("candles")),
+            analytics: AnalyticsStore::new(dir.join("analytics")),
+            dir,
+        }
+    }
+
+    /// The default target keeps the original history file; other targets get their own.
+    fn history_path(&self, symbol: &str) -> PathBuf {
+        if symbol == DEFAULT_TARGET {
+            self.dir.join(HISTORY_FILE)
+        } else {
+            self.dir.join(format!("prompt_history_{}.json", symbol))
+        }
+    }
+
+    fn read_history(&self, symbol: &str) -> Result<Vec<PromptRecord>> {
+        let path = self.history_path(symbol);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let data = fs::read_to_string(&path)?;
+        Ok(serde_json::from_str(&data).unwrap_or_default())
+    }
+
+    fn write_history(&self, symbol: &str, mut history: Vec<PromptRecord>) -> Result<()> {
+        // Keep only the last few
+        if history.len() > HISTORY_LIMIT {
+            history.drain(..history.len() - HISTORY_LIMIT);
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        let json = serde_json::to_string_pretty(&history)?;
+        fs::write(self.history_path(symbol), json).context("Failed to write prompt history")
+    }
+}
+
+impl Storage for FileStorage {
+    fn load_candles<'a>(
+        &'a self,
+        symbol: &'a str,
+        granularity: u32,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> BoxFuture<'a, Result<Vec<[f64; 6]>>> {
+        async move { self.candles.load_range(symbol, granularity, start, end) }.boxed()
+    }
+
+    fn save_candles<'a>(
+        &'a self,
+        symbol: &'a str,
+        granularity: u32,
+        candles: &'a [[f64; 6]],
+    ) -> BoxFuture<'a, Result<usize>> {
+        async move { self.candles.merge(symbol, granularity, candles) }.boxed()
+    }
+
+    fn append_predictions<'a>(&'a self, rows: &'a [PredictionRecord]) -> BoxFuture<'a, Result<()>> {
+        async move { self.analytics.append_predictions(rows) }.boxed()
+    }
+
+    fn append_trades<'a>(&'a self, rows: &'a [TradeRecord]) -> BoxFuture<'a, Result<()>> {
+        async move { self.analytics.append_trades(rows) }.boxed()
+    }
+
+    fn append_prompt_record<'a>(&'a self, record: &'a PromptRecord) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let mut history = self.read_history(&record.symbol)?;
+            history.push(record.clone());
+            self.write_history(&record.symbol, history)
+        }
+        .boxed()
+    }
+
+    fn record_out_of_sample<'a>(
+        &'a self,
+        symbol: &'a str,
+        prompt: &'a str,
+        in_sample: f64,
+        accuracy: f64,
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let mut history = self.read_history(symbol)?;
+            match history.iter_mut().rev().find(|r| r.prompt == prompt) {
+                Some(record) => record.out_of_sample = Some(accuracy),
+                // Pushed out of the bounded history by later backtests
+                None => history.push(PromptRecord {
+                    prompt: prompt.to_string(),
+                    score: in_sample,
+                    out_of_sample: Some(accuracy),
+                    symbol: symbol.to_string(),
+                }),
+            }
+            self.write_history(symbol, history)
+        }
+        .boxed()
+    }
+
+    fn prompt_history<'a>(
+        &'a self,
+        symbol: &'a str,
+        limit: usize,
+    ) -> BoxFuture<'a, Result<Vec<PromptRecord>>> {
+        async move {
+            let mut history = self.read_history(symbol)?;
+            if history.len() > limit {
+                history.drain(..history.len() - limit);
+            }
+            Ok(history)
+        }
+        .boxed()
+    }
+
+    fn save_manifest<'a>(&'a self, manifest: &'a RunManifest) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let dir = self.dir.join(RUNS_DIR);
+            fs::create_dir_all(&dir)?;
+            let json = serde_json::to_string_pretty(manifest)?;
+            fs::write(dir.join(format!("{}.json", manifest.run_id)), json)
+                .context("Failed to write run manifest")
+        }
+        .boxed()
+    }
+
+    fn manifests(&self) -> BoxFuture<'_, Result<Vec<RunManifest>>> {
+        async move {
+            let dir = self.dir.join(RUNS_DIR);
+            if !dir.exists() {
+                return Ok(Vec::new());
+            }
+            let mut manifests = Vec::new();
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|e| e == "json") {
+                    let data = fs::read_to_string(&path)?;
+                    manifests.push(
+                        serde_json::from_str(&data)
+                            .with_context(|| format!("Corrupt manifest {}", path.display()))?,
+                    );
+                }
+            }
+            Ok(manifests)
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_storage_history_is_bounded() {
+        let dir = std::env::temp_dir().join("happycharts_file_storage");
+        let _ = fs::remove_dir_all(&dir);
+        let storage = FileStorage::new(&dir);
+
+        for i in 0..HISTORY_LIMIT + 3 {
+            let record = PromptRecord {
+                prompt: format!("prompt {}", i),
+                score: i as f64,
+                out_of_sample: None,
+                symbol: DEFAULT_TARGET.to_string(),
+            };
+            storage.append_prompt_record(&record).await.unwrap();
+        }
+
+        let history = storage.prompt_history("ETH", usize::MAX).await.unwrap();
+        assert_eq!(history.len(), HISTORY_LIMIT);
+        assert_eq!(history.last().unwrap().prompt, "prompt 12");
+        let recent = storage.prompt_history("ETH", 2).await.unwrap();
+        assert_eq!(recent[0].prompt, "prompt 11");
+
+        // Other targets keep a history of their own
+        let sol = PromptRecord {
+            prompt: "sol prompt".to_string(),
+            score: 0.6,
+            out_of_sample: None,
+            symbol: "SOL".to_string(),
+        };
+        storage.append_prompt_record(&sol).await.unwrap();
+        let history = storage.prompt_history("SOL", usize::MAX).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].prompt, "sol prompt");
+        assert_eq!(
+            storage.prompt_history("ETH", 1).await.unwrap()[0].prompt,
+            "prompt 12"
+        );
+
+        storage
+            .record_out_of_sample("ETH", "prompt 11", 11.0, 0.4)
+            .await
+            .unwrap();
+        storage
+            .record_out_of_sample("ETH", "prompt 0", 0.0, 0.3)
+            .await
+            .unwrap();
+        storage
+            .record_out_of_sample("SOL", "sol prompt", 0.6, 0.5)
+            .await
+            .unwrap();
+        let sol = storage.prompt_history("SOL", usize::MAX).await.unwrap();
+        assert_eq!(sol[0].out_of_sample, Some(0.5));
+        let history = storage.prompt_history("ETH", usize::MAX).await.unwrap();
+        assert_eq!(history.len(), HISTORY_LIMIT);
+        assert_eq!(history[HISTORY_LIMIT - 3].out_of_sample, Some(0.4));
+        let evicted = history.last().unwrap();
+        assert_eq!(evicted.prompt, "prompt 0");
+        assert_eq!((evicted.score, evicted.out_of_sample), (0.0, Some(0.3)));
+
+        let candles = [[0.0, 1.0, 1.0, 1.0, 1.0, 1.0]];
+        assert_eq!(
+            storage.save_candles("ETH", 3600, &candles).await.unwrap(),
+            1
+        );
+        let start = DateTime::from_timestamp(0, 0).unwrap();
+        let loaded = storage
+            .load_candles("ETH", 3600, start, start + chrono::Duration::hours(1))
+            .await
+            .unwrap();
+        assert_eq!(loaded.len(), 1);
+    }
+}
@@ -0,0 +1,781 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::candles_to_array;
+use crate::compression;
+use crate::data_quality::repair_order;
+use crate::live::get_candle_data;
+
+pub const STORE_DIR: &str = "cache/candles";
+/// Where compaction keeps the candles it rolls up, at their own granularity, in the same
+/// layout as the store.
+pub const ARCHIVE_DIR: &str = "cache/archive/candles";
+/// Granularity of the daily aggregates compaction rolls older candles into.
+pub const DAILY: u32 = 86_400;
+/// Where a store records how far compaction rolled each of its series up.
+const WATERMARKS_FILE: &str = "compaction.json";
+/// Coinbase returns at most this many candles per request.
+pub const MAX_CANDLES_PER_REQUEST: i64 = 300;
+/// Pause between paginated requests to stay well under the public rate limit.
+const REQUEST_INTERVAL: std::time::Duration = std::time::Duration::from_millis(350);
+const MAX_RETRIES: u32 = 5;
+
+/// What one compaction rolled up, over every series.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Compaction {
+    pub series: usize,
+    /// Intraday candles removed from the store, and archived when an archive was given.
+    pub rolled: usize,
+    /// Daily candles they were rolled into.
+    pub days: usize,
+}
+
+/// How far compaction rolled one series up: its intraday candles opening before `before`
+/// were moved to `archive`, or dropped when it kept none.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Watermark {
+    pub before: f64,
+    pub archive: Option<PathBuf>,
+}
+
+impl Watermark {
+    /// The archive and how far it holds the series, when there is one.
+    fn archived(&self) -> Option<(CandleStore, DateTime<Utc>)> {
+        let archive = CandleStore::new(self.archive.clone()?);
+        Some((archive, DateTime::from_timestamp(self.before as i64, 0)?))
+    }
+}
+
+/// Daily candles aggregating chronological intraday `candles` by UTC day: the day's first
+/// open, highest high, lowest low, last close and total volume.
+pub fn daily_candles(candles: &[[f64; 6]]) -> Vec<[f64; 6]> {
+    let day = DAILY as f64;
+    let mut days: Vec<[f64; 6]> = Vec::new();
+    for c in candles {
+        let start = (c[0] / day).floor() * day;
+        match days.last_mut() {
+            Some(d) if d[0] == start => {
+                d[2] = d[2].max(c[2]);
+                d[3] = d[3].min(c[3]);
+                d[4] = c[4];
+                d[5] += c[5];
+            }
+            _ => days.push([start, c[1], c[2], c[3], c[4], c[5]]),
+        }
+    }
+    days
+}
+
+/// Size, range and freshness of one stored series.
+#[derive(Debug, Clone)]
+pub struct SeriesSummary {
+    pub symbol: String,
+    pub granularity: u32,
+    pub candles: usize,
+    pub first: Option<f64>,
+    pub last: Option<f64>,
+    pub bytes: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// On-disk candle history, one zstd-compressed JSON Lines file per symbol and granularity.
+/// Each line is a `[time, open, high, low, close, volume]` array, in chronological order.
+#[derive(Debug, Clone)]
+pub struct CandleStore {
+    dir: PathBuf,
+}
+
+impl Default for CandleStore {
+    fn default() -> Self {
+        Self::new(STORE_DIR)
+    }
+}
+
+impl CandleStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, symbol: &str, granularity: u32) -> PathBuf {
+        self.dir.join(format!("{}_{}.jsonl", symbol, granularity))
+    }
+
+    /// Lists the `(symbol, granularity)` series present in the store.
+    pub fn series(&self) -> Result<Vec<(String, u32)>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut series = Vec::new();
+        for entry in fs::read_dir(&self.dir).context("Failed to read candle store")? {
+            let path = entry?.path();
+            let Some(stem) = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| compression::logical_name(n).strip_suffix(".jsonl"))
+            else {
+                continue;
+            };
+            if let Some((symbol, granularity)) = stem.rsplit_once('_') {
+                if let Ok(granularity) = granularity.parse() {
+                    series.push((symbol.to_string(), granularity));
+                }
+            }
+        }
+
+        series.sort();
+        series.dedup();
+        Ok(series)
+    }
+
+    /// Loads the full series in chronological order. A series stored out of order or with
+    /// repeated timestamps is repaired, and written back so later reads stream it in order.
+    pub fn load(&self, symbol: &str, granularity: u32) -> Result<Vec<[f64; 6]>> {
+        let mut candles = self.load_stored(symbol, granularity)?;
+        let repair = repair_order(&mut candles);
+        if !repair.is_clean() {
+            tracing::warn!(
+                symbol,
+                granularity,
+                ?repair,
+                "Repaired the order of stored candles"
+            );
+            self.write(symbol, granularity, &candles)?;
+        }
+        Ok(candles)
+    }
+
+    /// Loads the full stored series in its stored order, as written.
+    pub fn load_stored(&self, symbol: &str, granularity: u32) -> Result<Vec<[f64; 6]>> {
+        let path = self.path(symbol, granularity);
+        let Some(bytes) = compression::read(&path)? else {
+            return Ok(Vec::new());
+        };
+
+        bytes
+            .lines()
+            .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty()))
+            .map(|line| {
+                let line = line?;
+                serde_json::from_str(&line)
+                    .with_context(|| format!("Corrupt candle line in {}", path.display()))
+            })
+            .collect()
+    }
+
+    /// Loads the stored candles whose open time falls within `[start, end)`, reading the
+    /// part before the series' [watermark](Self::watermark) from the archive compaction
+    /// moved it to.
+    pub fn load_range(
+        &self,
+        symbol: &str,
+        granularity: u32,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<[f64; 6]>> {
+        let archived = match self
+            .watermark(symbol, granularity)?
+            .and_then(|w| w.archived())
+        {
+            Some((archive, before)) if start < before => {
+                archive.load_range(symbol, granularity, start, end.min(before))?
+            }
+            _ => Vec::new(),
+        };
+        let (start, end) = (start.timestamp() as f64, end.timestamp() as f64);
+        let mut candles = self.load(symbol, granularity)?;
+        candles.retain(|c| c[0] >= start && c[0] < end);
+        if !archived.is_empty() {
+            // The stable sort keeps the store's copy of any candle also archived
+            candles.extend(archived);
+            candles.sort_by(|a, b| a[0].total_cmp(&b[0]));
+            candles.dedup_by(|later, earlier| later[0] == earlier[0]);
+        }
+        Ok(candles)
+    }
+
+    /// Streams the stored candles whose open time falls within `[start, end)` without
+    /// loading the whole series, the archived ones first as in
+    /// [`load_range`](Self::load_range). Stops reading once past `end`, and fails on a step
+    /// back in time, which [`load`](Self::load) would repair.
+    pub fn iter_range(
+        &self,
+        symbol: &str,
+        granularity: u32,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<impl Iterator<Item = Result<[f64; 6]>>> {
+        let (archived, start) = match self
+            .watermark(symbol, granularity)?
+            .and_then(|w| w.archived())
+        {
+            Some((archive, before)) if start < before => (
+                Some(archive.iter_stored(symbol, granularity, start, end.min(before))?),
+                before,
+            ),
+            _ => (None, start),
+        };
+        let stored = self.iter_stored(symbol, granularity, start, end)?;
+        Ok(archived.into_iter().flatten().chain(stored))
+    }
+
+    /// [`iter_range`](Self::iter_range) over this store's own file only.
+    fn iter_stored(
+        &self,
+        symbol: &str,
+        granularity: u32,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<impl Iterator<Item = Result<[f64; 6]>>> {
+        let path = self.path(symbol, granularity);
+        let (start, end) = (start.timestamp() as f64, end.timestamp() as f64);
+        let lines = compression::reader(&path)?.map(|reader| reader.lines());
+
+        let mut previous = f64::NEG_INFINITY;
+        Ok(lines
+            .into_iter()
+            .flatten()
+            .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty()))
+            .map(move |line| -> Result<[f64; 6]> {
+                let candle: [f64; 6] = serde_json::from_str(&line?)
+                    .with_context(|| format!("Corrupt candle line in {}", path.display()))?;
+                anyhow::ensure!(
+                    candle[0] > previous,
+                    "Candles in {} are out of order at {}; loading the series repairs it",
+                    path.display(),
+                    candle[0]
+                );
+                previous = candle[0];
+                Ok(candle)
+            })
+            .filter(move |c| !matches!(c, Ok(c) if c[0] < start))
+            .take_while(move |c| !matches!(c, Ok(c) if c[0] >= end)))
+    }
+
+    /// Merges `candles` into the stored series (newer data wins on duplicate timestamps)
+    /// and returns how many previously unknown candles were added.
+    pub fn merge(&self, symbol: &str, granularity: u32, candles: &[[f64; 6]]) -> Result<usize> {
+        let mut all = self.load(symbol, granularity)?;
+        let before = all.len();
+
+        // Put new candles first so the stable dedup keeps them over stale copies
+        let mut merged = candles.to_vec();
+        merged.append(&mut all);
+        merged.sort_by(|a, b| a[0].total_cmp(&b[0]));
+        merged.dedup_by(|later, earlier| later[0] == earlier[0]);
+
+        self.write(symbol, granularity, &merged)?;
+        Ok(merged.len().saturating_sub(before))
+    }
+
+    /// Replaces the stored series.
+    fn write(&self, symbol: &str, granularity: u32, candles: &[[f64; 6]]) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let mut buf = Vec::new();
+        for c in candles {
+            serde_json::to_writer(&mut buf, c)?;
+            buf.write_all(b"\n")?;
+        }
+        compression::write(&self.path(symbol, granularity), &buf)
+    }
+
+    pub fn summaries(&self) -> Result<Vec<SeriesSummary>> {
+        self.series()?
+            .into_iter()
+            .map(|(symbol, granularity)| {
+                let meta = compression::metadata(&self.path(&symbol, granularity))?;
+                let candles = self.load(&symbol, granularity)?;
+                Ok(SeriesSummary {
+                    candles: candles.len(),
+                    first: candles.first().map(|c| c[0]),
+                    last: candles.last().map(|c| c[0]),
+                    bytes: meta.len(),
+                    modified: meta.modified().ok(),
+                    symbol,
+                    granularity,
+                })
+            })
+            .collect()
+    }
+
+    /// Drops candles older than `cutoff` from every series and returns how many were removed.
+    pub fn prune_before(&self, cutoff: DateTime<Utc>) -> Result<usize> {
+        let cutoff = cutoff.timestamp() as f64;
+        let mut removed = 0;
+        for (symbol, granularity) in self.series()? {
+            let mut candles = self.load(&symbol, granularity)?;
+            let before = candles.len();
+            candles.retain(|c| c[0] >= cutoff);
+            if candles.len() < before {
+                removed += before - candles.len();
+                self.write(&symbol, granularity, &candles)?;
+            }
+        }
+        Ok(removed)
+    }
+
+    fn watermarks(&self) -> Result<BTreeMap<String, Watermark>> {
+        let path = self.dir.join(WATERMARKS_FILE);
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        serde_json::from_str(&fs::read_to_string(&path)?)
+            .with_context(|| format!("Invalid compaction watermarks in {}", path.display()))
+    }
+
+    /// How far compaction rolled the series up; `None` when it never touched it.
+    pub fn watermark(&self, symbol: &str, granularity: u32) -> Result<Option<Watermark>> {
+        Ok(self
+            .watermarks()?
+            .remove(&format!("{}_{}", symbol, granularity)))
+    }
+
+    fn set_watermark(&self, symbol: &str, granularity: u32, watermark: Watermark) -> Result<()> {
+        let mut watermarks = self.watermarks()?;
+        watermarks.insert(format!("{}_{}", symbol, granularity), watermark);
+        let path = self.dir.join(WATERMARKS_FILE);
+        let partial = path.with_extension("json.partial");
+        fs::write(&partial, serde_json::to_string_pretty(&watermarks)?)?;
+        fs::rename(&partial, path).context("Failed to write compaction watermarks")
+    }
+
+    /// Rolls every intraday series' candles from whole UTC days before `cutoff` into the
+    /// symbol's daily series, keeping the store small; days the daily series already holds,
+    /// such as the exchange's own, are kept as they are. The candles rolled up are first
+    /// merged into `archive`, if given, so no history is lost, and the series' watermark
+    /// records where they went.
+    pub fn compact(
+        &self,
+        cutoff: DateTime<Utc>,
+        archive: Option<&CandleStore>,
+    ) -> Result<Compaction> {
+        let cutoff = cutoff.timestamp().div_euclid(DAILY as i64) as f64 * DAILY as f64;
+        let mut compaction = Compaction::default();
+        for (symbol, granularity) in self.series()? {
+            if granularity >= DAILY {
+                continue;
+            }
+            let mut candles = self.load(&symbol, granularity)?;
+            let kept = candles.partition_point(|c| c[0] < cutoff);
+            if kept == 0 {
+                continue;
+            }
+            let old = candles.drain(..kept).collect::<Vec<_>>();
+            if let Some(archive) = archive {
+                archive.merge(&symbol, granularity, &old)?;
+            }
+            let stored = self.load(&symbol, DAILY)?;
+            let mut days = daily_candles(&old);
+            days.retain(|d| stored.binary_search_by(|c| c[0].total_cmp(&d[0])).is_err());
+            let days = self.merge(&symbol, DAILY, &days)?;
+            let previous = self.watermark(&symbol, granularity)?;
+            self.set_watermark(
+                &symbol,
+                granularity,
+                Watermark {
+                    before: previous.as_ref().map_or(cutoff, |w| w.before.max(cutoff)),
+                    archive: archive
+                        .map(|a| a.dir().to_path_buf())
+                        .or(previous.and_then(|w| w.archive)),
+                },
+            )?;
+            self.write(&symbol, granularity, &candles)?;
+            tracing::info!(%symbol, granularity, rolled = old.len(), days, "Compacted candles");
+            compaction.series += 1;
+            compaction.rolled += old.len();
+            compaction.days += days;
+        }
+        Ok(compaction)
+    }
+
+    /// Deletes every stored series and returns how many were removed.
+    pub fn clear(&self) -> Result<usize> {
+        let series = self.series()?;
+        for (symbol, granularity) in &series {
+            compression::remove(&self.path(symbol, *granularity))?;
+        }
+        Ok(series.len())
+    }
+
+    /// Whether the store already holds a candle at both ends of `[start, end)`.
+    pub fn covers(
+        &self,
+        symbol: &str,
+        granularity: u32,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<bool> {
+        let candles = self.load_range(symbol, granularity, start, end)?;
+        Ok(covers(&candles, granularity, start, end))
+    }
+
+    /// Paginates through the exchange API to fill `[from, to)`, merging each page as it
+    /// arrives so an interrupted fetch resumes where it stopped. Pages the store already
+    /// holds in full are skipped, and so is the part before the series'
+    /// [watermark](Self::watermark), which fills the archive instead so compaction isn't
+    /// undone. Returns the number of new candles stored.
+    pub async fn fetch_history(
+        &self,
+        symbol: &str,
+        granularity: u32,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<usize> {
+        let Some(watermark) = self.watermark(symbol, granularity)? else {
+            return self.fetch_pages(symbol, granularity, from, to).await;
+        };
+        let before = DateTime::from_timestamp(watermark.before as i64, 0).unwrap_or(from);
+        let mut added = 0;
+        if from < before {
+            match watermark.archived() {
+                Some((archive, _)) => {
+                    added += archive
+                        .fetch_pages(symbol, granularity, from, to.min(before))
+                        .await?;
+                }
+                None => tracing::warn!(
+                    symbol,
+                    %before,
+                    "Compaction dropped these candles without an archive; not refetching them"
+                ),
+            }
+        }
+        Ok(added
+            + self
+                .fetch_pages(symbol, granularity, from.max(before), to)
+                .await?)
+    }
+
+    /// [`fetch_history`](Self::fetch_history) into this store's own file only.
+    async fn fetch_pages(
+        &self,
+        symbol: &str,
+        granularity: u32,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<usize> {
+        let page = Duration::seconds(granularity as i64 * MAX_CANDLES_PER_REQUEST);
+        let stored = self.load_range(symbol, granularity, from, to)?;
+
+        let mut added = 0;
+        let mut page_start = from;
+        while page_start < to {
+            let page_end = (page_start + page).min(to);
+            let expected = (page_end - page_start).num_seconds() / granularity as i64;
+            let (lo, hi) = (page_start.timestamp() as f64, page_end.timestamp() as f64);
+            let have = stored.iter().filter(|c| c[0] >= lo && c[0] < hi).count() as i64;
+
+            if have < expected {
+                let candles =
+                    fetch_page_with_retry(symbol, granularity, page_start, page_end).await?;
+                let new = self.merge(symbol, granularity, &candles_to_array(candles))?;
+                added += new;
+                tracing::info!(symbol, %page_start, %page_end, new, "Fetched candle page");
+                tokio::time::sleep(REQUEST_INTERVAL).await;
+            }
+
+            page_start = page_end;
+        }
+
+        Ok(added)
+    }
+}
+
+/// Hourly series kept beside the candles, such as liquidations or implied volatility: one
+/// zstd-compressed JSON Lines file per symbol, each line a `[time, ..]` array of `N`
+/// values, in chronological order.
+#[derive(Debug, Clone)]
+pub struct RowStore<const N: usize> {
+    dir: PathBuf,
+}
+
+impl<const N: usize> RowStore<N> {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, symbol: &str) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", symbol))
+    }
+
+    /// The stored rows of `symbol`, oldest first; empty when none were stored.
+    pub fn load(&self, symbol: &str) -> Result<Vec<[f64; N]>> {
+        let path = self.path(symbol);
+        let Some(bytes) = compression::read(&path)? else {
+            return Ok(Vec::new());
+        };
+        bytes
+            .lines()
+            .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty()))
+            .map(|line| {
+                let row: Vec<f64> = serde_json::from_str(&line?)
+                    .with_context(|| format!("Corrupt line in {}", path.display()))?;
+                row.try_into().map_err(|row: Vec<f64>| {
+                    anyhow::anyhow!(
+                        "Line of {} values in {}, expected {}",
+                        row.len(),
+                        path.display(),
+                        N
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Merges `rows` into the stored series (newer data wins on duplicate timestamps) and
+    /// returns how many previously unknown rows were added.
+    pub fn merge(&self, symbol: &str, rows: &[[f64; N]]) -> Result<usize> {
+        let mut all = self.load(symbol)?;
+        let before = all.len();
+
+        let mut merged = rows.to_vec();
+        merged.append(&mut all);
+        merged.sort_by(|a, b| a[0].total_cmp(&b[0]));
+        merged.dedup_by(|later, earlier| later[0] == earlier[0]);
+
+        fs::create_dir_all(&self.dir)?;
+        let mut buf = Vec::new();
+        for row in &merged {
+            serde_json::to_writer(&mut buf, &row[..])?;
+            buf.write_all(b"\n")?;
+        }
+        compression::write(&self.path(symbol), &buf)?;
+        Ok(merged.len().saturating_sub(before))
+    }
+}
+
+/// Whether chronological `candles` reach both ends of `[start, end)`, allowing for the
+/// still-forming latest candle.
+pub fn covers(
+    candles: &[[f64; 6]],
+    granularity: u32,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> bool {
+    let g = granularity as f64;
+    match (candles.first(), candles.last()) {
+        (Some(first), Some(last)) => {
+            first[0] < start.timestamp() as f64 + g && last[0] >= end.timestamp() as f64 - g * 2.0
+        }
+        _ => false,
+    }
+}
+
+/// Fetches `[from, to)` from the exchange page by page, without touching any store.
+pub async fn fetch_range(
+    symbol: &str,
+    granularity: u32,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<[f64; 6]>> {
+    let page = Duration::seconds(granularity as i64 * MAX_CANDLES_PER_REQUEST);
+    let mut candles = Vec::new();
+    let mut page_start = from;
+    while page_start < to {
+        let page_end = (page_start + page).min(to);
+        let fetched = fetch_page_with_retry(symbol, granularity, page_start, page_end).await?;
+        candles.extend(candles_to_array(fetched));
+        page_start = page_end;
+        if page_start < to {
+            tokio::time::sleep(REQUEST_INTERVAL).await;
+        }
+    }
+
+    candles.sort_by(|a, b| a[0].total_cmp(&b[0]));
+    candles.dedup_by(|later, earlier| later[0] == earlier[0]);
+    Ok(candles)
+}
+
+async fn fetch_page_with_retry(
+    symbol: &str,
+    granularity: u32,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<crate::CoinbaseCandle>> {
+    let mut attempt = 0;
+    loop {
+        match get_candle_data(symbol, start, end, granularity).await {
+            Ok(candles) => return Ok(candles),
+            Err(e) if attempt < MAX_RETRIES => {
+                attempt += 1;
+                let backoff = REQUEST_INTERVAL * 2u32.pow(attempt);
+                tracing::warn!(error = ?e, attempt, ?backoff, "Candle request failed, retrying");
+                tokio::time::sleep(backoff).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(name: &str) -> CandleStore {
+        let dir = std::env::temp_dir().join(format!("happycharts_store_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        CandleStore::new(dir)
+    }
+
+    #[test]
+    fn test_merge_dedupes_and_sorts() {
+        let store = temp_store("merge");
+        let a = [
+            [7200.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+            [3600.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+        ];
+        assert_eq!(store.merge("ETH", 3600, &a).unwrap(), 2);
+
+        let b = [
+            [7200.0, 2.0, 2.0, 2.0, 2.0, 2.0],
+            [10800.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+        ];
+        assert_eq!(store.merge("ETH", 3600, &b).unwrap(), 1);
+
+        let loaded = store.load("ETH", 3600).unwrap();
+        let times = loaded.iter().map(|c| c[0]).collect::<Vec<_>>();
+        assert_eq!(times, vec![3600.0, 7200.0, 10800.0]);
+        assert_eq!(loaded[1][4], 2.0);
+        assert_eq!(store.series().unwrap(), vec![("ETH".to_string(), 3600)]);
+    }
+
+    #[test]
+    fn test_load_repairs_order() {
+        let store = temp_store("repair");
+        let newest_first = [
+            [10800.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+            [7200.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+            [7200.0, 2.0, 2.0, 2.0, 2.0, 2.0],
+            [3600.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+        ];
+        store.write("ETH", 3600, &newest_first).unwrap();
+        let start = DateTime::from_timestamp(0, 0).unwrap();
+        let end = start + Duration::hours(4);
+        assert!(store
+            .iter_range("ETH", 3600, start, end)
+            .unwrap()
+            .any(|c| c.is_err()));
+
+        let times = |candles: Vec<[f64; 6]>| candles.iter().map(|c| c[0]).collect::<Vec<_>>();
+        let loaded = store.load("ETH", 3600).unwrap();
+        assert_eq!(times(loaded), vec![3600.0, 7200.0, 10800.0]);
+        // Written back repaired
+        assert_eq!(store.load_stored("ETH", 3600).unwrap().len(), 3);
+        let streamed = store
+            .iter_range("ETH", 3600, start, end)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(times(streamed), vec![3600.0, 7200.0, 10800.0]);
+    }
+
+    #[test]
+    fn test_covers_range() {
+        let store = temp_store("covers");
+        let candles = (0..24)
+            .map(|i| [i as f64 * 3600.0, 1.0, 1.0, 1.0, 1.0, 1.0])
+            .collect::<Vec<_>>();
+        store.merge("BTC", 3600, &candles).unwrap();
+
+        let start = DateTime::from_timestamp(0, 0).unwrap();
+        assert!(store
+            .covers("BTC", 3600, start, start + Duration::hours(24))
+            .unwrap());
+        assert!(!store
+            .covers("BTC", 3600, start, start + Duration::hours(48))
+            .unwrap());
+        assert!(!store
+            .covers("SOL", 3600, start, start + Duration::hours(24))
+            .unwrap());
+    }
+
+    #[test]
+    fn test_prune_and_clear() {
+        let store = temp_store("prune");
+        let candles = (0..10)
+            .map(|i| [i as f64 * 3600.0, 1.0, 1.0, 1.0, 1.0, 1.0])
+            .collect::<Vec<_>>();
+        store.merge("SOL", 3600, &candles).unwrap();
+
+        let cutoff = DateTime::from_timestamp(4 * 3600, 0).unwrap();
+        assert_eq!(store.prune_before(cutoff).unwrap(), 4);
+        let summaries = store.summaries().unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].candles, 6);
+        assert_eq!(summaries[0].first, Some(4.0 * 3600.0));
+
+        assert_eq!(store.clear().unwrap(), 1);
+        assert!(store.series().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_compact_rolls_whole_days_into_the_archive() {
+        let store = temp_store("compact");
+        let archive = temp_store("compact_archive");
+        // Two and a half days of hourly candles rising by one each hour
+        let candles = (0..60)
+            .map(|i| {
+                let p = i as f64;
+                [p * 3600.0, p, p + 0.5, p - 0.5, p + 0.25, 1.0]
+            })
+            .collect::<Vec<_>>();
+        store.merge("ETH", 3600, &candles).unwrap();
+        // The exchange's own candle of the second day
+        let exchange_day = [86400.0, 24.0, 48.0, 23.0, 47.0, 30.0];
+        store.merge("ETH", DAILY, &[exchange_day]).unwrap();
+
+        // Mid-way through the third day: only the first two are whole
+        let cutoff = DateTime::from_timestamp(50 * 3600, 0).unwrap();
+        let compaction = store.compact(cutoff, Some(&archive)).unwrap();
+        assert_eq!(
+            compaction,
+            Compaction {
+                series: 1,
+                rolled: 48,
+                days: 1
+            }
+        );
+        assert_eq!(store.load("ETH", 3600).unwrap(), candles[48..]);
+        assert_eq!(
+            store.load("ETH", DAILY).unwrap(),
+            vec![[0.0, 0.0, 23.5, -0.5, 23.25, 24.0], exchange_day]
+        );
+        assert_eq!(archive.load("ETH", 3600).unwrap(), candles[..48]);
+        assert_eq!(
+            store.watermark("ETH", 3600).unwrap(),
+            Some(Watermark {
+                before: 48.0 * 3600.0,
+                archive: Some(archive.dir().to_path_buf())
+            })
+        );
+
+        // Ranges reaching before the watermark are read through the archive
+        let start = DateTime::from_timestamp(40 * 3600, 0).unwrap();
+        let end = start + Duration::hours(12);
+        assert_eq!(
+            store.load_range("ETH", 3600, start, end).unwrap(),
+            candles[40..52]
+        );
+        let streamed = store
+            .iter_range("ETH", 3600, start, end)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(streamed, candles[40..52]);
+        assert!(store.covers("ETH", 3600, start, end).unwrap());
+
+        // Nothing left before the cutoff
+        assert_eq!(store.compact(cutoff, None).unwrap(), Compaction::default());
+    }
+}
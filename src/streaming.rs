@@ -0,0 +1,355 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use anyhow::Result;
+#[cfg(feature = "native")]
+use chrono::{DateTime, Utc};
+
+use crate::calibration::CalibrationTracker;
+use crate::compute::label_candles_with;
+use crate::latency::{self, LatencyStats};
+use crate::simulator::{SimulationSummary, Trade};
+#[cfg(feature = "native")]
+use crate::store::CandleStore;
+use crate::Action;
+
+const TIME: usize = 0;
+
+/// A rolling window of aligned candles plus the candle that follows it.
+#[derive(Debug, Clone)]
+pub struct Window {
+    /// Per-symbol candles, in the order the symbols were given.
+    pub series: Vec<Vec<[f64; 6]>>,
+    /// The candle after the window for the first (traded) symbol.
+    pub next: [f64; 6],
+}
+
+impl Window {
+    /// The last candle of the traded symbol, where predictions are entered.
+    pub fn last(&self) -> [f64; 6] {
+        *self.series[0].last().expect("windows are never empty")
+    }
+
+    /// Ground-truth label of the window's last candle with the traded symbol's long and
+    /// short `thresholds`.
+    pub fn label(&self, (long, short): (f64, f64)) -> Action {
+        label_candles_with(&[self.last(), self.next], long, short)[0]
+    }
+
+    /// The pair `[last, next]`, suitable for `next_candle_trade(.., 0, ..)`.
+    pub fn trade_candles(&self) -> [[f64; 6]; 2] {
+        [self.last(), self.next]
+    }
+}
+
+/// Joins chronological candle streams on open time, yielding one row per timestamp that
+/// every stream has. Candles missing from any stream are skipped.
+pub struct Aligned<I> {
+    streams: Vec<I>,
+}
+
+impl<I: Iterator<Item = Result<[f64; 6]>>> Aligned<I> {
+    pub fn new(streams: Vec<I>) -> Self {
+        Self { streams }
+    }
+}
+
+impl<I: Iterator<Item = Result<[f64; 6]>>> Iterator for Aligned<I> {
+    type Item = Result<Vec<[f64; 6]>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut row = Vec::with_capacity(self.streams.len());
+        for s in self.streams.iter_mut() {
+            match s.next()? {
+                Ok(c) => row.push(c),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        loop {
+            let latest = row.iter().map(|c| c[TIME]).fold(f64::MIN, f64::max);
+            if row.iter().all(|c| c[TIME] == latest) {
+                return Some(Ok(row));
+            }
+            // Advance every stream that lags behind the latest timestamp
+            for (slot, s) in row.iter_mut().zip(self.streams.iter_mut()) {
+                while slot[TIME] < latest {
+                    match s.next()? {
+                        Ok(c) => *slot = c,
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Slides a window of `len` aligned rows over a row stream, `stride` rows at a time,
+/// holding only `len + 1` rows in memory at a time.
+pub struct Windows<I> {
+    rows: I,
+    len: usize,
+    stride: usize,
+    emitted: bool,
+    buf: VecDeque<Vec<[f64; 6]>>,
+}
+
+impl<I: Iterator<Item = Result<Vec<[f64; 6]>>>> Windows<I> {
+    pub fn new(rows: I, len: usize) -> Self {
+        Self {
+            rows,
+            len,
+            stride: 1,
+            emitted: false,
+            buf: VecDeque::with_capacity(len + 1),
+        }
+    }
+
+    /// Advances `stride` rows between windows instead of one; a stride of `len` gives
+    /// non-overlapping windows.
+    pub fn with_stride(mut self, stride: usize) -> Self {
+        self.stride = stride.max(1);
+        self
+    }
+}
+
+impl<I: Iterator<Item = Result<Vec<[f64; 6]>>>> Iterator for Windows<I> {
+    type Item = Result<Window>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.emitted {
+            // A stride past the buffered rows skips rows that were never buffered
+            for _ in 0..self.stride {
+                if self.buf.pop_front().is_none() {
+                    if let Err(e) = self.rows.next()? {
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+        while self.buf.len() <= self.len {
+            match self.rows.next()? {
+                Ok(row) => self.buf.push_back(row),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        self.emitted = true;
+        let symbols = self.buf[0].len();
+        let series = (0..symbols)
+            .map(|s| self.buf.iter().take(self.len).map(|row| row[s]).collect())
+            .collect();
+        Some(Ok(Window {
+            series,
+            next: self.buf[self.len][0],
+        }))
+    }
+}
+
+/// Streams `len`-candle windows for `symbols` from the store over `[start, end)`, one
+/// every `stride` candles. The first symbol is the traded one. Memory use is bounded by
+/// the window length regardless of the range.
+#[cfg(feature = "native")]
+pub fn stream_windows(
+    store: &CandleStore,
+    symbols: &[&str],
+    granularity: u32,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    len: usize,
+    stride: usize,
+) -> Result<impl Iterator<Item = Result<Window>>> {
+    let streams = symbols
+        .iter()
+        .map(|s| store.iter_range(s, granularity, start, end))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Windows::new(Aligned::new(streams), len).with_stride(stride))
+}
+
+/// Rough number of independent samples among `windows` windows of `len` candles taken
+/// every `stride` candles: overlapping windows share most of their data, so they count
+/// only for the span they cover, in units of one window.
+pub fn effective_samples(windows: usize, stride: usize, len: usize) -> f64 {
+    if windows == 0 || stride >= len {
+        return windows as f64;
+    }
+    ((windows - 1) * stride + len) as f64 / len as f64
+}
+
+/// Accuracy and PnL accumulated one window at a time, so no per-window results need to be
+/// kept. Trades must be recorded in chronological order for the drawdown to be correct.
+#[derive(Debug, Clone)]
+pub struct RunningMetrics {
+    pub windows: usize,
+    pub correct: usize,
+    pub baseline_correct: usize,
+    pub calibration: CalibrationTracker,
+    pub latency: LatencyStats,
+    /// Wall-clock duration of the run, set by whoever drives it.
+    pub elapsed: Duration,
+    /// See [`effective_samples`]; set by whoever drives the run.
+    pub effective_samples: f64,
+    trades: usize,
+    wins: usize,
+    equity: f64,
+    peak: f64,
+    max_drawdown: f64,
+    liquidations: usize,
+}
+
+impl Default for RunningMetrics {
+    fn default() -> Self {
+        Self {
+            windows: 0,
+            correct: 0,
+            baseline_correct: 0,
+            calibration: CalibrationTracker::default(),
+            latency: LatencyStats::default(),
+            elapsed: Duration::ZERO,
+            effective_samples: 0.0,
+            trades: 0,
+            wins: 0,
+            equity: 1.0,
+            peak: 1.0,
+            max_drawdown: 0.0,
+            liquidations: 0,
+        }
+    }
+}
+
+impl RunningMetrics {
+    pub fn record(&mut self, prediction: Action, baseline: Action, label: Action) {
+        self.windows += 1;
+        if prediction == label {
+            self.correct += 1;
+        }
+        if baseline == label {
+            self.baseline_correct += 1;
+        }
+    }
+
+    pub fn record_trade(&mut self, trade: &Trade) {
+        self.trades += 1;
+        if trade.return_pct > 0.0 {
+            self.wins += 1;
+        }
+        self.liquidations += trade.is_liquidation() as usize;
+        self.equity *= 1.0 + trade.return_pct;
+        self.peak = self.peak.max(self.equity);
+        self.max_drawdown = self.max_drawdown.max(1.0 - self.equity / self.peak);
+    }
+
+    pub fn accuracy(&self) -> f64 {
+        ratio(self.correct, self.windows)
+    }
+
+    pub fn baseline_accuracy(&self) -> f64 {
+        ratio(self.baseline_correct, self.windows)
+    }
+
+    /// Windows scored per second over [`elapsed`](Self::elapsed).
+    pub fn throughput(&self) -> f64 {
+        latency::throughput(self.windows, self.elapsed)
+    }
+
+    /// Same figures `simulator::summarize` computes over the full trade list.
+    pub fn simulation(&self) -> SimulationSummary {
+        SimulationSummary {
+            trades: self.trades,
+            total_return: self.equity - 1.0,
+            win_rate: ratio(self.wins, self.trades),
+            max_drawdown: self.max_drawdown,
+            liquidations: self.liquidations,
+        }
+    }
+}
+
+fn ratio(n: usize, d: usize) -> f64 {
+    if d == 0 {
+        0.0
+    } else {
+        n as f64 / d as f64
+    }
+}
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+    use crate::compute::{LONG_THRESHOLD, SHORT_THRESHOLD};
+    use crate::simulator::{next_candle_trade, summarize};
+
+    fn candle(hour: usize, close: f64) -> [f64; 6] {
+        [hour as f64 * 3600.0, close, close, close, close, 1.0]
+    }
+
+    #[test]
+    fn test_stream_windows_aligns_and_matches_batch_metrics() {
+        let dir = std::env::temp_dir().join("happycharts_streaming");
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = CandleStore::new(&dir);
+
+        let closes = [100.0, 106.0, 100.0, 94.0, 95.0, 101.0, 100.0, 100.0];
+        let eth = closes
+            .iter()
+            .enumerate()
+            .map(|(h, c)| candle(h, *c))
+            .collect::<Vec<_>>();
+        // BTC is missing hour 3, so no window may contain it
+        let btc = (0..8)
+            .filter(|h| *h != 3)
+            .map(|h| candle(h, 1.0))
+            .collect::<Vec<_>>();
+        store.merge("ETH", 3600, &eth).unwrap();
+        store.merge("BTC", 3600, &btc).unwrap();
+
+        let start = DateTime::from_timestamp(0, 0).unwrap();
+        let end = start + chrono::Duration::hours(8);
+        let windows = stream_windows(&store, &["ETH", "BTC"], 3600, start, end, 2, 1)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+
+        // Aligned hours: 0,1,2,4,5,6,7 -> 5 windows of 2 with a following candle
+        assert_eq!(windows.len(), 5);
+        assert_eq!(windows[0].series[1].len(), 2);
+        assert_eq!(windows[1].next[TIME], 4.0 * 3600.0);
+        assert!(windows
+            .iter()
+            .all(|w| w.series[0][1][TIME] == w.series[1][1][TIME]));
+
+        let mut metrics = RunningMetrics::default();
+        let mut trades = Vec::new();
+        for w in &windows {
+            let label = w.label((LONG_THRESHOLD, SHORT_THRESHOLD));
+            metrics.record(Action::Long, Action::None, label);
+            if let Some(t) = next_candle_trade(&w.trade_candles(), 0, Action::Long, 0.0) {
+                metrics.record_trade(&t);
+                trades.push(t);
+            }
+        }
+        assert_eq!(
+            windows[0].label((LONG_THRESHOLD, SHORT_THRESHOLD)),
+            Action::Short
+        );
+        assert_eq!(metrics.windows, 5);
+        assert_eq!(metrics.simulation(), summarize(&trades));
+
+        // Non-overlapping: windows start at aligned rows 0, 2 and 4 (hours 0, 2 and 5)
+        let disjoint = stream_windows(&store, &["ETH", "BTC"], 3600, start, end, 2, 2)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(disjoint.len(), 3);
+        assert_eq!(disjoint[1].series[0][0][TIME], 2.0 * 3600.0);
+        let sparse = stream_windows(&store, &["ETH", "BTC"], 3600, start, end, 2, 4)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(sparse.len(), 2);
+        assert_eq!(sparse[1].series[0][0][TIME], 5.0 * 3600.0);
+
+        assert_eq!(effective_samples(5, 1, 2), 3.0);
+        assert_eq!(effective_samples(3, 2, 2), 3.0);
+        assert_eq!(effective_samples(0, 1, 24), 0.0);
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::compute::Action;
+use crate::signal_filter::RepeatPolicy;
+
+/// Recipients the daemon posts signals to besides each profile's webhook.
+pub const SUBSCRIPTIONS_FILE: &str = "subscriptions.json";
+
+/// Hours of the day, in UTC, a recipient gets no signals: from `start` up to but not
+/// including `end`, wrapping past midnight when `end` is the smaller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl QuietHours {
+    pub fn contains(&self, hour: u32) -> bool {
+        if self.start <= self.end {
+            (self.start..self.end).contains(&hour)
+        } else {
+            hour >= self.start || hour < self.end
+        }
+    }
+}
+
+/// One recipient of the daemon's signals and which of them they want. A filter left out
+/// lets every signal through.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Subscription {
+    pub name: String,
+    /// URL the wanted signals are POSTed to, in the same JSON as a profile's webhook gets.
+    pub webhook: String,
+    #[serde(default)]
+    pub symbols: Option<Vec<String>>,
+    #[serde(default)]
+    pub actions: Option<Vec<Action>>,
+    /// Signals the model gave less confidence, or none, are left out.
+    #[serde(default)]
+    pub min_confidence: Option<f64>,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    /// Which repeats of the open position are posted; every signal when omitted.
+    #[serde(default)]
+    pub repeat: RepeatPolicy,
+}
+
+impl Subscription {
+    /// Whether a signal of `symbol` is wanted, given at `hour` UTC.
+    pub fn wants(&self, symbol: &str, action: Action, confidence: Option<f64>, hour: u32) -> bool {
+        self.symbols
+            .as_ref()
+            .is_none_or(|s| s.iter().any(|s| s == symbol))
+            && self.actions.as_ref().is_none_or(|a| a.contains(&action))
+            && self
+                .min_confidence
+                .is_none_or(|min| confidence.is_some_and(|c| c >= min))
+            && !self.quiet_hours.is_some_and(|q| q.contains(hour))
+    }
+}
+
+/// Parses a subscriptions file: a JSON array with one subscription per recipient.
+pub fn parse_subscriptions(json: &str) -> Result<Vec<Subscription>> {
+    let subscriptions: Vec<Subscription> = serde_json::from_str(json)?;
+    for (i, s) in subscriptions.iter().enumerate() {
+        anyhow::ensure!(
+            !subscriptions[..i].iter().any(|o| o.name == s.name),
+            "Subscription name {:?} is used twice",
+            s.name
+        );
+        anyhow::ensure!(
+            s.min_confidence.is_none_or(|c| (0.0..=1.0).contains(&c)),
+            "Subscription {:?} needs a min_confidence between 0 and 1",
+            s.name
+        );
+        anyhow::ensure!(
+            s.quiet_hours
+                .is_none_or(|q| q.start < 24 && q.end < 24 && q.start != q.end),
+            "Subscription {:?} needs quiet hours from one hour of the day (0-23) to another",
+            s.name
+        );
+    }
+    Ok(subscriptions)
+}
+
+/// Reads the subscriptions file; none when there is no file.
+#[cfg(feature = "native")]
+pub fn load_subscriptions(path: &std::path::Path) -> Result<Vec<Subscription>> {
+    use anyhow::Context;
+
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let json = std::fs::read_to_string(path)?;
+    parse_subscriptions(&json)
+        .with_context(|| format!("Invalid subscriptions in {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscriptions_filter_signals() {
+        let subscriptions = parse_subscriptions(
+            r#"[
+                {"name": "all", "webhook": "https://example.com/all"},
+                {"name": "eth-longs", "webhook": "https://example.com/eth",
+                 "symbols": ["ETH"], "actions": ["long"], "min_confidence": 0.6,
+                 "quiet_hours": {"start": 22, "end": 7}}
+            ]"#,
+        )
+        .unwrap();
+        let [all, picky] = &subscriptions[..] else {
+            panic!("Expected two subscriptions");
+        };
+        assert!(all.wants("SOL", Action::None, None, 3));
+
+        assert!(picky.wants("ETH", Action::Long, Some(0.7), 12));
+        assert!(!picky.wants("BTC", Action::Long, Some(0.7), 12));
+        assert!(!picky.wants("ETH", Action::Short, Some(0.7), 12));
+        assert!(!picky.wants("ETH", Action::Long, Some(0.5), 12));
+        // Without a confidence the minimum can't be vouched for
+        assert!(!picky.wants("ETH", Action::Long, None, 12));
+        // Quiet from 22:00 through 06:59
+        assert!(!picky.wants("ETH", Action::Long, Some(0.7), 23));
+        assert!(!picky.wants("ETH", Action::Long, Some(0.7), 6));
+        assert!(picky.wants("ETH", Action::Long, Some(0.7), 7));
+
+        let twice = r#"[{"name": "a", "webhook": "x"}, {"name": "a", "webhook": "y"}]"#;
+        assert!(parse_subscriptions(twice).is_err());
+        let hours = r#"[{"name": "a", "webhook": "x", "quiet_hours": {"start": 3, "end": 24}}]"#;
+        assert!(parse_subscriptions(hours).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use std::fmt::Write as _;
+
+use serde::Serialize;
+
+use crate::encoding::CandleEncoding;
+use crate::ReasoningEffort;
+
+/// One reasoning effort and candle encoding's score in a sweep.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SweepPoint {
+    pub reasoning_effort: ReasoningEffort,
+    pub encoding: CandleEncoding,
+    pub accuracy: f64,
+    pub total_return: f64,
+    /// Completion tokens billed for fresh answers, reasoning included; cached answers
+    /// count nothing.
+    pub completion_tokens: u64,
+    pub reasoning_tokens: u64,
+    /// List price in dollars of the fresh answers.
+    pub cost: f64,
+}
+
+/// The sweep as a table, one line per effort and encoding.
+pub fn describe_sweep(points: &[SweepPoint]) -> String {
+    let mut out = format!(
+        "{:<8} {:<8} {:>9} {:>9} {:>12} {:>12} {:>9}\n",
+        "effort", "encoding", "accuracy", "return", "completion", "reasoning", "cost"
+    );
+    for p in points {
+        let _ = writeln!(
+            out,
+            "{:<8} {:<8} {:>8.1}% {:>+8.2}% {:>12} {:>12} {:>9}",
+            p.reasoning_effort.as_str(),
+            p.encoding.as_str(),
+            p.accuracy * 100.0,
+            p.total_return * 100.0,
+            p.completion_tokens,
+            p.reasoning_tokens,
+            format!("${:.2}", p.cost)
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ChatOptions;
+
+    #[test]
+    fn test_sweep_table_and_cache_keys() {
+        let point = SweepPoint {
+            reasoning_effort: ReasoningEffort::High,
+            encoding: CandleEncoding::Returns,
+            accuracy: 0.625,
+            total_return: -0.0125,
+            completion_tokens: 48_000,
+            reasoning_tokens: 40_000,
+            cost: 0.576,
+        };
+        let table = describe_sweep(&[point]);
+        let row = table.lines().nth(1).unwrap();
+        assert_eq!(
+            row,
+            "high     returns      62.5%    -1.25%        48000        40000     $0.58"
+        );
+
+        assert_eq!(
+            "medium".parse::<ReasoningEffort>().unwrap(),
+            ReasoningEffort::Medium
+        );
+        assert!("max".parse::<ReasoningEffort>().is_err());
+
+        // Default options keep the keys of answers cached before efforts existed
+        let prompt = "Analyze\nETH: []";
+        assert_eq!(ChatOptions::default().cache_key(prompt), prompt);
+        let low = ChatOptions {
+            reasoning_effort: Some(ReasoningEffort::Low),
+            ..Default::default()
+        };
+        let high = ChatOptions {
+            reasoning_effort: Some(ReasoningEffort::High),
+            ..Default::default()
+        };
+        assert_ne!(low.cache_key(prompt), prompt);
+        assert_ne!(low.cache_key(prompt), high.cache_key(prompt));
+    }
+}
@@ -0,0 +1,240 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::compute::{label_candles_with, LONG_THRESHOLD, SHORT_THRESHOLD};
+use crate::Action;
+
+/// The labeling config: thresholds calibrated per symbol. Symbols without a calibration are
+/// labeled with [`LONG_THRESHOLD`] and [`SHORT_THRESHOLD`].
+#[cfg(feature = "native")]
+pub const LABELING_FILE: &str = "labeling.json";
+/// Fewest candles a calibration is trusted from: about two weeks of hourly history.
+pub const MIN_CALIBRATION_CANDLES: usize = 336;
+
+/// Shares of candles labeled each way.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LabelMix {
+    pub long: f64,
+    pub short: f64,
+    pub none: f64,
+}
+
+impl LabelMix {
+    /// The mix of `labels`, leaving out the last, which has no next candle to label it.
+    pub fn of(labels: &[Action]) -> Self {
+        let labeled = &labels[..labels.len().saturating_sub(1)];
+        let share = |action| {
+            labeled.iter().filter(|l| **l == action).count() as f64 / labeled.len().max(1) as f64
+        };
+        LabelMix {
+            long: share(Action::Long),
+            short: share(Action::Short),
+            none: share(Action::None),
+        }
+    }
+}
+
+/// Thresholds calibrated on a symbol's history to label it in a desired mix.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThresholdCalibration {
+    pub symbol: String,
+    /// Multipliers of the close the next candle's high or low must reach.
+    pub long_threshold: f64,
+    pub short_threshold: f64,
+    /// The mix asked for, and the mix the thresholds give over the scanned history once
+    /// ties go short.
+    pub target: LabelMix,
+    pub achieved: LabelMix,
+    pub candles: usize,
+    /// Open times of the first and last candle scanned.
+    pub first: f64,
+    pub last: f64,
+    pub calibrated_at: DateTime<Utc>,
+}
+
+impl ThresholdCalibration {
+    pub fn thresholds(&self) -> (f64, f64) {
+        (self.long_threshold, self.short_threshold)
+    }
+}
+
+/// Thresholds labeling about `long` of chronological `candles` long and `short` of them
+/// short: the quantiles of the next candle's low relative to each close, then of its high
+/// among the candles that low leaves unlabeled, as ties go short.
+pub fn calibrate_thresholds(
+    symbol: &str,
+    candles: &[[f64; 6]],
+    long: f64,
+    short: f64,
+) -> Result<ThresholdCalibration> {
+    anyhow::ensure!(
+        long > 0.0 && short > 0.0 && long + short < 1.0,
+        "The long and short shares must be positive and leave some candles unlabeled"
+    );
+    anyhow::ensure!(
+        candles.len() >= MIN_CALIBRATION_CANDLES,
+        "{} has {} candles; calibrating needs at least {}",
+        symbol,
+        candles.len(),
+        MIN_CALIBRATION_CANDLES
+    );
+    let moves = candles
+        .windows(2)
+        .filter(|w| w[0][4] > 0.0)
+        .map(|w| (w[1][2] / w[0][4], w[1][3] / w[0][4]))
+        .collect::<Vec<_>>();
+    // The `share` of all moves counted into `sorted`, or its last value when it runs short
+    let quantile = |sorted: &[f64], share: f64| {
+        let k = (share * moves.len() as f64).round() as usize;
+        sorted.get(k.clamp(1, sorted.len().max(1)) - 1).copied()
+    };
+    let mut downs = moves.iter().map(|(_, down)| *down).collect::<Vec<_>>();
+    downs.sort_by(|a, b| a.total_cmp(b));
+    let short_threshold = quantile(&downs, short).unwrap_or(1.0);
+    let mut ups = moves
+        .iter()
+        .filter(|(_, down)| *down > short_threshold)
+        .map(|(up, _)| *up)
+        .collect::<Vec<_>>();
+    ups.sort_by(|a, b| b.total_cmp(a));
+    let long_threshold = quantile(&ups, long).unwrap_or(1.0);
+    anyhow::ensure!(
+        long_threshold > 1.0 && short_threshold < 1.0,
+        "{} rarely moves enough for {:.0}% long and {:.0}% short labels",
+        symbol,
+        long * 100.0,
+        short * 100.0
+    );
+    Ok(ThresholdCalibration {
+        symbol: symbol.to_string(),
+        long_threshold,
+        short_threshold,
+        target: LabelMix {
+            long,
+            short,
+            none: 1.0 - long - short,
+        },
+        achieved: LabelMix::of(&label_candles_with(
+            candles,
+            long_threshold,
+            short_threshold,
+        )),
+        candles: candles.len(),
+        first: candles[0][0],
+        last: candles[candles.len() - 1][0],
+        calibrated_at: Utc::now(),
+    })
+}
+
+/// The calibrations labels are computed with, one per symbol at most.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LabelingConfig {
+    #[serde(default)]
+    pub calibrations: Vec<ThresholdCalibration>,
+}
+
+impl LabelingConfig {
+    /// The long and short multipliers `symbol` is labeled with.
+    pub fn thresholds(&self, symbol: &str) -> (f64, f64) {
+        self.calibrations
+            .iter()
+            .find(|c| c.symbol == symbol)
+            .map_or((LONG_THRESHOLD, SHORT_THRESHOLD), |c| c.thresholds())
+    }
+
+    /// `symbol`'s chronological `candles` labeled with its thresholds.
+    pub fn label(&self, symbol: &str, candles: &[[f64; 6]]) -> Vec<Action> {
+        let (long, short) = self.thresholds(symbol);
+        label_candles_with(candles, long, short)
+    }
+
+    /// Keeps `calibration`, replacing any earlier one of its symbol.
+    pub fn set(&mut self, calibration: ThresholdCalibration) {
+        self.calibrations.retain(|c| c.symbol != calibration.symbol);
+        self.calibrations.push(calibration);
+    }
+}
+
+/// Reads the labeling config; the defaults when there is no file.
+#[cfg(feature = "native")]
+pub fn load_labeling(path: &std::path::Path) -> Result<LabelingConfig> {
+    use anyhow::Context;
+
+    if !path.exists() {
+        return Ok(LabelingConfig::default());
+    }
+    let json = std::fs::read_to_string(path)?;
+    serde_json::from_str(&json)
+        .with_context(|| format!("Invalid labeling config in {}", path.display()))
+}
+
+/// Writes the labeling config through a temporary file, so a crash mid-write can't leave
+/// backtests a truncated one.
+#[cfg(feature = "native")]
+pub fn save_labeling(path: &std::path::Path, config: &LabelingConfig) -> Result<()> {
+    use anyhow::Context;
+
+    let partial = path.with_extension("json.partial");
+    std::fs::write(&partial, serde_json::to_string_pretty(config)?)?;
+    std::fs::rename(&partial, path).context("Failed to write the labeling config")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calibrate_thresholds_hits_the_mix() {
+        // Closes at 100, highs of 100.1..=110 and lows of 90..=99.9, in different orders
+        let candles = (0..1000)
+            .map(|i| {
+                let (up, down) = (
+                    (i % 100 + 1) as f64 / 10.0,
+                    ((i * 37) % 100 + 1) as f64 / 10.0,
+                );
+                [
+                    i as f64 * 3600.0,
+                    100.0,
+                    100.0 + up,
+                    100.0 - down,
+                    100.0,
+                    1.0,
+                ]
+            })
+            .collect::<Vec<_>>();
+        let calibration = calibrate_thresholds("ETH", &candles, 0.2, 0.2).unwrap();
+        assert!((calibration.short_threshold - 0.919).abs() < 1e-9);
+        // Candles reaching both thresholds are labeled short, so the long threshold is
+        // lower than the highs' own 20% quantile of 1.081
+        assert!(calibration.long_threshold < 1.081);
+        assert!((calibration.achieved.short - 0.2).abs() < 0.01);
+        assert!((calibration.achieved.long - 0.2).abs() < 0.01);
+
+        let mut config = LabelingConfig::default();
+        assert_eq!(config.thresholds("ETH"), (LONG_THRESHOLD, SHORT_THRESHOLD));
+        let thresholds = calibration.thresholds();
+        config.set(calibration.clone());
+        config.set(calibration);
+        assert_eq!(config.calibrations.len(), 1);
+        assert_eq!(config.thresholds("ETH"), thresholds);
+        assert_eq!(config.thresholds("BTC"), (LONG_THRESHOLD, SHORT_THRESHOLD));
+        #[cfg(feature = "native")]
+        {
+            let dir = std::env::temp_dir().join("happycharts_thresholds");
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            let path = dir.join(LABELING_FILE);
+            save_labeling(&path, &config).unwrap();
+            assert_eq!(load_labeling(&path).unwrap(), config);
+            assert!(!path.with_extension("json.partial").exists());
+        }
+        assert_eq!(
+            LabelMix::of(&config.label("ETH", &candles)),
+            config.calibrations[0].achieved
+        );
+
+        assert!(calibrate_thresholds("ETH", &candles, 0.6, 0.5).is_err());
+        assert!(calibrate_thresholds("ETH", &candles[..10], 0.2, 0.2).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+
+use crate::compute::{assemble_prompt, AnnotatedWindow};
+
+const TIME: usize = 0;
+const OPEN: usize = 1;
+const HIGH: usize = 2;
+const LOW: usize = 3;
+const CLOSE: usize = 4;
+const VOLUME: usize = 5;
+
+/// Rough token count of a prompt, at about four characters per token. Candle data is
+/// mostly digits and punctuation, which tokenizes no better than that.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+/// Merges every `factor` consecutive candles into one (first open, highest high, lowest
+/// low, last close, summed volume, first open time). Groups are counted back from the
+/// newest candle, so only the oldest group can be partial.
+pub fn resample(candles: &[[f64; 6]], factor: usize) -> Vec<[f64; 6]> {
+    if factor <= 1 {
+        return candles.to_vec();
+    }
+    let partial = candles.len() % factor;
+    let (head, tail) = candles.split_at(partial);
+    std::iter::once(head)
+        .filter(|h| !h.is_empty())
+        .chain(tail.chunks(factor))
+        .map(|group| {
+            let (first, last) = (group[0], group[group.len() - 1]);
+            [
+                first[TIME],
+                first[OPEN],
+                group.iter().map(|c| c[HIGH]).fold(f64::MIN, f64::max),
+                group.iter().map(|c| c[LOW]).fold(f64::MAX, f64::min),
+                last[CLOSE],
+                group.iter().map(|c| c[VOLUME]).sum(),
+            ]
+        })
+        .collect()
+}
+
+/// How a window was shrunk to fit the model's context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Truncation {
+    /// Newest candles kept at full resolution.
+    pub keep_recent: usize,
+    /// Older candles were merged this many at a time.
+    pub factor: usize,
+    pub estimated_tokens: usize,
+}
+
+impl Truncation {
+    pub fn apply(&self, candles: &[[f64; 6]]) -> Vec<[f64; 6]> {
+        let split = candles.len().saturating_sub(self.keep_recent);
+        let mut out = resample(&candles[..split], self.factor);
+        out.extend_from_slice(&candles[split..]);
+        out
+    }
+}
+
+// Tried in order until the prompt fits: full resolution for the last 6 hours, then
+// 4-hourly, 12-hourly and daily candles before that
+const DOWNSAMPLE_STEPS: [(usize, usize); 3] = [(6, 4), (6, 12), (6, 24)];
+
+/// Assembles the prompt, down-sampling older candles of every symbol when the full
+/// windows would exceed `max_tokens`. Returns the truncation applied, if any; when even
+/// the coarsest step doesn't fit, that step's prompt is returned anyway.
+pub fn fit_prompt(
+    base_prompt: &str,
+    windows: &[AnnotatedWindow],
+    max_tokens: usize,
+) -> (String, Option<Truncation>) {
+    fit_prompt_with(windows, max_tokens, |w| assemble_prompt(base_prompt, w))
+}
+
+/// [`fit_prompt`] with the prompt assembled from the windows by `assemble`.
+pub fn fit_prompt_with(
+    windows: &[AnnotatedWindow],
+    max_tokens: usize,
+    assemble: impl Fn(&[AnnotatedWindow]) -> String,
+) -> (String, Option<Truncation>) {
+    let prompt = assemble(windows);
+    if estimate_tokens(&prompt) <= max_tokens {
+        return (prompt, None);
+    }
+
+    let mut fitted = (prompt, None);
+    for (keep_recent, factor) in DOWNSAMPLE_STEPS {
+        let mut truncation = Truncation {
+            keep_recent,
+            factor,
+            estimated_tokens: 0,
+        };
+        let resampled = windows
+            .iter()
+            .map(|(_, window, _)| truncation.apply(window))
+            .collect::<Vec<_>>();
+        let shrunk = windows
+            .iter()
+            .zip(&resampled)
+            .map(|((symbol, _, anomalies), window)| (*symbol, &window[..], *anomalies))
+            .collect::<Vec<_>>();
+        let prompt = assemble(&shrunk);
+        truncation.estimated_tokens = estimate_tokens(&prompt);
+        let fits = truncation.estimated_tokens <= max_tokens;
+        fitted = (prompt, Some(truncation));
+        if fits {
+            break;
+        }
+    }
+    fitted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hourly(n: usize) -> Vec<[f64; 6]> {
+        (0..n)
+            .map(|h| {
+                let p = 100.0 + h as f64;
+                [h as f64 * 3600.0, p, p + 1.0, p - 1.0, p + 0.5, 1.0]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_downsampling_keeps_recent_candles() {
+        let candles = hourly(10);
+        let merged = resample(&candles, 4);
+        // 10 = 2 + 4 + 4, with the partial group oldest
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[0], [0.0, 100.0, 102.0, 99.0, 101.5, 2.0]);
+        assert_eq!(merged[2][TIME], 6.0 * 3600.0);
+        assert_eq!(merged[2][CLOSE], candles[9][CLOSE]);
+
+        let t = Truncation {
+            keep_recent: 6,
+            factor: 4,
+            estimated_tokens: 0,
+        };
+        let applied = t.apply(&hourly(30));
+        assert_eq!(applied.len(), 6 + 6);
+        assert_eq!(&applied[6..], &hourly(30)[24..]);
+
+        let eth = hourly(240);
+        let windows: [AnnotatedWindow; 1] = [("ETH", &eth, &[])];
+        let (_, none) = fit_prompt("base", &windows, usize::MAX);
+        assert_eq!(none, None);
+        let full = estimate_tokens(&assemble_prompt("base", &windows));
+        let (prompt, applied) = fit_prompt("base", &windows, full / 2);
+        let applied = applied.unwrap();
+        assert_eq!((applied.keep_recent, applied.factor), (6, 4));
+        assert_eq!(applied.estimated_tokens, estimate_tokens(&prompt));
+        assert!(applied.estimated_tokens <= full / 2);
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::Result;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::indicators;
+use crate::simulator::{next_candle_trade, SimulationSummary, FEE_RATE};
+use crate::{assemble_prompt, label_candles, prepare_candles, Action};
+
+// Browser bindings over the pure core. Candles cross the boundary as JSON arrays of
+// `[time, open, high, low, close, volume]` in chronological order, results as JSON.
+
+fn js_error(e: anyhow::Error) -> JsError {
+    JsError::new(&format!("{:#}", e))
+}
+
+fn parse_candles(json: &str) -> Result<Vec<[f64; 6]>> {
+    Ok(serde_json::from_str(json)?)
+}
+
+/// Labels for every candle, exactly as the backtest computes them.
+#[wasm_bindgen(js_name = labelCandles)]
+pub fn label_candles_json(candles: &str) -> Result<String, JsError> {
+    label_json(candles).map_err(js_error)
+}
+
+fn label_json(candles: &str) -> Result<String> {
+    Ok(serde_json::to_string(&label_candles(&parse_candles(
+        candles,
+    )?))?)
+}
+
+/// The prompt the backend would send for the last `window` candles of each series, after
+/// the same data-quality pass.
+#[wasm_bindgen(js_name = buildPrompt)]
+pub fn build_prompt_json(
+    base_prompt: &str,
+    eth: &str,
+    btc: &str,
+    sol: &str,
+    window: usize,
+) -> Result<String, JsError> {
+    prompt_json(base_prompt, [eth, btc, sol], window).map_err(js_error)
+}
+
+fn prompt_json(base_prompt: &str, series: [&str; 3], window: usize) -> Result<String> {
+    let [eth, btc, sol] = [("ETH", series[0]), ("BTC", series[1]), ("SOL", series[2])]
+        .map(|(symbol, json)| parse_candles(json).map(|c| prepare_candles(symbol, c)));
+    let ((eth, eth_anomalies), (btc, btc_anomalies), (sol, sol_anomalies)) = (eth?, btc?, sol?);
+    let tail = |c: &[[f64; 6]]| c.len().saturating_sub(window);
+
+    Ok(assemble_prompt(
+        base_prompt,
+        &[
+            ("ETH", &eth[tail(&eth)..], &eth_anomalies),
+            ("BTC", &btc[tail(&btc)..], &btc_anomalies),
+            ("SOL", &sol[tail(&sol)..], &sol_anomalies),
+        ],
+    ))
+}
+
+/// VWAP, VWAP distance and volume profile of a window, or `null` without volume.
+#[wasm_bindgen(js_name = volumeFeatures)]
+pub fn volume_features_json(candles: &str) -> Result<String, JsError> {
+    parse_candles(candles)
+        .and_then(|c| Ok(serde_json::to_string(&indicators::volume_features(&c))?))
+        .map_err(js_error)
+}
+
+#[derive(Debug, Serialize)]
+struct Score {
+    accuracy: f64,
+    simulation: SimulationSummary,
+}
+
+/// Scores one action per candle against the labels and the next-candle PnL simulation.
+#[wasm_bindgen(js_name = scoreActions)]
+pub fn score_actions_json(candles: &str, actions: &str) -> Result<String, JsError> {
+    score_json(candles, actions).map_err(js_error)
+}
+
+fn score_json(candles: &str, actions: &str) -> Result<String> {
+    let candles = parse_candles(candles)?;
+    let actions: Vec<Action> = serde_json::from_str(actions)?;
+    anyhow::ensure!(
+        actions.len() == candles.len(),
+        "Expected one action per candle"
+    );
+
+    let labels = label_candles(&candles);
+    let correct = actions.iter().zip(&labels).filter(|(a, l)| a == l).count();
+    let trades = actions
+        .iter()
+        .enumerate()
+        .filter_map(|(i, a)| next_candle_trade(&candles, i, *a, FEE_RATE))
+        .collect::<Vec<_>>();
+
+    Ok(serde_json::to_string(&Score {
+        accuracy: if candles.is_empty() {
+            0.0
+        } else {
+            correct as f64 / candles.len() as f64
+        },
+        simulation: crate::simulator::summarize(&trades),
+    })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_bindings() {
+        let candles = "[[0,100,100,100,100,1],[3600,100,110,100,110,1]]";
+        assert_eq!(label_json(candles).unwrap(), r#"["long","none"]"#);
+
+        let score: serde_json::Value =
+            serde_json::from_str(&score_json(candles, r#"["long","none"]"#).unwrap()).unwrap();
+        assert_eq!(score["accuracy"], 1.0);
+        assert_eq!(score["simulation"]["trades"], 1);
+
+        let prompt = prompt_json("Base", [candles, candles, candles], 1).unwrap();
+        assert!(prompt.starts_with("Base\n\n"));
+        assert!(score_json(candles, r#"["long"]"#).is_err());
+    }
+}
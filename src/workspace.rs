@@ -0,0 +1,78 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::PROMPT_FILE;
+
+/// Named workspaces live under this directory of the project.
+pub const WORKSPACES_DIR: &str = "workspaces";
+/// Files copied from the project into a new workspace, so it starts from the current prompt.
+const SEEDED_FILES: [&str; 1] = [PROMPT_FILE];
+
+/// Creates workspace `name` under `root` if needed, seeding it with the project's prompt,
+/// and returns its directory.
+pub fn prepare(root: &Path, name: &str) -> Result<PathBuf> {
+    anyhow::ensure!(
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+        "Workspace names may only use letters, digits, '-' and '_': {:?}",
+        name
+    );
+    let dir = root.join(WORKSPACES_DIR).join(name);
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create workspace {}", dir.display()))?;
+    for file in SEEDED_FILES {
+        let (from, to) = (root.join(file), dir.join(file));
+        if from.exists() && !to.exists() {
+            fs::copy(&from, &to)
+                .with_context(|| format!("Failed to seed {} into workspace {}", file, name))?;
+            tracing::info!(workspace = name, file, "Seeded workspace");
+        }
+    }
+    Ok(dir)
+}
+
+/// Switches into workspace `name`. Every file the app reads and writes is relative to the
+/// working directory (`prompt.txt`, `cache/`, `challengers/`, reports), so experiments in
+/// different workspaces never share a prompt, history or cache.
+pub fn enter(name: &str) -> Result<PathBuf> {
+    let dir = prepare(Path::new("."), name)?;
+    env::set_current_dir(&dir)
+        .with_context(|| format!("Failed to enter workspace {}", dir.display()))?;
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepare_seeds_the_prompt_once() {
+        let root = env::temp_dir().join("happycharts_workspace");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("prompt.txt"), "project prompt").unwrap();
+
+        let dir = prepare(&root, "btc-target").unwrap();
+        assert_eq!(dir, root.join(WORKSPACES_DIR).join("btc-target"));
+        assert_eq!(
+            fs::read_to_string(dir.join("prompt.txt")).unwrap(),
+            "project prompt"
+        );
+
+        // The workspace's own edits survive re-entering it
+        fs::write(dir.join("prompt.txt"), "tuned for BTC").unwrap();
+        prepare(&root, "btc-target").unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("prompt.txt")).unwrap(),
+            "tuned for BTC"
+        );
+
+        assert!(prepare(&root, "../escape").is_err());
+        assert!(prepare(&root, "").is_err());
+    }
+}